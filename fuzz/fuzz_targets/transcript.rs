@@ -0,0 +1,25 @@
+#![no_main]
+
+use halo2_proofs::transcript::{Blake2bRead, Challenge255, TranscriptRead, TranscriptReadBuffer};
+use halo2curves::bn256::G1Affine;
+use libfuzzer_sys::fuzz_target;
+
+// The first byte selects the sequence of elements to read: a set bit reads a point and
+// a cleared one reads a scalar, starting from the least significant bit.
+fuzz_target!(|data: &[u8]| {
+    let (&pattern, proof) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof);
+    for i in 0.. {
+        let read = if (pattern >> (i % 8)) & 1 == 1 {
+            transcript.read_point().map(|_| ())
+        } else {
+            transcript.read_scalar().map(|_| ())
+        };
+        if read.is_err() {
+            break;
+        }
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+
+use std::sync::OnceLock;
+
+use halo2_fuzz::setup;
+use halo2_proofs::{
+    plonk::{verify_arbitrary_bytes, ProvingKey},
+    poly::{
+        commitment::ParamsProver,
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG},
+            multiopen::VerifierSHPLONK,
+            strategy::SingleStrategy,
+        },
+    },
+};
+use halo2curves::bn256::{Bn256, G1Affine};
+use libfuzzer_sys::fuzz_target;
+
+static SETUP: OnceLock<(ParamsKZG<Bn256>, ProvingKey<G1Affine>)> = OnceLock::new();
+
+fuzz_target!(|data: &[u8]| {
+    let (params, pk) = SETUP.get_or_init(setup);
+    let params = params.verifier_params();
+    verify_arbitrary_bytes::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<_>, SingleStrategy<_>>(
+        pk.get_vk(),
+        params,
+        data,
+    );
+});
@@ -0,0 +1,18 @@
+//! Writes valid proofs to the corpus of the verifier target.
+//!
+//! Run with `cargo run --bin seed_corpus` from the `fuzz` directory.
+
+use std::fs;
+use std::path::Path;
+
+use halo2_fuzz::{setup, valid_input};
+
+fn main() {
+    let (params, pk) = setup();
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus/verifier");
+    fs::create_dir_all(&corpus).expect("can create the corpus directory");
+    for (a, b) in [(0, 0), (1, 1), (2, 3), (u64::MAX, u64::MAX)] {
+        let input = valid_input(&params, &pk, a, b);
+        fs::write(corpus.join(format!("valid_{a}_{b}")), input).expect("can write the corpus");
+    }
+}
@@ -0,0 +1,135 @@
+//! Shared setup of the fuzz targets.
+//!
+//! The verifier target checks proofs against the verifying key of [`FuzzCircuit`], which
+//! is derived from fixed parameters so that the corpus written by the `seed_corpus`
+//! binary stays valid across runs.
+
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    plonk::{
+        create_proof, keygen_pk, keygen_vk, Advice, Circuit, Column, ConstraintSystem, Error,
+        FuzzInput, Instance, ProvingKey, Selector,
+    },
+    poly::{
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG},
+            multiopen::ProverSHPLONK,
+        },
+        Rotation,
+    },
+    transcript::{Blake2bWrite, Challenge255, TranscriptWriterBuffer},
+};
+use halo2curves::bn256::{Bn256, Fr, G1Affine};
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+/// The circuits have `2^K` rows.
+pub const K: u32 = 4;
+
+/// A circuit proving knowledge of `a` and `b` such that `a * b` is the public input.
+#[derive(Clone, Default)]
+pub struct FuzzCircuit {
+    pub a: Value<Fr>,
+    pub b: Value<Fr>,
+}
+
+#[derive(Clone)]
+pub struct FuzzConfig {
+    advice: [Column<Advice>; 3],
+    instance: Column<Instance>,
+    s_mul: Selector,
+}
+
+impl Circuit<Fr> for FuzzCircuit {
+    type Config = FuzzConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(advice[2]);
+        meta.enable_equality(instance);
+
+        let s_mul = meta.selector();
+        meta.create_gate("mul", |meta| {
+            let s_mul = meta.query_selector(s_mul);
+            let a = meta.query_advice(advice[0], Rotation::cur());
+            let b = meta.query_advice(advice[1], Rotation::cur());
+            let c = meta.query_advice(advice[2], Rotation::cur());
+            vec![s_mul * (a * b - c)]
+        });
+
+        FuzzConfig {
+            advice,
+            instance,
+            s_mul,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), Error> {
+        let c = layouter.assign_region(
+            || "mul",
+            |mut region| {
+                config.s_mul.enable(&mut region, 0)?;
+                region.assign_advice(|| "a", config.advice[0], 0, || self.a)?;
+                region.assign_advice(|| "b", config.advice[1], 0, || self.b)?;
+                region.assign_advice(|| "c", config.advice[2], 0, || self.a * self.b)
+            },
+        )?;
+        layouter.constrain_instance(c.cell(), config.instance, 0)
+    }
+}
+
+/// Returns the parameters and the proving key of [`FuzzCircuit`].
+///
+/// They are derived from a fixed seed, and are therefore not secure.
+pub fn setup() -> (ParamsKZG<Bn256>, ProvingKey<G1Affine>) {
+    let params = ParamsKZG::<Bn256>::setup(K, ChaCha20Rng::seed_from_u64(0));
+    let vk = keygen_vk(&params, &FuzzCircuit::default()).expect("keygen_vk should not fail");
+    let pk = keygen_pk(&params, vk, &FuzzCircuit::default()).expect("keygen_pk should not fail");
+    (params, pk)
+}
+
+/// Returns a valid fuzz input for the verifier target, proving that `a * b` is the
+/// public input.
+pub fn valid_input(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    a: u64,
+    b: u64,
+) -> Vec<u8> {
+    let circuit = FuzzCircuit {
+        a: Value::known(Fr::from(a)),
+        b: Value::known(Fr::from(b)),
+    };
+    let instances = vec![vec![Fr::from(a) * Fr::from(b)]];
+
+    let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+    create_proof::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _, _, _, _>(
+        params,
+        pk,
+        &[circuit],
+        &[&[&instances[0]]],
+        ChaCha20Rng::seed_from_u64(a ^ b.rotate_left(32)),
+        &mut transcript,
+    )
+    .expect("proof generation should not fail");
+
+    FuzzInput {
+        instances,
+        proof: transcript.finalize(),
+    }
+    .to_bytes()
+}
@@ -171,6 +171,141 @@ pub fn parallelize<T: Send, F: Fn(&mut [T], usize) + Send + Sync + Clone>(v: &mu
     });
 }
 
+/// Computes in place the running product of the fractions `numerator(i) / denominator(i)`
+/// over the first `len` values of `z`, with `z[0] = first` and
+/// `z[i + 1] = z[i] * numerator(i) / denominator(i)`. The values of `z` after `len` are
+/// left untouched.
+///
+/// The fractions are streamed into `z` itself by chunks, which are inverted in parallel
+/// with a batch inversion each, so that no other vector of the size of `z` is allocated.
+/// `denominators(chunk, start)` sets the values of `chunk` to the denominators of the rows
+/// from `start`, and `numerators(chunk, start)` multiplies them by their numerators. Zero
+/// denominators are left as zero, as in [`BatchInvert`].
+pub(crate) fn running_product<F, D, N>(
+    z: &mut [F],
+    len: usize,
+    first: F,
+    denominators: D,
+    numerators: N,
+) where
+    F: Field,
+    D: Fn(&mut [F], usize) + Send + Sync + Clone,
+    N: Fn(&mut [F], usize) + Send + Sync + Clone,
+{
+    if len == 0 {
+        return;
+    }
+    z[0] = first;
+    // The fraction of row `i` is written at `z[i + 1]`.
+    parallelize(&mut z[1..len], |fractions, start| {
+        denominators(fractions, start);
+        fractions.iter_mut().batch_invert();
+        numerators(fractions, start);
+    });
+    for i in 1..len {
+        let previous = z[i - 1];
+        z[i] *= previous;
+    }
+}
+
+/// Sorts `values` by their canonical representations, read as little-endian integers.
+/// This is the order of the fields of `halo2curves`, so the result is that of
+/// `values.sort()`, which the lookup prover used to permute its inputs.
+///
+/// This is a most significant digit radix sort: the values are first distributed into
+/// buckets by the 16 most significant bits in which they differ, and the buckets are then
+/// sorted in parallel, so that the sort scales with the number of threads.
+pub fn sort_by_repr<F: PrimeField>(values: &mut [F]) {
+    fn cmp_repr<R: AsRef<[u8]>>(a: &R, b: &R) -> std::cmp::Ordering {
+        a.as_ref().iter().rev().cmp(b.as_ref().iter().rev())
+    }
+
+    /// Writes the values of the sorted `keys` to `values`.
+    fn write_back<F: PrimeField>(values: &mut [F], keys: &[F::Repr]) {
+        parallelize(values, |values, start| {
+            for (value, key) in values.iter_mut().zip(keys[start..].iter()) {
+                *value = F::from_repr(*key).unwrap();
+            }
+        });
+    }
+
+    let mut keys = vec![F::Repr::default(); values.len()];
+    parallelize(&mut keys, |keys, start| {
+        for (key, value) in keys.iter_mut().zip(values[start..].iter()) {
+            *key = value.to_repr();
+        }
+    });
+
+    // The most significant byte in which the keys differ, and the digit of a key, made of
+    // this byte and the next one.
+    let first = keys.first().copied().unwrap_or_default();
+    let top = (0..first.as_ref().len()).rev().find(|&i| {
+        let byte = first.as_ref()[i];
+        keys.iter().any(|key| key.as_ref()[i] != byte)
+    });
+    let top = match top {
+        Some(top) if keys.len() >= 1 << 12 => top,
+        // The keys are equal, or too few to be worth distributing.
+        Some(_) => {
+            keys.sort_unstable_by(cmp_repr);
+            write_back(values, &keys);
+            return;
+        }
+        None => return,
+    };
+    let digit = |key: &F::Repr| {
+        let key = key.as_ref();
+        let low = if top > 0 { key[top - 1] } else { 0 };
+        u16::from_be_bytes([key[top], low]) as usize
+    };
+
+    let mut counts = vec![0usize; 1 << 16];
+    for key in keys.iter() {
+        counts[digit(key)] += 1;
+    }
+    let mut offsets: Vec<usize> = counts
+        .iter()
+        .scan(0, |offset, count| {
+            let start = *offset;
+            *offset += count;
+            Some(start)
+        })
+        .collect();
+    let mut sorted = vec![F::Repr::default(); keys.len()];
+    for key in keys.iter() {
+        let offset = &mut offsets[digit(key)];
+        sorted[*offset] = *key;
+        *offset += 1;
+    }
+    drop(keys);
+
+    // Each thread sorts a group of consecutive buckets.
+    let group_len = sorted.len() / (4 * multicore::current_num_threads()) + 1;
+    let mut groups: Vec<Vec<&mut [F::Repr]>> = vec![vec![]];
+    let mut rest = &mut sorted[..];
+    let mut len = 0;
+    for count in counts.into_iter().filter(|count| *count > 0) {
+        let (bucket, tail) = std::mem::take(&mut rest).split_at_mut(count);
+        rest = tail;
+        if len >= group_len {
+            groups.push(vec![]);
+            len = 0;
+        }
+        groups.last_mut().unwrap().push(bucket);
+        len += count;
+    }
+    multicore::scope(|scope| {
+        for group in groups {
+            scope.spawn(move |_| {
+                for bucket in group {
+                    bucket.sort_unstable_by(cmp_repr);
+                }
+            });
+        }
+    });
+    write_back(values, &sorted);
+}
+
 /// Returns coefficients of an n - 1 degree polynomial given a set of n points
 /// and their evaluations. This function will panic if two values in `points`
 /// are the same.
@@ -278,3 +413,60 @@ fn test_lagrange_interpolate() {
         }
     }
 }
+
+#[test]
+fn test_running_product() {
+    let rng = OsRng;
+
+    let n = 64;
+    let len = 60;
+    let mut numerators = (0..n).map(|_| Fp::random(rng)).collect::<Vec<_>>();
+    let mut denominators = (0..n).map(|_| Fp::random(rng)).collect::<Vec<_>>();
+    numerators[3] = Fp::ZERO;
+    denominators[7] = Fp::ZERO;
+
+    let first = Fp::random(rng);
+    let mut expected = vec![first];
+    for i in 0..len - 1 {
+        let fraction = numerators[i] * denominators[i].invert().unwrap_or(Fp::ZERO);
+        expected.push(expected[i] * fraction);
+    }
+
+    let mut z = vec![Fp::ONE; n];
+    running_product(
+        &mut z,
+        len,
+        first,
+        |chunk, start| chunk.copy_from_slice(&denominators[start..start + chunk.len()]),
+        |chunk, start| {
+            for (value, numerator) in chunk.iter_mut().zip(numerators[start..].iter()) {
+                *value *= numerator;
+            }
+        },
+    );
+    assert_eq!(z[..len], expected[..]);
+    assert!(z[len..].iter().all(|value| *value == Fp::ONE));
+}
+
+#[test]
+fn test_sort_by_repr() {
+    use halo2curves::bn256::Fr;
+
+    fn check<F: PrimeField + Ord>(mut values: Vec<F>) {
+        let mut expected = values.clone();
+        expected.sort();
+        sort_by_repr(&mut values);
+        assert_eq!(values, expected);
+    }
+
+    let rng = OsRng;
+    for n in [0, 1, 100, 5000] {
+        check((0..n).map(|_| Fp::random(rng)).collect());
+        check((0..n).map(|_| Fr::random(rng)).collect());
+        // Small values, as in range checks, which only differ in their low bytes.
+        check((0..n).map(|i| Fp::from((i * 7919) % 1000)).collect());
+        check((0..n).map(|i| Fr::from((i * 7919) % 70000)).collect());
+        // Values which only differ in their least significant byte.
+        check((0..n).map(|i| Fp::from(i % 3) - Fp::ONE).collect());
+    }
+}
@@ -0,0 +1,315 @@
+//! Encoding of proofs and instances as the calldata of on-chain verifiers.
+//!
+//! A verifier contract verifies a proof with the function `verifyProof(bytes,uint256[])`,
+//! which takes the proof, created with a [`Keccak256Write`] transcript, and the instances
+//! of the proof, or with `verifyProof(address,bytes,uint256[])` when the verifying key is
+//! deployed as a separate contract, whose address comes first. The calldata of a call is
+//! the selector of the function followed by its arguments in the layout of the Solidity
+//! ABI: the head of the arguments, with the address and the offsets of the proof and of
+//! the instances, then the length of the proof and the proof padded with zeros to a
+//! multiple of 32 bytes, then the number of instances and each instance as a big-endian
+//! 32-byte word.
+//!
+//! [`Calldata::encode`] produces this layout, and [`Calldata::decode`] only accepts it,
+//! with its canonical offsets and zero padding.
+//!
+//! [`Keccak256Write`]: crate::transcript::Keccak256Write
+
+use std::io;
+
+use halo2_middleware::ff::PrimeField;
+use sha3::{Digest, Keccak256};
+
+/// The signature of the function verifying a proof with the verifying key embedded in the
+/// verifier contract.
+pub const VERIFY_PROOF_SIGNATURE: &str = "verifyProof(bytes,uint256[])";
+
+/// The signature of the function verifying a proof with the verifying key deployed at
+/// the given address.
+pub const VERIFY_PROOF_WITH_VK_SIGNATURE: &str = "verifyProof(address,bytes,uint256[])";
+
+/// The size of the words of the Solidity ABI.
+const WORD: usize = 32;
+
+/// Returns the selector of the function with the given signature: the first four bytes
+/// of its Keccak-256 hash.
+pub fn function_selector(signature: &str) -> [u8; 4] {
+    let hash = Keccak256::digest(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Returns the number of bytes of `len` bytes padded to a multiple of a word.
+fn padded_len(len: usize) -> usize {
+    (len + WORD - 1) / WORD * WORD
+}
+
+/// Returns the word holding the integer `value`.
+fn word_from_usize(value: usize) -> [u8; WORD] {
+    let mut word = [0; WORD];
+    word[WORD - 8..].copy_from_slice(&(value as u64).to_be_bytes());
+    word
+}
+
+/// Returns the integer held by `word`, if it fits into a `usize`.
+fn usize_from_word(word: &[u8]) -> io::Result<usize> {
+    let (high, low) = word.split_at(WORD - 8);
+    if high.iter().any(|byte| *byte != 0) {
+        return Err(invalid_data("an integer of the calldata is too large"));
+    }
+    usize::try_from(u64::from_be_bytes(low.try_into().unwrap()))
+        .map_err(|_| invalid_data("an integer of the calldata is too large"))
+}
+
+/// Returns whether the representation of the elements of `F` is little-endian.
+fn is_little_endian<F: PrimeField>() -> bool {
+    F::ONE.to_repr().as_ref()[0] == 1
+}
+
+/// Returns the word holding the canonical integer of `value`.
+fn word_from_field<F: PrimeField>(value: &F) -> [u8; WORD] {
+    let repr = value.to_repr();
+    let mut bytes = repr.as_ref().to_vec();
+    if is_little_endian::<F>() {
+        bytes.reverse();
+    }
+    assert!(bytes.len() <= WORD, "field elements must fit into a word");
+    let mut word = [0; WORD];
+    word[WORD - bytes.len()..].copy_from_slice(&bytes);
+    word
+}
+
+/// Returns the field element held by `word`, if it is smaller than the modulus.
+fn field_from_word<F: PrimeField>(word: &[u8]) -> io::Result<F> {
+    let mut repr = F::Repr::default();
+    let len = repr.as_ref().len();
+    let (high, low) = word.split_at(WORD - len);
+    if high.iter().any(|byte| *byte != 0) {
+        return Err(invalid_data("an instance is not smaller than the modulus"));
+    }
+    repr.as_mut().copy_from_slice(low);
+    if is_little_endian::<F>() {
+        repr.as_mut().reverse();
+    }
+    Option::from(F::from_repr(repr))
+        .ok_or_else(|| invalid_data("an instance is not smaller than the modulus"))
+}
+
+/// A call to a verifier contract: the proof to verify and its instances.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Calldata<F> {
+    /// The address of the contract of the verifying key, if it is not embedded in the
+    /// verifier contract.
+    pub vk_address: Option<[u8; 20]>,
+    /// The proof, created with a `Keccak256Write` transcript.
+    pub proof: Vec<u8>,
+    /// The instances of the proof, the values of its instance columns one after the other.
+    pub instances: Vec<F>,
+}
+
+impl<F: PrimeField> Calldata<F> {
+    /// Creates the call verifying `proof` with the verifying key embedded in the verifier
+    /// contract.
+    pub fn new(proof: Vec<u8>, instances: Vec<F>) -> Self {
+        Calldata {
+            vk_address: None,
+            proof,
+            instances,
+        }
+    }
+
+    /// Verifies the proof with the verifying key deployed at `vk_address`.
+    pub fn vk_address(mut self, vk_address: [u8; 20]) -> Self {
+        self.vk_address = Some(vk_address);
+        self
+    }
+
+    /// Returns the signature of the function called.
+    pub fn signature(&self) -> &'static str {
+        if self.vk_address.is_some() {
+            VERIFY_PROOF_WITH_VK_SIGNATURE
+        } else {
+            VERIFY_PROOF_SIGNATURE
+        }
+    }
+
+    /// Returns the number of words of the head of the arguments.
+    fn head_words(&self) -> usize {
+        if self.vk_address.is_some() {
+            3
+        } else {
+            2
+        }
+    }
+
+    /// Encodes the call as calldata.
+    pub fn encode(&self) -> Vec<u8> {
+        let proof_offset = self.head_words() * WORD;
+        let instances_offset = proof_offset + WORD + padded_len(self.proof.len());
+
+        let mut calldata =
+            Vec::with_capacity(4 + instances_offset + WORD + self.instances.len() * WORD);
+        calldata.extend(function_selector(self.signature()));
+        if let Some(vk_address) = self.vk_address {
+            calldata.extend([0; WORD - 20]);
+            calldata.extend(vk_address);
+        }
+        calldata.extend(word_from_usize(proof_offset));
+        calldata.extend(word_from_usize(instances_offset));
+
+        calldata.extend(word_from_usize(self.proof.len()));
+        calldata.extend(&self.proof);
+        calldata.resize(4 + instances_offset, 0);
+
+        calldata.extend(word_from_usize(self.instances.len()));
+        for instance in &self.instances {
+            calldata.extend(word_from_field(instance));
+        }
+        calldata
+    }
+
+    /// Decodes calldata encoded by [`Self::encode`].
+    ///
+    /// Returns an error if the selector is not the one of a function of the verifier, or
+    /// if the arguments are not in the layout of [`Self::encode`], for example if the
+    /// offsets are not the canonical ones, if the padding is not zero or if an instance
+    /// is not smaller than the modulus.
+    pub fn decode(calldata: &[u8]) -> io::Result<Self> {
+        if calldata.len() < 4 {
+            return Err(invalid_data("the calldata has no selector"));
+        }
+        let (selector, args) = calldata.split_at(4);
+        let mut call = if selector == function_selector(VERIFY_PROOF_SIGNATURE) {
+            Calldata::new(vec![], vec![])
+        } else if selector == function_selector(VERIFY_PROOF_WITH_VK_SIGNATURE) {
+            Calldata::new(vec![], vec![]).vk_address([0; 20])
+        } else {
+            return Err(invalid_data("unknown function selector"));
+        };
+
+        let word = |index: usize| {
+            args.get(index * WORD..(index + 1) * WORD)
+                .ok_or_else(|| invalid_data("the calldata is truncated"))
+        };
+        let head = call.head_words();
+        if let Some(vk_address) = call.vk_address.as_mut() {
+            let (padding, address) = word(0)?.split_at(WORD - 20);
+            if padding.iter().any(|byte| *byte != 0) {
+                return Err(invalid_data("invalid address"));
+            }
+            vk_address.copy_from_slice(address);
+        }
+        let proof_offset = usize_from_word(word(head - 2)?)?;
+        let instances_offset = usize_from_word(word(head - 1)?)?;
+        if proof_offset != head * WORD {
+            return Err(invalid_data("unexpected offset of the proof"));
+        }
+
+        let proof_len = usize_from_word(word(head)?)?;
+        if proof_len > args.len() {
+            return Err(invalid_data("the calldata is truncated"));
+        }
+        let proof_end = proof_offset + WORD + padded_len(proof_len);
+        if proof_end > args.len() {
+            return Err(invalid_data("the calldata is truncated"));
+        }
+        if instances_offset != proof_end {
+            return Err(invalid_data("unexpected offset of the instances"));
+        }
+        let (proof, padding) = args[proof_offset + WORD..proof_end].split_at(proof_len);
+        if padding.iter().any(|byte| *byte != 0) {
+            return Err(invalid_data("the padding of the proof is not zero"));
+        }
+        call.proof = proof.to_vec();
+
+        let num_instances = usize_from_word(word(instances_offset / WORD)?)?;
+        let instances = &args[instances_offset + WORD..];
+        if num_instances > instances.len() / WORD || instances.len() != num_instances * WORD {
+            return Err(invalid_data(
+                "the calldata doesn't have the number of instances it declares",
+            ));
+        }
+        call.instances = instances
+            .chunks(WORD)
+            .map(field_from_word)
+            .collect::<io::Result<_>>()?;
+        Ok(call)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_middleware::ff::Field;
+    use halo2curves::bn256::Fr;
+    use rand_core::OsRng;
+
+    #[test]
+    fn selector() {
+        assert_eq!(
+            function_selector("transfer(address,uint256)"),
+            [0xa9, 0x05, 0x9c, 0xbb]
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let instances: Vec<_> = (0..3).map(|_| Fr::random(OsRng)).collect();
+        let call = Calldata::new(vec![7; 40], instances.clone());
+        let calldata = call.encode();
+        assert_eq!(calldata[..4], function_selector(VERIFY_PROOF_SIGNATURE));
+        // The head, the proof padded to two words and the instances.
+        assert_eq!(calldata.len(), 4 + 2 * 32 + 3 * 32 + 4 * 32);
+        assert_eq!(calldata[4..36], word_from_usize(0x40));
+        assert_eq!(calldata[36..68], word_from_usize(0x40 + 3 * 32));
+        assert_eq!(calldata[68..100], word_from_usize(40));
+        assert_eq!(
+            calldata[calldata.len() - 32..],
+            word_from_field(&instances[2])
+        );
+        assert_eq!(Calldata::decode(&calldata).unwrap(), call);
+
+        let call = Calldata::new(vec![], vec![-Fr::ONE]).vk_address([0xab; 20]);
+        let calldata = call.encode();
+        assert_eq!(
+            calldata[..4],
+            function_selector(VERIFY_PROOF_WITH_VK_SIGNATURE)
+        );
+        assert_eq!(calldata[4 + 12..36], [0xab; 20]);
+        assert_eq!(Calldata::decode(&calldata).unwrap(), call);
+
+        let call = Calldata::<Fr>::new(vec![1; 64], vec![]);
+        assert_eq!(Calldata::decode(&call.encode()).unwrap(), call);
+    }
+
+    #[test]
+    fn malformed() {
+        let calldata = Calldata::new(vec![7; 40], vec![Fr::ONE]).encode();
+        let rejected = |calldata: &[u8]| Calldata::<Fr>::decode(calldata).is_err();
+
+        // Unknown selector.
+        let mut other = calldata.clone();
+        other[0] ^= 1;
+        assert!(rejected(&other));
+        // Truncated or extended calldata.
+        assert!(rejected(&calldata[..calldata.len() - 1]));
+        assert!(rejected(&calldata[..50]));
+        assert!(rejected(&[calldata.clone(), vec![0; 32]].concat()));
+        // Non-canonical offset.
+        let mut other = calldata.clone();
+        other[35] += 32;
+        assert!(rejected(&other));
+        // Non-zero padding of the proof.
+        let mut other = calldata.clone();
+        other[4 + 3 * 32 + 40] = 1;
+        assert!(rejected(&other));
+        // Instance not smaller than the modulus.
+        let mut other = calldata.clone();
+        let len = other.len();
+        other[len - 32..].fill(0xff);
+        assert!(rejected(&other));
+    }
+}
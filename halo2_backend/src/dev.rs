@@ -0,0 +1,10 @@
+//! Tools for testing provers and verifiers.
+
+pub mod arithmetic;
+pub mod blinding;
+#[cfg(feature = "lookup-injection")]
+pub mod lookup_injection;
+pub mod tamper;
+pub mod transcript_log;
+
+pub use transcript_log::diff_transcripts;
@@ -0,0 +1,299 @@
+//! Differential checks of the field, MSM and FFT arithmetic the proof system relies on.
+//!
+//! The prover and the verifier rely on invariants of fields, curves and engines that
+//! their traits don't enforce: a field must have the roots of unity it advertises, an MSM
+//! engine must handle zero scalars and the point at infinity, and an FFT engine must be
+//! exactly invertible. The checks of this module compare an implementation with naive
+//! ones, on random inputs of several sizes and on degenerate inputs, so that implementers
+//! of curves and of [`zal`](crate::zal) engines can validate theirs against the
+//! expectations of this crate, for example in their own tests:
+//!
+//! - [`check_field`] checks the constants and the operations of a field;
+//! - [`check_msm`] compares an MSM engine, such as [`H2cEngine`] which calls
+//!   `best_multiexp`, with [`naive_msm`];
+//! - [`check_fft`] compares an FFT engine with [`naive_dft`], and checks the round trips
+//!   between the bases of an [`EvaluationDomain`].
+//!
+//! ```ignore
+//! check_field::<Fr>(&mut rng, 100)?;
+//! check_msm::<G1Affine>(&H2cEngine, &mut rng, &[0, 1, 2, 3, 64, 257])?;
+//! check_fft::<Fr>(&H2cEngine, &mut rng, 10)?;
+//! ```
+//!
+//! Each check returns an error describing the first mismatch it finds.
+//!
+//! [`H2cEngine`]: crate::zal::H2cEngine
+
+use group::{Curve, Group};
+use halo2_common::plonk::Error;
+use halo2_middleware::ff::{Field, PrimeField, WithSmallOrderMulGroup};
+use halo2curves::CurveAffine;
+use rand_core::RngCore;
+
+use crate::arithmetic::eval_polynomial;
+use crate::poly::EvaluationDomain;
+use crate::zal::traits::{FftAccel, MsmAccel};
+
+/// The largest FFTs compared with [`naive_dft`], whose cost is quadratic.
+const NAIVE_DFT_MAX_LOG_N: u32 = 8;
+
+/// Returns the sum of `bases` multiplied by `coeffs`, one term at a time.
+pub fn naive_msm<C: CurveAffine>(coeffs: &[C::Scalar], bases: &[C]) -> C::CurveExt {
+    assert_eq!(coeffs.len(), bases.len());
+    coeffs
+        .iter()
+        .zip(bases)
+        .fold(C::CurveExt::identity(), |acc, (coeff, base)| {
+            acc + base.to_curve() * coeff
+        })
+}
+
+/// Returns the evaluations of the polynomial of coefficients `values` at the powers of
+/// `omega`, one evaluation at a time.
+pub fn naive_dft<F: Field>(values: &[F], omega: F) -> Vec<F> {
+    let mut point = F::ONE;
+    (0..values.len())
+        .map(|_| {
+            let eval = eval_polynomial(values, point);
+            point *= omega;
+            eval
+        })
+        .collect()
+}
+
+/// Checks the constants of the field `F`, and its operations on `samples` random
+/// elements and on zero and one.
+pub fn check_field<F: WithSmallOrderMulGroup<3>>(
+    mut rng: impl RngCore,
+    samples: usize,
+) -> Result<(), Error> {
+    let check = |holds: bool, invariant: &str| {
+        if holds {
+            Ok(())
+        } else {
+            Err(Error::Other(format!(
+                "the field doesn't satisfy {}",
+                invariant
+            )))
+        }
+    };
+
+    let two_adic_order = |mut root: F| {
+        let mut order = 0;
+        while root != F::ONE && order <= F::S {
+            root = root.square();
+            order += 1;
+        }
+        order
+    };
+    check(
+        two_adic_order(F::ROOT_OF_UNITY) == F::S,
+        "ROOT_OF_UNITY having order 2^S",
+    )?;
+    check(
+        F::ROOT_OF_UNITY * F::ROOT_OF_UNITY_INV == F::ONE,
+        "ROOT_OF_UNITY * ROOT_OF_UNITY_INV = 1",
+    )?;
+    check(F::TWO_INV.double() == F::ONE, "2 * TWO_INV = 1")?;
+    check(
+        (0..F::S).fold(F::MULTIPLICATIVE_GENERATOR, |delta, _| delta.square()) == F::DELTA,
+        "DELTA = MULTIPLICATIVE_GENERATOR^(2^S)",
+    )?;
+    check(
+        F::ZETA != F::ONE && F::ZETA.square() * F::ZETA == F::ONE,
+        "ZETA having order 3",
+    )?;
+    check(
+        bool::from(F::ZERO.invert().is_none()),
+        "0 having no inverse",
+    )?;
+
+    let elements = [F::ZERO, F::ONE]
+        .into_iter()
+        .chain((0..samples).map(|_| F::random(&mut rng)))
+        .collect::<Vec<_>>();
+    for (a, b) in elements.iter().zip(elements.iter().rev()) {
+        let (a, b) = (*a, *b);
+        check(
+            Option::from(F::from_repr(a.to_repr())) == Some(a),
+            "from_repr(to_repr(a)) = a",
+        )?;
+        check(a * b == b * a, "a * b = b * a")?;
+        check(
+            (a + b) * b == a * b + b.square(),
+            "(a + b) * b = a * b + b^2",
+        )?;
+        check(a.double() == a + a, "a.double() = a + a")?;
+        check(a - b + b == a && -a + a == F::ZERO, "a - b + b = a")?;
+        if a != F::ZERO {
+            check(a * a.invert().unwrap() == F::ONE, "a * a^-1 = 1")?;
+        }
+        check(
+            Option::from(a.square().sqrt()).map(|root: F| root.square()) == Some(a.square()),
+            "sqrt(a^2)^2 = a^2",
+        )?;
+    }
+    Ok(())
+}
+
+/// Compares the MSMs of `engine` with [`naive_msm`], for each number of terms of `sizes`,
+/// on random terms and on degenerate ones: zero, unit and negative unit scalars, bases at
+/// infinity, a repeated base, and terms cancelling each other.
+pub fn check_msm<C: CurveAffine>(
+    engine: &dyn MsmAccel<C>,
+    mut rng: impl RngCore,
+    sizes: &[usize],
+) -> Result<(), Error> {
+    for &size in sizes {
+        let coeffs: Vec<_> = (0..size).map(|_| C::Scalar::random(&mut rng)).collect();
+        let bases: Vec<C> = (0..size)
+            .map(|_| C::CurveExt::random(&mut rng).to_affine())
+            .collect();
+        let base = bases.first().copied().unwrap_or_else(C::identity);
+        let negated: Vec<_> = bases.iter().map(|base| -*base).collect();
+
+        let cases = [
+            ("random terms", coeffs.clone(), bases.clone()),
+            ("zero scalars", vec![C::Scalar::ZERO; size], bases.clone()),
+            ("unit scalars", vec![C::Scalar::ONE; size], bases.clone()),
+            (
+                "negative unit scalars",
+                vec![-C::Scalar::ONE; size],
+                bases.clone(),
+            ),
+            (
+                "bases at infinity",
+                coeffs.clone(),
+                vec![C::identity(); size],
+            ),
+            ("a repeated base", coeffs.clone(), vec![base; size]),
+            (
+                "cancelling terms",
+                [coeffs.clone(), coeffs].concat(),
+                [bases, negated].concat(),
+            ),
+        ];
+        for (case, coeffs, bases) in cases {
+            if engine.msm(&coeffs, &bases) != naive_msm(&coeffs, &bases) {
+                return Err(Error::Other(format!(
+                    "the MSM of {} {} differs from the naive MSM",
+                    coeffs.len(),
+                    case
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compares the FFTs of `engine` with [`naive_dft`] for every size from 2 up to
+/// `2^max_log_n` supported by the field, on random, zero, constant and unit impulse
+/// values, and checks that they are inverted by the FFTs of the inverse root of unity, and
+/// by the conversions between the bases of an [`EvaluationDomain`].
+pub fn check_fft<F: WithSmallOrderMulGroup<3>>(
+    engine: &dyn FftAccel<F>,
+    mut rng: impl RngCore,
+    max_log_n: u32,
+) -> Result<(), Error> {
+    let mismatch = |log_n: u32, case: &str, what: &str| {
+        Err(Error::Other(format!(
+            "the FFT of 2^{} {} {}",
+            log_n, case, what
+        )))
+    };
+
+    for log_n in 1..=max_log_n.min(F::S) {
+        let n = 1 << log_n;
+        let omega = (log_n..F::S).fold(F::ROOT_OF_UNITY, |omega, _| omega.square());
+        let omega_inv = omega.invert().unwrap();
+        let n_inv = F::from(n as u64).invert().unwrap();
+
+        let random: Vec<_> = (0..n).map(|_| F::random(&mut rng)).collect();
+        let mut impulse = vec![F::ZERO; n];
+        impulse[0] = F::ONE;
+        let cases = [
+            ("random values", random.clone()),
+            ("zeros", vec![F::ZERO; n]),
+            ("constant values", vec![random[0]; n]),
+            ("unit impulse values", impulse),
+        ];
+        for (case, values) in cases {
+            let mut evals = values.clone();
+            engine.fft(&mut evals, omega, log_n);
+            if log_n <= NAIVE_DFT_MAX_LOG_N && evals != naive_dft(&values, omega) {
+                return mismatch(log_n, case, "differs from the naive DFT");
+            }
+            engine.fft(&mut evals, omega_inv, log_n);
+            evals.iter_mut().for_each(|eval| *eval *= n_inv);
+            if evals != values {
+                return mismatch(log_n, case, "isn't inverted by the inverse FFT");
+            }
+        }
+
+        if EvaluationDomain::<F>::extended_k(3, log_n) > F::S {
+            continue;
+        }
+        let domain = EvaluationDomain::<F>::new(3, log_n);
+        let coeffs =
+            domain.lagrange_to_coeff_with_engine(engine, domain.lagrange_from_vec(random.clone()));
+        if log_n <= NAIVE_DFT_MAX_LOG_N && naive_dft(&coeffs, omega) != random {
+            return mismatch(
+                log_n,
+                "random values",
+                "isn't inverted by lagrange_to_coeff",
+            );
+        }
+        let extended = domain.coeff_to_extended_with_engine(engine, coeffs.clone());
+        let back = domain.extended_to_coeff_with_engine(engine, extended);
+        if back[..n] != coeffs[..] || back[n..].iter().any(|coeff| *coeff != F::ZERO) {
+            return mismatch(
+                log_n,
+                "random coefficients",
+                "isn't inverted by extended_to_coeff",
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_fft, check_field, check_msm};
+    use crate::zal::H2cEngine;
+    use halo2curves::{bn256, pasta};
+    use proptest::prelude::*;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    #[test]
+    fn fields() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        check_field::<bn256::Fr>(&mut rng, 32).unwrap();
+        check_field::<pasta::Fp>(&mut rng, 32).unwrap();
+        check_field::<pasta::Fq>(&mut rng, 32).unwrap();
+    }
+
+    #[test]
+    fn ffts() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        check_fft::<bn256::Fr>(&H2cEngine, &mut rng, 10).unwrap();
+        check_fft::<pasta::Fp>(&H2cEngine, &mut rng, 10).unwrap();
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn msm_matches_naive(seed in any::<u64>(), size in 0usize..80) {
+            let mut rng = ChaCha20Rng::seed_from_u64(seed);
+            prop_assert!(check_msm::<bn256::G1Affine>(&H2cEngine, &mut rng, &[size]).is_ok());
+            prop_assert!(check_msm::<pasta::EqAffine>(&H2cEngine, &mut rng, &[size]).is_ok());
+        }
+
+        #[test]
+        fn fft_round_trips(seed in any::<u64>(), log_n in 1u32..8) {
+            let mut rng = ChaCha20Rng::seed_from_u64(seed);
+            prop_assert!(check_fft::<pasta::Fq>(&H2cEngine, &mut rng, log_n).is_ok());
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! A report of how the polynomials committed in a proof are blinded.
+//!
+//! A proof hides the witness as long as every committed polynomial derived from it is
+//! blinded: its commitment uses a random blinding factor, and it has more random rows
+//! than the number of points it is opened at. [`BlindingReport`] lists every polynomial
+//! committed by the prover for a given circuit, so that the zero-knowledge properties of
+//! custom configurations (for example circuits with unblinded advice columns) can be
+//! audited.
+//!
+//! ```ignore
+//! let report = BlindingReport::new(&vk);
+//! println!("{}", report);
+//! assert!(report.unhidden().all(|poly| poly.kind.is_advice()));
+//! ```
+
+use std::fmt;
+
+use halo2_common::plonk::{circuit::Column, ConstraintSystem};
+use halo2_middleware::circuit::{Advice, Any};
+use halo2_middleware::ff::Field;
+use halo2curves::CurveAffine;
+
+use crate::plonk::VerifyingKey;
+
+/// The role of a committed polynomial in a proof.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolynomialKind {
+    /// The advice column with the given index.
+    Advice(usize),
+    /// The permuted input expression of the lookup with the given index.
+    LookupPermutedInput(usize),
+    /// The permuted table expression of the lookup with the given index.
+    LookupPermutedTable(usize),
+    /// The grand product of the lookup with the given index.
+    LookupProduct(usize),
+    /// The grand product of the shuffle with the given index.
+    ShuffleProduct(usize),
+    /// The grand product of the permutation argument for the given set of columns.
+    PermutationProduct(usize),
+    /// The random polynomial of the vanishing argument.
+    VanishingRandom,
+    /// The piece of the quotient polynomial `h(X)` with the given index.
+    VanishingPiece(usize),
+}
+
+impl PolynomialKind {
+    /// Returns `true` if this is an advice column.
+    pub fn is_advice(&self) -> bool {
+        matches!(self, PolynomialKind::Advice(_))
+    }
+}
+
+impl fmt::Display for PolynomialKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolynomialKind::Advice(index) => write!(f, "advice column {}", index),
+            PolynomialKind::LookupPermutedInput(index) => {
+                write!(f, "lookup {} permuted input", index)
+            }
+            PolynomialKind::LookupPermutedTable(index) => {
+                write!(f, "lookup {} permuted table", index)
+            }
+            PolynomialKind::LookupProduct(index) => write!(f, "lookup {} product", index),
+            PolynomialKind::ShuffleProduct(index) => write!(f, "shuffle {} product", index),
+            PolynomialKind::PermutationProduct(index) => {
+                write!(f, "permutation set {} product", index)
+            }
+            PolynomialKind::VanishingRandom => write!(f, "vanishing random polynomial"),
+            PolynomialKind::VanishingPiece(index) => write!(f, "h(X) piece {}", index),
+        }
+    }
+}
+
+/// A polynomial committed in a proof, and how it is blinded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommittedPolynomial {
+    /// The role of the polynomial.
+    pub kind: PolynomialKind,
+    /// The name of the column or argument the polynomial belongs to, if any.
+    pub name: Option<String>,
+    /// Whether the commitment uses a random blinding factor.
+    pub commitment_blinded: bool,
+    /// The number of rows (or coefficients) of the polynomial that are random.
+    pub random_rows: usize,
+    /// The number of points the polynomial is evaluated at in the proof, including the
+    /// evaluation of the multiopen argument. The pieces of `h(X)` are only revealed
+    /// combined with the random polynomial, so they are never evaluated on their own.
+    pub evaluations: usize,
+}
+
+impl CommittedPolynomial {
+    /// Returns `true` if the commitment and the evaluations of this polynomial reveal
+    /// nothing about its values.
+    pub fn is_hidden(&self) -> bool {
+        self.commitment_blinded && self.random_rows >= self.evaluations
+    }
+}
+
+/// The blinding of every polynomial committed by the prover for a circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlindingReport {
+    /// The number of random rows at the end of the blinded columns.
+    pub blinding_factors: usize,
+    /// The committed polynomials of a single circuit, grouped in the order they are
+    /// committed: advice columns, permuted lookup expressions, permutation products, lookup
+    /// products, shuffle products and the vanishing argument.
+    pub polynomials: Vec<CommittedPolynomial>,
+}
+
+impl BlindingReport {
+    /// Builds the report for the circuit of `vk`.
+    pub fn new<C: CurveAffine>(vk: &VerifyingKey<C>) -> Self {
+        Self::from_cs(vk.cs(), vk.get_domain().k())
+    }
+
+    /// Builds the report for a circuit with the given constraint system and `2^k` rows.
+    pub fn from_cs<F: Field>(cs: &ConstraintSystem<F>, k: u32) -> Self {
+        let blinding_factors = cs.blinding_factors();
+        let blinded = |kind, name, evaluations| CommittedPolynomial {
+            kind,
+            name,
+            commitment_blinded: true,
+            random_rows: blinding_factors,
+            evaluations,
+        };
+
+        let mut polynomials = vec![];
+
+        let advice_phases = cs.advice_column_phase();
+        for (index, &num_queries) in cs.num_advice_queries.iter().enumerate() {
+            let column = Column::<Any>::new(index, Any::Advice(Advice::new(advice_phases[index])));
+            let name = cs.column_name(column).map(str::to_string);
+            let evaluations = num_queries + 1;
+            polynomials.push(if cs.unblinded_advice_columns.contains(&index) {
+                CommittedPolynomial {
+                    kind: PolynomialKind::Advice(index),
+                    name,
+                    commitment_blinded: false,
+                    random_rows: 0,
+                    evaluations,
+                }
+            } else {
+                blinded(PolynomialKind::Advice(index), name, evaluations)
+            });
+        }
+
+        for (index, lookup) in cs.lookups().iter().enumerate() {
+            let name = || Some(lookup.name.clone());
+            polynomials.push(blinded(
+                PolynomialKind::LookupPermutedInput(index),
+                name(),
+                3,
+            ));
+            polynomials.push(blinded(
+                PolynomialKind::LookupPermutedTable(index),
+                name(),
+                2,
+            ));
+        }
+
+        // Circuits without a permutation argument may have a degree lower than 3.
+        let chunk_len = std::cmp::max(cs.degree().saturating_sub(2), 1);
+        let num_sets = cs.permutation().get_columns().chunks(chunk_len).count();
+        for index in 0..num_sets {
+            // The last set is not evaluated at the last usable row.
+            let evaluations = if index + 1 < num_sets { 4 } else { 3 };
+            polynomials.push(blinded(
+                PolynomialKind::PermutationProduct(index),
+                None,
+                evaluations,
+            ));
+        }
+
+        for (index, lookup) in cs.lookups().iter().enumerate() {
+            polynomials.push(blinded(
+                PolynomialKind::LookupProduct(index),
+                Some(lookup.name.clone()),
+                3,
+            ));
+        }
+
+        for (index, shuffle) in cs.shuffles().iter().enumerate() {
+            polynomials.push(blinded(
+                PolynomialKind::ShuffleProduct(index),
+                Some(shuffle.name.clone()),
+                3,
+            ));
+        }
+
+        polynomials.push(CommittedPolynomial {
+            kind: PolynomialKind::VanishingRandom,
+            name: None,
+            commitment_blinded: true,
+            random_rows: 1 << k,
+            evaluations: 2,
+        });
+
+        for index in 0..cs.degree() - 1 {
+            polynomials.push(CommittedPolynomial {
+                kind: PolynomialKind::VanishingPiece(index),
+                name: None,
+                commitment_blinded: true,
+                random_rows: 0,
+                evaluations: 0,
+            });
+        }
+
+        BlindingReport {
+            blinding_factors,
+            polynomials,
+        }
+    }
+
+    /// Returns the polynomials that are not hidden, see [`CommittedPolynomial::is_hidden`].
+    pub fn unhidden(&self) -> impl Iterator<Item = &CommittedPolynomial> {
+        self.polynomials.iter().filter(|poly| !poly.is_hidden())
+    }
+}
+
+impl fmt::Display for BlindingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "blinding factors: {}", self.blinding_factors)?;
+        for poly in self.polynomials.iter() {
+            write!(f, "{}", poly.kind)?;
+            if let Some(name) = &poly.name {
+                write!(f, " ({})", name)?;
+            }
+            writeln!(
+                f,
+                ": commitment {}, {} random rows, {} evaluations{}",
+                if poly.commitment_blinded {
+                    "blinded"
+                } else {
+                    "unblinded"
+                },
+                poly.random_rows,
+                poly.evaluations,
+                if poly.is_hidden() {
+                    ""
+                } else {
+                    " [NOT HIDDEN]"
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlindingReport, PolynomialKind};
+    use halo2_common::plonk::{ConstraintSystem, TableColumn};
+    use halo2_middleware::poly::Rotation;
+    use halo2curves::pasta::Fp;
+
+    #[test]
+    fn unblinded_advice() {
+        let mut meta = ConstraintSystem::<Fp>::default();
+        let a = meta.advice_column();
+        let b = meta.unblinded_advice_column();
+        let table: TableColumn = meta.lookup_table_column();
+        meta.enable_equality(a);
+        meta.enable_equality(b);
+        meta.lookup("lookup", |meta| {
+            vec![(meta.query_advice(a, Rotation::cur()), table)]
+        });
+        meta.create_gate("gate", |meta| {
+            let cur = meta.query_advice(a, Rotation::cur());
+            let next = meta.query_advice(a, Rotation::next());
+            vec![cur - next]
+        });
+
+        let report = BlindingReport::from_cs(&meta, 4);
+        let kinds: Vec<_> = report.polynomials.iter().map(|poly| poly.kind).collect();
+        assert_eq!(
+            kinds[..2],
+            [PolynomialKind::Advice(0), PolynomialKind::Advice(1)]
+        );
+        assert!(kinds.contains(&PolynomialKind::LookupProduct(0)));
+        assert!(kinds.contains(&PolynomialKind::PermutationProduct(0)));
+        assert!(kinds.contains(&PolynomialKind::VanishingRandom));
+
+        let unhidden: Vec<_> = report.unhidden().map(|poly| poly.kind).collect();
+        assert_eq!(unhidden, [PolynomialKind::Advice(1)]);
+        assert!(report
+            .to_string()
+            .contains("advice column 1: commitment unblinded"));
+    }
+}
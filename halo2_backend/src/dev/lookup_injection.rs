@@ -0,0 +1,150 @@
+//! Injection of inconsistent lookup witnesses, to check that a verifier rejects them.
+//!
+//! A lookup argument proves that each row of its compressed input expression `A` is a row
+//! of its compressed table expression `S`. The prover commits to the permuted expressions
+//! `A'` and `S'`, and the constraints of the argument check that:
+//! - `A'` and `S'` are permutations of `A` and `S`, with a grand product, and
+//! - each row of `A'` is equal to the same row of `S'` or to the previous row of `A'`.
+//!
+//! The honest prover computes `A'` and `S'` by sorting `A`, and fails if an input is not in
+//! the table. [`ProverV2::inject_lookup_witness`] bypasses this, and commits to the `A'`
+//! and `S'` of a [`LookupInjection`] instead. [`LookupInjection::Unpermuted`] and
+//! [`LookupInjection::Forged`] each satisfy one of the two checks above but not the other,
+//! so a verifier accepting their proofs has lost a part of the constraints of the
+//! argument, for example while refactoring it.
+//!
+//! This module and [`ProverV2::inject_lookup_witness`] are only built with the
+//! `lookup-injection` feature, which is meant for tests and never for production provers.
+//!
+//! ```ignore
+//! let mut prover = ProverV2Single::<Scheme, P, _, _, _>::new(
+//!     &params, &pk, instances, OsRng, &mut transcript,
+//! )?;
+//! prover.inject_lookup_witness(0, LookupInjection::Forged)?;
+//! // Commit the phases of the witness, whose inputs are not all in the table.
+//! prover.create_proof()?;
+//! assert!(verify_proof(&params, &vk, strategy, &[instances], &mut transcript).is_err());
+//! ```
+//!
+//! [`ProverV2::inject_lookup_witness`]: crate::plonk::prover::ProverV2::inject_lookup_witness
+
+use std::collections::BTreeMap;
+
+use halo2_common::plonk::Error;
+use halo2_middleware::ff::PrimeField;
+
+use crate::arithmetic::sort_by_repr;
+
+/// Permuted expressions of a lookup committed instead of the ones of the honest prover.
+///
+/// The expressions are given over the usable rows, those before the blinding rows. For a
+/// lookup of several expressions, `A` and `S` are their compressions with the challenge
+/// `theta`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LookupInjection<F> {
+    /// Commits to `A` and `S` themselves as `A'` and `S'`.
+    ///
+    /// They are permutations of `A` and `S`, but unless `A` and `S` are equal in the first
+    /// row and each row of `A` after it is equal to the same row of `S` or to the previous
+    /// row of `A`, the proof doesn't satisfy the second check.
+    Unpermuted,
+    /// Commits to `A` sorted as `A'`, and to `S'` with the first row of each run of equal
+    /// inputs set to the input, whether or not it is in the table, and the other rows set
+    /// to the rest of the table.
+    ///
+    /// The proof satisfies the second check, but if an input is not in the table, `S'` is
+    /// not a permutation of `S`.
+    Forged,
+    /// Commits to the given values of `A'` and `S'`.
+    Values {
+        /// The values of `A'` at the usable rows.
+        input: Vec<F>,
+        /// The values of `S'` at the usable rows.
+        table: Vec<F>,
+    },
+}
+
+impl<F: PrimeField + Ord> LookupInjection<F> {
+    /// Returns the `A'` and `S'` of the injection, given the values of `A` and `S` at the
+    /// usable rows.
+    pub(crate) fn permute(&self, input: &[F], table: &[F]) -> Result<(Vec<F>, Vec<F>), Error> {
+        match self {
+            LookupInjection::Unpermuted => Ok((input.to_vec(), table.to_vec())),
+            LookupInjection::Forged => Ok(forge(input, table)),
+            LookupInjection::Values {
+                input: permuted_input,
+                table: permuted_table,
+            } => {
+                if permuted_input.len() != input.len() || permuted_table.len() != table.len() {
+                    return Err(Error::Other(format!(
+                        "the injected lookup witness must have {} rows",
+                        input.len()
+                    )));
+                }
+                Ok((permuted_input.clone(), permuted_table.clone()))
+            }
+        }
+    }
+}
+
+/// Returns the `A'` and `S'` of [`LookupInjection::Forged`].
+fn forge<F: PrimeField + Ord>(input: &[F], table: &[F]) -> (Vec<F>, Vec<F>) {
+    let mut permuted_input = input.to_vec();
+    sort_by_repr(&mut permuted_input);
+
+    let mut leftover_table: BTreeMap<F, usize> = BTreeMap::new();
+    for value in table {
+        *leftover_table.entry(*value).or_insert(0) += 1;
+    }
+    let mut permuted_table = permuted_input.clone();
+    let mut repeated_input_rows = vec![];
+    for row in 0..permuted_input.len() {
+        if row == 0 || permuted_input[row] != permuted_input[row - 1] {
+            // Missing inputs are kept at the start of their run all the same.
+            if let Some(count) = leftover_table.get_mut(&permuted_input[row]) {
+                *count = count.saturating_sub(1);
+            }
+        } else {
+            repeated_input_rows.push(row);
+        }
+    }
+    let leftover_table = leftover_table
+        .into_iter()
+        .flat_map(|(value, count)| std::iter::repeat(value).take(count));
+    for (row, value) in repeated_input_rows.into_iter().zip(leftover_table) {
+        permuted_table[row] = value;
+    }
+    (permuted_input, permuted_table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2curves::pasta::Fp;
+
+    fn values(values: &[u64]) -> Vec<Fp> {
+        values.iter().copied().map(Fp::from).collect()
+    }
+
+    #[test]
+    fn forged_witness() {
+        let table = values(&[0, 1, 2, 3]);
+
+        // Without missing inputs, the forged witness is a valid one.
+        let injection = LookupInjection::Forged;
+        let (input, permuted_table) = injection.permute(&values(&[2, 0, 2, 2]), &table).unwrap();
+        assert_eq!(input, values(&[0, 2, 2, 2]));
+        assert_eq!(permuted_table, values(&[0, 2, 1, 3]));
+
+        // A missing input takes the place of a row of the table.
+        let (input, permuted_table) = injection.permute(&values(&[9, 0, 0, 0]), &table).unwrap();
+        assert_eq!(input, values(&[0, 0, 0, 9]));
+        assert_eq!(permuted_table, values(&[0, 1, 2, 9]));
+
+        let injection = LookupInjection::Values {
+            input: values(&[1]),
+            table: values(&[1]),
+        };
+        assert!(injection.permute(&table, &table).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! Utilities to check that a verifier rejects corrupted proofs.
+//!
+//! A proof is written through a [`RecordingTranscript`], which records the position and
+//! value of every element (commitment or evaluation) written to the proof. The
+//! resulting [`ProofLayout`] is then used by [`tampered_proofs`] to build, for each
+//! element, a copy of the proof in which only that element is replaced by a different
+//! valid element. A sound verifier must reject every one of them, which
+//! [`assert_rejects_tampered_proofs`] checks.
+//!
+//! ```ignore
+//! let mut transcript =
+//!     RecordingTranscript::new(Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]));
+//! create_proof(&params, &pk, &circuits, &instances, OsRng, &mut transcript)?;
+//! let (transcript, layout) = transcript.into_parts();
+//! let proof = transcript.finalize();
+//!
+//! assert_rejects_tampered_proofs(&proof, &layout, |proof| {
+//!     let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+//!     verify_proof(&params, &vk, SingleStrategy::new(&params), &instances, &mut transcript)
+//!         .is_ok()
+//! });
+//! ```
+
+use group::{
+    ff::{Field, PrimeField},
+    prime::PrimeCurveAffine,
+    Curve, GroupEncoding,
+};
+use halo2curves::CurveAffine;
+use std::io;
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use crate::transcript::{EncodedChallenge, Transcript, TranscriptPhase, TranscriptWrite};
+
+/// An element of a proof.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofElement<C: CurveAffine> {
+    /// A curve point, such as a commitment.
+    Point(C),
+    /// A scalar, such as an evaluation.
+    Scalar(C::Scalar),
+}
+
+impl<C: CurveAffine> ProofElement<C> {
+    fn to_bytes(self) -> Vec<u8> {
+        match self {
+            ProofElement::Point(point) => point.to_bytes().as_ref().to_vec(),
+            ProofElement::Scalar(scalar) => scalar.to_repr().as_ref().to_vec(),
+        }
+    }
+
+    /// Returns a different element of the same kind.
+    fn tampered(self) -> Self {
+        match self {
+            ProofElement::Point(point) => ProofElement::Point((point + C::generator()).to_affine()),
+            ProofElement::Scalar(scalar) => ProofElement::Scalar(scalar + C::Scalar::ONE),
+        }
+    }
+}
+
+/// The position and value of every element written to a proof.
+#[derive(Clone, Debug, Default)]
+pub struct ProofLayout<C: CurveAffine> {
+    elements: Vec<(Range<usize>, ProofElement<C>)>,
+}
+
+impl<C: CurveAffine> ProofLayout<C> {
+    /// Returns the byte range and the value of each element of the proof, in the order
+    /// they were written.
+    pub fn elements(&self) -> &[(Range<usize>, ProofElement<C>)] {
+        &self.elements
+    }
+
+    fn push(&mut self, element: ProofElement<C>) {
+        let start = self.elements.last().map_or(0, |(range, _)| range.end);
+        let len = element.to_bytes().len();
+        self.elements.push((start..start + len, element));
+    }
+}
+
+/// A transcript that records the [`ProofLayout`] of the proof written through it.
+///
+/// This assumes that the inner transcript writes points in their compressed form and
+/// scalars in their canonical representation, without any other data, as the
+/// transcripts of this crate do.
+#[derive(Debug)]
+pub struct RecordingTranscript<C: CurveAffine, E: EncodedChallenge<C>, T: TranscriptWrite<C, E>> {
+    inner: T,
+    layout: ProofLayout<C>,
+    _marker: PhantomData<E>,
+}
+
+impl<C: CurveAffine, E: EncodedChallenge<C>, T: TranscriptWrite<C, E>>
+    RecordingTranscript<C, E, T>
+{
+    /// Wraps `inner`, which must not have written any element yet.
+    pub fn new(inner: T) -> Self {
+        RecordingTranscript {
+            inner,
+            layout: ProofLayout {
+                elements: Vec::new(),
+            },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the inner transcript and the layout of the proof written to it.
+    pub fn into_parts(self) -> (T, ProofLayout<C>) {
+        (self.inner, self.layout)
+    }
+}
+
+impl<C: CurveAffine, E: EncodedChallenge<C>, T: TranscriptWrite<C, E>> Transcript<C, E>
+    for RecordingTranscript<C, E, T>
+{
+    fn label(&mut self, phase: TranscriptPhase, purpose: &'static str) {
+        self.inner.label(phase, purpose)
+    }
+
+    fn squeeze_challenge(&mut self) -> E {
+        self.inner.squeeze_challenge()
+    }
+
+    fn common_point(&mut self, point: C) -> io::Result<()> {
+        self.inner.common_point(point)
+    }
+
+    fn common_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        self.inner.common_scalar(scalar)
+    }
+}
+
+impl<C: CurveAffine, E: EncodedChallenge<C>, T: TranscriptWrite<C, E>> TranscriptWrite<C, E>
+    for RecordingTranscript<C, E, T>
+{
+    fn write_point(&mut self, point: C) -> io::Result<()> {
+        self.inner.write_point(point)?;
+        self.layout.push(ProofElement::Point(point));
+        Ok(())
+    }
+
+    fn write_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        self.inner.write_scalar(scalar)?;
+        self.layout.push(ProofElement::Scalar(scalar));
+        Ok(())
+    }
+}
+
+/// A copy of a proof in which a single element was replaced.
+#[derive(Clone, Debug)]
+pub struct TamperedProof<C: CurveAffine> {
+    /// The index of the replaced element in the [`ProofLayout`].
+    pub index: usize,
+    /// The original element.
+    pub original: ProofElement<C>,
+    /// The element it was replaced with.
+    pub replacement: ProofElement<C>,
+    /// The tampered proof.
+    pub proof: Vec<u8>,
+}
+
+/// Returns, for each element of `layout`, a copy of `proof` in which that element is
+/// replaced by a different valid element of the same kind.
+///
+/// # Panics
+///
+/// Panics if `proof` is shorter than described by `layout`.
+pub fn tampered_proofs<'a, C: CurveAffine>(
+    proof: &'a [u8],
+    layout: &'a ProofLayout<C>,
+) -> impl Iterator<Item = TamperedProof<C>> + 'a {
+    layout
+        .elements
+        .iter()
+        .enumerate()
+        .map(move |(index, (range, original))| {
+            let replacement = original.tampered();
+            let mut tampered = proof.to_vec();
+            tampered[range.clone()].copy_from_slice(&replacement.to_bytes());
+            TamperedProof {
+                index,
+                original: *original,
+                replacement,
+                proof: tampered,
+            }
+        })
+}
+
+/// Asserts that `verify` rejects every proof returned by [`tampered_proofs`].
+///
+/// `verify` must return `true` if the proof it is given is accepted.
+///
+/// # Panics
+///
+/// Panics, listing the accepted tampered proofs, if any of them is accepted.
+pub fn assert_rejects_tampered_proofs<C: CurveAffine>(
+    proof: &[u8],
+    layout: &ProofLayout<C>,
+    mut verify: impl FnMut(&[u8]) -> bool,
+) {
+    let accepted: Vec<_> = tampered_proofs(proof, layout)
+        .filter(|tampered| verify(&tampered.proof))
+        .map(|tampered| (tampered.index, tampered.original))
+        .collect();
+    assert!(
+        accepted.is_empty(),
+        "the verifier accepted proofs with tampered elements: {accepted:?}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tampered_proofs, ProofElement, RecordingTranscript};
+    use crate::transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptRead, TranscriptReadBuffer,
+        TranscriptWrite, TranscriptWriterBuffer,
+    };
+    use group::{prime::PrimeCurveAffine, Curve};
+    use halo2curves::pasta::{EqAffine, Fp};
+
+    #[test]
+    fn tampers_every_element() {
+        let point = (EqAffine::generator() * Fp::from(5)).to_affine();
+        let scalar = Fp::from(7);
+
+        let mut transcript =
+            RecordingTranscript::new(Blake2bWrite::<_, EqAffine, Challenge255<_>>::init(vec![]));
+        transcript.write_point(point).unwrap();
+        transcript.write_scalar(scalar).unwrap();
+        let (transcript, layout) = transcript.into_parts();
+        let proof = transcript.finalize();
+
+        assert_eq!(layout.elements().len(), 2);
+        assert_eq!(layout.elements()[1].0.end, proof.len());
+
+        let tampered: Vec<_> = tampered_proofs(&proof, &layout).collect();
+        assert_eq!(tampered.len(), 2);
+        for tampered in tampered.iter() {
+            assert_ne!(tampered.proof, proof);
+            assert_ne!(tampered.original, tampered.replacement);
+
+            // Tampered proofs are still well-formed.
+            let mut transcript =
+                Blake2bRead::<_, EqAffine, Challenge255<_>>::init(&tampered.proof[..]);
+            let read = [
+                ProofElement::Point(transcript.read_point().unwrap()),
+                ProofElement::Scalar(transcript.read_scalar().unwrap()),
+            ];
+            assert_eq!(read[tampered.index], tampered.replacement);
+            assert_eq!(
+                read[1 - tampered.index],
+                layout.elements()[1 - tampered.index].1
+            );
+        }
+    }
+}
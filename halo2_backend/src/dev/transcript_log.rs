@@ -0,0 +1,423 @@
+//! Utilities to diagnose mismatches between the transcripts of a prover and a verifier.
+//!
+//! A proof is created and verified through [`LoggingTranscript`]s, which record every
+//! message absorbed into the transcript and every challenge squeezed from it, with the
+//! [`TranscriptPhase`] and the purpose given by the prover or verifier with
+//! [`Transcript::label`]. If a proof doesn't verify because the prover and the verifier
+//! disagree on the Fiat-Shamir transcript, [`diff_transcripts`] reports the first
+//! message on which they differ.
+//!
+//! ```ignore
+//! let mut transcript =
+//!     LoggingTranscript::new(Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]));
+//! create_proof(&params, &pk, &circuits, &instances, OsRng, &mut transcript)?;
+//! let (transcript, prover_log) = transcript.into_parts();
+//! let proof = transcript.finalize();
+//!
+//! let mut transcript =
+//!     LoggingTranscript::new(Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]));
+//! let result =
+//!     verify_proof(&params, &vk, SingleStrategy::new(&params), &instances, &mut transcript);
+//! if let Some(diff) = diff_transcripts(&prover_log, transcript.log()) {
+//!     println!("{}", diff);
+//! }
+//! ```
+
+use group::{ff::PrimeField, GroupEncoding};
+use halo2curves::CurveAffine;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+
+use crate::transcript::{
+    EncodedChallenge, Transcript, TranscriptPhase, TranscriptRead, TranscriptWrite,
+};
+
+/// The label of a message of a transcript, see [`Transcript::label`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TranscriptLabel {
+    /// The phase of the protocol the message belongs to.
+    pub phase: TranscriptPhase,
+    /// What the message is, e.g. `"beta"` or `"advice commitments"`.
+    pub purpose: &'static str,
+}
+
+/// A message absorbed into, or squeezed from, a transcript.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TranscriptMessage<C: CurveAffine> {
+    /// A point absorbed as a common input, which is not part of the proof.
+    CommonPoint(C),
+    /// A scalar absorbed as a common input, which is not part of the proof.
+    CommonScalar(C::Scalar),
+    /// A point of the proof, written by the prover or read by the verifier.
+    Point(C),
+    /// A scalar of the proof, written by the prover or read by the verifier.
+    Scalar(C::Scalar),
+    /// A challenge squeezed from the transcript.
+    Challenge(C::Scalar),
+}
+
+impl<C: CurveAffine> TranscriptMessage<C> {
+    /// Returns the kind of the message, e.g. `"common point"` or `"challenge"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TranscriptMessage::CommonPoint(_) => "common point",
+            TranscriptMessage::CommonScalar(_) => "common scalar",
+            TranscriptMessage::Point(_) => "point",
+            TranscriptMessage::Scalar(_) => "scalar",
+            TranscriptMessage::Challenge(_) => "challenge",
+        }
+    }
+
+    /// Returns the encoding of the point, or the representation of the scalar, of the
+    /// message.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            TranscriptMessage::CommonPoint(point) | TranscriptMessage::Point(point) => {
+                point.to_bytes().as_ref().to_vec()
+            }
+            TranscriptMessage::CommonScalar(scalar)
+            | TranscriptMessage::Scalar(scalar)
+            | TranscriptMessage::Challenge(scalar) => scalar.to_repr().as_ref().to_vec(),
+        }
+    }
+}
+
+/// A message of a [`TranscriptLog`], with the label it was given.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TranscriptLogEntry<C: CurveAffine> {
+    /// The last label given to the transcript before the message, if any.
+    pub label: Option<TranscriptLabel>,
+    /// The message.
+    pub message: TranscriptMessage<C>,
+}
+
+impl<C: CurveAffine> fmt::Display for TranscriptLogEntry<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.label {
+            Some(label) => write!(f, "{:?} {}: ", label.phase, label.purpose)?,
+            None => write!(f, "unlabeled: ")?,
+        }
+        write!(f, "{} 0x", self.message.kind())?;
+        for byte in self.message.to_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// The messages of a transcript, in the order they were absorbed or squeezed.
+#[derive(Clone, Debug)]
+pub struct TranscriptLog<C: CurveAffine> {
+    entries: Vec<TranscriptLogEntry<C>>,
+}
+
+impl<C: CurveAffine> Default for TranscriptLog<C> {
+    fn default() -> Self {
+        TranscriptLog {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<C: CurveAffine> TranscriptLog<C> {
+    /// Returns the messages of the transcript.
+    pub fn entries(&self) -> &[TranscriptLogEntry<C>] {
+        &self.entries
+    }
+
+    /// Returns the challenges squeezed from the transcript, with their labels.
+    pub fn challenges(&self) -> impl Iterator<Item = (Option<TranscriptLabel>, C::Scalar)> + '_ {
+        self.entries.iter().filter_map(|entry| match entry.message {
+            TranscriptMessage::Challenge(challenge) => Some((entry.label, challenge)),
+            _ => None,
+        })
+    }
+
+    /// Returns the index of the first entry which differs between `self` and `other`,
+    /// in its label or its message, or `None` if both logs are equal.
+    ///
+    /// If one log is a prefix of the other, the index is the length of the shorter one.
+    pub fn first_mismatch(&self, other: &TranscriptLog<C>) -> Option<usize> {
+        self.entries
+            .iter()
+            .zip(other.entries.iter())
+            .position(|(a, b)| a != b)
+            .or_else(|| {
+                (self.entries.len() != other.entries.len())
+                    .then(|| self.entries.len().min(other.entries.len()))
+            })
+    }
+
+    fn push(&mut self, label: Option<TranscriptLabel>, message: TranscriptMessage<C>) {
+        self.entries.push(TranscriptLogEntry { label, message });
+    }
+}
+
+impl<C: CurveAffine> fmt::Display for TranscriptLog<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, entry) in self.entries.iter().enumerate() {
+            writeln!(f, "{}: {}", index, entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// The first message on which the transcripts of a prover and a verifier diverge,
+/// returned by [`diff_transcripts`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranscriptDiff<C: CurveAffine> {
+    /// The index of the message in both logs.
+    pub index: usize,
+    /// The last message on which both logs agree, if any.
+    pub previous: Option<TranscriptLogEntry<C>>,
+    /// The message of the prover, or `None` if its log ended before.
+    pub prover: Option<TranscriptLogEntry<C>>,
+    /// The message of the verifier, or `None` if its log ended before.
+    pub verifier: Option<TranscriptLogEntry<C>>,
+}
+
+impl<C: CurveAffine> fmt::Display for TranscriptDiff<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entry = |entry: &Option<TranscriptLogEntry<C>>| match entry {
+            Some(entry) => entry.to_string(),
+            None => "end of the transcript".to_string(),
+        };
+        writeln!(f, "the transcripts diverge at message {}", self.index)?;
+        if let Some(previous) = &self.previous {
+            writeln!(f, "  after:    {}", previous)?;
+        }
+        writeln!(f, "  prover:   {}", entry(&self.prover))?;
+        write!(f, "  verifier: {}", entry(&self.verifier))
+    }
+}
+
+/// Compares the logs of the transcripts of a prover and a verifier, and returns the
+/// first message on which they diverge, or `None` if they are equal.
+///
+/// The messages are compared with their labels, so a diverging label points at a
+/// prover and a verifier which don't follow the same steps, and a diverging value with
+/// the same label points at a value absorbed differently by them. In both cases the
+/// challenges squeezed afterwards differ, so the first diverging message is the cause
+/// and the later ones are consequences.
+pub fn diff_transcripts<C: CurveAffine>(
+    prover_log: &TranscriptLog<C>,
+    verifier_log: &TranscriptLog<C>,
+) -> Option<TranscriptDiff<C>> {
+    let index = prover_log.first_mismatch(verifier_log)?;
+    Some(TranscriptDiff {
+        index,
+        previous: index
+            .checked_sub(1)
+            .map(|previous| prover_log.entries[previous]),
+        prover: prover_log.entries.get(index).copied(),
+        verifier: verifier_log.entries.get(index).copied(),
+    })
+}
+
+/// A transcript that records the [`TranscriptLog`] of the messages absorbed into, and
+/// the challenges squeezed from, the transcript it wraps.
+///
+/// It wraps either the transcript of a prover or the transcript of a verifier, so that
+/// their logs can be compared. Only the messages which succeed are recorded.
+#[derive(Debug)]
+pub struct LoggingTranscript<C: CurveAffine, E: EncodedChallenge<C>, T: Transcript<C, E>> {
+    inner: T,
+    label: Option<TranscriptLabel>,
+    log: TranscriptLog<C>,
+    _marker: PhantomData<E>,
+}
+
+impl<C: CurveAffine, E: EncodedChallenge<C>, T: Transcript<C, E>> LoggingTranscript<C, E, T> {
+    /// Wraps `inner`, recording the messages from now on.
+    pub fn new(inner: T) -> Self {
+        LoggingTranscript {
+            inner,
+            label: None,
+            log: TranscriptLog::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the log of the messages recorded so far.
+    pub fn log(&self) -> &TranscriptLog<C> {
+        &self.log
+    }
+
+    /// Returns the inner transcript and the log of its messages.
+    pub fn into_parts(self) -> (T, TranscriptLog<C>) {
+        (self.inner, self.log)
+    }
+
+    fn record<V>(
+        &mut self,
+        result: io::Result<V>,
+        message: impl FnOnce(&V) -> TranscriptMessage<C>,
+    ) -> io::Result<V> {
+        if let Ok(value) = &result {
+            self.log.push(self.label, message(value));
+        }
+        result
+    }
+}
+
+impl<C: CurveAffine, E: EncodedChallenge<C>, T: Transcript<C, E>> Transcript<C, E>
+    for LoggingTranscript<C, E, T>
+{
+    fn label(&mut self, phase: TranscriptPhase, purpose: &'static str) {
+        self.label = Some(TranscriptLabel { phase, purpose });
+        self.inner.label(phase, purpose)
+    }
+
+    fn squeeze_challenge(&mut self) -> E {
+        let challenge = self.inner.squeeze_challenge();
+        self.log.push(
+            self.label,
+            TranscriptMessage::Challenge(challenge.get_scalar()),
+        );
+        challenge
+    }
+
+    fn common_point(&mut self, point: C) -> io::Result<()> {
+        let result = self.inner.common_point(point);
+        self.record(result, |_| TranscriptMessage::CommonPoint(point))
+    }
+
+    fn common_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        let result = self.inner.common_scalar(scalar);
+        self.record(result, |_| TranscriptMessage::CommonScalar(scalar))
+    }
+}
+
+impl<C: CurveAffine, E: EncodedChallenge<C>, T: TranscriptWrite<C, E>> TranscriptWrite<C, E>
+    for LoggingTranscript<C, E, T>
+{
+    fn write_point(&mut self, point: C) -> io::Result<()> {
+        let result = self.inner.write_point(point);
+        self.record(result, |_| TranscriptMessage::Point(point))
+    }
+
+    fn write_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        let result = self.inner.write_scalar(scalar);
+        self.record(result, |_| TranscriptMessage::Scalar(scalar))
+    }
+}
+
+impl<C: CurveAffine, E: EncodedChallenge<C>, T: TranscriptRead<C, E>> TranscriptRead<C, E>
+    for LoggingTranscript<C, E, T>
+{
+    fn read_point(&mut self) -> io::Result<C> {
+        let result = self.inner.read_point();
+        self.record(result, |point| TranscriptMessage::Point(*point))
+    }
+
+    fn read_scalar(&mut self) -> io::Result<C::Scalar> {
+        let result = self.inner.read_scalar();
+        self.record(result, |scalar| TranscriptMessage::Scalar(*scalar))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_transcripts, LoggingTranscript, TranscriptLabel, TranscriptMessage};
+    use crate::transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, Transcript, TranscriptPhase, TranscriptRead,
+        TranscriptReadBuffer, TranscriptWrite, TranscriptWriterBuffer,
+    };
+    use group::{prime::PrimeCurveAffine, Curve};
+    use halo2curves::pasta::{EqAffine, Fp};
+
+    #[test]
+    fn logs_match_between_prover_and_verifier() {
+        let point = (EqAffine::generator() * Fp::from(5)).to_affine();
+
+        let mut transcript =
+            LoggingTranscript::new(Blake2bWrite::<_, EqAffine, Challenge255<_>>::init(vec![]));
+        transcript.common_scalar(Fp::from(3)).unwrap();
+        transcript.label(TranscriptPhase::Advice(0), "advice commitments");
+        transcript.write_point(point).unwrap();
+        transcript.label(TranscriptPhase::Lookups, "theta");
+        let theta = transcript.squeeze_challenge_scalar::<()>();
+        let (transcript, prover_log) = transcript.into_parts();
+        let proof = transcript.finalize();
+
+        let entries = prover_log.entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].label, None);
+        assert_eq!(
+            entries[1].label,
+            Some(TranscriptLabel {
+                phase: TranscriptPhase::Advice(0),
+                purpose: "advice commitments",
+            })
+        );
+        assert_eq!(entries[1].message, TranscriptMessage::Point(point));
+        let challenges: Vec<_> = prover_log.challenges().collect();
+        assert_eq!(challenges.len(), 1);
+        assert_eq!(challenges[0].1, *theta);
+
+        let read = |common: Fp, purpose| {
+            let mut transcript = LoggingTranscript::new(
+                Blake2bRead::<_, EqAffine, Challenge255<_>>::init(&proof[..]),
+            );
+            transcript.common_scalar(common).unwrap();
+            transcript.label(TranscriptPhase::Advice(0), purpose);
+            transcript.read_point().unwrap();
+            transcript.label(TranscriptPhase::Lookups, "theta");
+            transcript.squeeze_challenge();
+            transcript.into_parts().1
+        };
+        assert_eq!(
+            prover_log.first_mismatch(&read(Fp::from(3), "advice commitments")),
+            None
+        );
+        // The common inputs differ first.
+        assert_eq!(
+            prover_log.first_mismatch(&read(Fp::from(4), "advice commitments")),
+            Some(0)
+        );
+        let log = read(Fp::from(3), "other");
+        assert_eq!(prover_log.first_mismatch(&log), Some(1));
+        assert_eq!(log.challenges().next().unwrap().1, *theta);
+
+        let diff = diff_transcripts(&prover_log, &log).unwrap();
+        assert_eq!(diff.index, 1);
+        assert_eq!(diff.previous, Some(entries[0]));
+        assert_eq!(diff.prover, Some(entries[1]));
+        assert_eq!(diff.verifier.unwrap().label.unwrap().purpose, "other");
+        assert!(diff
+            .to_string()
+            .contains("verifier: Advice(0) other: point 0x"));
+    }
+
+    #[test]
+    fn diff_of_truncated_transcript() {
+        let mut transcript =
+            LoggingTranscript::new(Blake2bWrite::<_, EqAffine, Challenge255<_>>::init(vec![]));
+        transcript.label(TranscriptPhase::Evaluations, "fixed evaluations");
+        transcript.write_scalar(Fp::from(2)).unwrap();
+        transcript.write_scalar(Fp::from(3)).unwrap();
+        let (transcript, prover_log) = transcript.into_parts();
+        let proof = transcript.finalize();
+
+        // The verifier reads one scalar less.
+        let mut transcript = LoggingTranscript::new(
+            Blake2bRead::<_, EqAffine, Challenge255<_>>::init(&proof[..]),
+        );
+        transcript.label(TranscriptPhase::Evaluations, "fixed evaluations");
+        transcript.read_scalar().unwrap();
+        let verifier_log = transcript.into_parts().1;
+
+        assert_eq!(diff_transcripts(&prover_log, &prover_log), None);
+        let diff = diff_transcripts(&prover_log, &verifier_log).unwrap();
+        assert_eq!(diff.index, 1);
+        assert_eq!(diff.verifier, None);
+        assert_eq!(
+            diff.prover.unwrap().message,
+            TranscriptMessage::Scalar(Fp::from(3))
+        );
+        assert!(diff
+            .to_string()
+            .ends_with("verifier: end of the transcript"));
+    }
+}
@@ -1,7 +1,10 @@
-use crate::poly::Polynomial;
+use crate::poly::{commitment::Blind, Polynomial};
+use blake2b_simd::{Params as Blake2bParams, State as Blake2bState};
 pub(crate) use halo2_common::helpers::{SerdeFormat, SerdePrimeField};
-use halo2_middleware::ff::PrimeField;
-use std::io;
+use halo2_middleware::ff::{Field, PrimeField};
+use halo2curves::{CurveAffine, CurveExt};
+use std::io::{self, Read};
+use std::ops::{Deref, DerefMut};
 
 pub(crate) use halo2_common::helpers::{pack, unpack, CurveRead, SerdeCurveAffine};
 
@@ -37,3 +40,398 @@ pub(crate) fn polynomial_slice_byte_length<F: PrimeField, B>(slice: &[Polynomial
     let field_len = F::default().to_repr().as_ref().len();
     4 + slice.len() * (4 + field_len * slice.get(0).map(|poly| poly.len()).unwrap_or(0))
 }
+
+/// Columns with at most one non-zero value in this many rows, such as selectors, are
+/// sparse: they are committed to with the bases of their non-zero rows only, and proving
+/// keys store their non-zero values instead of their polynomials.
+pub(crate) const SPARSE_COLUMN_DENSITY: usize = 16;
+
+/// Returns the rows and the values of the non-zero values of a column, if it is sparse.
+pub(crate) fn sparse_entries<F: Field>(column: &[F]) -> Option<(Vec<usize>, Vec<F>)> {
+    let max_entries = column.len() / SPARSE_COLUMN_DENSITY;
+    let mut rows = vec![];
+    let mut values = vec![];
+    for (row, value) in column.iter().enumerate() {
+        if !bool::from(value.is_zero()) {
+            if rows.len() == max_entries {
+                return None;
+            }
+            rows.push(row);
+            values.push(*value);
+        }
+    }
+    Some((rows, values))
+}
+
+/// Bytes preceding the identifier of the curve in serialized artifacts. Parameters
+/// serialized without an identifier start with `k` as a little-endian `u32`, which never
+/// has these bytes.
+pub(crate) const CURVE_ID_MAGIC: [u8; 4] = *b"H2CV";
+
+/// Writes the identifier of the curve `C`, which artifacts serialized for it start with:
+/// [`CURVE_ID_MAGIC`], then the length of the name of the curve as a byte followed by
+/// the name, then the length of the modulus of its scalar field as a byte followed by the
+/// modulus, as a hexadecimal string.
+pub(crate) fn write_curve_id<C: CurveAffine, W: io::Write>(writer: &mut W) -> io::Result<()> {
+    writer.write_all(&CURVE_ID_MAGIC)?;
+    for id in [C::CurveExt::CURVE_ID, C::Scalar::MODULUS] {
+        writer.write_all(&[id.len() as u8])?;
+        writer.write_all(id.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Returns the number of bytes written by [`write_curve_id`].
+pub(crate) fn curve_id_byte_length<C: CurveAffine>() -> usize {
+    CURVE_ID_MAGIC.len() + 2 + C::CurveExt::CURVE_ID.len() + C::Scalar::MODULUS.len()
+}
+
+/// Reads the identifier written by [`write_curve_id`], and checks that `artifact` was
+/// serialized for the curve `C`.
+pub(crate) fn read_curve_id<C: CurveAffine, R: io::Read>(
+    reader: &mut R,
+    artifact: &str,
+) -> io::Result<()> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != CURVE_ID_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the {artifact} doesn't start with the identifier of its curve"),
+        ));
+    }
+    check_curve_id::<C, _>(reader, artifact)
+}
+
+/// Checks the identifier written by [`write_curve_id`] after [`CURVE_ID_MAGIC`], which
+/// has already been read.
+pub(crate) fn check_curve_id<C: CurveAffine, R: io::Read>(
+    reader: &mut R,
+    artifact: &str,
+) -> io::Result<()> {
+    let mut read_id = || -> io::Result<String> {
+        let mut len = [0u8; 1];
+        reader.read_exact(&mut len)?;
+        let mut id = vec![0u8; len[0] as usize];
+        reader.read_exact(&mut id)?;
+        Ok(String::from_utf8_lossy(&id).into_owned())
+    };
+    let curve = read_id()?;
+    let modulus = read_id()?;
+    if curve != C::CurveExt::CURVE_ID || modulus != C::Scalar::MODULUS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the {artifact} was serialized for the curve {curve} with the scalar field \
+                 of modulus {modulus}, but is read for the curve {} with the scalar field of \
+                 modulus {}",
+                C::CurveExt::CURVE_ID,
+                C::Scalar::MODULUS
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the size `k` of parameters for the curve `C`, which is preceded by the identifier
+/// of the curve, or not for parameters serialized before it was recorded.
+pub(crate) fn read_params_k<C: CurveAffine, R: io::Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    if bytes == CURVE_ID_MAGIC {
+        check_curve_id::<C, _>(reader, "parameters")?;
+        reader.read_exact(&mut bytes)?;
+    }
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Bytes starting parameters whose sections are followed by their checksums, before the
+/// identifier of the curve. Neither `k` nor [`CURVE_ID_MAGIC`] has these bytes.
+pub(crate) const CHECKSUMS_MAGIC: [u8; 4] = *b"H2CS";
+
+/// Writes [`CHECKSUMS_MAGIC`] if `checksums` is set, and returns a writer for the rest of
+/// the parameters, starting with the identifier of the curve `C` and `k`.
+pub(crate) fn write_params_start<C: CurveAffine, W: io::Write>(
+    writer: &mut W,
+    k: u32,
+    checksums: bool,
+) -> io::Result<ChecksumWriter<&mut W>> {
+    if checksums {
+        writer.write_all(&CHECKSUMS_MAGIC)?;
+    }
+    let mut writer = ChecksumWriter::new(writer, checksums);
+    write_curve_id::<C, _>(&mut writer)?;
+    writer.write_all(&k.to_le_bytes())?;
+    Ok(writer)
+}
+
+/// Reads the start of parameters written by [`write_params_start`], or by releases which
+/// recorded neither checksums nor the curve, and returns a reader for the rest of the
+/// parameters, which checks the checksums of their sections if they have them.
+pub(crate) fn read_params_start<C: CurveAffine, R: io::Read>(
+    reader: &mut R,
+) -> io::Result<(ChecksumReader<&mut R>, u32)> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    let checksums = bytes == CHECKSUMS_MAGIC;
+    let mut reader = ChecksumReader::new(reader, checksums);
+    let k = if checksums {
+        read_params_k::<C, _>(&mut reader)?
+    } else {
+        read_params_k::<C, _>(&mut (&bytes[..]).chain(&mut reader))?
+    };
+    Ok((reader, k))
+}
+
+/// The length of the checksums of sections.
+const CHECKSUM_LENGTH: usize = 32;
+
+fn checksum_state() -> Blake2bState {
+    Blake2bParams::new()
+        .hash_length(CHECKSUM_LENGTH)
+        .personal(b"Halo2-Checksums-")
+        .to_state()
+}
+
+/// A writer which follows each section of an artifact with the BLAKE2b checksum of its
+/// bytes, if checksums are enabled, so that corrupted artifacts are detected on read
+/// without validating their points.
+#[derive(Debug)]
+pub(crate) struct ChecksumWriter<W> {
+    inner: W,
+    state: Option<Blake2bState>,
+}
+
+impl<W: io::Write> ChecksumWriter<W> {
+    /// Wraps `inner`, which is written to unchanged if `checksums` is not set.
+    pub(crate) fn new(inner: W, checksums: bool) -> Self {
+        Self {
+            inner,
+            state: checksums.then(checksum_state),
+        }
+    }
+
+    /// Ends a section, writing the checksum of the bytes written since the previous one.
+    pub(crate) fn end_section(&mut self) -> io::Result<()> {
+        if let Some(state) = self.state.as_mut() {
+            let checksum = std::mem::replace(state, checksum_state()).finalize();
+            self.inner.write_all(checksum.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: io::Write> io::Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(state) = self.state.as_mut() {
+            state.update(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader of the artifacts written by a [`ChecksumWriter`], which checks the checksum
+/// of each section if checksums are enabled.
+#[derive(Debug)]
+pub(crate) struct ChecksumReader<R> {
+    inner: R,
+    state: Option<Blake2bState>,
+    section: usize,
+}
+
+impl<R: io::Read> ChecksumReader<R> {
+    /// Wraps `inner`, which is read from unchanged if `checksums` is not set.
+    pub(crate) fn new(inner: R, checksums: bool) -> Self {
+        Self {
+            inner,
+            state: checksums.then(checksum_state),
+            section: 0,
+        }
+    }
+
+    /// Ends a section, checking the checksum of the bytes read since the previous one.
+    pub(crate) fn end_section(&mut self) -> io::Result<()> {
+        if let Some(state) = self.state.as_mut() {
+            let expected = std::mem::replace(state, checksum_state()).finalize();
+            let mut checksum = [0u8; CHECKSUM_LENGTH];
+            self.inner.read_exact(&mut checksum)?;
+            if checksum[..] != *expected.as_bytes() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "the checksum of section {} doesn't match its bytes, which are corrupted",
+                        self.section
+                    ),
+                ));
+            }
+        }
+        self.section += 1;
+        Ok(())
+    }
+}
+
+impl<R: io::Read> io::Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some(state) = self.state.as_mut() {
+            state.update(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
+/// Values derived from the witness, which must not outlive the proof when the
+/// `hardened-witness` feature is enabled.
+pub(crate) trait Wipe {
+    /// Overwrites the values with zeros.
+    fn wipe(&mut self);
+}
+
+impl<F: Field, B> Wipe for Polynomial<F, B> {
+    fn wipe(&mut self) {
+        halo2_common::helpers::wipe(&mut self.values, F::ZERO);
+    }
+}
+
+impl<F: Field> Wipe for Blind<F> {
+    fn wipe(&mut self) {
+        halo2_common::helpers::wipe(std::slice::from_mut(&mut self.0), F::ZERO);
+    }
+}
+
+impl<T: Wipe> Wipe for Vec<T> {
+    fn wipe(&mut self) {
+        for value in self.iter_mut() {
+            value.wipe();
+        }
+    }
+}
+
+#[cfg(feature = "hardened-witness")]
+impl<F: Field, B> zeroize::Zeroize for Polynomial<F, B> {
+    fn zeroize(&mut self) {
+        self.wipe();
+    }
+}
+
+#[cfg(feature = "hardened-witness")]
+impl<F: Field> zeroize::Zeroize for Blind<F> {
+    fn zeroize(&mut self) {
+        self.wipe();
+    }
+}
+
+/// Wipes `value` if the `hardened-witness` feature is enabled.
+pub(crate) fn wipe_if_hardened<T: Wipe>(value: &mut T) {
+    if cfg!(feature = "hardened-witness") {
+        value.wipe();
+    }
+}
+
+/// Wipes `values` if the `hardened-witness` feature is enabled.
+pub(crate) fn wipe_scalars_if_hardened<F: Field>(values: &mut [F]) {
+    if cfg!(feature = "hardened-witness") {
+        halo2_common::helpers::wipe(values, F::ZERO);
+    }
+}
+
+/// Holds values derived from the witness, and wipes them when dropped if the
+/// `hardened-witness` feature is enabled.
+#[derive(Debug, Default)]
+pub(crate) struct Wiped<T: Wipe>(pub(crate) T);
+
+impl<T: Wipe> Drop for Wiped<T> {
+    fn drop(&mut self) {
+        wipe_if_hardened(&mut self.0);
+    }
+}
+
+impl<T: Wipe> Deref for Wiped<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Wipe> DerefMut for Wiped<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        read_curve_id, read_params_k, read_params_start, write_curve_id, write_params_start,
+        ChecksumReader,
+    };
+    use halo2curves::bn256::G1Affine;
+    use halo2curves::pasta::EqAffine;
+    use halo2curves::CurveExt;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn curve_id() {
+        let mut bytes = vec![];
+        write_curve_id::<G1Affine, _>(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), super::curve_id_byte_length::<G1Affine>());
+        assert!(read_curve_id::<G1Affine, _>(&mut &bytes[..], "proof").is_ok());
+
+        let error = read_curve_id::<EqAffine, _>(&mut &bytes[..], "proof")
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("the proof was serialized"), "{error}");
+        assert!(error.contains(halo2curves::bn256::G1::CURVE_ID), "{error}");
+        assert!(error.contains(halo2curves::pasta::Eq::CURVE_ID), "{error}");
+
+        // Parameters may or may not start with the identifier.
+        bytes.extend_from_slice(&7u32.to_le_bytes());
+        assert_eq!(read_params_k::<G1Affine, _>(&mut &bytes[..]).unwrap(), 7);
+        assert!(read_params_k::<EqAffine, _>(&mut &bytes[..]).is_err());
+        assert_eq!(
+            read_params_k::<EqAffine, _>(&mut &7u32.to_le_bytes()[..]).unwrap(),
+            7
+        );
+    }
+
+    #[test]
+    fn checksums() {
+        for checksums in [false, true] {
+            let mut bytes = vec![];
+            let mut writer = write_params_start::<G1Affine, _>(&mut bytes, 7, checksums).unwrap();
+            writer.end_section().unwrap();
+            writer.write_all(&[1, 2, 3]).unwrap();
+            writer.end_section().unwrap();
+
+            let read = |bytes: &[u8]| -> std::io::Result<Vec<u8>> {
+                let mut reader = &bytes[..];
+                let (mut reader, k) = read_params_start::<G1Affine, _>(&mut reader)?;
+                assert_eq!(k, 7);
+                reader.end_section()?;
+                let mut section = vec![0u8; 3];
+                reader.read_exact(&mut section)?;
+                reader.end_section()?;
+                Ok(section)
+            };
+            assert_eq!(read(&bytes).unwrap(), vec![1, 2, 3]);
+
+            // Corrupted bytes are only detected with checksums.
+            let position = bytes.len() - if checksums { 33 } else { 1 };
+            bytes[position] ^= 1;
+            assert_eq!(read(&bytes).is_err(), checksums);
+        }
+
+        // Sections without checksums are read unchanged.
+        let mut reader = ChecksumReader::new(&[1u8, 2][..], false);
+        let mut section = vec![];
+        reader.read_to_end(&mut section).unwrap();
+        reader.end_section().unwrap();
+        assert_eq!(section, vec![1, 2]);
+    }
+}
@@ -1,8 +1,13 @@
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
 pub mod arithmetic;
+pub mod calldata;
+pub mod dev;
 mod helpers;
 pub mod plonk;
 pub mod poly;
 pub mod transcript;
+pub mod zal;
 
 // Internal re-exports
 pub use halo2_common::circuit;
@@ -1,29 +1,49 @@
 use blake2b_simd::Params as Blake2bParams;
-use group::ff::{Field, FromUniformBytes, PrimeField};
+use group::ff::{Field, FromUniformBytes, PrimeField, WithSmallOrderMulGroup};
 
 use crate::arithmetic::CurveAffine;
 use crate::helpers::{
-    self, polynomial_slice_byte_length, read_polynomial_vec, write_polynomial_slice,
-    SerdeCurveAffine, SerdePrimeField,
+    self, curve_id_byte_length, read_curve_id, sparse_entries, write_curve_id, ChecksumReader,
+    ChecksumWriter, SerdeCurveAffine, SerdePrimeField, SPARSE_COLUMN_DENSITY,
 };
+use crate::poly::commitment::Params;
 use crate::poly::{
     Coeff, EvaluationDomain, ExtendedLagrangeCoeff, LagrangeCoeff, PinnedEvaluationDomain,
     Polynomial,
 };
 use crate::transcript::{ChallengeScalar, EncodedChallenge, Transcript};
+use crate::zal::{traits::FftAccel, PlonkEngine};
 pub(crate) use evaluation::Evaluator;
-use halo2_common::plonk::{Circuit, ConstraintSystem, PinnedConstraintSystem};
+use halo2_common::plonk::{Circuit, Column, ConstraintSystem, PinnedConstraintSystem};
 use halo2_common::SerdeFormat;
+use halo2_middleware::circuit::{Any, ConstraintSystemV2Backend, Fixed};
 
-use std::io;
+use std::io::{self, Read};
+use std::sync::Arc;
 
 pub(crate) use halo2_common::plonk::Error;
 
+#[cfg(feature = "jit")]
+pub use evaluation::GateEvaluation;
+pub use evaluation::WitnessLayout;
+pub use evaluation_proof::{create_evaluation_proof, verify_evaluation_proof, EvaluationProof};
+pub use extra_queries::ExtraQuery;
+pub use limits::ProverLimits;
+pub use query_order::{QueryGroup, QueryOrder};
+pub use sections::{verify_proof_section, ProofSection, ProofSections};
+#[cfg(feature = "vanishing-pieces")]
+pub use vanishing::VanishingPieces;
+
 mod evaluation;
+mod evaluation_proof;
+mod extra_queries;
 pub mod keygen;
+mod limits;
 mod lookup;
 mod permutation;
 pub mod prover;
+mod query_order;
+mod sections;
 mod shuffle;
 mod vanishing;
 pub mod verifier;
@@ -44,6 +64,8 @@ pub struct VerifyingKey<C: CurveAffine> {
     cs_degree: usize,
     /// The representative of this `VerifyingKey` in transcripts.
     transcript_repr: C::Scalar,
+    /// The version of the protocol proofs for this `VerifyingKey` follow.
+    protocol_version: u8,
     /// Selectors
     selectors: Vec<Vec<bool>>,
     // TODO: Use setter/getter https://github.com/privacy-scaling-explorations/halo2/issues/259
@@ -52,7 +74,38 @@ pub struct VerifyingKey<C: CurveAffine> {
 }
 
 // Current version of the VK
-const VERSION: u8 = 0x03;
+const VERSION: u8 = 0x05;
+// Version of the VK serialization with checksums, which is followed by a key of the
+// current version and its checksum.
+const VERSION_CHECKSUMS: u8 = 0x06;
+// Version of the VK serialization before the curve was recorded.
+const VERSION_WITHOUT_CURVE: u8 = 0x04;
+// Version of the VK serialization before the protocol version was recorded. Keys in this
+// format follow protocol version 0.
+const VERSION_LEGACY: u8 = 0x03;
+
+/// The current version of the proof protocol.
+///
+/// The protocol version is bound to every proof through the transcript, so that proofs
+/// created by incompatible versions of this crate are rejected instead of being
+/// misinterpreted:
+///
+/// - The representative of a [`VerifyingKey`] in transcripts is the BLAKE2b-512 hash,
+///   personalized with `"Halo2-Verify-Key"`, of [`PROTOCOL_DOMAIN_SEPARATOR`], the
+///   protocol version byte, and the length-prefixed debug representation of the
+///   [`PinnedVerificationKey`], which covers the full constraint system, the domain,
+///   the fixed commitments and the permutation.
+/// - The protocol version is absorbed into the transcript as a common scalar, before the
+///   representative of the verifying key.
+///
+/// Version 0 is the protocol of previous releases, in which neither the domain separator
+/// nor the version were hashed or absorbed. Keys can be switched to it with
+/// [`VerifyingKey::with_protocol_version`] to verify or create proofs compatible with
+/// those releases.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Domain separator hashed into the representative of a [`VerifyingKey`] in transcripts.
+pub const PROTOCOL_DOMAIN_SEPARATOR: &[u8] = b"halo2-plonk-protocol";
 
 impl<C: SerdeCurveAffine> VerifyingKey<C>
 where
@@ -60,6 +113,9 @@ where
 {
     /// Writes a verifying key to a buffer.
     ///
+    /// The key starts with the identifier of the curve, which is checked when it is read,
+    /// so that a key can't be read for another curve.
+    ///
     /// Writes a curve element according to `format`:
     /// - `Processed`: Writes a compressed curve element with coordinates in standard form.
     /// Writes a field element in standard form, with endianness specified by the
@@ -68,8 +124,36 @@ where
     /// Writes a field element into raw bytes in its internal Montgomery representation,
     /// WITHOUT performing the expensive Montgomery reduction.
     pub fn write<W: io::Write>(&self, writer: &mut W, format: SerdeFormat) -> io::Result<()> {
+        self.write_sections(writer, format, false)
+    }
+
+    /// Writes a verifying key to a buffer like [`Self::write`], followed by a BLAKE2b
+    /// checksum of its bytes which [`Self::read`] checks, so that a corrupted key is
+    /// detected even when it is read with [`SerdeFormat::RawBytesUnchecked`].
+    pub fn write_with_checksums<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+    ) -> io::Result<()> {
+        self.write_sections(writer, format, true)
+    }
+
+    fn write_sections<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+        checksums: bool,
+    ) -> io::Result<()> {
         // Version byte that will be checked on read.
-        writer.write_all(&[VERSION])?;
+        let version = if checksums {
+            VERSION_CHECKSUMS
+        } else {
+            VERSION
+        };
+        writer.write_all(&[version])?;
+        let writer = &mut ChecksumWriter::new(writer, checksums);
+        write_curve_id::<C, _>(writer)?;
+        writer.write_all(&[self.protocol_version])?;
         let k = &self.domain.k();
         assert!(*k <= C::Scalar::S);
         // k value fits in 1 byte
@@ -91,7 +175,7 @@ where
                 writer.write_all(&[helpers::pack(bits)])?;
             }
         }
-        Ok(())
+        writer.end_section()
     }
 
     /// Reads a verification key from a buffer.
@@ -104,19 +188,104 @@ where
     /// Checks that field elements are less than modulus, and then checks that the point is on the curve.
     /// - `RawBytesUnchecked`: Reads an uncompressed curve element with coordinates in Montgomery form;
     /// does not perform any checks
+    ///
+    /// The checksum of a key written by [`Self::write_with_checksums`] is checked.
     pub fn read<R: io::Read, ConcreteCircuit: Circuit<C::Scalar>>(
         reader: &mut R,
         format: SerdeFormat,
         #[cfg(feature = "circuit-params")] params: ConcreteCircuit::Params,
     ) -> io::Result<Self> {
+        Self::read_sections::<R, ConcreteCircuit>(
+            reader,
+            format,
+            #[cfg(feature = "circuit-params")]
+            params,
+        )
+        .map(|(vk, _)| vk)
+    }
+
+    /// Reads a verification key like [`Self::read`], and returns whether it was written
+    /// with checksums.
+    fn read_sections<R: io::Read, ConcreteCircuit: Circuit<C::Scalar>>(
+        reader: &mut R,
+        format: SerdeFormat,
+        #[cfg(feature = "circuit-params")] params: ConcreteCircuit::Params,
+    ) -> io::Result<(Self, bool)> {
+        Self::read_inner(reader, format, |k| {
+            keygen::create_domain::<C, ConcreteCircuit>(
+                k,
+                #[cfg(feature = "circuit-params")]
+                params,
+            )
+            .map(|(domain, cs, _)| (domain, cs))
+        })
+    }
+
+    /// Reads a verification key from a buffer, like [`Self::read`], for a circuit given by
+    /// the constraint system of a [`CompiledCircuitV2`] instead of a `Circuit`.
+    ///
+    /// The key must have been generated from a circuit with the same constraint system,
+    /// for example with [`keygen::keygen_vk_v2`].
+    ///
+    /// [`CompiledCircuitV2`]: halo2_middleware::circuit::CompiledCircuitV2
+    pub fn read_v2<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        cs: &ConstraintSystemV2Backend<C::Scalar>,
+    ) -> io::Result<Self> {
+        Self::read_v2_sections(reader, format, cs).map(|(vk, _)| vk)
+    }
+
+    /// Reads a verification key like [`Self::read_v2`], and returns whether it was
+    /// written with checksums.
+    fn read_v2_sections<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        cs: &ConstraintSystemV2Backend<C::Scalar>,
+    ) -> io::Result<(Self, bool)> {
+        Self::read_inner(reader, format, |k| {
+            let cs: ConstraintSystem<C::Scalar> = cs.clone().into();
+            keygen::create_domain_for_cs::<C>(k, &cs).map(|domain| (domain, cs))
+        })
+    }
+
+    fn read_inner<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        create_domain: impl FnOnce(
+            u32,
+        ) -> Result<
+            (EvaluationDomain<C::Scalar>, ConstraintSystem<C::Scalar>),
+            Error,
+        >,
+    ) -> io::Result<(Self, bool)> {
         let mut version_byte = [0u8; 1];
         reader.read_exact(&mut version_byte)?;
-        if VERSION != version_byte[0] {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "unexpected version byte",
-            ));
-        }
+        let checksums = version_byte[0] == VERSION_CHECKSUMS;
+        let reader = &mut ChecksumReader::new(reader, checksums);
+        let protocol_version = match version_byte[0] {
+            version @ (VERSION | VERSION_WITHOUT_CURVE | VERSION_CHECKSUMS) => {
+                if version != VERSION_WITHOUT_CURVE {
+                    read_curve_id::<C, _>(reader, "verifying key")?;
+                }
+                let mut protocol_version = [0u8; 1];
+                reader.read_exact(&mut protocol_version)?;
+                if protocol_version[0] > PROTOCOL_VERSION {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "unsupported protocol version",
+                    ));
+                }
+                protocol_version[0]
+            }
+            VERSION_LEGACY => 0,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected version byte",
+                ))
+            }
+        };
 
         let mut k = [0u8; 1];
         reader.read_exact(&mut k)?;
@@ -140,14 +309,19 @@ where
             ));
         }
         let compress_selectors = compress_selectors[0] == 1;
-        let (domain, cs, _) = keygen::create_domain::<C, ConcreteCircuit>(
-            k as u32,
-            #[cfg(feature = "circuit-params")]
-            params,
-        );
+        let (domain, cs) = create_domain(k as u32)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
         let mut num_fixed_columns = [0u8; 4];
         reader.read_exact(&mut num_fixed_columns)?;
-        let num_fixed_columns = u32::from_le_bytes(num_fixed_columns);
+        let num_fixed_columns = u32::from_le_bytes(num_fixed_columns) as usize;
+        // Selectors are turned into at most one fixed column each. Bounding the number of
+        // columns here prevents a malformed key from triggering a huge allocation.
+        if num_fixed_columns > cs.num_fixed_columns + cs.num_selectors {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected number of fixed columns",
+            ));
+        }
 
         let fixed_commitments: Vec<_> = (0..num_fixed_columns)
             .map(|_| C::read(reader, format))
@@ -156,12 +330,19 @@ where
         let permutation = permutation::VerifyingKey::read(reader, &cs.permutation, format)?;
 
         let (cs, selectors) = if compress_selectors {
-            // read selectors
-            let selectors: Vec<Vec<bool>> = vec![vec![false; 1 << k]; cs.num_selectors]
-                .into_iter()
-                .map(|mut selector| {
-                    let mut selector_bytes = vec![0u8; (selector.len() + 7) / 8];
-                    reader.read_exact(&mut selector_bytes)?;
+            // read selectors, allocating them only once their bytes have been read
+            let selectors: Vec<Vec<bool>> = (0..cs.num_selectors)
+                .map(|_| {
+                    let len = ((1 << k) + 7) / 8;
+                    let mut selector_bytes = Vec::new();
+                    reader
+                        .by_ref()
+                        .take(len as u64)
+                        .read_to_end(&mut selector_bytes)?;
+                    if selector_bytes.len() != len {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    let mut selector = vec![false; 1 << k];
                     for (bits, byte) in selector.chunks_mut(8).zip(selector_bytes) {
                         helpers::unpack(byte, bits);
                     }
@@ -176,15 +357,26 @@ where
             let (cs, _) = cs.directly_convert_selectors_to_fixed(fake_selectors);
             (cs, vec![])
         };
+        if fixed_commitments.len() != cs.num_fixed_columns {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected number of fixed columns",
+            ));
+        }
+        reader.end_section()?;
 
-        Ok(Self::from_parts(
+        let vk = Self::from_parts(
             domain,
             fixed_commitments,
             permutation,
             cs,
             selectors,
             compress_selectors,
-        ))
+        );
+        let vk = vk
+            .with_protocol_version(protocol_version)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        Ok((vk, checksums))
     }
 
     /// Writes a verifying key to a vector of bytes using [`Self::write`].
@@ -207,6 +399,15 @@ where
             params,
         )
     }
+
+    /// Reads a verification key from a slice of bytes using [`Self::read_v2`].
+    pub fn from_bytes_v2(
+        mut bytes: &[u8],
+        format: SerdeFormat,
+        cs: &ConstraintSystemV2Backend<C::Scalar>,
+    ) -> io::Result<Self> {
+        Self::read_v2(&mut bytes, format, cs)
+    }
 }
 
 impl<C: CurveAffine> VerifyingKey<C> {
@@ -214,7 +415,8 @@ impl<C: CurveAffine> VerifyingKey<C> {
     where
         C: SerdeCurveAffine,
     {
-        10 + (self.fixed_commitments.len() * C::byte_length(format))
+        11 + curve_id_byte_length::<C>()
+            + (self.fixed_commitments.len() * C::byte_length(format))
             + self.permutation.bytes_length(format)
             + self.selectors.len()
                 * (self
@@ -246,24 +448,59 @@ impl<C: CurveAffine> VerifyingKey<C> {
             cs_degree,
             // Temporary, this is not pinned.
             transcript_repr: C::Scalar::ZERO,
+            protocol_version: PROTOCOL_VERSION,
             selectors,
             compress_selectors,
         };
+        vk.transcript_repr = vk.compute_transcript_repr();
+
+        vk
+    }
 
+    fn compute_transcript_repr(&self) -> C::Scalar
+    where
+        C::ScalarExt: FromUniformBytes<64>,
+    {
         let mut hasher = Blake2bParams::new()
             .hash_length(64)
             .personal(b"Halo2-Verify-Key")
             .to_state();
 
-        let s = format!("{:?}", vk.pinned());
+        if self.protocol_version > 0 {
+            hasher.update(PROTOCOL_DOMAIN_SEPARATOR);
+            hasher.update(&[self.protocol_version]);
+        }
+
+        let s = format!("{:?}", self.pinned());
 
         hasher.update(&(s.len() as u64).to_le_bytes());
         hasher.update(s.as_bytes());
 
         // Hash in final Blake2bState
-        vk.transcript_repr = C::Scalar::from_uniform_bytes(hasher.finalize().as_array());
+        C::Scalar::from_uniform_bytes(hasher.finalize().as_array())
+    }
 
-        vk
+    /// Returns this verifying key, for proofs following the given protocol version.
+    ///
+    /// See [`PROTOCOL_VERSION`] for the differences between versions. Returns an error
+    /// if `protocol_version` is newer than [`PROTOCOL_VERSION`].
+    pub fn with_protocol_version(mut self, protocol_version: u8) -> Result<Self, Error>
+    where
+        C::ScalarExt: FromUniformBytes<64>,
+    {
+        if protocol_version > PROTOCOL_VERSION {
+            return Err(Error::Other(format!(
+                "unsupported protocol version {protocol_version}, the latest is {PROTOCOL_VERSION}"
+            )));
+        }
+        self.protocol_version = protocol_version;
+        self.transcript_repr = self.compute_transcript_repr();
+        Ok(self)
+    }
+
+    /// Returns the version of the protocol proofs for this verifying key follow.
+    pub fn protocol_version(&self) -> u8 {
+        self.protocol_version
     }
 
     /// Hashes a verification key into a transcript.
@@ -271,6 +508,9 @@ impl<C: CurveAffine> VerifyingKey<C> {
         &self,
         transcript: &mut T,
     ) -> io::Result<()> {
+        if self.protocol_version > 0 {
+            transcript.common_scalar(C::Scalar::from(self.protocol_version as u64))?;
+        }
         transcript.common_scalar(self.transcript_repr)?;
 
         Ok(())
@@ -299,6 +539,57 @@ impl<C: CurveAffine> VerifyingKey<C> {
         &self.permutation
     }
 
+    /// Returns the commitment of each fixed column with the column, in the order of the
+    /// indices of the columns, which is the order of [`Self::fixed_commitments`].
+    ///
+    /// If selectors were compressed, the fixed columns they were compressed into come
+    /// after the fixed columns of the circuit.
+    pub fn fixed_column_commitments(&self) -> impl Iterator<Item = (Column<Fixed>, C)> + '_ {
+        self.fixed_commitments
+            .iter()
+            .enumerate()
+            .map(|(index, commitment)| (Column::new(index, Fixed), *commitment))
+    }
+
+    /// Returns the commitment of the permutation polynomial of each column of the
+    /// permutation argument with the column, in the order of the columns of the
+    /// argument, which is the order of the commitments of [`Self::permutation`].
+    pub fn permutation_column_commitments(&self) -> impl Iterator<Item = (Column<Any>, C)> + '_ {
+        self.cs
+            .permutation
+            .columns
+            .iter()
+            .zip(self.permutation.commitments().iter())
+            .map(|(column, commitment)| (*column, *commitment))
+    }
+
+    /// Returns the maximum number of columns of a chunk of the permutation argument.
+    ///
+    /// The columns of the argument are split into chunks of this many columns, the last
+    /// one possibly shorter, and the proof commits to a permutation product polynomial
+    /// `z_i` for each chunk. The products of consecutive chunks are linked at the last
+    /// usable row `u` (the one before the blinding rows):
+    /// - `z_0(\omega^0) = 1`,
+    /// - `z_i(\omega^0) = z_{i-1}(\omega^u)` for each chunk `i > 0`,
+    /// - `z_l(\omega^u)` is 0 or 1 for the last chunk `l`.
+    ///
+    /// The product constraint of a chunk has degree `2 + chunk_len`, so this is the degree
+    /// of the constraint system minus 2.
+    pub fn permutation_chunk_len(&self) -> usize {
+        self.cs_degree - 2
+    }
+
+    /// Returns the columns of each chunk of the permutation argument, in the order of the
+    /// commitments to their permutation product polynomials in proofs.
+    ///
+    /// See [`Self::permutation_chunk_len`].
+    pub fn permutation_chunks(&self) -> impl Iterator<Item = &[Column<Any>]> + '_ {
+        self.cs
+            .permutation
+            .columns
+            .chunks(self.permutation_chunk_len())
+    }
+
     /// Returns `ConstraintSystem`
     pub fn cs(&self) -> &ConstraintSystem<C::Scalar> {
         &self.cs
@@ -331,13 +622,64 @@ pub struct ProvingKey<C: CurveAffine> {
     l0: Polynomial<C::Scalar, ExtendedLagrangeCoeff>,
     l_last: Polynomial<C::Scalar, ExtendedLagrangeCoeff>,
     l_active_row: Polynomial<C::Scalar, ExtendedLagrangeCoeff>,
-    fixed_values: Vec<Polynomial<C::Scalar, LagrangeCoeff>>,
-    fixed_polys: Vec<Polynomial<C::Scalar, Coeff>>,
-    fixed_cosets: Vec<Polynomial<C::Scalar, ExtendedLagrangeCoeff>>,
+    fixed: Vec<FixedColumn<C::Scalar>>,
     permutation: permutation::ProvingKey<C>,
     ev: Evaluator<C>,
 }
 
+/// The values of a fixed column, with its polynomial in coefficient form and its
+/// evaluations over the extended domain. The polynomials are shared by the keys generated
+/// with the same [`keygen::TableRegistry`].
+#[derive(Clone, Debug)]
+pub(crate) struct FixedPolynomials<F> {
+    pub(crate) values: Polynomial<F, LagrangeCoeff>,
+    pub(crate) poly: Arc<Polynomial<F, Coeff>>,
+    pub(crate) coset: Arc<Polynomial<F, ExtendedLagrangeCoeff>>,
+}
+
+impl<F: WithSmallOrderMulGroup<3>> FixedPolynomials<F> {
+    /// Computes the polynomials of the column `values` with `fft`.
+    pub(crate) fn new(
+        domain: &EvaluationDomain<F>,
+        fft: &dyn FftAccel<F>,
+        values: Polynomial<F, LagrangeCoeff>,
+    ) -> Self {
+        let poly = domain.lagrange_to_coeff_with_engine(fft, values.clone());
+        let coset = domain.coeff_to_extended_with_engine(fft, poly.clone());
+        Self {
+            values,
+            poly: Arc::new(poly),
+            coset: Arc::new(coset),
+        }
+    }
+}
+
+/// A fixed column of a proving key.
+#[derive(Clone, Debug)]
+pub(crate) enum FixedColumn<F> {
+    Dense(FixedPolynomials<F>),
+    /// A column with few non-zero values, such as a selector, which is kept as its
+    /// non-zero values and whose polynomials are computed for each proof.
+    Sparse {
+        rows: Vec<usize>,
+        values: Vec<F>,
+    },
+}
+
+impl<F: WithSmallOrderMulGroup<3>> FixedColumn<F> {
+    /// Keeps the column `values` as its non-zero values if it is sparse, and otherwise
+    /// computes its polynomials with `polynomials`.
+    pub(crate) fn new(
+        values: Vec<F>,
+        polynomials: impl FnOnce(Vec<F>) -> FixedPolynomials<F>,
+    ) -> Self {
+        match sparse_entries(&values) {
+            Some((rows, values)) => FixedColumn::Sparse { rows, values },
+            None => FixedColumn::Dense(polynomials(values)),
+        }
+    }
+}
+
 impl<C: CurveAffine> ProvingKey<C>
 where
     C::Scalar: FromUniformBytes<64>,
@@ -347,22 +689,157 @@ where
         &self.vk
     }
 
-    /// Gets the total number of bytes in the serialization of `self`
+    /// Sets how the prover evaluates the custom gates, compiling them to native code for
+    /// [`GateEvaluation::Jit`] and [`GateEvaluation::CrossCheck`]. Keys are created and
+    /// read with [`GateEvaluation::Interpreter`].
+    ///
+    /// Returns an error if the gates cannot be compiled for the host.
+    #[cfg(feature = "jit")]
+    pub fn set_gate_evaluation(&mut self, evaluation: GateEvaluation) -> Result<(), Error> {
+        self.ev.set_gate_evaluation(evaluation)
+    }
+
+    /// Replaces the values of the fixed column `table`, such as a lookup table whose
+    /// contents changed but not its structure, recomputing only the polynomials and the
+    /// commitment of the column instead of the whole key.
+    ///
+    /// The representative of the verifying key in transcripts changes with the
+    /// commitment, so proofs created with the updated key must be verified with its
+    /// updated verifying key. The column of a [`TableColumn`] is given by
+    /// [`TableColumn::inner`].
+    ///
+    /// Returns an error if `table` isn't a fixed column of the circuit, if it holds
+    /// selectors, or if `values` doesn't have a value for each row.
+    ///
+    /// [`TableColumn`]: halo2_common::plonk::TableColumn
+    /// [`TableColumn::inner`]: halo2_common::plonk::TableColumn::inner
+    pub fn update_fixed_table<'params, P: Params<'params, C>>(
+        &mut self,
+        params: &P,
+        table: Column<Fixed>,
+        values: Vec<C::Scalar>,
+    ) -> Result<(), Error> {
+        self.update_fixed_table_with_engine(&PlonkEngine::default(), params, table, values)
+    }
+
+    /// Replaces the values of the fixed column `table` like [`Self::update_fixed_table`],
+    /// computing the multiscalar multiplication and FFTs with `engine`.
+    pub fn update_fixed_table_with_engine<'params, P: Params<'params, C>>(
+        &mut self,
+        engine: &PlonkEngine<C>,
+        params: &P,
+        table: Column<Fixed>,
+        values: Vec<C::Scalar>,
+    ) -> Result<(), Error> {
+        let index = table.index();
+        if index >= self.fixed.len() || self.vk.cs.selector_map.contains(&table) {
+            return Err(Error::Other(format!(
+                "fixed column {index} is not a table of the circuit"
+            )));
+        }
+        let n = 1usize << self.vk.domain.k();
+        if values.len() != n || params.n() as usize != n {
+            return Err(Error::Other(format!(
+                "the table has {} values and the params {} rows, but the circuit has {n} rows",
+                values.len(),
+                params.n()
+            )));
+        }
+
+        let domain = &self.vk.domain;
+        let fft = &*engine.fft_backend;
+        let commitment = keygen::commit_fixed_column(params, engine, &values);
+        let column = FixedColumn::new(values, |values| {
+            FixedPolynomials::new(domain, fft, Polynomial::new_lagrange_from_vec(values))
+        });
+        self.fixed[index] = column;
+
+        self.vk.fixed_commitments[index] = commitment;
+        self.vk.transcript_repr = self.vk.compute_transcript_repr();
+        Ok(())
+    }
+
+    /// Computes the polynomials of the sparse fixed columns, which the key keeps as their
+    /// non-zero values, with the FFTs of `engine`.
+    ///
+    /// The polynomials aren't kept, to keep the memory of the key, so each proof pays two
+    /// FFTs per sparse column: one over the rows and one over the extended domain. The
+    /// `sparse_fixed` benchmark of `halo2_proofs` compares it with dense columns.
+    pub(crate) fn expand_sparse_fixed(
+        &self,
+        engine: &PlonkEngine<C>,
+    ) -> Vec<Option<FixedPolynomials<C::Scalar>>> {
+        self.fixed
+            .iter()
+            .map(|column| match column {
+                FixedColumn::Dense(_) => None,
+                FixedColumn::Sparse { rows, values } => {
+                    let mut column = self.vk.domain.empty_lagrange();
+                    for (row, value) in rows.iter().zip(values) {
+                        column[*row] = *value;
+                    }
+                    let fft = &*engine.fft_backend;
+                    Some(FixedPolynomials::new(&self.vk.domain, fft, column))
+                }
+            })
+            .collect()
+    }
+
+    /// Gets the polynomials of the fixed columns, taking those of the sparse columns from
+    /// `expanded`, as returned by [`Self::expand_sparse_fixed`].
+    ///
+    /// Returns an error if `expanded` misses a sparse column.
+    pub(crate) fn fixed_polynomials<'a>(
+        &'a self,
+        expanded: &'a [Option<FixedPolynomials<C::Scalar>>],
+    ) -> Result<Vec<&'a FixedPolynomials<C::Scalar>>, Error> {
+        self.fixed
+            .iter()
+            .enumerate()
+            .map(|(index, column)| match column {
+                FixedColumn::Dense(polynomials) => Ok(polynomials),
+                FixedColumn::Sparse { .. } => {
+                    expanded.get(index).and_then(Option::as_ref).ok_or_else(|| {
+                        Error::Other(format!("the sparse fixed column {index} isn't expanded"))
+                    })
+                }
+            })
+            .collect()
+    }
+
+    /// Gets the number of bytes in the serialization of `self`, up to the per-column tags
+    /// of the fixed columns when none of them is sparse
     fn bytes_length(&self, format: SerdeFormat) -> usize
     where
         C: SerdeCurveAffine,
     {
         let scalar_len = C::Scalar::default().to_repr().as_ref().len();
+        let fixed_len: usize = self
+            .fixed
+            .iter()
+            .map(|column| match column {
+                FixedColumn::Dense(polynomials) => {
+                    13 + scalar_len
+                        * (polynomials.values.len()
+                            + polynomials.poly.len()
+                            + polynomials.coset.len())
+                }
+                FixedColumn::Sparse { rows, .. } => 5 + rows.len() * (4 + scalar_len),
+            })
+            .sum();
         self.vk.bytes_length(format)
             + 12
             + scalar_len * (self.l0.len() + self.l_last.len() + self.l_active_row.len())
-            + polynomial_slice_byte_length(&self.fixed_values)
-            + polynomial_slice_byte_length(&self.fixed_polys)
-            + polynomial_slice_byte_length(&self.fixed_cosets)
+            + 8
+            + fixed_len
             + self.permutation.bytes_length()
     }
 }
 
+/// Marks proving keys storing some fixed columns by their non-zero values. It takes the
+/// place of the number of fixed columns, so keys without sparse columns keep their layout.
+const SPARSE_FIXED_MAGIC: u32 = u32::MAX;
+
 impl<C: SerdeCurveAffine> ProvingKey<C>
 where
     C::Scalar: SerdePrimeField + FromUniformBytes<64>,
@@ -377,15 +854,89 @@ where
     /// Writes a field element into raw bytes in its internal Montgomery representation,
     /// WITHOUT performing the expensive Montgomery reduction.
     /// Does so by first writing the verifying key and then serializing the rest of the data (in the form of field polynomials)
+    ///
+    /// Sparse fixed columns, such as selectors, are written as their non-zero values only,
+    /// as the key keeps them in memory, and their polynomials are computed for each proof.
     pub fn write<W: io::Write>(&self, writer: &mut W, format: SerdeFormat) -> io::Result<()> {
-        self.vk.write(writer, format)?;
+        self.write_sections(writer, format, false)
+    }
+
+    /// Writes a proving key to a buffer like [`Self::write`], with a BLAKE2b checksum
+    /// after the verifying key, the Lagrange polynomials, the fixed columns and the
+    /// permutation, which [`Self::read`] checks. A corrupted key is then detected even
+    /// when it is read with [`SerdeFormat::RawBytesUnchecked`].
+    pub fn write_with_checksums<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+    ) -> io::Result<()> {
+        self.write_sections(writer, format, true)
+    }
+
+    fn write_sections<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+        checksums: bool,
+    ) -> io::Result<()> {
+        self.vk.write_sections(writer, format, checksums)?;
+        let writer = &mut ChecksumWriter::new(writer, checksums);
         self.l0.write(writer, format)?;
         self.l_last.write(writer, format)?;
         self.l_active_row.write(writer, format)?;
-        write_polynomial_slice(&self.fixed_values, writer, format)?;
-        write_polynomial_slice(&self.fixed_polys, writer, format)?;
-        write_polynomial_slice(&self.fixed_cosets, writer, format)?;
+        writer.end_section()?;
+        self.write_fixed(writer, format)?;
+        writer.end_section()?;
         self.permutation.write(writer, format)?;
+        writer.end_section()
+    }
+
+    fn write_fixed<W: io::Write>(&self, writer: &mut W, format: SerdeFormat) -> io::Result<()> {
+        let len = (self.fixed.len() as u32).to_be_bytes();
+        let dense: Option<Vec<_>> = self
+            .fixed
+            .iter()
+            .map(|column| match column {
+                FixedColumn::Dense(polynomials) => Some(polynomials),
+                FixedColumn::Sparse { .. } => None,
+            })
+            .collect();
+        if let Some(dense) = dense {
+            writer.write_all(&len)?;
+            for polynomials in dense.iter() {
+                polynomials.values.write(writer, format)?;
+            }
+            writer.write_all(&len)?;
+            for polynomials in dense.iter() {
+                polynomials.poly.write(writer, format)?;
+            }
+            writer.write_all(&len)?;
+            for polynomials in dense.iter() {
+                polynomials.coset.write(writer, format)?;
+            }
+            return Ok(());
+        }
+
+        writer.write_all(&SPARSE_FIXED_MAGIC.to_be_bytes())?;
+        writer.write_all(&len)?;
+        for column in self.fixed.iter() {
+            match column {
+                FixedColumn::Sparse { rows, values } => {
+                    writer.write_all(&[1])?;
+                    writer.write_all(&(rows.len() as u32).to_be_bytes())?;
+                    for (row, value) in rows.iter().zip(values) {
+                        writer.write_all(&(*row as u32).to_be_bytes())?;
+                        value.write(writer, format)?;
+                    }
+                }
+                FixedColumn::Dense(polynomials) => {
+                    writer.write_all(&[0])?;
+                    polynomials.values.write(writer, format)?;
+                    polynomials.poly.write(writer, format)?;
+                    polynomials.coset.write(writer, format)?;
+                }
+            }
+        }
         Ok(())
     }
 
@@ -400,38 +951,178 @@ where
     /// Checks that field elements are less than modulus, and then checks that the point is on the curve.
     /// - `RawBytesUnchecked`: Reads an uncompressed curve element with coordinates in Montgomery form;
     /// does not perform any checks
+    ///
+    /// The checksums of a key written by [`Self::write_with_checksums`] are checked.
     pub fn read<R: io::Read, ConcreteCircuit: Circuit<C::Scalar>>(
         reader: &mut R,
         format: SerdeFormat,
         #[cfg(feature = "circuit-params")] params: ConcreteCircuit::Params,
     ) -> io::Result<Self> {
-        let vk = VerifyingKey::<C>::read::<R, ConcreteCircuit>(
+        let (vk, checksums) = VerifyingKey::<C>::read_sections::<R, ConcreteCircuit>(
             reader,
             format,
             #[cfg(feature = "circuit-params")]
             params,
         )?;
+        Self::read_with_vk(vk, reader, format, checksums)
+    }
+
+    /// Reads a proving key from a buffer, like [`Self::read`], for a circuit given by the
+    /// constraint system of a [`CompiledCircuitV2`] instead of a `Circuit`.
+    ///
+    /// The key must have been generated from a circuit with the same constraint system,
+    /// for example with [`keygen::keygen_pk_v2`].
+    ///
+    /// [`CompiledCircuitV2`]: halo2_middleware::circuit::CompiledCircuitV2
+    pub fn read_v2<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        cs: &ConstraintSystemV2Backend<C::Scalar>,
+    ) -> io::Result<Self> {
+        let (vk, checksums) = VerifyingKey::<C>::read_v2_sections(reader, format, cs)?;
+        Self::read_with_vk(vk, reader, format, checksums)
+    }
+
+    fn read_with_vk<R: io::Read>(
+        vk: VerifyingKey<C>,
+        reader: &mut R,
+        format: SerdeFormat,
+        checksums: bool,
+    ) -> io::Result<Self> {
+        let reader = &mut ChecksumReader::new(reader, checksums);
         let l0 = Polynomial::read(reader, format)?;
         let l_last = Polynomial::read(reader, format)?;
         let l_active_row = Polynomial::read(reader, format)?;
-        let fixed_values = read_polynomial_vec(reader, format)?;
-        let fixed_polys = read_polynomial_vec(reader, format)?;
-        let fixed_cosets = read_polynomial_vec(reader, format)?;
+        reader.end_section()?;
+        let fixed = Self::read_fixed(&vk, reader, format)?;
+        reader.end_section()?;
         let permutation = permutation::ProvingKey::read(reader, format)?;
+        reader.end_section()?;
         let ev = Evaluator::new(vk.cs());
         Ok(Self {
             vk,
             l0,
             l_last,
             l_active_row,
-            fixed_values,
-            fixed_polys,
-            fixed_cosets,
+            fixed,
             permutation,
             ev,
         })
     }
 
+    /// Reads the fixed columns, checking that there is one for each fixed commitment of
+    /// `vk` and that they have a value for each row. The columns of a key written before
+    /// sparse columns were kept as their non-zero values are made sparse.
+    fn read_fixed<R: io::Read>(
+        vk: &VerifyingKey<C>,
+        reader: &mut R,
+        format: SerdeFormat,
+    ) -> io::Result<Vec<FixedColumn<C::Scalar>>> {
+        fn read_len<R: io::Read>(reader: &mut R) -> io::Result<u32> {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len)?;
+            Ok(u32::from_be_bytes(len))
+        }
+        fn read_poly<R: io::Read, F: SerdePrimeField, B>(
+            reader: &mut R,
+            format: SerdeFormat,
+            len: usize,
+        ) -> io::Result<Polynomial<F, B>> {
+            let poly = Polynomial::read(reader, format)?;
+            if poly.len() != len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "fixed column of unexpected length",
+                ));
+            }
+            Ok(poly)
+        }
+
+        let n = 1usize << vk.domain.k();
+        let extended_len = vk.domain.extended_len();
+        // The lengths are not trusted to allocate memory: the columns are read one by one.
+        let check_len = |len: u32| {
+            if len as usize == vk.fixed_commitments.len() {
+                Ok(())
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected number of fixed columns",
+                ))
+            }
+        };
+
+        let len = read_len(reader)?;
+        if len != SPARSE_FIXED_MAGIC {
+            check_len(len)?;
+            let mut fixed_values: Vec<Polynomial<_, LagrangeCoeff>> = vec![];
+            for _ in 0..len {
+                fixed_values.push(read_poly(reader, format, n)?);
+            }
+            check_len(read_len(reader)?)?;
+            let mut fixed_polys: Vec<Polynomial<_, Coeff>> = vec![];
+            for _ in 0..len {
+                fixed_polys.push(read_poly(reader, format, n)?);
+            }
+            check_len(read_len(reader)?)?;
+            let mut fixed = vec![];
+            for (values, poly) in fixed_values.into_iter().zip(fixed_polys) {
+                let coset = read_poly(reader, format, extended_len)?;
+                fixed.push(FixedColumn::new(values.values, |values| FixedPolynomials {
+                    values: Polynomial::new_lagrange_from_vec(values),
+                    poly: Arc::new(poly),
+                    coset: Arc::new(coset),
+                }));
+            }
+            return Ok(fixed);
+        }
+
+        let len = read_len(reader)?;
+        check_len(len)?;
+        let mut fixed = vec![];
+        for _ in 0..len {
+            let mut tag = [0u8; 1];
+            reader.read_exact(&mut tag)?;
+            match tag[0] {
+                0 => fixed.push(FixedColumn::Dense(FixedPolynomials {
+                    values: read_poly(reader, format, n)?,
+                    poly: Arc::new(read_poly(reader, format, n)?),
+                    coset: Arc::new(read_poly(reader, format, extended_len)?),
+                })),
+                1 => {
+                    let entries = read_len(reader)? as usize;
+                    if entries > n / SPARSE_COLUMN_DENSITY {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "too many values in a sparse fixed column",
+                        ));
+                    }
+                    let mut rows = vec![];
+                    let mut values = vec![];
+                    for _ in 0..entries {
+                        let row = read_len(reader)? as usize;
+                        if row >= n {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                "sparse fixed column row out of range",
+                            ));
+                        }
+                        rows.push(row);
+                        values.push(C::Scalar::read(reader, format)?);
+                    }
+                    fixed.push(FixedColumn::Sparse { rows, values });
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "unexpected fixed column tag",
+                    ))
+                }
+            }
+        }
+        Ok(fixed)
+    }
+
     /// Writes a proving key to a vector of bytes using [`Self::write`].
     pub fn to_bytes(&self, format: SerdeFormat) -> Vec<u8> {
         let mut bytes = Vec::<u8>::with_capacity(self.bytes_length(format));
@@ -452,6 +1143,15 @@ where
             params,
         )
     }
+
+    /// Reads a proving key from a slice of bytes using [`Self::read_v2`].
+    pub fn from_bytes_v2(
+        mut bytes: &[u8],
+        format: SerdeFormat,
+        cs: &ConstraintSystemV2Backend<C::Scalar>,
+    ) -> io::Result<Self> {
+        Self::read_v2(&mut bytes, format, cs)
+    }
 }
 
 impl<C: CurveAffine> VerifyingKey<C> {
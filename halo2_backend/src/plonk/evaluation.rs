@@ -2,25 +2,131 @@
 //! - Evaluates the h polynomial: Evaluator::new(ConstraintSystem).evaluate_h(...)
 //! - Evaluates an Expression using Lagrange basis
 
+use crate::helpers::{wipe_if_hardened, Wipe, Wiped};
 use crate::multicore;
-use crate::plonk::{lookup, permutation, ProvingKey};
+use crate::plonk::{lookup, permutation, Error, ProvingKey};
 use crate::poly::{Basis, LagrangeBasis};
+use crate::zal::PlonkEngine;
 use crate::{
     arithmetic::{parallelize, CurveAffine},
     poly::{Coeff, ExtendedLagrangeCoeff, Polynomial},
 };
 use group::ff::{Field, PrimeField, WithSmallOrderMulGroup};
+use halo2_common::plonk::circuit::Column;
 use halo2_common::plonk::{ConstraintSystem, Expression};
-use halo2_middleware::circuit::Any;
+use halo2_middleware::circuit::{Any, Subdomain};
 use halo2_middleware::poly::Rotation;
 
 use super::shuffle;
 
+#[cfg(feature = "jit")]
+mod jit;
+#[cfg(feature = "jit")]
+pub use jit::GateEvaluation;
+
 /// Return the index in the polynomial of size `isize` after rotation `rot`.
 fn get_rotation_idx(idx: usize, rot: i32, rot_scale: i32, isize: i32) -> usize {
     (((idx as i32) + (rot * rot_scale)).rem_euclid(isize)) as usize
 }
 
+/// How the prover stores the advice columns over the extended domain while it evaluates
+/// the quotient polynomial `h(X)`.
+///
+/// The constraints are evaluated row by row, and each row reads the advice columns
+/// queried by the gates. With [`WitnessLayout::ColumnMajor`], the columns are stored
+/// separately, so each row of a wide circuit reads from as many distant places in memory
+/// as it queries columns. [`WitnessLayout::RowMajor`] transposes the columns once, after
+/// the last phase, so that the values of a row are next to each other, at the cost of
+/// the time of the transposition.
+///
+/// The proof doesn't depend on the layout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WitnessLayout {
+    /// Each advice column is stored separately.
+    #[default]
+    ColumnMajor,
+    /// The advice values of each row are stored together.
+    RowMajor,
+}
+
+/// Advice values read by the evaluator.
+trait AdviceValues<F> {
+    /// Returns the value of the advice column `column` at `row`.
+    fn value(&self, column: usize, row: usize) -> F;
+}
+
+impl<F: Field, B: Basis> AdviceValues<F> for [Polynomial<F, B>] {
+    #[inline]
+    fn value(&self, column: usize, row: usize) -> F {
+        self[column][row]
+    }
+}
+
+impl<F: Field, B: Basis> AdviceValues<F> for Vec<Polynomial<F, B>> {
+    #[inline]
+    fn value(&self, column: usize, row: usize) -> F {
+        self[column][row]
+    }
+}
+
+/// The advice columns of a circuit over the extended domain, in a [`WitnessLayout`].
+enum AdviceCosets<F: Field> {
+    ColumnMajor(Vec<Polynomial<F, ExtendedLagrangeCoeff>>),
+    /// The value of column `c` at row `r` is `values[r * num_columns + c]`.
+    RowMajor {
+        num_columns: usize,
+        values: Vec<F>,
+    },
+}
+
+impl<F: Field> AdviceCosets<F> {
+    fn new(mut columns: Vec<Polynomial<F, ExtendedLagrangeCoeff>>, layout: WitnessLayout) -> Self {
+        match layout {
+            WitnessLayout::ColumnMajor => AdviceCosets::ColumnMajor(columns),
+            WitnessLayout::RowMajor => {
+                let num_columns = columns.len();
+                let num_rows = columns.first().map_or(0, |column| column.len());
+                let mut values = vec![F::ZERO; num_rows * num_columns];
+                if num_columns > 0 {
+                    parallelize(&mut values, |values, start| {
+                        for (i, value) in values.iter_mut().enumerate() {
+                            let idx = start + i;
+                            *value = columns[idx % num_columns][idx / num_columns];
+                        }
+                    });
+                }
+                wipe_if_hardened(&mut columns);
+                AdviceCosets::RowMajor {
+                    num_columns,
+                    values,
+                }
+            }
+        }
+    }
+}
+
+impl<F: Field> AdviceValues<F> for AdviceCosets<F> {
+    #[inline]
+    fn value(&self, column: usize, row: usize) -> F {
+        match self {
+            AdviceCosets::ColumnMajor(columns) => columns[column][row],
+            AdviceCosets::RowMajor {
+                num_columns,
+                values,
+            } => values[row * num_columns + column],
+        }
+    }
+}
+
+impl<F: Field> Wipe for AdviceCosets<F> {
+    fn wipe(&mut self) {
+        match self {
+            AdviceCosets::ColumnMajor(columns) => columns.wipe(),
+            AdviceCosets::RowMajor { values, .. } => halo2_common::helpers::wipe(values, F::ZERO),
+        }
+    }
+}
+
 /// Value used in [`Calculation`]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd)]
 enum ValueSource {
@@ -34,6 +140,8 @@ enum ValueSource {
     Advice(usize, usize),
     /// This is an instance (external) column
     Instance(usize, usize),
+    /// This is the selector of a subdomain
+    Subdomain(usize, usize),
     /// This is a challenge
     Challenge(usize),
     /// beta
@@ -57,14 +165,15 @@ impl Default for ValueSource {
 impl ValueSource {
     /// Get the value for this source
     #[allow(clippy::too_many_arguments)]
-    fn get<F: Field, B: Basis>(
+    fn get<F: Field, B: Basis, A: AdviceValues<F> + ?Sized>(
         &self,
         rotations: &[usize],
         constants: &[F],
         intermediates: &[F],
-        fixed_values: &[Polynomial<F, B>],
-        advice_values: &[Polynomial<F, B>],
+        fixed_values: &[&Polynomial<F, B>],
+        advice_values: &A,
         instance_values: &[Polynomial<F, B>],
+        subdomain_values: &[Polynomial<F, B>],
         challenges: &[F],
         beta: &F,
         gamma: &F,
@@ -79,11 +188,14 @@ impl ValueSource {
                 fixed_values[*column_index][rotations[*rotation]]
             }
             ValueSource::Advice(column_index, rotation) => {
-                advice_values[*column_index][rotations[*rotation]]
+                advice_values.value(*column_index, rotations[*rotation])
             }
             ValueSource::Instance(column_index, rotation) => {
                 instance_values[*column_index][rotations[*rotation]]
             }
+            ValueSource::Subdomain(index, rotation) => {
+                subdomain_values[*index][rotations[*rotation]]
+            }
             ValueSource::Challenge(index) => challenges[*index],
             ValueSource::Beta() => *beta,
             ValueSource::Gamma() => *gamma,
@@ -118,14 +230,15 @@ enum Calculation {
 impl Calculation {
     /// Get the resulting value of this calculation
     #[allow(clippy::too_many_arguments)]
-    fn evaluate<F: Field, B: Basis>(
+    fn evaluate<F: Field, B: Basis, A: AdviceValues<F> + ?Sized>(
         &self,
         rotations: &[usize],
         constants: &[F],
         intermediates: &[F],
-        fixed_values: &[Polynomial<F, B>],
-        advice_values: &[Polynomial<F, B>],
+        fixed_values: &[&Polynomial<F, B>],
+        advice_values: &A,
         instance_values: &[Polynomial<F, B>],
+        subdomain_values: &[Polynomial<F, B>],
         challenges: &[F],
         beta: &F,
         gamma: &F,
@@ -141,6 +254,7 @@ impl Calculation {
                 fixed_values,
                 advice_values,
                 instance_values,
+                subdomain_values,
                 challenges,
                 beta,
                 gamma,
@@ -174,10 +288,15 @@ impl Calculation {
 pub struct Evaluator<C: CurveAffine> {
     ///  Custom gates evalution
     custom_gates: GraphEvaluator<C>,
+    ///  Subdomains of the custom gates, whose selectors are computed by `evaluate_h`
+    subdomains: Vec<Subdomain>,
     ///  Lookups evalution
     lookups: Vec<GraphEvaluator<C>>,
     ///  Shuffle evalution
     shuffles: Vec<GraphEvaluator<C>>,
+    ///  Custom gates compiled to native code, if they aren't interpreted
+    #[cfg(feature = "jit")]
+    jit: Option<jit::JitGates<C::ScalarExt>>,
 }
 
 /// The purpose of GraphEvaluator to is to collect a set of computations and compute them by making a graph of
@@ -232,11 +351,32 @@ impl<C: CurveAffine> Evaluator<C> {
         // Custom gates
         let mut parts = Vec::new();
         for gate in cs.gates.iter() {
-            parts.extend(
-                gate.polynomials()
-                    .iter()
-                    .map(|poly| ev.custom_gates.add_expression(poly)),
-            );
+            let polys = gate.polynomials().iter();
+            match gate.subdomain() {
+                None => parts.extend(polys.map(|poly| ev.custom_gates.add_expression(poly))),
+                Some(subdomain) => {
+                    // The constraints of a gate on a subdomain are multiplied by its selector.
+                    let index = match ev.subdomains.iter().position(|s| *s == subdomain) {
+                        Some(index) => index,
+                        None => {
+                            ev.subdomains.push(subdomain);
+                            ev.subdomains.len() - 1
+                        }
+                    };
+                    let rot_idx = ev.custom_gates.add_rotation(&Rotation::cur());
+                    let selector = ev.custom_gates.add_calculation(Calculation::Store(
+                        ValueSource::Subdomain(index, rot_idx),
+                    ));
+                    for poly in polys {
+                        let poly = ev.custom_gates.add_expression(poly);
+                        parts.push(ev.custom_gates.add_calculation(if poly <= selector {
+                            Calculation::Mul(poly, selector)
+                        } else {
+                            Calculation::Mul(selector, poly)
+                        }));
+                    }
+                }
+            }
         }
         ev.custom_gates.add_calculation(Calculation::Horner(
             ValueSource::PreviousValue(),
@@ -314,11 +454,24 @@ impl<C: CurveAffine> Evaluator<C> {
         ev
     }
 
+    /// Sets how the custom gates are evaluated, compiling them to native code if needed.
+    #[cfg(feature = "jit")]
+    pub(in crate::plonk) fn set_gate_evaluation(
+        &mut self,
+        evaluation: GateEvaluation,
+    ) -> Result<(), Error> {
+        self.jit = jit::JitGates::new(&self.custom_gates, evaluation)?;
+        Ok(())
+    }
+
     /// Evaluate h poly
     #[allow(clippy::too_many_arguments)]
     pub(in crate::plonk) fn evaluate_h(
         &self,
         pk: &ProvingKey<C>,
+        engine: &PlonkEngine<C>,
+        layout: WitnessLayout,
+        fixed: &[&Polynomial<C::ScalarExt, ExtendedLagrangeCoeff>],
         advice_polys: &[&[Polynomial<C::ScalarExt, Coeff>]],
         instance_polys: &[&[Polynomial<C::ScalarExt, Coeff>]],
         challenges: &[C::ScalarExt],
@@ -329,11 +482,10 @@ impl<C: CurveAffine> Evaluator<C> {
         lookups: &[Vec<lookup::prover::Committed<C>>],
         shuffles: &[Vec<shuffle::prover::Committed<C>>],
         permutations: &[permutation::prover::Committed<C>],
-    ) -> Polynomial<C::ScalarExt, ExtendedLagrangeCoeff> {
+    ) -> Result<Polynomial<C::ScalarExt, ExtendedLagrangeCoeff>, Error> {
         let domain = &pk.vk.domain;
         let size = domain.extended_len();
         let rot_scale = 1 << (domain.extended_k() - domain.k());
-        let fixed = &pk.fixed_cosets[..];
         let extended_omega = domain.get_extended_omega();
         let isize = size as i32;
         let one = C::ScalarExt::ONE;
@@ -341,23 +493,37 @@ impl<C: CurveAffine> Evaluator<C> {
         let l_last = &pk.l_last;
         let l_active_row = &pk.l_active_row;
         let p = &pk.vk.cs.permutation;
+        let fft = &*engine.fft_backend;
 
-        // Calculate the advice and instance cosets
-        let advice: Vec<Vec<Polynomial<C::Scalar, ExtendedLagrangeCoeff>>> = advice_polys
+        // Calculate the selectors of the subdomains of the gates, which are zero on the
+        // unusable rows
+        let usable_rows = (1 << domain.k()) - (pk.vk.cs.blinding_factors() + 1);
+        let subdomains: Vec<_> = self
+            .subdomains
             .iter()
-            .map(|advice_polys| {
-                advice_polys
-                    .iter()
-                    .map(|poly| domain.coeff_to_extended(poly.clone()))
-                    .collect()
-            })
+            .map(|subdomain| domain.subdomain_selector_with_engine(fft, *subdomain, usable_rows))
             .collect();
+        let subdomains = &subdomains[..];
+
+        // Calculate the advice and instance cosets
+        let advice: Wiped<Vec<AdviceCosets<C::Scalar>>> = Wiped(
+            advice_polys
+                .iter()
+                .map(|advice_polys| {
+                    let cosets = advice_polys
+                        .iter()
+                        .map(|poly| domain.coeff_to_extended_with_engine(fft, poly.clone()))
+                        .collect();
+                    AdviceCosets::new(cosets, layout)
+                })
+                .collect(),
+        );
         let instance: Vec<Vec<Polynomial<C::Scalar, ExtendedLagrangeCoeff>>> = instance_polys
             .iter()
             .map(|instance_polys| {
                 instance_polys
                     .iter()
-                    .map(|poly| domain.coeff_to_extended(poly.clone()))
+                    .map(|poly| domain.coeff_to_extended_with_engine(fft, poly.clone()))
                     .collect()
             })
             .collect();
@@ -374,33 +540,33 @@ impl<C: CurveAffine> Evaluator<C> {
             .zip(permutations.iter())
         {
             // Custom gates
+            let chunk_size = (size + num_threads - 1) / num_threads;
+            let mut results: Vec<Result<(), Error>> =
+                values.chunks(chunk_size).map(|_| Ok(())).collect();
             multicore::scope(|scope| {
-                let chunk_size = (size + num_threads - 1) / num_threads;
-                for (thread_idx, values) in values.chunks_mut(chunk_size).enumerate() {
+                for ((thread_idx, values), result) in values
+                    .chunks_mut(chunk_size)
+                    .enumerate()
+                    .zip(results.iter_mut())
+                {
                     let start = thread_idx * chunk_size;
                     scope.spawn(move |_| {
-                        let mut eval_data = self.custom_gates.instance();
-                        for (i, value) in values.iter_mut().enumerate() {
-                            let idx = start + i;
-                            *value = self.custom_gates.evaluate(
-                                &mut eval_data,
-                                fixed,
-                                advice,
-                                instance,
-                                challenges,
-                                &beta,
-                                &gamma,
-                                &theta,
-                                &y,
-                                value,
-                                idx,
-                                rot_scale,
-                                isize,
-                            );
-                        }
+                        *result = self.evaluate_custom_gates(
+                            fixed,
+                            advice,
+                            instance,
+                            subdomains,
+                            challenges,
+                            [beta, gamma, theta, y],
+                            values,
+                            start,
+                            rot_scale,
+                            isize,
+                        );
                     });
                 }
             });
+            results.into_iter().collect::<Result<(), _>>()?;
 
             // Permutations
             let sets = &permutation.sets;
@@ -454,26 +620,22 @@ impl<C: CurveAffine> Evaluator<C> {
                             .zip(p.columns.chunks(chunk_len))
                             .zip(pk.permutation.cosets.chunks(chunk_len))
                         {
+                            let column_value = |column: &Column<Any>| match column.column_type() {
+                                Any::Advice(_) => advice.value(column.index(), idx),
+                                Any::Fixed => fixed[column.index()][idx],
+                                Any::Instance => instance[column.index()][idx],
+                            };
+
                             let mut left = set.permutation_product_coset[r_next];
-                            for (values, permutation) in columns
-                                .iter()
-                                .map(|&column| match column.column_type() {
-                                    Any::Advice(_) => &advice[column.index()],
-                                    Any::Fixed => &fixed[column.index()],
-                                    Any::Instance => &instance[column.index()],
-                                })
-                                .zip(cosets.iter())
+                            for (value, permutation) in
+                                columns.iter().map(column_value).zip(cosets.iter())
                             {
-                                left *= values[idx] + beta * permutation[idx] + gamma;
+                                left *= value + beta * permutation[idx] + gamma;
                             }
 
                             let mut right = set.permutation_product_coset[idx];
-                            for values in columns.iter().map(|&column| match column.column_type() {
-                                Any::Advice(_) => &advice[column.index()],
-                                Any::Fixed => &fixed[column.index()],
-                                Any::Instance => &instance[column.index()],
-                            }) {
-                                right *= values[idx] + current_delta + gamma;
+                            for value in columns.iter().map(column_value) {
+                                right *= value + current_delta + gamma;
                                 current_delta *= &C::Scalar::DELTA;
                             }
 
@@ -489,15 +651,14 @@ impl<C: CurveAffine> Evaluator<C> {
                 // Polynomials required for this lookup.
                 // Calculated here so these only have to be kept in memory for the short time
                 // they are actually needed.
-                let product_coset = pk.vk.domain.coeff_to_extended(lookup.product_poly.clone());
-                let permuted_input_coset = pk
-                    .vk
-                    .domain
-                    .coeff_to_extended(lookup.permuted_input_poly.clone());
-                let permuted_table_coset = pk
-                    .vk
-                    .domain
-                    .coeff_to_extended(lookup.permuted_table_poly.clone());
+                let product_coset =
+                    Wiped(domain.coeff_to_extended_with_engine(fft, lookup.product_poly.clone()));
+                let permuted_input_coset = Wiped(
+                    domain.coeff_to_extended_with_engine(fft, lookup.permuted_input_poly.clone()),
+                );
+                let permuted_table_coset = Wiped(
+                    domain.coeff_to_extended_with_engine(fft, lookup.permuted_table_poly.clone()),
+                );
 
                 // Lookup constraints
                 parallelize(&mut values, |values, start| {
@@ -511,6 +672,7 @@ impl<C: CurveAffine> Evaluator<C> {
                             fixed,
                             advice,
                             instance,
+                            &[],
                             challenges,
                             &beta,
                             &gamma,
@@ -561,7 +723,8 @@ impl<C: CurveAffine> Evaluator<C> {
 
             // Shuffle constraints
             for (n, shuffle) in shuffles.iter().enumerate() {
-                let product_coset = pk.vk.domain.coeff_to_extended(shuffle.product_poly.clone());
+                let product_coset =
+                    Wiped(domain.coeff_to_extended_with_engine(fft, shuffle.product_poly.clone()));
 
                 // Shuffle constraints
                 parallelize(&mut values, |values, start| {
@@ -577,6 +740,7 @@ impl<C: CurveAffine> Evaluator<C> {
                             fixed,
                             advice,
                             instance,
+                            &[],
                             challenges,
                             &beta,
                             &gamma,
@@ -593,6 +757,7 @@ impl<C: CurveAffine> Evaluator<C> {
                             fixed,
                             advice,
                             instance,
+                            &[],
                             challenges,
                             &beta,
                             &gamma,
@@ -621,7 +786,63 @@ impl<C: CurveAffine> Evaluator<C> {
                 });
             }
         }
-        values
+        Ok(values)
+    }
+
+    /// Evaluates the custom gates at the rows from `start` on, accumulating them into
+    /// `values`, with the compiled gates if there are some.
+    #[allow(clippy::too_many_arguments)]
+    fn evaluate_custom_gates(
+        &self,
+        fixed: &[&Polynomial<C::ScalarExt, ExtendedLagrangeCoeff>],
+        advice: &AdviceCosets<C::ScalarExt>,
+        instance: &[Polynomial<C::ScalarExt, ExtendedLagrangeCoeff>],
+        subdomains: &[Polynomial<C::ScalarExt, ExtendedLagrangeCoeff>],
+        challenges: &[C::ScalarExt],
+        [beta, gamma, theta, y]: [C::ScalarExt; 4],
+        values: &mut [C::ScalarExt],
+        start: usize,
+        rot_scale: i32,
+        isize: i32,
+    ) -> Result<(), Error> {
+        let mut eval_data = self.custom_gates.instance();
+        #[cfg(feature = "jit")]
+        if let Some(jit) = &self.jit {
+            return jit.evaluate(
+                &self.custom_gates,
+                &mut eval_data,
+                fixed,
+                advice,
+                instance,
+                subdomains,
+                challenges,
+                [beta, gamma, theta, y],
+                values,
+                start,
+                rot_scale,
+                isize,
+            );
+        }
+        for (i, value) in values.iter_mut().enumerate() {
+            let idx = start + i;
+            *value = self.custom_gates.evaluate(
+                &mut eval_data,
+                fixed,
+                advice,
+                instance,
+                subdomains,
+                challenges,
+                &beta,
+                &gamma,
+                &theta,
+                &y,
+                value,
+                idx,
+                rot_scale,
+                isize,
+            );
+        }
+        Ok(())
     }
 }
 
@@ -805,12 +1026,13 @@ impl<C: CurveAffine> GraphEvaluator<C> {
     ///     .intermediaries with the evaluation the calculation
     ///     .rotations with the indexes of the polinomials after rotations
     /// returns the value of last evaluation done.
-    fn evaluate<B: Basis>(
+    fn evaluate<B: Basis, A: AdviceValues<C::ScalarExt> + ?Sized>(
         &self,
         data: &mut EvaluationData<C>,
-        fixed: &[Polynomial<C::ScalarExt, B>],
-        advice: &[Polynomial<C::ScalarExt, B>],
+        fixed: &[&Polynomial<C::ScalarExt, B>],
+        advice: &A,
         instance: &[Polynomial<C::ScalarExt, B>],
+        subdomains: &[Polynomial<C::ScalarExt, B>],
         challenges: &[C::ScalarExt],
         beta: &C::ScalarExt,
         gamma: &C::ScalarExt,
@@ -835,6 +1057,7 @@ impl<C: CurveAffine> GraphEvaluator<C> {
                 fixed,
                 advice,
                 instance,
+                subdomains,
                 challenges,
                 beta,
                 gamma,
@@ -858,7 +1081,7 @@ pub fn evaluate<F: Field, B: LagrangeBasis>(
     expression: &Expression<F>,
     size: usize,
     rot_scale: i32,
-    fixed: &[Polynomial<F, B>],
+    fixed: &[&Polynomial<F, B>],
     advice: &[Polynomial<F, B>],
     instance: &[Polynomial<F, B>],
     challenges: &[F],
@@ -928,12 +1151,14 @@ mod test {
             unreachable!()
         }
 
+        let fixed = lagranges(&[&[2, 3], &[1002, 1003]]);
         let mut evaluation_data = gv.instance();
         let result = gv.evaluate(
             &mut evaluation_data,
-            &lagranges(&[&[2, 3], &[1002, 1003]]), // fixed
+            &fixed.iter().collect::<Vec<_>>(),     // fixed
             &lagranges(&[&[4, 5], &[1004, 1005]]), // advice
             &lagranges(&[&[6, 7], &[1006, 1007]]), // instance
+            &lagranges(&[&[15, 16]]),              // subdomains
             &[8u64.into(), 9u64.into()],           // challenges
             &Scalar::from_raw([10, 0, 0, 0]),      // beta
             &Scalar::from_raw([11, 0, 0, 0]),      // gamma
@@ -1012,6 +1237,8 @@ mod test {
         check_calc(Calculation::Store(ValueSource::Theta()), 12);
         check_calc(Calculation::Store(ValueSource::Y()), 13);
         check_calc(Calculation::Store(ValueSource::PreviousValue()), 14);
+        check_calc(Calculation::Store(ValueSource::Subdomain(0, 0)), 15);
+        check_calc(Calculation::Store(ValueSource::Subdomain(0, 1)), 16);
     }
 
     #[test]
@@ -0,0 +1,528 @@
+//! Native code for the evaluation of the custom gates, emitted with Cranelift from the
+//! calculations of their [`GraphEvaluator`].
+//!
+//! The emitted function evaluates the calculations of a row in order, like
+//! [`GraphEvaluator::evaluate`], writing the result of each one to its intermediate. The
+//! field arithmetic itself calls the implementation of the field: the native code only
+//! removes the dispatch of the interpreter on each calculation and each operand, which
+//! dominates the evaluation of circuits with many gates. The `gate_evaluation` benchmark
+//! of `halo2_proofs` compares the proving times of both.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::sync::Arc;
+
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, MemFlags, SigRef, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Linkage, Module};
+use group::ff::Field;
+
+use super::ValueSource;
+use super::{get_rotation_idx, AdviceCosets, Calculation, EvaluationData, GraphEvaluator};
+use crate::arithmetic::CurveAffine;
+use crate::plonk::Error;
+use crate::poly::{ExtendedLagrangeCoeff, Polynomial};
+
+/// How the prover evaluates the custom gates at each row of the extended domain, see
+/// [`ProvingKey::set_gate_evaluation`](crate::plonk::ProvingKey::set_gate_evaluation).
+///
+/// The proof doesn't depend on the evaluation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GateEvaluation {
+    /// The calculations of the gates are interpreted.
+    #[default]
+    Interpreter,
+    /// The calculations of the gates are compiled to native code.
+    Jit,
+    /// The calculations of the gates are compiled to native code, and also interpreted
+    /// at each row to check the compiled code, failing the proof if the results differ.
+    CrossCheck,
+}
+
+/// The addresses read by the emitted function. They are stored as integers so that the
+/// threads evaluating different rows can share the arrays of column addresses.
+#[repr(C)]
+#[allow(dead_code)] // The fields are only read by the emitted function.
+struct Context {
+    /// The address of the first value of each fixed column.
+    fixed: usize,
+    /// The address of the first value of each advice column.
+    advice: usize,
+    /// The number of bytes between the values of two rows of an advice column, which
+    /// depends on the [`WitnessLayout`](super::WitnessLayout).
+    advice_stride: usize,
+    /// The address of the first value of each instance column.
+    instance: usize,
+    /// The address of the first value of each subdomain selector.
+    subdomains: usize,
+    constants: usize,
+    challenges: usize,
+    /// `beta`, `gamma`, `theta`, `y` and the previous value.
+    scalars: usize,
+    /// The row of each rotation of the graph.
+    rotations: usize,
+    intermediates: usize,
+}
+
+// The index of each field of the `Context`.
+const FIXED: usize = 0;
+const ADVICE: usize = 1;
+const ADVICE_STRIDE: usize = 2;
+const INSTANCE: usize = 3;
+const SUBDOMAINS: usize = 4;
+const CONSTANTS: usize = 5;
+const CHALLENGES: usize = 6;
+const SCALARS: usize = 7;
+const ROTATIONS: usize = 8;
+const INTERMEDIATES: usize = 9;
+
+type Function = unsafe extern "C" fn(*const Context);
+type UnaryFn<F> = unsafe extern "C" fn(*mut F, *const F);
+type BinaryFn<F> = unsafe extern "C" fn(*mut F, *const F, *const F);
+
+// The field operations called by the emitted code. They read their operands before
+// writing the result, so `out` may alias an operand.
+unsafe extern "C" fn add<F: Field>(out: *mut F, a: *const F, b: *const F) {
+    *out = *a + *b;
+}
+
+unsafe extern "C" fn sub<F: Field>(out: *mut F, a: *const F, b: *const F) {
+    *out = *a - *b;
+}
+
+unsafe extern "C" fn mul<F: Field>(out: *mut F, a: *const F, b: *const F) {
+    *out = *a * *b;
+}
+
+unsafe extern "C" fn square<F: Field>(out: *mut F, a: *const F) {
+    *out = (*a).square();
+}
+
+unsafe extern "C" fn double<F: Field>(out: *mut F, a: *const F) {
+    *out = (*a).double();
+}
+
+unsafe extern "C" fn negate<F: Field>(out: *mut F, a: *const F) {
+    *out = -*a;
+}
+
+unsafe extern "C" fn store<F: Field>(out: *mut F, a: *const F) {
+    *out = *a;
+}
+
+/// The native code of a [`GraphEvaluator`] over the field `F`.
+pub(super) struct JitGraph<F: Field> {
+    /// Owns the memory of `function`, which is freed when the graph is dropped.
+    module: Option<JITModule>,
+    function: Function,
+    _field: PhantomData<fn(F)>,
+}
+
+// SAFETY: the module isn't modified once its definitions are finalized, and the emitted
+// function only accesses the memory of the context it's called with.
+unsafe impl<F: Field> Send for JitGraph<F> {}
+unsafe impl<F: Field> Sync for JitGraph<F> {}
+
+impl<F: Field> fmt::Debug for JitGraph<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JitGraph").finish_non_exhaustive()
+    }
+}
+
+impl<F: Field> Drop for JitGraph<F> {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            // SAFETY: `function` is only called through `self`.
+            unsafe { module.free_memory() }
+        }
+    }
+}
+
+impl<F: Field> JitGraph<F> {
+    /// Emits the native code of the calculations of `graph`, for the host.
+    fn compile<C: CurveAffine<ScalarExt = F>>(graph: &GraphEvaluator<C>) -> Result<Self, Error> {
+        let error = |e: &dyn fmt::Display| Error::Other(format!("cannot compile the gates: {e}"));
+        let mut flags = settings::builder();
+        flags.set("opt_level", "speed").map_err(|e| error(&e))?;
+        let isa = cranelift_native::builder()
+            .map_err(|e| error(&e))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| error(&e))?;
+        let mut module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+
+        let pointer = module.target_config().pointer_type();
+        let mut unary = module.make_signature();
+        unary.params.extend([AbiParam::new(pointer); 2]);
+        let mut binary = module.make_signature();
+        binary.params.extend([AbiParam::new(pointer); 3]);
+        let mut ctx = module.make_context();
+        ctx.func.signature.params.push(AbiParam::new(pointer));
+
+        let mut builder_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut builder_ctx);
+        let unary_signature = builder.import_signature(unary);
+        let binary_signature = builder.import_signature(binary);
+        let block = builder.create_block();
+        builder.append_block_params_for_function_params(block);
+        builder.switch_to_block(block);
+        builder.seal_block(block);
+        let context = builder.block_params(block)[0];
+        let mut emitter = Emitter {
+            builder,
+            context,
+            pointer,
+            unary_signature,
+            binary_signature,
+            size: size_of::<F>() as i64,
+        };
+        for calc in graph.calculations.iter() {
+            emitter.calculation::<F>(&calc.calculation, calc.target);
+        }
+        emitter.builder.ins().return_(&[]);
+        emitter.builder.finalize();
+
+        let id = module
+            .declare_function("evaluate", Linkage::Local, &ctx.func.signature)
+            .map_err(|e| error(&e))?;
+        module
+            .define_function(id, &mut ctx)
+            .map_err(|e| error(&e))?;
+        module.clear_context(&mut ctx);
+        module.finalize_definitions().map_err(|e| error(&e))?;
+        // SAFETY: the function was emitted with the signature of `Function`.
+        let function = unsafe {
+            std::mem::transmute::<*const u8, Function>(module.get_finalized_function(id))
+        };
+
+        Ok(JitGraph {
+            module: Some(module),
+            function,
+            _field: PhantomData,
+        })
+    }
+}
+
+/// Emits the calculations of a graph into the body of the function.
+struct Emitter<'a> {
+    builder: FunctionBuilder<'a>,
+    /// The address of the [`Context`].
+    context: Value,
+    pointer: types::Type,
+    unary_signature: SigRef,
+    binary_signature: SigRef,
+    /// The size of a field element.
+    size: i64,
+}
+
+impl Emitter<'_> {
+    /// Loads the address at `index` in the array at `array`.
+    fn load(&mut self, array: Value, index: usize) -> Value {
+        let offset = (index * size_of::<usize>()) as i32;
+        self.builder
+            .ins()
+            .load(self.pointer, MemFlags::trusted(), array, offset)
+    }
+
+    /// Returns the address of the element `index` of the array of field elements at the
+    /// field `field` of the context.
+    fn element(&mut self, field: usize, index: usize) -> Value {
+        let array = self.load(self.context, field);
+        self.builder.ins().iadd_imm(array, index as i64 * self.size)
+    }
+
+    /// Returns the address of the value of `column` at the row of `rotation`, in the
+    /// columns at the field `field` of the context.
+    fn cell(&mut self, field: usize, column: usize, rotation: usize) -> Value {
+        let columns = self.load(self.context, field);
+        let column = self.load(columns, column);
+        let rotations = self.load(self.context, ROTATIONS);
+        let row = self.load(rotations, rotation);
+        let offset = if field == ADVICE {
+            let stride = self.load(self.context, ADVICE_STRIDE);
+            self.builder.ins().imul(row, stride)
+        } else {
+            self.builder.ins().imul_imm(row, self.size)
+        };
+        self.builder.ins().iadd(column, offset)
+    }
+
+    /// Returns the address of the value of `source`.
+    fn source(&mut self, source: &ValueSource) -> Value {
+        match *source {
+            ValueSource::Constant(idx) => self.element(CONSTANTS, idx),
+            ValueSource::Intermediate(idx) => self.element(INTERMEDIATES, idx),
+            ValueSource::Fixed(column, rotation) => self.cell(FIXED, column, rotation),
+            ValueSource::Advice(column, rotation) => self.cell(ADVICE, column, rotation),
+            ValueSource::Instance(column, rotation) => self.cell(INSTANCE, column, rotation),
+            ValueSource::Subdomain(idx, rotation) => self.cell(SUBDOMAINS, idx, rotation),
+            ValueSource::Challenge(idx) => self.element(CHALLENGES, idx),
+            ValueSource::Beta() => self.element(SCALARS, 0),
+            ValueSource::Gamma() => self.element(SCALARS, 1),
+            ValueSource::Theta() => self.element(SCALARS, 2),
+            ValueSource::Y() => self.element(SCALARS, 3),
+            ValueSource::PreviousValue() => self.element(SCALARS, 4),
+        }
+    }
+
+    fn call(&mut self, signature: SigRef, function: usize, args: &[Value]) {
+        let callee = self.builder.ins().iconst(self.pointer, function as i64);
+        self.builder.ins().call_indirect(signature, callee, args);
+    }
+
+    fn unary<F: Field>(&mut self, function: UnaryFn<F>, out: Value, a: &ValueSource) {
+        let a = self.source(a);
+        self.call(self.unary_signature, function as usize, &[out, a]);
+    }
+
+    fn binary<F: Field>(
+        &mut self,
+        function: BinaryFn<F>,
+        out: Value,
+        a: &ValueSource,
+        b: &ValueSource,
+    ) {
+        let (a, b) = (self.source(a), self.source(b));
+        self.call(self.binary_signature, function as usize, &[out, a, b]);
+    }
+
+    /// Emits `calculation`, storing its result in the intermediate `target`.
+    fn calculation<F: Field>(&mut self, calculation: &Calculation, target: usize) {
+        let out = self.element(INTERMEDIATES, target);
+        match calculation {
+            Calculation::Add(a, b) => self.binary(add::<F>, out, a, b),
+            Calculation::Sub(a, b) => self.binary(sub::<F>, out, a, b),
+            Calculation::Mul(a, b) => self.binary(mul::<F>, out, a, b),
+            Calculation::Square(v) => self.unary(square::<F>, out, v),
+            Calculation::Double(v) => self.unary(double::<F>, out, v),
+            Calculation::Negate(v) => self.unary(negate::<F>, out, v),
+            Calculation::Horner(start_value, parts, factor) => {
+                // The parts are earlier intermediates, so accumulating in the target
+                // doesn't overwrite them.
+                self.unary(store::<F>, out, start_value);
+                let factor = self.source(factor);
+                for part in parts.iter() {
+                    let part = self.source(part);
+                    self.call(
+                        self.binary_signature,
+                        mul::<F> as BinaryFn<F> as usize,
+                        &[out, out, factor],
+                    );
+                    self.call(
+                        self.binary_signature,
+                        add::<F> as BinaryFn<F> as usize,
+                        &[out, out, part],
+                    );
+                }
+            }
+            Calculation::Store(v) => self.unary(store::<F>, out, v),
+        }
+    }
+}
+
+/// The custom gates of an [`Evaluator`](super::Evaluator) compiled to native code.
+#[derive(Clone, Debug)]
+pub(super) struct JitGates<F: Field> {
+    graph: Arc<JitGraph<F>>,
+    /// Whether each row is also interpreted, see [`GateEvaluation::CrossCheck`].
+    cross_check: bool,
+}
+
+impl<F: Field> JitGates<F> {
+    /// Compiles the custom gates of `graph` for `evaluation`, or returns `None` if they
+    /// are interpreted.
+    pub(super) fn new<C: CurveAffine<ScalarExt = F>>(
+        graph: &GraphEvaluator<C>,
+        evaluation: GateEvaluation,
+    ) -> Result<Option<Self>, Error> {
+        let cross_check = match evaluation {
+            GateEvaluation::Interpreter => return Ok(None),
+            GateEvaluation::Jit => false,
+            GateEvaluation::CrossCheck => true,
+        };
+        Ok(Some(JitGates {
+            graph: Arc::new(JitGraph::compile(graph)?),
+            cross_check,
+        }))
+    }
+
+    /// Evaluates the custom gates at the rows from `start` on, like the interpreter in
+    /// [`Evaluator::evaluate_h`](super::Evaluator::evaluate_h).
+    ///
+    /// Returns an error if the gates are cross-checked and the interpreter evaluates a row
+    /// differently.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn evaluate<C: CurveAffine<ScalarExt = F>>(
+        &self,
+        graph: &GraphEvaluator<C>,
+        data: &mut EvaluationData<C>,
+        fixed: &[&Polynomial<F, ExtendedLagrangeCoeff>],
+        advice: &AdviceCosets<F>,
+        instance: &[Polynomial<F, ExtendedLagrangeCoeff>],
+        subdomains: &[Polynomial<F, ExtendedLagrangeCoeff>],
+        challenges: &[F],
+        [beta, gamma, theta, y]: [F; 4],
+        values: &mut [F],
+        start: usize,
+        rot_scale: i32,
+        isize: i32,
+    ) -> Result<(), Error> {
+        let addresses = |columns: &[Polynomial<F, ExtendedLagrangeCoeff>]| -> Vec<usize> {
+            columns
+                .iter()
+                .map(|column| column.as_ptr() as usize)
+                .collect()
+        };
+        let fixed_columns = fixed
+            .iter()
+            .map(|column| column.as_ptr() as usize)
+            .collect::<Vec<_>>();
+        let instance_columns = addresses(instance);
+        let subdomain_columns = addresses(subdomains);
+        let (advice_columns, advice_stride) = match advice {
+            AdviceCosets::ColumnMajor(columns) => (addresses(columns), size_of::<F>()),
+            AdviceCosets::RowMajor {
+                num_columns,
+                values,
+            } => (
+                (0..*num_columns)
+                    .map(|column| values.as_ptr() as usize + column * size_of::<F>())
+                    .collect(),
+                num_columns * size_of::<F>(),
+            ),
+        };
+        let mut scalars = [beta, gamma, theta, y, F::ZERO];
+        let mut check = self.cross_check.then(|| graph.instance());
+
+        for (i, value) in values.iter_mut().enumerate() {
+            let idx = start + i;
+            for (rot_idx, rot) in graph.rotations.iter().enumerate() {
+                data.rotations[rot_idx] = get_rotation_idx(idx, *rot, rot_scale, isize);
+            }
+            scalars[4] = *value;
+            let context = Context {
+                fixed: fixed_columns.as_ptr() as usize,
+                advice: advice_columns.as_ptr() as usize,
+                advice_stride,
+                instance: instance_columns.as_ptr() as usize,
+                subdomains: subdomain_columns.as_ptr() as usize,
+                constants: graph.constants.as_ptr() as usize,
+                challenges: challenges.as_ptr() as usize,
+                scalars: scalars.as_ptr() as usize,
+                rotations: data.rotations.as_ptr() as usize,
+                intermediates: data.intermediates.as_mut_ptr() as usize,
+            };
+            // SAFETY: the function was compiled from `graph`, whose columns, rotations,
+            // constants, challenges and intermediates are all in the context, and whose
+            // rows are in the columns.
+            unsafe { (self.graph.function)(&context) };
+            let result = graph
+                .calculations
+                .last()
+                .map_or(F::ZERO, |calc| data.intermediates[calc.target]);
+
+            if let Some(check) = check.as_mut() {
+                let expected = graph.evaluate(
+                    check, fixed, advice, instance, subdomains, challenges, &beta, &gamma, &theta,
+                    &y, value, idx, rot_scale, isize,
+                );
+                if result != expected {
+                    return Err(Error::Other(format!(
+                        "the compiled gates differ from the interpreter at row {idx}"
+                    )));
+                }
+            }
+            *value = result;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Evaluator, WitnessLayout};
+    use super::*;
+    use halo2_common::plonk::{ConstraintSystem, Expression};
+    use halo2_middleware::poly::Rotation;
+    use halo2curves::pasta::pallas::{Affine, Scalar};
+
+    #[test]
+    fn matches_interpreter() {
+        let mut meta = ConstraintSystem::<Scalar>::default();
+        let a = meta.advice_column();
+        let b = meta.advice_column();
+        let f = meta.fixed_column();
+        let i = meta.instance_column();
+        meta.create_gate("gate", |meta| {
+            let a_cur = meta.query_advice(a, Rotation::cur());
+            let a_next = meta.query_advice(a, Rotation::next());
+            let b_prev = meta.query_advice(b, Rotation::prev());
+            let f = meta.query_fixed(f, Rotation::cur());
+            let i = meta.query_instance(i, Rotation::cur());
+            vec![
+                f.clone() * (a_cur.clone() * a_cur.clone() - b_prev.clone()),
+                (a_next.clone() + i.clone()).square() - Expression::Constant(Scalar::from(3)),
+                -(a_cur * b_prev * f + a_next.clone() + a_next) + i,
+            ]
+        });
+        let graph = Evaluator::<Affine>::new(&meta).custom_gates;
+        let gates = JitGates::new(&graph, GateEvaluation::CrossCheck)
+            .unwrap()
+            .unwrap();
+
+        let size = 16;
+        let column = |seed: u64| Polynomial::<_, ExtendedLagrangeCoeff> {
+            values: (0..size)
+                .map(|row| Scalar::from(seed * 1000 + row * row + 7))
+                .collect(),
+            _marker: PhantomData,
+        };
+        let fixed_column = column(1);
+        let fixed = vec![&fixed_column];
+        let instance = vec![column(2)];
+        let scalars = [5, 6, 7, 8].map(Scalar::from);
+        for layout in [WitnessLayout::ColumnMajor, WitnessLayout::RowMajor] {
+            let advice = AdviceCosets::new(vec![column(3), column(4)], layout);
+            let mut values = vec![Scalar::from(9); size as usize];
+            let mut interpreted = values.clone();
+            gates
+                .evaluate(
+                    &graph,
+                    &mut graph.instance(),
+                    &fixed,
+                    &advice,
+                    &instance,
+                    &[],
+                    &[],
+                    scalars,
+                    &mut values,
+                    0,
+                    1,
+                    size as i32,
+                )
+                .unwrap();
+            let mut data = graph.instance();
+            for (idx, value) in interpreted.iter_mut().enumerate() {
+                *value = graph.evaluate(
+                    &mut data,
+                    &fixed,
+                    &advice,
+                    &instance,
+                    &[],
+                    &[],
+                    &scalars[0],
+                    &scalars[1],
+                    &scalars[2],
+                    &scalars[3],
+                    value,
+                    idx,
+                    1,
+                    size as i32,
+                );
+            }
+            assert_eq!(values, interpreted);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Standalone proofs of the openings of a proof, for light clients.
+//!
+//! The multiopen argument at the end of a proof is bound to everything before it through
+//! the transcript, so it can't be checked without the rest of the proof. An
+//! [`EvaluationProof`] instead proves the same openings with a multiopen argument over a
+//! fresh transcript, which only absorbs the openings themselves. Light clients that only
+//! need to check the values of some columns at some points can verify it with
+//! [`verify_evaluation_proof`], without the verifying key, the instances or the rest of
+//! the proof.
+
+use std::io::{self, Read};
+
+use group::Curve;
+use halo2_middleware::ff::FromUniformBytes;
+use halo2curves::CurveAffine;
+use rand_core::RngCore;
+
+use super::verifier::Opening;
+use super::Error;
+use crate::arithmetic::eval_polynomial;
+use crate::helpers::{
+    read_curve_id, write_curve_id, SerdeCurveAffine, SerdeFormat, SerdePrimeField,
+};
+use crate::poly::commitment::{CommitmentScheme, ParamsProver, Prover, Verifier};
+use crate::poly::{Coeff, Polynomial, ProverQuery, VerificationStrategy, VerifierQuery};
+use crate::transcript::{
+    absorb_domain, EncodedChallenge, Transcript, TranscriptReadBuffer, TranscriptWriterBuffer,
+};
+
+/// The label absorbed by the transcript of an evaluation proof before the openings, so
+/// that its challenges differ from those of any other proof.
+const EVALUATION_PROOF_DOMAIN: &[u8] = b"Halo2-Evaluation-Proof";
+
+/// Openings of committed polynomials, with a multiopen argument that proves them.
+///
+/// The openings of the proof of a circuit are in the order of
+/// [`VerifierTrace::openings`], so that a light client can find those of the columns it
+/// is interested in, and compare their commitments with those it knows.
+///
+/// [`VerifierTrace::openings`]: super::verifier::VerifierTrace::openings
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvaluationProof<C: CurveAffine> {
+    /// The openings proven by the argument.
+    pub openings: Vec<Opening<C>>,
+    /// The multiopen argument, as written by the transcript it was created with.
+    pub proof: Vec<u8>,
+}
+
+impl<C: SerdeCurveAffine> EvaluationProof<C>
+where
+    C::Scalar: SerdePrimeField,
+{
+    /// Writes the evaluation proof to a buffer: the identifier of the curve, the number of
+    /// openings as a `u32` in little-endian order, the commitment, point and evaluation
+    /// of each opening, the length of the argument as a `u32` in little-endian order and
+    /// the argument.
+    pub fn write<W: io::Write>(&self, writer: &mut W, format: SerdeFormat) -> io::Result<()> {
+        write_curve_id::<C, _>(writer)?;
+        writer.write_all(&(self.openings.len() as u32).to_le_bytes())?;
+        for opening in &self.openings {
+            opening.commitment.write(writer, format)?;
+            opening.point.write(writer, format)?;
+            opening.eval.write(writer, format)?;
+        }
+        writer.write_all(&(self.proof.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.proof)
+    }
+
+    /// Reads an evaluation proof written by [`Self::write`] with the same `format`, and
+    /// checks that it was written for the same curve.
+    pub fn read<R: io::Read>(reader: &mut R, format: SerdeFormat) -> io::Result<Self> {
+        fn read_len<R: io::Read>(reader: &mut R) -> io::Result<usize> {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len)?;
+            Ok(u32::from_le_bytes(len) as usize)
+        }
+
+        read_curve_id::<C, _>(reader, "evaluation proof")?;
+        let num_openings = read_len(reader)?;
+        // The length is not trusted to allocate memory: the openings are read one by one.
+        let mut openings = vec![];
+        for _ in 0..num_openings {
+            openings.push(Opening {
+                commitment: C::read(reader, format)?,
+                point: C::Scalar::read(reader, format)?,
+                eval: C::Scalar::read(reader, format)?,
+            });
+        }
+        let proof_len = read_len(reader)?;
+        let mut proof = vec![];
+        reader
+            .by_ref()
+            .take(proof_len as u64)
+            .read_to_end(&mut proof)?;
+        if proof.len() != proof_len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "evaluation proof argument is truncated",
+            ));
+        }
+        Ok(EvaluationProof { openings, proof })
+    }
+}
+
+/// Absorbs the domain separation label and the openings into the transcript of an
+/// evaluation proof.
+fn absorb_openings<C: CurveAffine, E: EncodedChallenge<C>, T: Transcript<C, E>>(
+    transcript: &mut T,
+    openings: &[Opening<C>],
+) -> io::Result<()>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    absorb_domain(transcript, EVALUATION_PROOF_DOMAIN)?;
+    transcript.common_scalar(C::Scalar::from(openings.len() as u64))?;
+    for opening in openings {
+        transcript.common_point(opening.commitment)?;
+        transcript.common_scalar(opening.point)?;
+        transcript.common_scalar(opening.eval)?;
+    }
+    Ok(())
+}
+
+/// Creates an evaluation proof of `queries`, with a new transcript of type `T`.
+///
+/// The commitment of each query is recomputed from its polynomial and blind, once for
+/// each polynomial.
+pub fn create_evaluation_proof<
+    'params,
+    Scheme: CommitmentScheme,
+    P: Prover<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    R: RngCore,
+    T: TranscriptWriterBuffer<Vec<u8>, Scheme::Curve, E>,
+>(
+    params: &'params Scheme::ParamsProver,
+    queries: &[ProverQuery<'_, Scheme::Curve>],
+    rng: R,
+) -> Result<EvaluationProof<Scheme::Curve>, Error>
+where
+    Scheme::Scalar: FromUniformBytes<64>,
+{
+    let mut commitments: Vec<(&Polynomial<Scheme::Scalar, Coeff>, Scheme::Curve)> = vec![];
+    let openings = queries
+        .iter()
+        .map(|query| {
+            let commitment = match commitments
+                .iter()
+                .find(|(poly, _)| std::ptr::eq(*poly, query.poly))
+            {
+                Some((_, commitment)) => *commitment,
+                None => {
+                    let commitment = params.commit(query.poly, query.blind).to_affine();
+                    commitments.push((query.poly, commitment));
+                    commitment
+                }
+            };
+            Opening {
+                commitment,
+                point: query.point,
+                eval: eval_polynomial(query.poly, query.point),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut transcript = T::init(vec![]);
+    absorb_openings(&mut transcript, &openings)?;
+    P::new(params)
+        .create_proof(rng, &mut transcript, queries.iter().copied())
+        .map_err(|_| Error::ConstraintSystemFailure)?;
+
+    Ok(EvaluationProof {
+        openings,
+        proof: transcript.finalize(),
+    })
+}
+
+/// Verifies an evaluation proof, with a transcript of type `T` reading its argument.
+///
+/// The openings are only proven once the output of the strategy is checked, for example
+/// with [`VerificationStrategy::finalize`].
+pub fn verify_evaluation_proof<
+    'params,
+    'proof,
+    Scheme: CommitmentScheme,
+    V: Verifier<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    T: TranscriptReadBuffer<&'proof [u8], Scheme::Curve, E>,
+    Strategy: VerificationStrategy<'params, Scheme, V>,
+>(
+    params: &'params Scheme::ParamsVerifier,
+    proof: &'proof EvaluationProof<Scheme::Curve>,
+    strategy: Strategy,
+) -> Result<Strategy::Output, Error>
+where
+    Scheme::Scalar: FromUniformBytes<64>,
+{
+    let mut transcript = T::init(&proof.proof[..]);
+    absorb_openings(&mut transcript, &proof.openings)?;
+
+    let queries = proof.openings.iter().map(|opening| {
+        VerifierQuery::new_commitment(&opening.commitment, opening.point, opening.eval)
+    });
+    let verifier = V::new(params);
+    strategy.process(|msm| {
+        verifier
+            .verify_proof(&mut transcript, queries, msm)
+            .map_err(|_| Error::Opening)
+    })
+}
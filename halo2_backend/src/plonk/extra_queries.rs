@@ -0,0 +1,79 @@
+use halo2_common::plonk::{circuit::Column, ConstraintSystem, Error};
+use halo2_middleware::circuit::Any;
+use halo2_middleware::ff::Field;
+
+/// An opening of a committed column at a point chosen by the caller, in addition to the
+/// queries of the circuit.
+///
+/// Extra queries let another protocol learn the evaluations of the columns of a proof at
+/// points of its own, for example points derived from its transcript, without a separate
+/// opening proof. The prover evaluates each column at its point, and the point and the
+/// evaluation are bound to the transcript after the evaluations of the circuit queries.
+/// The multiopen argument then proves them along with the other evaluations.
+///
+/// The evaluation of a column at a point of the evaluation domain is the value of one of
+/// its cells, so such points are rejected. Any other evaluation still reveals a linear
+/// combination of the cells of a column: blinded columns only hide their values from as
+/// many evaluations as they have blinding factors, see
+/// [`ConstraintSystem::blinding_factors`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExtraQuery<F> {
+    column: Column<Any>,
+    point: F,
+}
+
+impl<F: Field> ExtraQuery<F> {
+    /// Creates a query opening `column` at `point`.
+    pub fn new(column: impl Into<Column<Any>>, point: F) -> Self {
+        ExtraQuery {
+            column: column.into(),
+            point,
+        }
+    }
+
+    /// Returns the column opened by this query.
+    pub fn column(&self) -> Column<Any> {
+        self.column
+    }
+
+    /// Returns the point this query opens its column at.
+    pub fn point(&self) -> F {
+        self.point
+    }
+}
+
+/// Checks that `queries` open columns of `cs` that are committed, at points outside of
+/// the evaluation domain of size `n`.
+pub(crate) fn check_extra_queries<F: Field>(
+    queries: &[ExtraQuery<F>],
+    cs: &ConstraintSystem<F>,
+    n: u64,
+    query_instance: bool,
+) -> Result<(), Error> {
+    for query in queries {
+        let (count, committed) = match query.column.column_type() {
+            Any::Advice(_) => (cs.num_advice_columns, true),
+            Any::Fixed => (cs.num_fixed_columns, true),
+            Any::Instance => (cs.num_instance_columns, query_instance),
+        };
+        if query.column.index() >= count {
+            return Err(Error::Other(format!(
+                "extra query of {:?}, which isn't a column of the circuit",
+                query.column
+            )));
+        }
+        if !committed {
+            return Err(Error::Other(format!(
+                "extra query of {:?}, which isn't committed by the commitment scheme",
+                query.column
+            )));
+        }
+        if query.point.pow([n]) == F::ONE {
+            return Err(Error::Other(format!(
+                "extra query of {:?} at a point of the evaluation domain",
+                query.column
+            )));
+        }
+    }
+    Ok(())
+}
@@ -4,30 +4,113 @@
 
 #![allow(clippy::int_plus_one)]
 
+use blake2b_simd::Params as Blake2bParams;
 use group::Curve;
-use halo2_middleware::ff::{Field, FromUniformBytes};
+use halo2_middleware::ff::{Field, FromUniformBytes, PrimeField};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
-use super::{evaluation::Evaluator, permutation, Polynomial, ProvingKey, VerifyingKey};
+use super::{
+    evaluation::Evaluator, permutation, FixedColumn, FixedPolynomials, Polynomial, ProverLimits,
+    ProvingKey, VerifyingKey,
+};
 use crate::{
     arithmetic::{parallelize, CurveAffine},
+    helpers::sparse_entries,
     poly::{
         commitment::{Blind, Params},
-        EvaluationDomain,
+        Coeff, EvaluationDomain, ExtendedLagrangeCoeff,
     },
+    zal::PlonkEngine,
 };
 use halo2_common::plonk::circuit::{Circuit, ConstraintSystem};
 use halo2_common::plonk::Error;
 use halo2_middleware::circuit::CompiledCircuitV2;
 
+/// The polynomials derived from a fixed column, computed at most once and shared by the
+/// keys that have the column.
+#[derive(Debug, Default)]
+struct SharedFixedColumn<C: CurveAffine> {
+    commitment: Mutex<Option<C>>,
+    poly: Mutex<Option<Arc<Polynomial<C::Scalar, Coeff>>>>,
+    coset: Mutex<Option<Arc<Polynomial<C::Scalar, ExtendedLagrangeCoeff>>>>,
+}
+
+/// Returns the value of `cell`, computing it with `init` if it is empty.
+fn get_or_init<T: Clone>(cell: &Mutex<Option<T>>, init: impl FnOnce() -> T) -> T {
+    cell.lock().unwrap().get_or_insert_with(init).clone()
+}
+
+/// A registry of fixed column polynomials and commitments shared between the keys of
+/// different circuits.
+///
+/// Circuits that use the same fixed tables otherwise recompute the same polynomials and
+/// commitments at keygen. The registry keys every fixed column by a hash of its contents
+/// and the circuit size, so that each of them is computed once per process, no matter
+/// how many keys are generated with [`keygen_vk_with_registry`] and
+/// [`keygen_pk_with_registry`].
+///
+/// A registry must only be used with a single set of `Params`, as the commitments it
+/// caches depend on them.
+#[derive(Debug)]
+pub struct TableRegistry<C: CurveAffine> {
+    columns: Mutex<HashMap<[u8; 32], Arc<SharedFixedColumn<C>>>>,
+}
+
+impl<C: CurveAffine> Default for TableRegistry<C> {
+    fn default() -> Self {
+        TableRegistry {
+            columns: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<C: CurveAffine> TableRegistry<C> {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of distinct fixed columns in the registry.
+    pub fn len(&self) -> usize {
+        self.columns.lock().unwrap().len()
+    }
+
+    /// Returns `true` if the registry holds no fixed column.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entry(&self, k: u32, values: &[C::Scalar]) -> Arc<SharedFixedColumn<C>> {
+        let mut hasher = Blake2bParams::new()
+            .hash_length(32)
+            .personal(b"Halo2-FixedTable")
+            .to_state();
+        hasher.update(&k.to_le_bytes());
+        for value in values {
+            hasher.update(value.to_repr().as_ref());
+        }
+        let key = hasher.finalize().as_bytes().try_into().unwrap();
+
+        self.columns.lock().unwrap().entry(key).or_default().clone()
+    }
+}
+
 /// Creates a domain, constraint system, and configuration for a circuit.
+///
+/// Returns an error if `2^k` rows are not enough for the circuit, or if its extended
+/// domain is too large for the scalar field.
 pub(crate) fn create_domain<C, ConcreteCircuit>(
     k: u32,
     #[cfg(feature = "circuit-params")] params: ConcreteCircuit::Params,
-) -> (
-    EvaluationDomain<C::Scalar>,
-    ConstraintSystem<C::Scalar>,
-    ConcreteCircuit::Config,
-)
+) -> Result<
+    (
+        EvaluationDomain<C::Scalar>,
+        ConstraintSystem<C::Scalar>,
+        ConcreteCircuit::Config,
+    ),
+    Error,
+>
 where
     C: CurveAffine,
     ConcreteCircuit: Circuit<C::Scalar>,
@@ -38,11 +121,30 @@ where
     #[cfg(not(feature = "circuit-params"))]
     let config = ConcreteCircuit::configure(&mut cs);
 
-    let degree = cs.degree();
+    let domain = create_domain_for_cs::<C>(k, &cs)?;
+
+    Ok((domain, cs, config))
+}
 
-    let domain = EvaluationDomain::new(degree as u32, k);
+/// Creates the domain of a circuit with the constraint system `cs` and `2^k` rows.
+///
+/// Returns an error if `2^k` rows are not enough for the circuit, or if its extended
+/// domain is too large for the scalar field.
+pub(crate) fn create_domain_for_cs<C: CurveAffine>(
+    k: u32,
+    cs: &ConstraintSystem<C::Scalar>,
+) -> Result<EvaluationDomain<C::Scalar>, Error> {
+    let degree = cs.degree() as u32;
+    if k > C::Scalar::S || EvaluationDomain::<C::Scalar>::extended_k(degree, k) > C::Scalar::S {
+        return Err(Error::Other(format!(
+            "circuit of degree {degree} is too large for 2^{k} rows"
+        )));
+    }
+    if (1usize << k) < cs.minimum_rows() {
+        return Err(Error::not_enough_rows_available(k));
+    }
 
-    (domain, cs, config)
+    Ok(EvaluationDomain::new(degree, k))
 }
 
 /// Generate a `VerifyingKey` from an instance of `CompiledCircuit`.
@@ -50,6 +152,67 @@ pub fn keygen_vk_v2<'params, C, P>(
     params: &P,
     circuit: &CompiledCircuitV2<C::Scalar>,
 ) -> Result<VerifyingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    C::Scalar: FromUniformBytes<64>,
+{
+    keygen_vk_inner(params, &PlonkEngine::default(), circuit, None)
+}
+
+/// Generate a `VerifyingKey` from an instance of `CompiledCircuit`, computing the
+/// multiscalar multiplications and FFTs with `engine`.
+pub fn keygen_vk_with_engine<'params, C, P>(
+    engine: &PlonkEngine<C>,
+    params: &P,
+    circuit: &CompiledCircuitV2<C::Scalar>,
+) -> Result<VerifyingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    C::Scalar: FromUniformBytes<64>,
+{
+    keygen_vk_inner(params, engine, circuit, None)
+}
+
+/// Generate a `VerifyingKey` from an instance of `CompiledCircuit`, reusing the fixed
+/// column commitments already computed in `registry`.
+pub fn keygen_vk_with_registry<'params, C, P>(
+    params: &P,
+    circuit: &CompiledCircuitV2<C::Scalar>,
+    registry: &TableRegistry<C>,
+) -> Result<VerifyingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    C::Scalar: FromUniformBytes<64>,
+{
+    keygen_vk_inner(params, &PlonkEngine::default(), circuit, Some(registry))
+}
+
+/// Generate a `VerifyingKey` from an instance of `CompiledCircuit`, after checking that
+/// the circuit is within `limits`.
+pub fn keygen_vk_with_limits<'params, C, P>(
+    params: &P,
+    circuit: &CompiledCircuitV2<C::Scalar>,
+    limits: &ProverLimits,
+) -> Result<VerifyingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    C::Scalar: FromUniformBytes<64>,
+{
+    let cs: ConstraintSystem<C::Scalar> = circuit.cs.clone().into();
+    limits.check(params.k(), &cs, 1)?;
+    keygen_vk_inner(params, &PlonkEngine::default(), circuit, None)
+}
+
+fn keygen_vk_inner<'params, C, P>(
+    params: &P,
+    engine: &PlonkEngine<C>,
+    circuit: &CompiledCircuitV2<C::Scalar>,
+    registry: Option<&TableRegistry<C>>,
+) -> Result<VerifyingKey<C>, Error>
 where
     C: CurveAffine,
     P: Params<'params, C>,
@@ -68,19 +231,17 @@ where
         &cs_backend.permutation,
         &circuit.preprocessing.permutation,
     )?
-    .build_vk(params, &domain, &cs.permutation);
+    .build_vk(params, engine, &domain, &cs.permutation);
 
     let fixed_commitments = circuit
         .preprocessing
         .fixed
         .iter()
-        .map(|poly| {
-            params
-                .commit_lagrange(
-                    &Polynomial::new_lagrange_from_vec(poly.clone()),
-                    Blind::default(),
-                )
-                .to_affine()
+        .map(|poly| match registry {
+            Some(registry) => get_or_init(&registry.entry(params.k(), poly).commitment, || {
+                commit_fixed_column(params, engine, poly)
+            }),
+            None => commit_fixed_column(params, engine, poly),
         })
         .collect();
 
@@ -96,12 +257,97 @@ where
     ))
 }
 
+/// Commits to the values of a fixed column.
+pub(crate) fn commit_fixed_column<'params, C, P>(
+    params: &P,
+    engine: &PlonkEngine<C>,
+    column: &[C::Scalar],
+) -> C
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+{
+    let msm = &*engine.msm_backend;
+    match sparse_entries(column) {
+        // Selectors and other sparse columns only need the bases of their non-zero rows.
+        Some((rows, values)) => {
+            params.commit_lagrange_sparse_with_engine(msm, &rows, &values, Blind::default())
+        }
+        None => params.commit_lagrange_with_engine(
+            msm,
+            &Polynomial::new_lagrange_from_vec(column.to_vec()),
+            Blind::default(),
+        ),
+    }
+    .to_affine()
+}
+
 /// Generate a `ProvingKey` from a `VerifyingKey` and an instance of `CompiledCircuit`.
 pub fn keygen_pk_v2<'params, C, P>(
     params: &P,
     vk: VerifyingKey<C>,
     circuit: &CompiledCircuitV2<C::Scalar>,
 ) -> Result<ProvingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+{
+    keygen_pk_inner(params, &PlonkEngine::default(), vk, circuit, None)
+}
+
+/// Generate a `ProvingKey` from a `VerifyingKey` and an instance of `CompiledCircuit`,
+/// computing the multiscalar multiplications and FFTs with `engine`.
+pub fn keygen_pk_with_engine<'params, C, P>(
+    engine: &PlonkEngine<C>,
+    params: &P,
+    vk: VerifyingKey<C>,
+    circuit: &CompiledCircuitV2<C::Scalar>,
+) -> Result<ProvingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+{
+    keygen_pk_inner(params, engine, vk, circuit, None)
+}
+
+/// Generate a `ProvingKey` from a `VerifyingKey` and an instance of `CompiledCircuit`,
+/// reusing the fixed column polynomials already computed in `registry`.
+pub fn keygen_pk_with_registry<'params, C, P>(
+    params: &P,
+    vk: VerifyingKey<C>,
+    circuit: &CompiledCircuitV2<C::Scalar>,
+    registry: &TableRegistry<C>,
+) -> Result<ProvingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+{
+    keygen_pk_inner(params, &PlonkEngine::default(), vk, circuit, Some(registry))
+}
+
+/// Generate a `ProvingKey` from a `VerifyingKey` and an instance of `CompiledCircuit`,
+/// after checking that the circuit is within `limits`.
+pub fn keygen_pk_with_limits<'params, C, P>(
+    params: &P,
+    vk: VerifyingKey<C>,
+    circuit: &CompiledCircuitV2<C::Scalar>,
+    limits: &ProverLimits,
+) -> Result<ProvingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+{
+    limits.check(params.k(), &vk.cs, 1)?;
+    keygen_pk_inner(params, &PlonkEngine::default(), vk, circuit, None)
+}
+
+fn keygen_pk_inner<'params, C, P>(
+    params: &P,
+    engine: &PlonkEngine<C>,
+    vk: VerifyingKey<C>,
+    circuit: &CompiledCircuitV2<C::Scalar>,
+    registry: Option<&TableRegistry<C>>,
+) -> Result<ProvingKey<C>, Error>
 where
     C: CurveAffine,
     P: Params<'params, C>,
@@ -114,36 +360,45 @@ where
 
     // Compute fixeds
 
-    let fixed_polys: Vec<_> = circuit
+    let fft = &*engine.fft_backend;
+    let fixed = circuit
         .preprocessing
         .fixed
         .iter()
-        .map(|poly| {
-            vk.domain
-                .lagrange_to_coeff(Polynomial::new_lagrange_from_vec(poly.clone()))
+        .map(|column| {
+            FixedColumn::new(column.clone(), |values| match registry {
+                Some(registry) => {
+                    let entry = registry.entry(params.k(), &values);
+                    let values = Polynomial::new_lagrange_from_vec(values);
+                    let poly = get_or_init(&entry.poly, || {
+                        Arc::new(vk.domain.lagrange_to_coeff_with_engine(fft, values.clone()))
+                    });
+                    let coset = get_or_init(&entry.coset, || {
+                        let poly = poly.as_ref().clone();
+                        Arc::new(vk.domain.coeff_to_extended_with_engine(fft, poly))
+                    });
+                    FixedPolynomials {
+                        values,
+                        poly,
+                        coset,
+                    }
+                }
+                None => FixedPolynomials::new(
+                    &vk.domain,
+                    fft,
+                    Polynomial::new_lagrange_from_vec(values),
+                ),
+            })
         })
         .collect();
 
-    let fixed_cosets = fixed_polys
-        .iter()
-        .map(|poly| vk.domain.coeff_to_extended(poly.clone()))
-        .collect();
-
-    let fixed_values = circuit
-        .preprocessing
-        .fixed
-        .clone()
-        .into_iter()
-        .map(Polynomial::new_lagrange_from_vec)
-        .collect();
-
     // Compute l_0(X)
     // TODO: this can be done more efficiently
     // https://github.com/privacy-scaling-explorations/halo2/issues/269
     let mut l0 = vk.domain.empty_lagrange();
     l0[0] = C::Scalar::ONE;
-    let l0 = vk.domain.lagrange_to_coeff(l0);
-    let l0 = vk.domain.coeff_to_extended(l0);
+    let l0 = vk.domain.lagrange_to_coeff_with_engine(fft, l0);
+    let l0 = vk.domain.coeff_to_extended_with_engine(fft, l0);
 
     // Compute l_blind(X) which evaluates to 1 for each blinding factor row
     // and 0 otherwise over the domain.
@@ -151,15 +406,15 @@ where
     for evaluation in l_blind[..].iter_mut().rev().take(vk.cs.blinding_factors()) {
         *evaluation = C::Scalar::ONE;
     }
-    let l_blind = vk.domain.lagrange_to_coeff(l_blind);
-    let l_blind = vk.domain.coeff_to_extended(l_blind);
+    let l_blind = vk.domain.lagrange_to_coeff_with_engine(fft, l_blind);
+    let l_blind = vk.domain.coeff_to_extended_with_engine(fft, l_blind);
 
     // Compute l_last(X) which evaluates to 1 on the first inactive row (just
     // before the blinding factors) and 0 otherwise over the domain
     let mut l_last = vk.domain.empty_lagrange();
     l_last[params.n() as usize - vk.cs.blinding_factors() - 1] = C::Scalar::ONE;
-    let l_last = vk.domain.lagrange_to_coeff(l_last);
-    let l_last = vk.domain.coeff_to_extended(l_last);
+    let l_last = vk.domain.lagrange_to_coeff_with_engine(fft, l_last);
+    let l_last = vk.domain.coeff_to_extended_with_engine(fft, l_last);
 
     // Compute l_active_row(X)
     let one = C::Scalar::ONE;
@@ -180,17 +435,38 @@ where
         &cs.permutation,
         &circuit.preprocessing.permutation,
     )?
-    .build_pk(params, &vk.domain, &cs.permutation.clone().into());
+    .build_pk(params, engine, &vk.domain, &cs.permutation.clone().into());
 
     Ok(ProvingKey {
         vk,
         l0,
         l_last,
         l_active_row,
-        fixed_values,
-        fixed_polys,
-        fixed_cosets,
+        fixed,
         permutation: permutation_pk,
         ev,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TableRegistry;
+    use halo2curves::pasta::{EqAffine, Fp};
+    use std::sync::Arc;
+
+    #[test]
+    fn registry_deduplicates_by_content() {
+        let registry = TableRegistry::<EqAffine>::new();
+        let table: Vec<Fp> = (0..16).map(|i| Fp::from(i as u64)).collect();
+        let other: Vec<Fp> = (0..16).map(|i| Fp::from(2 * i as u64)).collect();
+
+        let a = registry.entry(4, &table);
+        let b = registry.entry(4, &table.clone());
+        assert!(Arc::ptr_eq(&a, &b));
+
+        // Different contents or sizes get their own entry.
+        registry.entry(4, &other);
+        registry.entry(5, &table);
+        assert_eq!(registry.len(), 3);
+    }
+}
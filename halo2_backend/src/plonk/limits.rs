@@ -0,0 +1,165 @@
+use halo2_common::plonk::{ConstraintSystem, Error};
+use halo2_middleware::ff::Field;
+
+use crate::poly::EvaluationDomain;
+
+/// Limits on the size of the circuits a key is generated or a proof is created for.
+///
+/// Services that generate keys or create proofs for circuits they don't control can check
+/// these limits before any polynomial of the circuit is allocated, with the `_with_limits`
+/// variants of the keygen functions and the `with_limits` option of the proof builder, and
+/// reject oversized jobs with a descriptive error. Every limit is unset by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProverLimits {
+    /// The maximum `k` of a circuit, which has `2^k` rows.
+    pub max_k: Option<u32>,
+    /// The maximum number of fixed, advice and instance columns of a circuit.
+    pub max_columns: Option<usize>,
+    /// The maximum memory, in bytes, estimated by [`ProverLimits::memory_estimate`].
+    pub max_memory_estimate: Option<usize>,
+}
+
+impl ProverLimits {
+    /// Returns limits with no limit set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum `k` of a circuit.
+    pub fn with_max_k(mut self, max_k: u32) -> Self {
+        self.max_k = Some(max_k);
+        self
+    }
+
+    /// Sets the maximum number of columns of a circuit.
+    pub fn with_max_columns(mut self, max_columns: usize) -> Self {
+        self.max_columns = Some(max_columns);
+        self
+    }
+
+    /// Sets the maximum estimated memory, in bytes, of creating a proof.
+    pub fn with_max_memory_estimate(mut self, max_memory_estimate: usize) -> Self {
+        self.max_memory_estimate = Some(max_memory_estimate);
+        self
+    }
+
+    /// Estimates the memory, in bytes, used to create a proof of `num_proofs` circuits
+    /// with the constraint system `cs` and `2^k` rows.
+    ///
+    /// The estimate counts the polynomials of the proving key, the witness polynomials of
+    /// every circuit, and the extended domain polynomials of the circuit whose quotient
+    /// is being computed, which dominate the memory of the prover. It ignores the memory
+    /// of the commitment scheme and of intermediate values, so it is a lower bound of the
+    /// peak memory of the prover, meant to compare circuits rather than to be exact.
+    pub fn memory_estimate<F: Field>(k: u32, cs: &ConstraintSystem<F>, num_proofs: usize) -> usize {
+        let degree = cs.degree();
+        let n = 1usize << k;
+        let extended_n = 1usize << EvaluationDomain::<F>::extended_k(degree as u32, k);
+        let permutation_columns = cs.permutation.get_columns().len();
+        let permutation_chunks = if permutation_columns == 0 {
+            0
+        } else {
+            let chunk_len = degree.max(3) - 2;
+            (permutation_columns + chunk_len - 1) / chunk_len
+        };
+
+        // The fixed and permutation polynomials in Lagrange and coefficient form and on
+        // the extended domain, and the extended Lagrange basis polynomials.
+        let key =
+            (cs.num_fixed_columns + permutation_columns) * (2 * n + extended_n) + 3 * extended_n;
+        // The advice and instance polynomials in Lagrange and coefficient form, the
+        // permuted columns and grand products of the lookups, the grand products of the
+        // shuffles, and the grand products of the permutation.
+        let witness = (cs.num_advice_columns + cs.num_instance_columns) * 2 * n
+            + cs.lookups.len() * 7 * n
+            + cs.shuffles.len() * 2 * n
+            + permutation_chunks * 2 * n;
+        // The advice, instance, lookup, shuffle and permutation polynomials on the
+        // extended domain, and the quotient polynomial.
+        let extended = (cs.num_advice_columns
+            + cs.num_instance_columns
+            + cs.lookups.len() * 3
+            + cs.shuffles.len()
+            + permutation_chunks
+            + 1)
+            * extended_n;
+
+        (key + witness * num_proofs + extended).saturating_mul(std::mem::size_of::<F>())
+    }
+
+    /// Checks that a proof of `num_proofs` circuits with the constraint system `cs` and
+    /// `2^k` rows is within these limits, and returns a descriptive error otherwise.
+    pub fn check<F: Field>(
+        &self,
+        k: u32,
+        cs: &ConstraintSystem<F>,
+        num_proofs: usize,
+    ) -> Result<(), Error> {
+        self.check_k(k)?;
+        if let Some(max_columns) = self.max_columns {
+            let columns = cs.num_fixed_columns + cs.num_advice_columns + cs.num_instance_columns;
+            if columns > max_columns {
+                return Err(Error::Other(format!(
+                    "circuit has {columns} columns, more than the limit of {max_columns}"
+                )));
+            }
+        }
+        if let Some(max_memory_estimate) = self.max_memory_estimate {
+            let estimate = Self::memory_estimate(k, cs, num_proofs);
+            if estimate > max_memory_estimate {
+                return Err(Error::Other(format!(
+                    "proving is estimated to use {estimate} bytes, more than the limit of {max_memory_estimate}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that `k` is within [`ProverLimits::max_k`], which only depends on the
+    /// parameters and can be checked before the circuit is even configured.
+    pub fn check_k(&self, k: u32) -> Result<(), Error> {
+        match self.max_k {
+            Some(max_k) if k > max_k => Err(Error::Other(format!(
+                "circuit has 2^{k} rows, more than the limit of 2^{max_k}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProverLimits;
+    use halo2_common::plonk::ConstraintSystem;
+    use halo2_middleware::poly::Rotation;
+    use halo2curves::pasta::Fp;
+
+    #[test]
+    fn limits() {
+        let mut cs = ConstraintSystem::<Fp>::default();
+        let a = cs.advice_column();
+        let f = cs.fixed_column();
+        cs.create_gate("mul", |cells| {
+            let a = cells.query_advice(a, Rotation::cur());
+            let f = cells.query_fixed(f, Rotation::cur());
+            vec![a.clone() * a * f]
+        });
+        cs.enable_equality(a);
+
+        let limits = ProverLimits::new();
+        assert!(limits.check(30, &cs, 1).is_ok());
+
+        let limits = ProverLimits::new().with_max_k(10).with_max_columns(2);
+        assert!(limits.check(10, &cs, 1).is_ok());
+        assert!(limits.check(11, &cs, 1).is_err());
+        cs.instance_column();
+        assert!(limits.check(10, &cs, 1).is_err());
+
+        let estimate = ProverLimits::memory_estimate(10, &cs, 1);
+        assert!(ProverLimits::memory_estimate(10, &cs, 2) > estimate);
+        assert!(ProverLimits::memory_estimate(11, &cs, 1) > estimate);
+        let limits = ProverLimits::new().with_max_memory_estimate(estimate);
+        assert!(limits.check(10, &cs, 1).is_ok());
+        assert!(limits.check(10, &cs, 2).is_err());
+    }
+}
@@ -1,19 +1,20 @@
 use super::super::ProvingKey;
 use super::Argument;
+#[cfg(feature = "lookup-injection")]
+use crate::dev::lookup_injection::LookupInjection;
 use crate::plonk::evaluation::evaluate;
 use crate::{
-    arithmetic::{eval_polynomial, parallelize, CurveAffine},
+    arithmetic::{eval_polynomial, running_product, sort_by_repr, CurveAffine},
+    helpers::{wipe_if_hardened, Wipe},
     plonk::{ChallengeBeta, ChallengeGamma, ChallengeTheta, ChallengeX},
     poly::{
         commitment::{Blind, Params},
         Coeff, EvaluationDomain, LagrangeCoeff, Polynomial, ProverQuery,
     },
     transcript::{EncodedChallenge, TranscriptWrite},
+    zal::PlonkEngine,
 };
-use group::{
-    ff::{BatchInvert, Field},
-    Curve,
-};
+use group::{ff::Field, Curve};
 use halo2_common::plonk::{Error, Expression};
 use halo2_middleware::ff::WithSmallOrderMulGroup;
 use halo2_middleware::poly::Rotation;
@@ -34,6 +35,10 @@ pub(in crate::plonk) struct Permuted<C: CurveAffine> {
     permuted_table_expression: Polynomial<C::Scalar, LagrangeCoeff>,
     permuted_table_poly: Polynomial<C::Scalar, Coeff>,
     permuted_table_blind: Blind<C::Scalar>,
+    // Whether the permuted expressions were injected, in which case the sanity checks of
+    // the grand product don't hold.
+    #[cfg(feature = "sanity-checks")]
+    injected: bool,
 }
 
 #[derive(Debug)]
@@ -50,6 +55,75 @@ pub(in crate::plonk) struct Evaluated<C: CurveAffine> {
     constructed: Committed<C>,
 }
 
+/// Evaluates `expressions` at the `n` rows of the domain and compresses them with `theta`.
+#[allow(clippy::too_many_arguments)]
+fn compress_expressions<C: CurveAffine>(
+    expressions: &[Expression<C::Scalar>],
+    pk: &ProvingKey<C>,
+    n: usize,
+    theta: ChallengeTheta<C>,
+    fixed_values: &[&Polynomial<C::Scalar, LagrangeCoeff>],
+    advice_values: &[Polynomial<C::Scalar, LagrangeCoeff>],
+    instance_values: &[Polynomial<C::Scalar, LagrangeCoeff>],
+    challenges: &[C::Scalar],
+) -> Polynomial<C::Scalar, LagrangeCoeff> {
+    expressions
+        .iter()
+        .map(|expression| {
+            pk.vk.domain.lagrange_from_vec(evaluate(
+                expression,
+                n,
+                1,
+                fixed_values,
+                advice_values,
+                instance_values,
+                challenges,
+            ))
+        })
+        .fold(pk.vk.domain.empty_lagrange(), |acc, expression| {
+            acc * *theta + &expression
+        })
+}
+
+/// Compresses the table expressions of the lookup once for all the circuit instances of
+/// a proof, if they only depend on the fixed columns and the challenges, as they do for
+/// fixed tables. Returns `None` if the table has to be compressed for each instance.
+pub(in crate::plonk) fn compress_shared_table<'params, C: CurveAffine, P: Params<'params, C>>(
+    arg: &Argument<C::Scalar>,
+    pk: &ProvingKey<C>,
+    params: &P,
+    theta: ChallengeTheta<C>,
+    fixed_values: &[&Polynomial<C::Scalar, LagrangeCoeff>],
+    challenges: &[C::Scalar],
+) -> Option<Polynomial<C::Scalar, LagrangeCoeff>> {
+    let shared = arg.table_expressions.iter().all(|expression| {
+        expression.evaluate(
+            &|_| true,
+            &|_| true,
+            &|_| true,
+            &|_| false,
+            &|_| false,
+            &|_| true,
+            &|a| a,
+            &|a, b| a && b,
+            &|a, b| a && b,
+            &|a, _| a,
+        )
+    });
+    shared.then(|| {
+        compress_expressions(
+            &arg.table_expressions,
+            pk,
+            params.n() as usize,
+            theta,
+            fixed_values,
+            &[],
+            &[],
+            challenges,
+        )
+    })
+}
+
 /// Given a Lookup with input expressions [A_0, A_1, ..., A_{m-1}] and table expressions
 /// [S_0, S_1, ..., S_{m-1}], this method
 /// - constructs A_compressed = \theta^{m-1} A_0 + theta^{m-2} A_1 + ... + \theta A_{m-2} + A_{m-1}
@@ -59,6 +133,9 @@ pub(in crate::plonk) struct Evaluated<C: CurveAffine> {
 /// - constructs Permuted<C> struct using permuted_input_value = A', and
 ///   permuted_table_expression = S'.
 /// The Permuted<C> struct is used to update the Lookup, and is then returned.
+///
+/// S_compressed is taken from `shared_table` when it is given, see
+/// [`compress_shared_table`]. A' and S' are taken from `injection` when it is given.
 #[allow(clippy::too_many_arguments)]
 pub(in crate::plonk) fn lookup_commit_permuted<
     'a,
@@ -73,12 +150,15 @@ pub(in crate::plonk) fn lookup_commit_permuted<
     arg: &Argument<F>,
     pk: &ProvingKey<C>,
     params: &P,
+    engine: &PlonkEngine<C>,
     domain: &EvaluationDomain<C::Scalar>,
     theta: ChallengeTheta<C>,
     advice_values: &'a [Polynomial<C::Scalar, LagrangeCoeff>],
-    fixed_values: &'a [Polynomial<C::Scalar, LagrangeCoeff>],
+    fixed_values: &'a [&'a Polynomial<C::Scalar, LagrangeCoeff>],
     instance_values: &'a [Polynomial<C::Scalar, LagrangeCoeff>],
     challenges: &'a [C::Scalar],
+    shared_table: Option<&Polynomial<C::Scalar, LagrangeCoeff>>,
+    #[cfg(feature = "lookup-injection")] injection: Option<&LookupInjection<C::Scalar>>,
     mut rng: R,
     transcript: &mut T,
 ) -> Result<Permuted<C>, Error>
@@ -87,31 +167,44 @@ where
     C::Curve: Mul<F, Output = C::Curve> + MulAssign<F>,
 {
     // Closure to get values of expressions and compress them
-    let compress_expressions = |expressions: &[Expression<C::Scalar>]| {
-        let compressed_expression = expressions
-            .iter()
-            .map(|expression| {
-                pk.vk.domain.lagrange_from_vec(evaluate(
-                    expression,
-                    params.n() as usize,
-                    1,
-                    fixed_values,
-                    advice_values,
-                    instance_values,
-                    challenges,
-                ))
-            })
-            .fold(domain.empty_lagrange(), |acc, expression| {
-                acc * *theta + &expression
-            });
-        compressed_expression
+    let compress = |expressions: &[Expression<C::Scalar>]| {
+        compress_expressions(
+            expressions,
+            pk,
+            params.n() as usize,
+            theta,
+            fixed_values,
+            advice_values,
+            instance_values,
+            challenges,
+        )
     };
 
     // Get values of input expressions involved in the lookup and compress them
-    let compressed_input_expression = compress_expressions(&arg.input_expressions);
+    let compressed_input_expression = compress(&arg.input_expressions);
 
     // Get values of table expressions involved in the lookup and compress them
-    let compressed_table_expression = compress_expressions(&arg.table_expressions);
+    let compressed_table_expression = match shared_table {
+        Some(table) => table.clone(),
+        None => compress(&arg.table_expressions),
+    };
+
+    // Take the permuted expressions of the injection, if any
+    #[cfg(feature = "lookup-injection")]
+    let injected = match injection {
+        Some(injection) => {
+            let usable_rows = params.n() as usize - (pk.vk.cs.blinding_factors() + 1);
+            Some(injection.permute(
+                &compressed_input_expression[..usable_rows],
+                &compressed_table_expression[..usable_rows],
+            )?)
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "lookup-injection"))]
+    let injected: Option<(Vec<C::Scalar>, Vec<C::Scalar>)> = None;
+    #[cfg(feature = "sanity-checks")]
+    let is_injected = injected.is_some();
 
     // Permute compressed (InputExpression, TableExpression) pair
     let (permuted_input_expression, permuted_table_expression) = permute_expression_pair(
@@ -121,13 +214,19 @@ where
         &mut rng,
         &compressed_input_expression,
         &compressed_table_expression,
+        injected,
     )?;
 
     // Closure to construct commitment to vector of values
     let mut commit_values = |values: &Polynomial<C::Scalar, LagrangeCoeff>| {
-        let poly = pk.vk.domain.lagrange_to_coeff(values.clone());
+        let poly = pk
+            .vk
+            .domain
+            .lagrange_to_coeff_with_engine(&*engine.fft_backend, values.clone());
         let blind = Blind(C::Scalar::random(&mut rng));
-        let commitment = params.commit_lagrange(values, blind).to_affine();
+        let commitment = params
+            .commit_lagrange_with_engine(&*engine.msm_backend, values, blind)
+            .to_affine();
         (poly, blind, commitment)
     };
 
@@ -154,6 +253,8 @@ where
         permuted_table_expression,
         permuted_table_poly,
         permuted_table_blind,
+        #[cfg(feature = "sanity-checks")]
+        injected: is_injected,
     })
 }
 
@@ -170,9 +271,10 @@ impl<C: CurveAffine> Permuted<C> {
         R: RngCore,
         T: TranscriptWrite<C, E>,
     >(
-        self,
+        mut self,
         pk: &ProvingKey<C>,
         params: &P,
+        engine: &PlonkEngine<C>,
         beta: ChallengeBeta<C>,
         gamma: ChallengeGamma<C>,
         mut rng: R,
@@ -190,68 +292,48 @@ impl<C: CurveAffine> Permuted<C> {
         // s_j(X) is the jth table expression in this lookup,
         // s'(X) is the compression of the permuted table expressions,
         // and i is the ith row of the expression.
-        let mut lookup_product = vec![C::Scalar::ZERO; params.n() as usize];
-        // Denominator uses the permuted input expression and permuted table expression
-        parallelize(&mut lookup_product, |lookup_product, start| {
-            for ((lookup_product, permuted_input_value), permuted_table_value) in lookup_product
-                .iter_mut()
-                .zip(self.permuted_input_expression[start..].iter())
-                .zip(self.permuted_table_expression[start..].iter())
-            {
-                *lookup_product = (*beta + permuted_input_value) * (*gamma + permuted_table_value);
-            }
-        });
-
-        // Batch invert to obtain the denominators for the lookup product
-        // polynomials
-        lookup_product.iter_mut().batch_invert();
-
-        // Finish the computation of the entire fraction by computing the numerators
-        // (\theta^{m-1} a_0(\omega^i) + \theta^{m-2} a_1(\omega^i) + ... + \theta a_{m-2}(\omega^i) + a_{m-1}(\omega^i) + \beta)
-        // * (\theta^{m-1} s_0(\omega^i) + \theta^{m-2} s_1(\omega^i) + ... + \theta s_{m-2}(\omega^i) + s_{m-1}(\omega^i) + \gamma)
-        parallelize(&mut lookup_product, |product, start| {
-            for (i, product) in product.iter_mut().enumerate() {
-                let i = i + start;
-
-                *product *= &(self.compressed_input_expression[i] + *beta);
-                *product *= &(self.compressed_table_expression[i] + *gamma);
-            }
-        });
-
-        // The product vector is a vector of products of fractions of the form
-        //
-        // Numerator: (\theta^{m-1} a_0(\omega^i) + \theta^{m-2} a_1(\omega^i) + ... + \theta a_{m-2}(\omega^i) + a_{m-1}(\omega^i) + \beta)
-        //            * (\theta^{m-1} s_0(\omega^i) + \theta^{m-2} s_1(\omega^i) + ... + \theta s_{m-2}(\omega^i) + s_{m-1}(\omega^i) + \gamma)
-        // Denominator: (a'(\omega^i) + \beta) (s'(\omega^i) + \gamma)
         //
-        // where there are m input expressions and m table expressions,
-        // a_j(\omega^i) is the jth input expression in this lookup,
-        // a'j(\omega^i) is the permuted input expression,
-        // s_j(\omega^i) is the jth table expression in this lookup,
-        // s'(\omega^i) is the permuted table expression,
-        // and i is the ith row of the expression.
-
-        // Compute the evaluations of the lookup product polynomial
-        // over our domain, starting with z[0] = 1
-        let z = iter::once(C::Scalar::ONE)
-            .chain(lookup_product)
-            .scan(C::Scalar::ONE, |state, cur| {
-                *state *= &cur;
-                Some(*state)
-            })
-            // Take all rows including the "last" row which should
-            // be a boolean (and ideally 1, else soundness is broken)
-            .take(params.n() as usize - blinding_factors)
-            // Chain random blinding factors.
-            .chain((0..blinding_factors).map(|_| C::Scalar::random(&mut rng)))
-            .collect::<Vec<_>>();
-        assert_eq!(z.len(), params.n() as usize);
-        let z = pk.vk.domain.lagrange_from_vec(z);
+        // The fractions are streamed into the buffer of z(X), which is then transformed in
+        // place, starting with z[0] = 1. All rows are taken including the "last" row,
+        // which should be a boolean (and ideally 1, else soundness is broken).
+        let n = params.n() as usize;
+        let mut z = pk.vk.domain.empty_lagrange();
+        running_product(
+            &mut z,
+            n - blinding_factors,
+            C::Scalar::ONE,
+            // Denominator uses the permuted input expression and permuted table expression
+            |denominators, start| {
+                for ((denominator, permuted_input_value), permuted_table_value) in denominators
+                    .iter_mut()
+                    .zip(self.permuted_input_expression[start..].iter())
+                    .zip(self.permuted_table_expression[start..].iter())
+                {
+                    *denominator = (*beta + permuted_input_value) * (*gamma + permuted_table_value);
+                }
+            },
+            // (\theta^{m-1} a_0(\omega^i) + \theta^{m-2} a_1(\omega^i) + ... + \theta a_{m-2}(\omega^i) + a_{m-1}(\omega^i) + \beta)
+            // * (\theta^{m-1} s_0(\omega^i) + \theta^{m-2} s_1(\omega^i) + ... + \theta s_{m-2}(\omega^i) + s_{m-1}(\omega^i) + \gamma)
+            |products, start| {
+                for ((product, input_value), table_value) in products
+                    .iter_mut()
+                    .zip(self.compressed_input_expression[start..].iter())
+                    .zip(self.compressed_table_expression[start..].iter())
+                {
+                    *product *= &(*input_value + *beta);
+                    *product *= &(*table_value + *gamma);
+                }
+            },
+        );
+        // Set blinding factors
+        for z in &mut z[n - blinding_factors..] {
+            *z = C::Scalar::random(&mut rng);
+        }
 
         #[cfg(feature = "sanity-checks")]
         // This test works only with intermediate representations in this method.
         // It can be used for debugging purposes.
-        {
+        if !self.injected {
             // While in Lagrange basis, check that product is correctly constructed
             let u = (params.n() as usize) - (blinding_factors + 1);
 
@@ -287,12 +369,23 @@ impl<C: CurveAffine> Permuted<C> {
         }
 
         let product_blind = Blind(C::Scalar::random(rng));
-        let product_commitment = params.commit_lagrange(&z, product_blind).to_affine();
-        let z = pk.vk.domain.lagrange_to_coeff(z);
+        let product_commitment = params
+            .commit_lagrange_with_engine(&*engine.msm_backend, &z, product_blind)
+            .to_affine();
+        let z = pk
+            .vk
+            .domain
+            .lagrange_to_coeff_with_engine(&*engine.fft_backend, z);
 
         // Hash product commitment
         transcript.write_point(product_commitment)?;
 
+        // The expressions are not needed anymore, only the polynomials are kept.
+        wipe_if_hardened(&mut self.compressed_input_expression);
+        wipe_if_hardened(&mut self.permuted_input_expression);
+        wipe_if_hardened(&mut self.compressed_table_expression);
+        wipe_if_hardened(&mut self.permuted_table_expression);
+
         Ok(Committed::<C> {
             permuted_input_poly: self.permuted_input_poly,
             permuted_input_blind: self.permuted_input_blind,
@@ -304,6 +397,36 @@ impl<C: CurveAffine> Permuted<C> {
     }
 }
 
+impl<C: CurveAffine> Wipe for Permuted<C> {
+    fn wipe(&mut self) {
+        self.compressed_input_expression.wipe();
+        self.permuted_input_expression.wipe();
+        self.permuted_input_poly.wipe();
+        self.permuted_input_blind.wipe();
+        self.compressed_table_expression.wipe();
+        self.permuted_table_expression.wipe();
+        self.permuted_table_poly.wipe();
+        self.permuted_table_blind.wipe();
+    }
+}
+
+impl<C: CurveAffine> Wipe for Committed<C> {
+    fn wipe(&mut self) {
+        self.permuted_input_poly.wipe();
+        self.permuted_input_blind.wipe();
+        self.permuted_table_poly.wipe();
+        self.permuted_table_blind.wipe();
+        self.product_poly.wipe();
+        self.product_blind.wipe();
+    }
+}
+
+impl<C: CurveAffine> Wipe for Evaluated<C> {
+    fn wipe(&mut self) {
+        self.constructed.wipe();
+    }
+}
+
 impl<C: CurveAffine> Committed<C> {
     pub(in crate::plonk) fn evaluate<E: EncodedChallenge<C>, T: TranscriptWrite<C, E>>(
         self,
@@ -387,6 +510,8 @@ type ExpressionPair<F> = (Polynomial<F, LagrangeCoeff>, Polynomial<F, LagrangeCo
 /// - the first row in a sequence of like values in A' is the row
 ///   that has the corresponding value in S'.
 /// This method returns (A', S') if no errors are encountered.
+///
+/// If the `injected` A' and S' of the usable rows are given, they are taken instead.
 fn permute_expression_pair<'params, C: CurveAffine, P: Params<'params, C>, R: RngCore>(
     pk: &ProvingKey<C>,
     params: &P,
@@ -394,25 +519,67 @@ fn permute_expression_pair<'params, C: CurveAffine, P: Params<'params, C>, R: Rn
     mut rng: R,
     input_expression: &Polynomial<C::Scalar, LagrangeCoeff>,
     table_expression: &Polynomial<C::Scalar, LagrangeCoeff>,
+    injected: Option<(Vec<C::Scalar>, Vec<C::Scalar>)>,
 ) -> Result<ExpressionPair<C::Scalar>, Error> {
     let blinding_factors = pk.vk.cs.blinding_factors();
     let usable_rows = params.n() as usize - (blinding_factors + 1);
 
-    let mut permuted_input_expression: Vec<C::Scalar> = input_expression.to_vec();
+    #[cfg(feature = "sanity-checks")]
+    let is_injected = injected.is_some();
+    let (mut permuted_input_expression, mut permuted_table_coeffs) = match injected {
+        Some(injected) => injected,
+        None => permute_usable_rows(usable_rows, input_expression, table_expression)?,
+    };
+
+    permuted_input_expression
+        .extend((0..(blinding_factors + 1)).map(|_| C::Scalar::random(&mut rng)));
+    permuted_table_coeffs.extend((0..(blinding_factors + 1)).map(|_| C::Scalar::random(&mut rng)));
+    assert_eq!(permuted_input_expression.len(), params.n() as usize);
+    assert_eq!(permuted_table_coeffs.len(), params.n() as usize);
+
+    #[cfg(feature = "sanity-checks")]
+    if !is_injected {
+        let mut last = None;
+        for (a, b) in permuted_input_expression
+            .iter()
+            .zip(permuted_table_coeffs.iter())
+            .take(usable_rows)
+        {
+            if *a != *b {
+                assert_eq!(*a, last.unwrap());
+            }
+            last = Some(*a);
+        }
+    }
+
+    Ok((
+        domain.lagrange_from_vec(permuted_input_expression),
+        domain.lagrange_from_vec(permuted_table_coeffs),
+    ))
+}
+
+/// Permutes the `usable_rows` first values of A and S like [`permute_expression_pair`].
+fn permute_usable_rows<F: WithSmallOrderMulGroup<3> + Ord>(
+    usable_rows: usize,
+    input_expression: &Polynomial<F, LagrangeCoeff>,
+    table_expression: &Polynomial<F, LagrangeCoeff>,
+) -> Result<(Vec<F>, Vec<F>), Error> {
+    let mut permuted_input_expression: Vec<F> = input_expression.to_vec();
     permuted_input_expression.truncate(usable_rows);
 
     // Sort input lookup expression values
-    permuted_input_expression.sort();
+    sort_by_repr(&mut permuted_input_expression);
 
     // A BTreeMap of each unique element in the table expression and its count
-    let mut leftover_table_map: BTreeMap<C::Scalar, u32> = table_expression
-        .iter()
-        .take(usable_rows)
-        .fold(BTreeMap::new(), |mut acc, coeff| {
-            *acc.entry(*coeff).or_insert(0) += 1;
-            acc
-        });
-    let mut permuted_table_coeffs = vec![C::Scalar::ZERO; usable_rows];
+    let mut leftover_table_map: BTreeMap<F, u32> =
+        table_expression
+            .iter()
+            .take(usable_rows)
+            .fold(BTreeMap::new(), |mut acc, coeff| {
+                *acc.entry(*coeff).or_insert(0) += 1;
+                acc
+            });
+    let mut permuted_table_coeffs = vec![F::ZERO; usable_rows];
 
     let mut repeated_input_rows = permuted_input_expression
         .iter()
@@ -446,29 +613,5 @@ fn permute_expression_pair<'params, C: CurveAffine, P: Params<'params, C>, R: Rn
     }
     assert!(repeated_input_rows.is_empty());
 
-    permuted_input_expression
-        .extend((0..(blinding_factors + 1)).map(|_| C::Scalar::random(&mut rng)));
-    permuted_table_coeffs.extend((0..(blinding_factors + 1)).map(|_| C::Scalar::random(&mut rng)));
-    assert_eq!(permuted_input_expression.len(), params.n() as usize);
-    assert_eq!(permuted_table_coeffs.len(), params.n() as usize);
-
-    #[cfg(feature = "sanity-checks")]
-    {
-        let mut last = None;
-        for (a, b) in permuted_input_expression
-            .iter()
-            .zip(permuted_table_coeffs.iter())
-            .take(usable_rows)
-        {
-            if *a != *b {
-                assert_eq!(*a, last.unwrap());
-            }
-            last = Some(*a);
-        }
-    }
-
-    Ok((
-        domain.lagrange_from_vec(permuted_input_expression),
-        domain.lagrange_from_vec(permuted_table_coeffs),
-    ))
+    Ok((permuted_input_expression, permuted_table_coeffs))
 }
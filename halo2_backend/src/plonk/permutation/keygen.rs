@@ -8,6 +8,7 @@ use crate::{
         commitment::{Blind, Params},
         EvaluationDomain,
     },
+    zal::PlonkEngine,
 };
 use halo2_common::plonk::Error;
 use halo2_middleware::circuit::ColumnMid;
@@ -134,19 +135,21 @@ impl Assembly {
     pub(crate) fn build_vk<'params, C: CurveAffine, P: Params<'params, C>>(
         self,
         params: &P,
+        engine: &PlonkEngine<C>,
         domain: &EvaluationDomain<C::Scalar>,
         p: &Argument,
     ) -> VerifyingKey<C> {
-        build_vk(params, domain, p, |i, j| self.mapping[i][j])
+        build_vk(params, engine, domain, p, |i, j| self.mapping[i][j])
     }
 
     pub(crate) fn build_pk<'params, C: CurveAffine, P: Params<'params, C>>(
         self,
         params: &P,
+        engine: &PlonkEngine<C>,
         domain: &EvaluationDomain<C::Scalar>,
         p: &Argument,
     ) -> ProvingKey<C> {
-        build_pk(params, domain, p, |i, j| self.mapping[i][j])
+        build_pk(params, engine, domain, p, |i, j| self.mapping[i][j])
     }
 }
 
@@ -267,7 +270,7 @@ impl Assembly {
     /// Builds the ordered mapping of the cycles.
     /// This will only get executed once.
     pub fn build_ordered_mapping(&mut self) {
-        use crate::multicore::IntoParallelRefMutIterator;
+        use crate::multicore::prelude::*;
 
         // will only get called once
         if self.ordered_cycles.is_empty() && !self.cycles.is_empty() {
@@ -312,21 +315,23 @@ impl Assembly {
     pub(crate) fn build_vk<'params, C: CurveAffine, P: Params<'params, C>>(
         &mut self,
         params: &P,
+        engine: &PlonkEngine<C>,
         domain: &EvaluationDomain<C::Scalar>,
         p: &Argument,
     ) -> VerifyingKey<C> {
         self.build_ordered_mapping();
-        build_vk(params, domain, p, |i, j| self.mapping_at_idx(i, j))
+        build_vk(params, engine, domain, p, |i, j| self.mapping_at_idx(i, j))
     }
 
     pub(crate) fn build_pk<'params, C: CurveAffine, P: Params<'params, C>>(
         &mut self,
         params: &P,
+        engine: &PlonkEngine<C>,
         domain: &EvaluationDomain<C::Scalar>,
         p: &Argument,
     ) -> ProvingKey<C> {
         self.build_ordered_mapping();
-        build_pk(params, domain, p, |i, j| self.mapping_at_idx(i, j))
+        build_pk(params, engine, domain, p, |i, j| self.mapping_at_idx(i, j))
     }
 
     /// Returns columns that participate in the permutation argument.
@@ -349,6 +354,7 @@ impl Assembly {
 
 pub(crate) fn build_pk<'params, C: CurveAffine, P: Params<'params, C>>(
     params: &P,
+    engine: &PlonkEngine<C>,
     domain: &EvaluationDomain<C::Scalar>,
     p: &Argument,
     mapping: impl Fn(usize, usize) -> (usize, usize) + Sync,
@@ -400,7 +406,8 @@ pub(crate) fn build_pk<'params, C: CurveAffine, P: Params<'params, C>>(
             for (x, poly) in o.iter_mut().enumerate() {
                 let i = start + x;
                 let permutation_poly = permutations[i].clone();
-                *poly = domain.lagrange_to_coeff(permutation_poly);
+                *poly =
+                    domain.lagrange_to_coeff_with_engine(&*engine.fft_backend, permutation_poly);
             }
         });
     }
@@ -411,7 +418,7 @@ pub(crate) fn build_pk<'params, C: CurveAffine, P: Params<'params, C>>(
             for (x, coset) in o.iter_mut().enumerate() {
                 let i = start + x;
                 let poly = polys[i].clone();
-                *coset = domain.coeff_to_extended(poly);
+                *coset = domain.coeff_to_extended_with_engine(&*engine.fft_backend, poly);
             }
         });
     }
@@ -425,6 +432,7 @@ pub(crate) fn build_pk<'params, C: CurveAffine, P: Params<'params, C>>(
 
 pub(crate) fn build_vk<'params, C: CurveAffine, P: Params<'params, C>>(
     params: &P,
+    engine: &PlonkEngine<C>,
     domain: &EvaluationDomain<C::Scalar>,
     p: &Argument,
     mapping: impl Fn(usize, usize) -> (usize, usize) + Sync,
@@ -477,7 +485,7 @@ pub(crate) fn build_vk<'params, C: CurveAffine, P: Params<'params, C>>(
         // Compute commitment to permutation polynomial
         commitments.push(
             params
-                .commit_lagrange(permutation, Blind::default())
+                .commit_lagrange_with_engine(&*engine.msm_backend, permutation, Blind::default())
                 .to_affine(),
         );
     }
@@ -8,13 +8,15 @@ use std::iter::{self, ExactSizeIterator};
 
 use super::Argument;
 use crate::{
-    arithmetic::{eval_polynomial, parallelize, CurveAffine},
+    arithmetic::{eval_polynomial, parallelize, running_product, CurveAffine},
+    helpers::{wipe_if_hardened, Wipe},
     plonk::{self, permutation::ProvingKey, ChallengeBeta, ChallengeGamma, ChallengeX},
     poly::{
         commitment::{Blind, Params},
         Coeff, ExtendedLagrangeCoeff, LagrangeCoeff, Polynomial, ProverQuery,
     },
     transcript::{EncodedChallenge, TranscriptWrite},
+    zal::PlonkEngine,
 };
 use halo2_common::plonk::Error;
 use halo2_middleware::circuit::Any;
@@ -46,6 +48,78 @@ pub(crate) struct Evaluated<C: CurveAffine> {
     constructed: Constructed<C>,
 }
 
+/// The factors of the permutation product polynomials that only depend on the fixed
+/// columns, which are the same for every circuit instance of a proof.
+///
+/// For each chunk of columns with a fixed column, this holds at each row the product over
+/// its fixed columns of
+///
+/// (p_j(\omega^i) + \delta^j \omega^i \beta + \gamma) /
+/// (p_j(\omega^i) + \beta s_j(\omega^i) + \gamma)
+pub(in crate::plonk) struct SharedFactors<F> {
+    chunks: Vec<Option<Vec<F>>>,
+}
+
+/// Computes the [`SharedFactors`] of the permutation argument, once for all the circuit
+/// instances of a proof.
+pub(in crate::plonk) fn permutation_shared_factors<C: CurveAffine>(
+    arg: &Argument,
+    pk: &plonk::ProvingKey<C>,
+    pkey: &ProvingKey<C>,
+    fixed: &[&Polynomial<C::Scalar, LagrangeCoeff>],
+    beta: ChallengeBeta<C>,
+    gamma: ChallengeGamma<C>,
+) -> SharedFactors<C::Scalar> {
+    let domain = &pk.vk.domain;
+    let n = 1 << domain.k();
+    let chunk_len = pk.vk.permutation_chunk_len();
+    let omega = domain.get_omega();
+
+    // Each column gets its own delta power, whether it is fixed or not.
+    let mut delta = C::Scalar::ONE;
+    let chunks = arg
+        .columns
+        .chunks(chunk_len)
+        .zip(pkey.permutations.chunks(chunk_len))
+        .map(|(columns, permutations)| {
+            let mut denominators: Option<Vec<C::Scalar>> = None;
+            let mut numerators = vec![C::Scalar::ONE; n];
+            for (&column, permuted_column_values) in columns.iter().zip(permutations.iter()) {
+                if column.column_type() == Any::Fixed {
+                    let values = fixed[column.index()];
+                    let denominators = denominators.get_or_insert_with(|| vec![C::Scalar::ONE; n]);
+                    parallelize(denominators, |denominators, start| {
+                        for ((denominator, value), permuted_value) in denominators
+                            .iter_mut()
+                            .zip(values[start..].iter())
+                            .zip(permuted_column_values[start..].iter())
+                        {
+                            *denominator *= *beta * permuted_value + *gamma + value;
+                        }
+                    });
+                    parallelize(&mut numerators, |numerators, start| {
+                        let mut deltaomega = delta * omega.pow_vartime([start as u64, 0, 0, 0]);
+                        for (numerator, value) in numerators.iter_mut().zip(values[start..].iter())
+                        {
+                            *numerator *= deltaomega * *beta + *gamma + value;
+                            deltaomega *= &omega;
+                        }
+                    });
+                }
+                delta *= &<C::Scalar as PrimeField>::DELTA;
+            }
+            denominators.map(|mut factors| {
+                factors.batch_invert();
+                for (factor, numerator) in factors.iter_mut().zip(numerators.iter()) {
+                    *factor *= numerator;
+                }
+                factors
+            })
+        })
+        .collect();
+    SharedFactors { chunks }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub(in crate::plonk) fn permutation_commit<
     'params,
@@ -57,11 +131,12 @@ pub(in crate::plonk) fn permutation_commit<
 >(
     arg: &Argument,
     params: &P,
+    engine: &PlonkEngine<C>,
     pk: &plonk::ProvingKey<C>,
     pkey: &ProvingKey<C>,
     advice: &[Polynomial<C::Scalar, LagrangeCoeff>],
-    fixed: &[Polynomial<C::Scalar, LagrangeCoeff>],
     instance: &[Polynomial<C::Scalar, LagrangeCoeff>],
+    shared: &SharedFactors<C::Scalar>,
     beta: ChallengeBeta<C>,
     gamma: ChallengeGamma<C>,
     mut rng: R,
@@ -74,7 +149,7 @@ pub(in crate::plonk) fn permutation_commit<
     // will never underflow because of the requirement of at least a degree
     // 3 circuit for the permutation argument.
     assert!(pk.vk.cs_degree >= 3);
-    let chunk_len = pk.vk.cs_degree - 2;
+    let chunk_len = pk.vk.permutation_chunk_len();
     let blinding_factors = pk.vk.cs.blinding_factors();
 
     // Each column gets its own delta power.
@@ -85,10 +160,11 @@ pub(in crate::plonk) fn permutation_commit<
 
     let mut sets = vec![];
 
-    for (columns, permutations) in arg
+    for ((columns, permutations), shared_factors) in arg
         .columns
         .chunks(chunk_len)
         .zip(pkey.permutations.chunks(chunk_len))
+        .zip(shared.chunks.iter())
     {
         // Goal is to compute the products of fractions
         //
@@ -97,87 +173,94 @@ pub(in crate::plonk) fn permutation_commit<
         //
         // where p_j(X) is the jth column in this permutation,
         // and i is the ith row of the column.
+        //
+        // The fractions of the fixed columns are the shared factors of the chunk.
+        //
+        // The fractions are streamed into the buffer of the permutation product
+        // polynomial, which is then transformed in place, starting with z[0] = last_z.
 
-        let mut modified_values = vec![C::Scalar::ONE; params.n() as usize];
-
-        // Iterate over each column of the permutation
-        for (&column, permuted_column_values) in columns.iter().zip(permutations.iter()) {
+        // The columns of the chunk whose fractions are computed here, with their delta
+        // powers.
+        let mut columns_delta = vec![];
+        for &column in columns.iter() {
             let values = match column.column_type() {
-                Any::Advice(_) => advice,
-                Any::Fixed => fixed,
-                Any::Instance => instance,
+                Any::Advice(_) => Some(&advice[column.index()]),
+                Any::Fixed => None,
+                Any::Instance => Some(&instance[column.index()]),
             };
-            parallelize(&mut modified_values, |modified_values, start| {
-                for ((modified_values, value), permuted_value) in modified_values
-                    .iter_mut()
-                    .zip(values[column.index()][start..].iter())
-                    .zip(permuted_column_values[start..].iter())
+            columns_delta.push((values, deltaomega));
+            deltaomega *= &<C::Scalar as PrimeField>::DELTA;
+        }
+        let omega = domain.get_omega();
+
+        let n = params.n() as usize;
+        let mut z = domain.empty_lagrange();
+        running_product(
+            &mut z,
+            n - blinding_factors,
+            last_z,
+            |denominators, start| {
+                denominators.fill(C::Scalar::ONE);
+                for (values, permuted_column_values) in columns_delta
+                    .iter()
+                    .zip(permutations.iter())
+                    .filter_map(|((values, _), permuted)| values.map(|values| (values, permuted)))
                 {
-                    *modified_values *= *beta * permuted_value + *gamma + value;
+                    for ((denominator, value), permuted_value) in denominators
+                        .iter_mut()
+                        .zip(values[start..].iter())
+                        .zip(permuted_column_values[start..].iter())
+                    {
+                        *denominator *= *beta * permuted_value + *gamma + value;
+                    }
                 }
-            });
-        }
-
-        // Invert to obtain the denominator for the permutation product polynomial
-        modified_values.batch_invert();
-
-        // Iterate over each column again, this time finishing the computation
-        // of the entire fraction by computing the numerators
-        for &column in columns.iter() {
-            let omega = domain.get_omega();
-            let values = match column.column_type() {
-                Any::Advice(_) => advice,
-                Any::Fixed => fixed,
-                Any::Instance => instance,
-            };
-            parallelize(&mut modified_values, |modified_values, start| {
-                let mut deltaomega = deltaomega * omega.pow_vartime([start as u64, 0, 0, 0]);
-                for (modified_values, value) in modified_values
-                    .iter_mut()
-                    .zip(values[column.index()][start..].iter())
+            },
+            |fractions, start| {
+                if let Some(shared_factors) = shared_factors {
+                    for (fraction, factor) in
+                        fractions.iter_mut().zip(shared_factors[start..].iter())
+                    {
+                        *fraction *= factor;
+                    }
+                }
+                for (values, delta) in columns_delta
+                    .iter()
+                    .filter_map(|(values, delta)| values.map(|values| (values, delta)))
                 {
-                    // Multiply by p_j(\omega^i) + \delta^j \omega^i \beta
-                    *modified_values *= deltaomega * *beta + *gamma + value;
-                    deltaomega *= &omega;
+                    let mut deltaomega = *delta * omega.pow_vartime([start as u64, 0, 0, 0]);
+                    for (fraction, value) in fractions.iter_mut().zip(values[start..].iter()) {
+                        // Multiply by p_j(\omega^i) + \delta^j \omega^i \beta
+                        *fraction *= deltaomega * *beta + *gamma + value;
+                        deltaomega *= &omega;
+                    }
                 }
-            });
-            deltaomega *= &<C::Scalar as PrimeField>::DELTA;
-        }
+            },
+        );
 
-        // The modified_values vector is a vector of products of fractions
+        // The permutation product polynomial now holds the running products of fractions
         // of the form
         //
         // (p_j(\omega^i) + \delta^j \omega^i \beta + \gamma) /
         // (p_j(\omega^i) + \beta s_j(\omega^i) + \gamma)
         //
-        // where i is the index into modified_values, for the jth column in
-        // the permutation
+        // over the columns j of the chunk, up to the blinding rows.
 
-        // Compute the evaluations of the permutation product polynomial
-        // over our domain, starting with z[0] = 1
-        let mut z = vec![last_z];
-        for row in 1..(params.n() as usize) {
-            let mut tmp = z[row - 1];
-
-            tmp *= &modified_values[row - 1];
-            z.push(tmp);
-        }
-        let mut z = domain.lagrange_from_vec(z);
         // Set blinding factors
-        for z in &mut z[params.n() as usize - blinding_factors..] {
+        for z in &mut z[n - blinding_factors..] {
             *z = C::Scalar::random(&mut rng);
         }
         // Set new last_z
-        last_z = z[params.n() as usize - (blinding_factors + 1)];
+        last_z = z[n - (blinding_factors + 1)];
 
         let blind = Blind(C::Scalar::random(&mut rng));
 
-        let permutation_product_commitment_projective = params.commit_lagrange(&z, blind);
+        let permutation_product_commitment_projective =
+            params.commit_lagrange_with_engine(&*engine.msm_backend, &z, blind);
         let permutation_product_blind = blind;
-        let z = domain.lagrange_to_coeff(z);
-        let permutation_product_poly = z.clone();
-
-        let permutation_product_coset = domain.coeff_to_extended(z.clone());
+        let z = domain.lagrange_to_coeff_with_engine(&*engine.fft_backend, z);
+        let permutation_product_coset =
+            domain.coeff_to_extended_with_engine(&*engine.fft_backend, z.clone());
+        let permutation_product_poly = z;
 
         let permutation_product_commitment = permutation_product_commitment_projective.to_affine();
 
@@ -191,24 +274,108 @@ pub(in crate::plonk) fn permutation_commit<
         });
     }
 
+    // The boundaries are evaluated from the committed polynomials, since the running
+    // products start where the previous chunk ends by construction.
+    #[cfg(feature = "sanity-checks")]
+    {
+        let last_row = domain
+            .get_omega()
+            .pow_vartime([params.n() - (blinding_factors as u64 + 1)]);
+        let boundaries: Vec<_> = sets
+            .iter()
+            .map(|set| {
+                (
+                    eval_polynomial(&set.permutation_product_poly, C::Scalar::ONE),
+                    eval_polynomial(&set.permutation_product_poly, last_row),
+                )
+            })
+            .collect();
+        check_chunk_boundaries(&boundaries).expect("permutation product is correctly constructed");
+    }
+
     Ok(Committed { sets })
 }
 
+/// Checks the relations between the permutation product polynomials `z_i` of consecutive
+/// chunks, given their values at the first row and at the last usable row `u` of each
+/// chunk:
+/// - `z_0(\omega^0) = 1`,
+/// - `z_i(\omega^0) = z_{i-1}(\omega^u)` for each chunk `i > 0`,
+/// - `z_l(\omega^u) = 1` for the last chunk `l`.
+///
+/// The verifier only checks that `z_l(\omega^u)` is 0 or 1, but it is 1 for a witness that
+/// satisfies the copy constraints.
+#[cfg(any(test, feature = "sanity-checks"))]
+fn check_chunk_boundaries<F: Field>(boundaries: &[(F, F)]) -> Result<(), Error> {
+    let mut last_z = F::ONE;
+    for (chunk, (first, last)) in boundaries.iter().enumerate() {
+        if *first != last_z {
+            return Err(Error::Other(if chunk == 0 {
+                "the permutation product of chunk 0 doesn't start at 1".to_string()
+            } else {
+                format!(
+                    "the permutation product of chunk {} doesn't start where chunk {} ends",
+                    chunk,
+                    chunk - 1
+                )
+            }));
+        }
+        last_z = *last;
+    }
+    if last_z != F::ONE {
+        return Err(Error::Other(
+            "the permutation product doesn't end at 1, the copy constraints are not satisfied"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
 impl<C: CurveAffine> Committed<C> {
     pub(in crate::plonk) fn construct(self) -> Constructed<C> {
         Constructed {
             sets: self
                 .sets
-                .iter()
-                .map(|set| ConstructedSet {
-                    permutation_product_poly: set.permutation_product_poly.clone(),
-                    permutation_product_blind: set.permutation_product_blind,
+                .into_iter()
+                .map(|mut set| {
+                    wipe_if_hardened(&mut set.permutation_product_coset);
+                    ConstructedSet {
+                        permutation_product_poly: set.permutation_product_poly,
+                        permutation_product_blind: set.permutation_product_blind,
+                    }
                 })
                 .collect(),
         }
     }
 }
 
+impl<C: CurveAffine> Wipe for CommittedSet<C> {
+    fn wipe(&mut self) {
+        self.permutation_product_poly.wipe();
+        self.permutation_product_coset.wipe();
+        self.permutation_product_blind.wipe();
+    }
+}
+
+impl<C: CurveAffine> Wipe for Committed<C> {
+    fn wipe(&mut self) {
+        self.sets.wipe();
+    }
+}
+
+impl<C: CurveAffine> Wipe for ConstructedSet<C> {
+    fn wipe(&mut self) {
+        self.permutation_product_poly.wipe();
+        self.permutation_product_blind.wipe();
+    }
+}
+
+impl<C: CurveAffine> Wipe for Evaluated<C> {
+    fn wipe(&mut self) {
+        self.constructed.sets.wipe();
+    }
+}
+
 impl<C: CurveAffine> super::ProvingKey<C> {
     pub(in crate::plonk) fn open(
         &self,
@@ -329,3 +496,28 @@ impl<C: CurveAffine> Evaluated<C> {
             )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::check_chunk_boundaries;
+    use group::ff::Field;
+    use halo2curves::pasta::Fp;
+
+    #[test]
+    fn chunk_boundaries() {
+        let (a, b) = (Fp::from(3), Fp::from(5));
+
+        assert!(check_chunk_boundaries::<Fp>(&[]).is_ok());
+        assert!(check_chunk_boundaries(&[(Fp::ONE, Fp::ONE)]).is_ok());
+        assert!(check_chunk_boundaries(&[(Fp::ONE, a), (a, b), (b, Fp::ONE)]).is_ok());
+
+        // The first chunk must start at 1.
+        assert!(check_chunk_boundaries(&[(a, Fp::ONE)]).is_err());
+        // Each chunk must start where the previous one ends.
+        assert!(check_chunk_boundaries(&[(Fp::ONE, a), (b, Fp::ONE)]).is_err());
+        assert!(check_chunk_boundaries(&[(Fp::ONE, a), (a, b), (a, Fp::ONE)]).is_err());
+        // The last chunk must end at 1, which the verifier relaxes to 0 or 1.
+        assert!(check_chunk_boundaries(&[(Fp::ONE, a), (a, b)]).is_err());
+        assert!(check_chunk_boundaries(&[(Fp::ONE, a), (a, Fp::ZERO)]).is_err());
+    }
+}
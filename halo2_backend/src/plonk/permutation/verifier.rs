@@ -1,4 +1,4 @@
-use halo2_middleware::ff::{Field, PrimeField};
+use halo2_middleware::ff::Field;
 use std::iter;
 
 use super::{Argument, VerifyingKey};
@@ -40,7 +40,7 @@ pub(crate) fn permutation_read_product_commitments<
     vk: &plonk::VerifyingKey<C>,
     transcript: &mut T,
 ) -> Result<Committed<C>, Error> {
-    let chunk_len = vk.cs_degree - 2;
+    let chunk_len = vk.permutation_chunk_len();
 
     let permutation_product_commitments = arg
         .columns
@@ -114,9 +114,36 @@ impl<C: CurveAffine> Evaluated<C> {
         beta: ChallengeBeta<C>,
         gamma: ChallengeGamma<C>,
         x: ChallengeX<C>,
-    ) -> impl Iterator<Item = C::Scalar> + 'a {
-        let chunk_len = vk.cs_degree - 2;
-        iter::empty()
+    ) -> Result<impl Iterator<Item = C::Scalar> + 'a, Error> {
+        let chunk_len = vk.permutation_chunk_len();
+        // A key whose commitments don't match the columns of the argument would silently
+        // drop some of the constraints below.
+        if self.sets.len() != p.columns.chunks(chunk_len).len()
+            || common.permutation_evals.len() != p.columns.len()
+        {
+            return Err(Error::ConstraintSystemFailure);
+        }
+        // Except for the first set, enforce.
+        // l_0(X) * (z_i(X) - z_{i-1}(\omega^(last) X)) = 0
+        let chained_evals = self
+            .sets
+            .iter()
+            .skip(1)
+            .zip(self.sets.iter())
+            .map(|(set, last_set)| {
+                last_set
+                    .permutation_product_last_eval
+                    .map(|prev_last| (set.permutation_product_eval - prev_last) * l_0)
+                    .ok_or(Error::Opening)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // The first power of delta of each chunk, times beta * x
+        let chunk_delta = C::Scalar::DELTA.pow_vartime([chunk_len as u64]);
+        let chunk_deltas: Vec<_> =
+            iter::successors(Some(*beta * *x), |delta| Some(*delta * chunk_delta))
+                .take(self.sets.len())
+                .collect();
+        Ok(iter::empty()
             // Enforce only for the first set.
             // l_0(X) * (1 - z_0(X)) = 0
             .chain(
@@ -130,21 +157,7 @@ impl<C: CurveAffine> Evaluated<C> {
                 (last_set.permutation_product_eval.square() - last_set.permutation_product_eval)
                     * l_last
             }))
-            // Except for the first set, enforce.
-            // l_0(X) * (z_i(X) - z_{i-1}(\omega^(last) X)) = 0
-            .chain(
-                self.sets
-                    .iter()
-                    .skip(1)
-                    .zip(self.sets.iter())
-                    .map(|(set, last_set)| {
-                        (
-                            set.permutation_product_eval,
-                            last_set.permutation_product_last_eval.unwrap(),
-                        )
-                    })
-                    .map(move |(set, prev_last)| (set - prev_last) * l_0),
-            )
+            .chain(chained_evals)
             // And for all the sets we enforce:
             // (1 - (l_last(X) + l_blind(X))) * (
             //   z_i(\omega X) \prod (p(X) + \beta s_i(X) + \gamma)
@@ -178,9 +191,7 @@ impl<C: CurveAffine> Evaluated<C> {
                         }
 
                         let mut right = set.permutation_product_eval;
-                        let mut current_delta = (*beta * *x)
-                            * (<C::Scalar as PrimeField>::DELTA
-                                .pow_vartime([(chunk_index * chunk_len) as u64]));
+                        let mut current_delta = chunk_deltas[chunk_index];
                         for eval in columns.iter().map(|&column| match column.column_type() {
                             Any::Advice(_) => {
                                 advice_evals[vk.cs.get_any_query_index(column, Rotation::cur())]
@@ -198,7 +209,7 @@ impl<C: CurveAffine> Evaluated<C> {
 
                         (left - right) * (C::Scalar::ONE - (l_last + l_blind))
                     }),
-            )
+            ))
     }
 
     pub(in crate::plonk) fn queries<'r, M: MSM<C> + 'r>(
@@ -228,13 +239,12 @@ impl<C: CurveAffine> Evaluated<C> {
                         set.permutation_product_next_eval,
                     )))
             }))
-            // Open it at \omega^{last} x for all but the last set
+            // Open it at \omega^{last} x for all but the last set, which `expressions` has
+            // checked have an evaluation there
             .chain(self.sets.iter().rev().skip(1).flat_map(move |set| {
-                Some(VerifierQuery::new_commitment(
-                    &set.permutation_product_commitment,
-                    x_last,
-                    set.permutation_product_last_eval.unwrap(),
-                ))
+                set.permutation_product_last_eval.map(|eval| {
+                    VerifierQuery::new_commitment(&set.permutation_product_commitment, x_last, eval)
+                })
             }))
     }
 }
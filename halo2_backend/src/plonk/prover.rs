@@ -3,33 +3,73 @@
 use group::Curve;
 use halo2_middleware::ff::{Field, FromUniformBytes, WithSmallOrderMulGroup};
 use rand_core::RngCore;
-use std::collections::{BTreeSet, HashSet};
-use std::{collections::HashMap, iter};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use crate::arithmetic::{eval_polynomial, CurveAffine};
-use crate::plonk::lookup::prover::lookup_commit_permuted;
-use crate::plonk::permutation::prover::permutation_commit;
+#[cfg(feature = "lookup-injection")]
+use crate::dev::lookup_injection::LookupInjection;
+use crate::helpers::{Wipe, Wiped};
+use crate::multicore::prelude::*;
+use crate::plonk::extra_queries::check_extra_queries;
+use crate::plonk::lookup::prover::{compress_shared_table, lookup_commit_permuted};
+use crate::plonk::permutation::prover::{permutation_commit, permutation_shared_factors};
+use crate::plonk::sections::create_section_proofs;
 use crate::plonk::shuffle::prover::shuffle_commit_product;
+#[cfg(feature = "vanishing-pieces")]
+use crate::plonk::VanishingPieces;
 use crate::plonk::{
-    lookup, permutation, shuffle, vanishing, ChallengeBeta, ChallengeGamma, ChallengeTheta,
-    ChallengeX, ChallengeY, ProvingKey,
+    create_evaluation_proof, lookup, permutation, shuffle, vanishing, ChallengeBeta,
+    ChallengeGamma, ChallengeTheta, ChallengeX, ChallengeY, EvaluationProof, ExtraQuery,
+    ProofSections, ProverLimits, ProvingKey, QueryGroup, QueryOrder, WitnessLayout,
 };
 use crate::poly::{
     commitment::{Blind, CommitmentScheme, Params, Prover},
     Basis, Coeff, LagrangeCoeff, Polynomial, ProverQuery,
 };
 
-use crate::transcript::{EncodedChallenge, TranscriptWrite};
+use crate::transcript::{
+    absorb_domain, EncodedChallenge, TranscriptPhase, TranscriptWrite, TranscriptWriterBuffer,
+};
+use crate::zal::PlonkEngine;
 use group::prime::PrimeCurveAffine;
-use halo2_common::plonk::{circuit::sealed, Error};
+use halo2_common::plonk::{
+    circuit::{sealed, Column},
+    Error,
+};
+use halo2_middleware::circuit::{Advice, Any};
+use tracing::{debug_span, info_span};
 
 /// Collection of instance data used during proving for a single circuit proof.
 #[derive(Debug)]
 struct InstanceSingle<C: CurveAffine> {
     pub instance_values: Vec<Polynomial<C::Scalar, LagrangeCoeff>>,
     pub instance_polys: Vec<Polynomial<C::Scalar, Coeff>>,
+    pub instance_blinds: Vec<Blind<C::Scalar>>,
+}
+
+/// A commitment to an advice column produced outside of the prover, for example by a data
+/// availability layer, with the blind it was made with.
+///
+/// The prover checks that the commitment matches the values of the column, and sends it in
+/// the proof with the same blind, so that the proof refers to the data behind the external
+/// commitment. The column must be unblinded, see
+/// [`ConstraintSystem::unblinded_advice_column`]: the prover doesn't add random values to
+/// its unusable rows, so the commitment only depends on the assigned values and the blind.
+///
+/// [`ConstraintSystem::unblinded_advice_column`]: halo2_common::plonk::ConstraintSystem::unblinded_advice_column
+#[derive(Clone, Copy, Debug)]
+pub struct ExternalCommitment<C: CurveAffine> {
+    /// The committed advice column.
+    pub column: Column<Advice>,
+    /// The commitment to the Lagrange basis polynomial of the column.
+    pub commitment: C,
+    /// The blind of the commitment.
+    pub blind: Blind<C::Scalar>,
 }
 
+// The external commitments of a circuit, by advice column index.
+type ExternalCommitments<C> = HashMap<usize, ExternalCommitment<C>>;
+
 /// Collection of advice data used during proving for a single circuit proof.
 #[derive(Debug, Clone)]
 struct AdviceSingle<C: CurveAffine, B: Basis> {
@@ -37,6 +77,119 @@ struct AdviceSingle<C: CurveAffine, B: Basis> {
     pub advice_blinds: Vec<Blind<C::Scalar>>,
 }
 
+impl<C: CurveAffine, B: Basis> Wipe for AdviceSingle<C, B> {
+    fn wipe(&mut self) {
+        self.advice_polys.wipe();
+        self.advice_blinds.wipe();
+    }
+}
+
+/// This creates a proof for the circuits whose proving key is `pk`, without depending on
+/// the frontend that produced them.
+///
+/// `witness` is called once per phase, in order, with the phase and the challenges
+/// squeezed so far. It returns the advice values of the phase, arranged as
+/// `[circuit][column][row]`, with `None` for the columns of other phases. The provided
+/// `instances` are zero-padded internally.
+///
+/// This is equivalent to driving a [`ProverV2`] through all the phases of the circuit.
+#[allow(clippy::type_complexity)]
+pub fn create_proof_v2<
+    'params,
+    Scheme: CommitmentScheme,
+    P: Prover<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    R: RngCore,
+    T: TranscriptWrite<Scheme::Curve, E>,
+    W: FnMut(
+        u8,
+        &HashMap<usize, Scheme::Scalar>,
+    ) -> Result<Vec<Vec<Option<Vec<Scheme::Scalar>>>>, Error>,
+>(
+    params: &'params Scheme::ParamsProver,
+    pk: &ProvingKey<Scheme::Curve>,
+    instances: &[&[&[Scheme::Scalar]]],
+    mut witness: W,
+    rng: R,
+    transcript: &mut T,
+) -> Result<(), Error>
+where
+    Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    create_proof_v2_with_queries::<Scheme, P, _, _, _, _>(
+        params,
+        pk,
+        instances,
+        witness,
+        &[],
+        rng,
+        transcript,
+    )
+    .map(|_| ())
+}
+
+/// This creates a proof like [`create_proof_v2`], opening the columns of each circuit at
+/// the points of `queries` in addition to the queries of the circuit.
+///
+/// Returns the evaluations of `queries` for each circuit, which the verifier obtains from
+/// [`VerifierBuilder::verify_with_evaluations`].
+///
+/// [`VerifierBuilder::verify_with_evaluations`]: crate::plonk::verifier::VerifierBuilder::verify_with_evaluations
+#[allow(clippy::too_many_arguments)]
+pub fn create_proof_v2_with_queries<
+    'params,
+    Scheme: CommitmentScheme,
+    P: Prover<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    R: RngCore,
+    T: TranscriptWrite<Scheme::Curve, E>,
+    W: FnMut(
+        u8,
+        &HashMap<usize, Scheme::Scalar>,
+    ) -> Result<Vec<Vec<Option<Vec<Scheme::Scalar>>>>, Error>,
+>(
+    params: &'params Scheme::ParamsProver,
+    pk: &ProvingKey<Scheme::Curve>,
+    instances: &[&[&[Scheme::Scalar]]],
+    mut witness: W,
+    queries: &[ExtraQuery<Scheme::Scalar>],
+    rng: R,
+    transcript: &mut T,
+) -> Result<Vec<Vec<Scheme::Scalar>>, Error>
+where
+    Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    let mut prover = ProverV2::<Scheme, P, _, _, _>::new(params, pk, instances, rng, transcript)?;
+    let mut challenges = HashMap::new();
+    let phases = prover.phases().to_vec();
+    for phase in phases.iter() {
+        let witnesses = witness(phase.0, &challenges)?;
+        challenges = prover.commit_phase(phase.0, witnesses)?;
+    }
+    prover.create_proof_with_queries(queries)
+}
+
+/// Returns the polynomial of `column` in a circuit with the given `fixed`, `instance` and
+/// `advice` polynomials, and the blind of its commitment.
+fn column_poly<'a, C: CurveAffine>(
+    fixed_polys: &[&'a Polynomial<C::Scalar, Coeff>],
+    instance: &'a InstanceSingle<C>,
+    advice: &'a AdviceSingle<C, Coeff>,
+    column: Column<Any>,
+) -> (&'a Polynomial<C::Scalar, Coeff>, Blind<C::Scalar>) {
+    match column.column_type() {
+        Any::Advice(_) => (
+            &advice.advice_polys[column.index()],
+            advice.advice_blinds[column.index()],
+        ),
+        Any::Fixed => (fixed_polys[column.index()], Blind::default()),
+        Any::Instance => (
+            &instance.instance_polys[column.index()],
+            instance.instance_blinds[column.index()],
+        ),
+    }
+}
+
 /// The prover object used to create proofs interactively by passing the witnesses to commit at
 /// each phase.  This works for a single proof.  This is a wrapper over ProverV2.
 #[derive(Debug)]
@@ -82,6 +235,68 @@ impl<
         )?))
     }
 
+    /// Create a new prover object, which computes the multiscalar multiplications and
+    /// FFTs of the proof with `engine`.
+    pub fn new_with_engine(
+        engine: PlonkEngine<Scheme::Curve>,
+        params: &'params Scheme::ParamsProver,
+        pk: &'a ProvingKey<Scheme::Curve>,
+        instance: &[&[Scheme::Scalar]],
+        rng: R,
+        transcript: &'a mut T,
+    ) -> Result<Self, Error>
+    where
+        Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+    {
+        Ok(Self(ProverV2::new_with_engine(
+            engine,
+            params,
+            pk,
+            &[instance],
+            rng,
+            transcript,
+        )?))
+    }
+
+    /// Sets the external commitments of advice columns of the circuit, see
+    /// [`ProverV2::set_external_commitments`].
+    pub fn set_external_commitments(
+        &mut self,
+        commitments: &[ExternalCommitment<Scheme::Curve>],
+    ) -> Result<(), Error> {
+        self.0.set_external_commitments(&[commitments])
+    }
+
+    /// Sets `output` to receive the commitments to the pieces of `h(X)` and their blinds
+    /// when the proof is created, see [`ProverV2::output_vanishing_pieces`].
+    #[cfg(feature = "vanishing-pieces")]
+    pub fn output_vanishing_pieces(&mut self, output: &'a mut VanishingPieces<Scheme::Curve>) {
+        self.0.output_vanishing_pieces(output)
+    }
+
+    /// Sets the layout of the advice columns while `h(X)` is evaluated, see
+    /// [`ProverV2::set_witness_layout`].
+    pub fn set_witness_layout(&mut self, layout: WitnessLayout) {
+        self.0.set_witness_layout(layout)
+    }
+
+    /// Sets the order of the queries of the multiopen argument, see
+    /// [`ProverV2::set_query_order`].
+    pub fn set_query_order(&mut self, order: QueryOrder) {
+        self.0.set_query_order(order)
+    }
+
+    /// Commits to the permuted expressions of `injection` for the lookup with index
+    /// `lookup`, see [`ProverV2::inject_lookup_witness`].
+    #[cfg(feature = "lookup-injection")]
+    pub fn inject_lookup_witness(
+        &mut self,
+        lookup: usize,
+        injection: LookupInjection<Scheme::Scalar>,
+    ) -> Result<(), Error> {
+        self.0.inject_lookup_witness(lookup, injection)
+    }
+
     /// Commit the `witness` at `phase` and return the challenges after `phase`.
     pub fn commit_phase(
         &mut self,
@@ -94,6 +309,12 @@ impl<
         self.0.commit_phase(phase, vec![witness])
     }
 
+    /// Returns the values of the advice columns committed so far, see
+    /// [`ProverV2::committed_advice`].
+    pub fn committed_advice(&self) -> Vec<Option<&[Scheme::Scalar]>> {
+        self.0.committed_advice(0)
+    }
+
     /// Finalizes the proof creation.
     pub fn create_proof(self) -> Result<(), Error>
     where
@@ -101,6 +322,39 @@ impl<
     {
         self.0.create_proof()
     }
+
+    /// Finalizes the proof creation, opening the columns of the circuit at the points of
+    /// `queries` in addition to the queries of the circuit, and returns their evaluations.
+    pub fn create_proof_with_queries(
+        self,
+        queries: &[ExtraQuery<Scheme::Scalar>],
+    ) -> Result<Vec<Scheme::Scalar>, Error>
+    where
+        Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+    {
+        let mut evals = self.0.create_proof_with_queries(queries)?;
+        Ok(evals.pop().unwrap())
+    }
+
+    /// Finalizes the proof creation, and returns an evaluation proof of its openings, see
+    /// [`ProverV2::create_proof_with_evaluation_proof`].
+    pub fn create_proof_with_evaluation_proof(self) -> Result<EvaluationProof<Scheme::Curve>, Error>
+    where
+        Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+        T: TranscriptWriterBuffer<Vec<u8>, Scheme::Curve, E>,
+    {
+        self.0.create_proof_with_evaluation_proof()
+    }
+
+    /// Finalizes the proof creation, and returns the arguments of the sections of a split
+    /// proof, see [`ProverV2::create_proof_with_sections`].
+    pub fn create_proof_with_sections(self, sections: &ProofSections) -> Result<Vec<Vec<u8>>, Error>
+    where
+        Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+        T: TranscriptWriterBuffer<Vec<u8>, Scheme::Curve, E>,
+    {
+        self.0.create_proof_with_sections(sections)
+    }
 }
 
 /// The prover object used to create proofs interactively by passing the witnesses to commit at
@@ -117,6 +371,8 @@ pub struct ProverV2<
 > {
     // Circuit and setup fields
     params: &'params Scheme::ParamsProver,
+    // Engine of the multiscalar multiplications and FFTs
+    engine: PlonkEngine<Scheme::Curve>,
     // Plonk proving key
     pk: &'a ProvingKey<Scheme::Curve>,
     // Phases
@@ -124,11 +380,23 @@ pub struct ProverV2<
     // Polynomials (Lagrange and Coeff) for all circuits instances
     instances: Vec<InstanceSingle<Scheme::Curve>>,
     // Advice polynomials with its blindings
-    advices: Vec<AdviceSingle<Scheme::Curve, LagrangeCoeff>>,
+    advices: Wiped<Vec<AdviceSingle<Scheme::Curve, LagrangeCoeff>>>,
     // The phase challenges by challenge index
     challenges: HashMap<usize, Scheme::Scalar>,
     // The next phase to be committed
     next_phase_index: usize,
+    // External commitments by advice column index, for all circuits instances
+    external_commitments: Vec<ExternalCommitments<Scheme::Curve>>,
+    // Layout of the advice columns during the evaluation of h(X)
+    witness_layout: WitnessLayout,
+    // Order of the queries of the multiopen argument
+    query_order: QueryOrder,
+    // Permuted expressions committed instead of the honest ones, by lookup index
+    #[cfg(feature = "lookup-injection")]
+    lookup_injections: HashMap<usize, LookupInjection<Scheme::Scalar>>,
+    // Where to output the pieces of h(X), if requested
+    #[cfg(feature = "vanishing-pieces")]
+    vanishing_pieces: Option<&'a mut VanishingPieces<Scheme::Curve>>,
     // Transcript to be updated
     transcript: &'a mut T,
     // Randomness
@@ -159,6 +427,30 @@ impl<
     where
         Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
     {
+        Self::new_with_engine(
+            PlonkEngine::default(),
+            params,
+            pk,
+            circuits_instances,
+            rng,
+            transcript,
+        )
+    }
+
+    /// Create a new prover object, which computes the multiscalar multiplications and
+    /// FFTs of the proof with `engine`.
+    pub fn new_with_engine(
+        engine: PlonkEngine<Scheme::Curve>,
+        params: &'params Scheme::ParamsProver,
+        pk: &'a ProvingKey<Scheme::Curve>,
+        circuits_instances: &[&[&[Scheme::Scalar]]],
+        mut rng: R,
+        transcript: &'a mut T,
+    ) -> Result<Self, Error>
+    where
+        Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+    {
+        let _span = info_span!("commit_instances", circuits = circuits_instances.len()).entered();
         for instance in circuits_instances.iter() {
             if instance.len() != pk.vk.cs.num_instance_columns {
                 return Err(Error::InvalidInstances);
@@ -166,6 +458,7 @@ impl<
         }
 
         // Hash verification key into transcript [TRANSCRIPT-1]
+        transcript.label(TranscriptPhase::VerifyingKey, "verifying key");
         pk.vk.hash_into(transcript)?;
 
         let meta = &pk.vk.cs;
@@ -173,18 +466,31 @@ impl<
 
         let domain = &pk.vk.domain;
 
+        // The values of blinded instance columns can only be hidden if they are committed.
+        if !P::QUERY_INSTANCE && !meta.blinded_instance_columns.is_empty() {
+            return Err(Error::Other(
+                "blinded instance columns require a commitment scheme that commits to instance columns"
+                    .to_string(),
+            ));
+        }
+        let blinded_instance: HashSet<usize> =
+            HashSet::from_iter(meta.blinded_instance_columns.clone());
+
         // commit_instance_fn is a helper function to return the polynomials (and its commitments) of
         // instance columns while updating the transcript.
+        transcript.label(TranscriptPhase::Instances, "instances");
         let mut commit_instance_fn =
             |instance: &[&[Scheme::Scalar]]| -> Result<InstanceSingle<Scheme::Curve>, Error> {
                 // Create a lagrange polynomial for each instance column
 
+                let unusable_rows_start = params.n() as usize - (meta.blinding_factors() + 1);
                 let instance_values = instance
                     .iter()
-                    .map(|values| {
+                    .enumerate()
+                    .map(|(column_index, values)| {
                         let mut poly = domain.empty_lagrange();
                         assert_eq!(poly.len(), params.n() as usize);
-                        if values.len() > (poly.len() - (meta.blinding_factors() + 1)) {
+                        if values.len() > unusable_rows_start {
                             return Err(Error::InstanceTooLarge);
                         }
                         for (poly, value) in poly.iter_mut().zip(values.iter()) {
@@ -194,16 +500,37 @@ impl<
                             }
                             *poly = *value;
                         }
+                        // Add blinding factors to blinded instance columns.
+                        if blinded_instance.contains(&column_index) {
+                            for cell in &mut poly[unusable_rows_start..] {
+                                *cell = Scheme::Scalar::random(&mut rng);
+                            }
+                        }
                         Ok(poly)
                     })
                     .collect::<Result<Vec<_>, _>>()?;
 
+                let instance_blinds: Vec<_> = (0..instance_values.len())
+                    .map(|column_index| {
+                        if blinded_instance.contains(&column_index) {
+                            Blind(Scheme::Scalar::random(&mut rng))
+                        } else {
+                            Blind::default()
+                        }
+                    })
+                    .collect();
+
                 if P::QUERY_INSTANCE {
-                    // Add to the transcript the commitments of the instance lagrange polynomials
+                    // Add to the transcript the commitments of the instance lagrange polynomials.
+                    // The commitments of blinded instance columns can't be computed by the
+                    // verifier, and are written to the proof.
 
                     let instance_commitments_projective: Vec<_> = instance_values
                         .iter()
-                        .map(|poly| params.commit_lagrange(poly, Blind::default()))
+                        .zip(instance_blinds.iter())
+                        .map(|(poly, blind)| {
+                            params.commit_lagrange_with_engine(&*engine.msm_backend, poly, *blind)
+                        })
                         .collect();
                     let mut instance_commitments =
                         vec![Scheme::Curve::identity(); instance_commitments_projective.len()];
@@ -214,8 +541,12 @@ impl<
                     let instance_commitments = instance_commitments;
                     drop(instance_commitments_projective);
 
-                    for commitment in &instance_commitments {
-                        transcript.common_point(*commitment)?;
+                    for (column_index, commitment) in instance_commitments.iter().enumerate() {
+                        if blinded_instance.contains(&column_index) {
+                            transcript.write_point(*commitment)?;
+                        } else {
+                            transcript.common_point(*commitment)?;
+                        }
                     }
                 }
 
@@ -225,13 +556,14 @@ impl<
                     .iter()
                     .map(|poly| {
                         let lagrange_vec = domain.lagrange_from_vec(poly.to_vec());
-                        domain.lagrange_to_coeff(lagrange_vec)
+                        domain.lagrange_to_coeff_with_engine(&*engine.fft_backend, lagrange_vec)
                     })
                     .collect();
 
                 Ok(InstanceSingle {
                     instance_values,
                     instance_polys,
+                    instance_blinds,
                 })
             };
 
@@ -246,7 +578,7 @@ impl<
         // Create an structure to hold the advice polynomials and its blinds, it will be filled later in the
         // [`commit_phase`].
 
-        let advices = vec![
+        let advices = Wiped(vec![
             AdviceSingle::<Scheme::Curve, LagrangeCoeff> {
                 // Create vectors with empty polynomials to free space while they are not being used
                 advice_polys: vec![
@@ -256,7 +588,7 @@ impl<
                 advice_blinds: vec![Blind::default(); meta.num_advice_columns],
             };
             circuits_instances.len()
-        ];
+        ]);
 
         // Challenges will be also filled later in the [`commit_phase`].
 
@@ -264,6 +596,7 @@ impl<
 
         Ok(ProverV2 {
             params,
+            engine,
             pk,
             phases,
             instances,
@@ -272,10 +605,147 @@ impl<
             advices,
             challenges,
             next_phase_index: 0,
+            external_commitments: vec![HashMap::new(); circuits_instances.len()],
+            witness_layout: WitnessLayout::default(),
+            query_order: QueryOrder::default(),
+            #[cfg(feature = "lookup-injection")]
+            lookup_injections: HashMap::new(),
+            #[cfg(feature = "vanishing-pieces")]
+            vanishing_pieces: None,
             _marker: std::marker::PhantomData {},
         })
     }
 
+    /// Create a new prover object, after checking that the proofs of the circuits of `pk`
+    /// are within `limits`.
+    ///
+    /// The limits are checked before the instance polynomials are allocated.
+    pub fn new_with_limits(
+        params: &'params Scheme::ParamsProver,
+        pk: &'a ProvingKey<Scheme::Curve>,
+        circuits_instances: &[&[&[Scheme::Scalar]]],
+        limits: &ProverLimits,
+        rng: R,
+        transcript: &'a mut T,
+    ) -> Result<Self, Error>
+    where
+        Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+    {
+        limits.check(params.k(), &pk.vk.cs, circuits_instances.len())?;
+        Self::new(params, pk, circuits_instances, rng, transcript)
+    }
+
+    /// Create a new prover object for the application with the domain separation
+    /// `label`, which is absorbed into the transcript before the verifying key, see
+    /// [`absorb_domain`].
+    ///
+    /// The proof only verifies with [`VerifierBuilder::with_domain`] and the same label.
+    ///
+    /// [`VerifierBuilder::with_domain`]: crate::plonk::verifier::VerifierBuilder::with_domain
+    pub fn new_with_domain(
+        label: &[u8],
+        params: &'params Scheme::ParamsProver,
+        pk: &'a ProvingKey<Scheme::Curve>,
+        circuits_instances: &[&[&[Scheme::Scalar]]],
+        rng: R,
+        transcript: &'a mut T,
+    ) -> Result<Self, Error>
+    where
+        Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+    {
+        absorb_domain(transcript, label)?;
+        Self::new(params, pk, circuits_instances, rng, transcript)
+    }
+
+    /// Sets the external commitments of advice columns of each circuit, which are used
+    /// instead of committing to these columns, see [`ExternalCommitment`].
+    ///
+    /// This must be called before the first phase is committed.
+    pub fn set_external_commitments(
+        &mut self,
+        commitments: &[&[ExternalCommitment<Scheme::Curve>]],
+    ) -> Result<(), Error> {
+        if self.next_phase_index != 0 {
+            return Err(Error::Other(
+                "external commitments must be set before the first phase".to_string(),
+            ));
+        }
+        if commitments.len() != self.external_commitments.len() {
+            return Err(Error::Other(format!(
+                "expected external commitments for {} circuits, got {}",
+                self.external_commitments.len(),
+                commitments.len()
+            )));
+        }
+        let meta = &self.pk.vk.cs;
+        for (external, commitments) in self.external_commitments.iter_mut().zip(commitments) {
+            external.clear();
+            for commitment in commitments.iter() {
+                let index = commitment.column.index();
+                if index >= meta.num_advice_columns
+                    || !meta.unblinded_advice_columns.contains(&index)
+                {
+                    return Err(Error::Other(format!(
+                        "advice column {} is not an unblinded advice column of the circuit",
+                        index
+                    )));
+                }
+                if external.insert(index, *commitment).is_some() {
+                    return Err(Error::Other(format!(
+                        "advice column {} has several external commitments",
+                        index
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets `output` to receive the commitments to the pieces of `h(X)` and their blinds
+    /// when the proof is created.
+    #[cfg(feature = "vanishing-pieces")]
+    pub fn output_vanishing_pieces(&mut self, output: &'a mut VanishingPieces<Scheme::Curve>) {
+        self.vanishing_pieces = Some(output);
+    }
+
+    /// Sets the layout of the advice columns while `h(X)` is evaluated, which is
+    /// [`WitnessLayout::ColumnMajor`] by default. The proof doesn't depend on it.
+    pub fn set_witness_layout(&mut self, layout: WitnessLayout) {
+        self.witness_layout = layout;
+    }
+
+    /// Sets the order of the queries of the multiopen argument, which is
+    /// [`QueryOrder::canonical`] by default. The proof only verifies with the same order,
+    /// see [`VerifierBuilder::with_query_order`].
+    ///
+    /// [`VerifierBuilder::with_query_order`]: crate::plonk::verifier::VerifierBuilder::with_query_order
+    pub fn set_query_order(&mut self, order: QueryOrder) {
+        self.query_order = order;
+    }
+
+    /// Commits to the permuted expressions of `injection` for the lookup with index
+    /// `lookup`, in all the circuits, instead of the ones computed by sorting its inputs.
+    ///
+    /// This creates proofs that must not verify, to test the soundness of the lookup
+    /// argument, see [`LookupInjection`].
+    ///
+    /// Returns an error if the circuits have no lookup with index `lookup`.
+    #[cfg(feature = "lookup-injection")]
+    pub fn inject_lookup_witness(
+        &mut self,
+        lookup: usize,
+        injection: LookupInjection<Scheme::Scalar>,
+    ) -> Result<(), Error> {
+        if lookup >= self.pk.vk.cs.lookups.len() {
+            return Err(Error::Other(format!(
+                "the circuit has no lookup with index {}",
+                lookup
+            )));
+        }
+        self.lookup_injections.insert(lookup, injection);
+        Ok(())
+    }
+
     /// Commit the `witness` at `phase` and return the challenges after `phase`.
     #[allow(clippy::type_complexity)]
     pub fn commit_phase(
@@ -286,6 +756,8 @@ impl<
     where
         Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
     {
+        let _span = info_span!("commit_phase", phase, circuits = witness.len()).entered();
+
         // Check if the phase is valid.
 
         let current_phase = match self.phases.get(self.next_phase_index) {
@@ -302,12 +774,14 @@ impl<
         }
 
         let params = self.params;
+        let engine = &self.engine;
         let meta = &self.pk.vk.cs;
 
         let mut rng = &mut self.rng;
 
         let advices = &mut self.advices;
         let challenges = &mut self.challenges;
+        let external_commitments = &self.external_commitments;
 
         // Get the indices of the advice columns that are in the current phase.
 
@@ -368,19 +842,23 @@ impl<
             }
         }
 
-        // commit_phase_fn fills advice columns (no defined as unblinded) with binding factors,
-        // adding to the transcript its blinded affine commitments.
-        // Also sets advice_polys with the (blinding) updated advice columns and advice_blinds with
-        // the blinding factor used for each advice column.
+        // Fill the advice columns (not defined as unblinded) with blinding factors, and draw
+        // the blinding factor of each column. The randomness is drawn circuit by circuit, so
+        // that the proof doesn't depend on how the commitments below are scheduled.
+        // Columns with an external commitment use its blind.
 
-        let mut commit_phase_fn =
-            |advice: &mut AdviceSingle<Scheme::Curve, LagrangeCoeff>,
-             witness: Vec<Option<Polynomial<Scheme::Scalar, LagrangeCoeff>>>|
-             -> Result<(), Error> {
-                let unusable_rows_start = params.n() as usize - (meta.blinding_factors() + 1);
-                let mut advice_values: Vec<_> = witness.into_iter().flatten().collect();
-                let unblinded_advice: HashSet<usize> =
-                    HashSet::from_iter(meta.unblinded_advice_columns.clone());
+        let unusable_rows_start = params.n() as usize - (meta.blinding_factors() + 1);
+        let unblinded_advice: HashSet<usize> =
+            HashSet::from_iter(meta.unblinded_advice_columns.clone());
+        let blinded: Vec<(Vec<Polynomial<Scheme::Scalar, LagrangeCoeff>>, Vec<_>)> = witness
+            .into_iter()
+            .zip(external_commitments.iter())
+            .map(|(witness, external)| {
+                let mut advice_values: Vec<_> = witness
+                    .into_iter()
+                    .flatten()
+                    .map(Polynomial::new_lagrange_from_vec)
+                    .collect();
 
                 // Add blinding factors to advice columns.
                 for (column_index, advice_values) in column_indices.iter().zip(&mut advice_values) {
@@ -396,63 +874,89 @@ impl<
                     }
                 }
 
-                // Compute commitments to advice column polynomials
                 let blinds: Vec<_> = column_indices
                     .iter()
                     .map(|i| {
-                        if unblinded_advice.contains(i) {
+                        if let Some(external) = external.get(i) {
+                            external.blind
+                        } else if unblinded_advice.contains(i) {
                             Blind::default()
                         } else {
                             Blind(Scheme::Scalar::random(&mut rng))
                         }
                     })
                     .collect();
-                let advice_commitments_projective: Vec<_> = advice_values
-                    .iter()
-                    .zip(blinds.iter())
-                    .map(|(poly, blind)| params.commit_lagrange(poly, *blind))
-                    .collect();
-                let mut advice_commitments_affine =
-                    vec![Scheme::Curve::identity(); advice_commitments_projective.len()];
-                <Scheme::Curve as CurveAffine>::CurveExt::batch_normalize(
-                    &advice_commitments_projective,
-                    &mut advice_commitments_affine,
-                );
-                let advice_commitments_affine = advice_commitments_affine;
-                drop(advice_commitments_projective);
-
-                // Update transcript.
-                // [TRANSCRIPT-3]
-                for commitment in &advice_commitments_affine {
-                    self.transcript.write_point(*commitment)?;
-                }
+                (advice_values, blinds)
+            })
+            .collect();
 
-                // Set advice_polys & advice_blinds
-                for ((column_index, advice_values), blind) in
-                    column_indices.iter().zip(advice_values).zip(blinds)
-                {
-                    advice.advice_polys[*column_index] = advice_values;
-                    advice.advice_blinds[*column_index] = blind;
-                }
-                Ok(())
-            };
+        // Compute commitments to advice column polynomials. The circuits are independent
+        // of each other, so all their columns are committed in parallel.
+        let commitments_projective: Vec<Vec<_>> = blinded
+            .par_iter()
+            .map(|(advice_values, blinds)| {
+                advice_values
+                    .par_iter()
+                    .zip(blinds.par_iter())
+                    .map(|(poly, blind)| {
+                        params.commit_lagrange_with_engine(&*engine.msm_backend, poly, *blind)
+                    })
+                    .collect()
+            })
+            .collect();
 
-        // Update blindings for each advice column
+        // Update blindings for each advice column, adding to the transcript its blinded
+        // affine commitments. Also sets advice_polys with the (blinding) updated advice
+        // columns and advice_blinds with the blinding factor used for each advice column.
+        // Columns with an external commitment are checked against it.
         // [TRANSCRIPT-3]
 
-        for (witness, advice) in witness.into_iter().zip(advices.iter_mut()) {
-            commit_phase_fn(
-                advice,
-                witness
-                    .into_iter()
-                    .map(|v| v.map(Polynomial::new_lagrange_from_vec))
-                    .collect(),
-            )?;
+        for (((advice_values, blinds), commitments_projective), (advice, external)) in blinded
+            .into_iter()
+            .zip(commitments_projective)
+            .zip(advices.iter_mut().zip(external_commitments.iter()))
+        {
+            let mut advice_commitments_affine =
+                vec![Scheme::Curve::identity(); commitments_projective.len()];
+            <Scheme::Curve as CurveAffine>::CurveExt::batch_normalize(
+                &commitments_projective,
+                &mut advice_commitments_affine,
+            );
+
+            for (column_index, commitment) in column_indices.iter().zip(&advice_commitments_affine)
+            {
+                if let Some(external) = external.get(column_index) {
+                    if external.commitment != *commitment {
+                        return Err(Error::Other(format!(
+                            "the external commitment of advice column {} doesn't match its values",
+                            column_index
+                        )));
+                    }
+                }
+            }
+
+            self.transcript.label(
+                TranscriptPhase::Advice(current_phase.0),
+                "advice commitments",
+            );
+            for commitment in &advice_commitments_affine {
+                self.transcript.write_point(*commitment)?;
+            }
+
+            // Set advice_polys & advice_blinds
+            for ((column_index, advice_values), blind) in
+                column_indices.iter().zip(advice_values).zip(blinds)
+            {
+                advice.advice_polys[*column_index] = advice_values;
+                advice.advice_blinds[*column_index] = blind;
+            }
         }
 
         // Squeeze the current transcript and get an new fresh challenge from the current phase.
         // [TRANSCRIPT-4]
 
+        self.transcript
+            .label(TranscriptPhase::Advice(current_phase.0), "challenges");
         for (index, phase) in meta.challenge_phase.iter().enumerate() {
             if current_phase == phase {
                 let existing =
@@ -483,19 +987,131 @@ impl<
     /// - 12. Evaluate permutation, lookups and shuffles at x
     /// - 13. Generate all queries ([`PowerQuery`])
     /// - 14. Send the queries to the [`Prover`]  
-    pub fn create_proof(mut self) -> Result<(), Error>
+    ///
+    /// The proof is created in an `info` span, and each group of steps in a `debug` span.
+    pub fn create_proof(self) -> Result<(), Error>
+    where
+        Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+    {
+        self.create_proof_with_queries(&[]).map(|_| ())
+    }
+
+    /// Finalizes the proof creation like [`Self::create_proof`], opening the columns of
+    /// each circuit at the points of `queries` in addition to the queries of the circuit.
+    ///
+    /// Returns the evaluations of `queries` for each circuit.
+    pub fn create_proof_with_queries(
+        self,
+        queries: &[ExtraQuery<Scheme::Scalar>],
+    ) -> Result<Vec<Vec<Scheme::Scalar>>, Error>
+    where
+        Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+    {
+        self.create_proof_inner(queries, None, None)
+    }
+
+    /// Finalizes the proof creation like [`Self::create_proof`], and returns an
+    /// [`EvaluationProof`] of the openings of the proof, which can be verified on its own
+    /// by light clients.
+    ///
+    /// The evaluation proof is created with a new transcript of the same type as the one
+    /// of the proof, and proves the openings in the order of [`VerifierTrace::openings`].
+    ///
+    /// [`VerifierTrace::openings`]: crate::plonk::verifier::VerifierTrace::openings
+    pub fn create_proof_with_evaluation_proof(self) -> Result<EvaluationProof<Scheme::Curve>, Error>
+    where
+        Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+        T: TranscriptWriterBuffer<Vec<u8>, Scheme::Curve, E>,
+    {
+        let params = self.params;
+        let mut evaluation_proof = None;
+        self.create_proof_inner(
+            &[],
+            Some(
+                &mut |queries: &[ProverQuery<'_, Scheme::Curve>], rng: &mut R| {
+                    evaluation_proof = Some(create_evaluation_proof::<Scheme, P, E, _, T>(
+                        params, queries, rng,
+                    )?);
+                    Ok(())
+                },
+            ),
+            None,
+        )?;
+        Ok(evaluation_proof.expect("the evaluation proof is created with the proof"))
+    }
+
+    /// Finalizes the proof creation like [`Self::create_proof`], and returns the
+    /// arguments of the sections of a split proof, see [`ProofSections`].
+    ///
+    /// The proof written to the transcript ends with the seed of the sections instead of
+    /// a multiopen argument, and the argument of each section is created with a new
+    /// transcript of the same type. The argument of a section without queries is empty.
+    pub fn create_proof_with_sections(self, sections: &ProofSections) -> Result<Vec<Vec<u8>>, Error>
+    where
+        Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+        T: TranscriptWriterBuffer<Vec<u8>, Scheme::Curve, E>,
+    {
+        let params = self.params;
+        let mut proofs = None;
+        self.create_proof_inner(
+            &[],
+            None,
+            Some((
+                sections,
+                &mut |seed: Scheme::Scalar,
+                      query_sets: &[Vec<ProverQuery<'_, Scheme::Curve>>],
+                      rng: &mut R| {
+                    proofs = Some(create_section_proofs::<Scheme, P, E, _, T>(
+                        params, seed, query_sets, rng,
+                    )?);
+                    Ok(())
+                },
+            )),
+        )?;
+        Ok(proofs.expect("the arguments of the sections are created with the proof"))
+    }
+
+    /// Finalizes the proof creation, and passes the queries of the multiopen argument to
+    /// `open_queries` once it is created. With `sections`, the proof is split and the
+    /// queries of each section are passed to the callback of `sections` instead.
+    #[allow(clippy::type_complexity)]
+    fn create_proof_inner(
+        mut self,
+        queries: &[ExtraQuery<Scheme::Scalar>],
+        open_queries: Option<
+            &mut dyn FnMut(&[ProverQuery<'_, Scheme::Curve>], &mut R) -> Result<(), Error>,
+        >,
+        sections: Option<(
+            &ProofSections,
+            &mut dyn FnMut(
+                Scheme::Scalar,
+                &[Vec<ProverQuery<'_, Scheme::Curve>>],
+                &mut R,
+            ) -> Result<(), Error>,
+        )>,
+    ) -> Result<Vec<Vec<Scheme::Scalar>>, Error>
     where
         Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
     {
+        let _span = info_span!("create_proof", circuits = self.instances.len()).entered();
         let params = self.params;
+        let engine = &self.engine;
         let cs = &self.pk.vk.cs;
         let pk = self.pk;
         let domain = &self.pk.vk.domain;
+        check_extra_queries(queries, cs, params.n(), P::QUERY_INSTANCE)?;
+
+        // The polynomials of the sparse fixed columns are computed once for all the circuits.
+        let expanded_fixed = pk.expand_sparse_fixed(engine);
+        let fixed = pk.fixed_polynomials(&expanded_fixed)?;
+        let fixed_values: Vec<_> = fixed.iter().map(|column| &column.values).collect();
+        let fixed_polys: Vec<_> = fixed.iter().map(|column| &*column.poly).collect();
+        let fixed_cosets: Vec<_> = fixed.iter().map(|column| &*column.coset).collect();
 
         let mut rng = self.rng;
 
         let instances = std::mem::take(&mut self.instances);
-        let advices = std::mem::take(&mut self.advices);
+        let mut advices = std::mem::take(&mut self.advices);
         let mut challenges = self.challenges;
 
         assert_eq!(challenges.len(), cs.num_challenges);
@@ -504,155 +1120,229 @@ impl<
             .collect::<Vec<_>>();
 
         // 1. Generate commited ( added to transcript ) lookup polys  ---------------------------------------
+        let span = debug_span!("lookups_permuted").entered();
 
         // Sample theta challenge for keeping lookup columns linearly independent
         // [TRANSCRIPT-5]
 
+        self.transcript.label(TranscriptPhase::Lookups, "theta");
         let theta: ChallengeTheta<_> = self.transcript.squeeze_challenge_scalar();
 
+        // Tables that don't depend on the witness are compressed once for all the circuits.
+        let shared_tables: Vec<_> = cs
+            .lookups
+            .iter()
+            .map(|lookup| {
+                compress_shared_table(lookup, pk, params, theta, &fixed_values, &challenges)
+            })
+            .collect();
+
         // 2. Get permuted lookup polys
         // [TRANSCRIPT-6]
 
+        self.transcript
+            .label(TranscriptPhase::Lookups, "permuted commitments");
+        #[cfg(feature = "lookup-injection")]
+        let lookup_injections = std::mem::take(&mut self.lookup_injections);
         let mut lookups_fn =
             |instance: &InstanceSingle<Scheme::Curve>,
              advice: &AdviceSingle<Scheme::Curve, LagrangeCoeff>|
              -> Result<Vec<lookup::prover::Permuted<Scheme::Curve>>, Error> {
-                cs.lookups
-                    .iter()
-                    .map(|lookup| {
+                (0..cs.lookups.len())
+                    .map(|index| {
                         lookup_commit_permuted(
-                            lookup,
+                            &cs.lookups[index],
                             pk,
                             params,
+                            engine,
                             domain,
                             theta,
                             &advice.advice_polys,
-                            &pk.fixed_values,
+                            &fixed_values,
                             &instance.instance_values,
                             &challenges,
+                            shared_tables[index].as_ref(),
+                            #[cfg(feature = "lookup-injection")]
+                            lookup_injections.get(&index),
                             &mut rng,
                             self.transcript,
                         )
                     })
                     .collect::<Result<Vec<_>, _>>()
             };
-        let permuted_lookups: Vec<Vec<lookup::prover::Permuted<Scheme::Curve>>> = instances
-            .iter()
-            .zip(advices.iter())
-            .map(|(instance, advice)| -> Result<Vec<_>, Error> {
-                // Construct and commit to permuted values for each lookup
-                lookups_fn(instance, advice)
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+        let mut permuted_lookups: Wiped<Vec<Vec<lookup::prover::Permuted<Scheme::Curve>>>> = Wiped(
+            instances
+                .iter()
+                .zip(advices.iter())
+                .map(|(instance, advice)| -> Result<Vec<_>, Error> {
+                    // Construct and commit to permuted values for each lookup
+                    lookups_fn(instance, advice)
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+        span.exit();
 
         // Sample beta challenge
         // [TRANSCRIPT-7]
+        self.transcript.label(TranscriptPhase::Permutation, "beta");
         let beta: ChallengeBeta<_> = self.transcript.squeeze_challenge_scalar();
 
         // Sample gamma challenge
         // [TRANSCRIPT-8]
+        self.transcript.label(TranscriptPhase::Permutation, "gamma");
         let gamma: ChallengeGamma<_> = self.transcript.squeeze_challenge_scalar();
 
         // 2. Generate commited permutation polys  -----------------------------------------
         // [TRANSCRIPT-9]
-        let permutations_commited: Vec<permutation::prover::Committed<Scheme::Curve>> = instances
-            .iter()
-            .zip(advices.iter())
-            .map(|(instance, advice)| {
-                permutation_commit(
-                    &cs.permutation,
-                    params,
-                    pk,
-                    &pk.permutation,
-                    &advice.advice_polys,
-                    &pk.fixed_values,
-                    &instance.instance_values,
-                    beta,
-                    gamma,
-                    &mut rng,
-                    self.transcript,
-                )
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+        let span = debug_span!("permutation_commit").entered();
+        self.transcript
+            .label(TranscriptPhase::Permutation, "product commitments");
+        // The factors of the fixed columns are computed once for all the circuits.
+        let permutation_shared = permutation_shared_factors(
+            &cs.permutation,
+            pk,
+            &pk.permutation,
+            &fixed_values,
+            beta,
+            gamma,
+        );
+        let mut permutations_commited: Wiped<Vec<permutation::prover::Committed<Scheme::Curve>>> =
+            Wiped(
+                instances
+                    .iter()
+                    .zip(advices.iter())
+                    .map(|(instance, advice)| {
+                        permutation_commit(
+                            &cs.permutation,
+                            params,
+                            engine,
+                            pk,
+                            &pk.permutation,
+                            &advice.advice_polys,
+                            &instance.instance_values,
+                            &permutation_shared,
+                            beta,
+                            gamma,
+                            &mut rng,
+                            self.transcript,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            );
+        span.exit();
 
         // 3. Generate commited lookup polys ----------------------------------------------------------
 
         // [TRANSCRIPT-10]
-        let lookups_commited: Vec<Vec<lookup::prover::Committed<Scheme::Curve>>> = permuted_lookups
-            .into_iter()
-            .map(|lookups| -> Result<Vec<_>, _> {
-                // Construct and commit to products for each lookup
-                lookups
-                    .into_iter()
-                    .map(|lookup| {
-                        lookup.commit_product(pk, params, beta, gamma, &mut rng, self.transcript)
-                    })
-                    .collect::<Result<Vec<_>, _>>()
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+        let span = debug_span!("lookups_commit").entered();
+        self.transcript
+            .label(TranscriptPhase::Lookups, "product commitments");
+        let mut lookups_commited: Wiped<Vec<Vec<lookup::prover::Committed<Scheme::Curve>>>> = Wiped(
+            std::mem::take(&mut *permuted_lookups)
+                .into_iter()
+                .map(|lookups| -> Result<Vec<_>, _> {
+                    // Construct and commit to products for each lookup
+                    lookups
+                        .into_iter()
+                        .map(|lookup| {
+                            lookup.commit_product(
+                                pk,
+                                params,
+                                engine,
+                                beta,
+                                gamma,
+                                &mut rng,
+                                self.transcript,
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+        span.exit();
 
         // 4. Generate commited shuffle polys  -------------------------------------------------------
 
         // [TRANSCRIPT-11]
-        let shuffles_commited: Vec<Vec<shuffle::prover::Committed<Scheme::Curve>>> = instances
-            .iter()
-            .zip(advices.iter())
-            .map(|(instance, advice)| -> Result<Vec<_>, _> {
-                // Compress expressions for each shuffle
-                cs.shuffles
+        let span = debug_span!("shuffles_commit").entered();
+        self.transcript
+            .label(TranscriptPhase::Shuffles, "product commitments");
+        let mut shuffles_commited: Wiped<Vec<Vec<shuffle::prover::Committed<Scheme::Curve>>>> =
+            Wiped(
+                instances
                     .iter()
-                    .map(|shuffle| {
-                        shuffle_commit_product(
-                            shuffle,
-                            pk,
-                            params,
-                            domain,
-                            theta,
-                            gamma,
-                            &advice.advice_polys,
-                            &pk.fixed_values,
-                            &instance.instance_values,
-                            &challenges,
-                            &mut rng,
-                            self.transcript,
-                        )
+                    .zip(advices.iter())
+                    .map(|(instance, advice)| -> Result<Vec<_>, _> {
+                        // Compress expressions for each shuffle
+                        cs.shuffles
+                            .iter()
+                            .map(|shuffle| {
+                                shuffle_commit_product(
+                                    shuffle,
+                                    pk,
+                                    params,
+                                    engine,
+                                    domain,
+                                    theta,
+                                    gamma,
+                                    &advice.advice_polys,
+                                    &fixed_values,
+                                    &instance.instance_values,
+                                    &challenges,
+                                    &mut rng,
+                                    self.transcript,
+                                )
+                            })
+                            .collect::<Result<Vec<_>, _>>()
                     })
-                    .collect::<Result<Vec<_>, _>>()
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+                    .collect::<Result<Vec<_>, _>>()?,
+            );
+        span.exit();
 
         // 5. Commit to the vanishing argument's random polynomial for blinding h(x_3) -------------------
         // [TRANSCRIPT-12]
-        let vanishing = vanishing::Argument::commit(params, domain, &mut rng, self.transcript)?;
+        self.transcript
+            .label(TranscriptPhase::Vanishing, "random commitment");
+        let vanishing =
+            vanishing::Argument::commit(params, engine, domain, &mut rng, self.transcript)?;
 
         // 6. Generate the advice polys ------------------------------------------------------------------
+        let span = debug_span!("vanishing").entered();
 
-        let advice: Vec<AdviceSingle<Scheme::Curve, Coeff>> = advices
-            .into_iter()
-            .map(
-                |AdviceSingle {
-                     advice_polys,
-                     advice_blinds,
-                 }| {
-                    AdviceSingle {
-                        advice_polys: advice_polys
-                            .into_iter()
-                            .map(|poly| domain.lagrange_to_coeff(poly))
-                            .collect::<Vec<_>>(),
-                        advice_blinds,
-                    }
-                },
-            )
-            .collect();
+        let advice: Wiped<Vec<AdviceSingle<Scheme::Curve, Coeff>>> = Wiped(
+            std::mem::take(&mut *advices)
+                .into_iter()
+                .map(
+                    |AdviceSingle {
+                         advice_polys,
+                         advice_blinds,
+                     }| {
+                        AdviceSingle {
+                            advice_polys: advice_polys
+                                .into_iter()
+                                .map(|poly| {
+                                    domain.lagrange_to_coeff_with_engine(&*engine.fft_backend, poly)
+                                })
+                                .collect::<Vec<_>>(),
+                            advice_blinds,
+                        }
+                    },
+                )
+                .collect(),
+        );
 
         // 7. Evaluate the h(X) polynomial -----------------------------------------------------------
 
         // Obtain challenge for keeping all separate gates linearly independent
         // [TRANSCRIPT-13]
+        self.transcript.label(TranscriptPhase::Vanishing, "y");
         let y: ChallengeY<_> = self.transcript.squeeze_challenge_scalar();
 
         let h_poly = pk.ev.evaluate_h(
             pk,
+            engine,
+            self.witness_layout,
+            &fixed_cosets,
             &advice
                 .iter()
                 .map(|a| a.advice_polys.as_slice())
@@ -669,19 +1359,31 @@ impl<
             &lookups_commited,
             &shuffles_commited,
             &permutations_commited,
-        );
+        )?;
 
         // 8. Construct the vanishing argument's h(X) commitments --------------------------------------
         // [TRANSCRIPT-14]
-        let vanishing = vanishing.construct(params, domain, h_poly, &mut rng, self.transcript)?;
+        self.transcript
+            .label(TranscriptPhase::Vanishing, "h commitments");
+        let vanishing =
+            vanishing.construct(params, engine, domain, h_poly, &mut rng, self.transcript)?;
+        #[cfg(feature = "vanishing-pieces")]
+        if let Some(output) = self.vanishing_pieces.take() {
+            *output = vanishing.pieces();
+        }
+        span.exit();
 
         // 9. Compute x  --------------------------------------------------------------------------------
         // [TRANSCRIPT-15]
+        let span = debug_span!("evaluations").entered();
+        self.transcript.label(TranscriptPhase::Evaluations, "x");
         let x: ChallengeX<_> = self.transcript.squeeze_challenge_scalar();
 
         let x_pow_n = x.pow([params.n()]);
 
         // [TRANSCRIPT-16]
+        self.transcript
+            .label(TranscriptPhase::Evaluations, "instance evaluations");
         if P::QUERY_INSTANCE {
             // Compute and hash instance evals for the circuit instance
             for instance in instances.iter() {
@@ -706,6 +1408,8 @@ impl<
 
         // 10. Compute and hash advice evals for the circuit instance ------------------------------------
         // [TRANSCRIPT-17]
+        self.transcript
+            .label(TranscriptPhase::Evaluations, "advice evaluations");
         for advice in advice.iter() {
             // Evaluate polynomials at omega^i x
             let advice_evals: Vec<_> = cs
@@ -730,39 +1434,53 @@ impl<
             .fixed_queries
             .iter()
             .map(|&(column, at)| {
-                eval_polynomial(&pk.fixed_polys[column.index()], domain.rotate_omega(*x, at))
+                eval_polynomial(fixed_polys[column.index()], domain.rotate_omega(*x, at))
             })
             .collect();
 
         // Hash each fixed column evaluation
         // [TRANSCRIPT-18]
+        self.transcript
+            .label(TranscriptPhase::Evaluations, "fixed evaluations");
         for eval in fixed_evals.iter() {
             self.transcript.write_scalar(*eval)?;
         }
 
         // [TRANSCRIPT-19]
-        let vanishing = vanishing.evaluate(x, x_pow_n, domain, self.transcript)?;
+        self.transcript
+            .label(TranscriptPhase::Evaluations, "vanishing evaluations");
+        let vanishing = Wiped(vanishing.evaluate(x, x_pow_n, domain, self.transcript)?);
 
         // 12. Evaluate permutation, lookups and shuffles at x -----------------------------------
 
         // Evaluate common permutation data
         // [TRANSCRIPT-20]
+        self.transcript.label(
+            TranscriptPhase::Evaluations,
+            "permutation common evaluations",
+        );
         pk.permutation.evaluate(x, self.transcript)?;
 
         // Evaluate the permutations, if any, at omega^i x.
         // [TRANSCRIPT-21]
-        let permutations_evaluated: Vec<permutation::prover::Evaluated<Scheme::Curve>> =
-            permutations_commited
-                .into_iter()
-                .map(|permutation| -> Result<_, _> {
-                    permutation.construct().evaluate(pk, x, self.transcript)
-                })
-                .collect::<Result<Vec<_>, _>>()?;
+        self.transcript
+            .label(TranscriptPhase::Evaluations, "permutation evaluations");
+        let permutations_evaluated: Wiped<Vec<permutation::prover::Evaluated<Scheme::Curve>>> =
+            Wiped(
+                std::mem::take(&mut *permutations_commited)
+                    .into_iter()
+                    .map(|permutation| -> Result<_, _> {
+                        permutation.construct().evaluate(pk, x, self.transcript)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            );
 
         // Evaluate the lookups, if any, at omega^i x.
         // [TRANSCRIPT-22]
-        let lookups_evaluated: Vec<Vec<lookup::prover::Evaluated<Scheme::Curve>>> =
-            lookups_commited
+        self.transcript
+            .label(TranscriptPhase::Evaluations, "lookup evaluations");
+        let lookups_evaluated: Wiped<Vec<Vec<lookup::prover::Evaluated<Scheme::Curve>>>> = Wiped(
+            std::mem::take(&mut *lookups_commited)
                 .into_iter()
                 .map(|lookups| -> Result<Vec<_>, _> {
                     lookups
@@ -770,12 +1488,15 @@ impl<
                         .map(|p| p.evaluate(pk, x, self.transcript))
                         .collect::<Result<Vec<_>, _>>()
                 })
-                .collect::<Result<Vec<_>, _>>()?;
+                .collect::<Result<Vec<_>, _>>()?,
+        );
 
         // Evaluate the shuffles, if any, at omega^i x.
         // [TRANSCRIPT-23]
-        let shuffles_evaluated: Vec<Vec<shuffle::prover::Evaluated<Scheme::Curve>>> =
-            shuffles_commited
+        self.transcript
+            .label(TranscriptPhase::Evaluations, "shuffle evaluations");
+        let shuffles_evaluated: Wiped<Vec<Vec<shuffle::prover::Evaluated<Scheme::Curve>>>> = Wiped(
+            std::mem::take(&mut *shuffles_commited)
                 .into_iter()
                 .map(|shuffles| -> Result<Vec<_>, _> {
                     shuffles
@@ -783,73 +1504,163 @@ impl<
                         .map(|p| p.evaluate(pk, x, self.transcript))
                         .collect::<Result<Vec<_>, _>>()
                 })
-                .collect::<Result<Vec<_>, _>>()?;
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+
+        // Compute and hash the evaluations of the extra queries.
+        // [TRANSCRIPT-24]
+        self.transcript
+            .label(TranscriptPhase::Evaluations, "extra queries");
+        for query in queries {
+            self.transcript.common_scalar(query.point())?;
+        }
+        let extra_evals = instances
+            .iter()
+            .zip(advice.iter())
+            .map(|(instance, advice)| -> Result<Vec<_>, Error> {
+                queries
+                    .iter()
+                    .map(|query| -> Result<_, Error> {
+                        let (poly, _) = column_poly(&fixed_polys, instance, advice, query.column());
+                        let eval = eval_polynomial(poly, query.point());
+                        self.transcript.write_scalar(eval)?;
+                        Ok(eval)
+                    })
+                    .collect()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        span.exit();
 
         // 13. Generate all queries ([`PowerQuery`]) that needs to be sent to prover  --------------------
 
-        let queries = instances
-            // group the instance, advice, permutation, lookups and shuffles
+        // A split proof arranges the queries of each of its sections, instead of those of
+        // the proof in its query order.
+        let orders = match &sections {
+            Some((sections, _)) => sections.orders(),
+            None => std::slice::from_ref(&self.query_order),
+        };
+        let mut query_sets = orders
             .iter()
-            .zip(advice.iter())
-            .zip(permutations_evaluated.iter())
-            .zip(lookups_evaluated.iter())
-            .zip(shuffles_evaluated.iter())
-            .flat_map(|((((instance, advice), permutation), lookups), shuffles)| {
-                // Build a (an iterator) over a set of ProverQueries for each instance, advice, permutatiom, lookup and shuffle
-                iter::empty()
-                    // Instances
-                    .chain(
-                        P::QUERY_INSTANCE
-                            .then_some(cs.instance_queries.iter().map(move |&(column, at)| {
+            .map(|order| {
+                order.arrange(
+                    instances.len(),
+                    |group, circuit, out| {
+                        let (instance, advice) = (&instances[circuit], &advice[circuit]);
+                        match group {
+                            QueryGroup::Instance => {
+                                if P::QUERY_INSTANCE {
+                                    out.extend(cs.instance_queries.iter().map(|&(column, at)| {
+                                        ProverQuery {
+                                            point: domain.rotate_omega(*x, at),
+                                            poly: &instance.instance_polys[column.index()],
+                                            blind: instance.instance_blinds[column.index()],
+                                        }
+                                    }))
+                                }
+                            }
+                            QueryGroup::Advice => {
+                                out.extend(cs.advice_queries.iter().map(|&(column, at)| {
+                                    ProverQuery {
+                                        point: domain.rotate_omega(*x, at),
+                                        poly: &advice.advice_polys[column.index()],
+                                        blind: advice.advice_blinds[column.index()],
+                                    }
+                                }))
+                            }
+                            QueryGroup::Permutation => {
+                                out.extend(permutations_evaluated[circuit].open(pk, x))
+                            }
+                            QueryGroup::Lookups => out.extend(
+                                lookups_evaluated[circuit]
+                                    .iter()
+                                    .flat_map(|p| p.open(pk, x)),
+                            ),
+                            QueryGroup::Shuffles => out.extend(
+                                shuffles_evaluated[circuit]
+                                    .iter()
+                                    .flat_map(|p| p.open(pk, x)),
+                            ),
+                            QueryGroup::ExtraQueries => out.extend(queries.iter().map(|query| {
+                                let (poly, blind) =
+                                    column_poly(&fixed_polys, instance, advice, query.column());
                                 ProverQuery {
-                                    point: domain.rotate_omega(*x, at),
-                                    poly: &instance.instance_polys[column.index()],
-                                    blind: Blind::default(),
+                                    point: query.point(),
+                                    poly,
+                                    blind,
                                 }
-                            }))
-                            .into_iter()
-                            .flatten(),
-                    )
-                    // Advices
-                    .chain(
-                        cs.advice_queries
-                            .iter()
-                            .map(move |&(column, at)| ProverQuery {
+                            })),
+                            group => unreachable!("{:?} is not a per-circuit group", group),
+                        }
+                    },
+                    |group, out| match group {
+                        // Queries to fixed columns
+                        QueryGroup::Fixed => {
+                            out.extend(cs.fixed_queries.iter().map(|&(column, at)| ProverQuery {
                                 point: domain.rotate_omega(*x, at),
-                                poly: &advice.advice_polys[column.index()],
-                                blind: advice.advice_blinds[column.index()],
-                            }),
-                    )
-                    // Permutations
-                    .chain(permutation.open(pk, x))
-                    // Lookups
-                    .chain(lookups.iter().flat_map(move |p| p.open(pk, x)))
-                    // Shuffles
-                    .chain(shuffles.iter().flat_map(move |p| p.open(pk, x)))
+                                poly: fixed_polys[column.index()],
+                                blind: Blind::default(),
+                            }))
+                        }
+                        // Copy constraints
+                        QueryGroup::PermutationCommon => out.extend(pk.permutation.open(x)),
+                        // We query the h(X) polynomial at x
+                        QueryGroup::Vanishing => out.extend(vanishing.open(x)),
+                        group => unreachable!("{:?} is a per-circuit group", group),
+                    },
+                )
             })
-            // Queries to fixed columns
-            .chain(cs.fixed_queries.iter().map(|&(column, at)| ProverQuery {
-                point: domain.rotate_omega(*x, at),
-                poly: &pk.fixed_polys[column.index()],
-                blind: Blind::default(),
-            }))
-            // Copy constraints
-            .chain(pk.permutation.open(x))
-            // We query the h(X) polynomial at x
-            .chain(vanishing.open(x));
+            .collect::<Vec<_>>();
 
         // 14. Send the queries to the [`Prover`]  ------------------------------------------------
+        let _span = debug_span!("multiopen").entered();
+
+        if let Some((_, open_sections)) = sections {
+            // [TRANSCRIPT-25]
+            self.transcript
+                .label(TranscriptPhase::Multiopen, "section seed");
+            let seed = self.transcript.squeeze_challenge().get_scalar();
+            open_sections(seed, &query_sets, &mut rng)?;
+            return Ok(extra_evals);
+        }
+        let queries = query_sets.pop().expect("a proof has one query order");
 
         let prover = P::new(params);
+        self.transcript
+            .label(TranscriptPhase::Multiopen, "opening proof");
         prover
-            .create_proof(rng, self.transcript, queries)
+            .create_proof_with_engine(
+                &*engine.msm_backend,
+                &mut rng,
+                self.transcript,
+                queries.iter().copied(),
+            )
             .map_err(|_| Error::ConstraintSystemFailure)?;
+        if let Some(open_queries) = open_queries {
+            open_queries(&queries, &mut rng)?;
+        }
 
-        Ok(())
+        Ok(extra_evals)
     }
 
     /// Returns the phases of the circuit
     pub fn phases(&'a self) -> &'a [sealed::Phase] {
         self.phases.as_slice()
     }
+
+    /// Returns the values of the advice columns of `circuit` committed so far, by column
+    /// index, or `None` for the columns of the phases not committed yet.
+    ///
+    /// The rows after the usable ones hold the blinding factors.
+    pub fn committed_advice(&self, circuit: usize) -> Vec<Option<&[Scheme::Scalar]>> {
+        let committed = &self.phases[..self.next_phase_index];
+        self.pk
+            .vk
+            .cs
+            .advice_column_phase
+            .iter()
+            .zip(self.advices[circuit].advice_polys.iter())
+            .map(|(phase, values)| committed.contains(phase).then(|| &values[..]))
+            .collect()
+    }
 }
@@ -0,0 +1,207 @@
+use std::collections::HashSet;
+
+use halo2_common::plonk::Error;
+
+/// A group of queries of the multiopen argument of a proof, see [`QueryOrder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum QueryGroup {
+    /// The queries of the instance columns of a circuit, if the commitment scheme
+    /// commits to them.
+    Instance,
+    /// The queries of the advice columns of a circuit.
+    Advice,
+    /// The queries of the permutation product polynomials of a circuit.
+    Permutation,
+    /// The queries of the lookup arguments of a circuit.
+    Lookups,
+    /// The queries of the shuffle arguments of a circuit.
+    Shuffles,
+    /// The extra queries of a circuit, see [`ExtraQuery`](crate::plonk::ExtraQuery).
+    ExtraQueries,
+    /// The queries of the fixed columns.
+    Fixed,
+    /// The queries of the polynomials of the permutation argument in the proving key.
+    PermutationCommon,
+    /// The queries of the quotient polynomial `h(X)` and of the random polynomial of the
+    /// vanishing argument.
+    Vanishing,
+}
+
+impl QueryGroup {
+    /// Returns whether the group has queries for each circuit of a proof, rather than
+    /// for all of them.
+    pub fn is_per_circuit(self) -> bool {
+        matches!(
+            self,
+            QueryGroup::Instance
+                | QueryGroup::Advice
+                | QueryGroup::Permutation
+                | QueryGroup::Lookups
+                | QueryGroup::Shuffles
+                | QueryGroup::ExtraQueries
+        )
+    }
+}
+
+/// The order in which the prover and the verifier pass the queries of a proof to the
+/// multiopen argument.
+///
+/// The multiopen argument depends on the order of its queries, so a proof only verifies
+/// with the order it was created with. The order lists every [`QueryGroup`] once. Each
+/// run of consecutive per-circuit groups is repeated for each circuit of the proof in
+/// turn, and the queries of a group are in the order of the constraint system.
+///
+/// The canonical order, which is the default, is [`QueryOrder::CANONICAL`]: for each
+/// circuit, its instance, advice, permutation, lookup, shuffle and extra queries, then
+/// the fixed, common permutation and vanishing queries. It is the order of the upstream
+/// zcash and PSE implementations, so proofs of circuits they support interoperate with
+/// them. Forks with another order are matched with [`QueryOrder::new`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryOrder {
+    groups: Vec<QueryGroup>,
+}
+
+impl QueryOrder {
+    /// The groups of the canonical order.
+    pub const CANONICAL: [QueryGroup; 9] = [
+        QueryGroup::Instance,
+        QueryGroup::Advice,
+        QueryGroup::Permutation,
+        QueryGroup::Lookups,
+        QueryGroup::Shuffles,
+        QueryGroup::ExtraQueries,
+        QueryGroup::Fixed,
+        QueryGroup::PermutationCommon,
+        QueryGroup::Vanishing,
+    ];
+
+    /// Returns the canonical order.
+    pub fn canonical() -> Self {
+        QueryOrder {
+            groups: Self::CANONICAL.to_vec(),
+        }
+    }
+
+    /// Creates the order of `groups`, which must list every [`QueryGroup`] once.
+    pub fn new(groups: Vec<QueryGroup>) -> Result<Self, Error> {
+        let distinct: HashSet<_> = groups.iter().collect();
+        if groups.len() != Self::CANONICAL.len() || distinct.len() != groups.len() {
+            return Err(Error::Other(format!(
+                "the query order {:?} doesn't list every query group once",
+                groups
+            )));
+        }
+        Ok(QueryOrder { groups })
+    }
+
+    /// Creates the order of some of the groups, for a [`ProofSections`] section.
+    ///
+    /// [`ProofSections`]: crate::plonk::ProofSections
+    pub(crate) fn section(groups: Vec<QueryGroup>) -> Self {
+        QueryOrder { groups }
+    }
+
+    /// Returns the groups of the order.
+    pub fn groups(&self) -> &[QueryGroup] {
+        &self.groups
+    }
+
+    /// Returns the queries of a proof of `num_circuits` circuits in this order.
+    /// `circuit_queries(group, circuit, queries)` appends the queries of a per-circuit
+    /// group for a circuit, and `common_queries(group, queries)` those of another group.
+    pub(crate) fn arrange<Q>(
+        &self,
+        num_circuits: usize,
+        mut circuit_queries: impl FnMut(QueryGroup, usize, &mut Vec<Q>),
+        mut common_queries: impl FnMut(QueryGroup, &mut Vec<Q>),
+    ) -> Vec<Q> {
+        let mut queries = vec![];
+        let mut groups = self.groups.as_slice();
+        while let Some(&group) = groups.first() {
+            if group.is_per_circuit() {
+                let run = groups
+                    .iter()
+                    .take_while(|group| group.is_per_circuit())
+                    .count();
+                for circuit in 0..num_circuits {
+                    for &group in &groups[..run] {
+                        circuit_queries(group, circuit, &mut queries);
+                    }
+                }
+                groups = &groups[run..];
+            } else {
+                common_queries(group, &mut queries);
+                groups = &groups[1..];
+            }
+        }
+        queries
+    }
+}
+
+impl Default for QueryOrder {
+    fn default() -> Self {
+        Self::canonical()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QueryGroup, QueryOrder};
+
+    #[test]
+    fn arrange() {
+        let arrange = |order: &QueryOrder| {
+            order.arrange(
+                2,
+                |group, circuit, queries| queries.push((group, Some(circuit))),
+                |group, queries| queries.push((group, None)),
+            )
+        };
+
+        let canonical = arrange(&QueryOrder::default());
+        assert_eq!(canonical.len(), 2 * 6 + 3);
+        assert_eq!(
+            canonical[5..8],
+            [
+                (QueryGroup::ExtraQueries, Some(0)),
+                (QueryGroup::Instance, Some(1)),
+                (QueryGroup::Advice, Some(1)),
+            ]
+        );
+        assert_eq!(canonical[14], (QueryGroup::Vanishing, None));
+
+        // The per-circuit groups are split in two runs by the vanishing queries.
+        let order = QueryOrder::new(vec![
+            QueryGroup::Fixed,
+            QueryGroup::Instance,
+            QueryGroup::Advice,
+            QueryGroup::Vanishing,
+            QueryGroup::Permutation,
+            QueryGroup::Lookups,
+            QueryGroup::Shuffles,
+            QueryGroup::ExtraQueries,
+            QueryGroup::PermutationCommon,
+        ])
+        .unwrap();
+        let queries = arrange(&order);
+        assert_eq!(
+            queries[..7],
+            [
+                (QueryGroup::Fixed, None),
+                (QueryGroup::Instance, Some(0)),
+                (QueryGroup::Advice, Some(0)),
+                (QueryGroup::Instance, Some(1)),
+                (QueryGroup::Advice, Some(1)),
+                (QueryGroup::Vanishing, None),
+                (QueryGroup::Permutation, Some(0)),
+            ]
+        );
+        assert_eq!(queries[10], (QueryGroup::Permutation, Some(1)));
+        assert_eq!(queries[14], (QueryGroup::PermutationCommon, None));
+
+        let mut groups = QueryOrder::CANONICAL.to_vec();
+        groups[0] = QueryGroup::Advice;
+        assert!(QueryOrder::new(groups).is_err());
+        assert!(QueryOrder::new(vec![]).is_err());
+    }
+}
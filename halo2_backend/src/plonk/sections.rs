@@ -0,0 +1,230 @@
+//! Proofs whose multiopen argument is split into independently verifiable sections.
+//!
+//! The multiopen argument at the end of a proof opens every committed polynomial at once,
+//! and dominates the cost of verifying large proofs. A proof created with
+//! [`ProofSections`] instead ends with a seed squeezed from its transcript, and proves
+//! the openings of each section with its own multiopen argument, over a fresh transcript
+//! which absorbs the seed and the index of the section. As the seed is squeezed after
+//! every commitment and evaluation of the proof, it binds the arguments of the sections
+//! to them, and the sections can be verified in any order, in parallel or on different
+//! machines, with [`verify_proof_section`].
+
+use std::collections::HashSet;
+use std::io;
+
+use halo2_middleware::ff::FromUniformBytes;
+use halo2curves::CurveAffine;
+use rand_core::RngCore;
+
+use super::verifier::Opening;
+use super::{Error, QueryGroup, QueryOrder};
+use crate::poly::commitment::{CommitmentScheme, Prover, Verifier};
+use crate::poly::{ProverQuery, VerificationStrategy, VerifierQuery};
+use crate::transcript::{
+    absorb_domain, EncodedChallenge, Transcript, TranscriptReadBuffer, TranscriptWriterBuffer,
+};
+
+/// The label absorbed by the transcript of a section before the seed, so that its
+/// challenges differ from those of any other proof.
+const PROOF_SECTION_DOMAIN: &[u8] = b"Halo2-Proof-Section";
+
+/// The sections of the multiopen argument of a split proof.
+///
+/// Each section lists some of the [`QueryGroup`]s, whose queries are opened by its
+/// argument in the order of the section, as in a [`QueryOrder`]. The query order of the
+/// prover is not used by split proofs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofSections {
+    sections: Vec<QueryOrder>,
+}
+
+impl ProofSections {
+    /// Creates the sections of `sections`, which must list every [`QueryGroup`] once in
+    /// total, with at least one group in each section.
+    pub fn new(sections: Vec<Vec<QueryGroup>>) -> Result<Self, Error> {
+        let groups: Vec<_> = sections.iter().flatten().collect();
+        let distinct: HashSet<_> = groups.iter().collect();
+        if groups.len() != QueryOrder::CANONICAL.len()
+            || distinct.len() != groups.len()
+            || sections.iter().any(|section| section.is_empty())
+        {
+            return Err(Error::Other(format!(
+                "the proof sections {:?} don't list every query group once",
+                sections
+            )));
+        }
+        Ok(ProofSections {
+            sections: sections.into_iter().map(QueryOrder::section).collect(),
+        })
+    }
+
+    /// Returns the sections with one [`QueryGroup`] each, in the canonical order.
+    pub fn per_argument() -> Self {
+        ProofSections {
+            sections: QueryOrder::CANONICAL
+                .iter()
+                .map(|&group| QueryOrder::section(vec![group]))
+                .collect(),
+        }
+    }
+
+    /// Returns the four sections of the phases of a proof: the columns of the circuits
+    /// with the extra queries, the permutation, lookup and shuffle arguments of the
+    /// circuits, the polynomials of the proving key, and the vanishing argument.
+    pub fn per_phase() -> Self {
+        ProofSections::new(vec![
+            vec![
+                QueryGroup::Instance,
+                QueryGroup::Advice,
+                QueryGroup::ExtraQueries,
+            ],
+            vec![
+                QueryGroup::Permutation,
+                QueryGroup::Lookups,
+                QueryGroup::Shuffles,
+            ],
+            vec![QueryGroup::Fixed, QueryGroup::PermutationCommon],
+            vec![QueryGroup::Vanishing],
+        ])
+        .expect("the phases list every query group once")
+    }
+
+    /// Returns the number of sections.
+    pub fn num_sections(&self) -> usize {
+        self.sections.len()
+    }
+
+    /// Returns the groups of section `index`.
+    pub fn section(&self, index: usize) -> &[QueryGroup] {
+        self.sections[index].groups()
+    }
+
+    /// Returns the query orders of the sections.
+    pub(crate) fn orders(&self) -> &[QueryOrder] {
+        &self.sections
+    }
+}
+
+/// A section of a split proof, with the openings its argument proves.
+///
+/// The sections of a proof are returned by
+/// [`verify_split_proof`](crate::plonk::verifier::verify_split_proof), which checks the
+/// rest of the proof; the openings are only proven once the argument of each section is
+/// verified with [`verify_proof_section`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofSection<C: CurveAffine> {
+    /// The index of the section, and of its argument in the proofs of the sections.
+    pub index: usize,
+    /// The seed squeezed from the transcript of the proof.
+    pub seed: C::Scalar,
+    /// The openings proven by the argument of the section.
+    pub openings: Vec<Opening<C>>,
+}
+
+/// Absorbs the domain separation label, the seed and the index of a section into its
+/// transcript.
+fn absorb_section<C: CurveAffine, E: EncodedChallenge<C>, T: Transcript<C, E>>(
+    transcript: &mut T,
+    seed: C::Scalar,
+    index: usize,
+) -> io::Result<()>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    absorb_domain(transcript, PROOF_SECTION_DOMAIN)?;
+    transcript.common_scalar(seed)?;
+    transcript.common_scalar(C::Scalar::from(index as u64))
+}
+
+/// Creates the arguments of the sections of a proof, each with a new transcript of type
+/// `T`. The argument of a section without queries is empty.
+pub(crate) fn create_section_proofs<
+    'params,
+    Scheme: CommitmentScheme,
+    P: Prover<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    R: RngCore,
+    T: TranscriptWriterBuffer<Vec<u8>, Scheme::Curve, E>,
+>(
+    params: &'params Scheme::ParamsProver,
+    seed: Scheme::Scalar,
+    sections: &[Vec<ProverQuery<'_, Scheme::Curve>>],
+    mut rng: R,
+) -> Result<Vec<Vec<u8>>, Error>
+where
+    Scheme::Scalar: FromUniformBytes<64>,
+{
+    sections
+        .iter()
+        .enumerate()
+        .map(|(index, queries)| {
+            if queries.is_empty() {
+                return Ok(vec![]);
+            }
+            let mut transcript = T::init(vec![]);
+            absorb_section(&mut transcript, seed, index)?;
+            P::new(params)
+                .create_proof(&mut rng, &mut transcript, queries.iter().copied())
+                .map_err(|_| Error::ConstraintSystemFailure)?;
+            Ok(transcript.finalize())
+        })
+        .collect()
+}
+
+/// Verifies the argument of a section, with a transcript of type `T` reading `proof`.
+///
+/// The openings of the section are only proven once the output of the strategy is
+/// checked, for example with [`VerificationStrategy::finalize`].
+pub fn verify_proof_section<
+    'params,
+    'proof,
+    Scheme: CommitmentScheme,
+    V: Verifier<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    T: TranscriptReadBuffer<&'proof [u8], Scheme::Curve, E>,
+    Strategy: VerificationStrategy<'params, Scheme, V>,
+>(
+    params: &'params Scheme::ParamsVerifier,
+    section: &ProofSection<Scheme::Curve>,
+    proof: &'proof [u8],
+    strategy: Strategy,
+) -> Result<Strategy::Output, Error>
+where
+    Scheme::Scalar: FromUniformBytes<64>,
+{
+    let mut transcript = T::init(proof);
+    absorb_section(&mut transcript, section.seed, section.index)?;
+
+    let queries = section.openings.iter().map(|opening| {
+        VerifierQuery::new_commitment(&opening.commitment, opening.point, opening.eval)
+    });
+    let verifier = V::new(params);
+    strategy.process(|msm| {
+        verifier
+            .verify_proof(&mut transcript, queries, msm)
+            .map_err(|_| Error::Opening)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProofSections;
+    use crate::plonk::{QueryGroup, QueryOrder};
+
+    #[test]
+    fn new() {
+        assert_eq!(ProofSections::per_argument().num_sections(), 9);
+        assert_eq!(
+            ProofSections::per_phase().section(3),
+            [QueryGroup::Vanishing]
+        );
+
+        let mut groups = QueryOrder::CANONICAL.to_vec();
+        let vanishing = groups.pop().unwrap();
+        assert!(ProofSections::new(vec![groups.clone(), vec![vanishing]]).is_ok());
+        // Every group must be listed once, in a non-empty section.
+        assert!(ProofSections::new(vec![groups.clone()]).is_err());
+        assert!(ProofSections::new(vec![groups.clone(), vec![vanishing, vanishing]]).is_err());
+        assert!(ProofSections::new(vec![groups, vec![vanishing], vec![]]).is_err());
+    }
+}
@@ -2,15 +2,17 @@ use super::super::ProvingKey;
 use super::Argument;
 use crate::plonk::evaluation::evaluate;
 use crate::{
-    arithmetic::{eval_polynomial, parallelize, CurveAffine},
+    arithmetic::{eval_polynomial, running_product, CurveAffine},
+    helpers::{Wipe, Wiped},
     plonk::{ChallengeGamma, ChallengeTheta, ChallengeX},
     poly::{
         commitment::{Blind, Params},
         Coeff, EvaluationDomain, LagrangeCoeff, Polynomial, ProverQuery,
     },
     transcript::{EncodedChallenge, TranscriptWrite},
+    zal::PlonkEngine,
 };
-use group::{ff::BatchInvert, Curve};
+use group::Curve;
 use halo2_common::plonk::{Error, Expression};
 use halo2_middleware::ff::WithSmallOrderMulGroup;
 use halo2_middleware::poly::Rotation;
@@ -48,7 +50,7 @@ fn shuffle_compress<'a, 'params: 'a, F: WithSmallOrderMulGroup<3>, C, P: Params<
     domain: &EvaluationDomain<C::Scalar>,
     theta: ChallengeTheta<C>,
     advice_values: &'a [Polynomial<C::Scalar, LagrangeCoeff>],
-    fixed_values: &'a [Polynomial<C::Scalar, LagrangeCoeff>],
+    fixed_values: &'a [&'a Polynomial<C::Scalar, LagrangeCoeff>],
     instance_values: &'a [Polynomial<C::Scalar, LagrangeCoeff>],
     challenges: &'a [C::Scalar],
 ) -> Compressed<C>
@@ -107,11 +109,12 @@ pub(in crate::plonk) fn shuffle_commit_product<
     arg: &Argument<F>,
     pk: &ProvingKey<C>,
     params: &P,
+    engine: &PlonkEngine<C>,
     domain: &EvaluationDomain<C::Scalar>,
     theta: ChallengeTheta<C>,
     gamma: ChallengeGamma<C>,
     advice_values: &'a [Polynomial<C::Scalar, LagrangeCoeff>],
-    fixed_values: &'a [Polynomial<C::Scalar, LagrangeCoeff>],
+    fixed_values: &'a [&'a Polynomial<C::Scalar, LagrangeCoeff>],
     instance_values: &'a [Polynomial<C::Scalar, LagrangeCoeff>],
     challenges: &'a [C::Scalar],
     mut rng: R,
@@ -121,7 +124,7 @@ where
     C: CurveAffine<ScalarExt = F>,
     C::Curve: Mul<F, Output = C::Curve> + MulAssign<F>,
 {
-    let compressed = shuffle_compress(
+    let compressed = Wiped(shuffle_compress(
         arg,
         pk,
         params,
@@ -131,45 +134,41 @@ where
         fixed_values,
         instance_values,
         challenges,
-    );
+    ));
 
     let blinding_factors = pk.vk.cs.blinding_factors();
 
-    let mut shuffle_product = vec![C::Scalar::ZERO; params.n() as usize];
-    parallelize(&mut shuffle_product, |shuffle_product, start| {
-        for (shuffle_product, shuffle_value) in shuffle_product
-            .iter_mut()
-            .zip(compressed.shuffle_expression[start..].iter())
-        {
-            *shuffle_product = *gamma + shuffle_value;
-        }
-    });
-
-    shuffle_product.iter_mut().batch_invert();
-
-    parallelize(&mut shuffle_product, |product, start| {
-        for (i, product) in product.iter_mut().enumerate() {
-            let i = i + start;
-            *product *= &(*gamma + compressed.input_expression[i]);
-        }
-    });
-
     // Compute the evaluations of the shuffle product polynomial
-    // over our domain, starting with z[0] = 1
-    let z = iter::once(C::Scalar::ONE)
-        .chain(shuffle_product)
-        .scan(C::Scalar::ONE, |state, cur| {
-            *state *= &cur;
-            Some(*state)
-        })
-        // Take all rows including the "last" row which should
-        // be a boolean (and ideally 1, else soundness is broken)
-        .take(params.n() as usize - blinding_factors)
-        // Chain random blinding factors.
-        .chain((0..blinding_factors).map(|_| C::Scalar::random(&mut rng)))
-        .collect::<Vec<_>>();
-    assert_eq!(z.len(), params.n() as usize);
-    let z = pk.vk.domain.lagrange_from_vec(z);
+    // over our domain, starting with z[0] = 1, streaming the fractions
+    // into its buffer. Take all rows including the "last" row which should
+    // be a boolean (and ideally 1, else soundness is broken)
+    let n = params.n() as usize;
+    let mut z = pk.vk.domain.empty_lagrange();
+    running_product(
+        &mut z,
+        n - blinding_factors,
+        C::Scalar::ONE,
+        |denominators, start| {
+            for (denominator, shuffle_value) in denominators
+                .iter_mut()
+                .zip(compressed.shuffle_expression[start..].iter())
+            {
+                *denominator = *gamma + shuffle_value;
+            }
+        },
+        |products, start| {
+            for (product, input_value) in products
+                .iter_mut()
+                .zip(compressed.input_expression[start..].iter())
+            {
+                *product *= &(*gamma + input_value);
+            }
+        },
+    );
+    // Set blinding factors
+    for z in &mut z[n - blinding_factors..] {
+        *z = C::Scalar::random(&mut rng);
+    }
 
     #[cfg(feature = "sanity-checks")]
     {
@@ -189,8 +188,13 @@ where
     }
 
     let product_blind = Blind(C::Scalar::random(rng));
-    let product_commitment = params.commit_lagrange(&z, product_blind).to_affine();
-    let z = pk.vk.domain.lagrange_to_coeff(z);
+    let product_commitment = params
+        .commit_lagrange_with_engine(&*engine.msm_backend, &z, product_blind)
+        .to_affine();
+    let z = pk
+        .vk
+        .domain
+        .lagrange_to_coeff_with_engine(&*engine.fft_backend, z);
 
     // Hash product commitment
     transcript.write_point(product_commitment)?;
@@ -201,6 +205,26 @@ where
     })
 }
 
+impl<C: CurveAffine> Wipe for Compressed<C> {
+    fn wipe(&mut self) {
+        self.input_expression.wipe();
+        self.shuffle_expression.wipe();
+    }
+}
+
+impl<C: CurveAffine> Wipe for Committed<C> {
+    fn wipe(&mut self) {
+        self.product_poly.wipe();
+        self.product_blind.wipe();
+    }
+}
+
+impl<C: CurveAffine> Wipe for Evaluated<C> {
+    fn wipe(&mut self) {
+        self.constructed.wipe();
+    }
+}
+
 impl<C: CurveAffine> Committed<C> {
     pub(in crate::plonk) fn evaluate<E: EncodedChallenge<C>, T: TranscriptWrite<C, E>>(
         self,
@@ -1,10 +1,37 @@
 use std::marker::PhantomData;
 
 use crate::arithmetic::CurveAffine;
+#[cfg(feature = "vanishing-pieces")]
+use crate::poly::commitment::Blind;
 
 mod prover;
 mod verifier;
 
+/// The commitments to the pieces of the quotient polynomial `h(X)` of a proof, and their
+/// blinds, for aggregation layers that reuse them without reading the proof back.
+///
+/// The blinds are secret: with the hardened-witness feature, the prover wipes its own
+/// copies, but wiping these ones is left to the caller.
+#[cfg(feature = "vanishing-pieces")]
+#[derive(Clone, Debug)]
+pub struct VanishingPieces<C: CurveAffine> {
+    /// The commitment to each piece of `h(X)`, in the order they are written to the
+    /// transcript.
+    pub h_commitments: Vec<C>,
+    /// The blind of each commitment.
+    pub h_blinds: Vec<Blind<C::Scalar>>,
+}
+
+#[cfg(feature = "vanishing-pieces")]
+impl<C: CurveAffine> Default for VanishingPieces<C> {
+    fn default() -> Self {
+        VanishingPieces {
+            h_commitments: vec![],
+            h_blinds: vec![],
+        }
+    }
+}
+
 /// A vanishing argument.
 pub(crate) struct Argument<C: CurveAffine> {
     _marker: PhantomData<C>,
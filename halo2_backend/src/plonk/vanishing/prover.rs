@@ -1,4 +1,4 @@
-use std::{collections::HashMap, iter};
+use std::iter;
 
 use group::Curve;
 use halo2_common::plonk::Error;
@@ -8,16 +8,21 @@ use rand_core::{RngCore, SeedableRng};
 
 use super::Argument;
 use crate::{
-    arithmetic::{eval_polynomial, parallelize, CurveAffine},
-    multicore::current_num_threads,
+    arithmetic::{eval_polynomial, CurveAffine},
+    helpers::{wipe_if_hardened, Wipe},
+    multicore::prelude::*,
     plonk::ChallengeX,
     poly::{
         commitment::{Blind, ParamsProver},
         Coeff, EvaluationDomain, ExtendedLagrangeCoeff, Polynomial, ProverQuery,
     },
     transcript::{EncodedChallenge, TranscriptWrite},
+    zal::PlonkEngine,
 };
 
+/// The number of coefficients of the random polynomial sampled from each seed.
+const RANDOM_POLY_CHUNK: usize = 1 << 10;
+
 pub(in crate::plonk) struct Committed<C: CurveAffine> {
     random_poly: Polynomial<C::Scalar, Coeff>,
     random_blind: Blind<C::Scalar>,
@@ -26,6 +31,8 @@ pub(in crate::plonk) struct Committed<C: CurveAffine> {
 pub(in crate::plonk) struct Constructed<C: CurveAffine> {
     h_pieces: Vec<Polynomial<C::Scalar, Coeff>>,
     h_blinds: Vec<Blind<C::Scalar>>,
+    #[cfg(feature = "vanishing-pieces")]
+    h_commitments: Vec<C>,
     committed: Committed<C>,
 }
 
@@ -44,6 +51,7 @@ impl<C: CurveAffine> Argument<C> {
         T: TranscriptWrite<C, E>,
     >(
         params: &P,
+        engine: &PlonkEngine<C>,
         domain: &EvaluationDomain<C::Scalar>,
         mut rng: R,
         transcript: &mut T,
@@ -52,31 +60,25 @@ impl<C: CurveAffine> Argument<C> {
         let n = 1usize << domain.k() as usize;
         let mut rand_vec = vec![C::Scalar::ZERO; n];
 
-        let num_threads = current_num_threads();
-        let chunk_size = n / num_threads;
-        let thread_seeds = (0..)
-            .step_by(chunk_size + 1)
-            .take(n % num_threads)
-            .chain(
-                (chunk_size != 0)
-                    .then(|| ((n % num_threads) * (chunk_size + 1)..).step_by(chunk_size))
-                    .into_iter()
-                    .flatten(),
-            )
-            .take(num_threads)
-            .zip(iter::repeat_with(|| {
-                let mut seed = [0u8; 32];
-                rng.fill_bytes(&mut seed);
-                ChaCha20Rng::from_seed(seed)
-            }))
-            .collect::<HashMap<_, _>>();
-
-        parallelize(&mut rand_vec, |chunk, offset| {
-            let mut rng = thread_seeds[&offset].clone();
-            chunk
-                .iter_mut()
-                .for_each(|v| *v = C::Scalar::random(&mut rng));
-        });
+        // Each chunk is sampled from its own seed, drawn from `rng` in order, so that the
+        // polynomial doesn't depend on the number of threads it is sampled with.
+        let seeds = iter::repeat_with(|| {
+            let mut seed = [0u8; 32];
+            rng.fill_bytes(&mut seed);
+            seed
+        })
+        .take((n + RANDOM_POLY_CHUNK - 1) / RANDOM_POLY_CHUNK)
+        .collect::<Vec<_>>();
+
+        rand_vec
+            .par_chunks_mut(RANDOM_POLY_CHUNK)
+            .zip(seeds)
+            .for_each(|(chunk, seed)| {
+                let mut rng = ChaCha20Rng::from_seed(seed);
+                chunk
+                    .iter_mut()
+                    .for_each(|v| *v = C::Scalar::random(&mut rng));
+            });
 
         let random_poly: Polynomial<C::Scalar, Coeff> = domain.coeff_from_vec(rand_vec);
 
@@ -84,7 +86,9 @@ impl<C: CurveAffine> Argument<C> {
         let random_blind = Blind(C::Scalar::random(rng));
 
         // Commit
-        let c = params.commit(&random_poly, random_blind).to_affine();
+        let c = params
+            .commit_with_engine(&*engine.msm_backend, &random_poly, random_blind)
+            .to_affine();
         transcript.write_point(c)?;
 
         Ok(Committed {
@@ -104,6 +108,7 @@ impl<C: CurveAffine> Committed<C> {
     >(
         self,
         params: &P,
+        engine: &PlonkEngine<C>,
         domain: &EvaluationDomain<C::Scalar>,
         h_poly: Polynomial<C::Scalar, ExtendedLagrangeCoeff>,
         mut rng: R,
@@ -113,13 +118,14 @@ impl<C: CurveAffine> Committed<C> {
         let h_poly = domain.divide_by_vanishing_poly(h_poly);
 
         // Obtain final h(X) polynomial
-        let h_poly = domain.extended_to_coeff(h_poly);
+        let mut h_poly = domain.extended_to_coeff_with_engine(&*engine.fft_backend, h_poly);
 
         // Split h(X) up into pieces
         let h_pieces = h_poly
             .chunks_exact(params.n() as usize)
             .map(|v| domain.coeff_from_vec(v.to_vec()))
             .collect::<Vec<_>>();
+        wipe_if_hardened(&mut h_poly);
         drop(h_poly);
         let h_blinds: Vec<_> = h_pieces
             .iter()
@@ -130,7 +136,9 @@ impl<C: CurveAffine> Committed<C> {
         let h_commitments_projective: Vec<_> = h_pieces
             .iter()
             .zip(h_blinds.iter())
-            .map(|(h_piece, blind)| params.commit(h_piece, *blind))
+            .map(|(h_piece, blind)| {
+                params.commit_with_engine(&*engine.msm_backend, h_piece, *blind)
+            })
             .collect();
         let mut h_commitments = vec![C::identity(); h_commitments_projective.len()];
         C::Curve::batch_normalize(&h_commitments_projective, &mut h_commitments);
@@ -144,14 +152,24 @@ impl<C: CurveAffine> Committed<C> {
         Ok(Constructed {
             h_pieces,
             h_blinds,
+            #[cfg(feature = "vanishing-pieces")]
+            h_commitments,
             committed: self,
         })
     }
 }
 
 impl<C: CurveAffine> Constructed<C> {
+    #[cfg(feature = "vanishing-pieces")]
+    pub(in crate::plonk) fn pieces(&self) -> super::VanishingPieces<C> {
+        super::VanishingPieces {
+            h_commitments: self.h_commitments.clone(),
+            h_blinds: self.h_blinds.clone(),
+        }
+    }
+
     pub(in crate::plonk) fn evaluate<E: EncodedChallenge<C>, T: TranscriptWrite<C, E>>(
-        self,
+        mut self,
         x: ChallengeX<C>,
         xn: C::Scalar,
         domain: &EvaluationDomain<C::Scalar>,
@@ -172,6 +190,9 @@ impl<C: CurveAffine> Constructed<C> {
         let random_eval = eval_polynomial(&self.committed.random_poly, *x);
         transcript.write_scalar(random_eval)?;
 
+        wipe_if_hardened(&mut self.h_pieces);
+        wipe_if_hardened(&mut self.h_blinds);
+
         Ok(Evaluated {
             h_poly,
             h_blind,
@@ -180,6 +201,29 @@ impl<C: CurveAffine> Constructed<C> {
     }
 }
 
+impl<C: CurveAffine> Wipe for Committed<C> {
+    fn wipe(&mut self) {
+        self.random_poly.wipe();
+        self.random_blind.wipe();
+    }
+}
+
+impl<C: CurveAffine> Wipe for Constructed<C> {
+    fn wipe(&mut self) {
+        self.h_pieces.wipe();
+        self.h_blinds.wipe();
+        self.committed.wipe();
+    }
+}
+
+impl<C: CurveAffine> Wipe for Evaluated<C> {
+    fn wipe(&mut self) {
+        self.h_poly.wipe();
+        self.h_blind.wipe();
+        self.committed.wipe();
+    }
+}
+
 impl<C: CurveAffine> Evaluated<C> {
     pub(in crate::plonk) fn open(
         &self,
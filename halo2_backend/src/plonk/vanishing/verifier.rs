@@ -93,9 +93,10 @@ impl<C: CurveAffine> PartiallyEvaluated<C> {
         expressions: impl Iterator<Item = C::Scalar>,
         y: ChallengeY<C>,
         xn: C::Scalar,
+        zh_inv: C::Scalar,
     ) -> Evaluated<C, P::MSM> {
         let expected_h_eval = expressions.fold(C::Scalar::ZERO, |h_eval, v| h_eval * *y + v);
-        let expected_h_eval = expected_h_eval * ((xn - C::Scalar::ONE).invert().unwrap());
+        let expected_h_eval = expected_h_eval * zh_inv;
 
         let h_commitment =
             self.h_commitments
@@ -2,10 +2,12 @@
 
 use group::Curve;
 use halo2_middleware::ff::{Field, FromUniformBytes, WithSmallOrderMulGroup};
-use std::iter;
 
-use super::{vanishing, VerifyingKey};
-use crate::arithmetic::compute_inner_product;
+use super::extra_queries::check_extra_queries;
+use super::{
+    vanishing, ExtraQuery, ProofSection, ProofSections, QueryGroup, QueryOrder, VerifyingKey,
+};
+use crate::arithmetic::{compute_inner_product, CurveAffine};
 use crate::plonk::lookup::verifier::lookup_read_permuted_commitments;
 use crate::plonk::permutation::verifier::permutation_read_product_commitments;
 use crate::plonk::shuffle::verifier::shuffle_read_product_commitment;
@@ -16,12 +18,163 @@ use crate::poly::{
     commitment::{Blind, Params},
     VerifierQuery,
 };
-use crate::transcript::{read_n_scalars, EncodedChallenge, TranscriptRead};
+use crate::transcript::{
+    absorb_domain, read_n_scalars, EncodedChallenge, TranscriptPhase, TranscriptRead,
+};
+use halo2_common::plonk::{ConstraintSystem, Gate};
+use halo2_middleware::circuit::Any;
+use std::fmt;
+use std::marker::PhantomData;
 
 #[cfg(feature = "batch")]
 mod batch;
+#[cfg(feature = "ipa")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ipa")))]
+pub mod cycle;
+mod fuzz;
+mod instance_cache;
+mod inverses;
+mod metrics;
+#[cfg(feature = "kzg")]
+#[cfg_attr(docsrs, doc(cfg(feature = "kzg")))]
+pub mod recursion;
+mod trace;
 #[cfg(feature = "batch")]
 pub use batch::BatchVerifier;
+pub use fuzz::{verify_arbitrary_bytes, FuzzInput};
+pub use instance_cache::InstanceCache;
+use inverses::Inverses;
+use metrics::Stopwatch;
+pub use metrics::VerifierMetrics;
+pub use trace::{Opening, VerifierTrace};
+
+impl<C: CurveAffine> VerifyingKey<C>
+where
+    C::Scalar: WithSmallOrderMulGroup<3>,
+{
+    /// Evaluates the constraints of the gates of the circuit at `x`, from the evaluations
+    /// at `x` of the fixed, advice and instance queries of the constraint system, in the
+    /// order of its queries, and from the challenges of the circuit, by challenge index.
+    ///
+    /// The values are those the verifier folds into the expected value of the quotient
+    /// polynomial, in the order of the gates and of their polynomials, so that external
+    /// verifiers, such as the witness generators of aggregation circuits, don't have to
+    /// reimplement them. The constraints of the gates on a subdomain are multiplied by
+    /// their selectors, which are one on the usable rows of their subdomain.
+    ///
+    /// Returns an error if the number of evaluations or challenges doesn't match the
+    /// circuit, or if `x` is in the domain.
+    pub fn evaluate_gates_at(
+        &self,
+        x: C::Scalar,
+        advice_evals: &[C::Scalar],
+        fixed_evals: &[C::Scalar],
+        instance_evals: &[C::Scalar],
+        challenges: &[C::Scalar],
+    ) -> Result<Vec<C::Scalar>, Error> {
+        let cs = &self.cs;
+        if advice_evals.len() != cs.advice_queries.len()
+            || fixed_evals.len() != cs.fixed_queries.len()
+            || instance_evals.len() != cs.instance_queries.len()
+            || challenges.len() != cs.num_challenges
+        {
+            return Err(Error::Other(
+                "the evaluations and the challenges don't match the circuit".to_string(),
+            ));
+        }
+
+        let n = 1u64 << self.domain.k();
+        let blinding_factors = cs.blinding_factors();
+        let inverses = Inverses::new(
+            &self.domain,
+            x,
+            x.pow([n]),
+            -((blinding_factors + 1) as i32)..1,
+            0..0,
+            cs.gates.iter().map(|gate| gate.subdomain()),
+        )?;
+        let selectors = gate_selectors(cs, n as usize, &inverses);
+        evaluate_gates(
+            &cs.gates,
+            &selectors,
+            fixed_evals,
+            advice_evals,
+            instance_evals,
+            challenges,
+        )
+    }
+}
+
+/// Returns the selectors of the gates on a subdomain in a circuit of `n` rows, which are
+/// one on the usable rows of their subdomain: the rows after them are those of `l_last`
+/// and `l_blind`, whose Lagrange polynomials start the `l_evals` of `inverses`.
+fn gate_selectors<F: WithSmallOrderMulGroup<3>>(
+    cs: &ConstraintSystem<F>,
+    n: usize,
+    inverses: &Inverses<F>,
+) -> Vec<Option<F>> {
+    let blinding_factors = cs.blinding_factors();
+    let usable_rows = n - (blinding_factors + 1);
+    cs.gates
+        .iter()
+        .zip(inverses.subdomain_indicators.iter())
+        .map(|(gate, indicator)| {
+            gate.subdomain()
+                .zip(*indicator)
+                .map(|(subdomain, indicator)| {
+                    let unusable = inverses.l_evals[..=blinding_factors]
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| subdomain.contains(usable_rows + i))
+                        .fold(F::ZERO, |acc, (_, eval)| acc + eval);
+                    indicator - unusable
+                })
+        })
+        .collect()
+}
+
+/// Evaluates the constraints of `gates` from the evaluations of the queries, multiplying
+/// those of the gates on a subdomain by their `selectors`.
+///
+/// Returns `Error::ConstraintSystemFailure` if a gate queries an evaluation that isn't
+/// there, which only happens with a key inconsistent with its constraint system.
+fn evaluate_gates<F: Field>(
+    gates: &[Gate<F>],
+    selectors: &[Option<F>],
+    fixed_evals: &[F],
+    advice_evals: &[F],
+    instance_evals: &[F],
+    challenges: &[F],
+) -> Result<Vec<F>, Error> {
+    let get = |evals: &[F], index: Option<usize>| {
+        index
+            .and_then(|index| evals.get(index).copied())
+            .ok_or(Error::ConstraintSystemFailure)
+    };
+    gates
+        .iter()
+        .zip(selectors.iter())
+        .flat_map(|(gate, selector)| {
+            gate.polynomials().iter().map(move |poly| {
+                let eval = poly.evaluate(
+                    &|scalar| Ok(scalar),
+                    // Virtual selectors are removed during optimization.
+                    &|_| Err(Error::ConstraintSystemFailure),
+                    &|query| get(fixed_evals, query.index),
+                    &|query| get(advice_evals, query.index),
+                    &|query| get(instance_evals, query.index),
+                    &|challenge| get(challenges, Some(challenge.index())),
+                    &|a| Ok(-a?),
+                    &|a, b| Ok(a? + b?),
+                    &|a, b| Ok(a? * b?),
+                    &|a, scalar| Ok(a? * scalar),
+                )?;
+                // A gate on a subdomain is multiplied by its selector.
+                Ok(selector.map_or(eval, |selector| eval * selector))
+            })
+        })
+        .collect()
+}
 
 /// Returns a boolean indicating whether or not the proof is valid.  Verifies a single proof (not
 /// batched).
@@ -46,6 +199,13 @@ where
 }
 
 /// Returns a boolean indicating whether or not the proof is valid
+///
+/// Proofs created with other options are verified with a [`VerifierBuilder`].
+///
+/// Malformed proofs and instances are rejected with an error instead of causing a panic.
+/// The memory used by the verifier is bounded by the size of the circuit described by
+/// `vk`: the proof has no length prefixes, and instances that don't fit in the circuit
+/// are rejected before being processed.
 pub fn verify_proof<
     'params,
     Scheme: CommitmentScheme,
@@ -63,53 +223,504 @@ pub fn verify_proof<
 where
     Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
 {
+    VerifierBuilder::<Scheme, V>::new(params, vk, instances).verify(strategy, transcript)
+}
+
+/// The verifier of a proof like [`verify_proof`], with options.
+///
+/// The options are set with the `with_*` methods, then the proof is read from the
+/// transcript by one of the `verify*` methods:
+///
+/// ```ignore
+/// let output = VerifierBuilder::<KZGCommitmentScheme<_>, VerifierSHPLONK<_>>::new(
+///     &params,
+///     &vk,
+///     &[&[]],
+/// )
+/// .with_domain(b"application")
+/// .verify(SingleStrategy::new(&params), &mut transcript)?;
+/// ```
+///
+/// The options must match the ones the proof was created with.
+pub struct VerifierBuilder<'a, 'params, Scheme: CommitmentScheme, V: Verifier<'params, Scheme>> {
+    params: &'params Scheme::ParamsVerifier,
+    vk: &'a VerifyingKey<Scheme::Curve>,
+    instances: &'a [&'a [&'a [Scheme::Scalar]]],
+    queries: &'a [ExtraQuery<Scheme::Scalar>],
+    query_order: QueryOrder,
+    domain: Option<&'a [u8]>,
+    metrics: Option<&'a mut VerifierMetrics>,
+    instance_cache: Option<&'a InstanceCache<Scheme::Curve>>,
+    _marker: PhantomData<V>,
+}
+
+impl<'a, 'params, Scheme: CommitmentScheme, V: Verifier<'params, Scheme>> fmt::Debug
+    for VerifierBuilder<'a, 'params, Scheme, V>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifierBuilder")
+            .field("proofs", &self.instances.len())
+            .field("queries", &self.queries.len())
+            .field("query_order", &self.query_order)
+            .field("domain", &self.domain)
+            .field("metrics", &self.metrics.is_some())
+            .field("instance_cache", &self.instance_cache.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, 'params, Scheme: CommitmentScheme, V: Verifier<'params, Scheme>>
+    VerifierBuilder<'a, 'params, Scheme, V>
+where
+    Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    /// Starts the verification of proofs with the `instances`, when given the public
+    /// parameters `params` and the verifying key of the circuit.
+    pub fn new(
+        params: &'params Scheme::ParamsVerifier,
+        vk: &'a VerifyingKey<Scheme::Curve>,
+        instances: &'a [&'a [&'a [Scheme::Scalar]]],
+    ) -> Self {
+        Self {
+            params,
+            vk,
+            instances,
+            queries: &[],
+            query_order: QueryOrder::default(),
+            domain: None,
+            metrics: None,
+            instance_cache: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Verifies the evaluations of the columns at the points of `queries`, for a proof
+    /// created with the same extra queries by [`create_proof_v2_with_queries`].
+    /// [`Self::verify_with_evaluations`] returns the evaluations of each proof.
+    ///
+    /// [`create_proof_v2_with_queries`]: crate::plonk::prover::create_proof_v2_with_queries
+    pub fn with_queries(mut self, queries: &'a [ExtraQuery<Scheme::Scalar>]) -> Self {
+        self.queries = queries;
+        self
+    }
+
+    /// Verifies a proof created with the multiopen queries in `order`, see
+    /// [`ProverV2::set_query_order`].
+    ///
+    /// [`ProverV2::set_query_order`]: crate::plonk::prover::ProverV2::set_query_order
+    pub fn with_query_order(mut self, order: QueryOrder) -> Self {
+        self.query_order = order;
+        self
+    }
+
+    /// Verifies a proof created by [`ProverV2::new_with_domain`] for the application with
+    /// the domain separation `label`. Proofs created for other labels, or without one,
+    /// are rejected.
+    ///
+    /// [`ProverV2::new_with_domain`]: crate::plonk::prover::ProverV2::new_with_domain
+    pub fn with_domain(mut self, label: &'a [u8]) -> Self {
+        self.domain = Some(label);
+        self
+    }
+
+    /// Adds the time spent in each stage of the verification to `metrics`.
+    ///
+    /// The stages are only timed with this option, so other verifications don't pay for
+    /// the measurements.
+    pub fn with_metrics(mut self, metrics: &'a mut VerifierMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Takes the commitments to instance columns from `cache` when the same columns were
+    /// already committed to, and adds the other ones to it.
+    ///
+    /// The cache is only used by schemes that commit to instance columns, see
+    /// [`InstanceCache`].
+    pub fn with_instance_cache(mut self, cache: &'a InstanceCache<Scheme::Curve>) -> Self {
+        self.instance_cache = Some(cache);
+        self
+    }
+
+    /// Verifies the proofs, and returns the output of `strategy`.
+    pub fn verify<E, T, Strategy>(
+        self,
+        strategy: Strategy,
+        transcript: &mut T,
+    ) -> Result<Strategy::Output, Error>
+    where
+        E: EncodedChallenge<Scheme::Curve>,
+        T: TranscriptRead<Scheme::Curve, E>,
+        Strategy: VerificationStrategy<'params, Scheme, V>,
+    {
+        self.verify_impl::<E, T, Strategy>(strategy, None, transcript)
+            .map(|(output, _)| output)
+    }
+
+    /// Verifies the proofs, and returns the output of `strategy` with the evaluations of
+    /// the extra queries of [`Self::with_queries`] for each proof.
+    ///
+    /// The evaluations are only proven once the output of the strategy is checked, for
+    /// example with [`VerificationStrategy::finalize`].
+    #[allow(clippy::type_complexity)]
+    pub fn verify_with_evaluations<E, T, Strategy>(
+        self,
+        strategy: Strategy,
+        transcript: &mut T,
+    ) -> Result<(Strategy::Output, Vec<Vec<Scheme::Scalar>>), Error>
+    where
+        E: EncodedChallenge<Scheme::Curve>,
+        T: TranscriptRead<Scheme::Curve, E>,
+        Strategy: VerificationStrategy<'params, Scheme, V>,
+    {
+        self.verify_impl::<E, T, Strategy>(strategy, None, transcript)
+    }
+
+    /// Verifies the proofs, and returns the output of `strategy` with the commitments,
+    /// challenges and evaluations the verifier obtains from the proof, so that other
+    /// systems, such as recursive verifiers, don't have to parse the proof again.
+    pub fn verify_with_trace<E, T, Strategy>(
+        self,
+        strategy: Strategy,
+        transcript: &mut T,
+    ) -> Result<(Strategy::Output, VerifierTrace<Scheme::Curve>), Error>
+    where
+        E: EncodedChallenge<Scheme::Curve>,
+        T: TranscriptRead<Scheme::Curve, E>,
+        Strategy: VerificationStrategy<'params, Scheme, V>,
+    {
+        let mut trace = None;
+        let (output, _) =
+            self.verify_impl::<E, T, Strategy>(strategy, Some(&mut trace), transcript)?;
+        Ok((
+            output,
+            trace.expect("the trace is set once the proof is read"),
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn verify_impl<E, T, Strategy>(
+        self,
+        strategy: Strategy,
+        trace: Option<&mut Option<VerifierTrace<Scheme::Curve>>>,
+        transcript: &mut T,
+    ) -> Result<(Strategy::Output, Vec<Vec<Scheme::Scalar>>), Error>
+    where
+        E: EncodedChallenge<Scheme::Curve>,
+        T: TranscriptRead<Scheme::Curve, E>,
+        Strategy: VerificationStrategy<'params, Scheme, V>,
+    {
+        if let Some(label) = self.domain {
+            absorb_domain(transcript, label)?;
+        }
+        verify_proof_impl(
+            self.params,
+            self.vk,
+            strategy,
+            self.instances,
+            self.queries,
+            &self.query_order,
+            trace,
+            self.metrics,
+            self.instance_cache,
+            transcript,
+        )
+    }
+}
+
+/// Verifies a proof whose openings are split into the sections of `sections`, created
+/// with [`ProverV2::create_proof_with_sections`], and returns the sections whose
+/// arguments remain to be verified.
+///
+/// The rest of the proof is checked by this function, but its openings are only proven
+/// once the argument of each section is verified with [`verify_proof_section`]. The
+/// sections are independent, so that they can be verified in parallel, by different
+/// threads or machines. Sections without queries have no argument and are omitted.
+///
+/// [`ProverV2::create_proof_with_sections`]: crate::plonk::prover::ProverV2::create_proof_with_sections
+/// [`verify_proof_section`]: crate::plonk::verify_proof_section
+pub fn verify_split_proof<
+    'params,
+    Scheme: CommitmentScheme,
+    V: Verifier<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    T: TranscriptRead<Scheme::Curve, E>,
+>(
+    params: &'params Scheme::ParamsVerifier,
+    vk: &VerifyingKey<Scheme::Curve>,
+    instances: &[&[&[Scheme::Scalar]]],
+    sections: &ProofSections,
+    transcript: &mut T,
+) -> Result<Vec<ProofSection<Scheme::Curve>>, Error>
+where
+    Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    verify_proof_core::<Scheme, V, E, T, _>(
+        params,
+        vk,
+        SectionOpening,
+        instances,
+        &[],
+        sections.orders(),
+        None,
+        None,
+        None,
+        transcript,
+    )
+    .map(|(sections, _)| sections)
+}
+
+/// How the queries of a proof are opened, once the rest of the proof is verified.
+trait OpenQueries<'params, Scheme: CommitmentScheme, V: Verifier<'params, Scheme>> {
+    type Output;
+
+    /// Opens the queries of the proof, arranged in the query orders the proof was
+    /// verified with.
+    fn open<'com, E: EncodedChallenge<Scheme::Curve>, T: TranscriptRead<Scheme::Curve, E>>(
+        self,
+        params: &'params Scheme::ParamsVerifier,
+        transcript: &mut T,
+        query_sets: Vec<Vec<VerifierQuery<'com, Scheme::Curve, ParamsMsm<'params, Scheme>>>>,
+        stopwatch: &mut Stopwatch<'_>,
+    ) -> Result<Self::Output, Error>
+    where
+        'params: 'com;
+}
+
+/// The multiscalar multiplication of the verifier parameters of a commitment scheme.
+type ParamsMsm<'params, Scheme> = <<Scheme as CommitmentScheme>::ParamsVerifier as Params<
+    'params,
+    <Scheme as CommitmentScheme>::Curve,
+>>::MSM;
+
+/// Opens the queries of a proof with its multiopen argument, in a verification strategy.
+struct StrategyOpening<Strategy>(Strategy);
+
+impl<
+        'params,
+        Scheme: CommitmentScheme,
+        V: Verifier<'params, Scheme>,
+        Strategy: VerificationStrategy<'params, Scheme, V>,
+    > OpenQueries<'params, Scheme, V> for StrategyOpening<Strategy>
+{
+    type Output = Strategy::Output;
+
+    fn open<'com, E: EncodedChallenge<Scheme::Curve>, T: TranscriptRead<Scheme::Curve, E>>(
+        self,
+        params: &'params Scheme::ParamsVerifier,
+        transcript: &mut T,
+        mut query_sets: Vec<Vec<VerifierQuery<'com, Scheme::Curve, ParamsMsm<'params, Scheme>>>>,
+        stopwatch: &mut Stopwatch<'_>,
+    ) -> Result<Self::Output, Error>
+    where
+        'params: 'com,
+    {
+        let queries = query_sets.pop().expect("a proof has one query order");
+        let verifier = V::new(params);
+        transcript.label(TranscriptPhase::Multiopen, "opening proof");
+        let output = self.0.process(|msm| {
+            let guard = verifier
+                .verify_proof(transcript, queries, msm)
+                .map_err(|_| Error::Opening);
+            stopwatch.lap(|metrics| &mut metrics.msm);
+            guard
+        })?;
+        stopwatch.lap(|metrics| &mut metrics.pairing);
+        Ok(output)
+    }
+}
+
+/// Returns the queries of a split proof as the openings of its sections.
+struct SectionOpening;
+
+impl<'params, Scheme: CommitmentScheme, V: Verifier<'params, Scheme>>
+    OpenQueries<'params, Scheme, V> for SectionOpening
+{
+    type Output = Vec<ProofSection<Scheme::Curve>>;
+
+    fn open<'com, E: EncodedChallenge<Scheme::Curve>, T: TranscriptRead<Scheme::Curve, E>>(
+        self,
+        _params: &'params Scheme::ParamsVerifier,
+        transcript: &mut T,
+        query_sets: Vec<Vec<VerifierQuery<'com, Scheme::Curve, ParamsMsm<'params, Scheme>>>>,
+        stopwatch: &mut Stopwatch<'_>,
+    ) -> Result<Self::Output, Error>
+    where
+        'params: 'com,
+    {
+        // [TRANSCRIPT-25]
+        transcript.label(TranscriptPhase::Multiopen, "section seed");
+        let seed = transcript.squeeze_challenge().get_scalar();
+        let sections = query_sets
+            .iter()
+            .enumerate()
+            .filter(|(_, queries)| !queries.is_empty())
+            .map(|(index, queries)| ProofSection {
+                index,
+                seed,
+                openings: queries.iter().map(Opening::from_query).collect(),
+            })
+            .collect();
+        stopwatch.lap(|metrics| &mut metrics.msm);
+        Ok(sections)
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn verify_proof_impl<
+    'params,
+    Scheme: CommitmentScheme,
+    V: Verifier<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    T: TranscriptRead<Scheme::Curve, E>,
+    Strategy: VerificationStrategy<'params, Scheme, V>,
+>(
+    params: &'params Scheme::ParamsVerifier,
+    vk: &VerifyingKey<Scheme::Curve>,
+    strategy: Strategy,
+    instances: &[&[&[Scheme::Scalar]]],
+    queries: &[ExtraQuery<Scheme::Scalar>],
+    query_order: &QueryOrder,
+    trace: Option<&mut Option<VerifierTrace<Scheme::Curve>>>,
+    metrics: Option<&mut VerifierMetrics>,
+    instance_cache: Option<&InstanceCache<Scheme::Curve>>,
+    transcript: &mut T,
+) -> Result<(Strategy::Output, Vec<Vec<Scheme::Scalar>>), Error>
+where
+    Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    verify_proof_core::<Scheme, V, E, T, _>(
+        params,
+        vk,
+        StrategyOpening(strategy),
+        instances,
+        queries,
+        std::slice::from_ref(query_order),
+        trace,
+        metrics,
+        instance_cache,
+        transcript,
+    )
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn verify_proof_core<
+    'params,
+    Scheme: CommitmentScheme,
+    V: Verifier<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    T: TranscriptRead<Scheme::Curve, E>,
+    O: OpenQueries<'params, Scheme, V>,
+>(
+    params: &'params Scheme::ParamsVerifier,
+    vk: &VerifyingKey<Scheme::Curve>,
+    open: O,
+    instances: &[&[&[Scheme::Scalar]]],
+    queries: &[ExtraQuery<Scheme::Scalar>],
+    orders: &[QueryOrder],
+    trace: Option<&mut Option<VerifierTrace<Scheme::Curve>>>,
+    metrics: Option<&mut VerifierMetrics>,
+    instance_cache: Option<&InstanceCache<Scheme::Curve>>,
+    transcript: &mut T,
+) -> Result<(O::Output, Vec<Vec<Scheme::Scalar>>), Error>
+where
+    Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    if params.k() != vk.domain.k() {
+        return Err(Error::Other(format!(
+            "parameters for 2^{} rows can't verify a circuit with 2^{} rows",
+            params.k(),
+            vk.domain.k()
+        )));
+    }
+
+    check_extra_queries(queries, &vk.cs, params.n(), V::QUERY_INSTANCE)?;
+
     // Check that instances matches the expected number of instance columns
+    let max_instance_len = params.n() as usize - (vk.cs.blinding_factors() + 1);
     for instances in instances.iter() {
         if instances.len() != vk.cs.num_instance_columns {
             return Err(Error::InvalidInstances);
         }
+        if instances
+            .iter()
+            .any(|instance| instance.len() > max_instance_len)
+        {
+            return Err(Error::InstanceTooLarge);
+        }
+    }
+
+    // The values of blinded instance columns can only be hidden if they are committed.
+    if !vk.cs.blinded_instance_columns.is_empty() {
+        if !V::QUERY_INSTANCE {
+            return Err(Error::Other(
+                "blinded instance columns require a commitment scheme that commits to instance columns"
+                    .to_string(),
+            ));
+        }
+        if instances.iter().any(|instances| {
+            vk.cs
+                .blinded_instance_columns
+                .iter()
+                .any(|column| !instances[*column].is_empty())
+        }) {
+            return Err(Error::InvalidInstances);
+        }
     }
 
+    let mut stopwatch = Stopwatch::start(metrics);
+
     // 1. Get the commitments of the instance polynomials. ----------------------------------------
+    // The commitments of blinded instance columns are read from the proof in step 3.
 
-    let instance_commitments = if V::QUERY_INSTANCE {
+    let mut instance_commitments = if V::QUERY_INSTANCE {
         instances
             .iter()
             .map(|instance| {
                 instance
                     .iter()
                     .map(|instance| {
-                        if instance.len() > params.n() as usize - (vk.cs.blinding_factors() + 1) {
-                            return Err(Error::InstanceTooLarge);
-                        }
-                        let mut poly = instance.to_vec();
-                        poly.resize(params.n() as usize, Scheme::Scalar::ZERO);
-                        let poly = vk.domain.lagrange_from_vec(poly);
+                        let commit = || {
+                            let mut poly = instance.to_vec();
+                            poly.resize(params.n() as usize, Scheme::Scalar::ZERO);
+                            let poly = vk.domain.lagrange_from_vec(poly);
 
-                        Ok(params.commit_lagrange(&poly, Blind::default()).to_affine())
+                            params.commit_lagrange(&poly, Blind::default()).to_affine()
+                        };
+                        match instance_cache {
+                            Some(cache) => cache.commitment(instance, commit),
+                            None => commit(),
+                        }
                     })
-                    .collect::<Result<Vec<_>, _>>()
+                    .collect::<Vec<_>>()
             })
-            .collect::<Result<Vec<_>, _>>()?
+            .collect::<Vec<_>>()
     } else {
         vec![vec![]; instances.len()]
     };
 
     let num_proofs = instance_commitments.len();
+    stopwatch.lap(|metrics| &mut metrics.msm);
 
     // 2. Add hash of verification key and instances into transcript. -----------------------------
     // [TRANSCRIPT-1]
 
+    transcript.label(TranscriptPhase::VerifyingKey, "verifying key");
     vk.hash_into(transcript)?;
 
     // 3. Add instance commitments into the transcript. --------------------------------------------
     // [TRANSCRIPT-2]
 
+    transcript.label(TranscriptPhase::Instances, "instances");
     if V::QUERY_INSTANCE {
-        for instance_commitments in instance_commitments.iter() {
+        for instance_commitments in instance_commitments.iter_mut() {
             // Hash the instance (external) commitments into the transcript
-            for commitment in instance_commitments {
-                transcript.common_point(*commitment)?
+            for (column_index, commitment) in instance_commitments.iter_mut().enumerate() {
+                if vk.cs.blinded_instance_columns.contains(&column_index) {
+                    *commitment = transcript.read_point()?;
+                } else {
+                    transcript.common_point(*commitment)?
+                }
             }
         }
     } else {
@@ -131,6 +742,10 @@ where
 
         for current_phase in vk.cs.phases() {
             // [TRANSCRIPT-3]
+            transcript.label(
+                TranscriptPhase::Advice(current_phase.0),
+                "advice commitments",
+            );
             for advice_commitments in advice_commitments.iter_mut() {
                 for (phase, commitment) in vk
                     .cs
@@ -145,6 +760,7 @@ where
             }
 
             // [TRANSCRIPT-4]
+            transcript.label(TranscriptPhase::Advice(current_phase.0), "challenges");
             for (phase, challenge) in vk.cs.challenge_phase.iter().zip(challenges.iter_mut()) {
                 if current_phase == *phase {
                     *challenge = *transcript.squeeze_challenge_scalar::<()>();
@@ -158,11 +774,13 @@ where
     // 4. Sample theta challenge for keeping lookup columns linearly independent ------------------
     // [TRANSCRIPT-5]
 
+    transcript.label(TranscriptPhase::Lookups, "theta");
     let theta: ChallengeTheta<_> = transcript.squeeze_challenge_scalar();
 
     // 5. Read lookup permuted commitments
     // [TRANSCRIPT-6]
 
+    transcript.label(TranscriptPhase::Lookups, "permuted commitments");
     let lookups_permuted = (0..num_proofs)
         .map(|_| -> Result<Vec<_>, _> {
             // Hash each lookup permuted commitment
@@ -178,15 +796,18 @@ where
 
     // Sample beta challenge
     // [TRANSCRIPT-7]
+    transcript.label(TranscriptPhase::Permutation, "beta");
     let beta: ChallengeBeta<_> = transcript.squeeze_challenge_scalar();
 
     // Sample gamma challenge
     // [TRANSCRIPT-8]
+    transcript.label(TranscriptPhase::Permutation, "gamma");
     let gamma: ChallengeGamma<_> = transcript.squeeze_challenge_scalar();
 
     // 7. Read commitments for permutation, lookups, and shuffles ---------------------------------
 
     // [TRANSCRIPT-9]
+    transcript.label(TranscriptPhase::Permutation, "product commitments");
     let permutations_committed = (0..num_proofs)
         .map(|_| {
             // Hash each permutation product commitment
@@ -195,6 +816,7 @@ where
         .collect::<Result<Vec<_>, _>>()?;
 
     // [TRANSCRIPT-10]
+    transcript.label(TranscriptPhase::Lookups, "product commitments");
     let lookups_committed = lookups_permuted
         .into_iter()
         .map(|lookups| {
@@ -207,6 +829,7 @@ where
         .collect::<Result<Vec<_>, _>>()?;
 
     // [TRANSCRIPT-11]
+    transcript.label(TranscriptPhase::Shuffles, "product commitments");
     let shuffles_committed = (0..num_proofs)
         .map(|_| -> Result<Vec<_>, _> {
             // Hash each shuffle product commitment
@@ -220,22 +843,69 @@ where
 
     // 8. Read vanishing argument (before y) ------------------------------------------------------
     // [TRANSCRIPT-12]
+    transcript.label(TranscriptPhase::Vanishing, "random commitment");
     let vanishing = vanishing::Argument::read_commitments_before_y(transcript)?;
 
     // 9. Sample y challenge, which keeps the gates linearly independent. -------------------------
     // [TRANSCRIPT-13]
+    transcript.label(TranscriptPhase::Vanishing, "y");
     let y: ChallengeY<_> = transcript.squeeze_challenge_scalar();
 
     // 10. Read vanishing argument (after y) ------------------------------------------------------
     // [TRANSCRIPT-14]
+    transcript.label(TranscriptPhase::Vanishing, "h commitments");
     let vanishing = vanishing.read_commitments_after_y(vk, transcript)?;
 
     // 11. Sample x challenge, which is used to ensure the circuit is
     // satisfied with high probability. -----------------------------------------------------------
     // [TRANSCRIPT-15]
+    transcript.label(TranscriptPhase::Evaluations, "x");
     let x: ChallengeX<_> = transcript.squeeze_challenge_scalar();
+    stopwatch.lap(|metrics| &mut metrics.transcript);
+
+    // x^n
+    let xn = x.pow([params.n()]);
+
+    // The rotations of the Lagrange polynomials which evaluate the instance columns, if
+    // they aren't queried
+    let (min_rotation, max_rotation) =
+        vk.cs
+            .instance_queries
+            .iter()
+            .fold((0, 0), |(min, max), (_, rotation)| {
+                if rotation.0 < min {
+                    (rotation.0, max)
+                } else if rotation.0 > max {
+                    (min, rotation.0)
+                } else {
+                    (min, max)
+                }
+            });
+    let instance_rotations = if V::QUERY_INSTANCE {
+        0..0
+    } else {
+        let max_instance_len = instances
+            .iter()
+            .flat_map(|instance| instance.iter().map(|instance| instance.len()))
+            .max_by(Ord::cmp)
+            .unwrap_or_default();
+        -max_rotation..max_instance_len as i32 + min_rotation.abs()
+    };
+
+    // All the inverses at x, computed together
+    let blinding_factors = vk.cs.blinding_factors();
+    let inverses = Inverses::new(
+        &vk.domain,
+        *x,
+        xn,
+        -((blinding_factors + 1) as i32)..1,
+        instance_rotations,
+        vk.cs.gates.iter().map(|gate| gate.subdomain()),
+    )?;
+    stopwatch.lap(|metrics| &mut metrics.evaluation);
 
     // 12. Get the instance evaluations
+    transcript.label(TranscriptPhase::Evaluations, "instance evaluations");
     let instance_evals = if V::QUERY_INSTANCE {
         // [TRANSCRIPT-16]
         (0..num_proofs)
@@ -244,30 +914,7 @@ where
             })
             .collect::<Result<Vec<_>, _>>()?
     } else {
-        let xn = x.pow([params.n()]);
-        let (min_rotation, max_rotation) =
-            vk.cs
-                .instance_queries
-                .iter()
-                .fold((0, 0), |(min, max), (_, rotation)| {
-                    if rotation.0 < min {
-                        (rotation.0, max)
-                    } else if rotation.0 > max {
-                        (min, rotation.0)
-                    } else {
-                        (min, max)
-                    }
-                });
-        let max_instance_len = instances
-            .iter()
-            .flat_map(|instance| instance.iter().map(|instance| instance.len()))
-            .max_by(Ord::cmp)
-            .unwrap_or_default();
-        let l_i_s = &vk.domain.l_i_range(
-            *x,
-            xn,
-            -max_rotation..max_instance_len as i32 + min_rotation.abs(),
-        );
+        let l_i_s = &inverses.instance_l_evals;
         instances
             .iter()
             .map(|instances| {
@@ -283,28 +930,42 @@ where
             })
             .collect::<Vec<_>>()
     };
+    if V::QUERY_INSTANCE {
+        stopwatch.lap(|metrics| &mut metrics.transcript);
+    } else {
+        stopwatch.lap(|metrics| &mut metrics.evaluation);
+    }
 
     // [TRANSCRIPT-17]
+    transcript.label(TranscriptPhase::Evaluations, "advice evaluations");
     let advice_evals = (0..num_proofs)
         .map(|_| -> Result<Vec<_>, _> { read_n_scalars(transcript, vk.cs.advice_queries.len()) })
         .collect::<Result<Vec<_>, _>>()?;
 
     // [TRANSCRIPT-18]
+    transcript.label(TranscriptPhase::Evaluations, "fixed evaluations");
     let fixed_evals = read_n_scalars(transcript, vk.cs.fixed_queries.len())?;
 
     // [TRANSCRIPT-19]
+    transcript.label(TranscriptPhase::Evaluations, "vanishing evaluations");
     let vanishing = vanishing.evaluate_after_x(transcript)?;
 
     // [TRANSCRIPT-20]
+    transcript.label(
+        TranscriptPhase::Evaluations,
+        "permutation common evaluations",
+    );
     let permutations_common = vk.permutation.evaluate(transcript)?;
 
     // [TRANSCRIPT-21]
+    transcript.label(TranscriptPhase::Evaluations, "permutation evaluations");
     let permutations_evaluated = permutations_committed
         .into_iter()
         .map(|permutation| permutation.evaluate(transcript))
         .collect::<Result<Vec<_>, _>>()?;
 
     // [TRANSCRIPT-22]
+    transcript.label(TranscriptPhase::Evaluations, "lookup evaluations");
     let lookups_evaluated = lookups_committed
         .into_iter()
         .map(|lookups| -> Result<Vec<_>, _> {
@@ -316,6 +977,7 @@ where
         .collect::<Result<Vec<_>, _>>()?;
 
     // [TRANSCRIPT-23]
+    transcript.label(TranscriptPhase::Evaluations, "shuffle evaluations");
     let shuffles_evaluated = shuffles_committed
         .into_iter()
         .map(|shuffles| -> Result<Vec<_>, _> {
@@ -326,68 +988,90 @@ where
         })
         .collect::<Result<Vec<_>, _>>()?;
 
+    // [TRANSCRIPT-24]
+    transcript.label(TranscriptPhase::Evaluations, "extra queries");
+    for query in queries {
+        transcript.common_scalar(query.point())?;
+    }
+    let extra_evals = (0..num_proofs)
+        .map(|_| -> Result<Vec<_>, _> { read_n_scalars(transcript, queries.len()) })
+        .collect::<Result<Vec<_>, _>>()?;
+    stopwatch.lap(|metrics| &mut metrics.transcript);
+
     // This check ensures the circuit is satisfied so long as the polynomial
     // commitments open to the correct values.
     let vanishing = {
-        // x^n
-        let xn = x.pow([params.n()]);
-
-        let blinding_factors = vk.cs.blinding_factors();
-        let l_evals = vk
-            .domain
-            .l_i_range(*x, xn, (-((blinding_factors + 1) as i32))..=0);
-        assert_eq!(l_evals.len(), 2 + blinding_factors);
+        let l_evals = &inverses.l_evals;
+        if l_evals.len() != 2 + blinding_factors {
+            return Err(Error::ConstraintSystemFailure);
+        }
         let l_last = l_evals[0];
         let l_blind: Scheme::Scalar = l_evals[1..(1 + blinding_factors)]
             .iter()
             .fold(Scheme::Scalar::ZERO, |acc, eval| acc + eval);
         let l_0 = l_evals[1 + blinding_factors];
 
-        // Compute the expected value of h(x)
-        let expressions = advice_evals
+        let selectors = gate_selectors(&vk.cs, params.n() as usize, &inverses);
+
+        // Evaluate the circuit using the custom gates provided, and the permutation
+        // argument, which reject evaluations inconsistent with the key.
+        let gate_evals = advice_evals
+            .iter()
+            .zip(instance_evals.iter())
+            .map(|(advice_evals, instance_evals)| {
+                evaluate_gates(
+                    &vk.cs.gates,
+                    &selectors,
+                    &fixed_evals,
+                    advice_evals,
+                    instance_evals,
+                    &challenges,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let permutation_expressions = permutations_evaluated
             .iter()
+            .zip(advice_evals.iter())
+            .zip(instance_evals.iter())
+            .map(|((permutation, advice_evals), instance_evals)| {
+                permutation.expressions(
+                    vk,
+                    &vk.cs.permutation,
+                    &permutations_common,
+                    advice_evals,
+                    &fixed_evals,
+                    instance_evals,
+                    l_0,
+                    l_last,
+                    l_blind,
+                    beta,
+                    gamma,
+                    x,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Compute the expected value of h(x)
+        let expressions = gate_evals
+            .into_iter()
+            .zip(permutation_expressions)
+            .zip(advice_evals.iter())
             .zip(instance_evals.iter())
-            .zip(permutations_evaluated.iter())
             .zip(lookups_evaluated.iter())
             .zip(shuffles_evaluated.iter())
             .flat_map(
-                |((((advice_evals, instance_evals), permutation), lookups), shuffles)| {
+                |(
+                    (
+                        (((gate_evals, permutation_expressions), advice_evals), instance_evals),
+                        lookups,
+                    ),
+                    shuffles,
+                )| {
                     let challenges = &challenges;
                     let fixed_evals = &fixed_evals;
                     std::iter::empty()
-                        // Evaluate the circuit using the custom gates provided
-                        .chain(vk.cs.gates.iter().flat_map(move |gate| {
-                            gate.polynomials().iter().map(move |poly| {
-                                poly.evaluate(
-                                    &|scalar| scalar,
-                                    &|_| {
-                                        panic!("virtual selectors are removed during optimization")
-                                    },
-                                    &|query| fixed_evals[query.index.unwrap()],
-                                    &|query| advice_evals[query.index.unwrap()],
-                                    &|query| instance_evals[query.index.unwrap()],
-                                    &|challenge| challenges[challenge.index()],
-                                    &|a| -a,
-                                    &|a, b| a + b,
-                                    &|a, b| a * b,
-                                    &|a, scalar| a * scalar,
-                                )
-                            })
-                        }))
-                        .chain(permutation.expressions(
-                            vk,
-                            &vk.cs.permutation,
-                            &permutations_common,
-                            advice_evals,
-                            fixed_evals,
-                            instance_evals,
-                            l_0,
-                            l_last,
-                            l_blind,
-                            beta,
-                            gamma,
-                            x,
-                        ))
+                        .chain(gate_evals)
+                        .chain(permutation_expressions)
                         .chain(lookups.iter().zip(vk.cs.lookups.iter()).flat_map(
                             move |(p, argument)| {
                                 p.expressions(
@@ -424,71 +1108,116 @@ where
                 },
             );
 
-        vanishing.verify(params, expressions, y, xn)
+        vanishing.verify(params, expressions, y, xn, inverses.zh_inv)
     };
 
-    #[rustfmt::skip]
-    let queries = instance_commitments
+    let query_sets = orders
         .iter()
-        .zip(instance_evals.iter())
-        .zip(advice_commitments.iter())
-        .zip(advice_evals.iter())
-        .zip(permutations_evaluated.iter())
-        .zip(lookups_evaluated.iter())
-        .zip(shuffles_evaluated.iter())
-        .flat_map(|((((((instance_commitments, instance_evals), advice_commitments),advice_evals),permutation),lookups),shuffles)| {
-                iter::empty()
-                    .chain(
-                        V::QUERY_INSTANCE
-                            .then_some(vk.cs.instance_queries.iter().enumerate().map(
-                                move |(query_index, &(column, at))| {
+        .map(|order| {
+            order.arrange(
+                instance_commitments.len(),
+                |group, circuit, out| {
+                    let instance_commitments = &instance_commitments[circuit];
+                    let advice_commitments = &advice_commitments[circuit];
+                    match group {
+                        QueryGroup::Instance => {
+                            if V::QUERY_INSTANCE {
+                                out.extend(vk.cs.instance_queries.iter().enumerate().map(
+                                    |(query_index, &(column, at))| {
+                                        VerifierQuery::new_commitment(
+                                            &instance_commitments[column.index()],
+                                            vk.domain.rotate_omega(*x, at),
+                                            instance_evals[circuit][query_index],
+                                        )
+                                    },
+                                ))
+                            }
+                        }
+                        QueryGroup::Advice => {
+                            out.extend(vk.cs.advice_queries.iter().enumerate().map(
+                                |(query_index, &(column, at))| {
                                     VerifierQuery::new_commitment(
-                                        &instance_commitments[column.index()],
+                                        &advice_commitments[column.index()],
                                         vk.domain.rotate_omega(*x, at),
-                                        instance_evals[query_index],
+                                        advice_evals[circuit][query_index],
                                     )
                                 },
                             ))
-                            .into_iter()
-                            .flatten(),
-                    )
-                    .chain(vk.cs.advice_queries.iter().enumerate().map(
-                        move |(query_index, &(column, at))| {
+                        }
+                        QueryGroup::Permutation => {
+                            out.extend(permutations_evaluated[circuit].queries(vk, x))
+                        }
+                        QueryGroup::Lookups => out.extend(
+                            lookups_evaluated[circuit]
+                                .iter()
+                                .flat_map(|p| p.queries(vk, x)),
+                        ),
+                        QueryGroup::Shuffles => out.extend(
+                            shuffles_evaluated[circuit]
+                                .iter()
+                                .flat_map(|p| p.queries(vk, x)),
+                        ),
+                        QueryGroup::ExtraQueries => {
+                            out.extend(queries.iter().zip(extra_evals[circuit].iter()).map(
+                                |(query, eval)| {
+                                    let column = query.column();
+                                    let commitment = match column.column_type() {
+                                        Any::Advice(_) => &advice_commitments[column.index()],
+                                        Any::Fixed => &vk.fixed_commitments[column.index()],
+                                        Any::Instance => &instance_commitments[column.index()],
+                                    };
+                                    VerifierQuery::new_commitment(commitment, query.point(), *eval)
+                                },
+                            ))
+                        }
+                        group => unreachable!("{:?} is not a per-circuit group", group),
+                    }
+                },
+                |group, out| match group {
+                    QueryGroup::Fixed => out.extend(vk.cs.fixed_queries.iter().enumerate().map(
+                        |(query_index, &(column, at))| {
                             VerifierQuery::new_commitment(
-                                &advice_commitments[column.index()],
+                                &vk.fixed_commitments[column.index()],
                                 vk.domain.rotate_omega(*x, at),
-                                advice_evals[query_index],
+                                fixed_evals[query_index],
                             )
                         },
-                    ))
-                    .chain(permutation.queries(vk, x))
-                    .chain(lookups.iter().flat_map(move |p| p.queries(vk, x)))
-                    .chain(shuffles.iter().flat_map(move |p| p.queries(vk, x)))
-            },
-        )
-        .chain(
-            vk.cs
-                .fixed_queries
+                    )),
+                    QueryGroup::PermutationCommon => {
+                        out.extend(permutations_common.queries(&vk.permutation, x))
+                    }
+                    QueryGroup::Vanishing => out.extend(vanishing.queries(x)),
+                    group => unreachable!("{:?} is a per-circuit group", group),
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    stopwatch.lap(|metrics| &mut metrics.evaluation);
+
+    if let Some(trace) = trace {
+        *trace = Some(VerifierTrace {
+            instance_commitments: instance_commitments.clone(),
+            advice_commitments: advice_commitments.clone(),
+            challenges: challenges.clone(),
+            theta: *theta,
+            beta: *beta,
+            gamma: *gamma,
+            y: *y,
+            x: *x,
+            instance_evals: instance_evals.clone(),
+            advice_evals: advice_evals.clone(),
+            fixed_evals: fixed_evals.clone(),
+            openings: query_sets
                 .iter()
-                .enumerate()
-                .map(|(query_index, &(column, at))| {
-                    VerifierQuery::new_commitment(
-                        &vk.fixed_commitments[column.index()],
-                        vk.domain.rotate_omega(*x, at),
-                        fixed_evals[query_index],
-                    )
-                }),
-        )
-        .chain(permutations_common.queries(&vk.permutation, x))
-        .chain(vanishing.queries(x));
+                .flatten()
+                .map(Opening::from_query)
+                .collect(),
+        });
+    }
 
     // We are now convinced the circuit is satisfied so long as the
     // polynomial commitments open to the correct values.
 
-    let verifier = V::new(params);
-    strategy.process(|msm| {
-        verifier
-            .verify_proof(transcript, queries, msm)
-            .map_err(|_| Error::Opening)
-    })
+    let output = open.open(params, transcript, query_sets, &mut stopwatch)?;
+    Ok((output, extra_evals))
 }
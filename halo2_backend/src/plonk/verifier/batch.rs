@@ -4,11 +4,9 @@ use halo2_middleware::ff::FromUniformBytes;
 use halo2curves::CurveAffine;
 use rand_core::OsRng;
 
-use super::{verify_proof, VerificationStrategy};
+use super::{InstanceCache, VerificationStrategy, VerifierBuilder};
 use crate::{
-    multicore::{
-        IndexedParallelIterator, IntoParallelIterator, ParallelIterator, TryFoldAndReduce,
-    },
+    multicore::prelude::*,
     plonk::VerifyingKey,
     poly::{
         commitment::{Params, MSM},
@@ -87,10 +85,24 @@ where
     /// Returns `false` if *some* proof was invalid. If the caller needs to identify
     /// specific failing proofs, it must re-process the proofs separately.
     ///
+    /// The instance columns shared by several proofs of the batch are only committed to
+    /// once.
+    ///
     /// This uses [`OsRng`] internally instead of taking an `R: RngCore` argument, because
     /// the internal parallelization requires access to a RNG that is guaranteed to not
     /// clone its internal state when shared between threads.
     pub fn finalize(self, params: &ParamsVerifierIPA<C>, vk: &VerifyingKey<C>) -> bool {
+        self.finalize_with_instance_cache(params, vk, &InstanceCache::new())
+    }
+
+    /// Finalizes the batch like [`Self::finalize`], with the commitments to instance
+    /// columns of `cache`, so that they can be shared by several batches.
+    pub fn finalize_with_instance_cache(
+        self,
+        params: &ParamsVerifierIPA<C>,
+        vk: &VerifyingKey<C>,
+        cache: &InstanceCache<C>,
+    ) -> bool {
         fn accumulate_msm<'params, C: CurveAffine>(
             mut acc: MSMIPA<'params, C>,
             msm: MSMIPA<'params, C>,
@@ -118,7 +130,12 @@ where
 
                 let strategy = BatchStrategy::new(params);
                 let mut transcript = Blake2bRead::init(&item.proof[..]);
-                verify_proof(params, vk, strategy, &instances, &mut transcript).map_err(|e| {
+                VerifierBuilder::<IPACommitmentScheme<C>, VerifierIPA<_>>::new(
+                    params, vk, &instances,
+                )
+                .with_instance_cache(cache)
+                .verify(strategy, &mut transcript)
+                .map_err(|e| {
                     tracing::debug!("Batch item {} failed verification: {}", i, e);
                     e
                 })
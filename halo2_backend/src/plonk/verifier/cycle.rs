@@ -0,0 +1,291 @@
+//! Plumbing for recursion on a cycle of curves, such as the Pasta curves Pallas and Vesta.
+//!
+//! The scalar field of each curve of a cycle is the base field of the other one, so a
+//! circuit over one curve performs the arithmetic of the points of the other one natively
+//! and can verify its IPA proofs, up to the computation of the point `g` of the inner
+//! product argument, which is linear in the size of the circuit. This computation is
+//! deferred to an [`Accumulator`], which the next circuit of the cycle takes as instances,
+//! and which is eventually checked with [`Accumulator::check`].
+//!
+//! [`DeferredStrategy`] verifies a proof up to its accumulator and returns it, and
+//! [`CycleProof`] pairs a proof over one curve with the accumulator of the proofs it
+//! verified over the other curve.
+//!
+//! [`accumulator_to_instances`] encodes an accumulator over a curve as scalars of the other
+//! curve: the coordinates of `g` are native, and the challenges, which are scalars of the
+//! curve of the accumulator, are decomposed into little-endian limbs of [`LIMB_BITS`]
+//! bits. Conversions between the fields go through the little-endian bytes of the
+//! canonical representation of the elements, whatever the byte order of the
+//! representation of each field.
+
+use std::marker::PhantomData;
+
+use halo2_middleware::ff::{Field, PrimeField};
+use halo2curves::pasta::{pallas, vesta};
+use halo2curves::{Coordinates, CurveAffine};
+
+use crate::plonk::Error;
+use crate::poly::commitment::MSM;
+use crate::poly::ipa::commitment::{IPACommitmentScheme, ParamsIPA};
+use crate::poly::ipa::msm::MSMIPA;
+use crate::poly::ipa::multiopen::VerifierIPA;
+use crate::poly::ipa::strategy::{Accumulator, GuardIPA};
+use crate::poly::VerificationStrategy;
+
+/// A cycle of curves, in which the scalar field of each curve is the base field of the
+/// other one.
+pub trait CurveCycle {
+    /// The first curve of the cycle.
+    type Primary: CurveAffine<
+        Base = <Self::Secondary as CurveAffine>::ScalarExt,
+        ScalarExt = <Self::Secondary as CurveAffine>::Base,
+    >;
+    /// The second curve of the cycle.
+    type Secondary: CurveAffine;
+}
+
+/// The cycle of the Pasta curves, Pallas and Vesta.
+#[derive(Clone, Copy, Debug)]
+pub struct Pasta;
+
+impl CurveCycle for Pasta {
+    type Primary = pallas::Affine;
+    type Secondary = vesta::Affine;
+}
+
+/// The number of bits of the limbs of the elements of the other field of a cycle, which
+/// fit into the fields of the curves of the cycles of this crate.
+pub const LIMB_BITS: usize = 128;
+
+/// Whether the representation of the elements of `F` is little-endian.
+fn is_little_endian<F: PrimeField>() -> bool {
+    F::ONE.to_repr().as_ref()[0] == 1
+}
+
+/// Returns the little-endian bytes of the canonical representation of `value`.
+pub fn to_le_bytes<F: PrimeField>(value: &F) -> Vec<u8> {
+    let mut bytes = value.to_repr().as_ref().to_vec();
+    if !is_little_endian::<F>() {
+        bytes.reverse();
+    }
+    bytes
+}
+
+/// Returns the element of `F` with the little-endian bytes `bytes`, which may be shorter
+/// than its representation, or `None` if it isn't less than the modulus.
+pub fn from_le_bytes<F: PrimeField>(bytes: &[u8]) -> Option<F> {
+    let mut repr = F::Repr::default();
+    let len = repr.as_ref().len();
+    if bytes.iter().skip(len).any(|byte| *byte != 0) {
+        return None;
+    }
+    let copied = bytes.len().min(len);
+    repr.as_mut()[..copied].copy_from_slice(&bytes[..copied]);
+    if !is_little_endian::<F>() {
+        repr.as_mut().reverse();
+    }
+    F::from_repr(repr).into()
+}
+
+/// Converts `value` into an element of the field `B` with the same integer value, or
+/// returns `None` if it isn't less than the modulus of `B`.
+pub fn convert_field<A: PrimeField, B: PrimeField>(value: &A) -> Option<B> {
+    from_le_bytes(&to_le_bytes(value))
+}
+
+/// Decomposes `value` into little-endian limbs of [`LIMB_BITS`] bits, as elements of the
+/// field `B`.
+pub fn decompose<A: PrimeField, B: PrimeField>(value: &A) -> Vec<B> {
+    to_le_bytes(value)
+        .chunks(LIMB_BITS / 8)
+        .map(|limb| from_le_bytes(limb).expect("limbs fit into the field"))
+        .collect()
+}
+
+/// Returns the number of limbs [`decompose`] returns for the elements of `F`.
+pub fn num_limbs<F: PrimeField>() -> usize {
+    (F::Repr::default().as_ref().len() * 8 + LIMB_BITS - 1) / LIMB_BITS
+}
+
+/// Recomposes the element of `A` decomposed into `limbs` by [`decompose`], or returns
+/// `None` if a limb has more than [`LIMB_BITS`] bits or the element isn't less than the
+/// modulus of `A`.
+pub fn recompose<A: PrimeField, B: PrimeField>(limbs: &[B]) -> Option<A> {
+    let mut bytes = Vec::with_capacity(limbs.len() * LIMB_BITS / 8);
+    for limb in limbs {
+        let limb = to_le_bytes(limb);
+        if limb.iter().skip(LIMB_BITS / 8).any(|byte| *byte != 0) {
+            return None;
+        }
+        bytes.extend(limb.into_iter().take(LIMB_BITS / 8));
+    }
+    from_le_bytes(&bytes)
+}
+
+/// Encodes `accumulator` as scalars of the other curve of a cycle: the coordinates of `g`,
+/// which are zero for the identity, followed by the [`num_limbs`] limbs of each challenge.
+pub fn accumulator_to_instances<C: CurveAffine>(accumulator: &Accumulator<C>) -> Vec<C::Base> {
+    let coordinates: Option<Coordinates<C>> = accumulator.g.coordinates().into();
+    let (x, y) = coordinates
+        .map(|coordinates| (*coordinates.x(), *coordinates.y()))
+        .unwrap_or((C::Base::ZERO, C::Base::ZERO));
+    let mut instances = vec![x, y];
+    for challenge in &accumulator.u_packed {
+        instances.extend(decompose::<_, C::Base>(challenge));
+    }
+    instances
+}
+
+/// Decodes an accumulator with `k` challenges encoded by [`accumulator_to_instances`].
+///
+/// Returns an error if the instances don't encode such an accumulator, for example if
+/// `g` isn't on the curve.
+pub fn accumulator_from_instances<C: CurveAffine>(
+    instances: &[C::Base],
+    k: usize,
+) -> Result<Accumulator<C>, Error> {
+    let limbs = num_limbs::<C::Scalar>();
+    if instances.len() != 2 + k * limbs {
+        return Err(Error::InvalidInstances);
+    }
+    let (x, y) = (instances[0], instances[1]);
+    let g = if bool::from(x.is_zero() & y.is_zero()) {
+        C::identity()
+    } else {
+        Option::<C>::from(C::from_xy(x, y)).ok_or(Error::InvalidInstances)?
+    };
+    let u_packed = instances[2..]
+        .chunks(limbs)
+        .map(|limbs| recompose(limbs).ok_or(Error::InvalidInstances))
+        .collect::<Result<_, _>>()?;
+    Ok(Accumulator { g, u_packed })
+}
+
+/// A proof over the curve `C`, paired with the accumulator of the proofs over the other
+/// curve `D` of a cycle which its circuit verified.
+#[derive(Clone, Debug)]
+pub struct CycleProof<C: CurveAffine, D: CurveAffine<Base = C::ScalarExt>> {
+    /// The proof, over `C`.
+    pub proof: Vec<u8>,
+    /// The accumulator of the proofs verified by the circuit of the proof, over `D`.
+    pub accumulator: Accumulator<D>,
+    _marker: PhantomData<C>,
+}
+
+impl<C: CurveAffine, D: CurveAffine<Base = C::ScalarExt>> CycleProof<C, D> {
+    /// Pairs `proof` with `accumulator`.
+    pub fn new(proof: Vec<u8>, accumulator: Accumulator<D>) -> Self {
+        CycleProof {
+            proof,
+            accumulator,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the instances encoding the accumulator, see [`accumulator_to_instances`].
+    pub fn accumulator_instances(&self) -> Vec<C::Scalar> {
+        accumulator_to_instances(&self.accumulator)
+    }
+}
+
+/// A strategy that verifies a single IPA proof up to its accumulator, which it returns.
+///
+/// The point `g` of the accumulator, which an in-circuit verifier takes from the prover,
+/// is computed natively, and the proof is valid if [`Accumulator::check`] holds for the
+/// accumulator.
+#[derive(Debug)]
+pub struct DeferredStrategy<'params, C: CurveAffine> {
+    msm: MSMIPA<'params, C>,
+}
+
+impl<'params, C: CurveAffine>
+    VerificationStrategy<'params, IPACommitmentScheme<C>, VerifierIPA<'params, C>>
+    for DeferredStrategy<'params, C>
+{
+    type Output = Accumulator<C>;
+
+    fn new(params: &'params ParamsIPA<C>) -> Self {
+        DeferredStrategy {
+            msm: MSMIPA::new(params),
+        }
+    }
+
+    fn process(
+        self,
+        f: impl FnOnce(MSMIPA<'params, C>) -> Result<GuardIPA<'params, C>, Error>,
+    ) -> Result<Self::Output, Error> {
+        let guard = f(self.msm)?;
+        let g = guard.compute_g();
+        let (msm, accumulator) = guard.use_g(g);
+        if msm.check() {
+            Ok(accumulator)
+        } else {
+            Err(Error::ConstraintSystemFailure)
+        }
+    }
+
+    fn finalize(self) -> bool {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poly::commitment::ParamsProver;
+    use group::{prime::PrimeCurveAffine, Curve, Group};
+    use halo2curves::pasta::{Fp, Fq};
+    use rand_core::OsRng;
+
+    #[test]
+    fn field_conversions() {
+        let value = Fp::random(OsRng);
+        assert_eq!(to_le_bytes(&value), value.to_repr().to_vec());
+        assert_eq!(from_le_bytes::<Fp>(&to_le_bytes(&value)), Some(value));
+
+        // The modulus of Fp is smaller than the one of Fq.
+        assert_eq!(convert_field::<Fp, Fq>(&Fp::from(7)), Some(Fq::from(7)));
+        assert_eq!(
+            convert_field::<Fq, Fp>(&convert_field::<Fp, Fq>(&value).unwrap()),
+            Some(value)
+        );
+        assert_eq!(convert_field::<Fq, Fp>(&-Fq::ONE), None);
+
+        let limbs = decompose::<Fq, Fp>(&-Fq::ONE);
+        assert_eq!(limbs.len(), num_limbs::<Fq>());
+        assert_eq!(recompose::<Fq, Fp>(&limbs), Some(-Fq::ONE));
+        // Limbs must have at most `LIMB_BITS` bits.
+        assert_eq!(recompose::<Fq, Fp>(&[-Fp::ONE, Fp::ZERO]), None);
+    }
+
+    #[test]
+    fn accumulator_instances() {
+        let params = ParamsIPA::<vesta::Affine>::new(3);
+        let u_packed: Vec<_> = (0..3).map(|_| Fp::random(OsRng)).collect();
+        let accumulator = Accumulator {
+            g: (vesta::Point::generator() * Fp::from(5)).to_affine(),
+            u_packed,
+        };
+        let instances: Vec<Fq> = accumulator_to_instances(&accumulator);
+        assert_eq!(instances.len(), 2 + 3 * num_limbs::<Fp>());
+        let decoded = accumulator_from_instances::<vesta::Affine>(&instances, 3).unwrap();
+        assert_eq!(decoded.g, accumulator.g);
+        assert_eq!(decoded.u_packed, accumulator.u_packed);
+        assert!(!decoded.check(&params));
+
+        let identity = Accumulator {
+            g: vesta::Affine::identity(),
+            u_packed: vec![],
+        };
+        let decoded =
+            accumulator_from_instances::<vesta::Affine>(&accumulator_to_instances(&identity), 0)
+                .unwrap();
+        assert_eq!(decoded.g, identity.g);
+
+        // A point off the curve is rejected.
+        let mut instances = instances;
+        instances[1] += Fq::ONE;
+        assert!(accumulator_from_instances::<vesta::Affine>(&instances, 3).is_err());
+        assert!(accumulator_from_instances::<vesta::Affine>(&instances[1..], 3).is_err());
+    }
+}
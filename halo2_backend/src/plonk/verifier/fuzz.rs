@@ -0,0 +1,137 @@
+//! Entry points for fuzzing the verifier.
+//!
+//! [`verify_arbitrary_bytes`] accepts any byte string, so that it can be driven directly
+//! by a fuzzer. The bytes are decoded as a [`FuzzInput`], which holds the instances of a
+//! single circuit followed by the proof. Valid proofs can be encoded with
+//! [`FuzzInput::to_bytes`] to build a structured corpus, from which the fuzzer can reach
+//! the deeper parts of the verifier.
+
+use halo2_middleware::ff::{FromUniformBytes, PrimeField, WithSmallOrderMulGroup};
+
+use super::verify_proof;
+use crate::plonk::VerifyingKey;
+use crate::poly::commitment::{CommitmentScheme, Verifier};
+use crate::poly::VerificationStrategy;
+use crate::transcript::{Blake2bRead, Challenge255, TranscriptReadBuffer};
+
+/// The input of [`verify_arbitrary_bytes`].
+///
+/// The encoding is the number of instance columns as a `u8`, then for each of them its
+/// length as a `u8` followed by the canonical representations of its values, and finally
+/// the proof, which spans the remaining bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzInput<F> {
+    /// The instance values, arranged as `[column][row]`.
+    pub instances: Vec<Vec<F>>,
+    /// The proof, as written by a Blake2b transcript.
+    pub proof: Vec<u8>,
+}
+
+impl<F: PrimeField> FuzzInput<F> {
+    /// Decodes a fuzz input.
+    ///
+    /// Returns `None` if `bytes` is too short or contains a non-canonical field element.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&num_columns, mut bytes) = bytes.split_first()?;
+        let mut instances = Vec::with_capacity(num_columns as usize);
+        for _ in 0..num_columns {
+            let (&len, rest) = bytes.split_first()?;
+            bytes = rest;
+            let column = (0..len)
+                .map(|_| {
+                    let mut repr = F::Repr::default();
+                    let size = repr.as_ref().len();
+                    if bytes.len() < size {
+                        return None;
+                    }
+                    let (value, rest) = bytes.split_at(size);
+                    bytes = rest;
+                    repr.as_mut().copy_from_slice(value);
+                    Option::from(F::from_repr(repr))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            instances.push(column);
+        }
+
+        Some(FuzzInput {
+            instances,
+            proof: bytes.to_vec(),
+        })
+    }
+
+    /// Encodes this fuzz input, for example to add it to a corpus.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 255 instance columns or values in a column.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![u8::try_from(self.instances.len()).expect("too many columns")];
+        for column in self.instances.iter() {
+            bytes.push(u8::try_from(column.len()).expect("too many instance values"));
+            for value in column.iter() {
+                bytes.extend_from_slice(value.to_repr().as_ref());
+            }
+        }
+        bytes.extend_from_slice(&self.proof);
+        bytes
+    }
+}
+
+/// Decodes `bytes` as a [`FuzzInput`] and verifies its proof, read with a Blake2b
+/// transcript, against `vk`.
+///
+/// Returns `true` if the proof is accepted. Malformed inputs are rejected, so this can be
+/// called with arbitrary bytes: any panic is a bug in the verifier.
+pub fn verify_arbitrary_bytes<'params, Scheme, V, Strategy>(
+    vk: &VerifyingKey<Scheme::Curve>,
+    params: &'params Scheme::ParamsVerifier,
+    bytes: &[u8],
+) -> bool
+where
+    Scheme: CommitmentScheme,
+    V: Verifier<'params, Scheme>,
+    Strategy: VerificationStrategy<'params, Scheme, V, Output = Strategy>,
+    Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    let input = match FuzzInput::<Scheme::Scalar>::from_bytes(bytes) {
+        Some(input) => input,
+        None => return false,
+    };
+    let instances: Vec<&[Scheme::Scalar]> = input.instances.iter().map(Vec::as_slice).collect();
+
+    let mut transcript =
+        Blake2bRead::<_, Scheme::Curve, Challenge255<_>>::init(input.proof.as_slice());
+    verify_proof::<Scheme, V, _, _, _>(
+        params,
+        vk,
+        Strategy::new(params),
+        &[&instances[..]],
+        &mut transcript,
+    )
+    .map(|strategy| strategy.finalize())
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FuzzInput;
+    use halo2curves::pasta::Fp;
+
+    #[test]
+    fn roundtrip() {
+        let input = FuzzInput {
+            instances: vec![vec![Fp::from(1), -Fp::one()], vec![]],
+            proof: vec![1, 2, 3],
+        };
+        let bytes = input.to_bytes();
+        assert_eq!(FuzzInput::from_bytes(&bytes), Some(input));
+
+        // Truncated instances.
+        assert_eq!(FuzzInput::<Fp>::from_bytes(&bytes[..20]), None);
+        assert_eq!(FuzzInput::<Fp>::from_bytes(&[]), None);
+        // Non-canonical instance value.
+        let mut bytes = vec![1, 1];
+        bytes.extend_from_slice(&[0xff; 32]);
+        assert_eq!(FuzzInput::<Fp>::from_bytes(&bytes), None);
+    }
+}
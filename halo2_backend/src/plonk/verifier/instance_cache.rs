@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use blake2b_simd::Params as Blake2bParams;
+use group::ff::PrimeField;
+use halo2curves::CurveAffine;
+
+/// A cache of the commitments to instance columns, for verifiers checking many proofs
+/// with identical instances, such as proofs about the fields of the same block header.
+///
+/// The commitments are keyed by a hash of the values of the columns, so that each column
+/// is only committed to once. They also depend on the parameters of the commitment
+/// scheme, so a cache must only be used with a single set of parameters.
+///
+/// Only the verifiers of schemes that commit to instance columns, such as IPA, use the
+/// cache: the other ones hash the instance values into the transcript instead.
+#[derive(Debug)]
+pub struct InstanceCache<C: CurveAffine> {
+    commitments: RwLock<HashMap<[u8; 32], C>>,
+}
+
+impl<C: CurveAffine> Default for InstanceCache<C> {
+    fn default() -> Self {
+        Self {
+            commitments: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl<C: CurveAffine> InstanceCache<C> {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of instance columns whose commitment is cached.
+    pub fn len(&self) -> usize {
+        self.commitments.read().unwrap().len()
+    }
+
+    /// Returns `true` if no commitment is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes the cached commitments.
+    pub fn clear(&self) {
+        self.commitments.write().unwrap().clear()
+    }
+
+    /// Returns the commitment to the instance column with `values`, computed by `commit`
+    /// if it isn't cached yet.
+    pub(crate) fn commitment(&self, values: &[C::Scalar], commit: impl FnOnce() -> C) -> C {
+        let key = Self::key(values);
+        if let Some(commitment) = self.commitments.read().unwrap().get(&key) {
+            return *commitment;
+        }
+        let commitment = commit();
+        self.commitments.write().unwrap().insert(key, commitment);
+        commitment
+    }
+
+    fn key(values: &[C::Scalar]) -> [u8; 32] {
+        let mut hasher = Blake2bParams::new()
+            .hash_length(32)
+            .personal(b"Halo2-Instances-")
+            .to_state();
+        hasher.update(&(values.len() as u64).to_le_bytes());
+        for value in values {
+            hasher.update(value.to_repr().as_ref());
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(hasher.finalize().as_bytes());
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2curves::pasta::{EqAffine, Fp};
+
+    #[test]
+    fn commits_once_per_column() {
+        let cache = InstanceCache::<EqAffine>::new();
+        let mut commits = 0;
+        let mut commitment = |values: &[Fp]| {
+            cache.commitment(values, || {
+                commits += 1;
+                EqAffine::default()
+            })
+        };
+        commitment(&[Fp::from(1), Fp::from(2)]);
+        commitment(&[Fp::from(1), Fp::from(2)]);
+        commitment(&[Fp::from(2), Fp::from(1)]);
+        commitment(&[Fp::from(1)]);
+        assert_eq!(commits, 3);
+        assert_eq!(cache.len(), 3);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+use std::ops::Range;
+
+use group::ff::BatchInvert;
+use halo2_middleware::circuit::Subdomain;
+use halo2_middleware::ff::WithSmallOrderMulGroup;
+
+use crate::plonk::Error;
+use crate::poly::EvaluationDomain;
+
+/// The values at `x` which the verifier computes from inverses: the Lagrange polynomials
+/// of the unusable rows, of the first row and of the instance values, the indicators of
+/// the subdomains of the gates and the inverse of the vanishing polynomial.
+///
+/// They are computed with a single inversion per proof, instead of one per family of
+/// Lagrange polynomials, per gate on a subdomain and for the vanishing polynomial.
+#[derive(Debug)]
+pub(super) struct Inverses<F> {
+    /// The inverse of the vanishing polynomial of the domain.
+    pub(super) zh_inv: F,
+    /// The Lagrange polynomials of the rotations of `l_rotations`.
+    pub(super) l_evals: Vec<F>,
+    /// The Lagrange polynomials of the rotations of `instance_rotations`.
+    pub(super) instance_l_evals: Vec<F>,
+    /// The indicator of the subdomain of each gate, if it has one.
+    pub(super) subdomain_indicators: Vec<Option<F>>,
+}
+
+impl<F: WithSmallOrderMulGroup<3>> Inverses<F> {
+    /// Computes the values at `x`, where `xn` is $x^n$.
+    pub(super) fn new(
+        domain: &EvaluationDomain<F>,
+        x: F,
+        xn: F,
+        l_rotations: Range<i32>,
+        instance_rotations: Range<i32>,
+        subdomains: impl Iterator<Item = Option<Subdomain>>,
+    ) -> Result<Self, Error> {
+        // The vanishing polynomial is zero only if x is in the domain, in which case the
+        // proof can't be checked. Otherwise, none of the denominators is zero.
+        if xn == F::ONE {
+            return Err(Error::ConstraintSystemFailure);
+        }
+        let subdomains: Vec<_> = subdomains.collect();
+
+        let mut inverses = vec![xn - F::ONE];
+        inverses.extend(domain.l_i_range_denominators(x, l_rotations.clone()));
+        inverses.extend(domain.l_i_range_denominators(x, instance_rotations.clone()));
+        inverses.extend(
+            subdomains
+                .iter()
+                .flatten()
+                .map(|subdomain| domain.subdomain_indicator_denominator(x, *subdomain)),
+        );
+        inverses.iter_mut().batch_invert();
+
+        let zh_inv = inverses[0];
+        let (l_inverses, inverses) = inverses[1..].split_at(l_rotations.len());
+        let (instance_inverses, subdomain_inverses) = inverses.split_at(instance_rotations.len());
+        let mut subdomain_inverses = subdomain_inverses.iter();
+        Ok(Inverses {
+            zh_inv,
+            l_evals: domain.l_i_range_with_inverses(xn, l_rotations, l_inverses),
+            instance_l_evals: domain.l_i_range_with_inverses(
+                xn,
+                instance_rotations,
+                instance_inverses,
+            ),
+            subdomain_indicators: subdomains
+                .iter()
+                .map(|subdomain| {
+                    subdomain.map(|_| (xn - F::ONE) * subdomain_inverses.next().unwrap())
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_middleware::ff::Field;
+    use halo2curves::pasta::Fp;
+    use rand_core::OsRng;
+
+    #[test]
+    fn matches_domain() {
+        let domain = EvaluationDomain::<Fp>::new(3, 4);
+        let x = Fp::random(OsRng);
+        let xn = x.pow([16]);
+        let subdomains = [None, Some(Subdomain::new(4, 1)), Some(Subdomain::new(2, 0))];
+        let inverses = Inverses::new(&domain, x, xn, -4..1, -2..7, subdomains.into_iter()).unwrap();
+
+        assert_eq!(inverses.zh_inv * (xn - Fp::ONE), Fp::ONE);
+        assert_eq!(inverses.l_evals, domain.l_i_range(x, xn, -4..1));
+        assert_eq!(inverses.instance_l_evals, domain.l_i_range(x, xn, -2..7));
+        assert_eq!(
+            inverses.subdomain_indicators,
+            subdomains
+                .iter()
+                .map(|subdomain| subdomain.map(|s| domain.subdomain_indicator(x, xn, s)))
+                .collect::<Vec<_>>()
+        );
+
+        // The points of the domain are rejected.
+        let row = domain.get_omega().pow([3]);
+        assert!(Inverses::new(&domain, row, Fp::ONE, -4..1, 0..0, subdomains.into_iter()).is_err());
+    }
+}
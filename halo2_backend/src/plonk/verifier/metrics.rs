@@ -0,0 +1,77 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The time spent in each stage of the verification of proofs, measured by
+/// [`VerifierBuilder::with_metrics`].
+///
+/// Every verification adds the times of the proofs it verifies, so the same metrics can be passed
+/// to the verification of many proofs to measure their total.
+///
+/// [`VerifierBuilder::with_metrics`]: super::VerifierBuilder::with_metrics
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VerifierMetrics {
+    /// The number of verifications measured.
+    pub verifications: usize,
+    /// The time spent reading the commitments and evaluations from the proof, and
+    /// squeezing the challenges.
+    pub transcript: Duration,
+    /// The time spent evaluating the instance columns, when they are not committed, and
+    /// the constraints of the circuit at the challenge point.
+    pub evaluation: Duration,
+    /// The time spent committing to the instance columns, when they are committed, and
+    /// running the multiopen argument, which reads the opening proof and accumulates the
+    /// multiscalar multiplications checked by the strategy.
+    pub msm: Duration,
+    /// The time spent by the strategy to check the accumulated multiscalar
+    /// multiplications, which is the pairing check for KZG. Strategies that defer this
+    /// check until [`VerificationStrategy::finalize`] only report the time to accumulate
+    /// the proof.
+    ///
+    /// [`VerificationStrategy::finalize`]: crate::poly::VerificationStrategy::finalize
+    pub pairing: Duration,
+}
+
+impl VerifierMetrics {
+    /// Returns the total time measured.
+    pub fn total(&self) -> Duration {
+        self.transcript + self.evaluation + self.msm + self.pairing
+    }
+}
+
+impl fmt::Display for VerifierMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "verifications: {}", self.verifications)?;
+        writeln!(f, "transcript: {:?}", self.transcript)?;
+        writeln!(f, "evaluation: {:?}", self.evaluation)?;
+        writeln!(f, "msm: {:?}", self.msm)?;
+        writeln!(f, "pairing: {:?}", self.pairing)?;
+        write!(f, "total: {:?}", self.total())
+    }
+}
+
+/// Adds the time elapsed between laps to a stage of [`VerifierMetrics`]. Nothing is
+/// measured when no metrics are requested.
+#[derive(Debug)]
+pub(super) struct Stopwatch<'a> {
+    metrics: Option<(&'a mut VerifierMetrics, Instant)>,
+}
+
+impl<'a> Stopwatch<'a> {
+    pub(super) fn start(metrics: Option<&'a mut VerifierMetrics>) -> Self {
+        Stopwatch {
+            metrics: metrics.map(|metrics| {
+                metrics.verifications += 1;
+                (metrics, Instant::now())
+            }),
+        }
+    }
+
+    /// Adds the time elapsed since the previous lap to `stage`.
+    pub(super) fn lap(&mut self, stage: fn(&mut VerifierMetrics) -> &mut Duration) {
+        if let Some((metrics, last)) = &mut self.metrics {
+            let now = Instant::now();
+            *stage(metrics) += now - *last;
+            *last = now;
+        }
+    }
+}
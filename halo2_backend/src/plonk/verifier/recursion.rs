@@ -0,0 +1,359 @@
+//! The witness of a verifier of KZG proofs in a circuit, computed natively.
+//!
+//! A circuit that verifies a proof, for recursion or aggregation, needs the values the
+//! verifier reads from the proof and the challenges it squeezes, and defers the pairing
+//! check by accumulating the two points it is made of. [`recursion_witness`] computes all
+//! these values from a verifying key, a proof and its instances.
+//!
+//! [`RecursionWitness::to_bytes`] serializes a witness in the following layout, where
+//! lengths are `u32` in little-endian order, scalars are in their canonical
+//! representation, and points are compressed:
+//!
+//! 1. the instance commitments: the number of proofs, then for each proof the number of
+//!    commitments followed by the commitments;
+//! 2. the advice commitments, in the same layout;
+//! 3. the number of circuit challenges followed by the challenges, by challenge index;
+//! 4. the challenges `theta`, `beta`, `gamma`, `y` and `x`;
+//! 5. the instance evaluations, in the same layout as the commitments;
+//! 6. the advice evaluations, in the same layout;
+//! 7. the number of fixed evaluations followed by the evaluations;
+//! 8. the number of openings followed by the commitment, the point and the evaluation of
+//!    each opening;
+//! 9. the left and right terms of the final multiscalar multiplication: for each, the
+//!    number of terms followed by the scalar and the base of each term;
+//! 10. the left and right accumulator points.
+//!
+//! The in-circuit verifier also needs the verifying key of the proofs, which is fixed
+//! when the circuit is built. [`VerifyingKeyConstants`] extracts the constants of a
+//! verifying key, to be assigned as constants of the circuit, with the
+//! [`ConstantsLayout`] that locates each of them. The constants are only derived from the
+//! verifying key, and [`VerifyingKeyConstants::check`] rejects constants built for
+//! another one, so that the circuit can't drift from the proofs it verifies.
+
+use std::fmt::Debug;
+use std::ops::Range;
+
+use group::{prime::PrimeCurveAffine, Curve, GroupEncoding};
+use halo2_middleware::ff::{FromUniformBytes, PrimeField, WithSmallOrderMulGroup};
+use halo2curves::{
+    pairing::{Engine, MillerLoopResult, MultiMillerLoop},
+    CurveAffine, CurveExt,
+};
+
+use super::{VerifierBuilder, VerifierTrace};
+use crate::helpers::SerdeCurveAffine;
+use crate::plonk::{Error, VerifyingKey};
+use crate::poly::{
+    commitment::{Verifier, MSM},
+    kzg::{
+        commitment::{KZGCommitmentScheme, ParamsKZG},
+        msm::DualMSM,
+        strategy::GuardKZG,
+    },
+    VerificationStrategy,
+};
+use crate::transcript::{EncodedChallenge, TranscriptRead};
+use halo2_common::plonk::circuit::Column;
+use halo2_middleware::circuit::Fixed;
+
+/// The values an in-circuit verifier of a KZG proof needs, see the
+/// [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct RecursionWitness<E: Engine>
+where
+    E::G1Affine: CurveAffine<ScalarExt = <E as Engine>::Fr, CurveExt = <E as Engine>::G1>,
+{
+    /// The values read from the proof and the challenges of the circuit.
+    pub trace: VerifierTrace<E::G1Affine>,
+    /// The terms of the multiscalar multiplication paired with `[s]_2`, as scalars and
+    /// bases.
+    pub left_terms: Vec<(E::Fr, E::G1Affine)>,
+    /// The terms of the multiscalar multiplication paired with `[1]_2`, as scalars and
+    /// bases.
+    pub right_terms: Vec<(E::Fr, E::G1Affine)>,
+    /// The result of the left multiscalar multiplication.
+    pub left: E::G1Affine,
+    /// The result of the right multiscalar multiplication.
+    pub right: E::G1Affine,
+}
+
+/// A strategy that returns the accumulated multiscalar multiplications without
+/// randomizing or checking them.
+#[derive(Debug)]
+struct WitnessStrategy<'params, E: Engine>
+where
+    E::G1Affine: CurveAffine<ScalarExt = <E as Engine>::Fr, CurveExt = <E as Engine>::G1>,
+    E::G1: CurveExt<AffineExt = E::G1Affine>,
+{
+    msm: DualMSM<'params, E>,
+}
+
+impl<
+        'params,
+        E: MultiMillerLoop + Debug,
+        V: Verifier<
+            'params,
+            KZGCommitmentScheme<E>,
+            MSMAccumulator = DualMSM<'params, E>,
+            Guard = GuardKZG<'params, E>,
+        >,
+    > VerificationStrategy<'params, KZGCommitmentScheme<E>, V> for WitnessStrategy<'params, E>
+where
+    E::G1Affine: SerdeCurveAffine<ScalarExt = <E as Engine>::Fr, CurveExt = <E as Engine>::G1>,
+    E::G1: CurveExt<AffineExt = E::G1Affine>,
+    E::G2Affine: SerdeCurveAffine,
+{
+    type Output = DualMSM<'params, E>;
+
+    fn new(params: &'params ParamsKZG<E>) -> Self {
+        WitnessStrategy {
+            msm: DualMSM::new(params),
+        }
+    }
+
+    fn process(
+        self,
+        f: impl FnOnce(V::MSMAccumulator) -> Result<V::Guard, Error>,
+    ) -> Result<Self::Output, Error> {
+        Ok(f(self.msm)?.msm_accumulator)
+    }
+
+    fn finalize(self) -> bool {
+        self.msm.check()
+    }
+}
+
+/// Reads the proof in `transcript` for the circuit of `vk` with the given `instances`,
+/// and returns the witness of a verifier of this proof.
+///
+/// The pairing check is not performed: the proof is valid if
+/// [`RecursionWitness::check`] holds for the witness.
+pub fn recursion_witness<
+    'params,
+    E: MultiMillerLoop + Debug,
+    V: Verifier<
+        'params,
+        KZGCommitmentScheme<E>,
+        MSMAccumulator = DualMSM<'params, E>,
+        Guard = GuardKZG<'params, E>,
+    >,
+    Enc: EncodedChallenge<E::G1Affine>,
+    T: TranscriptRead<E::G1Affine, Enc>,
+>(
+    params: &'params ParamsKZG<E>,
+    vk: &VerifyingKey<E::G1Affine>,
+    instances: &[&[&[E::Fr]]],
+    transcript: &mut T,
+) -> Result<RecursionWitness<E>, Error>
+where
+    E::G1Affine: SerdeCurveAffine<ScalarExt = <E as Engine>::Fr, CurveExt = <E as Engine>::G1>,
+    E::G1: CurveExt<AffineExt = E::G1Affine>,
+    E::G2Affine: SerdeCurveAffine,
+    E::Fr: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    let strategy = WitnessStrategy {
+        msm: DualMSM::new(params),
+    };
+    let (msm, trace) = VerifierBuilder::<KZGCommitmentScheme<E>, V>::new(params, vk, instances)
+        .verify_with_trace(strategy, transcript)?;
+
+    Ok(RecursionWitness {
+        trace,
+        left_terms: terms(&msm.left),
+        right_terms: terms(&msm.right),
+        left: msm.left.eval().to_affine(),
+        right: msm.right.eval().to_affine(),
+    })
+}
+
+/// Returns the terms of `msm` as scalars and affine bases.
+fn terms<C: CurveAffine, M: MSM<C>>(msm: &M) -> Vec<(C::Scalar, C)> {
+    let projective = msm.bases();
+    let mut bases = vec![C::identity(); projective.len()];
+    C::Curve::batch_normalize(&projective, &mut bases);
+    msm.scalars().into_iter().zip(bases).collect()
+}
+
+impl<E: MultiMillerLoop + Debug> RecursionWitness<E>
+where
+    E::G1Affine: CurveAffine<ScalarExt = <E as Engine>::Fr, CurveExt = <E as Engine>::G1>,
+    E::G1: CurveExt<AffineExt = E::G1Affine>,
+{
+    /// Performs the pairing check deferred by the witness: `e(left, [s]_2) = e(right, [1]_2)`.
+    pub fn check(&self, params: &ParamsKZG<E>) -> bool {
+        let s_g2_prepared = E::G2Prepared::from(params.s_g2());
+        let n_g2_prepared = E::G2Prepared::from(-params.g2());
+        bool::from(
+            E::multi_miller_loop(&[(&self.left, &s_g2_prepared), (&self.right, &n_g2_prepared)])
+                .final_exponentiation()
+                .is_identity(),
+        )
+    }
+
+    /// Serializes the witness in the layout of the [module documentation](self).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        let write_len =
+            |bytes: &mut Vec<u8>, len: usize| bytes.extend_from_slice(&(len as u32).to_le_bytes());
+        let write_scalar = |bytes: &mut Vec<u8>, scalar: &E::Fr| {
+            bytes.extend_from_slice(scalar.to_repr().as_ref())
+        };
+        let write_point = |bytes: &mut Vec<u8>, point: &E::G1Affine| {
+            bytes.extend_from_slice(point.to_bytes().as_ref())
+        };
+
+        let trace = &self.trace;
+        for commitments in [&trace.instance_commitments, &trace.advice_commitments] {
+            write_len(&mut bytes, commitments.len());
+            for commitments in commitments {
+                write_len(&mut bytes, commitments.len());
+                commitments.iter().for_each(|c| write_point(&mut bytes, c));
+            }
+        }
+        write_len(&mut bytes, trace.challenges.len());
+        trace
+            .challenges
+            .iter()
+            .for_each(|c| write_scalar(&mut bytes, c));
+        for challenge in [&trace.theta, &trace.beta, &trace.gamma, &trace.y, &trace.x] {
+            write_scalar(&mut bytes, challenge);
+        }
+        for evals in [&trace.instance_evals, &trace.advice_evals] {
+            write_len(&mut bytes, evals.len());
+            for evals in evals {
+                write_len(&mut bytes, evals.len());
+                evals.iter().for_each(|e| write_scalar(&mut bytes, e));
+            }
+        }
+        write_len(&mut bytes, trace.fixed_evals.len());
+        trace
+            .fixed_evals
+            .iter()
+            .for_each(|e| write_scalar(&mut bytes, e));
+        write_len(&mut bytes, trace.openings.len());
+        for opening in &trace.openings {
+            write_point(&mut bytes, &opening.commitment);
+            write_scalar(&mut bytes, &opening.point);
+            write_scalar(&mut bytes, &opening.eval);
+        }
+        for terms in [&self.left_terms, &self.right_terms] {
+            write_len(&mut bytes, terms.len());
+            for (scalar, base) in terms {
+                write_scalar(&mut bytes, scalar);
+                write_point(&mut bytes, base);
+            }
+        }
+        write_point(&mut bytes, &self.left);
+        write_point(&mut bytes, &self.right);
+        bytes
+    }
+}
+
+/// The positions of the constants of a verifying key in [`VerifyingKeyConstants`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstantsLayout {
+    /// The index of the protocol version in the scalars.
+    pub protocol_version: usize,
+    /// The index of the representative of the verifying key absorbed by the transcript,
+    /// in the scalars.
+    pub transcript_repr: usize,
+    /// The index of the generator `omega` of the evaluation domain in the scalars.
+    pub omega: usize,
+    /// The index of `omega^-1` in the scalars.
+    pub omega_inv: usize,
+    /// The index of the number of rows `n` in the scalars.
+    pub n: usize,
+    /// The index of the number of blinding rows in the scalars.
+    pub blinding_factors: usize,
+    /// The range of the commitments of the fixed columns in the points, by column index.
+    pub fixed_commitments: Range<usize>,
+    /// The range of the commitments of the permutation argument in the points, in the
+    /// order of its columns.
+    pub permutation_commitments: Range<usize>,
+}
+
+/// The constants of a verifying key, for an in-circuit verifier of its proofs, see the
+/// [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyingKeyConstants<C: CurveAffine> {
+    /// The scalars of the verifying key.
+    pub scalars: Vec<C::Scalar>,
+    /// The points of the verifying key.
+    pub points: Vec<C>,
+    /// The positions of the constants in `scalars` and `points`.
+    pub layout: ConstantsLayout,
+}
+
+impl<C: CurveAffine> VerifyingKeyConstants<C> {
+    /// Extracts the constants of `vk`.
+    pub fn new(vk: &VerifyingKey<C>) -> Self {
+        let scalars = vec![
+            C::Scalar::from(vk.protocol_version as u64),
+            vk.transcript_repr,
+            vk.domain.get_omega(),
+            vk.domain.get_omega_inv(),
+            C::Scalar::from(1u64 << vk.domain.k()),
+            C::Scalar::from(vk.cs.blinding_factors() as u64),
+        ];
+        let mut points = vk.fixed_commitments.clone();
+        let fixed_commitments = 0..points.len();
+        points.extend_from_slice(vk.permutation.commitments());
+        let permutation_commitments = fixed_commitments.end..points.len();
+
+        VerifyingKeyConstants {
+            scalars,
+            points,
+            layout: ConstantsLayout {
+                protocol_version: 0,
+                transcript_repr: 1,
+                omega: 2,
+                omega_inv: 3,
+                n: 4,
+                blinding_factors: 5,
+                fixed_commitments,
+                permutation_commitments,
+            },
+        }
+    }
+
+    /// Checks that the constants are those of `vk`.
+    pub fn check(&self, vk: &VerifyingKey<C>) -> Result<(), Error> {
+        if *self != Self::new(vk) {
+            return Err(Error::Other(
+                "the constants are not those of the verifying key".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the representative of the verifying key absorbed by the transcript.
+    pub fn transcript_repr(&self) -> C::Scalar {
+        self.scalars[self.layout.transcript_repr]
+    }
+
+    /// Returns the commitment of the fixed column `column`.
+    pub fn fixed_commitment(&self, column: Column<Fixed>) -> C {
+        self.points[self.layout.fixed_commitments.clone()][column.index()]
+    }
+
+    /// Returns the commitments of the permutation argument, in the order of its columns.
+    pub fn permutation_commitments(&self) -> &[C] {
+        &self.points[self.layout.permutation_commitments.clone()]
+    }
+
+    /// Serializes the constants: the number of scalars followed by the scalars, then the
+    /// number of points followed by the points, in the encoding of the
+    /// [module documentation](self).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&(self.scalars.len() as u32).to_le_bytes());
+        for scalar in &self.scalars {
+            bytes.extend_from_slice(scalar.to_repr().as_ref());
+        }
+        bytes.extend_from_slice(&(self.points.len() as u32).to_le_bytes());
+        for point in &self.points {
+            bytes.extend_from_slice(point.to_bytes().as_ref());
+        }
+        bytes
+    }
+}
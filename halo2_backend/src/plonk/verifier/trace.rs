@@ -0,0 +1,69 @@
+use group::Curve;
+use halo2curves::CurveAffine;
+
+use crate::poly::{commitment::MSM, CommitmentReference, VerifierQuery};
+
+/// The values read from a proof and the challenges squeezed by the verifier, returned by
+/// [`VerifierBuilder::verify_with_trace`].
+///
+/// The challenges of the multiopen argument are not included: they depend on the
+/// commitment scheme, and are squeezed by its [`Verifier`].
+///
+/// [`VerifierBuilder::verify_with_trace`]: super::VerifierBuilder::verify_with_trace
+/// [`Verifier`]: crate::poly::commitment::Verifier
+#[derive(Clone, Debug)]
+pub struct VerifierTrace<C: CurveAffine> {
+    /// The commitments to the instance columns of each proof, or nothing for each proof
+    /// if the commitment scheme doesn't commit to instance columns.
+    pub instance_commitments: Vec<Vec<C>>,
+    /// The commitments to the advice columns of each proof.
+    pub advice_commitments: Vec<Vec<C>>,
+    /// The challenges of the circuit, by challenge index.
+    pub challenges: Vec<C::Scalar>,
+    /// The challenge keeping the lookup columns linearly independent.
+    pub theta: C::Scalar,
+    /// The first challenge of the permutation, lookup and shuffle arguments.
+    pub beta: C::Scalar,
+    /// The second challenge of the permutation, lookup and shuffle arguments.
+    pub gamma: C::Scalar,
+    /// The challenge keeping the constraints linearly independent.
+    pub y: C::Scalar,
+    /// The point at which the polynomials of the circuit are evaluated.
+    pub x: C::Scalar,
+    /// The evaluations of the instance queries of each proof, read from the proof or
+    /// computed from the instances if the commitment scheme doesn't commit to them.
+    pub instance_evals: Vec<Vec<C::Scalar>>,
+    /// The evaluations of the advice queries of each proof.
+    pub advice_evals: Vec<Vec<C::Scalar>>,
+    /// The evaluations of the fixed queries.
+    pub fixed_evals: Vec<C::Scalar>,
+    /// Every opening checked by the multiopen argument, in the order they are passed to
+    /// it.
+    pub openings: Vec<Opening<C>>,
+}
+
+/// A claimed evaluation of a committed polynomial at a point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Opening<C: CurveAffine> {
+    /// The commitment to the polynomial. Commitments that the verifier combines from
+    /// other ones, such as the commitment to `h(X)`, are given once combined.
+    pub commitment: C,
+    /// The point the polynomial is opened at.
+    pub point: C::Scalar,
+    /// The claimed evaluation of the polynomial at `point`.
+    pub eval: C::Scalar,
+}
+
+impl<C: CurveAffine> Opening<C> {
+    pub(super) fn from_query<M: MSM<C>>(query: &VerifierQuery<'_, C, M>) -> Self {
+        let commitment = match query.commitment {
+            CommitmentReference::Commitment(commitment) => *commitment,
+            CommitmentReference::MSM(msm) => msm.eval().to_affine(),
+        };
+        Opening {
+            commitment,
+            point: query.point,
+            eval: query.eval,
+        }
+    }
+}
@@ -16,19 +16,26 @@ use std::ops::{Add, Deref, DerefMut, Index, IndexMut, Mul, RangeFrom, RangeFull,
 /// Generic commitment scheme structures
 pub mod commitment;
 mod domain;
+/// Proofs that two commitments are commitments to the same polynomial
+pub mod equivalence;
 mod query;
 mod strategy;
 
 /// Inner product argument commitment scheme
+#[cfg(feature = "ipa")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ipa")))]
 pub mod ipa;
 
 /// KZG commitment scheme
+#[cfg(feature = "kzg")]
+#[cfg_attr(docsrs, doc(cfg(feature = "kzg")))]
 pub mod kzg;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "ipa", feature = "kzg"))]
 mod multiopen_test;
 
 pub use domain::*;
+pub(crate) use query::CommitmentReference;
 pub use query::{ProverQuery, VerifierQuery};
 pub use strategy::{Guard, VerificationStrategy};
 
@@ -5,7 +5,8 @@ use super::{
 };
 use crate::poly::Error;
 use crate::transcript::{EncodedChallenge, TranscriptRead, TranscriptWrite};
-use halo2_middleware::ff::Field;
+use crate::zal::{traits::MsmAccel, H2cEngine};
+use halo2_middleware::ff::{Field, PrimeField};
 use halo2curves::CurveAffine;
 use rand_core::RngCore;
 use std::{
@@ -40,7 +41,9 @@ pub trait CommitmentScheme {
 }
 
 /// Parameters for circuit synthesis and prover parameters.
-pub trait Params<'params, C: CurveAffine>: Sized + Clone + Debug {
+///
+/// The parameters are shared between the threads that commit to polynomials in parallel.
+pub trait Params<'params, C: CurveAffine>: Sized + Clone + Debug + Sync {
     /// Multiscalar multiplication engine
     type MSM: MSM<C> + 'params;
 
@@ -64,8 +67,97 @@ pub trait Params<'params, C: CurveAffine>: Sized + Clone + Debug {
         &self,
         poly: &Polynomial<C::ScalarExt, LagrangeCoeff>,
         r: Blind<C::ScalarExt>,
+    ) -> C::CurveExt {
+        self.commit_lagrange_with_engine(&H2cEngine, poly, r)
+    }
+
+    /// Commits like [`Self::commit_lagrange`], with `engine` computing the multiscalar
+    /// multiplication.
+    fn commit_lagrange_with_engine(
+        &self,
+        engine: &dyn MsmAccel<C>,
+        poly: &Polynomial<C::ScalarExt, LagrangeCoeff>,
+        r: Blind<C::ScalarExt>,
+    ) -> C::CurveExt {
+        self.commit_lagrange_slice_with_engine(engine, poly, r)
+    }
+
+    /// Commits to a column whose first rows hold `values`, and whose other rows are zero,
+    /// blinded by the blinding factor `r` in schemes that support blinding.
+    ///
+    /// This is the commitment `sum_i values[i] * G_i + r * W` to the polynomial that
+    /// evaluates to `values[i]` at `omega^i`, where `G_i` are the Lagrange basis
+    /// commitments of the parameters and `W` is their blinding base. KZG commitments are
+    /// not blinded, and ignore `r`. It is the commitment the prover makes to an
+    /// unblinded advice column assigned with `values`, so data committed with it can be
+    /// used as an [`ExternalCommitment`] by the proofs of circuits with these parameters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than `n` values.
+    ///
+    /// [`ExternalCommitment`]: crate::plonk::prover::ExternalCommitment
+    fn commit_lagrange_slice(
+        &self,
+        values: &[C::ScalarExt],
+        r: Blind<C::ScalarExt>,
+    ) -> C::CurveExt {
+        self.commit_lagrange_slice_with_engine(&H2cEngine, values, r)
+    }
+
+    /// Commits like [`Self::commit_lagrange_slice`], with `engine` computing the
+    /// multiscalar multiplication.
+    fn commit_lagrange_slice_with_engine(
+        &self,
+        engine: &dyn MsmAccel<C>,
+        values: &[C::ScalarExt],
+        r: Blind<C::ScalarExt>,
     ) -> C::CurveExt;
 
+    /// Commits like [`Self::commit_lagrange_slice_with_engine`] to a column which is zero
+    /// except at `rows`, where it holds `values`.
+    ///
+    /// Schemes compute the multiscalar multiplication with the Lagrange bases of `rows`
+    /// only, which is much cheaper for columns with few non-zero rows, such as selectors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are not as many rows as values, or if a row is not smaller than `n`.
+    fn commit_lagrange_sparse_with_engine(
+        &self,
+        engine: &dyn MsmAccel<C>,
+        rows: &[usize],
+        values: &[C::ScalarExt],
+        r: Blind<C::ScalarExt>,
+    ) -> C::CurveExt {
+        assert_eq!(rows.len(), values.len());
+        let mut column = vec![C::ScalarExt::ZERO; rows.iter().max().map_or(0, |row| row + 1)];
+        for (row, value) in rows.iter().zip(values) {
+            column[*row] = *value;
+        }
+        self.commit_lagrange_slice_with_engine(engine, &column, r)
+    }
+
+    /// Commits to several columns like [`Self::commit_lagrange_slice`], each blinded by
+    /// the blinding factor at the same index of `blinds`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are not as many blinds as columns, or if a column has more than
+    /// `n` values.
+    fn commit_lagrange_slices(
+        &self,
+        columns: &[&[C::ScalarExt]],
+        blinds: &[Blind<C::ScalarExt>],
+    ) -> Vec<C::CurveExt> {
+        assert_eq!(columns.len(), blinds.len());
+        columns
+            .iter()
+            .zip(blinds)
+            .map(|(values, blind)| self.commit_lagrange_slice(values, *blind))
+            .collect()
+    }
+
     /// Writes params to a buffer.
     fn write<W: io::Write>(&self, writer: &mut W) -> io::Result<()>;
 
@@ -84,8 +176,22 @@ pub trait ParamsProver<'params, C: CurveAffine>: Params<'params, C> {
     /// This computes a commitment to a polynomial described by the provided
     /// slice of coefficients. The commitment may be blinded by the blinding
     /// factor `r`.
-    fn commit(&self, poly: &Polynomial<C::ScalarExt, Coeff>, r: Blind<C::ScalarExt>)
-        -> C::CurveExt;
+    fn commit(
+        &self,
+        poly: &Polynomial<C::ScalarExt, Coeff>,
+        r: Blind<C::ScalarExt>,
+    ) -> C::CurveExt {
+        self.commit_with_engine(&H2cEngine, poly, r)
+    }
+
+    /// Commits like [`Self::commit`], with `engine` computing the multiscalar
+    /// multiplication.
+    fn commit_with_engine(
+        &self,
+        engine: &dyn MsmAccel<C>,
+        poly: &Polynomial<C::ScalarExt, Coeff>,
+        r: Blind<C::ScalarExt>,
+    ) -> C::CurveExt;
 
     /// Getter for g generators
     fn get_g(&self) -> &[C];
@@ -144,6 +250,28 @@ pub trait Prover<'params, Scheme: CommitmentScheme> {
         transcript: &mut T,
         queries: I,
     ) -> io::Result<()>
+    where
+        I: IntoIterator<Item = ProverQuery<'com, Scheme::Curve>> + Clone,
+        R: RngCore,
+    {
+        self.create_proof_with_engine(&H2cEngine, rng, transcript, queries)
+    }
+
+    /// Create a multi-opening proof like [`Self::create_proof`], with `engine` computing
+    /// the multiscalar multiplications.
+    fn create_proof_with_engine<
+        'com,
+        E: EncodedChallenge<Scheme::Curve>,
+        T: TranscriptWrite<Scheme::Curve, E>,
+        R,
+        I,
+    >(
+        &self,
+        engine: &dyn MsmAccel<Scheme::Curve>,
+        rng: R,
+        transcript: &mut T,
+        queries: I,
+    ) -> io::Result<()>
     where
         I: IntoIterator<Item = ProverQuery<'com, Scheme::Curve>> + Clone,
         R: RngCore;
@@ -187,6 +315,27 @@ pub trait Verifier<'params, Scheme: CommitmentScheme> {
             > + Clone;
 }
 
+/// Encodes `bytes` as the values of a column, to commit to them with
+/// [`Params::commit_lagrange_slice`].
+///
+/// The bytes are split into chunks of `F::CAPACITY / 8` bytes, the last of which may be
+/// shorter, and each chunk is the little-endian representation of a value. The values are
+/// thus smaller than the modulus of the field, and the encoding is injective for inputs
+/// of the same length.
+pub fn scalars_from_bytes<F: PrimeField>(bytes: &[u8]) -> Vec<F> {
+    let chunk_size = (F::CAPACITY / 8) as usize;
+    let base = F::from(256);
+    bytes
+        .chunks(chunk_size)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .rev()
+                .fold(F::ZERO, |value, byte| value * base + F::from(*byte as u64))
+        })
+        .collect()
+}
+
 /// Wrapper type around a blinding factor.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct Blind<F>(pub F);
@@ -2,14 +2,16 @@
 //! domain that is of a suitable size for the application.
 
 use crate::arithmetic::parallelize;
+use crate::zal::{traits::FftAccel, H2cEngine};
 
 use super::{Coeff, ExtendedLagrangeCoeff, LagrangeCoeff, Polynomial};
 use group::ff::{BatchInvert, Field};
+use halo2_middleware::circuit::Subdomain;
 use halo2_middleware::ff::WithSmallOrderMulGroup;
 use halo2_middleware::poly::Rotation;
-use halo2curves::fft::best_fft;
 
 use std::marker::PhantomData;
+use std::ops::Range;
 
 /// This structure contains precomputed constants and other details needed for
 /// performing operations on an evaluation domain of size $2^k$ and an extended
@@ -42,13 +44,7 @@ impl<F: WithSmallOrderMulGroup<3>> EvaluationDomain<F> {
         // n = 2^k
         let n = 1u64 << k;
 
-        // We need to work within an extended domain, not params.k but params.k + i
-        // for some integer i such that 2^(params.k + i) is sufficiently large to
-        // describe the quotient polynomial.
-        let mut extended_k = k;
-        while (1 << extended_k) < (n * quotient_poly_degree) {
-            extended_k += 1;
-        }
+        let extended_k = Self::extended_k(j, k);
 
         // ensure extended_k <= S
         assert!(extended_k <= F::S);
@@ -143,6 +139,23 @@ impl<F: WithSmallOrderMulGroup<3>> EvaluationDomain<F> {
         }
     }
 
+    /// Returns the size (as a power of two) of the extended domain used to describe the
+    /// quotient polynomial of a circuit of degree `j` over `2^k` rows.
+    pub(crate) fn extended_k(j: u32, k: u32) -> u32 {
+        // quotient_poly_degree * params.n - 1 is the degree of the quotient polynomial
+        let quotient_poly_degree = (j - 1) as u64;
+        let n = 1u64 << k;
+
+        // We need to work within an extended domain, not params.k but params.k + i
+        // for some integer i such that 2^(params.k + i) is sufficiently large to
+        // describe the quotient polynomial.
+        let mut extended_k = k;
+        while (1 << extended_k) < (n * quotient_poly_degree) {
+            extended_k += 1;
+        }
+        extended_k
+    }
+
     /// Obtains a polynomial in Lagrange form when given a vector of Lagrange
     /// coefficients of size `n`; panics if the provided vector is the wrong
     /// length.
@@ -213,11 +226,26 @@ impl<F: WithSmallOrderMulGroup<3>> EvaluationDomain<F> {
     ///
     /// This function will panic if the provided vector is not the correct
     /// length.
-    pub fn lagrange_to_coeff(&self, mut a: Polynomial<F, LagrangeCoeff>) -> Polynomial<F, Coeff> {
+    pub fn lagrange_to_coeff(&self, a: Polynomial<F, LagrangeCoeff>) -> Polynomial<F, Coeff> {
+        self.lagrange_to_coeff_with_engine(&H2cEngine, a)
+    }
+
+    /// Converts like [`Self::lagrange_to_coeff`], with `engine` computing the FFT.
+    pub fn lagrange_to_coeff_with_engine(
+        &self,
+        engine: &dyn FftAccel<F>,
+        mut a: Polynomial<F, LagrangeCoeff>,
+    ) -> Polynomial<F, Coeff> {
         assert_eq!(a.values.len(), 1 << self.k);
 
         // Perform inverse FFT to obtain the polynomial in coefficient form
-        Self::ifft(&mut a.values, self.omega_inv, self.k, self.ifft_divisor);
+        Self::ifft(
+            engine,
+            &mut a.values,
+            self.omega_inv,
+            self.k,
+            self.ifft_divisor,
+        );
 
         Polynomial {
             values: a.values,
@@ -229,13 +257,22 @@ impl<F: WithSmallOrderMulGroup<3>> EvaluationDomain<F> {
     /// evaluation domain, rotating by `rotation` if desired.
     pub fn coeff_to_extended(
         &self,
+        a: Polynomial<F, Coeff>,
+    ) -> Polynomial<F, ExtendedLagrangeCoeff> {
+        self.coeff_to_extended_with_engine(&H2cEngine, a)
+    }
+
+    /// Converts like [`Self::coeff_to_extended`], with `engine` computing the FFT.
+    pub fn coeff_to_extended_with_engine(
+        &self,
+        engine: &dyn FftAccel<F>,
         mut a: Polynomial<F, Coeff>,
     ) -> Polynomial<F, ExtendedLagrangeCoeff> {
         assert_eq!(a.values.len(), 1 << self.k);
 
         self.distribute_powers_zeta(&mut a.values, true);
         a.values.resize(self.extended_len(), F::ZERO);
-        best_fft(&mut a.values, self.extended_omega, self.extended_k);
+        engine.fft(&mut a.values, self.extended_omega, self.extended_k);
 
         Polynomial {
             values: a.values,
@@ -268,11 +305,21 @@ impl<F: WithSmallOrderMulGroup<3>> EvaluationDomain<F> {
     /// This function will panic if the provided vector is not the correct
     /// length.
     // TODO/FIXME: caller should be responsible for truncating
-    pub fn extended_to_coeff(&self, mut a: Polynomial<F, ExtendedLagrangeCoeff>) -> Vec<F> {
+    pub fn extended_to_coeff(&self, a: Polynomial<F, ExtendedLagrangeCoeff>) -> Vec<F> {
+        self.extended_to_coeff_with_engine(&H2cEngine, a)
+    }
+
+    /// Converts like [`Self::extended_to_coeff`], with `engine` computing the FFT.
+    pub fn extended_to_coeff_with_engine(
+        &self,
+        engine: &dyn FftAccel<F>,
+        mut a: Polynomial<F, ExtendedLagrangeCoeff>,
+    ) -> Vec<F> {
         assert_eq!(a.values.len(), self.extended_len());
 
         // Inverse FFT
         Self::ifft(
+            engine,
             &mut a.values,
             self.extended_omega_inv,
             self.extended_k,
@@ -340,8 +387,8 @@ impl<F: WithSmallOrderMulGroup<3>> EvaluationDomain<F> {
         });
     }
 
-    fn ifft(a: &mut [F], omega_inv: F, log_n: u32, divisor: F) {
-        best_fft(a, omega_inv, log_n);
+    fn ifft(engine: &dyn FftAccel<F>, a: &mut [F], omega_inv: F, log_n: u32, divisor: F) {
+        engine.fft(a, omega_inv, log_n);
         parallelize(a, |a, _| {
             for a in a {
                 // Finish iFFT
@@ -449,6 +496,88 @@ impl<F: WithSmallOrderMulGroup<3>> EvaluationDomain<F> {
         results
     }
 
+    /// Returns the denominators $x - \omega^i$ of the evaluations of [`Self::l_i_range`]
+    /// for each rotation $i$ of `rotations`, so that they can be inverted in a batch with
+    /// other values, see [`Self::l_i_range_with_inverses`].
+    pub fn l_i_range_denominators(
+        &self,
+        x: F,
+        rotations: Range<i32>,
+    ) -> impl Iterator<Item = F> + '_ {
+        let mut omega_i = self.rotate_omega(F::ONE, Rotation(rotations.start));
+        rotations.map(move |_| {
+            let denominator = x - omega_i;
+            omega_i *= self.omega;
+            denominator
+        })
+    }
+
+    /// Computes the evaluations of [`Self::l_i_range`] for `rotations`, given the
+    /// `inverses` of their [`Self::l_i_range_denominators`].
+    pub fn l_i_range_with_inverses(&self, xn: F, rotations: Range<i32>, inverses: &[F]) -> Vec<F> {
+        assert_eq!(inverses.len(), rotations.len());
+        let common = (xn - F::ONE) * self.barycentric_weight;
+        let mut omega_i = self.rotate_omega(F::ONE, Rotation(rotations.start));
+        inverses
+            .iter()
+            .map(|inverse| {
+                let result = *inverse * common * omega_i;
+                omega_i *= self.omega;
+                result
+            })
+            .collect()
+    }
+
+    /// Computes the evaluation at $x$, where $x^n$ is `xn`, of the polynomial of degree
+    /// less than $n$ which is one on the rows of `subdomain` and zero on the other rows.
+    ///
+    /// With $m$ the period and $r$ the offset of the subdomain, the rows of the subdomain
+    /// are the roots of $u(X) - 1$, with $u(X) = (\omega^{-r} X)^{n / m}$, and the
+    /// polynomial is the vanishing polynomial of the domain divided by theirs:
+    ///     $$\frac{X^n - 1}{m (u(X) - 1)} = \frac{1}{m} \sum_{i=0}^{m-1} u(X)^i.$$
+    ///
+    /// # Panics
+    ///
+    /// Panics if the period of the subdomain is larger than $n$.
+    pub fn subdomain_indicator(&self, x: F, xn: F, subdomain: Subdomain) -> F {
+        let denominator = self.subdomain_indicator_denominator(x, subdomain);
+        if denominator == F::ZERO {
+            return F::ONE;
+        }
+        (xn - F::ONE) * denominator.invert().unwrap()
+    }
+
+    /// Returns the denominator $m (u(x) - 1)$ of [`Self::subdomain_indicator`], so that it
+    /// can be inverted in a batch with other values. It is zero only if $x$ is a row of
+    /// the subdomain.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the period of the subdomain is larger than $n$.
+    pub fn subdomain_indicator_denominator(&self, x: F, subdomain: Subdomain) -> F {
+        let period = subdomain.period() as u64;
+        assert!(period <= self.n, "the subdomain is larger than the domain");
+        let u = (x * self.omega_inv.pow_vartime([subdomain.offset() as u64]))
+            .pow_vartime([self.n / period]);
+        F::from(period) * (u - F::ONE)
+    }
+
+    /// Returns the polynomial which is one on the rows of `subdomain` before
+    /// `usable_rows` and zero on the other rows, over the extended domain.
+    pub fn subdomain_selector_with_engine(
+        &self,
+        engine: &dyn FftAccel<F>,
+        subdomain: Subdomain,
+        usable_rows: usize,
+    ) -> Polynomial<F, ExtendedLagrangeCoeff> {
+        let mut selector = self.empty_lagrange();
+        for row in (subdomain.offset()..usable_rows).step_by(subdomain.period()) {
+            selector[row] = F::ONE;
+        }
+        let selector = self.lagrange_to_coeff_with_engine(engine, selector);
+        self.coeff_to_extended_with_engine(engine, selector)
+    }
+
     /// Gets the quotient polynomial's degree (as a multiple of n)
     pub fn get_quotient_poly_degree(&self) -> usize {
         self.quotient_poly_degree as usize
@@ -544,4 +673,46 @@ fn test_l_i() {
         assert_eq!(eval_polynomial(&l[i][..], x), evaluations[7 + i]);
         assert_eq!(eval_polynomial(&l[(8 - i) % 8][..], x), evaluations[7 - i]);
     }
+
+    let mut inverses: Vec<_> = domain.l_i_range_denominators(x, -7..8).collect();
+    inverses.iter_mut().batch_invert();
+    assert_eq!(
+        domain.l_i_range_with_inverses(xn, -7..8, &inverses),
+        evaluations
+    );
+}
+
+#[test]
+fn test_subdomain_indicator() {
+    use rand_core::OsRng;
+
+    use halo2curves::pasta::pallas::Scalar;
+    let domain = EvaluationDomain::<Scalar>::new(1, 4);
+
+    let x = Scalar::random(OsRng);
+    let xn = x.pow([16]);
+    let l = domain.l_i_range(x, xn, 0..16);
+    for (period, offset) in [(1, 0), (2, 1), (4, 3), (16, 5)] {
+        let subdomain = Subdomain::new(period, offset);
+        let expected = (offset..16)
+            .step_by(period)
+            .map(|row| l[row])
+            .sum::<Scalar>();
+        assert_eq!(domain.subdomain_indicator(x, xn, subdomain), expected);
+
+        // The rows of the subdomain are rows of the domain.
+        let row = domain.omega.pow([offset as u64 + period as u64]);
+        assert_eq!(
+            domain.subdomain_indicator(row, row.pow([16]), subdomain),
+            Scalar::ONE
+        );
+        assert_eq!(
+            domain.subdomain_indicator(domain.omega * row, Scalar::ONE, subdomain),
+            if period == 1 {
+                Scalar::ONE
+            } else {
+                Scalar::ZERO
+            }
+        );
+    }
 }
@@ -0,0 +1,279 @@
+//! Proofs that two commitments are commitments to the same polynomial.
+//!
+//! A polynomial may be committed to in coefficient basis, with
+//! [`ParamsProver::commit`], or in Lagrange basis, from its evaluations over the domain
+//! of the parameters, with [`Params::commit_lagrange`]. Both commitments are the same
+//! point for the same parameters and blind, but data committed to outside of a circuit,
+//! for example by a data availability layer, may be committed to with other parameters
+//! than the ones of the circuit, such as a larger domain or another setup of the scheme.
+//!
+//! An equivalence proof shows that a commitment with the parameters `params_a` and a
+//! commitment with the parameters `params_b` are commitments to the same polynomial, of
+//! degree smaller than the sizes of both domains. The commitments are added to the
+//! transcript, the polynomial is evaluated at a challenge `z`, and both commitments are
+//! opened at `z` to this value. By the Schwartz-Zippel lemma, two different polynomials
+//! only agree at `z` with negligible probability.
+//!
+//! This links a commitment used by the proofs of a circuit, such as an
+//! [`ExternalCommitment`], to the commitment of the same data made elsewhere.
+//!
+//! [`ExternalCommitment`]: crate::plonk::prover::ExternalCommitment
+
+use group::Curve;
+use halo2_middleware::ff::Field;
+use rand_core::RngCore;
+
+use super::commitment::{Blind, CommitmentScheme, Params, ParamsProver, Prover, Verifier};
+use super::query::{ProverQuery, VerifierQuery};
+use super::strategy::VerificationStrategy;
+use super::{Coeff, Polynomial};
+use crate::arithmetic::{eval_polynomial, CurveAffine};
+use crate::plonk::Error;
+use crate::transcript::{EncodedChallenge, TranscriptRead, TranscriptWrite};
+
+/// Pads `poly` with zero coefficients up to the size of the domain of `params`.
+fn pad<'params, C: CurveAffine, P: Params<'params, C>>(
+    params: &P,
+    poly: &Polynomial<C::Scalar, Coeff>,
+) -> Result<Polynomial<C::Scalar, Coeff>, Error> {
+    let n = params.n() as usize;
+    if poly.len() > n {
+        return Err(Error::Other(format!(
+            "the polynomial has {} coefficients, but the parameters only support {}",
+            poly.len(),
+            n
+        )));
+    }
+    let mut padded = Polynomial::new_empty(n, C::Scalar::ZERO);
+    padded.values[..poly.len()].copy_from_slice(&poly[..]);
+    Ok(padded)
+}
+
+/// Proves that the commitments to `poly` with `params_a` and the blind `blind_a`, and with
+/// `params_b` and the blind `blind_b`, are commitments to the same polynomial.
+///
+/// The commitments are not written to the transcript, the verifier is given them. A
+/// polynomial committed to in Lagrange basis is converted to coefficient basis with
+/// [`EvaluationDomain::lagrange_to_coeff`].
+///
+/// Returns an error if `poly` has more coefficients than the domain of one of the
+/// parameters.
+///
+/// [`EvaluationDomain::lagrange_to_coeff`]: super::EvaluationDomain::lagrange_to_coeff
+pub fn create_equivalence_proof<
+    'params,
+    Scheme: CommitmentScheme,
+    P: Prover<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    R: RngCore,
+    T: TranscriptWrite<Scheme::Curve, E>,
+>(
+    params_a: &'params Scheme::ParamsProver,
+    params_b: &'params Scheme::ParamsProver,
+    poly: &Polynomial<Scheme::Scalar, Coeff>,
+    blind_a: Blind<Scheme::Scalar>,
+    blind_b: Blind<Scheme::Scalar>,
+    mut rng: R,
+    transcript: &mut T,
+) -> Result<(), Error> {
+    let poly_a = pad(params_a, poly)?;
+    let poly_b = pad(params_b, poly)?;
+
+    transcript.common_point(params_a.commit(&poly_a, blind_a).to_affine())?;
+    transcript.common_point(params_b.commit(&poly_b, blind_b).to_affine())?;
+
+    let z = *transcript.squeeze_challenge_scalar::<()>();
+    transcript.write_scalar(eval_polynomial(poly, z))?;
+
+    for (params, poly, blind) in [(params_a, &poly_a, blind_a), (params_b, &poly_b, blind_b)] {
+        let query = ProverQuery {
+            point: z,
+            poly,
+            blind,
+        };
+        P::new(params).create_proof(&mut rng, transcript, [query])?;
+    }
+    Ok(())
+}
+
+/// Verifies a proof created by [`create_equivalence_proof`] that `commitment_a`, with
+/// `params_a`, and `commitment_b`, with `params_b`, are commitments to the same
+/// polynomial.
+///
+/// Each opening is processed by its own strategy, and their outputs are returned in the
+/// same order.
+pub fn verify_equivalence_proof<
+    'params,
+    Scheme: CommitmentScheme,
+    V: Verifier<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    T: TranscriptRead<Scheme::Curve, E>,
+    Strategy: VerificationStrategy<'params, Scheme, V>,
+>(
+    params_a: &'params Scheme::ParamsVerifier,
+    params_b: &'params Scheme::ParamsVerifier,
+    commitment_a: Scheme::Curve,
+    commitment_b: Scheme::Curve,
+    strategy_a: Strategy,
+    strategy_b: Strategy,
+    transcript: &mut T,
+) -> Result<(Strategy::Output, Strategy::Output), Error> {
+    transcript.common_point(commitment_a)?;
+    transcript.common_point(commitment_b)?;
+
+    let z = *transcript.squeeze_challenge_scalar::<()>();
+    let eval = transcript.read_scalar()?;
+
+    let mut open = |params, commitment, strategy: Strategy| {
+        strategy.process(|msm| {
+            let query = VerifierQuery::new_commitment(&commitment, z, eval);
+            V::new(params)
+                .verify_proof(transcript, [query], msm)
+                .map_err(|_| Error::Opening)
+        })
+    };
+    let output_a = open(params_a, commitment_a, strategy_a)?;
+    let output_b = open(params_b, commitment_b, strategy_b)?;
+    Ok((output_a, output_b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poly::EvaluationDomain;
+    use crate::transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+    };
+    use rand_core::OsRng;
+
+    #[cfg(feature = "ipa")]
+    #[test]
+    fn equivalence_ipa() {
+        use crate::poly::ipa::commitment::{IPACommitmentScheme, ParamsIPA};
+        use crate::poly::ipa::multiopen::{ProverIPA, VerifierIPA};
+        use crate::poly::ipa::strategy::SingleStrategy;
+        use halo2curves::pasta::{EqAffine, Fp};
+
+        // The circuit commits to the evaluations of the polynomial over its domain, and
+        // the other commitment is made to its coefficients with a larger domain.
+        let params_a = ParamsIPA::<EqAffine>::new(3);
+        let params_b = ParamsIPA::<EqAffine>::new(5);
+        let domain = EvaluationDomain::<Fp>::new(1, 3);
+        let evals: Vec<_> = (0..8).map(|_| Fp::random(OsRng)).collect();
+        let lagrange = domain.lagrange_from_vec(evals);
+        let poly = domain.lagrange_to_coeff(lagrange.clone());
+        let (blind_a, blind_b) = (Blind::new(&mut OsRng), Blind::new(&mut OsRng));
+
+        let commitment_a = params_a.commit_lagrange(&lagrange, blind_a).to_affine();
+        assert_eq!(commitment_a, params_a.commit(&poly, blind_a).to_affine());
+        let commitment_b = params_b
+            .commit(&pad(&params_b, &poly).unwrap(), blind_b)
+            .to_affine();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        create_equivalence_proof::<IPACommitmentScheme<_>, ProverIPA<_>, _, _, _>(
+            &params_a,
+            &params_b,
+            &poly,
+            blind_a,
+            blind_b,
+            OsRng,
+            &mut transcript,
+        )
+        .unwrap();
+        let proof = transcript.finalize();
+
+        let verify = |commitment_a, commitment_b| {
+            let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+            verify_equivalence_proof::<IPACommitmentScheme<_>, VerifierIPA<_>, _, _, _>(
+                params_a.verifier_params(),
+                params_b.verifier_params(),
+                commitment_a,
+                commitment_b,
+                SingleStrategy::new(&params_a),
+                SingleStrategy::new(&params_b),
+                &mut transcript,
+            )
+        };
+        assert!(verify(commitment_a, commitment_b).is_ok());
+
+        // A commitment to another polynomial is rejected.
+        let mut other = poly.clone();
+        other[0] += Fp::ONE;
+        let other = params_b
+            .commit(&pad(&params_b, &other).unwrap(), blind_b)
+            .to_affine();
+        assert!(verify(commitment_a, other).is_err());
+
+        // The polynomial must fit into both domains.
+        let large = pad(&params_b, &poly).unwrap();
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        assert!(
+            create_equivalence_proof::<IPACommitmentScheme<_>, ProverIPA<_>, _, _, _>(
+                &params_a,
+                &params_b,
+                &large,
+                blind_a,
+                blind_b,
+                OsRng,
+                &mut transcript,
+            )
+            .is_err()
+        );
+    }
+
+    #[cfg(feature = "kzg")]
+    #[test]
+    fn equivalence_kzg() {
+        use crate::poly::kzg::commitment::{KZGCommitmentScheme, ParamsKZG};
+        use crate::poly::kzg::multiopen::{ProverSHPLONK, VerifierSHPLONK};
+        use crate::poly::kzg::strategy::AccumulatorStrategy;
+        use halo2curves::bn256::{Bn256, Fr};
+
+        // The commitments are made with different setups.
+        let params_a = ParamsKZG::<Bn256>::setup(4, OsRng);
+        let params_b = ParamsKZG::<Bn256>::setup(4, OsRng);
+        let domain = EvaluationDomain::<Fr>::new(1, 4);
+        let evals: Vec<_> = (0..16).map(|_| Fr::random(OsRng)).collect();
+        let lagrange = domain.lagrange_from_vec(evals);
+        let poly = domain.lagrange_to_coeff(lagrange.clone());
+        let blind = Blind::default();
+
+        let commitment_a = params_a.commit_lagrange(&lagrange, blind).to_affine();
+        let commitment_b = params_b.commit(&poly, blind).to_affine();
+        assert_ne!(commitment_a, commitment_b);
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        create_equivalence_proof::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _, _, _>(
+            &params_a,
+            &params_b,
+            &poly,
+            blind,
+            blind,
+            OsRng,
+            &mut transcript,
+        )
+        .unwrap();
+        let proof = transcript.finalize();
+
+        let verify = |commitment_b| {
+            let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+            let (strategy_a, strategy_b) =
+                verify_equivalence_proof::<KZGCommitmentScheme<_>, VerifierSHPLONK<_>, _, _, _>(
+                    params_a.verifier_params(),
+                    params_b.verifier_params(),
+                    commitment_a,
+                    commitment_b,
+                    AccumulatorStrategy::new(params_a.verifier_params()),
+                    AccumulatorStrategy::new(params_b.verifier_params()),
+                    &mut transcript,
+                )
+                .unwrap();
+            strategy_a.finalize() && strategy_b.finalize()
+        };
+        assert!(verify(commitment_b));
+
+        // The commitments are only equivalent with their own parameters.
+        assert!(!verify(params_a.commit(&poly, blind).to_affine()));
+    }
+}
@@ -4,19 +4,21 @@
 //! [halo]: https://eprint.iacr.org/2019/1021
 
 use crate::arithmetic::{g_to_lagrange, parallelize, CurveAffine, CurveExt};
-use crate::helpers::CurveRead;
+use crate::helpers::{
+    read_params_start, write_curve_id, write_params_start, CurveRead, SerdeCurveAffine, SerdeFormat,
+};
 use crate::poly::commitment::{Blind, CommitmentScheme, Params, ParamsProver, ParamsVerifier};
 use crate::poly::ipa::msm::MSMIPA;
-use crate::poly::{Coeff, LagrangeCoeff, Polynomial};
+use crate::poly::{Coeff, Polynomial};
+use crate::zal::traits::MsmAccel;
 
 use group::{Curve, Group};
-use halo2curves::msm::best_multiexp;
 use std::marker::PhantomData;
 
 mod prover;
 mod verifier;
 
-pub use prover::create_proof;
+pub use prover::{create_proof, create_proof_with_engine};
 pub use verifier::verify_proof;
 
 use std::io;
@@ -83,28 +85,48 @@ impl<'params, C: CurveAffine> Params<'params, C> for ParamsIPA<C> {
         MSMIPA::new(self)
     }
 
-    /// This commits to a polynomial using its evaluations over the $2^k$ size
-    /// evaluation domain. The commitment will be blinded by the blinding factor
-    /// `r`.
-    fn commit_lagrange(
+    fn commit_lagrange_slice_with_engine(
         &self,
-        poly: &Polynomial<C::Scalar, LagrangeCoeff>,
+        engine: &dyn MsmAccel<C>,
+        values: &[C::Scalar],
         r: Blind<C::Scalar>,
     ) -> C::Curve {
-        let mut tmp_scalars = Vec::with_capacity(poly.len() + 1);
-        let mut tmp_bases = Vec::with_capacity(poly.len() + 1);
+        assert!(self.g_lagrange.len() >= values.len());
+        let mut tmp_scalars = Vec::with_capacity(values.len() + 1);
+        let mut tmp_bases = Vec::with_capacity(values.len() + 1);
 
-        tmp_scalars.extend(poly.iter());
+        tmp_scalars.extend(values.iter());
+        tmp_scalars.push(r.0);
+
+        tmp_bases.extend(self.g_lagrange[..values.len()].iter());
+        tmp_bases.push(self.w);
+
+        engine.msm(&tmp_scalars, &tmp_bases)
+    }
+
+    fn commit_lagrange_sparse_with_engine(
+        &self,
+        engine: &dyn MsmAccel<C>,
+        rows: &[usize],
+        values: &[C::Scalar],
+        r: Blind<C::Scalar>,
+    ) -> C::Curve {
+        assert_eq!(rows.len(), values.len());
+        let mut tmp_scalars = Vec::with_capacity(values.len() + 1);
+        let mut tmp_bases = Vec::with_capacity(values.len() + 1);
+
+        tmp_scalars.extend(values.iter());
         tmp_scalars.push(r.0);
 
-        tmp_bases.extend(self.g_lagrange.iter());
+        tmp_bases.extend(rows.iter().map(|row| self.g_lagrange[*row]));
         tmp_bases.push(self.w);
 
-        best_multiexp::<C>(&tmp_scalars, &tmp_bases)
+        engine.msm(&tmp_scalars, &tmp_bases)
     }
 
-    /// Writes params to a buffer.
+    /// Writes params to a buffer, after the identifier of the curve.
     fn write<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_curve_id::<C, _>(writer)?;
         writer.write_all(&self.k.to_le_bytes())?;
         for g_element in &self.g {
             writer.write_all(g_element.to_bytes().as_ref())?;
@@ -118,19 +140,22 @@ impl<'params, C: CurveAffine> Params<'params, C> for ParamsIPA<C> {
         Ok(())
     }
 
-    /// Reads params from a buffer.
+    /// Reads params from a buffer, which may start with the identifier of the curve or,
+    /// for params written before it was recorded, directly with `k`. The checksums of
+    /// params written by [`ParamsIPA::write_custom_with_checksums`] are checked.
     fn read<R: io::Read>(reader: &mut R) -> io::Result<Self> {
-        let mut k = [0u8; 4];
-        reader.read_exact(&mut k[..])?;
-        let k = u32::from_le_bytes(k);
+        let (mut reader, k) = read_params_start::<C, _>(reader)?;
+        let reader = &mut reader;
 
         let n: u64 = 1 << k;
 
         let g: Vec<_> = (0..n).map(|_| C::read(reader)).collect::<Result<_, _>>()?;
         let g_lagrange: Vec<_> = (0..n).map(|_| C::read(reader)).collect::<Result<_, _>>()?;
+        reader.end_section()?;
 
         let w = C::read(reader)?;
         let u = C::read(reader)?;
+        reader.end_section()?;
 
         Ok(Self {
             k,
@@ -143,16 +168,22 @@ impl<'params, C: CurveAffine> Params<'params, C> for ParamsIPA<C> {
     }
 }
 
-impl<'params, C: CurveAffine> ParamsProver<'params, C> for ParamsIPA<C> {
-    type ParamsVerifier = ParamsVerifierIPA<C>;
-
-    fn verifier_params(&'params self) -> &'params Self::ParamsVerifier {
-        self
+/// The domain prefix of the random oracle the parameters are drawn from.
+const PARAMS_DOMAIN: &str = "Halo2-Parameters";
+
+impl<C: CurveAffine> ParamsIPA<C> {
+    /// Initializes parameters like [`ParamsProver::new`], drawing the points from a
+    /// random oracle separated by the application `label`.
+    ///
+    /// Applications using different labels get independent parameters, so a proof
+    /// created with the parameters of one application doesn't verify with the
+    /// parameters of another, even when their circuits are identical.
+    pub fn new_with_domain(k: u32, label: &str) -> Self {
+        Self::from_oracle(k, &format!("{PARAMS_DOMAIN}-{label}"))
     }
 
-    /// Initializes parameters for the curve, given a random oracle to draw
-    /// points from.
-    fn new(k: u32) -> Self {
+    /// Draws the parameters from the random oracle with the given domain prefix.
+    fn from_oracle(k: u32, domain: &str) -> Self {
         // This is usually a limitation on the curve, but we also want 32-bit
         // architectures to be supported.
         assert!(k < 32);
@@ -166,7 +197,7 @@ impl<'params, C: CurveAffine> ParamsProver<'params, C> for ParamsIPA<C> {
             g.resize(n as usize, C::Curve::identity());
 
             parallelize(&mut g, move |g, start| {
-                let hasher = C::CurveExt::hash_to_curve("Halo2-Parameters");
+                let hasher = C::CurveExt::hash_to_curve(domain);
 
                 for (i, g) in g.iter_mut().enumerate() {
                     let i = (i + start) as u32;
@@ -193,7 +224,7 @@ impl<'params, C: CurveAffine> ParamsProver<'params, C> for ParamsIPA<C> {
         // using an inverse FFT.
         let g_lagrange = g_to_lagrange(g_projective, k);
 
-        let hasher = C::CurveExt::hash_to_curve("Halo2-Parameters");
+        let hasher = C::CurveExt::hash_to_curve(domain);
         let w = hasher(&[1]).to_affine();
         let u = hasher(&[2]).to_affine();
 
@@ -206,11 +237,100 @@ impl<'params, C: CurveAffine> ParamsProver<'params, C> for ParamsIPA<C> {
             u,
         }
     }
+}
+
+impl<C: SerdeCurveAffine> ParamsIPA<C> {
+    /// Writes params to a buffer in `format`, after the identifier of the curve. With
+    /// [`SerdeFormat::Processed`], this is the same as [`Params::write`].
+    pub fn write_custom<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+    ) -> io::Result<()> {
+        self.write_sections(writer, format, false)
+    }
+
+    /// Writes params to a buffer like [`Self::write_custom`], with a BLAKE2b checksum
+    /// after the points `g` and `g_lagrange` and after `w` and `u`, which
+    /// [`Self::read_custom`] and [`Params::read`] check. Corrupted params are then
+    /// detected even when they are read with [`SerdeFormat::RawBytesUnchecked`].
+    pub fn write_custom_with_checksums<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+    ) -> io::Result<()> {
+        self.write_sections(writer, format, true)
+    }
+
+    fn write_sections<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+        checksums: bool,
+    ) -> io::Result<()> {
+        let writer = &mut write_params_start::<C, _>(writer, self.k, checksums)?;
+        for el in self.g.iter().chain(self.g_lagrange.iter()) {
+            el.write(writer, format)?;
+        }
+        writer.end_section()?;
+        self.w.write(writer, format)?;
+        self.u.write(writer, format)?;
+        writer.end_section()
+    }
+
+    /// Reads params written by [`Self::write_custom`] or
+    /// [`Self::write_custom_with_checksums`] in the same `format`.
+    ///
+    /// With [`SerdeFormat::RawBytesUnchecked`], the points aren't checked to be on the
+    /// curve, which makes loading large params much faster: it must only be used for
+    /// params from trusted storage, or written with checksums.
+    pub fn read_custom<R: io::Read>(reader: &mut R, format: SerdeFormat) -> io::Result<Self> {
+        let (mut reader, k) = read_params_start::<C, _>(reader)?;
+        let reader = &mut reader;
+        let n: u64 = 1 << k;
+
+        let mut g = (0..2 * n)
+            .map(|_| <C as SerdeCurveAffine>::read(reader, format))
+            .collect::<Result<Vec<_>, _>>()?;
+        reader.end_section()?;
+        let g_lagrange = g.split_off(n as usize);
+        let w = <C as SerdeCurveAffine>::read(reader, format)?;
+        let u = <C as SerdeCurveAffine>::read(reader, format)?;
+        reader.end_section()?;
+
+        Ok(Self {
+            k,
+            n,
+            g,
+            g_lagrange,
+            w,
+            u,
+        })
+    }
+}
+
+impl<'params, C: CurveAffine> ParamsProver<'params, C> for ParamsIPA<C> {
+    type ParamsVerifier = ParamsVerifierIPA<C>;
+
+    fn verifier_params(&'params self) -> &'params Self::ParamsVerifier {
+        self
+    }
+
+    /// Initializes parameters for the curve, given a random oracle to draw
+    /// points from.
+    fn new(k: u32) -> Self {
+        Self::from_oracle(k, PARAMS_DOMAIN)
+    }
 
     /// This computes a commitment to a polynomial described by the provided
     /// slice of coefficients. The commitment will be blinded by the blinding
     /// factor `r`.
-    fn commit(&self, poly: &Polynomial<C::Scalar, Coeff>, r: Blind<C::Scalar>) -> C::Curve {
+    fn commit_with_engine(
+        &self,
+        engine: &dyn MsmAccel<C>,
+        poly: &Polynomial<C::Scalar, Coeff>,
+        r: Blind<C::Scalar>,
+    ) -> C::Curve {
         let mut tmp_scalars = Vec::with_capacity(poly.len() + 1);
         let mut tmp_bases = Vec::with_capacity(poly.len() + 1);
 
@@ -220,7 +340,7 @@ impl<'params, C: CurveAffine> ParamsProver<'params, C> for ParamsIPA<C> {
         tmp_bases.extend(self.g.iter());
         tmp_bases.push(self.w);
 
-        best_multiexp::<C>(&tmp_scalars, &tmp_bases)
+        engine.msm(&tmp_scalars, &tmp_bases)
     }
 
     fn get_g(&self) -> &[C] {
@@ -288,6 +408,60 @@ mod test {
         assert_eq!(params.commit(&b, alpha), params.commit_lagrange(&a, alpha));
     }
 
+    #[test]
+    fn test_commit_lagrange_slice() {
+        const K: u32 = 4;
+
+        use rand_core::OsRng;
+
+        use crate::poly::EvaluationDomain;
+        use halo2curves::pasta::{EqAffine, Fp};
+
+        let params = ParamsIPA::<EqAffine>::new(K);
+        let domain = EvaluationDomain::new(1, K);
+
+        // The commitment is the one of a column holding the values in its first rows.
+        let values: Vec<_> = (0..5).map(|_| Fp::random(OsRng)).collect();
+        let mut column = domain.empty_lagrange();
+        column[..values.len()].copy_from_slice(&values);
+        let alpha = Blind(Fp::random(OsRng));
+        assert_eq!(
+            params.commit_lagrange_slice(&values, alpha),
+            params.commit_lagrange(&column, alpha)
+        );
+        assert_ne!(
+            params.commit_lagrange_slice(&values, alpha),
+            params.commit_lagrange_slice(&values, Blind::default())
+        );
+    }
+
+    #[test]
+    fn test_commit_lagrange_sparse() {
+        const K: u32 = 4;
+
+        use rand_core::OsRng;
+
+        use crate::poly::EvaluationDomain;
+        use crate::zal::H2cEngine;
+        use halo2curves::pasta::{EqAffine, Fp};
+
+        let params = ParamsIPA::<EqAffine>::new(K);
+        let domain = EvaluationDomain::new(1, K);
+
+        // The commitment is the one of the column holding the values at the rows.
+        let rows = [3, 0, 15];
+        let values: Vec<_> = (0..3).map(|_| Fp::random(OsRng)).collect();
+        let mut column = domain.empty_lagrange();
+        for (row, value) in rows.iter().zip(&values) {
+            column[*row] = *value;
+        }
+        let alpha = Blind(Fp::random(OsRng));
+        assert_eq!(
+            params.commit_lagrange_sparse_with_engine(&H2cEngine, &rows, &values, alpha),
+            params.commit_lagrange(&column, alpha)
+        );
+    }
+
     #[test]
     fn test_opening_proof() {
         const K: u32 = 6;
@@ -368,4 +542,79 @@ mod test {
             assert!(msm_g.check());
         }
     }
+
+    #[test]
+    fn test_params_with_domain() {
+        const K: u32 = 4;
+
+        use crate::poly::EvaluationDomain;
+        use halo2curves::pasta::{EqAffine, Fp};
+
+        let params = ParamsIPA::<EqAffine>::new_with_domain(K, "application a");
+        assert_eq!(
+            params.g,
+            ParamsIPA::<EqAffine>::new_with_domain(K, "application a").g
+        );
+        assert_ne!(
+            params.g,
+            ParamsIPA::<EqAffine>::new_with_domain(K, "application b").g
+        );
+        assert_ne!(params.g, ParamsIPA::<EqAffine>::new(K).g);
+        assert_ne!(params.w, ParamsIPA::<EqAffine>::new(K).w);
+
+        // The Lagrange basis is derived from the separated generators.
+        let domain = EvaluationDomain::new(1, K);
+        let mut a = domain.empty_lagrange();
+        for (i, a) in a.iter_mut().enumerate() {
+            *a = Fp::from(i as u64);
+        }
+        let b = domain.lagrange_to_coeff(a.clone());
+        assert_eq!(
+            params.commit(&b, Blind::default()),
+            params.commit_lagrange(&a, Blind::default())
+        );
+    }
+
+    #[test]
+    fn test_params_custom_format() {
+        const K: u32 = 4;
+
+        use crate::helpers::SerdeFormat;
+        use halo2curves::bn256::G1Affine;
+
+        let params = ParamsIPA::<G1Affine>::new(K);
+        let mut processed = vec![];
+        params.write(&mut processed).unwrap();
+        let mut custom = vec![];
+        params
+            .write_custom(&mut custom, SerdeFormat::Processed)
+            .unwrap();
+        assert_eq!(custom, processed);
+
+        for format in [
+            SerdeFormat::Processed,
+            SerdeFormat::RawBytes,
+            SerdeFormat::RawBytesUnchecked,
+        ] {
+            let mut bytes = vec![];
+            params.write_custom(&mut bytes, format).unwrap();
+            let read = ParamsIPA::<G1Affine>::read_custom(&mut &bytes[..], format).unwrap();
+            assert_eq!(read.g, params.g);
+            assert_eq!(read.g_lagrange, params.g_lagrange);
+            assert_eq!((read.w, read.u), (params.w, params.u));
+
+            let mut bytes = vec![];
+            params
+                .write_custom_with_checksums(&mut bytes, format)
+                .unwrap();
+            let read = ParamsIPA::<G1Affine>::read_custom(&mut &bytes[..], format).unwrap();
+            assert_eq!(read.g, params.g);
+            assert_eq!((read.w, read.u), (params.w, params.u));
+
+            // A corrupted point is detected by its checksum.
+            let position = bytes.len() - 40;
+            bytes[position] ^= 1;
+            assert!(ParamsIPA::<G1Affine>::read_custom(&mut &bytes[..], format).is_err());
+        }
+    }
 }
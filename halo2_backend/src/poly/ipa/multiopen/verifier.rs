@@ -115,17 +115,21 @@ impl<'params, C: CurveAffine> Verifier<'params, IPACommitmentScheme<C>>
             .iter()
             .zip(q_eval_sets.iter())
             .zip(u.iter())
-            .fold(
+            .try_fold(
                 C::Scalar::ZERO,
                 |msm_eval, ((points, evals), proof_eval)| {
                     let r_poly = lagrange_interpolate(points, evals);
                     let r_eval = eval_polynomial(&r_poly, *x_3);
-                    let eval = points.iter().fold(*proof_eval - r_eval, |eval, point| {
-                        eval * (*x_3 - point).invert().unwrap()
-                    });
-                    msm_eval * (*x_2) + eval
+                    points
+                        .iter()
+                        .try_fold(*proof_eval - r_eval, |eval, point| {
+                            Option::<C::Scalar>::from((*x_3 - point).invert())
+                                .map(|inverse| eval * inverse)
+                        })
+                        .map(|eval| msm_eval * (*x_2) + eval)
                 },
-            );
+            )
+            .ok_or(Error::SamplingError)?;
 
         // Sample a challenge x_4 that we will use to collapse the openings of
         // the various remaining polynomials at x_3 together.
@@ -34,6 +34,21 @@ pub struct Accumulator<C: CurveAffine> {
     pub u_packed: Vec<C::Scalar>,
 }
 
+impl<C: CurveAffine> Accumulator<C> {
+    /// Checks that `g` is the commitment to the polynomial of the challenges, which is
+    /// the part of the verification of the proofs deferred to the accumulator.
+    ///
+    /// The challenges are their own encoding, as for the challenges of the transcripts
+    /// of this crate.
+    pub fn check(&self, params: &ParamsIPA<C>) -> bool {
+        if self.u_packed.len() != params.k as usize {
+            return false;
+        }
+        let s = compute_s(&self.u_packed, C::Scalar::ONE);
+        best_multiexp(&s, &params.g).to_affine() == self.g
+    }
+}
+
 /// Define accumulator type as `MSMIPA`
 impl<'params, C: CurveAffine> Guard<IPACommitmentScheme<C>> for GuardIPA<'params, C> {
     type MSMAccumulator = MSMIPA<'params, C>;
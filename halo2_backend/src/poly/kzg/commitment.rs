@@ -1,12 +1,12 @@
 use crate::arithmetic::{g_to_lagrange, parallelize};
-use crate::helpers::SerdeCurveAffine;
+use crate::helpers::{read_params_start, write_params_start, ChecksumReader, SerdeCurveAffine};
 use crate::poly::commitment::{Blind, CommitmentScheme, Params, ParamsProver, ParamsVerifier};
-use crate::poly::{Coeff, LagrangeCoeff, Polynomial};
+use crate::poly::{Coeff, Polynomial};
+use crate::zal::traits::MsmAccel;
 use crate::SerdeFormat;
 
 use group::{prime::PrimeCurveAffine, Curve, Group};
 use halo2_middleware::ff::{Field, PrimeField};
-use halo2curves::msm::best_multiexp;
 use halo2curves::pairing::Engine;
 use halo2curves::CurveExt;
 use rand_core::{OsRng, RngCore};
@@ -164,38 +164,67 @@ where
         self.s_g2
     }
 
-    /// Writes parameters to buffer
+    /// Writes parameters to buffer, after the identifier of the curve
     pub fn write_custom<W: io::Write>(&self, writer: &mut W, format: SerdeFormat) -> io::Result<()>
     where
         E::G2Affine: SerdeCurveAffine,
     {
-        writer.write_all(&self.k.to_le_bytes())?;
+        self.write_sections(writer, format, false)
+    }
+
+    /// Writes parameters to buffer like [`Self::write_custom`], with a BLAKE2b checksum
+    /// after the points on G1 and after the points on G2, which [`Self::read_custom`]
+    /// checks. Corrupted parameters are then detected even when they are read with
+    /// [`SerdeFormat::RawBytesUnchecked`].
+    pub fn write_custom_with_checksums<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+    ) -> io::Result<()>
+    where
+        E::G2Affine: SerdeCurveAffine,
+    {
+        self.write_sections(writer, format, true)
+    }
+
+    fn write_sections<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+        checksums: bool,
+    ) -> io::Result<()>
+    where
+        E::G2Affine: SerdeCurveAffine,
+    {
+        let writer = &mut write_params_start::<E::G1Affine, _>(writer, self.k, checksums)?;
         for el in self.g.iter() {
             el.write(writer, format)?;
         }
         for el in self.g_lagrange.iter() {
             el.write(writer, format)?;
         }
+        writer.end_section()?;
         self.g2.write(writer, format)?;
         self.s_g2.write(writer, format)?;
-        Ok(())
+        writer.end_section()
     }
 
-    /// Reads params from a buffer.
+    /// Reads params from a buffer, which may start with the identifier of the curve or,
+    /// for params written before it was recorded, directly with `k`. The checksums of
+    /// params written by [`Self::write_custom_with_checksums`] are checked.
     pub fn read_custom<R: io::Read>(reader: &mut R, format: SerdeFormat) -> io::Result<Self>
     where
         E::G2Affine: SerdeCurveAffine,
     {
-        let mut k = [0u8; 4];
-        reader.read_exact(&mut k[..])?;
-        let k = u32::from_le_bytes(k);
+        let (mut reader, k) = read_params_start::<E::G1Affine, _>(reader)?;
+        let reader = &mut reader;
         let n = 1 << k;
 
         let (g, g_lagrange) = match format {
             SerdeFormat::Processed => {
                 use group::GroupEncoding;
                 let load_points_from_file_parallelly =
-                    |reader: &mut R| -> io::Result<Vec<Option<E::G1Affine>>> {
+                    |reader: &mut ChecksumReader<_>| -> io::Result<Vec<Option<E::G1Affine>>> {
                         let mut points_compressed =
                             vec![<<E as Engine>::G1Affine as GroupEncoding>::Repr::default(); n];
                         for points_compressed in points_compressed.iter_mut() {
@@ -253,9 +282,11 @@ where
                 (g, g_lagrange)
             }
         };
+        reader.end_section()?;
 
         let g2 = E::G2Affine::read(reader, format)?;
         let s_g2 = E::G2Affine::read(reader, format)?;
+        reader.end_section()?;
 
         Ok(Self {
             k,
@@ -303,13 +334,28 @@ where
         MSMKZG::new()
     }
 
-    fn commit_lagrange(&self, poly: &Polynomial<E::Fr, LagrangeCoeff>, _: Blind<E::Fr>) -> E::G1 {
-        let mut scalars = Vec::with_capacity(poly.len());
-        scalars.extend(poly.iter());
+    fn commit_lagrange_slice_with_engine(
+        &self,
+        engine: &dyn MsmAccel<E::G1Affine>,
+        values: &[E::Fr],
+        _: Blind<E::Fr>,
+    ) -> E::G1 {
         let bases = &self.g_lagrange;
-        let size = scalars.len();
+        let size = values.len();
         assert!(bases.len() >= size);
-        best_multiexp(&scalars, &bases[0..size])
+        engine.msm(values, &bases[0..size])
+    }
+
+    fn commit_lagrange_sparse_with_engine(
+        &self,
+        engine: &dyn MsmAccel<E::G1Affine>,
+        rows: &[usize],
+        values: &[E::Fr],
+        _: Blind<E::Fr>,
+    ) -> E::G1 {
+        assert_eq!(rows.len(), values.len());
+        let bases: Vec<_> = rows.iter().map(|row| self.g_lagrange[*row]).collect();
+        engine.msm(values, &bases)
     }
 
     /// Writes params to a buffer.
@@ -347,13 +393,18 @@ where
         Self::setup(k, OsRng)
     }
 
-    fn commit(&self, poly: &Polynomial<E::Fr, Coeff>, _: Blind<E::Fr>) -> E::G1 {
+    fn commit_with_engine(
+        &self,
+        engine: &dyn MsmAccel<E::G1Affine>,
+        poly: &Polynomial<E::Fr, Coeff>,
+        _: Blind<E::Fr>,
+    ) -> E::G1 {
         let mut scalars = Vec::with_capacity(poly.len());
         scalars.extend(poly.iter());
         let bases = &self.g;
         let size = scalars.len();
         assert!(bases.len() >= size);
-        best_multiexp(&scalars, &bases[0..size])
+        engine.msm(&scalars, &bases[0..size])
     }
 
     fn get_g(&self) -> &[E::G1Affine] {
@@ -393,6 +444,70 @@ mod test {
         assert_eq!(params.commit(&b, alpha), params.commit_lagrange(&a, alpha));
     }
 
+    #[test]
+    fn test_commit_lagrange_slice() {
+        const K: u32 = 4;
+
+        use crate::poly::commitment::scalars_from_bytes;
+        use crate::poly::EvaluationDomain;
+        use halo2curves::bn256::{Bn256, Fr};
+
+        let params = ParamsKZG::<Bn256>::new(K);
+        let domain = EvaluationDomain::new(1, K);
+
+        // The bytes are encoded in chunks of 31 bytes.
+        let data: Vec<u8> = (0..97).collect();
+        let values = scalars_from_bytes::<Fr>(&data);
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[3], Fr::from(0x605f5e5d));
+
+        // The commitment is the one of a column holding the values in its first rows.
+        let mut column = domain.empty_lagrange();
+        column[..values.len()].copy_from_slice(&values);
+        let commitment = params.commit_lagrange(&column, Blind::default());
+        assert_eq!(
+            params.commit_lagrange_slice(&values, Blind::default()),
+            commitment
+        );
+        assert_eq!(
+            params.commit_lagrange_slices(&[&values, &[]], &[Blind::default(); 2]),
+            vec![
+                commitment,
+                params.commit_lagrange(&domain.empty_lagrange(), Blind::default())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_commit_lagrange_sparse() {
+        const K: u32 = 4;
+
+        use rand_core::OsRng;
+
+        use crate::poly::EvaluationDomain;
+        use crate::zal::H2cEngine;
+        use halo2curves::bn256::{Bn256, Fr};
+
+        let params = ParamsKZG::<Bn256>::new(K);
+        let domain = EvaluationDomain::new(1, K);
+
+        // The commitment is the one of the column holding the values at the rows.
+        let rows = [3, 0, 15];
+        let values: Vec<_> = (0..3).map(|_| Fr::random(OsRng)).collect();
+        let mut column = domain.empty_lagrange();
+        for (row, value) in rows.iter().zip(&values) {
+            column[*row] = *value;
+        }
+        assert_eq!(
+            params.commit_lagrange_sparse_with_engine(&H2cEngine, &rows, &values, Blind::default()),
+            params.commit_lagrange(&column, Blind::default())
+        );
+        assert_eq!(
+            params.commit_lagrange_sparse_with_engine(&H2cEngine, &[], &[], Blind::default()),
+            params.commit_lagrange(&domain.empty_lagrange(), Blind::default())
+        );
+    }
+
     #[test]
     fn test_parameter_serialisation_roundtrip() {
         const K: u32 = 4;
@@ -415,4 +530,31 @@ mod test {
         assert_eq!(params0.g2, params1.g2);
         assert_eq!(params0.s_g2, params1.s_g2);
     }
+
+    #[test]
+    fn test_parameter_checksums() {
+        const K: u32 = 4;
+
+        use crate::helpers::SerdeFormat;
+        use halo2curves::bn256::Bn256;
+
+        let params0 = ParamsKZG::<Bn256>::new(K);
+        let mut data = vec![];
+        params0
+            .write_custom_with_checksums(&mut data, SerdeFormat::RawBytesUnchecked)
+            .unwrap();
+        let params1 =
+            ParamsKZG::<Bn256>::read_custom(&mut &data[..], SerdeFormat::RawBytesUnchecked)
+                .unwrap();
+        assert_eq!(params0.g, params1.g);
+        assert_eq!(params0.g_lagrange, params1.g_lagrange);
+        assert_eq!(params0.s_g2, params1.s_g2);
+
+        // Unchecked points are read as they are, but their checksum doesn't match.
+        let position = data.len() / 2;
+        data[position] ^= 1;
+        let error = ParamsKZG::<Bn256>::read_custom(&mut &data[..], SerdeFormat::RawBytesUnchecked)
+            .unwrap_err();
+        assert!(error.to_string().contains("checksum"), "{error}");
+    }
 }
@@ -7,6 +7,7 @@ use crate::poly::kzg::commitment::{KZGCommitmentScheme, ParamsKZG};
 use crate::poly::query::ProverQuery;
 use crate::poly::{commitment::Blind, Polynomial};
 use crate::transcript::{EncodedChallenge, TranscriptWrite};
+use crate::zal::traits::MsmAccel;
 
 use group::Curve;
 use halo2curves::pairing::Engine;
@@ -36,7 +37,7 @@ where
     }
 
     /// Create a multi-opening proof
-    fn create_proof<
+    fn create_proof_with_engine<
         'com,
         Ch: EncodedChallenge<E::G1Affine>,
         T: TranscriptWrite<E::G1Affine, Ch>,
@@ -44,6 +45,7 @@ where
         I,
     >(
         &self,
+        engine: &dyn MsmAccel<E::G1Affine>,
         _: R,
         transcript: &mut T,
         queries: I,
@@ -79,7 +81,7 @@ where
             };
             let w = self
                 .params
-                .commit(&witness_poly, Blind::default())
+                .commit_with_engine(engine, &witness_poly, Blind::default())
                 .to_affine();
 
             transcript.write_point(w)?;
@@ -72,7 +72,6 @@ where
         for ((commitment_at_a_point, wi), power_of_u) in
             commitment_data.iter().zip(w.into_iter()).zip(powers(*u))
         {
-            assert!(!commitment_at_a_point.queries.is_empty());
             let z = commitment_at_a_point.point;
 
             let (mut commitment_batch, eval_batch) = commitment_at_a_point
@@ -102,7 +101,7 @@ where
                     commitment_acc.add_msm(&commitment);
                     (commitment_acc, eval_acc + eval)
                 })
-                .unwrap();
+                .ok_or(Error::OpeningError)?;
 
             commitment_batch.scale(power_of_u);
             commitment_multi.add_msm(&commitment_batch);
@@ -1,7 +1,7 @@
 mod prover;
 mod verifier;
 
-use crate::multicore::{IntoParallelIterator, ParallelIterator};
+use crate::multicore::prelude::*;
 use crate::{poly::query::Query, transcript::ChallengeScalar};
 use halo2_middleware::ff::Field;
 pub use prover::ProverSHPLONK;
@@ -84,7 +84,8 @@ where
             // normalize coefficients by the coefficient of the first commitment
             if i == 0 {
                 z_0 = evaluate_vanishing_polynomial(&rotation_set.points[..], *u);
-                z_0_diff_inverse = z_diff_i.invert().unwrap();
+                z_0_diff_inverse =
+                    Option::<E::Fr>::from(z_diff_i.invert()).ok_or(Error::SamplingError)?;
                 z_diff_i = E::Fr::ONE;
             } else {
                 z_diff_i.mul_assign(z_0_diff_inverse);
@@ -119,7 +120,7 @@ where
                     msm_acc.add_msm(&msm);
                     (msm_acc, r_eval_acc + r_eval)
                 })
-                .unwrap();
+                .ok_or(Error::OpeningError)?;
 
             inner_msm.scale(power_of_v * z_diff_i);
             outer_msm.add_msm(inner_msm);
@@ -8,6 +8,8 @@ use std::convert::TryInto;
 
 use halo2curves::{Coordinates, CurveAffine};
 
+use crate::helpers::{read_curve_id, write_curve_id};
+
 use std::io::{self, Read, Write};
 use std::marker::PhantomData;
 
@@ -37,8 +39,187 @@ const KECCAK256_PREFIX_POINT: u8 = 1;
 /// Prefix to a prover's message containing a scalar
 const KECCAK256_PREFIX_SCALAR: u8 = 2;
 
+/// How the points and scalars of a proof are encoded by a transcript.
+///
+/// The encoding doesn't change what is hashed in the transcript, only the bytes of the
+/// proof, so it is recorded by [`write_proof_envelope_with_encoding`] for the verifier.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProofEncoding {
+    /// Points and scalars are written with their representation. Points are compressed
+    /// for the curves of `halo2curves`.
+    #[default]
+    Standard,
+    /// Points are written with their representation, and scalars with only the
+    /// `NUM_BITS` bits of their field, everything packed together and padded with zeros
+    /// to a whole byte at the end. This needs the representation of the scalars to be
+    /// little-endian, like those of `halo2curves`.
+    ///
+    /// Only the unused high bits of each scalar are saved: 1 bit per scalar for the
+    /// 255-bit fields of the Pasta curves, 2 for the 254-bit scalar field of BN256, so
+    /// that a proof is less than 1% shorter.
+    Packed,
+}
+
+impl ProofEncoding {
+    fn pending_bits(self) -> Option<PendingBits> {
+        match self {
+            ProofEncoding::Standard => None,
+            ProofEncoding::Packed => Some(PendingBits::default()),
+        }
+    }
+}
+
+/// The bits of a packed proof which don't fill a byte yet.
+#[derive(Clone, Debug, Default)]
+struct PendingBits {
+    value: u16,
+    len: usize,
+}
+
+impl PendingBits {
+    /// Writes the `num_bits` low bits of the little-endian `bytes`, which must not have
+    /// other bits set.
+    fn write<W: Write>(&mut self, writer: &mut W, bytes: &[u8], num_bits: usize) -> io::Result<()> {
+        let kept_bits = |i: usize| num_bits.saturating_sub(8 * i).min(8);
+        if bytes
+            .iter()
+            .enumerate()
+            .any(|(i, byte)| u16::from(*byte) >> kept_bits(i) != 0)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the representation of a scalar has bits set above those of its field, \
+                 which can't be packed",
+            ));
+        }
+
+        let mut packed = Vec::with_capacity(bytes.len() + 1);
+        for (i, byte) in bytes.iter().enumerate() {
+            self.value |= u16::from(*byte) << self.len;
+            self.len += kept_bits(i);
+            if self.len >= 8 {
+                packed.push(self.value as u8);
+                self.value >>= 8;
+                self.len -= 8;
+            }
+        }
+        writer.write_all(&packed)
+    }
+
+    /// Reads `num_bits` bits into the little-endian `bytes`, with their other bits unset.
+    ///
+    /// When the bits read end within a byte, the next byte is read ahead, so that the
+    /// rest of the last byte of the proof is checked to be padding with zeros.
+    fn read<R: Read>(
+        &mut self,
+        reader: &mut R,
+        bytes: &mut [u8],
+        num_bits: usize,
+    ) -> io::Result<()> {
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let bits = num_bits.saturating_sub(8 * i).min(8);
+            if self.len < bits {
+                let mut next = [0u8; 1];
+                reader.read_exact(&mut next)?;
+                self.value |= u16::from(next[0]) << self.len;
+                self.len += 8;
+            }
+            *byte = (self.value & ((1 << bits) - 1)) as u8;
+            self.value >>= bits;
+            self.len -= bits;
+        }
+
+        if self.len > 0 && self.len < 8 {
+            let mut next = [0u8; 1];
+            if reader.read(&mut next)? == 0 {
+                if self.value != 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "the padding bits of the packed proof are not zero",
+                    ));
+                }
+            } else {
+                self.value |= u16::from(next[0]) << self.len;
+                self.len += 8;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the last bits, padded with zeros to a byte.
+    fn flush<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.len > 0 {
+            writer.write_all(&[self.value as u8])?;
+            self.value = 0;
+            self.len = 0;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the representation of a point or a scalar, of which only the `num_bits` low
+/// bits are kept if the proof is packed.
+fn write_repr<W: Write>(
+    writer: &mut W,
+    packed: &mut Option<PendingBits>,
+    bytes: &[u8],
+    num_bits: usize,
+) -> io::Result<()> {
+    match packed {
+        Some(pending) => pending.write(writer, bytes, num_bits),
+        None => writer.write_all(bytes),
+    }
+}
+
+/// Reads the representation of a point or a scalar written by [`write_repr`].
+fn read_repr<R: Read>(
+    reader: &mut R,
+    packed: &mut Option<PendingBits>,
+    bytes: &mut [u8],
+    num_bits: usize,
+) -> io::Result<()> {
+    match packed {
+        Some(pending) => pending.read(reader, bytes, num_bits),
+        None => reader.read_exact(bytes),
+    }
+}
+
+/// The part of the protocol to which messages of a transcript belong.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TranscriptPhase {
+    /// The domain separation label of the application, see [`absorb_domain`].
+    Domain,
+    /// The representative of the verifying key.
+    VerifyingKey,
+    /// The instance columns.
+    Instances,
+    /// The advice columns and the challenges of the given phase of the circuit.
+    Advice(u8),
+    /// The lookup arguments.
+    Lookups,
+    /// The permutation argument.
+    Permutation,
+    /// The shuffle arguments.
+    Shuffles,
+    /// The vanishing argument.
+    Vanishing,
+    /// The evaluations of the polynomials at `x`.
+    Evaluations,
+    /// The multiopen argument.
+    Multiopen,
+}
+
 /// Generic transcript view (from either the prover or verifier's perspective)
 pub trait Transcript<C: CurveAffine, E: EncodedChallenge<C>> {
+    /// Labels the next messages of the transcript with the `phase` of the protocol they
+    /// belong to and their `purpose`, until the next label.
+    ///
+    /// The prover and the verifier label the steps of the protocol, so that a
+    /// transcript recording its messages, like
+    /// [`LoggingTranscript`](crate::dev::transcript_log::LoggingTranscript), can tell
+    /// them apart. Labels are not absorbed, and are ignored by default.
+    fn label(&mut self, _phase: TranscriptPhase, _purpose: &'static str) {}
+
     /// Squeeze an encoded verifier challenge from the transcript.
     fn squeeze_challenge(&mut self) -> E;
 
@@ -103,6 +284,7 @@ pub trait TranscriptWriterBuffer<W: Write, C: CurveAffine, E: EncodedChallenge<C
 pub struct Blake2bRead<R: Read, C: CurveAffine, E: EncodedChallenge<C>> {
     state: Blake2bState,
     reader: R,
+    packed: Option<PendingBits>,
     _marker: PhantomData<(C, E)>,
 }
 
@@ -111,6 +293,7 @@ pub struct Blake2bRead<R: Read, C: CurveAffine, E: EncodedChallenge<C>> {
 pub struct Keccak256Read<R: Read, C: CurveAffine, E: EncodedChallenge<C>> {
     state: Keccak256,
     reader: R,
+    packed: Option<PendingBits>,
     _marker: PhantomData<(C, E)>,
 }
 
@@ -127,6 +310,7 @@ where
                 .personal(b"Halo2-Transcript")
                 .to_state(),
             reader,
+            packed: None,
             _marker: PhantomData,
         }
     }
@@ -144,11 +328,40 @@ where
         Keccak256Read {
             state,
             reader,
+            packed: None,
             _marker: PhantomData,
         }
     }
 }
 
+impl<R: Read, C: CurveAffine> Blake2bRead<R, C, Challenge255<C>>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    /// Initialize a transcript given an input buffer, whose proof is encoded with
+    /// `encoding`.
+    pub fn init_with_encoding(reader: R, encoding: ProofEncoding) -> Self {
+        Blake2bRead {
+            packed: encoding.pending_bits(),
+            ..<Self as TranscriptReadBuffer<_, _, _>>::init(reader)
+        }
+    }
+}
+
+impl<R: Read, C: CurveAffine> Keccak256Read<R, C, Challenge255<C>>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    /// Initialize a transcript given an input buffer, whose proof is encoded with
+    /// `encoding`.
+    pub fn init_with_encoding(reader: R, encoding: ProofEncoding) -> Self {
+        Keccak256Read {
+            packed: encoding.pending_bits(),
+            ..<Self as TranscriptReadBuffer<_, _, _>>::init(reader)
+        }
+    }
+}
+
 impl<R: Read, C: CurveAffine> TranscriptRead<C, Challenge255<C>>
     for Blake2bRead<R, C, Challenge255<C>>
 where
@@ -156,7 +369,13 @@ where
 {
     fn read_point(&mut self) -> io::Result<C> {
         let mut compressed = C::Repr::default();
-        self.reader.read_exact(compressed.as_mut())?;
+        let num_bits = 8 * compressed.as_ref().len();
+        read_repr(
+            &mut self.reader,
+            &mut self.packed,
+            compressed.as_mut(),
+            num_bits,
+        )?;
         let point: C = Option::from(C::from_bytes(&compressed)).ok_or_else(|| {
             io::Error::new(io::ErrorKind::Other, "invalid point encoding in proof")
         })?;
@@ -167,7 +386,8 @@ where
 
     fn read_scalar(&mut self) -> io::Result<C::Scalar> {
         let mut data = <C::Scalar as PrimeField>::Repr::default();
-        self.reader.read_exact(data.as_mut())?;
+        let num_bits = C::Scalar::NUM_BITS as usize;
+        read_repr(&mut self.reader, &mut self.packed, data.as_mut(), num_bits)?;
         let scalar: C::Scalar = Option::from(C::Scalar::from_repr(data)).ok_or_else(|| {
             io::Error::new(
                 io::ErrorKind::Other,
@@ -187,7 +407,13 @@ where
 {
     fn read_point(&mut self) -> io::Result<C> {
         let mut compressed = C::Repr::default();
-        self.reader.read_exact(compressed.as_mut())?;
+        let num_bits = 8 * compressed.as_ref().len();
+        read_repr(
+            &mut self.reader,
+            &mut self.packed,
+            compressed.as_mut(),
+            num_bits,
+        )?;
         let point: C = Option::from(C::from_bytes(&compressed)).ok_or_else(|| {
             io::Error::new(io::ErrorKind::Other, "invalid point encoding in proof")
         })?;
@@ -198,7 +424,8 @@ where
 
     fn read_scalar(&mut self) -> io::Result<C::Scalar> {
         let mut data = <C::Scalar as PrimeField>::Repr::default();
-        self.reader.read_exact(data.as_mut())?;
+        let num_bits = C::Scalar::NUM_BITS as usize;
+        read_repr(&mut self.reader, &mut self.packed, data.as_mut(), num_bits)?;
         let scalar: C::Scalar = Option::from(C::Scalar::from_repr(data)).ok_or_else(|| {
             io::Error::new(
                 io::ErrorKind::Other,
@@ -293,6 +520,7 @@ where
 pub struct Blake2bWrite<W: Write, C: CurveAffine, E: EncodedChallenge<C>> {
     state: Blake2bState,
     writer: W,
+    packed: Option<PendingBits>,
     _marker: PhantomData<(C, E)>,
 }
 
@@ -301,6 +529,7 @@ pub struct Blake2bWrite<W: Write, C: CurveAffine, E: EncodedChallenge<C>> {
 pub struct Keccak256Write<W: Write, C: CurveAffine, E: EncodedChallenge<C>> {
     state: Keccak256,
     writer: W,
+    packed: Option<PendingBits>,
     _marker: PhantomData<(C, E)>,
 }
 
@@ -317,12 +546,18 @@ where
                 .personal(b"Halo2-Transcript")
                 .to_state(),
             writer,
+            packed: None,
             _marker: PhantomData,
         }
     }
 
-    fn finalize(self) -> W {
+    fn finalize(mut self) -> W {
         // TODO: handle outstanding scalars? see issue #138
+        if let Some(pending) = &mut self.packed {
+            pending
+                .flush(&mut self.writer)
+                .expect("the last byte of a packed proof should be written");
+        }
         self.writer
     }
 }
@@ -339,17 +574,51 @@ where
         Keccak256Write {
             state,
             writer,
+            packed: None,
             _marker: PhantomData,
         }
     }
 
     /// Conclude the interaction and return the output buffer (writer).
-    fn finalize(self) -> W {
+    fn finalize(mut self) -> W {
         // TODO: handle outstanding scalars? see issue #138
+        if let Some(pending) = &mut self.packed {
+            pending
+                .flush(&mut self.writer)
+                .expect("the last byte of a packed proof should be written");
+        }
         self.writer
     }
 }
 
+impl<W: Write, C: CurveAffine> Blake2bWrite<W, C, Challenge255<C>>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    /// Initialize a transcript given an output buffer, whose proof is encoded with
+    /// `encoding`.
+    pub fn init_with_encoding(writer: W, encoding: ProofEncoding) -> Self {
+        Blake2bWrite {
+            packed: encoding.pending_bits(),
+            ..<Self as TranscriptWriterBuffer<_, _, _>>::init(writer)
+        }
+    }
+}
+
+impl<W: Write, C: CurveAffine> Keccak256Write<W, C, Challenge255<C>>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    /// Initialize a transcript given an output buffer, whose proof is encoded with
+    /// `encoding`.
+    pub fn init_with_encoding(writer: W, encoding: ProofEncoding) -> Self {
+        Keccak256Write {
+            packed: encoding.pending_bits(),
+            ..<Self as TranscriptWriterBuffer<_, _, _>>::init(writer)
+        }
+    }
+}
+
 impl<W: Write, C: CurveAffine> TranscriptWrite<C, Challenge255<C>>
     for Blake2bWrite<W, C, Challenge255<C>>
 where
@@ -358,12 +627,19 @@ where
     fn write_point(&mut self, point: C) -> io::Result<()> {
         self.common_point(point)?;
         let compressed = point.to_bytes();
-        self.writer.write_all(compressed.as_ref())
+        let num_bits = 8 * compressed.as_ref().len();
+        write_repr(
+            &mut self.writer,
+            &mut self.packed,
+            compressed.as_ref(),
+            num_bits,
+        )
     }
     fn write_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
         self.common_scalar(scalar)?;
         let data = scalar.to_repr();
-        self.writer.write_all(data.as_ref())
+        let num_bits = C::Scalar::NUM_BITS as usize;
+        write_repr(&mut self.writer, &mut self.packed, data.as_ref(), num_bits)
     }
 }
 
@@ -375,12 +651,19 @@ where
     fn write_point(&mut self, point: C) -> io::Result<()> {
         self.common_point(point)?;
         let compressed = point.to_bytes();
-        self.writer.write_all(compressed.as_ref())
+        let num_bits = 8 * compressed.as_ref().len();
+        write_repr(
+            &mut self.writer,
+            &mut self.packed,
+            compressed.as_ref(),
+            num_bits,
+        )
     }
     fn write_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
         self.common_scalar(scalar)?;
         let data = scalar.to_repr();
-        self.writer.write_all(data.as_ref())
+        let num_bits = C::Scalar::NUM_BITS as usize;
+        write_repr(&mut self.writer, &mut self.packed, data.as_ref(), num_bits)
     }
 }
 
@@ -552,3 +835,196 @@ pub fn read_n_scalars<C: CurveAffine, E: EncodedChallenge<C>, T: TranscriptRead<
 ) -> io::Result<Vec<C::Scalar>> {
     (0..n).map(|_| transcript.read_scalar()).collect()
 }
+
+/// Absorbs the domain separation `label` of an application into the transcript, as a
+/// common scalar derived from the label.
+///
+/// This is done by [`ProverV2::new_with_domain`] and [`VerifierBuilder::with_domain`]
+/// before anything else is added to the transcript, so that every challenge depends on
+/// the label, and a proof created for one application doesn't verify for another one
+/// using the same circuit and parameters.
+///
+/// [`ProverV2::new_with_domain`]: crate::plonk::prover::ProverV2::new_with_domain
+/// [`VerifierBuilder::with_domain`]: crate::plonk::verifier::VerifierBuilder::with_domain
+pub fn absorb_domain<C: CurveAffine, E: EncodedChallenge<C>, T: Transcript<C, E>>(
+    transcript: &mut T,
+    label: &[u8],
+) -> io::Result<()>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    let hash = Blake2bParams::new()
+        .hash_length(64)
+        .personal(b"Halo2-Domain")
+        .hash(label);
+    let scalar = C::Scalar::from_uniform_bytes(hash.as_array());
+    transcript.label(TranscriptPhase::Domain, "domain label");
+    transcript.common_scalar(scalar)
+}
+
+/// The bit of the length of a proof in its envelope which flags a
+/// [`ProofEncoding::Packed`] proof.
+const PACKED_PROOF_FLAG: u32 = 1 << 31;
+
+/// Writes a proof for the curve `C` in an envelope: the identifier of the curve, the
+/// length of the proof as a `u32` in little-endian order, and the proof.
+///
+/// Proofs themselves are only sequences of points and scalars, so a proof read for
+/// another curve fails with an opaque error, or not at all until it is verified. The
+/// envelope lets [`read_proof_envelope`] reject it with an error naming both curves.
+pub fn write_proof_envelope<C: CurveAffine, W: Write>(
+    writer: &mut W,
+    proof: &[u8],
+) -> io::Result<()> {
+    write_proof_envelope_with_encoding::<C, _>(writer, proof, ProofEncoding::Standard)
+}
+
+/// Writes a proof encoded with `encoding` in an envelope, like [`write_proof_envelope`].
+///
+/// The encoding is recorded in the top bit of the length of the proof, which is clear
+/// for [`ProofEncoding::Standard`] proofs, so that their envelopes are unchanged. Proofs
+/// must be shorter than 2 GiB.
+pub fn write_proof_envelope_with_encoding<C: CurveAffine, W: Write>(
+    writer: &mut W,
+    proof: &[u8],
+    encoding: ProofEncoding,
+) -> io::Result<()> {
+    let len = u32::try_from(proof.len())
+        .ok()
+        .filter(|len| len & PACKED_PROOF_FLAG == 0)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "proof is too long"))?;
+    let len = match encoding {
+        ProofEncoding::Standard => len,
+        ProofEncoding::Packed => len | PACKED_PROOF_FLAG,
+    };
+    write_curve_id::<C, _>(writer)?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(proof)
+}
+
+/// Reads a proof written by [`write_proof_envelope`], and checks that it was created for
+/// the curve `C`.
+///
+/// Returns an error if the proof is not [`ProofEncoding::Standard`]: those are read with
+/// [`read_proof_envelope_with_encoding`].
+pub fn read_proof_envelope<C: CurveAffine, R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let (proof, encoding) = read_proof_envelope_with_encoding::<C, _>(reader)?;
+    if encoding != ProofEncoding::Standard {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the proof is encoded with {:?}", encoding),
+        ));
+    }
+    Ok(proof)
+}
+
+/// Reads a proof written by [`write_proof_envelope_with_encoding`], checks that it was
+/// created for the curve `C`, and returns it with its encoding, with which its
+/// transcript is initialized.
+pub fn read_proof_envelope_with_encoding<C: CurveAffine, R: Read>(
+    reader: &mut R,
+) -> io::Result<(Vec<u8>, ProofEncoding)> {
+    read_curve_id::<C, _>(reader, "proof")?;
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len);
+    let encoding = if len & PACKED_PROOF_FLAG == 0 {
+        ProofEncoding::Standard
+    } else {
+        ProofEncoding::Packed
+    };
+    let len = (len & !PACKED_PROOF_FLAG) as usize;
+    let mut proof = vec![];
+    reader.by_ref().take(len as u64).read_to_end(&mut proof)?;
+    if proof.len() != len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "proof is truncated",
+        ));
+    }
+    Ok((proof, encoding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        read_proof_envelope, read_proof_envelope_with_encoding, write_proof_envelope,
+        write_proof_envelope_with_encoding, Blake2bRead, Blake2bWrite, Challenge255, ProofEncoding,
+        TranscriptRead, TranscriptWrite, TranscriptWriterBuffer,
+    };
+    use group::ff::Field;
+    use halo2curves::bn256::G1Affine;
+    use halo2curves::pasta::{EqAffine, Fp};
+    use std::io;
+
+    #[test]
+    fn packed_scalars() {
+        // A scalar of Pasta is packed in 255 bits, followed by a padding bit which must be
+        // unset.
+        let mut transcript = Blake2bWrite::<_, EqAffine, Challenge255<_>>::init_with_encoding(
+            vec![],
+            ProofEncoding::Packed,
+        );
+        transcript.write_scalar(Fp::ONE).unwrap();
+        let mut scalar = transcript.finalize();
+        let read = |proof: &[u8]| {
+            Blake2bRead::<_, EqAffine, Challenge255<_>>::init_with_encoding(
+                proof,
+                ProofEncoding::Packed,
+            )
+            .read_scalar()
+        };
+        assert_eq!(scalar.len(), 32);
+        assert_eq!(read(&scalar).unwrap(), Fp::ONE);
+        scalar[31] |= 0x80;
+        assert_eq!(
+            read(&scalar).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn proof_envelopes() {
+        let proof = vec![1, 2, 3];
+        let mut envelope = vec![];
+        write_proof_envelope::<G1Affine, _>(&mut envelope, &proof).unwrap();
+        assert_eq!(
+            read_proof_envelope::<G1Affine, _>(&mut &envelope[..]).unwrap(),
+            proof
+        );
+        assert!(read_proof_envelope::<G1Affine, _>(&mut &envelope[..envelope.len() - 1]).is_err());
+
+        // A proof read for another curve is rejected with both curves.
+        let error = read_proof_envelope::<EqAffine, _>(&mut &envelope[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let message = error.to_string();
+        assert!(
+            message.starts_with("the proof was serialized for the curve bn256_g1"),
+            "{message}"
+        );
+        assert!(message.contains("for the curve vesta"), "{message}");
+
+        // The envelope records the encoding, and keeps the format of standard proofs.
+        let mut packed = vec![];
+        write_proof_envelope_with_encoding::<G1Affine, _>(
+            &mut packed,
+            &proof,
+            ProofEncoding::Packed,
+        )
+        .unwrap();
+        assert_eq!(
+            read_proof_envelope_with_encoding::<G1Affine, _>(&mut &packed[..]).unwrap(),
+            (proof.clone(), ProofEncoding::Packed)
+        );
+        assert!(read_proof_envelope::<G1Affine, _>(&mut &packed[..]).is_err());
+
+        let mut standard = vec![];
+        write_proof_envelope_with_encoding::<G1Affine, _>(
+            &mut standard,
+            &proof,
+            ProofEncoding::Standard,
+        )
+        .unwrap();
+        assert_eq!(standard, envelope);
+    }
+}
@@ -0,0 +1,138 @@
+//! The ZK Acceleration Layer: engines computing the multiscalar multiplications and the
+//! FFTs of the prover and of key generation.
+//!
+//! The computations are done by a [`PlonkEngine`], which holds an engine for each of
+//! them. Engines are trait objects, so that an implementation, for example on a GPU or an
+//! FPGA, can be chosen at runtime and passed to the prover or to key generation without
+//! patching this crate. The default engine, [`H2cEngine`], uses the CPU implementations
+//! of `halo2curves`.
+//!
+//! Engines must compute exactly the same results as [`H2cEngine`]: proofs and keys don't
+//! depend on the engine they were computed with.
+
+use std::fmt;
+use std::sync::Arc;
+
+use halo2_middleware::ff::Field;
+use halo2curves::fft::best_fft;
+use halo2curves::msm::best_multiexp;
+use halo2curves::CurveAffine;
+
+/// Traits implemented by the engines.
+pub mod traits {
+    use super::{CurveAffine, Field};
+
+    /// An engine computing multiscalar multiplications over the curve `C`.
+    pub trait MsmAccel<C: CurveAffine>: Send + Sync {
+        /// Returns the sum of `bases` multiplied by `coeffs`, which have the same length.
+        fn msm(&self, coeffs: &[C::Scalar], bases: &[C]) -> C::CurveExt;
+    }
+
+    /// An engine computing FFTs, or NTTs, over the field `F`.
+    pub trait FftAccel<F: Field>: Send + Sync {
+        /// Replaces `values`, of length `2^log_n`, by their evaluations at the powers of
+        /// `omega`, a primitive `2^log_n`-th root of unity, when they are seen as the
+        /// coefficients of a polynomial.
+        fn fft(&self, values: &mut [F], omega: F, log_n: u32);
+    }
+}
+
+use traits::{FftAccel, MsmAccel};
+
+/// The engine of the CPU implementations of `halo2curves`, which is the default one.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct H2cEngine;
+
+impl H2cEngine {
+    /// Creates the engine.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<C: CurveAffine> MsmAccel<C> for H2cEngine {
+    fn msm(&self, coeffs: &[C::Scalar], bases: &[C]) -> C::CurveExt {
+        best_multiexp(coeffs, bases)
+    }
+}
+
+impl<F: Field> FftAccel<F> for H2cEngine {
+    fn fft(&self, values: &mut [F], omega: F, log_n: u32) {
+        best_fft(values, omega, log_n)
+    }
+}
+
+/// The engines used by the prover and by key generation, for the curve `C`.
+///
+/// It is cheap to clone, and can be shared between provers running in parallel.
+#[derive(Clone)]
+pub struct PlonkEngine<C: CurveAffine> {
+    /// The engine of the multiscalar multiplications, which compute the commitments.
+    pub msm_backend: Arc<dyn MsmAccel<C>>,
+    /// The engine of the FFTs, which convert polynomials between bases.
+    pub fft_backend: Arc<dyn FftAccel<C::Scalar>>,
+}
+
+impl<C: CurveAffine> fmt::Debug for PlonkEngine<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PlonkEngine").finish_non_exhaustive()
+    }
+}
+
+impl<C: CurveAffine> Default for PlonkEngine<C> {
+    fn default() -> Self {
+        PlonkEngineConfig::new().build()
+    }
+}
+
+/// Builds a [`PlonkEngine`], with [`H2cEngine`] for the computations that are not given
+/// another engine.
+pub struct PlonkEngineConfig<C: CurveAffine> {
+    msm_backend: Option<Arc<dyn MsmAccel<C>>>,
+    fft_backend: Option<Arc<dyn FftAccel<C::Scalar>>>,
+}
+
+impl<C: CurveAffine> fmt::Debug for PlonkEngineConfig<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PlonkEngineConfig")
+            .field("msm_backend", &self.msm_backend.is_some())
+            .field("fft_backend", &self.fft_backend.is_some())
+            .finish()
+    }
+}
+
+impl<C: CurveAffine> Default for PlonkEngineConfig<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: CurveAffine> PlonkEngineConfig<C> {
+    /// Creates a configuration without any engine.
+    pub fn new() -> Self {
+        Self {
+            msm_backend: None,
+            fft_backend: None,
+        }
+    }
+
+    /// Sets the engine of the multiscalar multiplications.
+    pub fn set_msm(mut self, msm_backend: impl MsmAccel<C> + 'static) -> Self {
+        self.msm_backend = Some(Arc::new(msm_backend));
+        self
+    }
+
+    /// Sets the engine of the FFTs.
+    pub fn set_fft(mut self, fft_backend: impl FftAccel<C::Scalar> + 'static) -> Self {
+        self.fft_backend = Some(Arc::new(fft_backend));
+        self
+    }
+
+    /// Builds the engine.
+    pub fn build(self) -> PlonkEngine<C> {
+        PlonkEngine {
+            msm_backend: self.msm_backend.unwrap_or_else(|| Arc::new(H2cEngine)),
+            fft_backend: self.fft_backend.unwrap_or_else(|| Arc::new(H2cEngine)),
+        }
+    }
+}
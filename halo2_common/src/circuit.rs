@@ -17,8 +17,19 @@ pub use value::Value;
 pub mod floor_planner;
 pub use floor_planner::single_pass::SimpleFloorPlanner;
 
+#[cfg(feature = "gadgets")]
+#[cfg_attr(docsrs, doc(cfg(feature = "gadgets")))]
+pub mod instance;
 pub mod layouter;
+#[cfg(feature = "gadgets")]
+#[cfg_attr(docsrs, doc(cfg(feature = "gadgets")))]
+pub mod packed_table;
+#[cfg(feature = "cell-provenance")]
+pub mod provenance;
 mod table_layouter;
+#[cfg(feature = "gadgets")]
+#[cfg_attr(docsrs, doc(cfg(feature = "gadgets")))]
+pub mod typed;
 
 pub use table_layouter::{SimpleTableLayouter, TableLayouter};
 
@@ -209,6 +220,25 @@ impl<'r, F: Field> Region<'r, F> {
             .enable_selector(&|| annotation().into(), selector, offset)
     }
 
+    /// Enables a selector at the `len` offsets starting at `offset`.
+    ///
+    /// This is equivalent to enabling the selector at each offset, but the offsets are
+    /// passed to the backend as a single run, which is faster for long runs of rows.
+    pub fn enable_selector_range<A, AR>(
+        &mut self,
+        annotation: A,
+        selector: &Selector,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), Error>
+    where
+        A: Fn() -> AR,
+        AR: Into<String>,
+    {
+        self.region
+            .enable_selector_range(&|| annotation().into(), selector, offset, len)
+    }
+
     /// Allows the circuit implementor to name/annotate a Column within a Region context.
     ///
     /// This is useful in order to improve the amount of information that `prover.verify()`
@@ -226,6 +256,7 @@ impl<'r, F: Field> Region<'r, F> {
     /// Assign an advice column value (witness).
     ///
     /// Even though `to` has `FnMut` bounds, it is guaranteed to be called at most once.
+    #[cfg_attr(feature = "cell-provenance", track_caller)]
     pub fn assign_advice<'v, V, VR, A, AR>(
         &'v mut self,
         annotation: A,
@@ -239,6 +270,8 @@ impl<'r, F: Field> Region<'r, F> {
         A: Fn() -> AR,
         AR: Into<String>,
     {
+        #[cfg(feature = "cell-provenance")]
+        let _caller = provenance::CallerGuard::new(std::panic::Location::caller());
         let mut value = Value::unknown();
         let cell =
             self.region
@@ -256,12 +289,37 @@ impl<'r, F: Field> Region<'r, F> {
         })
     }
 
+    /// Assigns the `len` cells of an advice column starting at `offset`, from a slice of
+    /// values.
+    ///
+    /// This is equivalent to assigning each cell with [`Region::assign_advice`], but the
+    /// values are passed to the backend at once, which avoids a call and a closure per
+    /// cell when generating large witnesses. `values`, if known, must contain `len`
+    /// values.
+    pub fn assign_advice_column_slice<A, AR>(
+        &mut self,
+        annotation: A,
+        column: Column<Advice>,
+        offset: usize,
+        len: usize,
+        values: Value<&[Assigned<F>]>,
+    ) -> Result<(), Error>
+    where
+        A: Fn() -> AR,
+        AR: Into<String>,
+    {
+        values.error_if_known_and(|values| values.len() != len)?;
+        self.region
+            .assign_advice_column_slice(&|| annotation().into(), column, offset, len, values)
+    }
+
     /// Assigns a constant value to the column `advice` at `offset` within this region.
     ///
     /// The constant value will be assigned to a cell within one of the fixed columns
     /// configured via `ConstraintSystem::enable_constant`.
     ///
     /// Returns the advice cell.
+    #[cfg_attr(feature = "cell-provenance", track_caller)]
     pub fn assign_advice_from_constant<VR, A, AR>(
         &mut self,
         annotation: A,
@@ -274,6 +332,8 @@ impl<'r, F: Field> Region<'r, F> {
         A: Fn() -> AR,
         AR: Into<String>,
     {
+        #[cfg(feature = "cell-provenance")]
+        let _caller = provenance::CallerGuard::new(std::panic::Location::caller());
         let cell = self.region.assign_advice_from_constant(
             &|| annotation().into(),
             column,
@@ -292,6 +352,7 @@ impl<'r, F: Field> Region<'r, F> {
     /// `row` to the column `advice` at `offset` within this region.
     ///
     /// Returns the advice cell, and its value if known.
+    #[cfg_attr(feature = "cell-provenance", track_caller)]
     pub fn assign_advice_from_instance<A, AR>(
         &mut self,
         annotation: A,
@@ -304,6 +365,8 @@ impl<'r, F: Field> Region<'r, F> {
         A: Fn() -> AR,
         AR: Into<String>,
     {
+        #[cfg(feature = "cell-provenance")]
+        let _caller = provenance::CallerGuard::new(std::panic::Location::caller());
         let (cell, value) = self.region.assign_advice_from_instance(
             &|| annotation().into(),
             instance,
@@ -332,9 +395,33 @@ impl<'r, F: Field> Region<'r, F> {
         self.region.instance_value(instance, row)
     }
 
+    /// Returns the value of the advice column's cell at `offset` within this region, if
+    /// the column belongs to an earlier phase than the one being synthesized.
+    ///
+    /// Circuits with several phases are synthesized once per phase. During the synthesis
+    /// of a phase:
+    /// - the closures passed to [`Self::assign_advice`] are only called for the columns of
+    ///   that phase, so the cells of other phases have unknown values;
+    /// - the cells of columns of earlier phases keep the values they were assigned in
+    ///   their phase, which this method returns, so that chips don't have to recompute
+    ///   them;
+    /// - the cells of the current and later phases have unknown values here.
+    ///
+    /// The value is also unknown when the backend doesn't keep witnesses, for example
+    /// during key generation. Like [`Self::instance_value`], this doesn't create any
+    /// constraints.
+    pub fn query_advice(
+        &mut self,
+        column: Column<Advice>,
+        offset: usize,
+    ) -> Result<Value<F>, Error> {
+        self.region.advice_value(column, offset)
+    }
+
     /// Assign a fixed value.
     ///
     /// Even though `to` has `FnMut` bounds, it is guaranteed to be called at most once.
+    #[cfg_attr(feature = "cell-provenance", track_caller)]
     pub fn assign_fixed<'v, V, VR, A, AR>(
         &'v mut self,
         annotation: A,
@@ -348,6 +435,8 @@ impl<'r, F: Field> Region<'r, F> {
         A: Fn() -> AR,
         AR: Into<String>,
     {
+        #[cfg(feature = "cell-provenance")]
+        let _caller = provenance::CallerGuard::new(std::panic::Location::caller());
         let mut value = Value::unknown();
         let cell =
             self.region
@@ -382,6 +471,23 @@ impl<'r, F: Field> Region<'r, F> {
     pub fn constrain_equal(&mut self, left: Cell, right: Cell) -> Result<(), Error> {
         self.region.constrain_equal(left, right)
     }
+
+    /// Constrains a cell to have the same value as the instance column's cell at absolute
+    /// location `row`.
+    ///
+    /// The cell can be in any column where equality has been enabled, including fixed
+    /// columns. This is the same as [`Layouter::constrain_instance`], from within a region.
+    ///
+    /// Returns an error if either of the cells are in columns where equality
+    /// has not been enabled.
+    pub fn constrain_instance(
+        &mut self,
+        cell: Cell,
+        instance: Column<Instance>,
+        row: usize,
+    ) -> Result<(), Error> {
+        self.region.constrain_instance(cell, instance, row)
+    }
 }
 
 /// A lookup table in the circuit.
@@ -402,6 +508,7 @@ impl<'r, F: Field> Table<'r, F> {
     /// Returns an error if the table cell has already been assigned to.
     ///
     /// Even though `to` has `FnMut` bounds, it is guaranteed to be called at most once.
+    #[cfg_attr(feature = "cell-provenance", track_caller)]
     pub fn assign_cell<'v, V, VR, A, AR>(
         &'v mut self,
         annotation: A,
@@ -415,6 +522,8 @@ impl<'r, F: Field> Table<'r, F> {
         A: Fn() -> AR,
         AR: Into<String>,
     {
+        #[cfg(feature = "cell-provenance")]
+        let _caller = provenance::CallerGuard::new(std::panic::Location::caller());
         self.table
             .assign_cell(&|| annotation().into(), column, offset, &mut || {
                 to().into_field()
@@ -450,6 +559,66 @@ pub trait Layouter<F: Field> {
         N: Fn() -> NR,
         NR: Into<String>;
 
+    /// Assigns `steps` regions with the same shape, such as the steps of a trace that
+    /// repeats a single step region.
+    ///
+    /// `assignment` is called with each region and the index of its step. Every step must
+    /// fit in the shape of the first one, that is use no other columns or rows relative to
+    /// the start of its region: layouters may lay all the steps out with the shape of the
+    /// first one, and return [`Error::Synthesis`] for a step that doesn't fit in it. The
+    /// results of the steps are returned in order.
+    ///
+    /// By default, this assigns each step with [`Layouter::assign_region`].
+    ///
+    /// ```ignore
+    /// layouter.assign_uniform_regions(|| "round", 64, |mut region, step| {
+    ///     region.assign_advice(|| "state", config.a, 0, || state[step])
+    /// })?;
+    /// ```
+    fn assign_uniform_regions<A, AR, N, NR>(
+        &mut self,
+        name: N,
+        steps: usize,
+        mut assignment: A,
+    ) -> Result<Vec<AR>, Error>
+    where
+        A: FnMut(Region<'_, F>, usize) -> Result<AR, Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        (0..steps)
+            .map(|step| self.assign_region(&name, |region| assignment(region, step)))
+            .collect()
+    }
+
+    /// Measures the shape of a region without assigning it: the columns it uses, the
+    /// number of rows it takes and the selectors it enables.
+    ///
+    /// `assignment` is called once with a region that records the cells it assigns, as
+    /// floor planners do before laying regions out, so that chips can make layout decisions
+    /// from the size of a sub-assignment before assigning it with
+    /// [`Layouter::assign_region`]. The closures computing the values of the cells are not
+    /// called, and the values of the assigned cells are unknown. Nothing is assigned to the
+    /// circuit, and the region index of the shape is 0.
+    ///
+    /// ```ignore
+    /// let shape = layouter.measure_region(|mut region| chip.assign_rounds(&mut region))?;
+    /// if shape.row_count() > rows_left {
+    ///     // Split the rounds over several regions.
+    /// }
+    /// ```
+    fn measure_region<A, AR>(&self, assignment: A) -> Result<layouter::RegionShape, Error>
+    where
+        A: FnOnce(Region<'_, F>) -> Result<AR, Error>,
+    {
+        let mut shape = layouter::RegionShape::new(0.into());
+        {
+            let region: &mut dyn layouter::RegionLayouter<F> = &mut shape;
+            assignment(region.into())?;
+        }
+        Ok(shape)
+    }
+
     /// Assign a table region to an absolute row number.
     ///
     /// ```ignore
@@ -525,6 +694,20 @@ impl<'a, F: Field, L: Layouter<F> + 'a> Layouter<F> for NamespacedLayouter<'a, F
         self.0.assign_region(name, assignment)
     }
 
+    fn assign_uniform_regions<A, AR, N, NR>(
+        &mut self,
+        name: N,
+        steps: usize,
+        assignment: A,
+    ) -> Result<Vec<AR>, Error>
+    where
+        A: FnMut(Region<'_, F>, usize) -> Result<AR, Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        self.0.assign_uniform_regions(name, steps, assignment)
+    }
+
     fn assign_table<A, N, NR>(&mut self, name: N, assignment: A) -> Result<(), Error>
     where
         A: FnMut(Table<'_, F>) -> Result<(), Error>,
@@ -1,6 +1,34 @@
 //! Implementations of common circuit floor planners.
 
+use halo2_middleware::ff::Field;
+use tracing::{field, Span};
+
+use crate::plonk::{Assignment, NamePolicy};
+
+pub mod abutment;
+pub use abutment::{Abutment, RotationAware, Strict, Unchecked};
+
 pub mod single_pass;
 
 pub mod v1;
 pub use v1::{V1Pass, V1};
+
+/// A [`V1`] floor planner that lays the regions out to minimize the number of rows of the
+/// circuit, with the [`MinimizeRows`](v1::strategy::MinimizeRows) strategy.
+///
+/// It measures all the regions before laying them out, like [`V1`], so it has the same
+/// cost at synthesis. It is worth it for circuits with many regions of irregular shapes,
+/// which the [`SimpleFloorPlanner`](single_pass::SimpleFloorPlanner) lays out in order.
+pub type PackedFloorPlanner<A = Unchecked> = V1<v1::strategy::MinimizeRows, A>;
+
+/// Records the name of a region in the `region` field of `span`, if the span is enabled
+/// and `cs` keeps every name.
+fn record_region_name<F: Field, CS: Assignment<F>, N: FnOnce() -> NR, NR: Into<String>>(
+    span: &Span,
+    cs: &CS,
+    name: N,
+) {
+    if !span.is_disabled() && cs.name_policy() == NamePolicy::KeepAll {
+        span.record("region", field::display(name().into()));
+    }
+}
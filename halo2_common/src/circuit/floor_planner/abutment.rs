@@ -0,0 +1,292 @@
+//! Placement of regions whose gates query cells at other rows.
+//!
+//! A gate enabled at a row of a region reads the cells at each of its rotations, which may
+//! be outside of the region: a region that assigns a single row and enables a gate
+//! querying `Rotation::next()` reads the first row of whichever region is placed after it.
+//! The floor planners lay regions out as rectangles bounded on the cells they assign, so
+//! whether this happens depends on the floor planner and on the other regions.
+//!
+//! Both floor planners are parameterized by an [`Abutment`] mode that controls how these
+//! reads are handled:
+//! - [`Unchecked`], the default, ignores them. Circuits that deliberately read the cells
+//!   of a neighbouring region keep working, and layouts are unchanged.
+//! - [`RotationAware`] extends each region to the rows its gates read, so that no other
+//!   region is placed over them. This changes the layout of regions with rotated queries.
+//! - [`Strict`] lays regions out as [`Unchecked`] does, but returns an error if a gate
+//!   enabled in a region reads a cell of another region, or a row before the first one.
+//!
+//! The rows read by a region are computed from the gates, lookups and shuffles of the
+//! selectors it enables, which requires running `configure` once more in the floor planner
+//! when the mode isn't [`Unchecked`].
+//!
+//! ```ignore
+//! type FloorPlanner = SimpleFloorPlanner<RotationAware>;
+//! type FloorPlanner = V1<SlotInBiggestAdviceFirst, Strict>;
+//! ```
+
+use std::cmp;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use halo2_middleware::circuit::{Advice, Any};
+use halo2_middleware::ff::Field;
+
+use crate::circuit::{
+    layouter::{RegionColumn, RegionShape},
+    RegionIndex, RegionStart,
+};
+use crate::plonk::{circuit::Column, Circuit, ConstraintSystem, Error, Expression, Selector};
+
+/// How a floor planner handles the cells read by gates outside of their region, see the
+/// [module documentation](self).
+pub trait Abutment {
+    /// The mode of the floor planner.
+    const MODE: AbutmentMode;
+}
+
+/// The modes of [`Abutment`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AbutmentMode {
+    /// Reads outside of a region are ignored.
+    Unchecked,
+    /// Regions are placed so that no region is read by the gates of another one.
+    RotationAware,
+    /// Regions are placed as with `Unchecked`, and reads of another region are errors.
+    Strict,
+}
+
+/// The default [`Abutment`] mode, which ignores reads outside of a region.
+#[derive(Debug)]
+pub struct Unchecked;
+
+impl Abutment for Unchecked {
+    const MODE: AbutmentMode = AbutmentMode::Unchecked;
+}
+
+/// The [`Abutment`] mode that places regions so that no region is read by the gates of
+/// another one.
+#[derive(Debug)]
+pub struct RotationAware;
+
+impl Abutment for RotationAware {
+    const MODE: AbutmentMode = AbutmentMode::RotationAware;
+}
+
+/// The [`Abutment`] mode that returns an error if a region is read by the gates of
+/// another one.
+#[derive(Debug)]
+pub struct Strict;
+
+impl Abutment for Strict {
+    const MODE: AbutmentMode = AbutmentMode::Strict;
+}
+
+type Queries = (Vec<Selector>, Vec<(Column<Any>, i32)>);
+
+/// Returns the selectors and the fixed and advice cells queried by `expression`.
+fn queries<F: Field>(expression: &Expression<F>) -> Queries {
+    let none = || (vec![], vec![]);
+    let concat = |(mut selectors, mut cells): Queries, (other_selectors, other_cells): Queries| {
+        selectors.extend(other_selectors);
+        cells.extend(other_cells);
+        (selectors, cells)
+    };
+    expression.evaluate(
+        &|_| none(),
+        &|selector| (vec![selector], vec![]),
+        &|query| {
+            let column = Column::new(query.column_index(), Any::Fixed);
+            (vec![], vec![(column, query.rotation().0)])
+        },
+        &|query| {
+            let column = Column::new(
+                query.column_index(),
+                Any::Advice(Advice::new(query.phase())),
+            );
+            (vec![], vec![(column, query.rotation().0)])
+        },
+        // Regions don't assign instance cells.
+        &|_| none(),
+        &|_| none(),
+        &|queries| queries,
+        &concat,
+        &concat,
+        &|queries, _| queries,
+    )
+}
+
+/// The cells read when each selector is enabled, as columns and rotations relative to
+/// the row the selector is enabled at.
+#[derive(Clone, Debug, Default)]
+pub struct SelectorFootprints(HashMap<usize, Vec<(Column<Any>, i32)>>);
+
+impl SelectorFootprints {
+    /// Collects the cells queried by the gates, lookups and shuffles that each selector of
+    /// `cs` enables.
+    pub fn new<F: Field>(cs: &ConstraintSystem<F>) -> Self {
+        let mut footprints = SelectorFootprints::default();
+        for gate in cs.gates() {
+            let cells: Vec<_> = gate
+                .queried_cells()
+                .iter()
+                .filter(|cell| *cell.column.column_type() != Any::Instance)
+                .map(|cell| (cell.column, cell.rotation.0))
+                .collect();
+            for selector in gate.queried_selectors() {
+                footprints.insert(*selector, &cells);
+            }
+        }
+
+        let arguments =
+            cs.lookups()
+                .iter()
+                .map(|lookup| &lookup.input_expressions)
+                .chain(cs.shuffles().iter().flat_map(|shuffle| {
+                    [&shuffle.input_expressions, &shuffle.shuffle_expressions]
+                }));
+        for expressions in arguments {
+            let (selectors, cells) = expressions.iter().map(queries).fold(
+                (vec![], vec![]),
+                |(mut selectors, mut cells), (other_selectors, other_cells)| {
+                    selectors.extend(other_selectors);
+                    cells.extend(other_cells);
+                    (selectors, cells)
+                },
+            );
+            for selector in selectors {
+                footprints.insert(selector, &cells);
+            }
+        }
+
+        footprints
+    }
+
+    /// Runs `configure` for `circuit`, and collects the footprints of its selectors if the
+    /// mode `A` needs them.
+    #[cfg_attr(not(feature = "circuit-params"), allow(unused_variables))]
+    pub(crate) fn for_mode<A: Abutment, F: Field, C: Circuit<F>>(circuit: &C) -> Self {
+        if A::MODE == AbutmentMode::Unchecked {
+            return SelectorFootprints::default();
+        }
+        let mut cs = ConstraintSystem::default();
+        #[cfg(feature = "circuit-params")]
+        C::configure_with_params(&mut cs, circuit.params());
+        #[cfg(not(feature = "circuit-params"))]
+        C::configure(&mut cs);
+        SelectorFootprints::new(&cs)
+    }
+
+    fn insert(&mut self, selector: Selector, cells: &[(Column<Any>, i32)]) {
+        let footprint = self.0.entry(selector.0).or_default();
+        for cell in cells {
+            if !footprint.contains(cell) {
+                footprint.push(*cell);
+            }
+        }
+    }
+
+    /// Returns the rows of each column read by the selectors enabled in `region`,
+    /// relative to the first row of the region.
+    pub fn reads(&self, region: &RegionShape) -> HashMap<RegionColumn, Range<i64>> {
+        let mut reads: HashMap<RegionColumn, Range<i64>> = HashMap::new();
+        for (selector, offset) in region.enabled_selectors() {
+            for (column, rotation) in self.0.get(&selector.0).into_iter().flatten() {
+                let row = *offset as i64 + *rotation as i64;
+                let rows = reads.entry((*column).into()).or_insert(row..row + 1);
+                rows.start = cmp::min(rows.start, row);
+                rows.end = cmp::max(rows.end, row + 1);
+            }
+        }
+        reads
+    }
+
+    /// Returns `region` extended to the rows and columns read by its selectors, and the
+    /// offset of the first row of `region` within the extended region.
+    pub fn padded(&self, region: &RegionShape) -> (RegionShape, usize) {
+        let reads = self.reads(region);
+        let start = cmp::min(reads.values().map(|rows| rows.start).min().unwrap_or(0), 0);
+        let end = cmp::max(
+            reads.values().map(|rows| rows.end).max().unwrap_or(0),
+            region.row_count() as i64,
+        );
+
+        let mut padded = region.clone();
+        padded.columns.extend(reads.into_keys());
+        padded.row_count = (end - start) as usize;
+        (padded, (-start) as usize)
+    }
+}
+
+/// The rows assigned and read by the regions placed so far, to detect regions that read
+/// the cells of another one.
+#[derive(Debug, Default)]
+pub(crate) struct RegionReads {
+    assigned: HashMap<RegionColumn, Vec<(Range<i64>, RegionIndex)>>,
+    read: HashMap<RegionColumn, Vec<(Range<i64>, RegionIndex)>>,
+}
+
+fn overlapping(
+    placed: Option<&Vec<(Range<i64>, RegionIndex)>>,
+    rows: &Range<i64>,
+    region: RegionIndex,
+) -> Option<RegionIndex> {
+    placed
+        .into_iter()
+        .flatten()
+        .find(|(other, index)| {
+            **index != *region && other.start < rows.end && rows.start < other.end
+        })
+        .map(|(_, index)| *index)
+}
+
+impl RegionReads {
+    /// Records `region`, placed at `start`.
+    ///
+    /// Returns an error if the region reads a row before the first one, reads a cell of a
+    /// region recorded before, or if one of those reads a cell of this region.
+    pub(crate) fn insert(
+        &mut self,
+        footprints: &SelectorFootprints,
+        region: &RegionShape,
+        start: RegionStart,
+    ) -> Result<(), Error> {
+        let index = region.region_index();
+        let start = *start as i64;
+
+        for (column, rows) in footprints.reads(region) {
+            let rows = start + rows.start..start + rows.end;
+            if rows.start < 0 {
+                return Err(Error::Other(format!(
+                    "region {} reads a row before the first one in {:?}",
+                    *index, column
+                )));
+            }
+            if let Some(other) = overlapping(self.assigned.get(&column), &rows, index) {
+                return Err(Error::Other(format!(
+                    "region {} reads the cells of region {} in {:?}",
+                    *index, *other, column
+                )));
+            }
+            self.read.entry(column).or_default().push((rows, index));
+        }
+
+        // Empty regions don't assign any row.
+        if region.row_count() > 0 {
+            let rows = start..start + region.row_count() as i64;
+            for column in region.columns() {
+                if let Some(other) = overlapping(self.read.get(column), &rows, index) {
+                    return Err(Error::Other(format!(
+                        "region {} reads the cells of region {} in {:?}",
+                        *other, *index, column
+                    )));
+                }
+                self.assigned
+                    .entry(*column)
+                    .or_default()
+                    .push((rows.clone(), index));
+            }
+        }
+
+        Ok(())
+    }
+}
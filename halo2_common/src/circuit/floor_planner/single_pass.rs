@@ -5,6 +5,8 @@ use std::marker::PhantomData;
 
 use halo2_middleware::ff::Field;
 
+use super::abutment::{Abutment, AbutmentMode, RegionReads, SelectorFootprints, Unchecked};
+use super::record_region_name;
 use crate::plonk::Assigned;
 use crate::{
     circuit::{
@@ -15,23 +17,32 @@ use crate::{
     plonk::{circuit::Challenge, Assignment, Circuit, Error, FloorPlanner, Selector, TableColumn},
 };
 use halo2_middleware::circuit::{Advice, Any, Fixed, Instance};
+use tracing::{debug_span, field};
 
 /// A simple [`FloorPlanner`] that performs minimal optimizations.
 ///
 /// This floor planner is suitable for debugging circuits. It aims to reflect the circuit
 /// "business logic" in the circuit layout as closely as possible. It uses a single-pass
 /// layouter that does not reorder regions for optimal packing.
+///
+/// The [`Abutment`] mode `A` controls how the cells read by gates outside of their region
+/// are handled, see the [`abutment`](super::abutment) module. `SimpleFloorPlanner` in type
+/// position keeps the [`Unchecked`] mode, but since the default type parameter isn't used
+/// in expressions, paths like `SimpleFloorPlanner::synthesize` must be written
+/// `<SimpleFloorPlanner as FloorPlanner>::synthesize`.
 #[derive(Debug)]
-pub struct SimpleFloorPlanner;
+pub struct SimpleFloorPlanner<A: Abutment = Unchecked>(PhantomData<A>);
 
-impl FloorPlanner for SimpleFloorPlanner {
+impl<A: Abutment> FloorPlanner for SimpleFloorPlanner<A> {
     fn synthesize<F: Field, CS: Assignment<F> + SyncDeps, C: Circuit<F>>(
         cs: &mut CS,
         circuit: &C,
         config: C::Config,
         constants: Vec<Column<Fixed>>,
     ) -> Result<(), Error> {
-        let layouter = SingleChipLayouter::new(cs, constants)?;
+        let mut layouter = SingleChipLayouter::new(cs, constants)?;
+        layouter.abutment = A::MODE;
+        layouter.footprints = SelectorFootprints::for_mode::<A, _, _>(circuit);
         circuit.synthesize(config, layouter)
     }
 }
@@ -46,6 +57,12 @@ pub struct SingleChipLayouter<'a, F: Field, CS: Assignment<F> + 'a> {
     columns: HashMap<RegionColumn, usize>,
     /// Stores the table fixed columns.
     table_columns: Vec<TableColumn>,
+    /// How the cells read by gates outside of their region are handled.
+    abutment: AbutmentMode,
+    /// Stores the cells read by each selector, if `abutment` needs them.
+    footprints: SelectorFootprints,
+    /// Stores the rows assigned and read by each region, in strict mode.
+    reads: RegionReads,
     _marker: PhantomData<F>,
 }
 
@@ -67,12 +84,50 @@ impl<'a, F: Field, CS: Assignment<F>> SingleChipLayouter<'a, F, CS> {
             regions: vec![],
             columns: HashMap::default(),
             table_columns: vec![],
+            abutment: AbutmentMode::Unchecked,
+            footprints: SelectorFootprints::default(),
+            reads: RegionReads::default(),
             _marker: PhantomData,
         };
         Ok(ret)
     }
 }
 
+impl<'a, F: Field, CS: Assignment<F> + 'a> SingleChipLayouter<'a, F, CS> {
+    /// Assigns the constants a region constrained its cells to. For the simple floor
+    /// planner, we assign constants in order in the first `constants` column.
+    fn assign_constants(&mut self, constants: Vec<(Assigned<F>, Cell)>) -> Result<(), Error> {
+        if self.constants.is_empty() {
+            if !constants.is_empty() {
+                return Err(Error::NotEnoughColumnsForConstants);
+            }
+        } else {
+            let constants_column = self.constants[0];
+            let next_constant_row = self
+                .columns
+                .entry(Column::<Any>::from(constants_column).into())
+                .or_default();
+            for (constant, advice) in constants {
+                self.cs.assign_fixed(
+                    || format!("Constant({:?})", constant.evaluate()),
+                    constants_column,
+                    *next_constant_row,
+                    || Value::known(constant),
+                )?;
+                self.cs.copy(
+                    constants_column.into(),
+                    *next_constant_row,
+                    advice.column,
+                    *self.regions[*advice.region_index] + advice.row_offset,
+                )?;
+                *next_constant_row += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl<'a, F: Field, CS: Assignment<F> + 'a + SyncDeps> Layouter<F>
     for SingleChipLayouter<'a, F, CS>
 {
@@ -85,6 +140,15 @@ impl<'a, F: Field, CS: Assignment<F> + 'a + SyncDeps> Layouter<F>
         NR: Into<String>,
     {
         let region_index = self.regions.len();
+        let span = debug_span!(
+            "assign_region",
+            region = field::Empty,
+            index = region_index,
+            start = field::Empty,
+            rows = field::Empty,
+        )
+        .entered();
+        record_region_name::<F, _, _, _>(&span, &*self.cs, &name);
 
         // Get shape of the region.
         let mut shape = RegionShape::new(region_index.into());
@@ -93,17 +157,35 @@ impl<'a, F: Field, CS: Assignment<F> + 'a + SyncDeps> Layouter<F>
             assignment(region.into())?;
         }
 
+        // In rotation-aware mode, the region is extended to the rows its gates read.
+        let padded = match self.abutment {
+            AbutmentMode::RotationAware => Some(self.footprints.padded(&shape)),
+            _ => None,
+        };
+        let (placed, offset) = padded
+            .as_ref()
+            .map_or((&shape, 0), |(padded, offset)| (padded, *offset));
+
         // Lay out this region. We implement the simplest approach here: position the
         // region starting at the earliest row for which none of the columns are in use.
-        let mut region_start = 0;
-        for column in &shape.columns {
-            region_start = cmp::max(region_start, self.columns.get(column).cloned().unwrap_or(0));
+        let mut placed_start = 0;
+        for column in &placed.columns {
+            placed_start = cmp::max(placed_start, self.columns.get(column).cloned().unwrap_or(0));
         }
+        let region_start = placed_start + offset;
         self.regions.push(region_start.into());
+        span.record("start", region_start);
+        span.record("rows", shape.row_count);
+
+        if self.abutment == AbutmentMode::Strict {
+            self.reads
+                .insert(&self.footprints, &shape, region_start.into())?;
+        }
 
         // Update column usage information.
-        for column in shape.columns {
-            self.columns.insert(column, region_start + shape.row_count);
+        for column in &placed.columns {
+            self.columns
+                .insert(*column, placed_start + placed.row_count);
         }
 
         // Assign region cells.
@@ -116,36 +198,116 @@ impl<'a, F: Field, CS: Assignment<F> + 'a + SyncDeps> Layouter<F>
         let constants_to_assign = region.constants;
         self.cs.exit_region();
 
-        // Assign constants. For the simple floor planner, we assign constants in order in
-        // the first `constants` column.
-        if self.constants.is_empty() {
-            if !constants_to_assign.is_empty() {
-                return Err(Error::NotEnoughColumnsForConstants);
+        self.assign_constants(constants_to_assign)?;
+
+        Ok(result)
+    }
+
+    fn assign_uniform_regions<A, AR, N, NR>(
+        &mut self,
+        name: N,
+        steps: usize,
+        mut assignment: A,
+    ) -> Result<Vec<AR>, Error>
+    where
+        A: FnMut(Region<'_, F>, usize) -> Result<AR, Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        if steps == 0 {
+            return Ok(vec![]);
+        }
+        let first_index = self.regions.len();
+        let span = debug_span!(
+            "assign_uniform_regions",
+            region = field::Empty,
+            index = first_index,
+            steps,
+            start = field::Empty,
+            rows = field::Empty,
+        )
+        .entered();
+        record_region_name::<F, _, _, _>(&span, &*self.cs, &name);
+
+        // The steps are laid out with the shape of the first one, which the others must
+        // fit in.
+        let measure = |assignment: &mut A, step: usize| {
+            let mut shape = RegionShape::new((first_index + step).into());
+            {
+                let region: &mut dyn RegionLayouter<F> = &mut shape;
+                assignment(region.into(), step)?;
             }
-        } else {
-            let constants_column = self.constants[0];
-            let next_constant_row = self
-                .columns
-                .entry(Column::<Any>::from(constants_column).into())
-                .or_default();
-            for (constant, advice) in constants_to_assign {
-                self.cs.assign_fixed(
-                    || format!("Constant({:?})", constant.evaluate()),
-                    constants_column,
-                    *next_constant_row,
-                    || Value::known(constant),
-                )?;
-                self.cs.copy(
-                    constants_column.into(),
-                    *next_constant_row,
-                    advice.column,
-                    *self.regions[*advice.region_index] + advice.row_offset,
+            Ok::<_, Error>(shape)
+        };
+        let shape = measure(&mut assignment, 0)?;
+        for step in 1..steps {
+            let step_shape = measure(&mut assignment, step)?;
+            let fits = step_shape.row_count <= shape.row_count
+                && step_shape.columns.is_subset(&shape.columns)
+                && step_shape
+                    .enabled_selectors()
+                    .iter()
+                    .all(|selector| shape.enabled_selectors().contains(selector));
+            if !fits {
+                return Err(Error::Synthesis);
+            }
+        }
+
+        let padded = match self.abutment {
+            AbutmentMode::RotationAware => Some(self.footprints.padded(&shape)),
+            _ => None,
+        };
+        let (placed, offset) = padded
+            .as_ref()
+            .map_or((&shape, 0), |(padded, offset)| (padded, *offset));
+
+        // The steps are laid out one after the other, from the earliest row for which
+        // none of the columns are in use.
+        let mut placed_start = 0;
+        for column in &placed.columns {
+            placed_start = cmp::max(placed_start, self.columns.get(column).cloned().unwrap_or(0));
+        }
+        let stride = placed.row_count;
+        let first_start = placed_start + offset;
+        self.regions
+            .extend((0..steps).map(|step| (first_start + step * stride).into()));
+        span.record("start", first_start);
+        span.record("rows", stride * steps);
+
+        if self.abutment == AbutmentMode::Strict {
+            let mut step_shape = shape.clone();
+            for step in 0..steps {
+                step_shape.region_index = (first_index + step).into();
+                self.reads.insert(
+                    &self.footprints,
+                    &step_shape,
+                    self.regions[first_index + step],
                 )?;
-                *next_constant_row += 1;
             }
         }
 
-        Ok(result)
+        for column in &placed.columns {
+            self.columns.insert(*column, placed_start + stride * steps);
+        }
+
+        // Assign the cells of each step.
+        let mut results = Vec::with_capacity(steps);
+        let mut constants_to_assign = vec![];
+        for step in 0..steps {
+            self.cs.enter_region(&name);
+            let mut region = SingleChipLayouterRegion::new(self, (first_index + step).into());
+            let result = {
+                let region: &mut dyn RegionLayouter<F> = &mut region;
+                assignment(region.into(), step)
+            }?;
+            constants_to_assign.extend(region.constants);
+            self.cs.exit_region();
+            results.push(result);
+        }
+
+        self.assign_constants(constants_to_assign)?;
+
+        Ok(results)
     }
 
     fn assign_table<A, N, NR>(&mut self, name: N, mut assignment: A) -> Result<(), Error>
@@ -264,6 +426,19 @@ impl<'r, 'a, F: Field, CS: Assignment<F> + 'a + SyncDeps> RegionLayouter<F>
         )
     }
 
+    fn enable_selector_range<'v>(
+        &'v mut self,
+        annotation: &'v (dyn Fn() -> String + 'v),
+        selector: &Selector,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), Error> {
+        let start = *self.layouter.regions[*self.region_index] + offset;
+        self.layouter
+            .cs
+            .enable_selector_range(annotation, selector, start..start + len)
+    }
+
     fn name_column<'v>(
         &'v mut self,
         annotation: &'v (dyn Fn() -> String + 'v),
@@ -293,6 +468,20 @@ impl<'r, 'a, F: Field, CS: Assignment<F> + 'a + SyncDeps> RegionLayouter<F>
         })
     }
 
+    fn assign_advice_column_slice<'v>(
+        &'v mut self,
+        annotation: &'v (dyn Fn() -> String + 'v),
+        column: Column<Advice>,
+        offset: usize,
+        len: usize,
+        values: Value<&[Assigned<F>]>,
+    ) -> Result<(), Error> {
+        let start = *self.layouter.regions[*self.region_index] + offset;
+        self.layouter
+            .cs
+            .assign_advice_column_slice(annotation, column, start..start + len, values)
+    }
+
     fn assign_advice_from_constant<'v>(
         &'v mut self,
         annotation: &'v (dyn Fn() -> String + 'v),
@@ -337,6 +526,12 @@ impl<'r, 'a, F: Field, CS: Assignment<F> + 'a + SyncDeps> RegionLayouter<F>
         self.layouter.cs.query_instance(instance, row)
     }
 
+    fn advice_value(&mut self, column: Column<Advice>, offset: usize) -> Result<Value<F>, Error> {
+        self.layouter
+            .cs
+            .query_advice(column, *self.layouter.regions[*self.region_index] + offset)
+    }
+
     fn assign_fixed<'v>(
         &'v mut self,
         annotation: &'v (dyn Fn() -> String + 'v),
@@ -373,4 +568,18 @@ impl<'r, 'a, F: Field, CS: Assignment<F> + 'a + SyncDeps> RegionLayouter<F>
 
         Ok(())
     }
+
+    fn constrain_instance(
+        &mut self,
+        cell: Cell,
+        instance: Column<Instance>,
+        row: usize,
+    ) -> Result<(), Error> {
+        self.layouter.cs.copy(
+            cell.column,
+            *self.layouter.regions[*cell.region_index] + cell.row_offset,
+            instance.into(),
+            row,
+        )
+    }
 }
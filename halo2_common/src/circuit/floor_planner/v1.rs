@@ -1,7 +1,10 @@
 use std::fmt;
+use std::marker::PhantomData;
 
 use halo2_middleware::ff::Field;
 
+use super::abutment::{Abutment, AbutmentMode, RegionReads, SelectorFootprints, Unchecked};
+use super::record_region_name;
 use crate::plonk::Assigned;
 use crate::{
     circuit::{
@@ -12,8 +15,10 @@ use crate::{
     plonk::{circuit::Challenge, Assignment, Circuit, Error, FloorPlanner, Selector, TableColumn},
 };
 use halo2_middleware::circuit::{Advice, Any, Fixed, Instance};
+use tracing::{debug_span, field};
 
 pub mod strategy;
+use strategy::{PlanningStrategy, SlotInBiggestAdviceFirst};
 
 /// The version 1 [`FloorPlanner`] provided by `halo2`.
 ///
@@ -21,10 +26,15 @@ pub mod strategy;
 ///   circuit designer.
 /// - A dual-pass layouter is used to measures regions prior to assignment.
 /// - Regions are measured as rectangles, bounded on the cells they assign.
-/// - Regions are laid out using a greedy first-fit strategy, after sorting regions by
-///   their "advice area" (number of advice columns * rows).
+/// - Regions are laid out by the [`PlanningStrategy`] `S`. By default, they are laid out
+///   using a greedy first-fit strategy, after sorting regions by their "advice area"
+///   (number of advice columns * rows).
+/// - The cells read by gates outside of their region are handled according to the
+///   [`Abutment`] mode `A`, see the [`abutment`](super::abutment) module.
 #[derive(Debug)]
-pub struct V1;
+pub struct V1<S: PlanningStrategy = SlotInBiggestAdviceFirst, A: Abutment = Unchecked>(
+    PhantomData<(S, A)>,
+);
 
 struct V1Plan<'a, F: Field, CS: Assignment<F> + 'a> {
     cs: &'a mut CS,
@@ -55,7 +65,7 @@ impl<'a, F: Field, CS: Assignment<F> + SyncDeps> V1Plan<'a, F, CS> {
     }
 }
 
-impl FloorPlanner for V1 {
+impl<S: PlanningStrategy, A: Abutment> FloorPlanner for V1<S, A> {
     fn synthesize<F: Field, CS: Assignment<F> + SyncDeps, C: Circuit<F>>(
         cs: &mut CS,
         circuit: &C,
@@ -74,8 +84,34 @@ impl FloorPlanner for V1 {
         }
 
         // Planning:
-        // - Position the regions.
-        let (regions, column_allocations) = strategy::slot_in_biggest_advice_first(measure.regions);
+        // - Position the regions. In rotation-aware mode, the regions are extended to the
+        //   rows their gates read before being positioned.
+        let footprints = SelectorFootprints::for_mode::<A, _, _>(circuit);
+        let (regions, column_allocations) = if A::MODE == AbutmentMode::RotationAware {
+            let (padded, offsets): (Vec<_>, Vec<_>) = measure
+                .regions
+                .iter()
+                .map(|region| footprints.padded(region))
+                .unzip();
+            let starts = S::plan(&padded);
+            let column_allocations = strategy::allocations(&padded, &starts)?;
+            let regions: Vec<RegionStart> = starts
+                .iter()
+                .zip(offsets)
+                .map(|(start, offset)| (**start + offset).into())
+                .collect();
+            (regions, column_allocations)
+        } else {
+            let regions = S::plan(&measure.regions);
+            let column_allocations = strategy::allocations(&measure.regions, &regions)?;
+            (regions, column_allocations)
+        };
+        if A::MODE == AbutmentMode::Strict {
+            let mut reads = RegionReads::default();
+            for (region, start) in measure.regions.iter().zip(regions.iter()) {
+                reads.insert(&footprints, region, *start)?;
+            }
+        }
         plan.regions = regions;
 
         // - Determine how many rows our planned circuit will require.
@@ -280,6 +316,14 @@ impl<'p, 'a, F: Field, CS: Assignment<F> + SyncDeps> AssignmentPass<'p, 'a, F, C
         // Get the next region we are assigning.
         let region_index = self.region_index;
         self.region_index += 1;
+        let span = debug_span!(
+            "assign_region",
+            region = field::Empty,
+            index = region_index,
+            start = *self.plan.regions[region_index],
+        )
+        .entered();
+        record_region_name::<F, _, _, _>(&span, &*self.plan.cs, &name);
 
         self.plan.cs.enter_region(name);
         let mut region = V1Region::new(self.plan, region_index.into());
@@ -380,6 +424,19 @@ impl<'r, 'a, F: Field, CS: Assignment<F> + SyncDeps> RegionLayouter<F> for V1Reg
         )
     }
 
+    fn enable_selector_range<'v>(
+        &'v mut self,
+        annotation: &'v (dyn Fn() -> String + 'v),
+        selector: &Selector,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), Error> {
+        let start = *self.plan.regions[*self.region_index] + offset;
+        self.plan
+            .cs
+            .enable_selector_range(annotation, selector, start..start + len)
+    }
+
     fn assign_advice<'v>(
         &'v mut self,
         annotation: &'v (dyn Fn() -> String + 'v),
@@ -401,6 +458,20 @@ impl<'r, 'a, F: Field, CS: Assignment<F> + SyncDeps> RegionLayouter<F> for V1Reg
         })
     }
 
+    fn assign_advice_column_slice<'v>(
+        &'v mut self,
+        annotation: &'v (dyn Fn() -> String + 'v),
+        column: Column<Advice>,
+        offset: usize,
+        len: usize,
+        values: Value<&[Assigned<F>]>,
+    ) -> Result<(), Error> {
+        let start = *self.plan.regions[*self.region_index] + offset;
+        self.plan
+            .cs
+            .assign_advice_column_slice(annotation, column, start..start + len, values)
+    }
+
     fn assign_advice_from_constant<'v>(
         &'v mut self,
         annotation: &'v (dyn Fn() -> String + 'v),
@@ -445,6 +516,12 @@ impl<'r, 'a, F: Field, CS: Assignment<F> + SyncDeps> RegionLayouter<F> for V1Reg
         self.plan.cs.query_instance(instance, row)
     }
 
+    fn advice_value(&mut self, column: Column<Advice>, offset: usize) -> Result<Value<F>, Error> {
+        self.plan
+            .cs
+            .query_advice(column, *self.plan.regions[*self.region_index] + offset)
+    }
+
     fn assign_fixed<'v>(
         &'v mut self,
         annotation: &'v (dyn Fn() -> String + 'v),
@@ -489,4 +566,18 @@ impl<'r, 'a, F: Field, CS: Assignment<F> + SyncDeps> RegionLayouter<F> for V1Reg
 
         Ok(())
     }
+
+    fn constrain_instance(
+        &mut self,
+        cell: Cell,
+        instance: Column<Instance>,
+        row: usize,
+    ) -> Result<(), Error> {
+        self.plan.cs.copy(
+            cell.column,
+            *self.plan.regions[*cell.region_index] + cell.row_offset,
+            instance.into(),
+            row,
+        )
+    }
 }
@@ -1,13 +1,54 @@
 use std::{
     cmp,
     collections::{BTreeSet, HashMap},
-    ops::Range,
+    ops::{Bound, Range},
 };
 
 use super::{RegionColumn, RegionShape};
 use crate::circuit::RegionStart;
+use crate::plonk::Error;
 use halo2_middleware::circuit::Any;
 
+/// A strategy to position the regions of a circuit laid out by the [`V1`] floor planner.
+///
+/// The regions are measured by a first pass over the circuit; the strategy then chooses
+/// the row at which each of them starts. Regions that share a column (or a selector) must
+/// not overlap, which the floor planner checks before assigning them.
+///
+/// [`V1`]: super::V1
+pub trait PlanningStrategy {
+    /// Returns the start row of each region of `regions`, in the same order.
+    ///
+    /// `regions` is sorted by region index.
+    fn plan(regions: &[RegionShape]) -> Vec<RegionStart>;
+}
+
+/// The default [`PlanningStrategy`] of the [`V1`] floor planner, which lays the regions
+/// out with [`slot_in_biggest_advice_first`].
+///
+/// [`V1`]: super::V1
+#[derive(Debug)]
+pub struct SlotInBiggestAdviceFirst;
+
+impl PlanningStrategy for SlotInBiggestAdviceFirst {
+    fn plan(regions: &[RegionShape]) -> Vec<RegionStart> {
+        slot_in_biggest_advice_first(regions.to_vec()).0
+    }
+}
+
+/// The [`PlanningStrategy`] of the [`PackedFloorPlanner`], which lays the regions out with
+/// [`pack`] to minimize the number of rows of the circuit.
+///
+/// [`PackedFloorPlanner`]: crate::circuit::floor_planner::PackedFloorPlanner
+#[derive(Debug)]
+pub struct MinimizeRows;
+
+impl PlanningStrategy for MinimizeRows {
+    fn plan(regions: &[RegionShape]) -> Vec<RegionStart> {
+        pack(regions.to_vec()).0
+    }
+}
+
 /// A region allocated within a column.
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
 struct AllocatedRegion {
@@ -102,6 +143,58 @@ impl Allocations {
 /// Allocated rows within a circuit.
 pub type CircuitAllocations = HashMap<RegionColumn, Allocations>;
 
+/// Returns the rows allocated by `regions` when they start at `starts`.
+///
+/// Returns an error if the number of starts doesn't match the number of regions, or if
+/// two regions overlap in one of their columns.
+pub(crate) fn allocations(
+    regions: &[RegionShape],
+    starts: &[RegionStart],
+) -> Result<CircuitAllocations, Error> {
+    if regions.len() != starts.len() {
+        return Err(Error::Other(format!(
+            "planning strategy positioned {} regions, expected {}",
+            starts.len(),
+            regions.len()
+        )));
+    }
+
+    let mut column_allocations: CircuitAllocations = Default::default();
+    for (region, start) in regions.iter().zip(starts.iter()) {
+        // Empty regions don't allocate any row.
+        if region.row_count() == 0 {
+            continue;
+        }
+        let allocated = AllocatedRegion {
+            start: **start,
+            length: region.row_count(),
+        };
+        for column in region.columns() {
+            let allocations = &mut column_allocations.entry(*column).or_default().0;
+            let overlaps_previous = allocations
+                .range(..=&allocated)
+                .next_back()
+                .map_or(false, |prev| prev.start + prev.length > allocated.start);
+            let overlaps_next = allocations
+                .range((Bound::Excluded(&allocated), Bound::Unbounded))
+                .next()
+                .map_or(false, |next| {
+                    next.start < allocated.start + allocated.length
+                });
+            if overlaps_previous || overlaps_next {
+                return Err(Error::Other(format!(
+                    "region {} overlaps another region in {:?}",
+                    *region.region_index(),
+                    column
+                )));
+            }
+            allocations.insert(allocated.clone());
+        }
+    }
+
+    Ok(column_allocations)
+}
+
 /// - `start` is the current start row of the region (not of this column).
 /// - `slack` is the maximum number of rows the start could be moved down, taking into
 ///   account prior columns.
@@ -241,3 +334,72 @@ pub fn slot_in_biggest_advice_first(
 
     (regions, column_allocations)
 }
+
+/// Returns the number of rows used by `column_allocations`.
+fn allocated_rows(column_allocations: &CircuitAllocations) -> usize {
+    column_allocations
+        .values()
+        .map(|a| a.unbounded_interval_start())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Sorts the regions in decreasing order of `key`, lays them out with the [`slot_in`]
+/// strategy, and returns their starts in the original order.
+fn slot_in_decreasing<K: Ord>(
+    region_shapes: Vec<RegionShape>,
+    key: impl Fn(&RegionShape) -> K,
+) -> (Vec<RegionStart>, CircuitAllocations) {
+    let mut sorted_regions = region_shapes;
+    // The sort is stable and `region_shapes` is sorted by region index, for determinism.
+    sorted_regions.sort_by_cached_key(key);
+    sorted_regions.reverse();
+
+    let (mut regions, column_allocations) = slot_in(sorted_regions);
+    regions.sort_unstable_by_key(|(_, region)| region.region_index().0);
+    let regions = regions.into_iter().map(|(start, _)| start).collect();
+
+    (regions, column_allocations)
+}
+
+/// Lays the regions out with the fewest rows found by first-fit decreasing.
+///
+/// No order of the regions is the best one for every circuit, so the regions are laid out
+/// with [`slot_in`] in several orders, and the layout with the fewest rows is kept, the
+/// first one on a tie:
+/// - the order of [`slot_in_biggest_advice_first`], so that the layout never has more
+///   rows than the one of the default strategy of [`V1`],
+/// - the order of the regions in the circuit, as the [`SimpleFloorPlanner`] lays them out,
+/// - the decreasing order of their areas, of their numbers of rows, and of their numbers
+///   of columns.
+///
+/// [`V1`]: super::V1
+/// [`SimpleFloorPlanner`]: crate::circuit::SimpleFloorPlanner
+pub fn pack(region_shapes: Vec<RegionShape>) -> (Vec<RegionStart>, CircuitAllocations) {
+    let mut best = slot_in_biggest_advice_first(region_shapes.clone());
+
+    let in_order = {
+        let (regions, column_allocations) = slot_in(region_shapes.clone());
+        let regions = regions.into_iter().map(|(start, _)| start).collect();
+        (regions, column_allocations)
+    };
+    let candidates = [
+        in_order,
+        slot_in_decreasing(region_shapes.clone(), |shape| {
+            shape.columns().len() * shape.row_count()
+        }),
+        slot_in_decreasing(region_shapes.clone(), |shape| {
+            (shape.row_count(), shape.columns().len())
+        }),
+        slot_in_decreasing(region_shapes, |shape| {
+            (shape.columns().len(), shape.row_count())
+        }),
+    ];
+    for candidate in candidates {
+        if allocated_rows(&candidate.1) < allocated_rows(&best.1) {
+            best = candidate;
+        }
+    }
+
+    best
+}
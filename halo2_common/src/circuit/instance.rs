@@ -0,0 +1,344 @@
+//! Canonical encodings of public inputs into instance values, and the gadgets decomposing
+//! them in a circuit.
+//!
+//! A value is encoded into field elements as follows:
+//!
+//! - a `u64` is the field element of the same integer value;
+//! - a byte string is split into chunks of [`bytes_per_element`] bytes, the last chunk
+//!   being shorter if the length of the string isn't a multiple of it, and each chunk is
+//!   the field element whose integer value is the chunk read in the given [`Endianness`];
+//! - an address is its 20 bytes read as a big-endian integer, as an Ethereum `uint160`.
+//!
+//! The chunks are smaller than the capacity of the field, so every encoding is injective
+//! and is checked in a circuit by [`InstanceDecompositionConfig`], which decomposes an
+//! instance value into range-checked bytes.
+//!
+//! Decoding relies on the canonical representation of the field being little-endian, as
+//! it is for the fields of `halo2curves`, and fails on other fields.
+
+use halo2_middleware::ff::PrimeField;
+use halo2_middleware::poly::Rotation;
+
+use super::typed::{AssignedByte, ByteConfig};
+use super::{AssignedCell, Cell, Layouter, Region, Value};
+use crate::plonk::{circuit::Column, ConstraintSystem, Error, Expression, Selector};
+use halo2_middleware::circuit::{Advice, Instance};
+
+/// The order in which the bytes of a chunk are read as an integer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    /// The first byte is the least significant.
+    Little,
+    /// The first byte is the most significant.
+    Big,
+}
+
+/// Returns the number of bytes encoded in each field element of a byte string, the
+/// largest number of bytes whose integer values all fit in the capacity of `F`.
+pub fn bytes_per_element<F: PrimeField>() -> usize {
+    (F::CAPACITY / 8) as usize
+}
+
+/// Encodes `value` into a field element.
+pub fn encode_u64<F: PrimeField>(value: u64) -> F {
+    F::from(value)
+}
+
+/// Encodes `bytes` into field elements, see the [module documentation](self).
+pub fn encode_bytes<F: PrimeField>(bytes: &[u8], endianness: Endianness) -> Vec<F> {
+    bytes
+        .chunks(bytes_per_element::<F>())
+        .map(|chunk| from_bytes(chunk, endianness))
+        .collect()
+}
+
+/// Encodes an Ethereum address into a field element, as a big-endian integer.
+///
+/// # Panics
+///
+/// Panics if the capacity of `F` is smaller than 160 bits.
+pub fn encode_address<F: PrimeField>(address: &[u8; 20]) -> F {
+    assert!(
+        bytes_per_element::<F>() >= 20,
+        "the field is too small to encode an address"
+    );
+    from_bytes(address, Endianness::Big)
+}
+
+/// Decodes a field element encoding a `u64`.
+///
+/// Returns an error if `element` is not in `0..2^64`.
+pub fn decode_u64<F: PrimeField>(element: &F) -> Result<u64, Error> {
+    let bytes = to_bytes(element, 8, Endianness::Little)
+        .ok_or_else(|| Error::Other("instance value is not a u64".to_string()))?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Decodes the `len` bytes encoded into `elements` by [`encode_bytes`].
+///
+/// Returns an error if `elements` is not the encoding of a string of `len` bytes.
+pub fn decode_bytes<F: PrimeField>(
+    elements: &[F],
+    len: usize,
+    endianness: Endianness,
+) -> Result<Vec<u8>, Error> {
+    let chunk = bytes_per_element::<F>();
+    if elements.len() != (len + chunk - 1) / chunk {
+        return Err(Error::Other(format!(
+            "{} instance values can't encode {} bytes",
+            elements.len(),
+            len
+        )));
+    }
+    let mut bytes = Vec::with_capacity(len);
+    for (index, element) in elements.iter().enumerate() {
+        let chunk_len = chunk.min(len - index * chunk);
+        bytes.extend(to_bytes(element, chunk_len, endianness).ok_or_else(|| {
+            Error::Other(format!(
+                "instance value {index} doesn't encode {chunk_len} bytes"
+            ))
+        })?);
+    }
+    Ok(bytes)
+}
+
+/// Decodes a field element encoding an Ethereum address.
+///
+/// Returns an error if `element` is not in `0..2^160`.
+pub fn decode_address<F: PrimeField>(element: &F) -> Result<[u8; 20], Error> {
+    let bytes = to_bytes(element, 20, Endianness::Big)
+        .ok_or_else(|| Error::Other("instance value is not an address".to_string()))?;
+    Ok(bytes.try_into().unwrap())
+}
+
+/// Returns the field element whose integer value is `bytes` read in `endianness`.
+fn from_bytes<F: PrimeField>(bytes: &[u8], endianness: Endianness) -> F {
+    let fold = |acc: F, byte: &u8| acc * F::from(256) + F::from(*byte as u64);
+    match endianness {
+        Endianness::Little => bytes.iter().rev().fold(F::ZERO, fold),
+        Endianness::Big => bytes.iter().fold(F::ZERO, fold),
+    }
+}
+
+/// Returns the `len` bytes whose integer value in `endianness` is `element`, or `None` if
+/// `element` is not in `0..2^(8 * len)`.
+fn to_bytes<F: PrimeField>(element: &F, len: usize, endianness: Endianness) -> Option<Vec<u8>> {
+    let repr = element.to_repr();
+    let repr = repr.as_ref();
+    if len > repr.len() || repr[len..].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    let mut bytes = repr[..len].to_vec();
+    if endianness == Endianness::Big {
+        bytes.reverse();
+    }
+    // Guards against fields whose representation isn't little-endian.
+    (from_bytes::<F>(&bytes, endianness) == *element).then_some(bytes)
+}
+
+/// Configuration for the decomposition of instance values into bytes.
+///
+/// A value of `n` bytes is decomposed on `n` rows: the bytes are assigned in the column of
+/// the [`ByteConfig`], from the most significant one, along with the running sum of the
+/// bytes read so far as a big-endian integer. The last running sum is constrained to be
+/// equal to the value. As `n` is at most [`bytes_per_element`], this proves that the value
+/// is the encoding of the bytes.
+#[derive(Clone, Copy, Debug)]
+pub struct InstanceDecompositionConfig {
+    bytes: ByteConfig,
+    sum: Column<Advice>,
+    q_first: Selector,
+    q_next: Selector,
+}
+
+impl InstanceDecompositionConfig {
+    /// Configures the decomposition of values copied into `sum` into the bytes of `bytes`.
+    ///
+    /// This enables equality on `sum`.
+    pub fn configure<F: PrimeField>(
+        meta: &mut ConstraintSystem<F>,
+        sum: Column<Advice>,
+        bytes: ByteConfig,
+    ) -> Self {
+        meta.enable_equality(sum);
+        let q_first = meta.selector();
+        let q_next = meta.selector();
+        meta.create_gate("instance decomposition", |meta| {
+            let q_first = meta.query_selector(q_first);
+            let q_next = meta.query_selector(q_next);
+            let byte = meta.query_advice(bytes.column(), Rotation::cur());
+            let sum_cur = meta.query_advice(sum, Rotation::cur());
+            let sum_prev = meta.query_advice(sum, Rotation::prev());
+            vec![
+                ("first byte", q_first * (sum_cur.clone() - byte.clone())),
+                (
+                    "next byte",
+                    q_next * (sum_cur - (sum_prev * Expression::Constant(F::from(256)) + byte)),
+                ),
+            ]
+        });
+
+        InstanceDecompositionConfig {
+            bytes,
+            sum,
+            q_first,
+            q_next,
+        }
+    }
+
+    /// Decomposes `value` into `len` bytes, returned in the order of `endianness`.
+    ///
+    /// Returns an error if `len` is zero or larger than [`bytes_per_element`].
+    pub fn decompose<F: PrimeField>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+        len: usize,
+        endianness: Endianness,
+    ) -> Result<Vec<AssignedByte<F>>, Error> {
+        layouter.assign_region(
+            || "instance decomposition",
+            |mut region| {
+                let (bytes, sum) =
+                    self.assign(&mut region, value.value().copied(), len, endianness)?;
+                region.constrain_equal(sum, value.cell())?;
+                Ok(bytes)
+            },
+        )
+    }
+
+    /// Decomposes the value of the instance column's cell at absolute location `row` into
+    /// `len` bytes, returned in the order of `endianness`.
+    ///
+    /// Returns an error if `len` is zero or larger than [`bytes_per_element`].
+    pub fn decompose_instance<F: PrimeField>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        instance: Column<Instance>,
+        row: usize,
+        len: usize,
+        endianness: Endianness,
+    ) -> Result<Vec<AssignedByte<F>>, Error> {
+        layouter.assign_region(
+            || "instance decomposition",
+            |mut region| {
+                let value = region.instance_value(instance, row)?;
+                let (bytes, sum) = self.assign(&mut region, value, len, endianness)?;
+                region.constrain_instance(sum, instance, row)?;
+                Ok(bytes)
+            },
+        )
+    }
+
+    /// Assigns the decomposition of `value` at the start of `region`, and returns the
+    /// bytes in the order of `endianness` and the last running sum.
+    fn assign<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        value: Value<F>,
+        len: usize,
+        endianness: Endianness,
+    ) -> Result<(Vec<AssignedByte<F>>, Cell), Error> {
+        if len == 0 || len > bytes_per_element::<F>() {
+            return Err(Error::Other(format!(
+                "can't decompose an instance value into {len} bytes"
+            )));
+        }
+        // A value out of range is decomposed into zeros, which fails the copy constraint.
+        let be_bytes = value.map(|value| to_bytes(&value, len, Endianness::Big));
+
+        let mut bytes = Vec::with_capacity(len);
+        let mut sum = Value::known(F::ZERO);
+        let mut sum_cell = None;
+        for offset in 0..len {
+            if offset == 0 {
+                self.q_first.enable(region, offset)?;
+            } else {
+                self.q_next.enable(region, offset)?;
+            }
+            let byte = be_bytes
+                .as_ref()
+                .map(|bytes| bytes.as_ref().map_or(0, |bytes| bytes[offset]));
+            bytes.push(self.bytes.assign(region, offset, byte)?);
+            sum = sum
+                .zip(byte)
+                .map(|(sum, byte)| sum * F::from(256) + F::from(byte as u64));
+            sum_cell = Some(
+                region
+                    .assign_advice(|| "sum", self.sum, offset, || sum)?
+                    .cell(),
+            );
+        }
+        if endianness == Endianness::Little {
+            bytes.reverse();
+        }
+        Ok((bytes, sum_cell.unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        bytes_per_element, decode_address, decode_bytes, decode_u64, encode_address, encode_bytes,
+        encode_u64, Endianness, InstanceDecompositionConfig,
+    };
+    use crate::circuit::typed::ByteConfig;
+    use crate::plonk::ConstraintSystem;
+    use halo2_middleware::ff::Field;
+    use halo2curves::pasta::Fp;
+
+    #[test]
+    fn encodings() {
+        assert_eq!(bytes_per_element::<Fp>(), 31);
+        assert_eq!(encode_u64::<Fp>(258), Fp::from(258));
+        assert_eq!(decode_u64(&Fp::from(u64::MAX)).unwrap(), u64::MAX);
+        assert!(decode_u64(&(Fp::from(u64::MAX) + Fp::ONE)).is_err());
+
+        assert_eq!(
+            encode_bytes::<Fp>(&[1, 2], Endianness::Little),
+            vec![Fp::from(0x0201)]
+        );
+        assert_eq!(
+            encode_bytes::<Fp>(&[1, 2], Endianness::Big),
+            vec![Fp::from(0x0102)]
+        );
+
+        let mut address = [0; 20];
+        address[0] = 1;
+        address[19] = 2;
+        let element = encode_address::<Fp>(&address);
+        assert_eq!(element, Fp::from(2) + Fp::from(2).pow([152]));
+        assert_eq!(decode_address(&element).unwrap(), address);
+        assert!(decode_address(&Fp::from(2).pow([160])).is_err());
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let bytes: Vec<u8> = (0..70).collect();
+        for endianness in [Endianness::Little, Endianness::Big] {
+            let elements = encode_bytes::<Fp>(&bytes, endianness);
+            assert_eq!(elements.len(), 3);
+            assert_eq!(decode_bytes(&elements, 70, endianness).unwrap(), bytes);
+            assert!(decode_bytes(&elements, 100, endianness).is_err());
+            // The last chunk only holds 8 bytes.
+            assert!(decode_bytes(&elements, 65, endianness).is_err());
+        }
+    }
+
+    #[test]
+    fn configure() {
+        let mut meta = ConstraintSystem::<Fp>::default();
+        let byte = meta.advice_column();
+        let sum = meta.advice_column();
+        let table = meta.lookup_table_column();
+
+        let bytes = ByteConfig::configure(&mut meta, byte, table);
+        InstanceDecompositionConfig::configure(&mut meta, sum, bytes);
+
+        assert_eq!(meta.gates().len(), 2);
+        assert_eq!(meta.gates()[1].name(), "instance decomposition");
+        assert_eq!(meta.gates()[1].polynomials().len(), 2);
+        assert_eq!(meta.lookups().len(), 1);
+        assert_eq!(meta.num_selectors(), 4);
+    }
+}
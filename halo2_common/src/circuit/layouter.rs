@@ -13,17 +13,21 @@ use crate::plonk::{circuit::Column, Error, Selector};
 use halo2_middleware::circuit::{Advice, Any, Fixed, Instance};
 
 /// Intermediate trait requirements for [`RegionLayouter`] when thread-safe regions are enabled.
-#[cfg(feature = "thread-safe-region")]
+#[cfg(all(feature = "thread-safe-region", not(target_arch = "wasm32")))]
 pub trait SyncDeps: Send + Sync {}
 
-#[cfg(feature = "thread-safe-region")]
+#[cfg(all(feature = "thread-safe-region", not(target_arch = "wasm32")))]
 impl<T: Send + Sync> SyncDeps for T {}
 
 /// Intermediate trait requirements for [`RegionLayouter`].
-#[cfg(not(feature = "thread-safe-region"))]
+///
+/// On `wasm32` targets, which are single-threaded, there are no requirements even if the
+/// `thread-safe-region` feature is enabled by another crate of the dependency graph, so
+/// that layouters, assignments and chips there may be built on `Rc` and `RefCell`.
+#[cfg(not(all(feature = "thread-safe-region", not(target_arch = "wasm32"))))]
 pub trait SyncDeps {}
 
-#[cfg(not(feature = "thread-safe-region"))]
+#[cfg(not(all(feature = "thread-safe-region", not(target_arch = "wasm32"))))]
 impl<T> SyncDeps for T {}
 
 /// Helper trait for implementing a custom [`Layouter`].
@@ -65,6 +69,22 @@ pub trait RegionLayouter<F: Field>: fmt::Debug + SyncDeps {
         offset: usize,
     ) -> Result<(), Error>;
 
+    /// Enables a selector at the `len` offsets starting at `offset`.
+    ///
+    /// By default, this enables the selector at each offset in turn.
+    fn enable_selector_range<'v>(
+        &'v mut self,
+        annotation: &'v (dyn Fn() -> String + 'v),
+        selector: &Selector,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), Error> {
+        for offset in offset..offset + len {
+            self.enable_selector(annotation, selector, offset)?;
+        }
+        Ok(())
+    }
+
     /// Allows the circuit implementor to name/annotate a Column within a Region context.
     ///
     /// This is useful in order to improve the amount of information that `prover.verify()`
@@ -84,6 +104,26 @@ pub trait RegionLayouter<F: Field>: fmt::Debug + SyncDeps {
         to: &'v mut (dyn FnMut() -> Value<Assigned<F>> + 'v),
     ) -> Result<Cell, Error>;
 
+    /// Assigns the `len` cells of an advice column starting at `offset`, from a slice of
+    /// values.
+    ///
+    /// By default, this assigns each cell in turn.
+    fn assign_advice_column_slice<'v>(
+        &'v mut self,
+        annotation: &'v (dyn Fn() -> String + 'v),
+        column: Column<Advice>,
+        offset: usize,
+        len: usize,
+        values: Value<&[Assigned<F>]>,
+    ) -> Result<(), Error> {
+        for i in 0..len {
+            self.assign_advice(annotation, column, offset + i, &mut || {
+                values.map(|values| values[i])
+            })?;
+        }
+        Ok(())
+    }
+
     /// Assigns a constant value to the column `advice` at `offset` within this region.
     ///
     /// The constant value will be assigned to a cell within one of the fixed columns
@@ -116,6 +156,13 @@ pub trait RegionLayouter<F: Field>: fmt::Debug + SyncDeps {
     fn instance_value(&mut self, instance: Column<Instance>, row: usize)
         -> Result<Value<F>, Error>;
 
+    /// Returns the value of the advice column's cell at `offset` within this region, if it
+    /// was assigned in an earlier phase and the backend kept it. By default, the value is
+    /// unknown.
+    fn advice_value(&mut self, _column: Column<Advice>, _offset: usize) -> Result<Value<F>, Error> {
+        Ok(Value::unknown())
+    }
+
     /// Assigns a fixed value
     fn assign_fixed<'v>(
         &'v mut self,
@@ -134,15 +181,31 @@ pub trait RegionLayouter<F: Field>: fmt::Debug + SyncDeps {
     ///
     /// Returns an error if either of the cells is not within the given permutation.
     fn constrain_equal(&mut self, left: Cell, right: Cell) -> Result<(), Error>;
+
+    /// Constrains a cell to have the same value as the instance column's cell at absolute
+    /// location `row`.
+    ///
+    /// Returns an error if either of the cells is not within the given permutation.
+    fn constrain_instance(
+        &mut self,
+        cell: Cell,
+        instance: Column<Instance>,
+        row: usize,
+    ) -> Result<(), Error>;
 }
 
 /// The shape of a region. For a region at a certain index, we track
 /// the set of columns it uses as well as the number of rows it uses.
+/// The selectors enabled in the region are also recorded, so that floor planners
+/// can account for the cells their gates query at other rotations.
+///
+/// Since the selectors are private, a `RegionShape` is created with [`RegionShape::new`].
 #[derive(Clone, Debug)]
 pub struct RegionShape {
     pub region_index: RegionIndex,
     pub columns: HashSet<RegionColumn>,
     pub row_count: usize,
+    enabled_selectors: Vec<(Selector, usize)>,
 }
 
 /// The virtual column involved in a region. This includes concrete columns,
@@ -191,6 +254,7 @@ impl RegionShape {
             region_index,
             columns: HashSet::default(),
             row_count: 0,
+            enabled_selectors: vec![],
         }
     }
 
@@ -208,6 +272,11 @@ impl RegionShape {
     pub fn row_count(&self) -> usize {
         self.row_count
     }
+
+    /// Get the selectors enabled in a `RegionShape`, with the offsets they are enabled at.
+    pub fn enabled_selectors(&self) -> &[(Selector, usize)] {
+        &self.enabled_selectors
+    }
 }
 
 impl<F: Field> RegionLayouter<F> for RegionShape {
@@ -219,10 +288,28 @@ impl<F: Field> RegionLayouter<F> for RegionShape {
     ) -> Result<(), Error> {
         // Track the selector's fixed column as part of the region's shape.
         self.columns.insert((*selector).into());
+        self.enabled_selectors.push((*selector, offset));
         self.row_count = cmp::max(self.row_count, offset + 1);
         Ok(())
     }
 
+    fn enable_selector_range<'v>(
+        &'v mut self,
+        _: &'v (dyn Fn() -> String + 'v),
+        selector: &Selector,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), Error> {
+        if len == 0 {
+            return Ok(());
+        }
+        self.columns.insert((*selector).into());
+        self.enabled_selectors
+            .extend((offset..offset + len).map(|offset| (*selector, offset)));
+        self.row_count = cmp::max(self.row_count, offset + len);
+        Ok(())
+    }
+
     fn assign_advice<'v>(
         &'v mut self,
         _: &'v (dyn Fn() -> String + 'v),
@@ -240,6 +327,21 @@ impl<F: Field> RegionLayouter<F> for RegionShape {
         })
     }
 
+    fn assign_advice_column_slice<'v>(
+        &'v mut self,
+        _: &'v (dyn Fn() -> String + 'v),
+        column: Column<Advice>,
+        offset: usize,
+        len: usize,
+        _: Value<&[Assigned<F>]>,
+    ) -> Result<(), Error> {
+        if len > 0 {
+            self.columns.insert(Column::<Any>::from(column).into());
+            self.row_count = cmp::max(self.row_count, offset + len);
+        }
+        Ok(())
+    }
+
     fn assign_advice_from_constant<'v>(
         &'v mut self,
         annotation: &'v (dyn Fn() -> String + 'v),
@@ -314,4 +416,14 @@ impl<F: Field> RegionLayouter<F> for RegionShape {
         // Equality constraints don't affect the region shape.
         Ok(())
     }
+
+    fn constrain_instance(
+        &mut self,
+        _cell: Cell,
+        _instance: Column<Instance>,
+        _row: usize,
+    ) -> Result<(), Error> {
+        // Equality constraints don't affect the region shape.
+        Ok(())
+    }
 }
@@ -0,0 +1,229 @@
+//! Lookup tables packed in shared fixed columns.
+//!
+//! Every [`TableColumn`] is a fixed column that is filled up to the last usable row, so a
+//! circuit with several small lookup tables wastes most of the rows of their columns.
+//! [`PackedTables`] instead stacks the tables vertically in the same columns, and adds a
+//! tag column that identifies the table each row belongs to. A lookup into a packed table
+//! matches the tag of the table along with its values, so it can't be satisfied by the
+//! rows of another table.
+//!
+//! The first row of the packed columns is all zeros, with the tag `0` that no table uses.
+//! It is the row that disabled lookups (whose selector is zero) match, and the value the
+//! columns are filled with after the last table.
+//!
+//! ```ignore
+//! let mut tables = PackedTables::configure(meta, 2);
+//! let bytes = tables.add_table(1);
+//! let xor = tables.add_table(2);
+//! tables.lookup(meta, "byte", bytes, |meta| {
+//!     let q = meta.query_selector(q_byte);
+//!     (q, vec![meta.query_advice(byte, Rotation::cur())])
+//! });
+//!
+//! // In `synthesize`:
+//! tables.load(&mut layouter, &[(bytes, byte_rows), (xor, xor_rows)])?;
+//! ```
+
+use halo2_middleware::ff::PrimeField;
+
+use super::{Layouter, Value};
+use crate::plonk::{ConstraintSystem, Error, Expression, TableColumn, VirtualCells};
+
+/// A table of [`PackedTables`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedTable {
+    tag: u64,
+    arity: usize,
+}
+
+impl PackedTable {
+    /// Returns the tag of the rows of this table.
+    pub fn tag(&self) -> u64 {
+        self.tag
+    }
+
+    /// Returns the number of values in each row of this table.
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+}
+
+/// Lookup tables sharing the same fixed columns, see the [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct PackedTables {
+    tag: TableColumn,
+    columns: Vec<TableColumn>,
+    num_tables: u64,
+}
+
+impl PackedTables {
+    /// Configures packed tables of at most `width` values per row.
+    pub fn configure<F: PrimeField>(meta: &mut ConstraintSystem<F>, width: usize) -> Self {
+        PackedTables {
+            tag: meta.lookup_table_column(),
+            columns: (0..width).map(|_| meta.lookup_table_column()).collect(),
+            num_tables: 0,
+        }
+    }
+
+    /// Adds a table with `arity` values per row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `arity` is larger than the width of the packed tables.
+    pub fn add_table(&mut self, arity: usize) -> PackedTable {
+        assert!(
+            arity <= self.columns.len(),
+            "table arity exceeds the width of the packed tables"
+        );
+        self.num_tables += 1;
+        PackedTable {
+            tag: self.num_tables,
+            arity,
+        }
+    }
+
+    /// Adds a lookup of input expressions into `table`.
+    ///
+    /// `inputs` returns the selector of the lookup, which must be boolean, and one input
+    /// expression per value of the table. The inputs are multiplied by the selector, as
+    /// is the tag of the table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of inputs doesn't match the arity of `table`.
+    pub fn lookup<F: PrimeField, S: AsRef<str>>(
+        &self,
+        meta: &mut ConstraintSystem<F>,
+        name: S,
+        table: PackedTable,
+        inputs: impl FnOnce(&mut VirtualCells<'_, F>) -> (Expression<F>, Vec<Expression<F>>),
+    ) -> usize {
+        meta.lookup(name, |meta| {
+            let (selector, inputs) = inputs(meta);
+            assert_eq!(
+                inputs.len(),
+                table.arity,
+                "the number of inputs must match the arity of the table"
+            );
+            let tag = selector.clone() * Expression::Constant(F::from(table.tag));
+            let padding = self.columns.len() - inputs.len();
+            std::iter::once((tag, self.tag))
+                .chain(
+                    inputs
+                        .into_iter()
+                        .map(|input| selector.clone() * input)
+                        .chain(std::iter::repeat(Expression::Constant(F::ZERO)).take(padding))
+                        .zip(self.columns.iter().copied()),
+                )
+                .collect()
+        })
+    }
+
+    /// Loads the rows of every table, each row holding the values of a table entry.
+    ///
+    /// This uses a single [`Layouter::assign_table`], so every table added with
+    /// [`PackedTables::add_table`] must be loaded by the same call, exactly once.
+    pub fn load<F: PrimeField>(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        tables: &[(PackedTable, Vec<Vec<F>>)],
+    ) -> Result<(), Error> {
+        let mut loaded = vec![false; self.num_tables as usize];
+        for (table, rows) in tables {
+            let index = (table.tag - 1) as usize;
+            if index >= loaded.len() || loaded[index] {
+                return Err(Error::Other(format!(
+                    "packed table {} is unknown or loaded twice",
+                    table.tag
+                )));
+            }
+            loaded[index] = true;
+            if rows.iter().any(|row| row.len() != table.arity) {
+                return Err(Error::Other(format!(
+                    "rows of packed table {} must have {} values",
+                    table.tag, table.arity
+                )));
+            }
+        }
+        if loaded.iter().any(|loaded| !loaded) {
+            return Err(Error::Other(
+                "every packed table must be loaded".to_string(),
+            ));
+        }
+
+        layouter.assign_table(
+            || "packed tables",
+            |mut assignment| {
+                let mut offset = 0;
+                let mut assign_row = |tag: u64, values: &[F]| {
+                    assignment.assign_cell(
+                        || "tag",
+                        self.tag,
+                        offset,
+                        || Value::known(F::from(tag)),
+                    )?;
+                    for (index, column) in self.columns.iter().enumerate() {
+                        let value = values.get(index).copied().unwrap_or(F::ZERO);
+                        assignment.assign_cell(
+                            || "value",
+                            *column,
+                            offset,
+                            || Value::known(value),
+                        )?;
+                    }
+                    offset += 1;
+                    Ok::<_, Error>(())
+                };
+
+                assign_row(0, &[])?;
+                for (table, rows) in tables {
+                    for row in rows {
+                        assign_row(table.tag, row)?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PackedTables;
+    use crate::plonk::ConstraintSystem;
+    use halo2_middleware::poly::Rotation;
+    use halo2curves::pasta::Fp;
+
+    #[test]
+    fn configure() {
+        let mut meta = ConstraintSystem::<Fp>::default();
+        let a = meta.advice_column();
+        let b = meta.advice_column();
+        let q = meta.complex_selector();
+
+        let mut tables = PackedTables::configure(&mut meta, 2);
+        let single = tables.add_table(1);
+        let pair = tables.add_table(2);
+        assert_eq!((single.tag(), pair.tag()), (1, 2));
+
+        tables.lookup(&mut meta, "single", single, |meta| {
+            let q = meta.query_selector(q);
+            (q, vec![meta.query_advice(a, Rotation::cur())])
+        });
+        tables.lookup(&mut meta, "pair", pair, |meta| {
+            let q = meta.query_selector(q);
+            let a = meta.query_advice(a, Rotation::cur());
+            let b = meta.query_advice(b, Rotation::cur());
+            (q, vec![a, b])
+        });
+
+        // Three fixed columns are shared by both lookups.
+        assert_eq!(meta.num_fixed_columns(), 3);
+        assert_eq!(meta.lookups().len(), 2);
+        for lookup in meta.lookups() {
+            assert_eq!(lookup.input_expressions().len(), 3);
+            assert_eq!(lookup.table_expressions().len(), 3);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Source locations of cell assignments, tracked when the `cell-provenance` feature is
+//! enabled.
+//!
+//! The assignment methods of [`Region`](super::Region) and [`Table`](super::Table) are
+//! `#[track_caller]` under this feature, and record their caller while the assignment is
+//! being made. Backends that want to keep it, such as `MockProver`, read it with
+//! [`caller`] from their `Assignment` implementation.
+//!
+//! Only the location of the outermost call is recorded: assignments made by a gadget
+//! point at the gadget's code, unless its own methods are `#[track_caller]`.
+
+use std::cell::Cell;
+use std::panic::Location;
+
+thread_local! {
+    static CALLER: Cell<Option<&'static Location<'static>>> = Cell::new(None);
+}
+
+/// Records a location as the caller of the assignment in progress, until dropped.
+pub(crate) struct CallerGuard(Option<&'static Location<'static>>);
+
+impl CallerGuard {
+    pub(crate) fn new(location: &'static Location<'static>) -> Self {
+        CallerGuard(CALLER.with(|caller| caller.replace(Some(location))))
+    }
+}
+
+impl Drop for CallerGuard {
+    fn drop(&mut self) {
+        CALLER.with(|caller| caller.set(self.0));
+    }
+}
+
+/// Returns the location of the `Region` or `Table` method call that made the assignment
+/// in progress, if any.
+pub fn caller() -> Option<&'static Location<'static>> {
+    CALLER.with(|caller| caller.get())
+}
@@ -0,0 +1,548 @@
+//! Typed wrappers over [`AssignedCell`] for bits, bytes and 64-bit words.
+//!
+//! The wrappers can only be constructed through the methods of their config, which
+//! enforce the corresponding range constraint on the cell. The constraint is enforced
+//! exactly once: copying a typed cell with `copy_advice` relies on the equality constraint
+//! instead of range-checking the copy again.
+//!
+//! The operations on typed cells are methods of their config, which copy the operands
+//! into the config's column and constrain the result with a gate. The results of the
+//! operations on bits are boolean whenever the operands are, so they are not
+//! range-checked again, while the results of the operations on bytes and words are.
+
+use halo2_middleware::ff::PrimeField;
+use halo2_middleware::poly::Rotation;
+
+use super::{AssignedCell, Cell, Layouter, Region, Value};
+use crate::plonk::{circuit::Column, ConstraintSystem, Error, Expression, Selector, TableColumn};
+use halo2_middleware::circuit::Advice;
+
+/// Configuration for boolean-constrained cells.
+#[derive(Clone, Copy, Debug)]
+pub struct BitConfig {
+    column: Column<Advice>,
+    q_bit: Selector,
+    q_not: Selector,
+    q_and: Selector,
+    q_or: Selector,
+    q_xor: Selector,
+}
+
+impl BitConfig {
+    /// Configures a boolean constraint, and the operations on bits, on `column`.
+    ///
+    /// This enables equality on `column`.
+    pub fn configure<F: PrimeField>(
+        meta: &mut ConstraintSystem<F>,
+        column: Column<Advice>,
+    ) -> Self {
+        meta.enable_equality(column);
+        let q_bit = meta.selector();
+        let q_not = meta.selector();
+        let q_and = meta.selector();
+        let q_or = meta.selector();
+        let q_xor = meta.selector();
+        meta.create_gate("bit", |meta| {
+            let q_bit = meta.query_selector(q_bit);
+            let v = meta.query_advice(column, Rotation::cur());
+            vec![(
+                "bit",
+                q_bit * v.clone() * (Expression::Constant(F::ONE) - v),
+            )]
+        });
+        meta.create_gate("bit operations", |meta| {
+            let q_not = meta.query_selector(q_not);
+            let q_and = meta.query_selector(q_and);
+            let q_or = meta.query_selector(q_or);
+            let q_xor = meta.query_selector(q_xor);
+            let a = meta.query_advice(column, Rotation::cur());
+            let b = meta.query_advice(column, Rotation::next());
+            // The result of `not` follows its operand, the others follow their two operands.
+            let not = b.clone();
+            let result = meta.query_advice(column, Rotation(2));
+            let ab = a.clone() * b.clone();
+            vec![
+                (
+                    "not",
+                    q_not * (not - (Expression::Constant(F::ONE) - a.clone())),
+                ),
+                ("and", q_and * (result.clone() - ab.clone())),
+                (
+                    "or",
+                    q_or * (result.clone() - (a.clone() + b.clone() - ab.clone())),
+                ),
+                (
+                    "xor",
+                    q_xor * (result - (a + b - ab * Expression::Constant(F::from(2)))),
+                ),
+            ]
+        });
+
+        BitConfig {
+            column,
+            q_bit,
+            q_not,
+            q_and,
+            q_or,
+            q_xor,
+        }
+    }
+
+    /// Assigns `value` at `offset` and constrains it to be boolean.
+    pub fn assign<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        value: Value<bool>,
+    ) -> Result<AssignedBit<F>, Error> {
+        self.q_bit.enable(region, offset)?;
+        let cell = region.assign_advice(
+            || "bit",
+            self.column,
+            offset,
+            || value.map(|b| F::from(b as u64)),
+        )?;
+        Ok(AssignedBit { cell, value })
+    }
+
+    /// Assigns the negation of `a`.
+    ///
+    /// This uses 2 rows of the column, starting at `offset`.
+    pub fn not<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        a: &AssignedBit<F>,
+    ) -> Result<AssignedBit<F>, Error> {
+        self.q_not.enable(region, offset)?;
+        a.copy_advice(|| "a", region, self.column, offset)?;
+        let value = a.value.map(|a| !a);
+        let cell = region.assign_advice(
+            || "not",
+            self.column,
+            offset + 1,
+            || value.map(|b| F::from(b as u64)),
+        )?;
+        Ok(AssignedBit { cell, value })
+    }
+
+    /// Assigns the conjunction of `a` and `b`.
+    ///
+    /// This uses 3 rows of the column, starting at `offset`.
+    pub fn and<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        a: &AssignedBit<F>,
+        b: &AssignedBit<F>,
+    ) -> Result<AssignedBit<F>, Error> {
+        self.binary(region, offset, self.q_and, a, b, |a, b| a & b)
+    }
+
+    /// Assigns the disjunction of `a` and `b`.
+    ///
+    /// This uses 3 rows of the column, starting at `offset`.
+    pub fn or<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        a: &AssignedBit<F>,
+        b: &AssignedBit<F>,
+    ) -> Result<AssignedBit<F>, Error> {
+        self.binary(region, offset, self.q_or, a, b, |a, b| a | b)
+    }
+
+    /// Assigns the exclusive disjunction of `a` and `b`.
+    ///
+    /// This uses 3 rows of the column, starting at `offset`.
+    pub fn xor<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        a: &AssignedBit<F>,
+        b: &AssignedBit<F>,
+    ) -> Result<AssignedBit<F>, Error> {
+        self.binary(region, offset, self.q_xor, a, b, |a, b| a ^ b)
+    }
+
+    /// Copies `a` and `b` at `offset` and `offset + 1`, and assigns `op(a, b)` after them,
+    /// constrained by the gate of `selector`.
+    fn binary<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        selector: Selector,
+        a: &AssignedBit<F>,
+        b: &AssignedBit<F>,
+        op: fn(bool, bool) -> bool,
+    ) -> Result<AssignedBit<F>, Error> {
+        selector.enable(region, offset)?;
+        a.copy_advice(|| "a", region, self.column, offset)?;
+        b.copy_advice(|| "b", region, self.column, offset + 1)?;
+        let value = a.value.zip(b.value).map(|(a, b)| op(a, b));
+        let cell = region.assign_advice(
+            || "result",
+            self.column,
+            offset + 2,
+            || value.map(|b| F::from(b as u64)),
+        )?;
+        Ok(AssignedBit { cell, value })
+    }
+}
+
+/// Configuration for cells constrained to `0..256` through a lookup table.
+#[derive(Clone, Copy, Debug)]
+pub struct ByteConfig {
+    column: Column<Advice>,
+    q_byte: Selector,
+    q_add: Selector,
+    table: TableColumn,
+}
+
+impl ByteConfig {
+    /// Configures a lookup of `column` into `table`, and the operations on bytes on
+    /// `column`.
+    ///
+    /// `table` must be loaded with [`ByteConfig::load_table`]. This enables equality on
+    /// `column`.
+    pub fn configure<F: PrimeField>(
+        meta: &mut ConstraintSystem<F>,
+        column: Column<Advice>,
+        table: TableColumn,
+    ) -> Self {
+        meta.enable_equality(column);
+        let q_byte = meta.complex_selector();
+        let q_add = meta.selector();
+        meta.lookup("byte", |meta| {
+            let q_byte = meta.query_selector(q_byte);
+            let v = meta.query_advice(column, Rotation::cur());
+            vec![(q_byte * v, table)]
+        });
+        meta.create_gate("byte wrapping add", |meta| {
+            let q_add = meta.query_selector(q_add);
+            let a = meta.query_advice(column, Rotation::cur());
+            let b = meta.query_advice(column, Rotation::next());
+            let sum = meta.query_advice(column, Rotation(2));
+            let carry = meta.query_advice(column, Rotation(3));
+            vec![
+                (
+                    "sum",
+                    q_add.clone()
+                        * (a + b - sum - carry.clone() * Expression::Constant(F::from(256))),
+                ),
+                (
+                    "carry",
+                    q_add * carry.clone() * (Expression::Constant(F::ONE) - carry),
+                ),
+            ]
+        });
+
+        ByteConfig {
+            column,
+            q_byte,
+            q_add,
+            table,
+        }
+    }
+
+    /// Returns the column of the bytes.
+    pub(crate) fn column(&self) -> Column<Advice> {
+        self.column
+    }
+
+    /// Loads the `0..256` table.
+    pub fn load_table<F: PrimeField>(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "byte table",
+            |mut table| {
+                for byte in 0..256 {
+                    table.assign_cell(
+                        || "byte",
+                        self.table,
+                        byte,
+                        || Value::known(F::from(byte as u64)),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Assigns `value` at `offset` and constrains it to be a byte.
+    pub fn assign<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        value: Value<u8>,
+    ) -> Result<AssignedByte<F>, Error> {
+        self.q_byte.enable(region, offset)?;
+        let cell = region.assign_advice(
+            || "byte",
+            self.column,
+            offset,
+            || value.map(|b| F::from(b as u64)),
+        )?;
+        Ok(AssignedByte { cell, value })
+    }
+
+    /// Assigns the sum of `a` and `b`, modulo 256.
+    ///
+    /// This uses 4 rows of the column, starting at `offset`: the operands, the sum, which
+    /// is range-checked, and the boolean carry.
+    pub fn wrapping_add<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        a: &AssignedByte<F>,
+        b: &AssignedByte<F>,
+    ) -> Result<AssignedByte<F>, Error> {
+        self.q_add.enable(region, offset)?;
+        a.copy_advice(|| "a", region, self.column, offset)?;
+        b.copy_advice(|| "b", region, self.column, offset + 1)?;
+        let sum = a.value.zip(b.value).map(|(a, b)| a as u64 + b as u64);
+        let result = self.assign(region, offset + 2, sum.map(|sum| sum as u8))?;
+        region.assign_advice(
+            || "carry",
+            self.column,
+            offset + 3,
+            || sum.map(|sum| F::from(sum >> 8)),
+        )?;
+        Ok(result)
+    }
+}
+
+/// Configuration for cells constrained to `0..2^64` through a little-endian byte
+/// decomposition.
+///
+/// The bytes are assigned in the column of the [`ByteConfig`] on the 8 rows starting at
+/// the offset of the word.
+#[derive(Clone, Copy, Debug)]
+pub struct U64Config {
+    column: Column<Advice>,
+    bytes: ByteConfig,
+    q_decompose: Selector,
+    q_add: Selector,
+    q_mul: Selector,
+}
+
+impl U64Config {
+    /// Configures the decomposition of `column` into the bytes of `bytes`, and the
+    /// operations on words on `column`.
+    ///
+    /// This enables equality on `column`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the modulus of `F` is smaller than `2^128`, as the product of two words
+    /// would wrap around it.
+    pub fn configure<F: PrimeField>(
+        meta: &mut ConstraintSystem<F>,
+        column: Column<Advice>,
+        bytes: ByteConfig,
+    ) -> Self {
+        assert!(
+            F::NUM_BITS > 128,
+            "the field is too small for the operations on words"
+        );
+        meta.enable_equality(column);
+        let q_decompose = meta.selector();
+        let q_add = meta.selector();
+        let q_mul = meta.selector();
+        meta.create_gate("u64 decomposition", |meta| {
+            let q_decompose = meta.query_selector(q_decompose);
+            let word = meta.query_advice(column, Rotation::cur());
+            let recomposed = (0..8).rev().fold(Expression::Constant(F::ZERO), |acc, i| {
+                acc * Expression::Constant(F::from(256))
+                    + meta.query_advice(bytes.column, Rotation(i))
+            });
+            vec![("u64 decomposition", q_decompose * (word - recomposed))]
+        });
+        meta.create_gate("u64 operations", |meta| {
+            let q_add = meta.query_selector(q_add);
+            let q_mul = meta.query_selector(q_mul);
+            let low = meta.query_advice(column, Rotation::cur());
+            let a = meta.query_advice(column, Rotation(1));
+            let b = meta.query_advice(column, Rotation(2));
+            let high = meta.query_advice(column, Rotation(8));
+            // Both halves are words, so the result is below 2^128 and doesn't wrap around.
+            let result = low + high * Expression::Constant(F::from_u128(1 << 64));
+            vec![
+                (
+                    "wrapping add",
+                    q_add * (a.clone() + b.clone() - result.clone()),
+                ),
+                ("wrapping mul", q_mul * (a * b - result)),
+            ]
+        });
+
+        U64Config {
+            column,
+            bytes,
+            q_decompose,
+            q_add,
+            q_mul,
+        }
+    }
+
+    /// Assigns `value` at `offset` and constrains it to be a 64-bit word.
+    ///
+    /// This uses 8 rows of the byte column, starting at `offset`.
+    pub fn assign<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        value: Value<u64>,
+    ) -> Result<AssignedU64<F>, Error> {
+        self.q_decompose.enable(region, offset)?;
+        for i in 0..8 {
+            self.bytes
+                .assign(region, offset + i, value.map(|v| (v >> (8 * i)) as u8))?;
+        }
+        let cell = region.assign_advice(|| "u64", self.column, offset, || value.map(F::from))?;
+        Ok(AssignedU64 { cell, value })
+    }
+
+    /// Assigns the sum of `a` and `b`, modulo `2^64`.
+    ///
+    /// This uses 9 rows of the column and 16 rows of the byte column, starting at
+    /// `offset`, see [`U64Config::wrapping_mul`].
+    pub fn wrapping_add<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        a: &AssignedU64<F>,
+        b: &AssignedU64<F>,
+    ) -> Result<AssignedU64<F>, Error> {
+        self.q_add.enable(region, offset)?;
+        self.operation(region, offset, a, b, |a, b| a as u128 + b as u128)
+    }
+
+    /// Assigns the product of `a` and `b`, modulo `2^64`.
+    ///
+    /// This uses 9 rows of the column and 16 rows of the byte column, starting at
+    /// `offset`: the result is assigned at `offset` and the operands after it, and the
+    /// high word of the full product, which is range-checked as well, at `offset + 8`.
+    pub fn wrapping_mul<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        a: &AssignedU64<F>,
+        b: &AssignedU64<F>,
+    ) -> Result<AssignedU64<F>, Error> {
+        self.q_mul.enable(region, offset)?;
+        self.operation(region, offset, a, b, |a, b| a as u128 * b as u128)
+    }
+
+    /// Assigns the low and high words of `op(a, b)` and copies `a` and `b`, in the layout
+    /// of [`U64Config::wrapping_mul`].
+    fn operation<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        a: &AssignedU64<F>,
+        b: &AssignedU64<F>,
+        op: fn(u64, u64) -> u128,
+    ) -> Result<AssignedU64<F>, Error> {
+        let full = a.value.zip(b.value).map(|(a, b)| op(a, b));
+        let low = self.assign(region, offset, full.map(|full| full as u64))?;
+        a.copy_advice(|| "a", region, self.column, offset + 1)?;
+        b.copy_advice(|| "b", region, self.column, offset + 2)?;
+        self.assign(region, offset + 8, full.map(|full| (full >> 64) as u64))?;
+        Ok(low)
+    }
+}
+
+macro_rules! typed_cell {
+    ($name:ident, $ty:ty, $doc:literal) => {
+        #[doc = $doc]
+        #[derive(Clone, Debug)]
+        pub struct $name<F: PrimeField> {
+            cell: AssignedCell<F, F>,
+            value: Value<$ty>,
+        }
+
+        impl<F: PrimeField> $name<F> {
+            /// Returns the value of this cell.
+            pub fn value(&self) -> Value<$ty> {
+                self.value
+            }
+
+            /// Returns the cell.
+            pub fn cell(&self) -> Cell {
+                self.cell.cell()
+            }
+
+            /// Returns the underlying [`AssignedCell`].
+            pub fn inner(&self) -> &AssignedCell<F, F> {
+                &self.cell
+            }
+
+            /// Copies this cell to the given advice cell and constrains them to be equal.
+            ///
+            /// The copy is not range-checked again, as the equality constraint already
+            /// implies it is in range.
+            pub fn copy_advice<A, AR>(
+                &self,
+                annotation: A,
+                region: &mut Region<'_, F>,
+                column: Column<Advice>,
+                offset: usize,
+            ) -> Result<Self, Error>
+            where
+                A: Fn() -> AR,
+                AR: Into<String>,
+            {
+                Ok($name {
+                    cell: self.cell.copy_advice(annotation, region, column, offset)?,
+                    value: self.value,
+                })
+            }
+        }
+
+        impl<F: PrimeField> From<$name<F>> for AssignedCell<F, F> {
+            fn from(typed: $name<F>) -> Self {
+                typed.cell
+            }
+        }
+    };
+}
+
+typed_cell!(AssignedBit, bool, "A cell constrained to be boolean.");
+typed_cell!(AssignedByte, u8, "A cell constrained to be in `0..256`.");
+typed_cell!(AssignedU64, u64, "A cell constrained to be in `0..2^64`.");
+
+#[cfg(test)]
+mod tests {
+    use super::{BitConfig, ByteConfig, U64Config};
+    use crate::plonk::ConstraintSystem;
+    use halo2curves::pasta::Fp;
+
+    #[test]
+    fn configure() {
+        let mut meta = ConstraintSystem::<Fp>::default();
+        let bit = meta.advice_column();
+        let byte = meta.advice_column();
+        let word = meta.advice_column();
+        let table = meta.lookup_table_column();
+
+        BitConfig::configure(&mut meta, bit);
+        let bytes = ByteConfig::configure(&mut meta, byte, table);
+        U64Config::configure(&mut meta, word, bytes);
+
+        let gates: Vec<_> = meta.gates().iter().map(|gate| gate.name()).collect();
+        assert_eq!(
+            gates,
+            [
+                "bit",
+                "bit operations",
+                "byte wrapping add",
+                "u64 decomposition",
+                "u64 operations"
+            ]
+        );
+        assert_eq!(meta.gates()[3].polynomials()[0].degree(), 2);
+        assert_eq!(meta.lookups().len(), 1);
+        assert_eq!(meta.num_selectors(), 10);
+        assert_eq!(meta.permutation().get_columns().len(), 3);
+    }
+}
@@ -15,6 +15,11 @@ pub enum SerdeFormat {
     /// are less than modulus.
     RawBytes,
     /// Serialization is the same as `RawBytes`, but no checks are performed.
+    ///
+    /// This is unsafe for untrusted data: a corrupted or malicious point which isn't on
+    /// the curve, or a field element which isn't reduced, is accepted and breaks the
+    /// soundness of the proofs made or verified with it. It must only be used to load
+    /// artifacts from trusted storage, for which it saves the checks of every point.
     RawBytesUnchecked,
 }
 
@@ -118,3 +123,16 @@ pub fn unpack(byte: u8, bits: &mut [bool]) {
         *bit = (byte >> bit_index) & 1 == 1;
     }
 }
+
+/// Overwrites every element of `values` with `zero`.
+///
+/// This is used to wipe secret values from memory once they are no longer needed. The
+/// writes are hidden from the optimizer, so that they are not removed because the values
+/// are dropped right after.
+pub fn wipe<T: Copy>(values: &mut [T], zero: T) {
+    for value in values.iter_mut() {
+        *value = zero;
+    }
+    std::hint::black_box(values);
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
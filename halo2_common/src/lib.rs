@@ -10,10 +10,18 @@ pub mod circuit;
 pub use halo2curves;
 pub mod multicore;
 pub mod plonk;
+pub mod witness;
 
 pub mod helpers;
 pub use helpers::SerdeFormat;
 
+// Re-exports used by the macros of this crate.
+#[doc(hidden)]
+pub mod __private {
+    pub use halo2_middleware::circuit::{Advice, Fixed, Instance};
+    pub use halo2_middleware::ff::Field;
+}
+
 // TODO: Everything that is moved from this crate to frontend or backend should recover the
 // pub(crate) status whenever possible.
 // https://github.com/privacy-scaling-explorations/halo2/issues/266
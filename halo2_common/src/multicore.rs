@@ -1,3 +1,12 @@
+//! Parallelism primitives used throughout the crates.
+//!
+//! With the `multicore` feature flag, which is enabled by default, these are re-exported
+//! from `rayon`. Without it, they are sequential stand-ins with the same interface, so
+//! the same code builds without `rayon` and runs on a single thread, in a deterministic
+//! order. Code should import the traits through [`prelude`], since some of them have no
+//! method of their own in the sequential build.
+
+#[cfg(feature = "multicore")]
 pub use rayon::{
     current_num_threads,
     iter::{IndexedParallelIterator, IntoParallelRefIterator},
@@ -7,13 +16,29 @@ pub use rayon::{
     Scope,
 };
 
+#[cfg(not(feature = "multicore"))]
+mod sequential;
+#[cfg(not(feature = "multicore"))]
+pub use sequential::{
+    current_num_threads, join, scope, IndexedParallelIterator, IntoParallelIterator,
+    IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator, ParallelSliceMut, Scope,
+};
+
+/// The parallel iterator and slice traits, to be glob imported.
+pub mod prelude {
+    pub use super::{
+        IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator,
+        IntoParallelRefMutIterator, ParallelIterator, ParallelSliceMut, TryFoldAndReduce,
+    };
+}
+
 pub trait TryFoldAndReduce<T, E> {
     /// Implements `iter.try_fold().try_reduce()` for `rayon::iter::ParallelIterator`,
     /// falling back on `Iterator::try_fold` when the `multicore` feature flag is
     /// disabled.
     /// The `try_fold_and_reduce` function can only be called by a iter with
     /// `Result<T, E>` item type because the `fold_op` must meet the trait
-    /// bounds of both `try_fold` and `try_reduce` from rayon.   
+    /// bounds of both `try_fold` and `try_reduce` from rayon.
     fn try_fold_and_reduce(
         self,
         identity: impl Fn() -> T + Send + Sync,
@@ -21,6 +46,7 @@ pub trait TryFoldAndReduce<T, E> {
     ) -> Result<T, E>;
 }
 
+#[cfg(feature = "multicore")]
 impl<T, E, I> TryFoldAndReduce<T, E> for I
 where
     T: Send + Sync,
@@ -36,3 +62,78 @@ where
             .try_reduce(&identity, |a, b| fold_op(a, Ok(b)))
     }
 }
+
+#[cfg(not(feature = "multicore"))]
+impl<T, E, I> TryFoldAndReduce<T, E> for I
+where
+    I: Iterator<Item = Result<T, E>>,
+{
+    fn try_fold_and_reduce(
+        mut self,
+        identity: impl Fn() -> T + Send + Sync,
+        fold_op: impl Fn(T, Result<T, E>) -> Result<T, E> + Send + Sync,
+    ) -> Result<T, E> {
+        self.try_fold(identity(), fold_op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::prelude::*;
+    use super::{join, scope};
+
+    // These run against whichever implementation is built, and check that it computes
+    // the same results as the sequential iterators.
+
+    #[test]
+    fn iterators() {
+        let values: Vec<u64> = (0..1000).map(|i| (i * 7919) % 1009).collect();
+
+        let doubled: Vec<u64> = values.par_iter().map(|v| v * 2).collect();
+        assert_eq!(doubled, values.iter().map(|v| v * 2).collect::<Vec<_>>());
+
+        let sum: u64 = values.clone().into_par_iter().sum();
+        assert_eq!(sum, values.iter().sum::<u64>());
+
+        let first = (0..values.len())
+            .into_par_iter()
+            .find_map_first(|i| (values[i] > 1000).then_some(i));
+        assert_eq!(first, values.iter().position(|v| *v > 1000));
+
+        let mut incremented = values.clone();
+        incremented.par_iter_mut().for_each(|v| *v += 1);
+        assert!(incremented.iter().zip(&values).all(|(a, b)| *a == b + 1));
+
+        let mut sorted = values.clone();
+        sorted.par_sort_unstable();
+        let mut expected = values.clone();
+        expected.sort_unstable();
+        assert_eq!(sorted, expected);
+
+        let total = values
+            .clone()
+            .into_par_iter()
+            .map(Ok::<_, ()>)
+            .try_fold_and_reduce(|| 0, |acc, v| v.map(|v| acc + v));
+        assert_eq!(total, Ok(sum));
+        let failed = values
+            .into_par_iter()
+            .map(|v| if v == 0 { Err(()) } else { Ok(v) })
+            .try_fold_and_reduce(|| 0, |acc, v| v.map(|v| acc + v));
+        assert_eq!(failed, Err(()));
+    }
+
+    #[test]
+    fn scopes() {
+        let mut chunks = vec![0usize; 100];
+        scope(|scope| {
+            for (i, chunk) in chunks.chunks_mut(10).enumerate() {
+                scope.spawn(move |_| chunk.iter_mut().for_each(|v| *v = i));
+            }
+        });
+        assert!(chunks.iter().enumerate().all(|(i, v)| *v == i / 10));
+
+        assert_eq!(join(|| 1, || 2), (1, 2));
+        assert!(super::current_num_threads() >= 1);
+    }
+}
@@ -0,0 +1,190 @@
+//! Sequential stand-ins for the `rayon` items re-exported by [`crate::multicore`], used
+//! when the `multicore` feature flag is disabled.
+//!
+//! Every item keeps the signature and trait bounds of its `rayon` counterpart, so code
+//! that builds without the feature also builds with it. The iterator traits are
+//! implemented for the standard iterators, which already provide the combinators shared
+//! with `rayon`.
+
+use std::marker::PhantomData;
+
+/// Returns the number of threads work is split into, which is always one.
+pub fn current_num_threads() -> usize {
+    1
+}
+
+/// Runs `oper_a` and then `oper_b`, and returns their results.
+pub fn join<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    (oper_a(), oper_b())
+}
+
+/// A scope in which tasks are spawned, which runs them as soon as they are spawned.
+pub struct Scope<'scope> {
+    marker: PhantomData<fn(&'scope ()) -> &'scope ()>,
+}
+
+impl<'scope> Scope<'scope> {
+    /// Runs `body` to completion.
+    pub fn spawn<BODY>(&self, body: BODY)
+    where
+        BODY: FnOnce(&Scope<'scope>) + Send + 'scope,
+    {
+        body(self)
+    }
+}
+
+/// Runs `op` with a [`Scope`], whose tasks have all completed when this returns.
+pub fn scope<'scope, OP, R>(op: OP) -> R
+where
+    OP: FnOnce(&Scope<'scope>) -> R + Send,
+    R: Send,
+{
+    op(&Scope {
+        marker: PhantomData,
+    })
+}
+
+/// The methods of `rayon::iter::ParallelIterator` that have no counterpart in
+/// [`Iterator`].
+pub trait ParallelIterator: Iterator + Sized {
+    /// Applies `predicate` to the items in order, and returns the first result that is
+    /// not `None`.
+    fn find_map_first<P, R>(mut self, predicate: P) -> Option<R>
+    where
+        P: Fn(Self::Item) -> Option<R> + Sync + Send,
+        R: Send,
+    {
+        self.find_map(predicate)
+    }
+
+    /// Returns the first item that satisfies `predicate`.
+    fn find_first<P>(mut self, predicate: P) -> Option<Self::Item>
+    where
+        P: Fn(&Self::Item) -> bool + Sync + Send,
+    {
+        self.find(predicate)
+    }
+}
+
+impl<I: Iterator> ParallelIterator for I {}
+
+/// Iterators that know the index of their items, which every iterator does when they
+/// are visited in order.
+pub trait IndexedParallelIterator: ParallelIterator {}
+
+impl<I: Iterator> IndexedParallelIterator for I {}
+
+/// Conversion into a sequential iterator, in place of a parallel one.
+pub trait IntoParallelIterator {
+    /// The iterator type.
+    type Iter: Iterator<Item = Self::Item>;
+    /// The type of the items.
+    type Item;
+
+    /// Converts `self` into an iterator.
+    fn into_par_iter(self) -> Self::Iter;
+}
+
+impl<I: IntoIterator> IntoParallelIterator for I {
+    type Iter = I::IntoIter;
+    type Item = I::Item;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.into_iter()
+    }
+}
+
+/// Iteration over references to the items of a collection.
+pub trait IntoParallelRefIterator<'data> {
+    /// The iterator type.
+    type Iter: Iterator<Item = Self::Item>;
+    /// The type of the items.
+    type Item: 'data;
+
+    /// Returns an iterator over references to the items of `self`.
+    fn par_iter(&'data self) -> Self::Iter;
+}
+
+impl<'data, I: 'data + ?Sized> IntoParallelRefIterator<'data> for I
+where
+    &'data I: IntoIterator,
+{
+    type Iter = <&'data I as IntoIterator>::IntoIter;
+    type Item = <&'data I as IntoIterator>::Item;
+
+    fn par_iter(&'data self) -> Self::Iter {
+        self.into_iter()
+    }
+}
+
+/// Iteration over mutable references to the items of a collection.
+pub trait IntoParallelRefMutIterator<'data> {
+    /// The iterator type.
+    type Iter: Iterator<Item = Self::Item>;
+    /// The type of the items.
+    type Item: 'data;
+
+    /// Returns an iterator over mutable references to the items of `self`.
+    fn par_iter_mut(&'data mut self) -> Self::Iter;
+}
+
+impl<'data, I: 'data + ?Sized> IntoParallelRefMutIterator<'data> for I
+where
+    &'data mut I: IntoIterator,
+{
+    type Iter = <&'data mut I as IntoIterator>::IntoIter;
+    type Item = <&'data mut I as IntoIterator>::Item;
+
+    fn par_iter_mut(&'data mut self) -> Self::Iter {
+        self.into_iter()
+    }
+}
+
+/// The methods of `rayon::slice::ParallelSliceMut` used by the crates.
+pub trait ParallelSliceMut<T: Send> {
+    /// Returns `self` as a mutable slice.
+    fn as_parallel_slice_mut(&mut self) -> &mut [T];
+
+    /// Returns an iterator over `chunk_size` elements of the slice at a time.
+    fn par_chunks_mut(&mut self, chunk_size: usize) -> std::slice::ChunksMut<'_, T> {
+        self.as_parallel_slice_mut().chunks_mut(chunk_size)
+    }
+
+    /// Sorts the slice.
+    fn par_sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.as_parallel_slice_mut().sort()
+    }
+
+    /// Sorts the slice, without preserving the order of equal elements.
+    fn par_sort_unstable(&mut self)
+    where
+        T: Ord,
+    {
+        self.as_parallel_slice_mut().sort_unstable()
+    }
+
+    /// Sorts the slice by the key `f` extracts, without preserving the order of equal
+    /// elements.
+    fn par_sort_unstable_by_key<K, F>(&mut self, f: F)
+    where
+        K: Ord,
+        F: Fn(&T) -> K + Sync,
+    {
+        self.as_parallel_slice_mut().sort_unstable_by_key(f)
+    }
+}
+
+impl<T: Send> ParallelSliceMut<T> for [T] {
+    fn as_parallel_slice_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
@@ -369,12 +369,15 @@ impl<F: Field> Assigned<F> {
     /// necessary.
     ///
     /// If the denominator is zero, this returns zero.
+    ///
+    /// With the `hardened-witness` feature, the inversion is always performed, so that
+    /// the time taken doesn't depend on the value of the denominator.
     pub fn evaluate(self) -> F {
         match self {
             Self::Zero => F::ZERO,
             Self::Trivial(x) => x,
             Self::Rational(numerator, denominator) => {
-                if denominator == F::ONE {
+                if !cfg!(feature = "hardened-witness") && denominator == F::ONE {
                     numerator
                 } else {
                     numerator * denominator.invert().unwrap_or(F::ZERO)
@@ -6,10 +6,10 @@ use core::cmp::max;
 use core::ops::{Add, Mul};
 use halo2_middleware::circuit::{
     Advice, AdviceQueryMid, Any, ChallengeMid, ColumnMid, ColumnType, ConstraintSystemV2Backend,
-    ExpressionMid, Fixed, FixedQueryMid, GateV2Backend, Instance, InstanceQueryMid,
+    ExpressionMid, Fixed, FixedQueryMid, GateV2Backend, Instance, InstanceQueryMid, Subdomain,
 };
 use halo2_middleware::ff::Field;
-use halo2_middleware::metadata;
+use halo2_middleware::metadata::{self, ColumnId};
 use halo2_middleware::poly::Rotation;
 use sealed::SealedPhase;
 use std::collections::HashMap;
@@ -17,10 +17,14 @@ use std::fmt::Debug;
 use std::iter::{Product, Sum};
 use std::{
     convert::TryFrom,
-    ops::{Neg, Sub},
+    ops::{Neg, Range, Sub},
 };
 
 mod compress_selectors;
+mod config;
+mod gate_builder;
+
+pub use gate_builder::GateBuilder;
 
 /// A column with an index and type
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
@@ -323,6 +327,17 @@ impl Selector {
         region.enable_selector(|| "", self, offset)
     }
 
+    /// Enable this selector at the `len` offsets starting at `offset` within the given
+    /// region.
+    pub fn enable_range<F: Field>(
+        &self,
+        region: &mut Region<F>,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), Error> {
+        region.enable_selector_range(|| "", self, offset, len)
+    }
+
     /// Is this selector "simple"? Simple selectors can only be multiplied
     /// by expressions that contain no other simple selectors.
     pub fn is_simple(&self) -> bool {
@@ -487,9 +502,32 @@ impl From<ChallengeMid> for Challenge {
     }
 }
 
+/// Which names of regions and namespaces an [`Assignment`] evaluates.
+///
+/// The names are given as closures, so that a backend which doesn't need them doesn't
+/// format them. Production provers only need them to report errors, and can skip them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NamePolicy {
+    /// Evaluates every name, including those of the tracing spans of the layouters.
+    #[default]
+    KeepAll,
+    /// Evaluates the names only to report errors.
+    KeepOnError,
+    /// Evaluates no name: errors refer to regions by index.
+    Discard,
+}
+
 /// This trait allows a [`Circuit`] to direct some backend to assign a witness
 /// for a constraint system.
 pub trait Assignment<F: Field> {
+    /// Returns which names of regions and namespaces the backend evaluates.
+    ///
+    /// Layouters only evaluate the names of the regions for their tracing spans if this
+    /// is [`NamePolicy::KeepAll`]. By default, every name is kept.
+    fn name_policy(&self) -> NamePolicy {
+        NamePolicy::KeepAll
+    }
+
     /// Creates a new region and enters into it.
     ///
     /// Panics if we are currently in a region (if `exit_region` was not called).
@@ -530,11 +568,41 @@ pub trait Assignment<F: Field> {
         A: FnOnce() -> AR,
         AR: Into<String>;
 
+    /// Enables a selector at each of the given rows.
+    ///
+    /// The rows are passed as a single run, so that backends storing selectors can assign
+    /// them at once. By default, this enables the selector at each row in turn.
+    fn enable_selector_range<A, AR>(
+        &mut self,
+        annotation: A,
+        selector: &Selector,
+        rows: Range<usize>,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let annotation = annotation().into();
+        for row in rows {
+            self.enable_selector(|| annotation.clone(), selector, row)?;
+        }
+        Ok(())
+    }
+
     /// Queries the cell of an instance column at a particular absolute row.
     ///
     /// Returns the cell's value, if known.
     fn query_instance(&self, column: Column<Instance>, row: usize) -> Result<Value<F>, Error>;
 
+    /// Queries the cell of an advice column at a particular absolute row.
+    ///
+    /// Returns the cell's value if the column belongs to an earlier phase than the one
+    /// being synthesized, and the backend kept the values of that phase. By default, the
+    /// value is unknown.
+    fn query_advice(&self, _column: Column<Advice>, _row: usize) -> Result<Value<F>, Error> {
+        Ok(Value::unknown())
+    }
+
     /// Assign an advice column value (witness)
     fn assign_advice<V, VR, A, AR>(
         &mut self,
@@ -549,6 +617,34 @@ pub trait Assignment<F: Field> {
         A: FnOnce() -> AR,
         AR: Into<String>;
 
+    /// Assigns the cells of an advice column at the given rows, from a slice of values.
+    ///
+    /// `values`, if known, contains a value for each row. Backends storing the witness can
+    /// copy the values at once, instead of evaluating a closure per cell. By default, this
+    /// assigns each cell in turn.
+    fn assign_advice_column_slice<A, AR>(
+        &mut self,
+        annotation: A,
+        column: Column<Advice>,
+        rows: Range<usize>,
+        values: Value<&[Assigned<F>]>,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let annotation = annotation().into();
+        for (i, row) in rows.enumerate() {
+            self.assign_advice(
+                || annotation.clone(),
+                column,
+                row,
+                || values.map(|values| values[i]),
+            )?;
+        }
+        Ok(())
+    }
+
     /// Assign a fixed value
     fn assign_fixed<V, VR, A, AR>(
         &mut self,
@@ -1447,6 +1543,7 @@ pub struct Gate<F: Field> {
     /// trigger debug checks on gates.
     queried_selectors: Vec<Selector>,
     queried_cells: Vec<VirtualCell>,
+    subdomain: Option<Subdomain>,
 }
 
 impl<F: Field> Gate<F> {
@@ -1472,6 +1569,51 @@ impl<F: Field> Gate<F> {
     pub fn queried_cells(&self) -> &[VirtualCell] {
         &self.queried_cells
     }
+
+    /// Returns the rows the constraints are enforced on, or `None` if they are enforced
+    /// on every row.
+    pub fn subdomain(&self) -> Option<Subdomain> {
+        self.subdomain
+    }
+
+    /// Returns the degree of the constraints once enforced, which is one more than that of
+    /// the polynomials for a gate on a subdomain.
+    pub fn degree(&self) -> usize {
+        let degree = self
+            .polys
+            .iter()
+            .map(|poly| poly.degree())
+            .max()
+            .unwrap_or(0);
+        match self.subdomain {
+            Some(_) => degree + 1,
+            None => degree,
+        }
+    }
+
+    /// Returns whether every constraint of the gate is zero at every row, because it is
+    /// multiplied by a selector that is never enabled in `selectors`, the assignment of
+    /// each selector of the circuit.
+    ///
+    /// Such a gate constrains nothing, but its constraints are still evaluated by the
+    /// prover and the verifier.
+    pub fn is_dead(&self, selectors: &[Vec<bool>]) -> bool {
+        // Whether an expression is zero whatever the values of the columns.
+        self.polys.iter().all(|poly| {
+            poly.evaluate(
+                &|scalar| scalar.is_zero_vartime(),
+                &|selector| !selectors[selector.0].iter().any(|enabled| *enabled),
+                &|_| false,
+                &|_| false,
+                &|_| false,
+                &|_| false,
+                &|a| a,
+                &|a, b| a && b,
+                &|a, b| a || b,
+                &|a, scalar| a || scalar.is_zero_vartime(),
+            )
+        })
+    }
 }
 
 struct QueriesMap {
@@ -1562,6 +1704,8 @@ impl<F: Field> From<ConstraintSystem<F>> for ConstraintSystemV2Backend<F> {
             num_instance_columns: cs.num_instance_columns,
             num_challenges: cs.num_challenges,
             unblinded_advice_columns: cs.unblinded_advice_columns,
+            blinded_instance_columns: cs.blinded_instance_columns,
+            extra_unusable_rows: cs.extra_unusable_rows,
             advice_column_phase: cs.advice_column_phase.iter().map(|p| p.0).collect(),
             challenge_phase: cs.challenge_phase.iter().map(|p| p.0).collect(),
             gates: cs
@@ -1570,6 +1714,7 @@ impl<F: Field> From<ConstraintSystem<F>> for ConstraintSystemV2Backend<F> {
                 .flat_map(|mut g| {
                     let constraint_names = std::mem::take(&mut g.constraint_names);
                     let gate_name = g.name.clone();
+                    let subdomain = g.subdomain;
                     g.polys.into_iter().enumerate().map(move |(i, e)| {
                         let name = match constraint_names[i].as_str() {
                             "" => gate_name.clone(),
@@ -1578,6 +1723,7 @@ impl<F: Field> From<ConstraintSystem<F>> for ConstraintSystemV2Backend<F> {
                         GateV2Backend {
                             name,
                             poly: e.into(),
+                            subdomain,
                         }
                     })
                 })
@@ -1613,6 +1759,7 @@ impl<F: Field> From<ConstraintSystem<F>> for ConstraintSystemV2Backend<F> {
                 })
                 .collect(),
             general_column_annotations: cs.general_column_annotations,
+            column_ids: cs.column_ids,
         }
     }
 }
@@ -1631,6 +1778,7 @@ fn cs2_collect_queries_gates<F: Field>(
             polys: vec![queries.as_expression(gate.polynomial())],
             queried_selectors: Vec::new(), // Unused?
             queried_cells: Vec::new(),     // Unused?
+            subdomain: gate.subdomain(),
         })
         .collect()
 }
@@ -1748,10 +1896,17 @@ pub struct ConstraintSystem<F: Field> {
     /// Contains the index of each advice column that is left unblinded.
     pub unblinded_advice_columns: Vec<usize>,
 
+    /// Contains the index of each instance column that is committed with a blinding factor
+    /// by commitment schemes that commit to instance columns.
+    pub blinded_instance_columns: Vec<usize>,
+
     /// Contains the phase for each advice column. Should have same length as num_advice_columns.
     pub advice_column_phase: Vec<sealed::Phase>,
     /// Contains the phase for each challenge. Should have same length as num_challenges.
     pub challenge_phase: Vec<sealed::Phase>,
+    /// Contains the index of each challenge requested with `challenge_for_phase`, whose
+    /// uses are checked by `check_challenge_phases`.
+    pub phase_checked_challenges: Vec<usize>,
 
     /// This is a cached vector that maps virtual selectors to the concrete
     /// fixed column that they were compressed into. This is just used by dev
@@ -1781,11 +1936,17 @@ pub struct ConstraintSystem<F: Field> {
     // List of indexes of Fixed columns which are associated to a circuit-general Column tied to their annotation.
     pub general_column_annotations: HashMap<metadata::Column, String>,
 
+    // The stable identifier of each column that was given one.
+    pub column_ids: HashMap<metadata::Column, metadata::ColumnId>,
+
     // Vector of fixed columns, which can be used to store constant values
     // that are copied into advice columns.
     pub constants: Vec<Column<Fixed>>,
 
     pub minimum_degree: Option<usize>,
+
+    // The number of rows left unusable on top of those needed by the blinding factors.
+    pub extra_unusable_rows: usize,
 }
 
 impl<F: Field> From<ConstraintSystemV2Backend<F>> for ConstraintSystem<F> {
@@ -1798,12 +1959,14 @@ impl<F: Field> From<ConstraintSystemV2Backend<F>> for ConstraintSystem<F> {
             num_selectors: 0,
             num_challenges: cs2.num_challenges,
             unblinded_advice_columns: cs2.unblinded_advice_columns,
+            blinded_instance_columns: cs2.blinded_instance_columns,
             advice_column_phase: cs2
                 .advice_column_phase
                 .into_iter()
                 .map(sealed::Phase)
                 .collect(),
             challenge_phase: cs2.challenge_phase.into_iter().map(sealed::Phase).collect(),
+            phase_checked_challenges: Vec::new(),
             selector_map: Vec::new(),
             gates,
             advice_queries: queries.advice,
@@ -1814,8 +1977,10 @@ impl<F: Field> From<ConstraintSystemV2Backend<F>> for ConstraintSystem<F> {
             lookups,
             shuffles,
             general_column_annotations: cs2.general_column_annotations,
+            column_ids: cs2.column_ids,
             constants: Vec::new(),
             minimum_degree: None,
+            extra_unusable_rows: cs2.extra_unusable_rows,
         }
     }
 }
@@ -1828,6 +1993,7 @@ pub struct PinnedConstraintSystem<'a, F: Field> {
     num_instance_columns: &'a usize,
     num_selectors: &'a usize,
     num_challenges: &'a usize,
+    blinded_instance_columns: &'a Vec<usize>,
     advice_column_phase: &'a Vec<sealed::Phase>,
     challenge_phase: &'a Vec<sealed::Phase>,
     gates: PinnedGates<'a, F>,
@@ -1839,6 +2005,7 @@ pub struct PinnedConstraintSystem<'a, F: Field> {
     shuffles: &'a Vec<shuffle::Argument<F>>,
     constants: &'a Vec<Column<Fixed>>,
     minimum_degree: &'a Option<usize>,
+    extra_unusable_rows: &'a usize,
 }
 
 impl<'a, F: Field> std::fmt::Debug for PinnedConstraintSystem<'a, F> {
@@ -1856,6 +2023,11 @@ impl<'a, F: Field> std::fmt::Debug for PinnedConstraintSystem<'a, F> {
                 .field("advice_column_phase", self.advice_column_phase)
                 .field("challenge_phase", self.challenge_phase);
         }
+        // Only show blinded instance columns if there are any, so that the representation
+        // of other circuits is unchanged.
+        if !self.blinded_instance_columns.is_empty() {
+            debug_struct.field("blinded_instance_columns", self.blinded_instance_columns);
+        }
         debug_struct
             .field("gates", &self.gates)
             .field("advice_queries", self.advice_queries)
@@ -1869,6 +2041,9 @@ impl<'a, F: Field> std::fmt::Debug for PinnedConstraintSystem<'a, F> {
         debug_struct
             .field("constants", self.constants)
             .field("minimum_degree", self.minimum_degree);
+        if *self.extra_unusable_rows > 0 {
+            debug_struct.field("extra_unusable_rows", self.extra_unusable_rows);
+        }
         debug_struct.finish()
     }
 }
@@ -1878,11 +2053,32 @@ struct PinnedGates<'a, F: Field>(&'a Vec<Gate<F>>);
 impl<'a, F: Field> std::fmt::Debug for PinnedGates<'a, F> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         f.debug_list()
-            .entries(self.0.iter().flat_map(|gate| gate.polynomials().iter()))
+            .entries(self.0.iter().flat_map(|gate| {
+                gate.polynomials()
+                    .iter()
+                    .map(move |poly| PinnedConstraint(poly, gate.subdomain))
+            }))
             .finish()
     }
 }
 
+/// A constraint of a gate, whose subdomain is only shown if it has one, so that the
+/// representation of other circuits is unchanged.
+struct PinnedConstraint<'a, F: Field>(&'a Expression<F>, Option<Subdomain>);
+
+impl<'a, F: Field> std::fmt::Debug for PinnedConstraint<'a, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self.1 {
+            None => self.0.fmt(f),
+            Some(subdomain) => f
+                .debug_struct("OnSubdomain")
+                .field("poly", self.0)
+                .field("subdomain", &subdomain)
+                .finish(),
+        }
+    }
+}
+
 impl<F: Field> Default for ConstraintSystem<F> {
     fn default() -> ConstraintSystem<F> {
         ConstraintSystem {
@@ -1892,8 +2088,10 @@ impl<F: Field> Default for ConstraintSystem<F> {
             num_selectors: 0,
             num_challenges: 0,
             unblinded_advice_columns: Vec::new(),
+            blinded_instance_columns: Vec::new(),
             advice_column_phase: Vec::new(),
             challenge_phase: Vec::new(),
+            phase_checked_challenges: Vec::new(),
             selector_map: vec![],
             gates: vec![],
             fixed_queries: Vec::new(),
@@ -1904,8 +2102,10 @@ impl<F: Field> Default for ConstraintSystem<F> {
             lookups: Vec::new(),
             shuffles: Vec::new(),
             general_column_annotations: HashMap::new(),
+            column_ids: HashMap::new(),
             constants: vec![],
             minimum_degree: None,
+            extra_unusable_rows: 0,
         }
     }
 }
@@ -1921,6 +2121,7 @@ impl<F: Field> ConstraintSystem<F> {
             num_instance_columns: &self.num_instance_columns,
             num_selectors: &self.num_selectors,
             num_challenges: &self.num_challenges,
+            blinded_instance_columns: &self.blinded_instance_columns,
             advice_column_phase: &self.advice_column_phase,
             challenge_phase: &self.challenge_phase,
             gates: PinnedGates(&self.gates),
@@ -1932,6 +2133,7 @@ impl<F: Field> ConstraintSystem<F> {
             shuffles: &self.shuffles,
             constants: &self.constants,
             minimum_degree: &self.minimum_degree,
+            extra_unusable_rows: &self.extra_unusable_rows,
         }
     }
 
@@ -1988,6 +2190,15 @@ impl<F: Field> ConstraintSystem<F> {
     ///
     /// `table_map` returns a map between input expressions and the table expressions
     /// they need to match.
+    ///
+    /// Both sides may query advice, fixed and instance columns at any rotation. The rows
+    /// of the table are the values of the table expressions at each usable row, so a table
+    /// querying a column at the current and next rotations contains every pair of
+    /// consecutive values of the column (a sliding window over it). Rotations that reach
+    /// past the usable rows read the unusable ones, which hold zeros in fixed columns and
+    /// random blinding values in advice columns, and the rows they produce are part of
+    /// the table as well. The queries are counted like those of gates, in the blinding
+    /// factors and in the degree of the argument.
     pub fn lookup_any<S: AsRef<str>>(
         &mut self,
         name: S,
@@ -2148,6 +2359,21 @@ impl<F: Field> ConstraintSystem<F> {
         self.minimum_degree = Some(degree);
     }
 
+    /// Leaves `rows` more rows unusable at the end of the circuit, before its last row,
+    /// for example to pad the circuit for a protocol built on top of the proofs.
+    ///
+    /// The rows are filled with random values like the blinding factors, and are counted
+    /// by [`Self::blinding_factors`], so that the `l_last` row and the rows covered by
+    /// `l_blind` move accordingly in the prover, in the verifier and in the keys.
+    pub fn set_extra_unusable_rows(&mut self, rows: usize) {
+        self.extra_unusable_rows = rows;
+    }
+
+    /// Returns the number of rows left unusable by [`Self::set_extra_unusable_rows`].
+    pub fn extra_unusable_rows(&self) -> usize {
+        self.extra_unusable_rows
+    }
+
     /// Creates a new gate.
     ///
     /// # Panics
@@ -2158,6 +2384,45 @@ impl<F: Field> ConstraintSystem<F> {
         &mut self,
         name: S,
         constraints: impl FnOnce(&mut VirtualCells<'_, F>) -> Iter,
+    ) {
+        self.create_gate_with_subdomain(name, None, constraints)
+    }
+
+    /// Creates a new gate whose constraints are only enforced on the rows of `subdomain`,
+    /// such as every fourth row, without a selector column.
+    ///
+    /// The constraints are multiplied by a polynomial which is one on the usable rows of
+    /// the subdomain and zero on the other rows, so the gate has one more degree than its
+    /// constraints, as if they were multiplied by a selector. The polynomial is computed by
+    /// the prover and the verifier, so the period of the subdomain must divide the number
+    /// of rows of the circuit.
+    ///
+    /// # Panics
+    ///
+    /// A gate is required to contain polynomial constraints. This method will panic if
+    /// `constraints` returns an empty iterator.
+    pub fn create_gate_on_subdomain<
+        C: Into<Constraint<F>>,
+        Iter: IntoIterator<Item = C>,
+        S: AsRef<str>,
+    >(
+        &mut self,
+        name: S,
+        subdomain: Subdomain,
+        constraints: impl FnOnce(&mut VirtualCells<'_, F>) -> Iter,
+    ) {
+        self.create_gate_with_subdomain(name, Some(subdomain), constraints)
+    }
+
+    fn create_gate_with_subdomain<
+        C: Into<Constraint<F>>,
+        Iter: IntoIterator<Item = C>,
+        S: AsRef<str>,
+    >(
+        &mut self,
+        name: S,
+        subdomain: Option<Subdomain>,
+        constraints: impl FnOnce(&mut VirtualCells<'_, F>) -> Iter,
     ) {
         let mut cells = VirtualCells::new(self);
         let constraints = constraints(&mut cells);
@@ -2184,6 +2449,7 @@ impl<F: Field> ConstraintSystem<F> {
             polys,
             queried_selectors,
             queried_cells,
+            subdomain,
         });
     }
 
@@ -2477,6 +2743,126 @@ impl<F: Field> ConstraintSystem<F> {
         tmp
     }
 
+    /// Allocate a new instance column that is committed with a blinding factor.
+    ///
+    /// With commitment schemes that commit to instance columns, such as IPA, the prover
+    /// commits to the values of this column with a random blind and sends the commitment in
+    /// the proof, instead of the verifier computing it from the values. The values stay
+    /// hidden from the verifier, which passes an empty slice for this column. Commitment
+    /// schemes that hash instance values into the transcript can't hide them, and reject
+    /// circuits with blinded instance columns.
+    pub fn blinded_instance_column(&mut self) -> Column<Instance> {
+        let column = self.instance_column();
+        self.blinded_instance_columns.push(column.index());
+        column
+    }
+
+    /// Allocate a new fixed column annotated with the given name.
+    ///
+    /// The name is used by the `MockProver`, the layout renderer and in error messages
+    /// wherever the column is referenced.
+    pub fn fixed_column_named(&mut self, name: impl Into<String>) -> Column<Fixed> {
+        let column = self.fixed_column();
+        self.name_column(column, name);
+        column
+    }
+
+    /// Allocate a new advice column at `FirstPhase` annotated with the given name.
+    pub fn advice_column_named(&mut self, name: impl Into<String>) -> Column<Advice> {
+        self.advice_column_in_named(FirstPhase, name)
+    }
+
+    /// Allocate a new advice column in given phase, annotated with the given name.
+    ///
+    /// # Panics
+    ///
+    /// It panics if previous phase before the given one doesn't have advice column allocated.
+    pub fn advice_column_in_named<P: Phase>(
+        &mut self,
+        phase: P,
+        name: impl Into<String>,
+    ) -> Column<Advice> {
+        let column = self.advice_column_in(phase);
+        self.name_column(column, name);
+        column
+    }
+
+    /// Allocate a new unblinded advice column at `FirstPhase` annotated with the given name.
+    pub fn unblinded_advice_column_named(&mut self, name: impl Into<String>) -> Column<Advice> {
+        let column = self.unblinded_advice_column();
+        self.name_column(column, name);
+        column
+    }
+
+    /// Allocate a new instance column annotated with the given name.
+    pub fn instance_column_named(&mut self, name: impl Into<String>) -> Column<Instance> {
+        let column = self.instance_column();
+        self.name_column(column, name);
+        column
+    }
+
+    /// Allocates a new fixed column that can be used in a lookup table, annotated with
+    /// the given name.
+    pub fn lookup_table_column_named(&mut self, name: impl Into<String>) -> TableColumn {
+        let column = self.lookup_table_column();
+        self.name_column(column.inner(), name);
+        column
+    }
+
+    fn name_column<T: Into<Column<Any>>>(&mut self, column: T, name: impl Into<String>) {
+        let column = column.into();
+        self.general_column_annotations.insert(
+            metadata::Column::from((column.column_type, column.index)),
+            name.into(),
+        );
+    }
+
+    /// Gives the column a stable identifier, which external tooling can reference it by
+    /// instead of its index. The column is annotated with the name of the identifier,
+    /// unless it already has a name.
+    ///
+    /// # Panics
+    ///
+    /// It panics if another column already has an identifier with the same UUID.
+    pub fn set_column_id<T: Into<Column<Any>>>(&mut self, column: T, id: ColumnId) {
+        let column = column.into();
+        let column = metadata::Column::from((column.column_type, column.index));
+        if let Some((other, _)) = self
+            .column_ids
+            .iter()
+            .find(|(other, other_id)| **other != column && other_id.uuid == id.uuid)
+        {
+            panic!("{} already identifies {}", id, other);
+        }
+        self.general_column_annotations
+            .entry(column)
+            .or_insert_with(|| id.name.clone());
+        self.column_ids.insert(column, id);
+    }
+
+    /// Returns the stable identifier of the given column, if it was given one.
+    pub fn column_id<T: Into<Column<Any>>>(&self, column: T) -> Option<&ColumnId> {
+        let column = column.into();
+        self.column_ids
+            .get(&metadata::Column::from((column.column_type, column.index)))
+    }
+
+    /// Returns the column identified by the given UUID, if any.
+    pub fn column_by_id(&self, uuid: u128) -> Option<Column<Any>> {
+        self.column_ids
+            .iter()
+            .find(|(_, id)| id.uuid == uuid)
+            .map(|(column, _)| Column::new(column.index, column.column_type))
+    }
+
+    /// Returns the name the given column was annotated with, if any.
+    pub fn column_name<T: Into<Column<Any>>>(&self, column: T) -> Option<&str> {
+        let column = column.into();
+        self.general_column_annotations
+            .get(&metadata::Column::from((column.column_type, column.index)))
+            .map(String::as_str)
+    }
+
     /// Requests a challenge that is usable after the given phase.
     ///
     /// # Panics
@@ -2498,6 +2884,28 @@ impl<F: Field> ConstraintSystem<F> {
         tmp
     }
 
+    /// Requests a challenge to assign the advice columns of the given phase, that is a
+    /// challenge usable after the previous phase.
+    ///
+    /// Unlike the challenges returned by [`ConstraintSystem::challenge_usable_after`], which
+    /// may also batch the constraints of earlier columns, every gate, lookup and shuffle
+    /// using this challenge must query an advice column of the given phase or of a later
+    /// one. This is checked by [`ConstraintSystem::check_challenge_phases`].
+    ///
+    /// # Panics
+    ///
+    /// It panics if the given phase is the first one, or if the previous phase doesn't have
+    /// advice column allocated.
+    pub fn challenge_for_phase<P: Phase>(&mut self, phase: P) -> Challenge {
+        let phase = phase.to_sealed();
+        let previous = phase
+            .prev()
+            .unwrap_or_else(|| panic!("No challenge is usable before phase {phase:?}"));
+        let challenge = self.challenge_usable_after(previous);
+        self.phase_checked_challenges.push(challenge.index());
+        challenge
+    }
+
     /// Helper funciotn to assert phase exists, to make sure phase-aware resources
     /// are allocated in order, and to avoid any phase to be skipped accidentally
     /// to cause unexpected issue in the future.
@@ -2512,6 +2920,120 @@ impl<F: Field> ConstraintSystem<F> {
             });
     }
 
+    /// Checks that every gate constraint, lookup and shuffle using a challenge requested
+    /// with [`ConstraintSystem::challenge_for_phase`] also queries an advice column of the
+    /// phase the challenge was requested for, or of a later one.
+    ///
+    /// The challenge is only squeezed once the columns of the earlier phases are committed,
+    /// so the prover can't use it to assign them: an argument relating the challenge to
+    /// earlier columns only means that it is used in the wrong phase. The error names the
+    /// offending gate or argument, the challenge and the columns. This is checked when a
+    /// circuit is compiled, right after `configure`.
+    pub fn check_challenge_phases(&self) -> Result<(), Error> {
+        type Queried = (Vec<Challenge>, Vec<(usize, u8)>);
+        fn merge((mut challenges, mut advice): Queried, (other, other_advice): Queried) -> Queried {
+            challenges.extend(other);
+            advice.extend(other_advice);
+            (challenges, advice)
+        }
+        fn queried<'e, F: Field + 'e>(
+            expressions: impl Iterator<Item = &'e Expression<F>>,
+        ) -> Queried {
+            expressions.fold((vec![], vec![]), |queried, expression| {
+                merge(
+                    queried,
+                    expression.evaluate(
+                        &|_| (vec![], vec![]),
+                        &|_| (vec![], vec![]),
+                        &|_| (vec![], vec![]),
+                        &|query| (vec![], vec![(query.column_index, query.phase.0)]),
+                        &|_| (vec![], vec![]),
+                        &|challenge| (vec![challenge], vec![]),
+                        &|queried| queried,
+                        &merge,
+                        &merge,
+                        &|queried, _| queried,
+                    ),
+                )
+            })
+        }
+        let check = |argument: String, (challenges, mut advice): Queried| {
+            let challenge = match challenges
+                .into_iter()
+                .filter(|challenge| self.phase_checked_challenges.contains(&challenge.index()))
+                .max_by_key(|challenge| challenge.phase)
+            {
+                Some(challenge) => challenge,
+                None => return Ok(()),
+            };
+            if advice.iter().any(|(_, phase)| *phase > challenge.phase) {
+                return Ok(());
+            }
+
+            advice.sort_unstable();
+            advice.dedup();
+            let columns = if advice.is_empty() {
+                "no advice column".to_string()
+            } else {
+                let columns: Vec<_> = advice
+                    .iter()
+                    .map(|&(index, phase)| {
+                        let column = Column::new(index, Advice { phase });
+                        match self.column_name(column) {
+                            Some(name) => format!("advice {index} {name:?} (phase {phase})"),
+                            None => format!("advice {index} (phase {phase})"),
+                        }
+                    })
+                    .collect();
+                format!("only {}", columns.join(", "))
+            };
+            Err(Error::Other(format!(
+                "{argument} uses challenge {}, requested for phase {}, but queries {columns}; \
+                 the challenge is unknown when these columns are assigned",
+                challenge.index(),
+                challenge.phase() + 1,
+            )))
+        };
+
+        for gate in self.gates.iter() {
+            for (constraint_index, poly) in gate.polynomials().iter().enumerate() {
+                let constraint_name = gate.constraint_name(constraint_index);
+                let constraint = if constraint_name.is_empty() {
+                    format!("constraint {constraint_index}")
+                } else {
+                    format!("constraint {constraint_index} {constraint_name:?}")
+                };
+                check(
+                    format!("{constraint} of gate {:?}", gate.name()),
+                    queried(std::iter::once(poly)),
+                )?;
+            }
+        }
+        for lookup in self.lookups.iter() {
+            check(
+                format!("lookup {:?}", lookup.name),
+                queried(
+                    lookup
+                        .input_expressions
+                        .iter()
+                        .chain(lookup.table_expressions.iter()),
+                ),
+            )?;
+        }
+        for shuffle in self.shuffles.iter() {
+            check(
+                format!("shuffle {:?}", shuffle.name),
+                queried(
+                    shuffle
+                        .input_expressions
+                        .iter()
+                        .chain(shuffle.shuffle_expressions.iter()),
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
     /// Returns the list of phases
     pub fn phases(&self) -> impl Iterator<Item = sealed::Phase> {
         let max_phase = self
@@ -2558,7 +3080,7 @@ impl<F: Field> ConstraintSystem<F> {
             degree,
             self.gates
                 .iter()
-                .flat_map(|gate| gate.polynomials().iter().map(|poly| poly.degree()))
+                .map(|gate| gate.degree())
                 .max()
                 .unwrap_or(0),
         );
@@ -2567,7 +3089,11 @@ impl<F: Field> ConstraintSystem<F> {
     }
 
     /// Compute the number of blinding factors necessary to perfectly blind
-    /// each of the prover's witness polynomials.
+    /// each of the prover's witness polynomials, plus the
+    /// [extra unusable rows](Self::set_extra_unusable_rows).
+    ///
+    /// These rows come right before the last row of the circuit, the `l_last` row, which
+    /// isn't usable either.
     pub fn blinding_factors(&self) -> usize {
         // All of the prover's advice columns are evaluated at no more than
         let factors = *self.num_advice_queries.iter().max().unwrap_or(&1);
@@ -2592,19 +3118,35 @@ impl<F: Field> ConstraintSystem<F> {
 
         // Add an additional blinding factor as a slight defense against
         // off-by-one errors.
-        factors + 1
+        factors + 1 + self.extra_unusable_rows
+    }
+
+    /// Returns the rows of a circuit with `2^k` rows which can be assigned, before the
+    /// blinding factors and the `l_last` row.
+    pub fn usable_rows(&self, k: u32) -> Range<usize> {
+        0..(1usize << k).saturating_sub(self.blinding_factors() + 1)
     }
 
     /// Returns the minimum necessary rows that need to exist in order to
     /// account for e.g. blinding factors.
     pub fn minimum_rows(&self) -> usize {
-        self.blinding_factors() // m blinding factors
+        let rows = self.blinding_factors() // m blinding factors
             + 1 // for l_{-(m + 1)} (l_last)
             + 1 // for l_0 (just for extra breathing room for the permutation
                 // argument, to essentially force a separation in the
                 // permutation polynomial between the roles of l_last, l_0
                 // and the interstitial values.)
-            + 1 // for at least one row
+            + 1; // for at least one row
+
+        // The periods of the subdomains of the gates must divide the number of rows.
+        let period = self
+            .gates
+            .iter()
+            .filter_map(|gate| gate.subdomain())
+            .map(|subdomain| subdomain.period())
+            .max()
+            .unwrap_or(1);
+        max(rows, period)
     }
 
     /// Returns number of fixed columns
@@ -2655,6 +3197,11 @@ impl<F: Field> ConstraintSystem<F> {
         &self.general_column_annotations
     }
 
+    /// Returns the stable identifiers of the columns
+    pub fn column_ids(&self) -> &HashMap<metadata::Column, ColumnId> {
+        &self.column_ids
+    }
+
     /// Returns advice queries
     pub fn advice_queries(&self) -> &Vec<(Column<Advice>, Rotation)> {
         &self.advice_queries
@@ -2764,7 +3311,10 @@ impl<'a, F: Field> VirtualCells<'a, F> {
 
 #[cfg(test)]
 mod tests {
-    use super::Expression;
+    use super::{ConstraintSystem, Expression, FirstPhase, SecondPhase};
+    use halo2_middleware::circuit::ConstraintSystemV2Backend;
+    use halo2_middleware::metadata::ColumnId;
+    use halo2_middleware::poly::Rotation;
     use halo2curves::bn256::Fr;
 
     #[test]
@@ -2804,4 +3354,135 @@ mod tests {
 
         assert_eq!(happened, expected);
     }
+
+    #[test]
+    fn lookup_any_rotated_table() {
+        let mut cs = ConstraintSystem::<Fr>::default();
+        let a = cs.advice_column();
+        let t = cs.advice_column();
+        let f = cs.fixed_column();
+
+        // Looks up pairs of consecutive values of `a` in a window of `t`, scaled by `f`.
+        cs.lookup_any("window", |meta| {
+            let a_cur = meta.query_advice(a, Rotation::cur());
+            let a_next = meta.query_advice(a, Rotation::next());
+            let t_prev = meta.query_advice(t, Rotation::prev());
+            let t_cur = meta.query_advice(t, Rotation::cur());
+            let t_next = meta.query_advice(t, Rotation::next());
+            let f_next = meta.query_fixed(f, Rotation::next());
+            vec![(a_cur, t_prev * f_next), (a_next, t_cur + t_next)]
+        });
+
+        for at in [Rotation::prev(), Rotation::cur(), Rotation::next()] {
+            assert!(cs.advice_queries().contains(&(t, at)));
+        }
+        assert!(cs.fixed_queries().contains(&(f, Rotation::next())));
+        assert_eq!(cs.num_advice_queries[t.index()], 3);
+
+        // The degree is 2 + 1 + 2, from the product on the table side.
+        assert_eq!(cs.degree(), 5);
+    }
+
+    #[test]
+    fn column_ids() {
+        let mut cs = ConstraintSystem::<Fr>::default();
+        let a = cs.advice_column_named("a");
+        let f = cs.fixed_column();
+        cs.set_column_id(a, ColumnId::new("state"));
+        cs.set_column_id(f, ColumnId::new("round constants"));
+
+        // Existing names are kept.
+        assert_eq!(cs.column_name(a), Some("a"));
+        assert_eq!(cs.column_name(f), Some("round constants"));
+        let id = cs.column_id(f).unwrap().clone();
+        assert_eq!(cs.column_by_id(id.uuid), Some(f.into()));
+        assert_eq!(cs.column_by_id(0), None);
+
+        // The identifiers are carried through the middleware constraint system.
+        let cs2 = ConstraintSystemV2Backend::from(cs);
+        assert_eq!(cs2.column_ids.len(), 2);
+        let cs = ConstraintSystem::from(cs2);
+        assert_eq!(cs.column_id(f), Some(&id));
+    }
+
+    #[test]
+    #[should_panic(expected = "already identifies")]
+    fn duplicate_column_id() {
+        let mut cs = ConstraintSystem::<Fr>::default();
+        let a = cs.advice_column();
+        let b = cs.advice_column();
+        cs.set_column_id(a, ColumnId::new("a"));
+        cs.set_column_id(b, ColumnId::new("a"));
+    }
+
+    #[test]
+    fn challenge_phases() {
+        let mut cs = ConstraintSystem::<Fr>::default();
+        let a = cs.advice_column_named("a");
+        let b = cs.advice_column_named("b");
+        let batch = cs.challenge_usable_after(FirstPhase);
+        let c = cs.challenge_for_phase(SecondPhase);
+        let z = cs.advice_column_in(SecondPhase);
+
+        // The challenge is known when the second phase column is assigned.
+        cs.create_gate("z = a * c", |meta| {
+            let a = meta.query_advice(a, Rotation::cur());
+            let z = meta.query_advice(z, Rotation::cur());
+            vec![z - a * meta.query_challenge(c)]
+        });
+        cs.lookup_any("(a + c, z)", |meta| {
+            let a = meta.query_advice(a, Rotation::cur());
+            let z = meta.query_advice(z, Rotation::cur());
+            vec![(a + meta.query_challenge(c), z)]
+        });
+        // Batching first phase constraints is allowed with the unchecked challenges.
+        cs.create_gate("a = b", |meta| {
+            let a = meta.query_advice(a, Rotation::cur());
+            let b = meta.query_advice(b, Rotation::cur());
+            vec![a.clone() - b.clone() + meta.query_challenge(batch) * (a - b)]
+        });
+        assert!(cs.check_challenge_phases().is_ok());
+
+        // The challenge is unknown when the first phase columns are assigned.
+        let mut shuffled = cs.clone();
+        shuffled.shuffle("(a * c, b * c)", |meta| {
+            let a = meta.query_advice(a, Rotation::cur());
+            let b = meta.query_advice(b, Rotation::cur());
+            let c = meta.query_challenge(c);
+            vec![(a * c.clone(), b * c)]
+        });
+        let error = shuffled.check_challenge_phases().unwrap_err().to_string();
+        assert!(
+            error.contains("shuffle \"(a * c, b * c)\" uses challenge 1"),
+            "{error}"
+        );
+        assert!(
+            error.contains("only advice 0 \"a\" (phase 0), advice 1 \"b\" (phase 0)"),
+            "{error}"
+        );
+
+        cs.create_gate("a' = a * c", |meta| {
+            let a_next = meta.query_advice(a, Rotation::next());
+            let a = meta.query_advice(a, Rotation::cur());
+            vec![("running product", a_next - a * meta.query_challenge(c))]
+        });
+        let error = cs.check_challenge_phases().unwrap_err().to_string();
+        assert!(
+            error.contains("\"running product\" of gate \"a' = a * c\""),
+            "{error}"
+        );
+        assert!(error.contains("advice 0 \"a\" (phase 0)"), "{error}");
+    }
+
+    #[test]
+    fn extra_unusable_rows() {
+        let cs = |extra| {
+            let mut cs = ConstraintSystem::<Fr>::default();
+            cs.set_extra_unusable_rows(extra);
+            cs.advice_column();
+            cs
+        };
+        assert_eq!(cs(0).usable_rows(5).len(), cs(3).usable_rows(5).len() + 3);
+        assert_eq!(cs(3).extra_unusable_rows(), 3);
+    }
 }
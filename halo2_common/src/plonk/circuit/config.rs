@@ -0,0 +1,267 @@
+//! Declarative circuit configuration.
+
+/// Declares a circuit configuration struct from a column specification.
+///
+/// Each field is declared with the kind of resource it holds, optionally followed by a
+/// list of options in parentheses:
+///
+/// - `advice`: a [`Column<Advice>`]. Options: `phase = <Phase>`, `unblinded`, `equality`.
+/// - `fixed`: a [`Column<Fixed>`]. Options: `equality`, `constant`.
+/// - `instance`: a [`Column<Instance>`]. Options: `equality`.
+/// - `selector`: a [`Selector`]. Options: `complex`.
+/// - `table`: a [`TableColumn`].
+///
+/// The macro generates the struct (deriving `Clone`, `Copy` and `Debug`) and an inherent
+/// `configure` function that allocates the resources in declaration order, enables the
+/// requested options and annotates every column with the name of its field.  The
+/// annotations are also available through the generated `annotations` function.
+///
+/// ```
+/// use halo2_common::circuit_config;
+/// use halo2_common::plonk::{ConstraintSystem, SecondPhase};
+/// use halo2curves::pasta::Fp;
+///
+/// circuit_config! {
+///     /// Configuration of my chip.
+///     pub struct MyConfig {
+///         pub a: advice(equality),
+///         pub b: advice(phase = SecondPhase),
+///         pub c: fixed(constant),
+///         pub pi: instance(equality),
+///         pub s: selector,
+///         pub range: table,
+///     }
+/// }
+///
+/// let mut meta = ConstraintSystem::<Fp>::default();
+/// let config = MyConfig::configure(&mut meta);
+/// assert_eq!(meta.num_advice_columns(), 2);
+/// assert_eq!(config.b.column_type().phase(), 1);
+/// assert_eq!(MyConfig::annotations()[0], "a");
+/// ```
+///
+/// [`Column<Advice>`]: crate::plonk::circuit::Column
+/// [`Column<Fixed>`]: crate::plonk::circuit::Column
+/// [`Column<Instance>`]: crate::plonk::circuit::Column
+/// [`Selector`]: crate::plonk::Selector
+/// [`TableColumn`]: crate::plonk::TableColumn
+#[macro_export]
+macro_rules! circuit_config {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_attr:meta])*
+                $field_vis:vis $field:ident : $kind:ident $( ( $($opt:tt)* ) )?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug)]
+        $vis struct $name {
+            $(
+                $(#[$field_attr])*
+                $field_vis $field: $crate::__circuit_config_type!($kind),
+            )*
+        }
+
+        impl $name {
+            /// Allocates the columns of this configuration, in declaration order.
+            pub fn configure<F: $crate::__private::Field>(
+                meta: &mut $crate::plonk::ConstraintSystem<F>,
+            ) -> Self {
+                $(
+                    let $field = $crate::__circuit_config_column!(
+                        meta,
+                        stringify!($field),
+                        $kind $( ( $($opt)* ) )?
+                    );
+                )*
+                Self { $($field,)* }
+            }
+
+            /// Returns the names of the fields of this configuration, in declaration order.
+            pub fn annotations() -> &'static [&'static str] {
+                &[$(stringify!($field),)*]
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __circuit_config_type {
+    (advice) => { $crate::plonk::circuit::Column<$crate::__private::Advice> };
+    (fixed) => { $crate::plonk::circuit::Column<$crate::__private::Fixed> };
+    (instance) => { $crate::plonk::circuit::Column<$crate::__private::Instance> };
+    (selector) => { $crate::plonk::Selector };
+    (table) => { $crate::plonk::TableColumn };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __circuit_config_column {
+    // Advice columns.
+    ($meta:ident, $name:expr, advice) => {
+        $crate::__circuit_config_column!($meta, $name, advice())
+    };
+    ($meta:ident, $name:expr, advice($($opt:tt)*)) => {
+        $crate::__circuit_config_column!(
+            @advice $meta, $name, [$crate::plonk::FirstPhase] [false] [false] $($opt)*
+        )
+    };
+    (@advice $meta:ident, $name:expr, [$phase:path] [$unblinded:tt] [$eq:tt]
+        phase = $new_phase:path $(, $($rest:tt)*)?) => {
+        $crate::__circuit_config_column!(
+            @advice $meta, $name, [$new_phase] [$unblinded] [$eq] $($($rest)*)?
+        )
+    };
+    (@advice $meta:ident, $name:expr, [$phase:path] [$unblinded:tt] [$eq:tt]
+        unblinded $(, $($rest:tt)*)?) => {
+        $crate::__circuit_config_column!(
+            @advice $meta, $name, [$phase] [true] [$eq] $($($rest)*)?
+        )
+    };
+    (@advice $meta:ident, $name:expr, [$phase:path] [$unblinded:tt] [$eq:tt]
+        equality $(, $($rest:tt)*)?) => {
+        $crate::__circuit_config_column!(
+            @advice $meta, $name, [$phase] [$unblinded] [true] $($($rest)*)?
+        )
+    };
+    (@advice $meta:ident, $name:expr, [$phase:path] [$unblinded:tt] [$eq:tt]) => {{
+        let column = if $unblinded {
+            $meta.unblinded_advice_column_in($phase)
+        } else {
+            $meta.advice_column_in($phase)
+        };
+        if $eq {
+            $meta.enable_equality(column);
+        }
+        $meta.annotate_lookup_any_column(column, || $name);
+        column
+    }};
+
+    // Fixed columns.
+    ($meta:ident, $name:expr, fixed) => {
+        $crate::__circuit_config_column!($meta, $name, fixed())
+    };
+    ($meta:ident, $name:expr, fixed($($opt:tt)*)) => {
+        $crate::__circuit_config_column!(@fixed $meta, $name, [false] [false] $($opt)*)
+    };
+    (@fixed $meta:ident, $name:expr, [$eq:tt] [$constant:tt] equality $(, $($rest:tt)*)?) => {
+        $crate::__circuit_config_column!(@fixed $meta, $name, [true] [$constant] $($($rest)*)?)
+    };
+    (@fixed $meta:ident, $name:expr, [$eq:tt] [$constant:tt] constant $(, $($rest:tt)*)?) => {
+        $crate::__circuit_config_column!(@fixed $meta, $name, [$eq] [true] $($($rest)*)?)
+    };
+    (@fixed $meta:ident, $name:expr, [$eq:tt] [$constant:tt]) => {{
+        let column = $meta.fixed_column();
+        if $eq {
+            $meta.enable_equality(column);
+        }
+        // `enable_constant` also enables equality on the column.
+        if $constant {
+            $meta.enable_constant(column);
+        }
+        $meta.annotate_lookup_any_column(column, || $name);
+        column
+    }};
+
+    // Instance columns.
+    ($meta:ident, $name:expr, instance) => {
+        $crate::__circuit_config_column!($meta, $name, instance())
+    };
+    ($meta:ident, $name:expr, instance($($opt:tt)*)) => {
+        $crate::__circuit_config_column!(@instance $meta, $name, [false] $($opt)*)
+    };
+    (@instance $meta:ident, $name:expr, [$eq:tt] equality $(, $($rest:tt)*)?) => {
+        $crate::__circuit_config_column!(@instance $meta, $name, [true] $($($rest)*)?)
+    };
+    (@instance $meta:ident, $name:expr, [$eq:tt]) => {{
+        let column = $meta.instance_column();
+        if $eq {
+            $meta.enable_equality(column);
+        }
+        $meta.annotate_lookup_any_column(column, || $name);
+        column
+    }};
+
+    // Selectors. These are not columns until selector compression, so they can't be
+    // annotated.
+    ($meta:ident, $name:expr, selector) => {
+        $meta.selector()
+    };
+    ($meta:ident, $name:expr, selector()) => {
+        $meta.selector()
+    };
+    ($meta:ident, $name:expr, selector(complex)) => {
+        $meta.complex_selector()
+    };
+
+    // Lookup table columns.
+    ($meta:ident, $name:expr, table) => {{
+        let column = $meta.lookup_table_column();
+        $meta.annotate_lookup_column(column, || $name);
+        column
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plonk::{ConstraintSystem, SecondPhase};
+    use halo2_middleware::circuit::Any;
+    use halo2_middleware::metadata;
+    use halo2curves::pasta::Fp;
+
+    crate::circuit_config! {
+        struct TestConfig {
+            a: advice,
+            b: advice(equality, phase = SecondPhase),
+            c: advice(unblinded),
+            f: fixed(constant),
+            i: instance(equality),
+            s: selector,
+            q: selector(complex),
+            t: table,
+        }
+    }
+
+    #[test]
+    fn configure_allocates_in_order() {
+        let mut meta = ConstraintSystem::<Fp>::default();
+        let config = TestConfig::configure(&mut meta);
+
+        assert_eq!(meta.num_advice_columns(), 3);
+        assert_eq!(meta.num_fixed_columns(), 2);
+        assert_eq!(meta.num_instance_columns(), 1);
+        assert_eq!(meta.num_selectors(), 2);
+
+        assert_eq!(config.a.index(), 0);
+        assert_eq!(config.b.index(), 1);
+        assert_eq!(config.b.column_type().phase(), 1);
+        assert_eq!(config.i.index(), 0);
+        assert_eq!(meta.unblinded_advice_columns, vec![config.c.index()]);
+        assert!(config.s.is_simple());
+        assert!(!config.q.is_simple());
+
+        // `b`, `f` (as a constant column) and `i` are part of the permutation.
+        assert_eq!(meta.permutation().get_columns().len(), 3);
+        assert_eq!(meta.constants(), &vec![config.f]);
+
+        let annotation = |column_type, index| {
+            meta.general_column_annotations()
+                .get(&metadata::Column::from((column_type, index)))
+                .cloned()
+        };
+        assert_eq!(annotation(Any::advice_in(1), 1), Some("b".to_string()));
+        assert_eq!(annotation(Any::Instance, 0), Some("i".to_string()));
+        assert_eq!(
+            annotation(Any::Fixed, config.t.inner().index()),
+            Some("t".to_string())
+        );
+        assert_eq!(
+            TestConfig::annotations(),
+            &["a", "b", "c", "f", "i", "s", "q", "t"]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use super::{Constraint, Expression};
+use halo2_middleware::ff::Field;
+
+/// A builder for the constraints of a gate.
+///
+/// Every constraint added to the builder is named, so that `MockProver` failures point
+/// at the exact constraint that is not satisfied. If the builder is given a selector, it
+/// is applied to every constraint. If it is given an expected degree, adding a constraint
+/// whose degree (including the selector) exceeds it panics, which prevents the degree of
+/// the circuit from silently growing.
+///
+/// ```
+/// use halo2_common::plonk::{ConstraintSystem, GateBuilder};
+/// use halo2_middleware::poly::Rotation;
+/// use halo2curves::pasta::Fp;
+///
+/// let mut meta = ConstraintSystem::<Fp>::default();
+/// let a = meta.advice_column();
+/// let b = meta.advice_column();
+/// let s = meta.selector();
+///
+/// meta.create_gate("foo", |meta| {
+///     let a = meta.query_advice(a, Rotation::cur());
+///     let b = meta.query_advice(b, Rotation::cur());
+///     let next = meta.query_advice(b, Rotation::next());
+///
+///     GateBuilder::with_selector(meta.query_selector(s))
+///         .expected_degree(3)
+///         .require_boolean("a is boolean", a.clone())
+///         .require_zero_if("b is zero if a is set", a, b.clone())
+///         .require_equal("b is copied", next, b)
+/// });
+/// assert_eq!(meta.gates()[0].constraint_name(1), "b is zero if a is set");
+/// ```
+#[derive(Debug)]
+pub struct GateBuilder<F: Field> {
+    selector: Option<Expression<F>>,
+    expected_degree: Option<usize>,
+    constraints: Vec<Constraint<F>>,
+}
+
+impl<F: Field> Default for GateBuilder<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Field> GateBuilder<F> {
+    /// Creates a builder for constraints that are not controlled by a selector.
+    pub fn new() -> Self {
+        GateBuilder {
+            selector: None,
+            expected_degree: None,
+            constraints: vec![],
+        }
+    }
+
+    /// Creates a builder for constraints that are controlled by the given selector.
+    ///
+    /// Each constraint `c` added to the builder will be converted into the constraint
+    /// `selector * c`.
+    pub fn with_selector(selector: Expression<F>) -> Self {
+        GateBuilder {
+            selector: Some(selector),
+            ..Self::new()
+        }
+    }
+
+    /// Sets the maximum degree of the constraints of this gate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a constraint already added to the builder exceeds `degree`.
+    pub fn expected_degree(mut self, degree: usize) -> Self {
+        self.expected_degree = Some(degree);
+        for constraint in self.constraints.iter() {
+            self.check_degree(constraint);
+        }
+        self
+    }
+
+    /// Requires `poly` to evaluate to zero.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the degree of the constraint exceeds the expected degree.
+    pub fn require_zero<S: AsRef<str>>(mut self, name: S, poly: Expression<F>) -> Self {
+        let poly = match &self.selector {
+            Some(selector) => selector.clone() * poly,
+            None => poly,
+        };
+        let constraint = Constraint::from((name, poly));
+        self.check_degree(&constraint);
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Requires `poly` to evaluate to either zero or one.
+    pub fn require_boolean<S: AsRef<str>>(self, name: S, poly: Expression<F>) -> Self {
+        let one_minus_poly = Expression::Constant(F::ONE) - poly.clone();
+        self.require_zero(name, poly * one_minus_poly)
+    }
+
+    /// Requires `lhs` and `rhs` to evaluate to the same value.
+    pub fn require_equal<S: AsRef<str>>(
+        self,
+        name: S,
+        lhs: Expression<F>,
+        rhs: Expression<F>,
+    ) -> Self {
+        self.require_zero(name, lhs - rhs)
+    }
+
+    /// Requires `poly` to evaluate to zero whenever `condition` is non-zero.
+    ///
+    /// `condition` is expected to be boolean-constrained elsewhere.
+    pub fn require_zero_if<S: AsRef<str>>(
+        self,
+        name: S,
+        condition: Expression<F>,
+        poly: Expression<F>,
+    ) -> Self {
+        self.require_zero(name, condition * poly)
+    }
+
+    fn check_degree(&self, constraint: &Constraint<F>) {
+        if let Some(expected) = self.expected_degree {
+            let degree = constraint.poly.degree();
+            assert!(
+                degree <= expected,
+                "constraint \"{}\" has degree {}, which exceeds the expected degree {}",
+                constraint.name,
+                degree,
+                expected
+            );
+        }
+    }
+}
+
+impl<F: Field> IntoIterator for GateBuilder<F> {
+    type Item = Constraint<F>;
+    type IntoIter = std::vec::IntoIter<Constraint<F>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.constraints.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GateBuilder;
+    use crate::plonk::ConstraintSystem;
+    use halo2_middleware::poly::Rotation;
+    use halo2curves::pasta::Fp;
+
+    #[test]
+    fn named_constraints() {
+        let mut meta = ConstraintSystem::<Fp>::default();
+        let a = meta.advice_column();
+        let b = meta.advice_column();
+        let s = meta.selector();
+
+        meta.create_gate("gate", |meta| {
+            let a = meta.query_advice(a, Rotation::cur());
+            let b = meta.query_advice(b, Rotation::cur());
+            GateBuilder::with_selector(meta.query_selector(s))
+                .require_boolean("a is boolean", a.clone())
+                .require_equal("a == b", a, b)
+                .expected_degree(3)
+        });
+
+        let gate = &meta.gates()[0];
+        assert_eq!(gate.polynomials().len(), 2);
+        assert_eq!(gate.constraint_name(0), "a is boolean");
+        assert_eq!(gate.constraint_name(1), "a == b");
+        assert_eq!(gate.polynomials()[0].degree(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "constraint \"a is boolean\" has degree 3")]
+    fn degree_creep() {
+        let mut meta = ConstraintSystem::<Fp>::default();
+        let a = meta.advice_column();
+        let s = meta.selector();
+
+        meta.create_gate("gate", |meta| {
+            let a = meta.query_advice(a, Rotation::cur());
+            GateBuilder::with_selector(meta.query_selector(s))
+                .expected_degree(2)
+                .require_boolean("a is boolean", a)
+        });
+    }
+}
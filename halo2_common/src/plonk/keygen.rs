@@ -49,6 +49,28 @@ impl<F: Field> Assignment<F> for Assembly<F> {
         Ok(())
     }
 
+    fn enable_selector_range<A, AR>(
+        &mut self,
+        _: A,
+        selector: &Selector,
+        rows: Range<usize>,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        if rows.start < self.usable_rows.start || rows.end > self.usable_rows.end {
+            return Err(Error::not_enough_rows_available(self.k));
+        }
+
+        self.selectors[selector.0][rows].fill(true);
+
+        Ok(())
+    }
+
     fn query_instance(&self, _: Column<Instance>, row: usize) -> Result<Value<F>, Error> {
         if !self.usable_rows.contains(&row) {
             return Err(Error::not_enough_rows_available(self.k));
@@ -75,6 +97,21 @@ impl<F: Field> Assignment<F> for Assembly<F> {
         Ok(())
     }
 
+    fn assign_advice_column_slice<A, AR>(
+        &mut self,
+        _: A,
+        _: Column<Advice>,
+        _: Range<usize>,
+        _: Value<&[Assigned<F>]>,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // We only care about fixed columns here
+        Ok(())
+    }
+
     fn assign_fixed<V, VR, A, AR>(
         &mut self,
         _: A,
@@ -0,0 +1,327 @@
+//! A canonical serialization format for circuit witnesses.
+//!
+//! A [`Witness`] holds the advice values of a circuit, its instance values and the map
+//! of the regions that were assigned. It can be written by one tool (for example the
+//! `MockProver`, or a witness generation worker) and read back by another one to replay
+//! it.
+//!
+//! The encoding is, with all integers in big-endian:
+//!
+//! - the magic bytes `b"H2WT"` and a version byte;
+//! - `k` as a `u32`;
+//! - the number of advice columns as a `u32`, and for each of them its phase as a `u8`
+//!   followed by `2^k` cells, each one a `u8` tag (`0` for an unassigned cell, `1` for
+//!   an assigned one) followed by the field element if assigned;
+//! - the number of instance columns as a `u32`, and for each of them its length as a
+//!   `u32` followed by the field elements;
+//! - the number of regions as a `u32`, and for each of them the length of its name as a
+//!   `u32`, its UTF-8 name and its rows as a `u8` tag followed, if `1`, by the first and
+//!   last rows as `u64`s.
+//!
+//! Field elements are encoded according to the given [`SerdeFormat`].
+
+use std::io;
+
+use crate::helpers::SerdePrimeField;
+use crate::SerdeFormat;
+
+const MAGIC: &[u8; 4] = b"H2WT";
+
+// Current version of the witness format
+const VERSION: u8 = 0x01;
+
+/// The witness of a circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Witness<F> {
+    /// The circuit has `2^k` rows.
+    pub k: u32,
+    /// The phase of each advice column.
+    pub advice_phases: Vec<u8>,
+    /// The advice cells, arranged as `[column][row]`. Unassigned cells are `None`.
+    pub advice: Vec<Vec<Option<F>>>,
+    /// The instance values, arranged as `[column][row]`.
+    pub instance: Vec<Vec<F>>,
+    /// The regions that were assigned.
+    pub regions: Vec<WitnessRegion>,
+}
+
+/// A region of a [`Witness`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WitnessRegion {
+    /// The name of the region.
+    pub name: String,
+    /// The first and last rows of the region, if it assigned any cell.
+    pub rows: Option<(usize, usize)>,
+}
+
+fn invalid_data(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn write_u32<W: io::Write>(writer: &mut W, value: usize) -> io::Result<()> {
+    let value = u32::try_from(value).map_err(|_| invalid_data("length overflows a u32"))?;
+    writer.write_all(&value.to_be_bytes())
+}
+
+fn read_u32<R: io::Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u8<R: io::Read>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0u8; 1];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_u64<R: io::Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Writes `witness` to `writer`.
+///
+/// Returns an error if an advice column doesn't have `2^k` cells, or if the phases
+/// don't match the advice columns.
+pub fn write_witness<W: io::Write, F: SerdePrimeField>(
+    witness: &Witness<F>,
+    writer: &mut W,
+    format: SerdeFormat,
+) -> io::Result<()> {
+    if witness.advice_phases.len() != witness.advice.len() {
+        return Err(invalid_data("one phase is required per advice column"));
+    }
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+    writer.write_all(&witness.k.to_be_bytes())?;
+
+    let n = 1usize << witness.k;
+    write_u32(writer, witness.advice.len())?;
+    for (phase, column) in witness.advice_phases.iter().zip(witness.advice.iter()) {
+        if column.len() != n {
+            return Err(invalid_data("advice columns must have 2^k cells"));
+        }
+        writer.write_all(&[*phase])?;
+        for cell in column {
+            match cell {
+                Some(value) => {
+                    writer.write_all(&[1])?;
+                    value.write(writer, format)?;
+                }
+                None => writer.write_all(&[0])?,
+            }
+        }
+    }
+
+    write_u32(writer, witness.instance.len())?;
+    for column in witness.instance.iter() {
+        write_u32(writer, column.len())?;
+        for value in column {
+            value.write(writer, format)?;
+        }
+    }
+
+    write_u32(writer, witness.regions.len())?;
+    for region in witness.regions.iter() {
+        write_u32(writer, region.name.len())?;
+        writer.write_all(region.name.as_bytes())?;
+        match region.rows {
+            Some((start, end)) => {
+                writer.write_all(&[1])?;
+                writer.write_all(&(start as u64).to_be_bytes())?;
+                writer.write_all(&(end as u64).to_be_bytes())?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a witness written by [`write_witness`] from `reader`.
+///
+/// `format` must be the format the witness was written with. Returns an error if `k`
+/// exceeds the two-adicity of the field, or if an instance column or a region doesn't fit
+/// in the `2^k` rows.
+pub fn read_witness<R: io::Read, F: SerdePrimeField>(
+    reader: &mut R,
+    format: SerdeFormat,
+) -> io::Result<Witness<F>> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a witness"));
+    }
+    if read_u8(reader)? != VERSION {
+        return Err(invalid_data("unexpected witness format version"));
+    }
+
+    let k = read_u32(reader)?;
+    if k > F::S {
+        return Err(invalid_data("k exceeds the two-adicity of the field"));
+    }
+    let n = 1usize << k;
+
+    // The lengths below come from the reader, so the vectors grow as their elements are
+    // read instead of being allocated upfront.
+    let num_advice = read_u32(reader)?;
+    let mut advice_phases = vec![];
+    let mut advice = vec![];
+    for _ in 0..num_advice {
+        advice_phases.push(read_u8(reader)?);
+        let mut column = vec![];
+        for _ in 0..n {
+            column.push(match read_u8(reader)? {
+                0 => None,
+                1 => Some(F::read(reader, format)?),
+                _ => return Err(invalid_data("invalid advice cell tag")),
+            });
+        }
+        advice.push(column);
+    }
+
+    let num_instance = read_u32(reader)?;
+    let mut instance = vec![];
+    for _ in 0..num_instance {
+        let len = read_u32(reader)? as usize;
+        if len > n {
+            return Err(invalid_data(
+                "instance columns must have at most 2^k values",
+            ));
+        }
+        let mut column = vec![];
+        for _ in 0..len {
+            column.push(F::read(reader, format)?);
+        }
+        instance.push(column);
+    }
+
+    let num_regions = read_u32(reader)?;
+    let mut regions = vec![];
+    for _ in 0..num_regions {
+        let len = read_u32(reader)? as u64;
+        let mut name = vec![];
+        if reader.by_ref().take(len).read_to_end(&mut name)? as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let name = String::from_utf8(name).map_err(|_| invalid_data("region name is not UTF-8"))?;
+        let rows = match read_u8(reader)? {
+            0 => None,
+            1 => {
+                let (start, end) = (read_u64(reader)?, read_u64(reader)?);
+                if start > end || end >= n as u64 {
+                    return Err(invalid_data("region rows must be within the 2^k rows"));
+                }
+                Some((start as usize, end as usize))
+            }
+            _ => return Err(invalid_data("invalid region rows tag")),
+        };
+        regions.push(WitnessRegion { name, rows });
+    }
+
+    Ok(Witness {
+        k,
+        advice_phases,
+        advice,
+        instance,
+        regions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_witness, write_witness, Witness, WitnessRegion};
+    use crate::SerdeFormat;
+    use halo2curves::pasta::Fp;
+
+    #[test]
+    fn roundtrip() {
+        let witness = Witness {
+            k: 2,
+            advice_phases: vec![0, 1],
+            advice: vec![
+                vec![Some(Fp::from(1)), None, Some(Fp::from(3)), None],
+                vec![None, None, None, Some(-Fp::one())],
+            ],
+            instance: vec![vec![Fp::from(5)], vec![]],
+            regions: vec![
+                WitnessRegion {
+                    name: "region".to_string(),
+                    rows: Some((0, 2)),
+                },
+                WitnessRegion {
+                    name: "empty".to_string(),
+                    rows: None,
+                },
+            ],
+        };
+
+        for format in [
+            SerdeFormat::Processed,
+            SerdeFormat::RawBytes,
+            SerdeFormat::RawBytesUnchecked,
+        ] {
+            let mut bytes = vec![];
+            write_witness(&witness, &mut bytes, format).unwrap();
+            let read = read_witness::<_, Fp>(&mut &bytes[..], format).unwrap();
+            assert_eq!(read, witness);
+        }
+
+        let mut bytes = vec![];
+        write_witness(&witness, &mut bytes, SerdeFormat::Processed).unwrap();
+        bytes[0] = b'X';
+        assert!(read_witness::<_, Fp>(&mut &bytes[..], SerdeFormat::Processed).is_err());
+    }
+
+    #[test]
+    fn malformed() {
+        let witness = |k, instance, rows| Witness {
+            k,
+            advice_phases: vec![],
+            advice: vec![],
+            instance: vec![instance],
+            regions: vec![WitnessRegion {
+                name: "region".to_string(),
+                rows,
+            }],
+        };
+        let read = |witness: &Witness<Fp>| {
+            let mut bytes = vec![];
+            write_witness(witness, &mut bytes, SerdeFormat::Processed).unwrap();
+            read_witness::<_, Fp>(&mut &bytes[..], SerdeFormat::Processed)
+        };
+        let error = |witness: &Witness<Fp>| read(witness).unwrap_err().to_string();
+
+        assert!(read(&witness(2, vec![Fp::one(); 4], Some((0, 3)))).is_ok());
+        assert_eq!(
+            error(&witness(33, vec![], None)),
+            "k exceeds the two-adicity of the field"
+        );
+        assert_eq!(
+            error(&witness(2, vec![Fp::one(); 5], None)),
+            "instance columns must have at most 2^k values"
+        );
+        for rows in [(0, 4), (2, 1)] {
+            assert_eq!(
+                error(&witness(2, vec![], Some(rows))),
+                "region rows must be within the 2^k rows"
+            );
+        }
+
+        // A name longer than the remaining bytes is not allocated upfront.
+        let mut bytes = vec![];
+        write_witness(
+            &witness(2, vec![], None),
+            &mut bytes,
+            SerdeFormat::Processed,
+        )
+        .unwrap();
+        let name = bytes.len() - 1 - "region".len() - 4;
+        bytes[name..name + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        let error = read_witness::<_, Fp>(&mut &bytes[..], SerdeFormat::Processed).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
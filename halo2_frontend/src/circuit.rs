@@ -1,18 +1,22 @@
 //! Traits and structs for implementing circuit components.
 
+use crate::dev::cost::Layout;
+use halo2_common::helpers::wipe;
 use halo2_common::plonk::{
     circuit::{Challenge, Column},
     permutation,
     sealed::{self, SealedPhase},
-    Assigned, Assignment, Circuit, ConstraintSystem, Error, FirstPhase, FloorPlanner, SecondPhase,
-    Selector, ThirdPhase,
+    Assigned, Assignment, Circuit, ConstraintSystem, Error, FirstPhase, FloorPlanner, NamePolicy,
+    SecondPhase, Selector, ThirdPhase,
 };
 use halo2_middleware::circuit::{Advice, Any, CompiledCircuitV2, Fixed, Instance, PreprocessingV2};
 use halo2_middleware::ff::{BatchInvert, Field};
 use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::ops::RangeTo;
+use std::ops::{Range, RangeTo};
+use std::sync::Mutex;
+use tracing::info_span;
 
 pub mod floor_planner;
 mod table_layouter;
@@ -21,6 +25,184 @@ mod table_layouter;
 pub use halo2_common::circuit::floor_planner::single_pass::SimpleFloorPlanner;
 pub use halo2_common::circuit::{layouter, Layouter, Value};
 
+/// The constraint system and the configuration of a circuit, obtained by running
+/// `configure` once.
+///
+/// `configure` builds the gates, lookups and tables of a circuit, which can be expensive.
+/// Entry points that would otherwise run it, such as [`compile_circuit_cached`],
+/// `MockProver::run_cached`, `CircuitCost::measure_cached`, `CircuitLayout::render_cached` or
+/// the `keygen_*_cached` functions, accept a `CompiledConstraintSystem` instead, so that
+/// `configure` runs once per circuit type when it is built and shared between all of them.
+///
+/// When the `circuit-params` feature is enabled, the configuration depends on the params of
+/// the circuit it was built from, so it must only be used with circuits that have the same
+/// params.
+pub struct CompiledConstraintSystem<F: Field, ConcreteCircuit: Circuit<F>> {
+    cs: ConstraintSystem<F>,
+    config: ConcreteCircuit::Config,
+    prune_unused_equality: bool,
+    prune_dead_gates: bool,
+}
+
+impl<F: Field, ConcreteCircuit: Circuit<F>> CompiledConstraintSystem<F, ConcreteCircuit> {
+    /// Runs `configure` for `circuit`.
+    #[cfg_attr(not(feature = "circuit-params"), allow(unused_variables))]
+    pub fn new(circuit: &ConcreteCircuit) -> Self {
+        let mut cs = ConstraintSystem::default();
+        #[cfg(feature = "circuit-params")]
+        let config = ConcreteCircuit::configure_with_params(&mut cs, circuit.params());
+        #[cfg(not(feature = "circuit-params"))]
+        let config = ConcreteCircuit::configure(&mut cs);
+        CompiledConstraintSystem {
+            cs,
+            config,
+            prune_unused_equality: false,
+            prune_dead_gates: false,
+        }
+    }
+
+    /// Removes the columns without any copy constraint from the permutation argument when
+    /// the circuit is compiled, for instance by the `keygen_*_cached` functions.
+    ///
+    /// Columns with equality enabled cost a commitment and evaluations in every proof, and
+    /// can enlarge the permutation argument by a chunk, whether or not they are used, see
+    /// `dev::EqualityReport`. The columns are pruned according to the copy constraints
+    /// made when compiling the circuit, so the verifying and proving keys must both be
+    /// generated with this option.
+    pub fn prune_unused_equality(mut self) -> Self {
+        self.prune_unused_equality = true;
+        self
+    }
+
+    /// Removes the gates whose selectors are never enabled, see `Gate::is_dead`, when the
+    /// circuit is compiled, for instance by the `keygen_*_cached` functions.
+    ///
+    /// Such gates constrain nothing but are still evaluated by the prover and the
+    /// verifier, see `dev::gate_coverage`. The gates are pruned according to the selectors
+    /// enabled when compiling the circuit, so the verifying and proving keys must both be
+    /// generated with this option.
+    pub fn prune_dead_gates(mut self) -> Self {
+        self.prune_dead_gates = true;
+        self
+    }
+
+    /// Returns the constraint system, before selectors are converted to fixed columns.
+    pub fn cs(&self) -> &ConstraintSystem<F> {
+        &self.cs
+    }
+
+    /// Returns the configuration of the circuit.
+    pub fn config(&self) -> &ConcreteCircuit::Config {
+        &self.config
+    }
+
+    /// Returns copies of the constraint system and the configuration, to be consumed by
+    /// an entry point.
+    pub fn to_parts(&self) -> (ConstraintSystem<F>, ConcreteCircuit::Config) {
+        (self.cs.clone(), self.config.clone())
+    }
+}
+
+impl<F: Field, ConcreteCircuit: Circuit<F>> Clone for CompiledConstraintSystem<F, ConcreteCircuit> {
+    fn clone(&self) -> Self {
+        CompiledConstraintSystem {
+            cs: self.cs.clone(),
+            config: self.config.clone(),
+            prune_unused_equality: self.prune_unused_equality,
+            prune_dead_gates: self.prune_dead_gates,
+        }
+    }
+}
+
+impl<F: Field, ConcreteCircuit: Circuit<F>> Debug for CompiledConstraintSystem<F, ConcreteCircuit> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompiledConstraintSystem")
+            .field("cs", &self.cs)
+            .field("prune_unused_equality", &self.prune_unused_equality)
+            .field("prune_dead_gates", &self.prune_dead_gates)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A circuit that is synthesized with the floor planner `P` instead of its own
+/// [`Circuit::FloorPlanner`].
+///
+/// This allows comparing floor planners on an existing circuit without changing its
+/// implementation. The layout, and so the keys, depend on the floor planner: proofs must
+/// be created with the same floor planner as the keys.
+pub struct WithFloorPlanner<'c, C, P> {
+    circuit: CircuitRef<'c, C>,
+    _marker: std::marker::PhantomData<P>,
+}
+
+enum CircuitRef<'c, C> {
+    Borrowed(&'c C),
+    Owned(C),
+}
+
+impl<'c, C, P: FloorPlanner> WithFloorPlanner<'c, C, P> {
+    /// Wraps `circuit`.
+    pub fn new(circuit: &'c C) -> Self {
+        WithFloorPlanner {
+            circuit: CircuitRef::Borrowed(circuit),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Wraps `circuit`, taking ownership of it.
+    pub fn from_owned(circuit: C) -> Self {
+        WithFloorPlanner {
+            circuit: CircuitRef::Owned(circuit),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the wrapped circuit.
+    pub fn inner(&self) -> &C {
+        match &self.circuit {
+            CircuitRef::Borrowed(circuit) => circuit,
+            CircuitRef::Owned(circuit) => circuit,
+        }
+    }
+}
+
+impl<'c, C, P> Debug for WithFloorPlanner<'c, C, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WithFloorPlanner")
+            .field("floor_planner", &std::any::type_name::<P>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'c, F: Field, C: Circuit<F>, P: FloorPlanner> Circuit<F> for WithFloorPlanner<'c, C, P> {
+    type Config = C::Config;
+    type FloorPlanner = P;
+    #[cfg(feature = "circuit-params")]
+    type Params = C::Params;
+
+    fn without_witnesses(&self) -> Self {
+        WithFloorPlanner::from_owned(self.inner().without_witnesses())
+    }
+
+    #[cfg(feature = "circuit-params")]
+    fn params(&self) -> Self::Params {
+        self.inner().params()
+    }
+
+    #[cfg(feature = "circuit-params")]
+    fn configure_with_params(meta: &mut ConstraintSystem<F>, params: Self::Params) -> Self::Config {
+        C::configure_with_params(meta, params)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        C::configure(meta)
+    }
+
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<F>) -> Result<(), Error> {
+        self.inner().synthesize(config, layouter)
+    }
+}
+
 /// Compile a circuit.  Runs configure and synthesize on the circuit in order to materialize the
 /// circuit into its columns and the column configuration; as well as doing the fixed column and
 /// copy constraints assignments.  The output of this function can then be used for the key
@@ -39,24 +221,45 @@ pub fn compile_circuit<F: Field, ConcreteCircuit: Circuit<F>>(
     ),
     Error,
 > {
+    compile_circuit_cached(
+        k,
+        circuit,
+        &CompiledConstraintSystem::new(circuit),
+        compress_selectors,
+    )
+}
+
+/// Compiles a circuit like [`compile_circuit`], using the result of `configure` cached in
+/// `compiled_cs` instead of running it again.
+#[allow(clippy::type_complexity)]
+pub fn compile_circuit_cached<F: Field, ConcreteCircuit: Circuit<F>>(
+    k: u32,
+    circuit: &ConcreteCircuit,
+    compiled_cs: &CompiledConstraintSystem<F, ConcreteCircuit>,
+    compress_selectors: bool,
+) -> Result<
+    (
+        CompiledCircuitV2<F>,
+        ConcreteCircuit::Config,
+        ConstraintSystem<F>,
+    ),
+    Error,
+> {
+    let _span = info_span!("compile_circuit", k).entered();
     let n = 2usize.pow(k);
-    let mut cs = ConstraintSystem::default();
-    #[cfg(feature = "circuit-params")]
-    let config = ConcreteCircuit::configure_with_params(&mut cs, circuit.params());
-    #[cfg(not(feature = "circuit-params"))]
-    let config = ConcreteCircuit::configure(&mut cs);
-    let cs = cs;
+    let (mut cs, config) = compiled_cs.to_parts();
 
     if n < cs.minimum_rows() {
         return Err(Error::not_enough_rows_available(k));
     }
+    cs.check_challenge_phases()?;
 
     let mut assembly = halo2_common::plonk::keygen::Assembly {
         k,
         fixed: vec![vec![F::ZERO.into(); n]; cs.num_fixed_columns],
         permutation: permutation::Assembly::new(n, &cs.permutation),
         selectors: vec![vec![false; n]; cs.num_selectors],
-        usable_rows: 0..n - (cs.blinding_factors() + 1),
+        usable_rows: cs.usable_rows(k),
         _marker: std::marker::PhantomData,
     };
 
@@ -68,6 +271,22 @@ pub fn compile_circuit<F: Field, ConcreteCircuit: Circuit<F>>(
         cs.constants.clone(),
     )?;
 
+    if compiled_cs.prune_unused_equality {
+        let copied: BTreeSet<Column<Any>> = assembly
+            .permutation
+            .copies
+            .iter()
+            .flat_map(|(left, right)| [Column::from(left.column), Column::from(right.column)])
+            .collect();
+        cs.permutation
+            .columns
+            .retain(|column| copied.contains(column));
+    }
+
+    if compiled_cs.prune_dead_gates {
+        cs.gates.retain(|gate| !gate.is_dead(&assembly.selectors));
+    }
+
     let mut fixed = batch_invert_assigned(assembly.fixed);
     let (cs, selector_polys) = if compress_selectors {
         cs.compress_selectors(assembly.selectors.clone())
@@ -95,6 +314,187 @@ pub fn compile_circuit<F: Field, ConcreteCircuit: Circuit<F>>(
     ))
 }
 
+/// Compiles a circuit like [`compile_circuit_cached`] for each `k` of `ks`, synthesizing
+/// it only once.
+///
+/// The circuit is synthesized, and its selectors compressed, at the smallest `k`, and
+/// its fixed columns are then padded with zeros to the size of each `k`. `synthesize`
+/// doesn't know the size of the circuit, so its layout is the same for every `k` it fits
+/// in: the results are those of [`compile_circuit_cached`] at each `k`.
+pub fn compile_circuit_multi_k<F: Field, ConcreteCircuit: Circuit<F>>(
+    ks: &[u32],
+    circuit: &ConcreteCircuit,
+    compiled_cs: &CompiledConstraintSystem<F, ConcreteCircuit>,
+    compress_selectors: bool,
+) -> Result<Vec<CompiledCircuitV2<F>>, Error> {
+    let Some(&min_k) = ks.iter().min() else {
+        return Ok(vec![]);
+    };
+    let (compiled, _, _) = compile_circuit_cached(min_k, circuit, compiled_cs, compress_selectors)?;
+    Ok(ks
+        .iter()
+        .map(|&k| {
+            let mut compiled = compiled.clone();
+            for column in compiled.preprocessing.fixed.iter_mut() {
+                column.resize(1 << k, F::ZERO);
+            }
+            compiled
+        })
+        .collect())
+}
+
+/// Returns the smallest `k` for which `circuit` fits, up to `max_k`.
+///
+/// This runs `configure` and a shape-only synthesis of the circuit (no witness or fixed
+/// values are computed) at size `2^max_k`, and accounts for the rows used by regions,
+/// lookup tables and constants, as well as the rows reserved for blinding factors.
+/// Selector compression only adds fixed columns, so it doesn't change the result.
+///
+/// Rows that are only used by instance values are not known at synthesis time, and are
+/// not taken into account.
+///
+/// Returns `Error::NotEnoughRowsAvailable` if the circuit doesn't fit in `2^max_k` rows.
+pub fn minimum_k<F: Field, ConcreteCircuit: Circuit<F>>(
+    circuit: &ConcreteCircuit,
+    max_k: u32,
+) -> Result<u32, Error> {
+    let mut cs = ConstraintSystem::default();
+    #[cfg(feature = "circuit-params")]
+    let config = ConcreteCircuit::configure_with_params(&mut cs, circuit.params());
+    #[cfg(not(feature = "circuit-params"))]
+    let config = ConcreteCircuit::configure(&mut cs);
+
+    let n = 1usize << max_k;
+    let mut layout = Layout::new(max_k, n, cs.num_selectors);
+    ConcreteCircuit::FloorPlanner::synthesize(&mut layout, circuit, config, cs.constants.clone())?;
+
+    // The used rows must all be usable rows, which excludes the blinding rows and the
+    // last row reserved for `l_last`.
+    let required_rows = std::cmp::max(
+        layout.total_rows + cs.blinding_factors() + 1,
+        cs.minimum_rows(),
+    );
+    let k = required_rows.next_power_of_two().trailing_zeros();
+    if k > max_k {
+        return Err(Error::not_enough_rows_available(max_k));
+    }
+    Ok(k)
+}
+
+/// The advice cells assigned and the selectors enabled during a synthesis, tracked by
+/// [`WitnessCalculator::check_assignments`].
+#[derive(Debug)]
+pub(crate) struct AssignmentTracker {
+    assigned: Vec<Vec<bool>>,
+    /// The name of each region, if the names are kept.
+    regions: Vec<Option<String>>,
+    current_region: Option<usize>,
+    enabled_selectors: Vec<(Selector, usize, Option<usize>)>,
+}
+
+impl AssignmentTracker {
+    fn new(num_advice_columns: usize, n: usize) -> Self {
+        AssignmentTracker {
+            assigned: vec![vec![false; n]; num_advice_columns],
+            regions: vec![],
+            current_region: None,
+            enabled_selectors: vec![],
+        }
+    }
+
+    /// Checks that every advice cell of the phase `phase` that is queried by a gate, at a
+    /// row where one of the selectors of the gate is enabled, was assigned.
+    fn check<F: Field>(&self, cs: &ConstraintSystem<F>, phase: sealed::Phase) -> Result<(), Error> {
+        let n = self.assigned.first().map(Vec::len).unwrap_or_default() as i32;
+        let mut unassigned = self
+            .enabled_selectors
+            .iter()
+            .flat_map(|(selector, row, region)| {
+                cs.gates
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, gate)| gate.queried_selectors().contains(selector))
+                    .flat_map(move |(gate_index, gate)| {
+                        gate.queried_cells().iter().filter_map(move |cell| {
+                            let cell_row = ((*row as i32 + n + cell.rotation.0) % n) as usize;
+                            let column = cell.column.index();
+                            let checked = matches!(cell.column.column_type(), Any::Advice(_))
+                                && cs.advice_column_phase[column] == phase;
+                            (checked && !self.assigned[column][cell_row]).then_some((
+                                gate_index,
+                                gate.name(),
+                                *row,
+                                *region,
+                                cell,
+                                cell_row,
+                            ))
+                        })
+                    })
+            });
+
+        match unassigned.next() {
+            None => Ok(()),
+            Some((gate_index, gate, row, region, cell, cell_row)) => {
+                let region = match region {
+                    Some(region) => match &self.regions[region] {
+                        Some(name) => format!("region \"{}\"", name),
+                        None => format!("region {}", region),
+                    },
+                    None => "no region".to_string(),
+                };
+                Err(Error::Other(format!(
+                    "advice column {} is not assigned at row {}, which is queried by gate {} \
+                     (\"{}\") enabled at row {} in {}, and {} other queries of unassigned cells",
+                    cell.column.index(),
+                    cell_row,
+                    gate_index,
+                    gate,
+                    row,
+                    region,
+                    unassigned.count()
+                )))
+            }
+        }
+    }
+}
+
+/// The first challenge whose value was requested while synthesizing a phase in which it is
+/// not known yet, which explains why an advice cell of that phase is assigned an unknown
+/// value.
+///
+/// A challenge usable after a phase is only squeezed once the advice columns of that phase
+/// are committed, so its value can't be used to assign them or the columns of earlier
+/// phases.
+#[derive(Debug, Default)]
+pub(crate) struct UnknownChallenge(Mutex<Option<Challenge>>);
+
+impl UnknownChallenge {
+    /// Records that the value of `challenge` was requested while it is unknown.
+    pub(crate) fn record(&self, challenge: Challenge) {
+        self.0.lock().unwrap().get_or_insert(challenge);
+    }
+
+    /// Returns the error of assigning an unknown value to `column` at `row` while
+    /// synthesizing `current_phase`, which names the challenge whose value was unknown, if
+    /// any.
+    pub(crate) fn error(&self, current_phase: u8, column: Column<Advice>, row: usize) -> Error {
+        match *self.0.lock().unwrap() {
+            Some(challenge) if challenge.phase() >= current_phase => Error::Other(format!(
+                "advice column {} of phase {} is assigned an unknown value at row {}, after the \
+                 value of challenge {} was requested; the challenge is usable after phase {}, \
+                 so its value is unknown while the columns of phase {} are assigned",
+                column.index(),
+                column.column_type().phase,
+                row,
+                challenge.index(),
+                challenge.phase(),
+                current_phase,
+            )),
+            _ => Error::Synthesis,
+        }
+    }
+}
+
 pub struct WitnessCollection<'a, F: Field> {
     pub k: u32,
     pub current_phase: sealed::Phase,
@@ -103,28 +503,71 @@ pub struct WitnessCollection<'a, F: Field> {
     pub challenges: &'a HashMap<usize, F>,
     pub instances: &'a [&'a [F]],
     pub usable_rows: RangeTo<usize>,
+    /// The values of the advice columns of the earlier phases, by column index, which are
+    /// returned by [`Assignment::query_advice`].
+    pub earlier_phases: &'a [Option<&'a [F]>],
+    /// Which names of regions are kept by the assignment check.
+    pub name_policy: NamePolicy,
+    pub(crate) assignments: Option<AssignmentTracker>,
+    pub(crate) unknown_challenge: UnknownChallenge,
     pub _marker: std::marker::PhantomData<F>,
 }
 
 impl<'a, F: Field> Assignment<F> for WitnessCollection<'a, F> {
-    fn enter_region<NR, N>(&mut self, _: N)
+    fn name_policy(&self) -> NamePolicy {
+        self.name_policy
+    }
+
+    fn enter_region<NR, N>(&mut self, name: N)
     where
         NR: Into<String>,
         N: FnOnce() -> NR,
     {
-        // Do nothing; we don't care about regions in this context.
+        // Regions only name the cells reported by the assignment check.
+        if let Some(assignments) = &mut self.assignments {
+            assignments.current_region = Some(assignments.regions.len());
+            let name = (self.name_policy == NamePolicy::KeepAll).then(|| name().into());
+            assignments.regions.push(name);
+        }
     }
 
     fn exit_region(&mut self) {
-        // Do nothing; we don't care about regions in this context.
+        if let Some(assignments) = &mut self.assignments {
+            assignments.current_region = None;
+        }
     }
 
-    fn enable_selector<A, AR>(&mut self, _: A, _: &Selector, _: usize) -> Result<(), Error>
+    fn enable_selector<A, AR>(&mut self, _: A, selector: &Selector, row: usize) -> Result<(), Error>
     where
         A: FnOnce() -> AR,
         AR: Into<String>,
     {
-        // We only care about advice columns here
+        // Selectors are only tracked for the assignment check.
+        if let Some(assignments) = &mut self.assignments {
+            let region = assignments.current_region;
+            assignments.enabled_selectors.push((*selector, row, region));
+        }
+
+        Ok(())
+    }
+
+    fn enable_selector_range<A, AR>(
+        &mut self,
+        _: A,
+        selector: &Selector,
+        rows: Range<usize>,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // Selectors are only tracked for the assignment check.
+        if let Some(assignments) = &mut self.assignments {
+            let region = assignments.current_region;
+            assignments
+                .enabled_selectors
+                .extend(rows.map(|row| (*selector, row, region)));
+        }
 
         Ok(())
     }
@@ -149,6 +592,21 @@ impl<'a, F: Field> Assignment<F> for WitnessCollection<'a, F> {
             .ok_or(Error::BoundsFailure)
     }
 
+    fn query_advice(&self, column: Column<Advice>, row: usize) -> Result<Value<F>, Error> {
+        if !self.usable_rows.contains(&row) {
+            return Err(Error::not_enough_rows_available(self.k));
+        }
+        if column.column_type().phase >= self.current_phase.0 {
+            return Ok(Value::unknown());
+        }
+
+        Ok(self
+            .earlier_phases
+            .get(column.index())
+            .and_then(|values| values.and_then(|values| values.get(row)))
+            .map_or_else(Value::unknown, |value| Value::known(*value)))
+    }
+
     fn assign_advice<V, VR, A, AR>(
         &mut self,
         _: A,
@@ -175,7 +633,52 @@ impl<'a, F: Field> Assignment<F> for WitnessCollection<'a, F> {
             .advice
             .get_mut(column.index())
             .and_then(|v| v.get_mut(row))
-            .ok_or(Error::BoundsFailure)? = to().into_field().assign()?;
+            .ok_or(Error::BoundsFailure)? = to().into_field().assign().map_err(|_| {
+            self.unknown_challenge
+                .error(self.current_phase.0, column, row)
+        })?;
+        if let Some(assignments) = &mut self.assignments {
+            assignments.assigned[column.index()][row] = true;
+        }
+
+        Ok(())
+    }
+
+    fn assign_advice_column_slice<A, AR>(
+        &mut self,
+        _: A,
+        column: Column<Advice>,
+        rows: Range<usize>,
+        values: Value<&[Assigned<F>]>,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // Ignore assignment of advice column in different phase than current one.
+        if self.current_phase.0 != column.column_type().phase || rows.is_empty() {
+            return Ok(());
+        }
+
+        if rows.end > self.usable_rows.end {
+            return Err(Error::not_enough_rows_available(self.k));
+        }
+
+        let values = values.assign().map_err(|_| {
+            self.unknown_challenge
+                .error(self.current_phase.0, column, rows.start)
+        })?;
+        if values.len() != rows.len() {
+            return Err(Error::Synthesis);
+        }
+        let advice = self
+            .advice
+            .get_mut(column.index())
+            .ok_or(Error::BoundsFailure)?;
+        advice[rows.clone()].copy_from_slice(values);
+        if let Some(assignments) = &mut self.assignments {
+            assignments.assigned[column.index()][rows].fill(true);
+        }
 
         Ok(())
     }
@@ -214,11 +717,13 @@ impl<'a, F: Field> Assignment<F> for WitnessCollection<'a, F> {
     }
 
     fn get_challenge(&self, challenge: Challenge) -> Value<F> {
-        self.challenges
-            .get(&challenge.index())
-            .cloned()
-            .map(Value::known)
-            .unwrap_or_else(Value::unknown)
+        match self.challenges.get(&challenge.index()) {
+            Some(value) => Value::known(*value),
+            None => {
+                self.unknown_challenge.record(challenge);
+                Value::unknown()
+            }
+        }
     }
 
     fn push_namespace<NR, N>(&mut self, _: N)
@@ -245,6 +750,8 @@ pub struct WitnessCalculator<'a, F: Field, ConcreteCircuit: Circuit<F>> {
     cs: &'a ConstraintSystem<F>,
     instances: &'a [&'a [F]],
     next_phase: u8,
+    check_assignments: bool,
+    name_policy: NamePolicy,
 }
 
 impl<'a, F: Field, ConcreteCircuit: Circuit<F>> WitnessCalculator<'a, F, ConcreteCircuit> {
@@ -267,28 +774,43 @@ impl<'a, F: Field, ConcreteCircuit: Circuit<F>> WitnessCalculator<'a, F, Concret
             cs,
             instances,
             next_phase: 0,
+            check_assignments: false,
+            name_policy: NamePolicy::KeepOnError,
         }
     }
 
-    /// Calculate witness at phase
-    pub fn calc(
-        &mut self,
-        phase: u8,
-        challenges: &HashMap<usize, F>,
-    ) -> Result<Vec<Option<Vec<F>>>, Error> {
-        if phase != self.next_phase {
-            return Err(Error::Other(format!(
-                "Expected phase {}, got {}",
-                self.next_phase, phase
-            )));
-        }
-        let current_phase = match phase {
-            0 => FirstPhase.to_sealed(),
-            1 => SecondPhase.to_sealed(),
-            2 => ThirdPhase.to_sealed(),
-            _ => unreachable!("only phase [0,2] supported"),
-        };
+    /// Makes [`Self::calc`] check that every advice cell queried by a gate, at a row where
+    /// one of the selectors of the gate is enabled, is assigned, like `MockProver` does.
+    ///
+    /// Unassigned advice cells are otherwise silently filled with zeros, which can satisfy
+    /// constraints by accident and hide bugs. The check tracks every assignment, so it is
+    /// meant for debugging rather than production proving. It doesn't check the fixed
+    /// cells, which are assigned at key generation.
+    pub fn check_assignments(mut self) -> Self {
+        self.check_assignments = true;
+        self
+    }
 
+    /// Sets which names of regions and namespaces are evaluated during synthesis.
+    ///
+    /// By default, the names are only evaluated to report errors: if the assignment check
+    /// fails, the phase is synthesized again with every name kept, to name the region of
+    /// the unassigned cell. With [`NamePolicy::Discard`], the error refers to the region by
+    /// index instead. With [`NamePolicy::KeepAll`], the names are also evaluated for the
+    /// tracing spans of the layouters.
+    pub fn name_policy(mut self, name_policy: NamePolicy) -> Self {
+        self.name_policy = name_policy;
+        self
+    }
+
+    /// Synthesizes the circuit for `current_phase`, evaluating the names of `name_policy`.
+    fn synthesize<'w>(
+        &'w self,
+        current_phase: sealed::Phase,
+        challenges: &'w HashMap<usize, F>,
+        earlier_phases: &'w [Option<&'w [F]>],
+        name_policy: NamePolicy,
+    ) -> Result<WitnessCollection<'w, F>, Error> {
         let mut witness = WitnessCollection {
             k: self.k,
             current_phase,
@@ -300,17 +822,90 @@ impl<'a, F: Field, ConcreteCircuit: Circuit<F>> WitnessCalculator<'a, F, Concret
             // number of blinding factors and an extra row for use in the
             // permutation argument.
             usable_rows: ..self.unusable_rows_start,
+            earlier_phases,
+            name_policy,
+            assignments: self
+                .check_assignments
+                .then(|| AssignmentTracker::new(self.cs.num_advice_columns, self.n)),
+            unknown_challenge: UnknownChallenge::default(),
             _marker: std::marker::PhantomData,
         };
 
         // Synthesize the circuit to obtain the witness and other information.
+        let span = info_span!("synthesize", phase = current_phase.0).entered();
         ConcreteCircuit::FloorPlanner::synthesize(
             &mut witness,
             self.circuit,
             self.config.clone(),
             self.cs.constants.clone(),
-        )
-        .expect("todo");
+        )?;
+        span.exit();
+
+        Ok(witness)
+    }
+
+    /// Calculate witness at phase
+    ///
+    /// The circuit is synthesized once per phase, and only the closures assigning the advice
+    /// columns of `phase` are called. [`Region::query_advice`] returns unknown values for
+    /// the columns of earlier phases, see [`Self::calc_with_earlier_phases`].
+    ///
+    /// [`Region::query_advice`]: halo2_common::circuit::Region::query_advice
+    pub fn calc(
+        &mut self,
+        phase: u8,
+        challenges: &HashMap<usize, F>,
+    ) -> Result<Vec<Option<Vec<F>>>, Error> {
+        self.calc_with_earlier_phases(phase, challenges, &[])
+    }
+
+    /// Calculate witness at phase, like [`Self::calc`], where chips read the values of the
+    /// advice columns of earlier phases with [`Region::query_advice`] instead of recomputing
+    /// them.
+    ///
+    /// `earlier_phases` borrows the values returned by the earlier calls, by column index,
+    /// such as the ones returned by `ProverV2::committed_advice` in the backend. The columns
+    /// without values are read as unknown.
+    ///
+    /// [`Region::query_advice`]: halo2_common::circuit::Region::query_advice
+    pub fn calc_with_earlier_phases(
+        &mut self,
+        phase: u8,
+        challenges: &HashMap<usize, F>,
+        earlier_phases: &[Option<&[F]>],
+    ) -> Result<Vec<Option<Vec<F>>>, Error> {
+        if phase != self.next_phase {
+            return Err(Error::Other(format!(
+                "Expected phase {}, got {}",
+                self.next_phase, phase
+            )));
+        }
+        let current_phase = match phase {
+            0 => FirstPhase.to_sealed(),
+            1 => SecondPhase.to_sealed(),
+            2 => ThirdPhase.to_sealed(),
+            _ => unreachable!("only phase [0,2] supported"),
+        };
+
+        let witness =
+            self.synthesize(current_phase, challenges, earlier_phases, self.name_policy)?;
+        if let Some(assignments) = &witness.assignments {
+            if let Err(error) = assignments.check(self.cs, current_phase) {
+                if self.name_policy != NamePolicy::KeepOnError {
+                    return Err(error);
+                }
+                // The names of the regions are only evaluated to report the error.
+                let named = self.synthesize(
+                    current_phase,
+                    challenges,
+                    earlier_phases,
+                    NamePolicy::KeepAll,
+                )?;
+                let named = named.assignments.as_ref().expect("assignments are tracked");
+                return Err(named.check(self.cs, current_phase).err().unwrap_or(error));
+            }
+        }
+        let advice = witness.advice;
 
         let column_indices = self
             .cs
@@ -327,7 +922,7 @@ impl<'a, F: Field, ConcreteCircuit: Circuit<F>> WitnessCalculator<'a, F, Concret
             .collect::<BTreeSet<_>>();
 
         self.next_phase += 1;
-        let advice_values = batch_invert_assigned(witness.advice);
+        let advice_values = batch_invert_assigned(advice);
         Ok(advice_values
             .into_iter()
             .enumerate()
@@ -344,7 +939,10 @@ impl<'a, F: Field, ConcreteCircuit: Circuit<F>> WitnessCalculator<'a, F, Concret
 
 // Turn vectors of `Assigned<F>` into vectors of `F` by evaluation the divisions in `Assigned<F>`
 // using batched inversions.
-fn batch_invert_assigned<F: Field>(assigned: Vec<Vec<Assigned<F>>>) -> Vec<Vec<F>> {
+//
+// With the `hardened-witness` feature, the `Assigned<F>` values and their inverted denominators
+// are wiped before returning.
+fn batch_invert_assigned<F: Field>(mut assigned: Vec<Vec<Assigned<F>>>) -> Vec<Vec<F>> {
     let mut assigned_denominators: Vec<_> = assigned
         .iter()
         .map(|f| {
@@ -364,13 +962,20 @@ fn batch_invert_assigned<F: Field>(assigned: Vec<Vec<Assigned<F>>>) -> Vec<Vec<F
         })
         .batch_invert();
 
-    assigned
+    let values = assigned
         .iter()
-        .zip(assigned_denominators)
-        .map(|(poly, inv_denoms)| {
-            poly_invert(poly, inv_denoms.into_iter().map(|d| d.unwrap_or(F::ONE)))
-        })
-        .collect()
+        .zip(assigned_denominators.iter())
+        .map(|(poly, inv_denoms)| poly_invert(poly, inv_denoms.iter().map(|d| d.unwrap_or(F::ONE))))
+        .collect();
+
+    if cfg!(feature = "hardened-witness") {
+        for (poly, inv_denoms) in assigned.iter_mut().zip(assigned_denominators.iter_mut()) {
+            wipe(poly, Assigned::Zero);
+            wipe(inv_denoms, None);
+        }
+    }
+
+    values
 }
 
 // Turn a slice of `Assigned<F>` into a vector of F by multiplying each numerator with the elements
@@ -386,3 +991,478 @@ fn poly_invert<F: Field>(
         .map(|(a, inv_den)| a.numerator() * inv_den)
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::pasta::Fp;
+    use std::cell::Cell;
+    use std::collections::HashMap;
+
+    use super::{
+        compile_circuit, compile_circuit_cached, minimum_k, Assigned, Column,
+        CompiledConstraintSystem, NamePolicy, SimpleFloorPlanner, Value, WithFloorPlanner,
+        WitnessCalculator,
+    };
+    use crate::circuit::{floor_planner::V1, Layouter};
+    use crate::dev::MockProver;
+    use halo2_common::plonk::{
+        circuit::Challenge, Circuit, ConstraintSystem, Error, Expression, FirstPhase, SecondPhase,
+        Selector,
+    };
+    use halo2_middleware::circuit::Advice;
+    use halo2_middleware::poly::Rotation;
+
+    thread_local! {
+        static CONFIGURE_CALLS: Cell<usize> = Cell::new(0);
+    }
+
+    struct MyCircuit {
+        rows: usize,
+    }
+
+    impl Circuit<Fp> for MyCircuit {
+        type Config = Column<Advice>;
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self { rows: self.rows }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            CONFIGURE_CALLS.with(|calls| calls.set(calls.get() + 1));
+            meta.advice_column()
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "rows",
+                |mut region| {
+                    for offset in 0..self.rows {
+                        region.assign_advice(|| "", config, offset, || Value::known(Fp::one()))?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn minimum_k_accounts_for_blinding_rows() {
+        // 5 blinding rows and the `l_last` row must fit after the used rows.
+        assert_eq!(minimum_k(&MyCircuit { rows: 26 }, 10).unwrap(), 5);
+        assert_eq!(minimum_k(&MyCircuit { rows: 27 }, 10).unwrap(), 6);
+        assert!(matches!(
+            minimum_k(&MyCircuit { rows: 27 }, 5),
+            Err(Error::NotEnoughRowsAvailable { current_k: 5 })
+        ));
+    }
+
+    #[test]
+    fn compiled_cs_runs_configure_once() {
+        let circuit = MyCircuit { rows: 4 };
+        let compiled_cs = CompiledConstraintSystem::new(&circuit);
+        let calls = CONFIGURE_CALLS.with(Cell::get);
+
+        for compress_selectors in [false, true] {
+            compile_circuit_cached(4, &circuit, &compiled_cs, compress_selectors).unwrap();
+        }
+        MockProver::run_cached(4, &circuit, &compiled_cs, vec![])
+            .unwrap()
+            .assert_satisfied();
+
+        assert_eq!(CONFIGURE_CALLS.with(Cell::get), calls);
+    }
+
+    #[test]
+    fn with_floor_planner() {
+        let circuit = MyCircuit { rows: 4 };
+        let v1 = WithFloorPlanner::<_, V1>::new(&circuit);
+
+        let (compiled, _, _) = compile_circuit(4, &circuit, false).unwrap();
+        let (compiled_v1, _, _) = compile_circuit(4, &v1, false).unwrap();
+        assert_eq!(
+            compiled.preprocessing.fixed,
+            compiled_v1.preprocessing.fixed
+        );
+        MockProver::run(4, &v1, vec![]).unwrap().assert_satisfied();
+        MockProver::run(4, &v1.without_witnesses(), vec![])
+            .unwrap()
+            .assert_satisfied();
+    }
+
+    struct SelectorCircuit {
+        rows: usize,
+        range: bool,
+    }
+
+    impl Circuit<Fp> for SelectorCircuit {
+        type Config = (Column<Advice>, Selector);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self { ..*self }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let s = meta.selector();
+            meta.create_gate("one", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                vec![s * (a - Expression::Constant(Fp::one()))]
+            });
+            (a, s)
+        }
+
+        fn synthesize(
+            &self,
+            (a, s): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "ones",
+                |mut region| {
+                    region.assign_advice(|| "", a, 0, || Value::known(Fp::zero()))?;
+                    for offset in 1..=self.rows {
+                        region.assign_advice(|| "", a, offset, || Value::known(Fp::one()))?;
+                    }
+                    if self.range {
+                        s.enable_range(&mut region, 1, self.rows)
+                    } else {
+                        (1..=self.rows).try_for_each(|offset| s.enable(&mut region, offset))
+                    }
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn selector_range() {
+        let rows = 8;
+        let (per_row, _, _) =
+            compile_circuit(4, &SelectorCircuit { rows, range: false }, false).unwrap();
+        for circuit in [
+            SelectorCircuit { rows, range: true },
+            SelectorCircuit {
+                rows: 0,
+                range: true,
+            },
+        ] {
+            let (compiled, _, _) = compile_circuit(4, &circuit, false).unwrap();
+            let v1 = WithFloorPlanner::<_, V1>::new(&circuit);
+            let (compiled_v1, _, _) = compile_circuit(4, &v1, false).unwrap();
+            assert_eq!(
+                compiled.preprocessing.fixed,
+                compiled_v1.preprocessing.fixed
+            );
+            assert_eq!(
+                compiled.preprocessing.fixed == per_row.preprocessing.fixed,
+                circuit.rows == rows
+            );
+            MockProver::run(4, &circuit, vec![])
+                .unwrap()
+                .assert_satisfied();
+        }
+
+        // The selector can't be enabled in the blinding rows.
+        let circuit = SelectorCircuit {
+            rows: 10,
+            range: true,
+        };
+        assert!(matches!(
+            compile_circuit(4, &circuit, false),
+            Err(Error::NotEnoughRowsAvailable { current_k: 4 })
+        ));
+    }
+
+    struct SliceCircuit {
+        slice: bool,
+    }
+
+    impl Circuit<Fp> for SliceCircuit {
+        type Config = (Column<Advice>, Selector);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self { slice: self.slice }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let s = meta.selector();
+            meta.create_gate("increment", |meta| {
+                let s = meta.query_selector(s);
+                let cur = meta.query_advice(a, Rotation::cur());
+                let next = meta.query_advice(a, Rotation::next());
+                vec![s * (next - cur - Expression::Constant(Fp::one()))]
+            });
+            (a, s)
+        }
+
+        fn synthesize(
+            &self,
+            (a, s): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let values: Vec<Assigned<Fp>> = (1..=8).map(|i| Fp::from(i).into()).collect();
+            layouter.assign_region(
+                || "counter",
+                |mut region| {
+                    s.enable_range(&mut region, 0, values.len() - 1)?;
+                    if self.slice {
+                        region.assign_advice_column_slice(
+                            || "a",
+                            a,
+                            0,
+                            values.len(),
+                            Value::known(&values[..]),
+                        )
+                    } else {
+                        for (offset, value) in values.iter().enumerate() {
+                            region.assign_advice(|| "a", a, offset, || Value::known(*value))?;
+                        }
+                        Ok(())
+                    }
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn advice_column_slice() {
+        let witness = |circuit: &SliceCircuit| {
+            let (_, config, cs) = compile_circuit(4, circuit, false).unwrap();
+            WitnessCalculator::new(4, circuit, &config, &cs, &[])
+                .calc(0, &HashMap::new())
+                .unwrap()
+        };
+        let sliced = SliceCircuit { slice: true };
+        assert_eq!(witness(&sliced), witness(&SliceCircuit { slice: false }));
+        MockProver::run(4, &sliced, vec![])
+            .unwrap()
+            .assert_satisfied();
+        MockProver::run(4, &WithFloorPlanner::<_, V1>::new(&sliced), vec![])
+            .unwrap()
+            .assert_satisfied();
+    }
+
+    thread_local! {
+        static REGION_NAMES: Cell<usize> = Cell::new(0);
+    }
+
+    struct UnassignedCircuit {
+        assign_next: bool,
+    }
+
+    impl Circuit<Fp> for UnassignedCircuit {
+        type Config = (Column<Advice>, Selector);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self { ..*self }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let s = meta.selector();
+            meta.create_gate("equal", |meta| {
+                let s = meta.query_selector(s);
+                let cur = meta.query_advice(a, Rotation::cur());
+                let next = meta.query_advice(a, Rotation::next());
+                vec![s * (next - cur)]
+            });
+            (a, s)
+        }
+
+        fn synthesize(
+            &self,
+            (a, s): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || {
+                    REGION_NAMES.with(|names| names.set(names.get() + 1));
+                    "pair"
+                },
+                |mut region| {
+                    s.enable(&mut region, 0)?;
+                    region.assign_advice(|| "cur", a, 0, || Value::known(Fp::zero()))?;
+                    if self.assign_next {
+                        region.assign_advice(|| "next", a, 1, || Value::known(Fp::zero()))?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn check_assignments() {
+        let witness = |circuit: &UnassignedCircuit, check: bool| {
+            let (_, config, cs) = compile_circuit(4, circuit, false).unwrap();
+            let calculator = WitnessCalculator::new(4, circuit, &config, &cs, &[]);
+            let mut calculator = if check {
+                calculator.check_assignments()
+            } else {
+                calculator
+            };
+            calculator.calc(0, &HashMap::new()).map(|_| ())
+        };
+
+        let assigned = UnassignedCircuit { assign_next: true };
+        assert!(witness(&assigned, true).is_ok());
+
+        // The unassigned cell is zero, which satisfies the gate.
+        let unassigned = UnassignedCircuit { assign_next: false };
+        assert!(witness(&unassigned, false).is_ok());
+        match witness(&unassigned, true) {
+            Err(Error::Other(message)) => assert_eq!(
+                message,
+                "advice column 0 is not assigned at row 1, which is queried by gate 0 \
+                 (\"equal\") enabled at row 0 in region \"pair\", and 0 other queries of \
+                 unassigned cells"
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn name_policy() {
+        let witness = |circuit: &UnassignedCircuit, name_policy: NamePolicy| {
+            let (_, config, cs) = compile_circuit(4, circuit, false).unwrap();
+            REGION_NAMES.with(|names| names.set(0));
+            let result = WitnessCalculator::new(4, circuit, &config, &cs, &[])
+                .check_assignments()
+                .name_policy(name_policy)
+                .calc(0, &HashMap::new())
+                .map(|_| ());
+            (result, REGION_NAMES.with(Cell::get))
+        };
+
+        // By default, the names are only evaluated to report an error.
+        let assigned = UnassignedCircuit { assign_next: true };
+        assert_eq!(witness(&assigned, NamePolicy::KeepOnError).1, 0);
+        assert_eq!(witness(&assigned, NamePolicy::KeepAll).1, 1);
+        let unassigned = UnassignedCircuit { assign_next: false };
+        let (result, names) = witness(&unassigned, NamePolicy::KeepOnError);
+        assert!(
+            matches!(result, Err(Error::Other(message)) if message.contains("region \"pair\""))
+        );
+        assert_eq!(names, 1);
+
+        let (result, names) = witness(&unassigned, NamePolicy::Discard);
+        assert!(matches!(result, Err(Error::Other(message)) if message.contains("in region 0,")));
+        assert_eq!(names, 0);
+    }
+
+    thread_local! {
+        static FIRST_PHASE_CALLS: Cell<usize> = Cell::new(0);
+    }
+
+    #[derive(Clone)]
+    struct PhasesConfig {
+        a: Column<Advice>,
+        b: Column<Advice>,
+        q: Selector,
+        c: Challenge,
+    }
+
+    struct PhasesCircuit;
+
+    impl Circuit<Fp> for PhasesCircuit {
+        type Config = PhasesConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column_in(FirstPhase);
+            let c = meta.challenge_usable_after(FirstPhase);
+            let b = meta.advice_column_in(SecondPhase);
+            let q = meta.selector();
+            meta.create_gate("b = a * c", |meta| {
+                let q = meta.query_selector(q);
+                let a = meta.query_advice(a, Rotation::cur());
+                let b = meta.query_advice(b, Rotation::cur());
+                let c = meta.query_challenge(c);
+                vec![q * (b - a * c)]
+            });
+            PhasesConfig { a, b, q, c }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let c = layouter.get_challenge(config.c);
+            layouter.assign_region(
+                || "rlc",
+                |mut region| {
+                    for offset in 0..4 {
+                        config.q.enable(&mut region, offset)?;
+                        region.assign_advice(
+                            || "a",
+                            config.a,
+                            offset,
+                            || {
+                                FIRST_PHASE_CALLS.with(|calls| calls.set(calls.get() + 1));
+                                Value::known(Fp::from(offset as u64 + 1))
+                            },
+                        )?;
+                        // The second phase reads `a` instead of recomputing it.
+                        let a = region.query_advice(config.a, offset)?;
+                        region.assign_advice(|| "b", config.b, offset, || a * c)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn earlier_phases() {
+        let (_, config, cs) = compile_circuit(4, &PhasesCircuit, false).unwrap();
+        let challenges = [(0, Fp::from(5))].into_iter().collect();
+        let mut calculator = WitnessCalculator::new(4, &PhasesCircuit, &config, &cs, &[]);
+
+        let calls = FIRST_PHASE_CALLS.with(Cell::get);
+        let first = calculator.calc(0, &HashMap::new()).unwrap();
+        assert_eq!(FIRST_PHASE_CALLS.with(Cell::get), calls + 4);
+        let earlier_phases: Vec<_> = first.iter().map(Option::as_deref).collect();
+        let second = calculator
+            .calc_with_earlier_phases(1, &challenges, &earlier_phases)
+            .unwrap();
+        assert_eq!(FIRST_PHASE_CALLS.with(Cell::get), calls + 4);
+
+        assert_eq!(first[0].as_ref().unwrap()[..4], [1, 2, 3, 4].map(Fp::from));
+        assert!(first[1].is_none());
+        assert!(second[0].is_none());
+        assert_eq!(
+            second[1].as_ref().unwrap()[..4],
+            [5, 10, 15, 20].map(Fp::from)
+        );
+
+        let calls = FIRST_PHASE_CALLS.with(Cell::get);
+        MockProver::run_with_challenges(4, &PhasesCircuit, vec![], vec![Fp::from(5)])
+            .unwrap()
+            .assert_satisfied();
+        assert_eq!(FIRST_PHASE_CALLS.with(Cell::get), calls + 4);
+    }
+}
@@ -2,3 +2,114 @@ pub mod single_pass;
 pub mod v1;
 
 pub use halo2_common::circuit::floor_planner::*;
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use halo2curves::pasta::vesta;
+
+    use super::{Abutment, RotationAware, SimpleFloorPlanner, Strict, Unchecked, V1};
+    use crate::circuit::{Layouter, Value};
+    use crate::dev::cost::Layout;
+    use halo2_common::circuit::floor_planner::v1::strategy::SlotInBiggestAdviceFirst;
+    use halo2_common::plonk::{
+        circuit::Column, Circuit, ConstraintSystem, Error, FloorPlanner, Selector,
+    };
+    use halo2_middleware::circuit::Advice;
+    use halo2_middleware::poly::Rotation;
+
+    #[test]
+    fn rotation_aware_abutment() {
+        struct MyCircuit<P>(PhantomData<P>);
+
+        impl<P: FloorPlanner> Circuit<vesta::Scalar> for MyCircuit<P> {
+            type Config = (Column<Advice>, Selector);
+            type FloorPlanner = P;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                MyCircuit(PhantomData)
+            }
+
+            fn configure(meta: &mut ConstraintSystem<vesta::Scalar>) -> Self::Config {
+                let a = meta.advice_column();
+                let s = meta.selector();
+                meta.create_gate("next", |meta| {
+                    let s = meta.query_selector(s);
+                    let cur = meta.query_advice(a, Rotation::cur());
+                    let next = meta.query_advice(a, Rotation::next());
+                    vec![s * (next - cur)]
+                });
+                (a, s)
+            }
+
+            fn synthesize(
+                &self,
+                (a, s): Self::Config,
+                mut layouter: impl Layouter<vesta::Scalar>,
+            ) -> Result<(), Error> {
+                // The first region reads the row after its last row.
+                layouter.assign_region(
+                    || "reads next",
+                    |mut region| {
+                        s.enable(&mut region, 1)?;
+                        for offset in 0..2 {
+                            region.assign_advice(
+                                || "a",
+                                a,
+                                offset,
+                                || Value::known(vesta::Scalar::one()),
+                            )?;
+                        }
+                        Ok(())
+                    },
+                )?;
+                layouter.assign_region(
+                    || "assigns",
+                    |mut region| {
+                        region.assign_advice(|| "a", a, 0, || Value::known(vesta::Scalar::one()))
+                    },
+                )?;
+                Ok(())
+            }
+        }
+
+        fn region_starts<P: FloorPlanner>() -> Result<Vec<Option<usize>>, Error> {
+            let circuit = MyCircuit::<P>(PhantomData);
+            let mut cs = ConstraintSystem::default();
+            let config = MyCircuit::<P>::configure(&mut cs);
+            let mut layout = Layout::new(4, 16, cs.num_selectors);
+            P::synthesize(&mut layout, &circuit, config, vec![])?;
+            Ok(layout.regions.iter().map(|region| region.offset).collect())
+        }
+
+        fn check<A: Abutment>() -> Result<(), Error> {
+            let simple = region_starts::<SimpleFloorPlanner<A>>()?;
+            let v1 = region_starts::<V1<SlotInBiggestAdviceFirst, A>>()?;
+            assert_eq!(simple, v1);
+            Ok(())
+        }
+
+        check::<Unchecked>().unwrap();
+        assert_eq!(
+            region_starts::<SimpleFloorPlanner>().unwrap(),
+            [Some(0), Some(2)]
+        );
+        // The second region is placed after the row read by the first one.
+        check::<RotationAware>().unwrap();
+        assert_eq!(
+            region_starts::<SimpleFloorPlanner<RotationAware>>().unwrap(),
+            [Some(0), Some(3)]
+        );
+        assert!(matches!(
+            region_starts::<SimpleFloorPlanner<Strict>>().unwrap_err(),
+            Error::Other(_)
+        ));
+        assert!(matches!(
+            region_starts::<V1<SlotInBiggestAdviceFirst, Strict>>().unwrap_err(),
+            Error::Other(_)
+        ));
+    }
+}
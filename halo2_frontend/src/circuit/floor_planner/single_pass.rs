@@ -53,4 +53,312 @@ mod tests {
             Error::NotEnoughColumnsForConstants,
         ));
     }
+
+    #[test]
+    fn uniform_regions() {
+        use crate::circuit::{compile_circuit, Layouter, Value};
+        use crate::dev::cost::Layout;
+        use halo2_common::circuit::Region;
+        use halo2_common::plonk::{FloorPlanner, Selector};
+        use halo2_middleware::poly::Rotation;
+
+        const STEPS: usize = 6;
+
+        struct Fibonacci {
+            uniform: bool,
+        }
+
+        impl Circuit<vesta::Scalar> for Fibonacci {
+            type Config = (Column<Advice>, Column<Advice>, Selector);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                Fibonacci {
+                    uniform: self.uniform,
+                }
+            }
+
+            fn configure(meta: &mut ConstraintSystem<vesta::Scalar>) -> Self::Config {
+                let a = meta.advice_column();
+                let b = meta.advice_column();
+                let s = meta.selector();
+                meta.create_gate("step", |meta| {
+                    let s = meta.query_selector(s);
+                    let a_cur = meta.query_advice(a, Rotation::cur());
+                    let b_cur = meta.query_advice(b, Rotation::cur());
+                    let a_next = meta.query_advice(a, Rotation::next());
+                    let b_next = meta.query_advice(b, Rotation::next());
+                    vec![
+                        s.clone() * (a_next - b_cur.clone()),
+                        s * (b_next - a_cur - b_cur),
+                    ]
+                });
+                (a, b, s)
+            }
+
+            fn synthesize(
+                &self,
+                (a, b, s): Self::Config,
+                mut layouter: impl Layouter<vesta::Scalar>,
+            ) -> Result<(), Error> {
+                let mut values = vec![(vesta::Scalar::one(), vesta::Scalar::one())];
+                for _ in 1..STEPS {
+                    let (a, b) = values[values.len() - 1];
+                    values.push((b, a + b));
+                }
+
+                let mut step = |mut region: Region<'_, vesta::Scalar>, step: usize| {
+                    if step + 1 < STEPS {
+                        s.enable(&mut region, 0)?;
+                    }
+                    region.assign_advice(|| "a", a, 0, || Value::known(values[step].0))?;
+                    region.assign_advice(|| "b", b, 0, || Value::known(values[step].1))
+                };
+                let cells = if self.uniform {
+                    layouter.assign_uniform_regions(|| "step", STEPS, step)?
+                } else {
+                    (0..STEPS)
+                        .map(|i| layouter.assign_region(|| "step", |region| step(region, i)))
+                        .collect::<Result<Vec<_>, _>>()?
+                };
+                assert_eq!(cells.len(), STEPS);
+                Ok(())
+            }
+        }
+
+        let region_starts = |uniform: bool| {
+            let circuit = Fibonacci { uniform };
+            let mut cs = ConstraintSystem::default();
+            let config = Fibonacci::configure(&mut cs);
+            let mut layout = Layout::new(4, 16, cs.num_selectors);
+            <SimpleFloorPlanner as FloorPlanner>::synthesize(&mut layout, &circuit, config, vec![])
+                .unwrap();
+            layout
+                .regions
+                .iter()
+                .map(|region| region.offset)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(region_starts(true), region_starts(false));
+        assert_eq!(region_starts(true)[STEPS - 1], Some(STEPS - 1));
+
+        let (uniform, _, _) = compile_circuit(4, &Fibonacci { uniform: true }, false).unwrap();
+        let (per_step, _, _) = compile_circuit(4, &Fibonacci { uniform: false }, false).unwrap();
+        assert_eq!(uniform.preprocessing.fixed, per_step.preprocessing.fixed);
+        MockProver::run(4, &Fibonacci { uniform: true }, vec![])
+            .unwrap()
+            .assert_satisfied();
+    }
+
+    #[test]
+    fn uniform_regions_shape() {
+        use crate::circuit::{Layouter, Value};
+
+        /// Assigns steps of one row, except the step `larger` which takes two rows or
+        /// another column.
+        struct MyCircuit {
+            larger: usize,
+            column: bool,
+        }
+
+        impl Circuit<vesta::Scalar> for MyCircuit {
+            type Config = (Column<Advice>, Column<Advice>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                MyCircuit {
+                    larger: self.larger,
+                    column: self.column,
+                }
+            }
+
+            fn configure(meta: &mut ConstraintSystem<vesta::Scalar>) -> Self::Config {
+                (meta.advice_column(), meta.advice_column())
+            }
+
+            fn synthesize(
+                &self,
+                (a, b): Self::Config,
+                mut layouter: impl Layouter<vesta::Scalar>,
+            ) -> Result<(), Error> {
+                layouter.assign_uniform_regions(
+                    || "step",
+                    4,
+                    |mut region, step| {
+                        let value = || Value::known(vesta::Scalar::one());
+                        if step == self.larger {
+                            let (column, offset) = if self.column { (b, 0) } else { (a, 1) };
+                            region.assign_advice(|| "larger", column, offset, value)?;
+                        }
+                        region.assign_advice(|| "a", a, 0, value).map(|_| ())
+                    },
+                )?;
+                Ok(())
+            }
+        }
+
+        // The first step sets the shape of the others.
+        for column in [false, true] {
+            let circuit = MyCircuit { larger: 0, column };
+            MockProver::run(4, &circuit, vec![])
+                .unwrap()
+                .assert_satisfied();
+        }
+        for (larger, column) in [(1, false), (3, false), (2, true)] {
+            let circuit = MyCircuit { larger, column };
+            assert!(matches!(
+                MockProver::run(4, &circuit, vec![]).unwrap_err(),
+                Error::Synthesis
+            ));
+        }
+    }
+
+    #[test]
+    fn measure_region() {
+        use crate::circuit::{Layouter, Value};
+        use halo2_common::circuit::layouter::RegionColumn;
+        use halo2_middleware::circuit::Any;
+
+        const ROWS: usize = 5;
+
+        struct MyCircuit;
+
+        impl Circuit<vesta::Scalar> for MyCircuit {
+            type Config = (Column<Advice>, Column<Advice>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                MyCircuit
+            }
+
+            fn configure(meta: &mut ConstraintSystem<vesta::Scalar>) -> Self::Config {
+                (meta.advice_column(), meta.advice_column())
+            }
+
+            fn synthesize(
+                &self,
+                (a, b): Self::Config,
+                mut layouter: impl Layouter<vesta::Scalar>,
+            ) -> Result<(), Error> {
+                // The values are not computed while measuring.
+                let shape = layouter.measure_region(|mut region| {
+                    for offset in 0..ROWS - 1 {
+                        region.assign_advice(
+                            || "a",
+                            a,
+                            offset,
+                            || -> Value<vesta::Scalar> { panic!("the value is computed") },
+                        )?;
+                    }
+                    region.assign_advice(
+                        || "b",
+                        b,
+                        ROWS - 1,
+                        || -> Value<vesta::Scalar> { panic!("the value is computed") },
+                    )
+                })?;
+                assert_eq!(shape.row_count(), ROWS);
+                assert_eq!(shape.columns().len(), 2);
+                assert!(shape
+                    .columns()
+                    .contains(&RegionColumn::from(Column::<Any>::from(b))));
+
+                layouter.assign_region(
+                    || "region",
+                    |mut region| {
+                        for offset in 0..shape.row_count() {
+                            region.assign_advice(
+                                || "a",
+                                a,
+                                offset,
+                                || Value::known(vesta::Scalar::zero()),
+                            )?;
+                        }
+                        Ok(())
+                    },
+                )
+            }
+        }
+
+        // Only the assigned region is in the circuit.
+        let prover = MockProver::run(4, &MyCircuit, vec![]).unwrap();
+        assert_eq!(prover.regions().len(), 1);
+        assert_eq!(prover.regions()[0].rows(), Some((0, ROWS - 1)));
+        prover.assert_satisfied();
+    }
+
+    #[test]
+    #[cfg(any(not(feature = "thread-safe-region"), target_arch = "wasm32"))]
+    fn rc_based_chip() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        use crate::circuit::{Layouter, Value};
+        use halo2_common::circuit::layouter::SyncDeps;
+
+        fn assert_sync_deps<T: SyncDeps>() {}
+        assert_sync_deps::<Rc<RefCell<Vec<usize>>>>();
+
+        // A chip that records the rows it assigns in state shared with its clones.
+        #[derive(Clone)]
+        struct RcChip {
+            column: Column<Advice>,
+            rows: Rc<RefCell<Vec<usize>>>,
+        }
+
+        struct MyCircuit;
+
+        impl Circuit<vesta::Scalar> for MyCircuit {
+            type Config = RcChip;
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                MyCircuit
+            }
+
+            fn configure(meta: &mut ConstraintSystem<vesta::Scalar>) -> Self::Config {
+                RcChip {
+                    column: meta.advice_column(),
+                    rows: Rc::default(),
+                }
+            }
+
+            fn synthesize(
+                &self,
+                chip: Self::Config,
+                mut layouter: impl Layouter<vesta::Scalar>,
+            ) -> Result<(), Error> {
+                for _ in 0..2 {
+                    let chip = chip.clone();
+                    layouter.assign_region(
+                        || "region",
+                        |mut region| {
+                            let cell = region.assign_advice(
+                                || "a",
+                                chip.column,
+                                0,
+                                || Value::known(vesta::Scalar::zero()),
+                            )?;
+                            chip.rows.borrow_mut().push(cell.cell().row_offset);
+                            Ok(())
+                        },
+                    )?;
+                }
+                assert!(chip.rows.borrow().len() >= 2);
+                Ok(())
+            }
+        }
+
+        MockProver::run(4, &MyCircuit, vec![])
+            .unwrap()
+            .assert_satisfied();
+    }
 }
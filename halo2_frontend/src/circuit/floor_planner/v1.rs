@@ -55,4 +55,150 @@ mod tests {
             Error::NotEnoughColumnsForConstants,
         ));
     }
+
+    #[test]
+    fn custom_planning_strategy() {
+        use super::V1;
+        use crate::circuit::{layouter::RegionShape, RegionStart, Value};
+        use halo2_common::circuit::floor_planner::v1::strategy::PlanningStrategy;
+
+        /// Stacks the regions in order, at most `OFFSET` rows apart.
+        struct Stacked<const OFFSET: usize>;
+
+        impl<const OFFSET: usize> PlanningStrategy for Stacked<OFFSET> {
+            fn plan(regions: &[RegionShape]) -> Vec<RegionStart> {
+                let mut row = 0;
+                regions
+                    .iter()
+                    .map(|region| {
+                        let start = row;
+                        row += std::cmp::min(region.row_count(), OFFSET);
+                        start.into()
+                    })
+                    .collect()
+            }
+        }
+
+        struct MyCircuit<S>(std::marker::PhantomData<S>);
+
+        impl<S: PlanningStrategy> Circuit<vesta::Scalar> for MyCircuit<S> {
+            type Config = Column<Advice>;
+            type FloorPlanner = V1<S>;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                MyCircuit(std::marker::PhantomData)
+            }
+
+            fn configure(meta: &mut ConstraintSystem<vesta::Scalar>) -> Self::Config {
+                meta.advice_column()
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl crate::circuit::Layouter<vesta::Scalar>,
+            ) -> Result<(), Error> {
+                for _ in 0..2 {
+                    layouter.assign_region(
+                        || "two rows",
+                        |mut region| {
+                            for offset in 0..2 {
+                                region.assign_advice(
+                                    || "one",
+                                    config,
+                                    offset,
+                                    || Value::known(vesta::Scalar::one()),
+                                )?;
+                            }
+                            Ok(())
+                        },
+                    )?;
+                }
+                Ok(())
+            }
+        }
+
+        let circuit = MyCircuit::<Stacked<2>>(std::marker::PhantomData);
+        MockProver::run(4, &circuit, vec![])
+            .unwrap()
+            .assert_satisfied();
+
+        // Both regions use the same column, so they can't be one row apart.
+        let circuit = MyCircuit::<Stacked<1>>(std::marker::PhantomData);
+        assert!(matches!(
+            MockProver::run(4, &circuit, vec![]).unwrap_err(),
+            Error::Other(_),
+        ));
+    }
+
+    #[test]
+    fn packed_floor_planner() {
+        use super::V1;
+        use crate::circuit::floor_planner::PackedFloorPlanner;
+        use crate::circuit::{Layouter, SimpleFloorPlanner, Value};
+        use crate::dev::cost::Layout;
+        use halo2_common::plonk::FloorPlanner;
+
+        struct MyCircuit;
+
+        impl Circuit<vesta::Scalar> for MyCircuit {
+            type Config = [Column<Advice>; 3];
+            type FloorPlanner = PackedFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                MyCircuit
+            }
+
+            fn configure(meta: &mut ConstraintSystem<vesta::Scalar>) -> Self::Config {
+                [(); 3].map(|_| meta.advice_column())
+            }
+
+            fn synthesize(
+                &self,
+                columns: Self::Config,
+                mut layouter: impl Layouter<vesta::Scalar>,
+            ) -> Result<(), Error> {
+                for (region_columns, rows) in
+                    [(&[2][..], 3), (&[0, 1, 2], 1), (&[0, 1], 4), (&[2], 2)]
+                {
+                    layouter.assign_region(
+                        || "region",
+                        |mut region| {
+                            for column in region_columns {
+                                for offset in 0..rows {
+                                    region.assign_advice(
+                                        || "one",
+                                        columns[*column],
+                                        offset,
+                                        || Value::known(vesta::Scalar::one()),
+                                    )?;
+                                }
+                            }
+                            Ok(())
+                        },
+                    )?;
+                }
+                Ok(())
+            }
+        }
+
+        fn rows<P: FloorPlanner>() -> usize {
+            let mut cs = ConstraintSystem::default();
+            let config = MyCircuit::configure(&mut cs);
+            let mut layout = Layout::new(4, 16, cs.num_selectors);
+            P::synthesize(&mut layout, &MyCircuit, config, vec![]).unwrap();
+            layout.total_rows
+        }
+
+        MockProver::run(4, &MyCircuit, vec![])
+            .unwrap()
+            .assert_satisfied();
+        assert_eq!(rows::<PackedFloorPlanner>(), 6);
+        assert_eq!(rows::<V1>(), 7);
+        assert_eq!(rows::<SimpleFloorPlanner>(), 8);
+    }
 }
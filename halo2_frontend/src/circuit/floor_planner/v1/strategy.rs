@@ -5,31 +5,19 @@ fn test_slot_in() {
     use halo2_common::plonk::circuit::Column;
     use halo2_middleware::circuit::Any;
 
+    let region = |index: usize, columns: &[usize], row_count| {
+        let mut region = RegionShape::new(index.into());
+        region.columns = columns
+            .iter()
+            .map(|column| Column::new(*column, Any::advice()).into())
+            .collect();
+        region.row_count = row_count;
+        region
+    };
     let regions = vec![
-        RegionShape {
-            region_index: 0.into(),
-            columns: vec![Column::new(0, Any::advice()), Column::new(1, Any::advice())]
-                .into_iter()
-                .map(|a| a.into())
-                .collect(),
-            row_count: 15,
-        },
-        RegionShape {
-            region_index: 1.into(),
-            columns: vec![Column::new(2, Any::advice())]
-                .into_iter()
-                .map(|a| a.into())
-                .collect(),
-            row_count: 10,
-        },
-        RegionShape {
-            region_index: 2.into(),
-            columns: vec![Column::new(2, Any::advice()), Column::new(0, Any::advice())]
-                .into_iter()
-                .map(|a| a.into())
-                .collect(),
-            row_count: 10,
-        },
+        region(0, &[0, 1], 15),
+        region(1, &[2], 10),
+        region(2, &[2, 0], 10),
     ];
     assert_eq!(
         slot_in(regions)
@@ -40,3 +28,41 @@ fn test_slot_in() {
         vec![0.into(), 0.into(), 15.into()]
     );
 }
+
+#[test]
+fn test_pack() {
+    use crate::circuit::layouter::RegionShape;
+    use halo2_common::circuit::floor_planner::v1::strategy::{pack, slot_in_biggest_advice_first};
+    use halo2_common::plonk::circuit::Column;
+    use halo2_middleware::circuit::Any;
+
+    let region = |index: usize, columns: &[usize], row_count| {
+        let mut region = RegionShape::new(index.into());
+        region.columns = columns
+            .iter()
+            .map(|column| Column::new(*column, Any::advice()).into())
+            .collect();
+        region.row_count = row_count;
+        region
+    };
+    let regions = vec![
+        region(0, &[2], 3),
+        region(1, &[0, 1, 2], 1),
+        region(2, &[0, 1], 4),
+        region(3, &[2], 2),
+    ];
+    let rows = |starts: Vec<crate::circuit::RegionStart>| {
+        starts
+            .iter()
+            .zip(regions.iter())
+            .map(|(start, region)| **start + region.row_count())
+            .max()
+            .unwrap()
+    };
+
+    // Column 2 has 6 rows of regions, which the tallest regions first fill without gaps.
+    assert_eq!(rows(slot_in_biggest_advice_first(regions.clone()).0), 7);
+    let (starts, _) = pack(regions.clone());
+    assert_eq!(starts, vec![0.into(), 5.into(), 0.into(), 3.into()]);
+    assert_eq!(rows(starts), 6);
+}
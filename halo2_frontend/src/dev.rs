@@ -8,6 +8,7 @@ use std::ops::{Add, Mul, Neg, Range};
 use blake2b_simd::blake2b;
 use halo2_middleware::ff::Field;
 use halo2_middleware::ff::FromUniformBytes;
+use rand_core::RngCore;
 
 use halo2_common::{
     circuit,
@@ -21,29 +22,59 @@ use halo2_common::{
 };
 use halo2_middleware::circuit::{Advice, Any, ColumnMid, Fixed, Instance};
 
-use halo2_common::multicore::{
-    IntoParallelIterator, IntoParallelRefIterator, ParallelIterator, ParallelSliceMut,
-};
+use crate::circuit::{CompiledConstraintSystem, UnknownChallenge};
+
+use halo2_common::witness::{Witness, WitnessRegion};
+
+use halo2_common::multicore::prelude::*;
 
 pub mod metadata;
 use metadata::Column as ColumnMetadata;
 mod util;
 
+mod bytecode;
+use bytecode::{Cells, GateBytecode};
+
 mod failure;
 pub use failure::{FailureLocation, VerifyFailure};
 
+mod fast;
+pub use fast::FastFailure;
+
 pub mod cost;
 pub use cost::CircuitCost;
 
+pub mod column_merge;
+
+mod equality;
+pub use equality::{EqualityColumn, EqualityReport};
+
 #[cfg(feature = "cost-estimator")]
 pub mod cost_model;
 
 mod gates;
 pub use gates::CircuitGates;
 
+mod lookups;
+pub use lookups::{LookupReport, LookupStats};
+
+mod selectors;
+pub use selectors::{gate_coverage, selector_map, GateActivity, GateCoverage, SelectorMap};
+
+#[cfg(feature = "cell-provenance")]
+mod provenance;
+#[cfg(feature = "cell-provenance")]
+pub use provenance::CellProvenance;
+
 mod tfp;
 pub use tfp::TracingFloorPlanner;
 
+mod shadow;
+pub use shadow::{shadow_planners, PlacedCell, PlannerMismatch, ShadowReport};
+
+mod timing;
+pub use timing::{time_synthesis, RegionTiming, SynthesisTimings};
+
 #[cfg(feature = "dev-graph")]
 mod graph;
 
@@ -112,6 +143,21 @@ impl Region {
         &self.annotations
     }
 
+    /// Returns the annotations of the region, falling back to the names given to the columns
+    /// at configuration time, to report a failure in the region.
+    pub(crate) fn annotations_or_names(
+        &self,
+        names: &HashMap<ColumnMetadata, String>,
+    ) -> HashMap<ColumnMetadata, String> {
+        let mut annotations = names.clone();
+        annotations.extend(
+            self.annotations
+                .iter()
+                .map(|(column, annotation)| (column.clone(), annotation.clone())),
+        );
+        annotations
+    }
+
     /// Returns the cells assigned in this region.
     pub fn cells(&self) -> &HashMap<(Column<Any>, usize), usize> {
         &self.cells
@@ -350,6 +396,20 @@ pub struct MockProver<F: Field> {
     usable_rows: Range<usize>,
 
     current_phase: sealed::Phase,
+
+    // Whether the advice cells were loaded from a witness, in which case the values
+    // assigned by the circuit are ignored.
+    replaying: bool,
+
+    // The first challenge requested in the current phase before its value is known.
+    unknown_challenge: UnknownChallenge,
+
+    // Where each fixed and advice cell was assigned.
+    #[cfg(feature = "cell-provenance")]
+    provenance: HashMap<(Column<Any>, usize), CellProvenance>,
+
+    // The gates of the constraint system, compiled once the selectors are compressed.
+    gates: Vec<GateBytecode<F>>,
 }
 
 /// Instance Value
@@ -372,6 +432,16 @@ impl<F: Field> InstanceValue<F> {
 }
 
 impl<F: Field> MockProver<F> {
+    /// Returns the cells read by the compiled gates.
+    fn gate_cells(&self) -> Cells<'_, F> {
+        Cells {
+            fixed: &self.fixed,
+            advice: &self.advice,
+            instance: &self.instance,
+            challenges: &self.challenges,
+        }
+    }
+
     fn in_phase<P: Phase>(&self, phase: P) -> bool {
         self.current_phase == phase.to_sealed()
     }
@@ -454,6 +524,43 @@ impl<F: Field> Assignment<F> for MockProver<F> {
         Ok(())
     }
 
+    fn enable_selector_range<A, AR>(
+        &mut self,
+        _: A,
+        selector: &Selector,
+        rows: Range<usize>,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        if !self.in_phase(FirstPhase) || rows.is_empty() {
+            return Ok(());
+        }
+
+        assert!(
+            self.usable_rows.start <= rows.start && rows.end <= self.usable_rows.end,
+            "rows={:?} not in usable_rows={:?}, k={}",
+            rows,
+            self.usable_rows,
+            self.k,
+        );
+
+        // Track that this selector was enabled. We require that all selectors are enabled
+        // inside some region (i.e. no floating selectors).
+        self.current_region
+            .as_mut()
+            .unwrap()
+            .enabled_selectors
+            .entry(*selector)
+            .or_default()
+            .extend(rows.clone());
+
+        self.selectors[selector.0][rows].fill(true);
+
+        Ok(())
+    }
+
     fn query_instance(
         &self,
         column: Column<Instance>,
@@ -475,9 +582,35 @@ impl<F: Field> Assignment<F> for MockProver<F> {
             .expect("bound failure"))
     }
 
+    fn query_advice(&self, column: Column<Advice>, row: usize) -> Result<circuit::Value<F>, Error> {
+        assert!(
+            self.usable_rows.contains(&row),
+            "row={}, usable_rows={:?}, k={}",
+            row,
+            self.usable_rows,
+            self.k,
+        );
+        if column.column_type().phase >= self.current_phase.0 {
+            return Ok(circuit::Value::unknown());
+        }
+
+        Ok(
+            match self
+                .advice
+                .get(column.index())
+                .and_then(|column| column.get(row))
+            {
+                Some(CellValue::Assigned(value)) => circuit::Value::known(*value),
+                Some(CellValue::Unassigned) => circuit::Value::known(F::ZERO),
+                _ => circuit::Value::unknown(),
+            },
+        )
+    }
+
+    #[cfg_attr(not(feature = "cell-provenance"), allow(unused_variables))]
     fn assign_advice<V, VR, A, AR>(
         &mut self,
-        _: A,
+        annotation: A,
         column: Column<Advice>,
         row: usize,
         to: V,
@@ -505,31 +638,34 @@ impl<F: Field> Assignment<F> for MockProver<F> {
                     .and_modify(|count| *count += 1)
                     .or_default();
             }
+
+            #[cfg(feature = "cell-provenance")]
+            self.record_provenance(column.into(), row, annotation().into());
         }
 
-        match to().into_field().evaluate().assign() {
-            Ok(to) => {
-                let value = self
-                    .advice
-                    .get_mut(column.index())
-                    .and_then(|v| v.get_mut(row))
-                    .expect("bounds failure");
-                *value = CellValue::Assigned(to);
-            }
-            Err(err) => {
-                // Propagate `assign` error if the column is in current phase.
-                if self.in_phase(sealed::Phase(column.column_type().phase)) {
-                    return Err(err);
-                }
-            }
+        // Like the prover, only call the closures of the columns in the current phase.
+        if self.replaying || !self.in_phase(sealed::Phase(column.column_type().phase)) {
+            return Ok(());
         }
 
+        let to = to().into_field().evaluate().assign().map_err(|_| {
+            self.unknown_challenge
+                .error(self.current_phase.0, column, row)
+        })?;
+        let value = self
+            .advice
+            .get_mut(column.index())
+            .and_then(|v| v.get_mut(row))
+            .expect("bounds failure");
+        *value = CellValue::Assigned(to);
+
         Ok(())
     }
 
+    #[cfg_attr(not(feature = "cell-provenance"), allow(unused_variables))]
     fn assign_fixed<V, VR, A, AR>(
         &mut self,
-        _: A,
+        annotation: A,
         column: Column<Fixed>,
         row: usize,
         to: V,
@@ -561,6 +697,9 @@ impl<F: Field> Assignment<F> for MockProver<F> {
                 .or_default();
         }
 
+        #[cfg(feature = "cell-provenance")]
+        self.record_provenance(column.into(), row, annotation().into());
+
         *self
             .fixed
             .get_mut(column.index())
@@ -621,6 +760,7 @@ impl<F: Field> Assignment<F> for MockProver<F> {
 
     fn get_challenge(&self, challenge: Challenge) -> circuit::Value<F> {
         if self.current_phase.0 <= challenge.phase() {
+            self.unknown_challenge.record(challenge);
             return circuit::Value::unknown();
         }
 
@@ -640,6 +780,9 @@ impl<F: Field> Assignment<F> for MockProver<F> {
     }
 }
 
+/// The largest `k` tried by [`MockProver::run_auto`].
+pub const MAX_AUTO_K: u32 = 24;
+
 impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
     /// Runs a synthetic keygen-and-prove operation on the given circuit, collecting data
     /// about the constraints and their assignments.
@@ -648,15 +791,157 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
         circuit: &ConcreteCircuit,
         instance: Vec<Vec<F>>,
     ) -> Result<Self, Error> {
-        let n = 1 << k;
+        Self::run_inner(
+            k,
+            circuit,
+            &CompiledConstraintSystem::new(circuit),
+            instance,
+            None,
+            None,
+        )
+    }
 
-        let mut cs = ConstraintSystem::default();
-        #[cfg(feature = "circuit-params")]
-        let config = ConcreteCircuit::configure_with_params(&mut cs, circuit.params());
-        #[cfg(not(feature = "circuit-params"))]
-        let config = ConcreteCircuit::configure(&mut cs);
-        let cs = cs;
+    /// Runs [`MockProver::run`], using the result of `configure` cached in `compiled_cs`
+    /// instead of running it again.
+    pub fn run_cached<ConcreteCircuit: Circuit<F>>(
+        k: u32,
+        circuit: &ConcreteCircuit,
+        compiled_cs: &CompiledConstraintSystem<F, ConcreteCircuit>,
+        instance: Vec<Vec<F>>,
+    ) -> Result<Self, Error> {
+        Self::run_inner(k, circuit, compiled_cs, instance, None, None)
+    }
+
+    /// Runs [`MockProver::run`] with the given challenge values, indexed by challenge.
+    ///
+    /// By default, the `MockProver` derives the challenges deterministically, so a
+    /// circuit using them (for example to compute random linear combinations) is only
+    /// tested with a single set of values. As in the real prover, each phase is
+    /// synthesized with the challenges of the previous phases only.
+    ///
+    /// Returns an error if there isn't one value for each challenge of the circuit.
+    pub fn run_with_challenges<ConcreteCircuit: Circuit<F>>(
+        k: u32,
+        circuit: &ConcreteCircuit,
+        instance: Vec<Vec<F>>,
+        challenges: Vec<F>,
+    ) -> Result<Self, Error> {
+        Self::run_inner(
+            k,
+            circuit,
+            &CompiledConstraintSystem::new(circuit),
+            instance,
+            None,
+            Some(challenges),
+        )
+    }
+
+    /// Runs [`MockProver::run_with_challenges`] with challenges sampled from `rng`.
+    ///
+    /// The challenges are sampled phase by phase, in the order the real prover squeezes
+    /// them from the transcript. The values used are available through
+    /// [`MockProver::challenges`], so that a failing run can be reproduced.
+    pub fn run_with_random_challenges<ConcreteCircuit: Circuit<F>>(
+        k: u32,
+        circuit: &ConcreteCircuit,
+        instance: Vec<Vec<F>>,
+        mut rng: impl RngCore,
+    ) -> Result<Self, Error> {
+        let compiled_cs = CompiledConstraintSystem::new(circuit);
+        let challenge_phase = compiled_cs.cs().challenge_phase();
+        let mut challenges = vec![F::ZERO; challenge_phase.len()];
+        for phase in compiled_cs.cs().phases() {
+            for (index, _) in challenge_phase
+                .iter()
+                .enumerate()
+                .filter(|(_, challenge_phase)| **challenge_phase == phase.0)
+            {
+                challenges[index] = F::random(&mut rng);
+            }
+        }
+        Self::run_inner(k, circuit, &compiled_cs, instance, None, Some(challenges))
+    }
+
+    /// Replays a recorded [`Witness`] against the given circuit.
+    ///
+    /// The circuit is synthesized to obtain its fixed values, selectors, copy constraints
+    /// and regions, but the advice values it assigns are ignored: the advice cells hold
+    /// the values of `witness` instead. `circuit` can therefore be the result of
+    /// [`Circuit::without_witnesses`].
+    ///
+    /// Note that values in later phases depend on the challenges, so a witness recorded
+    /// from a real proof only satisfies the constraints on later phase columns if it was
+    /// computed with the challenges of the `MockProver`.
+    ///
+    /// Returns an error if the witness doesn't have one column for each advice column of
+    /// the circuit, each of `2^witness.k` cells.
+    pub fn replay<ConcreteCircuit: Circuit<F>>(
+        circuit: &ConcreteCircuit,
+        witness: &Witness<F>,
+    ) -> Result<Self, Error> {
+        Self::run_inner(
+            witness.k,
+            circuit,
+            &CompiledConstraintSystem::new(circuit),
+            witness.instance.clone(),
+            Some(&witness.advice),
+            None,
+        )
+    }
+
+    /// Exports the advice values assigned by the circuit, its instance values and its
+    /// regions as a [`Witness`].
+    pub fn witness(&self) -> Witness<F> {
+        Witness {
+            k: self.k,
+            advice_phases: self.cs.advice_column_phase(),
+            advice: self
+                .advice
+                .iter()
+                .map(|column| {
+                    column
+                        .iter()
+                        .map(|cell| match cell {
+                            CellValue::Assigned(value) => Some(*value),
+                            _ => None,
+                        })
+                        .collect()
+                })
+                .collect(),
+            instance: self
+                .instance
+                .iter()
+                .map(|column| {
+                    column
+                        .iter()
+                        .take_while(|cell| matches!(cell, InstanceValue::Assigned(_)))
+                        .map(InstanceValue::value)
+                        .collect()
+                })
+                .collect(),
+            regions: self
+                .regions
+                .iter()
+                .map(|region| WitnessRegion {
+                    name: region.name.clone(),
+                    rows: region.rows,
+                })
+                .collect(),
+        }
+    }
+
+    fn run_inner<ConcreteCircuit: Circuit<F>>(
+        k: u32,
+        circuit: &ConcreteCircuit,
+        compiled_cs: &CompiledConstraintSystem<F, ConcreteCircuit>,
+        instance: Vec<Vec<F>>,
+        witness: Option<&Vec<Vec<Option<F>>>>,
+        challenges: Option<Vec<F>>,
+    ) -> Result<Self, Error> {
+        let n = 1 << k;
 
+        let (cs, config) = compiled_cs.to_parts();
+        cs.check_challenge_phases()?;
         assert!(
             n >= cs.minimum_rows(),
             "n={}, minimum_rows={}, k={}",
@@ -693,7 +978,7 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
         // Advice columns contain blinding factors.
         let blinding_factors = cs.blinding_factors();
         let usable_rows = n - (blinding_factors + 1);
-        let advice = vec![
+        let mut advice = vec![
             {
                 let mut column = vec![CellValue::Unassigned; n];
                 // Poison unusable rows.
@@ -704,18 +989,47 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
             };
             cs.num_advice_columns
         ];
+        if let Some(witness) = witness {
+            if witness.len() != cs.num_advice_columns
+                || witness.iter().any(|column| column.len() != n)
+            {
+                return Err(Error::Other(
+                    "witness does not match the advice columns of the circuit".to_string(),
+                ));
+            }
+            for (column, values) in advice.iter_mut().zip(witness.iter()) {
+                for (cell, value) in column.iter_mut().zip(values).take(usable_rows) {
+                    if let Some(value) = value {
+                        *cell = CellValue::Assigned(*value);
+                    }
+                }
+            }
+        }
         let permutation = permutation::Assembly::new(n, &cs.permutation);
         let constants = cs.constants.clone();
 
-        // Use hash chain to derive deterministic challenges for testing
-        let challenges = {
-            let mut hash: [u8; 64] = blake2b(b"Halo2-MockProver").as_bytes().try_into().unwrap();
-            iter::repeat_with(|| {
-                hash = blake2b(&hash).as_bytes().try_into().unwrap();
-                F::from_uniform_bytes(&hash)
-            })
-            .take(cs.num_challenges)
-            .collect()
+        let challenges = match challenges {
+            Some(challenges) => {
+                if challenges.len() != cs.num_challenges {
+                    return Err(Error::Other(format!(
+                        "expected {} challenges, got {}",
+                        cs.num_challenges,
+                        challenges.len()
+                    )));
+                }
+                challenges
+            }
+            // Use hash chain to derive deterministic challenges for testing
+            None => {
+                let mut hash: [u8; 64] =
+                    blake2b(b"Halo2-MockProver").as_bytes().try_into().unwrap();
+                iter::repeat_with(|| {
+                    hash = blake2b(&hash).as_bytes().try_into().unwrap();
+                    F::from_uniform_bytes(&hash)
+                })
+                .take(cs.num_challenges)
+                .collect()
+            }
         };
 
         let mut prover = MockProver {
@@ -732,6 +1046,11 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
             permutation,
             usable_rows: 0..usable_rows,
             current_phase: FirstPhase.to_sealed(),
+            replaying: witness.is_some(),
+            unknown_challenge: UnknownChallenge::default(),
+            #[cfg(feature = "cell-provenance")]
+            provenance: HashMap::default(),
+            gates: vec![],
         };
 
         for current_phase in prover.cs.phases() {
@@ -753,6 +1072,7 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
             }
             v
         }));
+        prover.gates = prover.cs.gates.iter().map(GateBytecode::compile).collect();
 
         // #[cfg(feature = "thread-safe-region")]
         // prover.permutation.build_ordered_mapping();
@@ -760,6 +1080,56 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
         Ok(prover)
     }
 
+    /// Runs [`MockProver::run`] at the smallest `k` the circuit and its instance fit in.
+    ///
+    /// The size is first estimated with [`minimum_k`], taking the length of the instance
+    /// columns into account, and is increased until synthesis no longer runs out of
+    /// rows. The chosen size is available through [`MockProver::k`].
+    ///
+    /// Returns `Error::NotEnoughRowsAvailable` if the circuit doesn't fit in
+    /// `2^MAX_AUTO_K` rows.
+    ///
+    /// [`minimum_k`]: crate::circuit::minimum_k
+    pub fn run_auto<ConcreteCircuit: Circuit<F>>(
+        circuit: &ConcreteCircuit,
+        instance: Vec<Vec<F>>,
+    ) -> Result<Self, Error> {
+        let mut cs = ConstraintSystem::default();
+        #[cfg(feature = "circuit-params")]
+        ConcreteCircuit::configure_with_params(&mut cs, circuit.params());
+        #[cfg(not(feature = "circuit-params"))]
+        ConcreteCircuit::configure(&mut cs);
+
+        let instance_rows = instance.iter().map(Vec::len).max().unwrap_or(0);
+        let mut k = std::cmp::max(instance_rows + cs.blinding_factors() + 1, cs.minimum_rows())
+            .next_power_of_two()
+            .trailing_zeros();
+        loop {
+            if k > MAX_AUTO_K {
+                return Err(Error::not_enough_rows_available(MAX_AUTO_K));
+            }
+            let result = crate::circuit::minimum_k(circuit, k).and_then(|min_k| {
+                k = std::cmp::max(k, min_k);
+                Self::run(k, circuit, instance.clone())
+            });
+            match result {
+                Err(Error::NotEnoughRowsAvailable { .. }) => k += 1,
+                result => return result,
+            }
+        }
+    }
+
+    /// Returns the `k` this `MockProver` was run with.
+    pub fn k(&self) -> u32 {
+        self.k
+    }
+
+    /// Returns the values of the challenges this `MockProver` was run with, indexed by
+    /// challenge.
+    pub fn challenges(&self) -> &[F] {
+        &self.challenges
+    }
+
     /// Return the content of an advice column as assigned by the circuit.
     pub fn advice_values(&self, column: Column<Advice>) -> &[CellValue<F>] {
         &self.advice[column.index()]
@@ -770,6 +1140,78 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
         &self.fixed[column.index()]
     }
 
+    /// Return the content of an instance column as provided to the circuit.
+    pub fn instance_values(&self, column: Column<Instance>) -> &[InstanceValue<F>] {
+        &self.instance[column.index()]
+    }
+
+    /// Returns the value of the cell at `row` of `column`, or `None` if the column is not
+    /// in the circuit or `row` is outside of it.
+    ///
+    /// Instance cells that were not provided are reported as [`CellValue::Unassigned`].
+    pub fn cell_value(&self, column: Column<Any>, row: usize) -> Option<CellValue<F>> {
+        match column.column_type() {
+            Any::Advice(_) => self.advice.get(column.index())?.get(row).copied(),
+            Any::Fixed => self.fixed.get(column.index())?.get(row).copied(),
+            Any::Instance => self
+                .instance
+                .get(column.index())?
+                .get(row)
+                .map(|cell| match cell {
+                    InstanceValue::Assigned(value) => CellValue::Assigned(*value),
+                    InstanceValue::Padding => CellValue::Unassigned,
+                }),
+        }
+    }
+
+    /// Returns the column identified by the given UUID, if any.
+    ///
+    /// See [`ConstraintSystem::set_column_id`].
+    pub fn column_by_id(&self, uuid: u128) -> Option<Column<Any>> {
+        self.cs.column_by_id(uuid)
+    }
+
+    /// Returns the value of the cell at `row` of the column identified by the given UUID,
+    /// or `None` if there is no such column or if `row` is outside of the circuit.
+    pub fn cell_value_by_id(&self, uuid: u128, row: usize) -> Option<CellValue<F>> {
+        self.cell_value(self.column_by_id(uuid)?, row)
+    }
+
+    /// Returns the index and the region in which the cell at `row` of `column` was
+    /// assigned, if any.
+    pub fn region_of(&self, column: Column<Any>, row: usize) -> Option<(usize, &Region)> {
+        self.regions
+            .iter()
+            .enumerate()
+            .find(|(_, region)| region.cells.contains_key(&(column, row)))
+    }
+
+    /// Returns an iterator over the assigned advice and fixed cells, as
+    /// `(column, row, value)`.
+    ///
+    /// Cells are visited column by column, advice columns first.
+    pub fn assigned_cells(&self) -> impl Iterator<Item = (Column<Any>, usize, F)> + '_ {
+        let advice = self.advice.iter().enumerate().map(|(index, values)| {
+            let column: Column<Any> =
+                Column::new(index, Advice::new(self.cs.advice_column_phase[index].0)).into();
+            (column, values)
+        });
+        let fixed = self
+            .fixed
+            .iter()
+            .enumerate()
+            .map(|(index, values)| (Column::new(index, Fixed).into(), values));
+        advice.chain(fixed).flat_map(|(column, values)| {
+            values
+                .iter()
+                .enumerate()
+                .filter_map(move |(row, value)| match value {
+                    CellValue::Assigned(value) => Some((column, row, *value)),
+                    _ => None,
+                })
+        })
+    }
+
     /// Returns `Ok(())` if this `MockProver` is satisfied, or a list of errors indicating
     /// the reasons that the circuit is not satisfied.
     /// Constraints and lookup are checked at `usable_rows`, parallelly.
@@ -860,7 +1302,9 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
                                                         region: (
                                                             r_i,
                                                             r.name.clone(),
-                                                            r.annotations.clone(),
+                                                            r.annotations_or_names(
+                                                                &self.cs.general_column_annotations,
+                                                            ),
                                                         )
                                                             .into(),
                                                         gate_offset: *selector_row,
@@ -892,66 +1336,56 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
                     .clone()
                     .into_par_iter()
                     .chain(blinding_rows.into_par_iter()))
+                // A gate on a subdomain is only enforced on its usable rows.
+                .filter(move |row| {
+                    gate.subdomain().map_or(true, |subdomain| {
+                        subdomain.contains(*row) && self.usable_rows.contains(row)
+                    })
+                })
                 .flat_map(move |row| {
+                    let values =
+                        self.gates[gate_index].evaluate(&self.gate_cells(), self.n as usize, row);
                     let row = row as i32 + n;
                     gate.polynomials()
                         .iter()
+                        .zip(values)
                         .enumerate()
-                        .filter_map(move |(poly_index, poly)| {
-                            match poly.evaluate_lazy(
-                                &|scalar| Value::Real(scalar),
-                                &|_| panic!("virtual selectors are removed during optimization"),
-                                &util::load(n, row, &self.cs.fixed_queries, &self.fixed),
-                                &util::load(n, row, &self.cs.advice_queries, &self.advice),
-                                &util::load_instance(
-                                    n,
-                                    row,
-                                    &self.cs.instance_queries,
-                                    &self.instance,
+                        .filter_map(move |(poly_index, (poly, value))| match value {
+                            Value::Real(x) if x.is_zero_vartime() => None,
+                            Value::Real(_) => Some(VerifyFailure::ConstraintNotSatisfied {
+                                constraint: (
+                                    (gate_index, gate.name()).into(),
+                                    poly_index,
+                                    gate.constraint_name(poly_index),
+                                )
+                                    .into(),
+                                location: FailureLocation::find_expressions(
+                                    &self.cs,
+                                    &self.regions,
+                                    (row - n) as usize,
+                                    Some(poly).into_iter(),
                                 ),
-                                &|challenge| Value::Real(self.challenges[challenge.index()]),
-                                &|a| -a,
-                                &|a, b| a + b,
-                                &|a, b| a * b,
-                                &|a, scalar| a * scalar,
-                                &Value::Real(F::ZERO),
-                            ) {
-                                Value::Real(x) if x.is_zero_vartime() => None,
-                                Value::Real(_) => Some(VerifyFailure::ConstraintNotSatisfied {
-                                    constraint: (
-                                        (gate_index, gate.name()).into(),
-                                        poly_index,
-                                        gate.constraint_name(poly_index),
-                                    )
-                                        .into(),
-                                    location: FailureLocation::find_expressions(
-                                        &self.cs,
-                                        &self.regions,
-                                        (row - n) as usize,
-                                        Some(poly).into_iter(),
+                                cell_values: util::cell_values(
+                                    gate,
+                                    poly,
+                                    &util::load(n, row, &self.cs.fixed_queries, &self.fixed),
+                                    &util::load(n, row, &self.cs.advice_queries, &self.advice),
+                                    &util::load_instance(
+                                        n,
+                                        row,
+                                        &self.cs.instance_queries,
+                                        &self.instance,
                                     ),
-                                    cell_values: util::cell_values(
-                                        gate,
-                                        poly,
-                                        &util::load(n, row, &self.cs.fixed_queries, &self.fixed),
-                                        &util::load(n, row, &self.cs.advice_queries, &self.advice),
-                                        &util::load_instance(
-                                            n,
-                                            row,
-                                            &self.cs.instance_queries,
-                                            &self.instance,
-                                        ),
-                                    ),
-                                }),
-                                Value::Poison => Some(VerifyFailure::ConstraintPoisoned {
-                                    constraint: (
-                                        (gate_index, gate.name()).into(),
-                                        poly_index,
-                                        gate.constraint_name(poly_index),
-                                    )
-                                        .into(),
-                                }),
-                            }
+                                ),
+                            }),
+                            Value::Poison => Some(VerifyFailure::ConstraintPoisoned {
+                                constraint: (
+                                    (gate_index, gate.name()).into(),
+                                    poly_index,
+                                    gate.constraint_name(poly_index),
+                                )
+                                    .into(),
+                            }),
                         })
                         .collect::<Vec<_>>()
                 })
@@ -1149,7 +1583,11 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
         // Original values of columns involved in the permutation.
         let original = |column: ColumnMid, row: usize| match column.column_type {
             Any::Advice(_) => self.advice[column.index][row],
-            Any::Fixed => self.fixed[column.index][row],
+            // Unassigned fixed cells are zero in the fixed polynomials.
+            Any::Fixed => match self.fixed[column.index][row] {
+                CellValue::Unassigned => CellValue::Assigned(F::ZERO),
+                cell => cell,
+            },
             Any::Instance => {
                 let cell: &InstanceValue<F> = &self.instance[column.index][row];
                 CellValue::Assigned(cell.value())
@@ -1167,6 +1605,7 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
                 Some(VerifyFailure::Permutation {
                     column: cell_a.column.into(),
                     location: FailureLocation::find(
+                        &self.cs,
                         &self.regions,
                         cell_a.row,
                         Some(&cell_a.column.into()).into_iter().cloned().collect(),
@@ -1243,6 +1682,29 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
         }
     }
 
+    /// Returns where the cell of `column` at `row` was assigned, if it was.
+    #[cfg(feature = "cell-provenance")]
+    pub fn provenance(
+        &self,
+        column: impl Into<Column<Any>>,
+        row: usize,
+    ) -> Option<&CellProvenance> {
+        self.provenance.get(&(column.into(), row))
+    }
+
+    #[cfg(feature = "cell-provenance")]
+    fn record_provenance(&mut self, column: Column<Any>, row: usize, annotation: String) {
+        let provenance = CellProvenance {
+            region: self
+                .current_region
+                .as_ref()
+                .map(|region| region.name.clone()),
+            annotation,
+            location: circuit::provenance::caller(),
+        };
+        self.provenance.insert((column, row), provenance);
+    }
+
     /// Returns the constraint system
     pub fn cs(&self) -> &ConstraintSystem<F> {
         &self.cs
@@ -1288,7 +1750,7 @@ impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
 mod tests {
     use halo2curves::pasta::Fp;
 
-    use super::{FailureLocation, MockProver, VerifyFailure};
+    use super::{CellValue, FailureLocation, InstanceValue, MockProver, VerifyFailure};
     use crate::circuit::{Layouter, SimpleFloorPlanner, Value};
     use halo2_common::plonk::{
         circuit::Column, Circuit, ConstraintSystem, Error, Expression, Selector, TableColumn,
@@ -1836,4 +2298,1076 @@ mod tests {
             },])
         )
     }
+
+    #[test]
+    fn named_columns() {
+        const K: u32 = 4;
+
+        #[derive(Clone)]
+        struct NamedCircuitConfig {
+            a: Column<Advice>,
+            b: Column<Advice>,
+        }
+
+        struct NamedCircuit {}
+
+        impl Circuit<Fp> for NamedCircuit {
+            type Config = NamedCircuitConfig;
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                let a = meta.advice_column_named("rlp_byte");
+                let b = meta.advice_column_named("rlp_len");
+                assert_eq!(meta.column_name(a), Some("rlp_byte"));
+
+                NamedCircuitConfig { a, b }
+            }
+
+            fn without_witnesses(&self) -> Self {
+                Self {}
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                layouter.assign_region(
+                    || "Named synthesis",
+                    |mut region| {
+                        region.assign_advice(|| "a", config.a, 0, || Value::known(Fp::zero()))?;
+
+                        // Region annotations take precedence over the configured name.
+                        region.name_column(|| "length", config.b);
+                        Ok(())
+                    },
+                )
+            }
+        }
+
+        let prover = MockProver::run(K, &NamedCircuit {}, vec![]).unwrap();
+        let region = &prover.regions()[0];
+        assert_eq!(region.annotations().len(), 1);
+        let annotations = region.annotations_or_names(&prover.cs.general_column_annotations);
+        assert_eq!(
+            annotations
+                .get(&(Any::advice(), 0).into())
+                .map(String::as_str),
+            Some("rlp_byte")
+        );
+        assert_eq!(
+            annotations
+                .get(&(Any::advice(), 1).into())
+                .map(String::as_str),
+            Some("length")
+        );
+    }
+
+    #[test]
+    fn run_auto() {
+        struct RowsCircuit {
+            rows: usize,
+        }
+
+        impl Circuit<Fp> for RowsCircuit {
+            type Config = Column<Advice>;
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                meta.instance_column();
+                meta.advice_column()
+            }
+
+            fn without_witnesses(&self) -> Self {
+                Self { rows: self.rows }
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                layouter.assign_region(
+                    || "rows",
+                    |mut region| {
+                        for offset in 0..self.rows {
+                            region.assign_advice(
+                                || "",
+                                config,
+                                offset,
+                                || Value::known(Fp::one()),
+                            )?;
+                        }
+                        Ok(())
+                    },
+                )
+            }
+        }
+
+        let prover = MockProver::run_auto(&RowsCircuit { rows: 30 }, vec![vec![]]).unwrap();
+        assert_eq!(prover.k(), 6);
+        assert_eq!(prover.verify(), Ok(()));
+
+        // The instance column needs more rows than the region.
+        let prover =
+            MockProver::run_auto(&RowsCircuit { rows: 1 }, vec![vec![Fp::one(); 100]]).unwrap();
+        assert_eq!(prover.k(), 7);
+    }
+
+    #[test]
+    fn witness_access() {
+        const K: u32 = 4;
+
+        struct WitnessCircuit {}
+
+        impl Circuit<Fp> for WitnessCircuit {
+            type Config = (Column<Advice>, Column<Fixed>, Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                (
+                    meta.advice_column(),
+                    meta.fixed_column(),
+                    meta.instance_column(),
+                )
+            }
+
+            fn without_witnesses(&self) -> Self {
+                Self {}
+            }
+
+            fn synthesize(
+                &self,
+                (a, f, _): Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                layouter.assign_region(
+                    || "first",
+                    |mut region| {
+                        region.assign_advice(|| "a", a, 0, || Value::known(Fp::from(2)))?;
+                        region.assign_fixed(|| "f", f, 0, || Value::known(Fp::from(3)))
+                    },
+                )?;
+                layouter.assign_region(
+                    || "second",
+                    |mut region| {
+                        region.assign_advice(|| "a", a, 0, || Value::known(Fp::from(5)))?;
+                        Ok(())
+                    },
+                )
+            }
+        }
+
+        let prover = MockProver::run(K, &WitnessCircuit {}, vec![vec![Fp::from(7)]]).unwrap();
+        let a: Column<Any> = Column::new(0, Any::advice());
+        let i = Column::new(0, Instance);
+
+        assert_eq!(
+            prover.cell_value(a, 1),
+            Some(CellValue::Assigned(Fp::from(5)))
+        );
+        assert_eq!(prover.cell_value(a, 1 << K), None);
+        assert_eq!(prover.cell_value(Column::new(10, Any::Fixed), 0), None);
+        assert_eq!(
+            prover.cell_value(i.into(), 0),
+            Some(CellValue::Assigned(Fp::from(7)))
+        );
+        assert_eq!(prover.instance_values(i)[1], InstanceValue::Padding);
+
+        let (index, region) = prover.region_of(a, 1).unwrap();
+        assert_eq!((index, region.name().as_str()), (1, "second"));
+        assert!(prover.region_of(a, 2).is_none());
+
+        assert_eq!(
+            prover.assigned_cells().collect::<Vec<_>>(),
+            vec![
+                (a, 0, Fp::from(2)),
+                (a, 1, Fp::from(5)),
+                (Column::new(0, Any::Fixed), 0, Fp::from(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn column_ids() {
+        use halo2_middleware::metadata::ColumnId;
+
+        const K: u32 = 4;
+
+        // Allocates the same columns in either order.
+        struct IdCircuit<const SWAP: bool>;
+
+        impl<const SWAP: bool> Circuit<Fp> for IdCircuit<SWAP> {
+            type Config = (Column<Advice>, Column<Advice>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                let (x, y) = if SWAP {
+                    let y = meta.advice_column();
+                    (meta.advice_column(), y)
+                } else {
+                    (meta.advice_column(), meta.advice_column())
+                };
+                meta.set_column_id(x, ColumnId::new("x"));
+                meta.set_column_id(y, ColumnId::new("y"));
+                (x, y)
+            }
+
+            fn without_witnesses(&self) -> Self {
+                Self
+            }
+
+            fn synthesize(
+                &self,
+                (x, y): Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                layouter.assign_region(
+                    || "values",
+                    |mut region| {
+                        region.assign_advice(|| "x", x, 0, || Value::known(Fp::from(2)))?;
+                        region.assign_advice(|| "y", y, 0, || Value::known(Fp::from(3)))
+                    },
+                )?;
+                Ok(())
+            }
+        }
+
+        let x = ColumnId::new("x").uuid;
+        let unswapped = MockProver::run(K, &IdCircuit::<false>, vec![]).unwrap();
+        let swapped = MockProver::run(K, &IdCircuit::<true>, vec![]).unwrap();
+        assert_eq!(
+            unswapped.column_by_id(x),
+            Some(Column::new(0, Any::advice()))
+        );
+        assert_eq!(swapped.column_by_id(x), Some(Column::new(1, Any::advice())));
+        for prover in [&unswapped, &swapped] {
+            assert_eq!(
+                prover.cell_value_by_id(x, 0),
+                Some(CellValue::Assigned(Fp::from(2)))
+            );
+        }
+        assert_eq!(unswapped.cell_value_by_id(0, 0), None);
+    }
+
+    #[test]
+    fn witness_replay() {
+        const K: u32 = 4;
+
+        #[derive(Clone)]
+        struct ReplayConfig {
+            a: Column<Advice>,
+            b: Column<Advice>,
+            q: Selector,
+        }
+
+        struct ReplayCircuit {
+            value: Value<Fp>,
+        }
+
+        impl Circuit<Fp> for ReplayCircuit {
+            type Config = ReplayConfig;
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                let a = meta.advice_column();
+                let b = meta.advice_column();
+                let q = meta.selector();
+
+                meta.create_gate("Equality check", |cells| {
+                    let a = cells.query_advice(a, Rotation::cur());
+                    let b = cells.query_advice(b, Rotation::cur());
+                    let q = cells.query_selector(q);
+                    vec![q * (a - b)]
+                });
+
+                ReplayConfig { a, b, q }
+            }
+
+            fn without_witnesses(&self) -> Self {
+                Self {
+                    value: Value::unknown(),
+                }
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                layouter.assign_region(
+                    || "Replayed synthesis",
+                    |mut region| {
+                        config.q.enable(&mut region, 0)?;
+                        region.assign_advice(|| "a", config.a, 0, || self.value)?;
+                        region.assign_advice(|| "b", config.b, 0, || self.value)?;
+                        Ok(())
+                    },
+                )
+            }
+        }
+
+        let circuit = ReplayCircuit {
+            value: Value::known(Fp::from(3)),
+        };
+        let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+        let mut witness = prover.witness();
+        assert_eq!(witness.regions[0].name, "Replayed synthesis");
+        assert_eq!(witness.advice[1][0], Some(Fp::from(3)));
+
+        let replayed = MockProver::replay(&circuit.without_witnesses(), &witness).unwrap();
+        assert_eq!(replayed.verify(), Ok(()));
+
+        witness.advice[1][0] = Some(Fp::from(4));
+        let replayed = MockProver::replay(&circuit.without_witnesses(), &witness).unwrap();
+        assert!(replayed.verify().is_err());
+    }
+
+    #[test]
+    fn packed_tables() {
+        use halo2_common::circuit::packed_table::{PackedTable, PackedTables};
+
+        const K: u32 = 5;
+
+        #[derive(Clone)]
+        struct PackedConfig {
+            a: Column<Advice>,
+            b: Column<Advice>,
+            q_single: Selector,
+            q_pair: Selector,
+            tables: PackedTables,
+            single: PackedTable,
+            pair: PackedTable,
+        }
+
+        struct PackedCircuit {
+            a: u64,
+            b: u64,
+        }
+
+        impl Circuit<Fp> for PackedCircuit {
+            type Config = PackedConfig;
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                let a = meta.advice_column();
+                let b = meta.advice_column();
+                let q_single = meta.complex_selector();
+                let q_pair = meta.complex_selector();
+
+                let mut tables = PackedTables::configure(meta, 2);
+                let single = tables.add_table(1);
+                let pair = tables.add_table(2);
+                tables.lookup(meta, "single", single, |meta| {
+                    let q = meta.query_selector(q_single);
+                    (q, vec![meta.query_advice(a, Rotation::cur())])
+                });
+                tables.lookup(meta, "pair", pair, |meta| {
+                    let q = meta.query_selector(q_pair);
+                    let a = meta.query_advice(a, Rotation::cur());
+                    let b = meta.query_advice(b, Rotation::cur());
+                    (q, vec![a, b])
+                });
+
+                PackedConfig {
+                    a,
+                    b,
+                    q_single,
+                    q_pair,
+                    tables,
+                    single,
+                    pair,
+                }
+            }
+
+            fn without_witnesses(&self) -> Self {
+                Self { a: 0, b: 0 }
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                // `single` holds 0..4, `pair` holds (x, x + 10) for x in 0..4.
+                config.tables.load(
+                    &mut layouter,
+                    &[
+                        (config.single, (0..4).map(|x| vec![Fp::from(x)]).collect()),
+                        (
+                            config.pair,
+                            (0..4)
+                                .map(|x| vec![Fp::from(x), Fp::from(x + 10)])
+                                .collect(),
+                        ),
+                    ],
+                )?;
+
+                layouter.assign_region(
+                    || "lookups",
+                    |mut region| {
+                        config.q_single.enable(&mut region, 0)?;
+                        config.q_pair.enable(&mut region, 1)?;
+                        region.assign_advice(|| "a", config.a, 0, || Value::known(Fp::from(3)))?;
+                        region.assign_advice(
+                            || "a",
+                            config.a,
+                            1,
+                            || Value::known(Fp::from(self.a)),
+                        )?;
+                        region.assign_advice(
+                            || "b",
+                            config.b,
+                            1,
+                            || Value::known(Fp::from(self.b)),
+                        )?;
+                        Ok(())
+                    },
+                )
+            }
+        }
+
+        let prover = MockProver::run(K, &PackedCircuit { a: 2, b: 12 }, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // (3, 0) is not in `pair`, even though it is a row of `single`, padded with zero.
+        let prover = MockProver::run(K, &PackedCircuit { a: 3, b: 0 }, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn typed_cells() {
+        use halo2_common::circuit::typed::{BitConfig, ByteConfig, U64Config};
+
+        const K: u32 = 9;
+
+        #[derive(Clone)]
+        struct TypedConfig {
+            bits: BitConfig,
+            bytes: ByteConfig,
+            words: U64Config,
+        }
+
+        struct TypedCircuit;
+
+        impl Circuit<Fp> for TypedCircuit {
+            type Config = TypedConfig;
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                let bit = meta.advice_column();
+                let byte = meta.advice_column();
+                let word = meta.advice_column();
+                let table = meta.lookup_table_column();
+                let bits = BitConfig::configure(meta, bit);
+                let bytes = ByteConfig::configure(meta, byte, table);
+                let words = U64Config::configure(meta, word, bytes);
+                TypedConfig { bits, bytes, words }
+            }
+
+            fn without_witnesses(&self) -> Self {
+                Self
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                config.bytes.load_table(&mut layouter)?;
+                layouter.assign_region(
+                    || "operations",
+                    |mut region| {
+                        // The results of the bit operations are at rows 3, 6, 9 and 12.
+                        let bits = config.bits;
+                        let a = bits.assign(&mut region, 0, Value::known(true))?;
+                        let b = bits.assign(&mut region, 1, Value::known(false))?;
+                        bits.not(&mut region, 2, &a)?;
+                        bits.and(&mut region, 4, &a, &b)?;
+                        bits.or(&mut region, 7, &a, &b)?;
+                        bits.xor(&mut region, 10, &a, &b)?;
+
+                        // The results of the word operations are at rows 16 and 32, with
+                        // their low bytes at the same rows of the byte column.
+                        let words = config.words;
+                        let a = words.assign(&mut region, 0, Value::known(u64::MAX - 1))?;
+                        let b = words.assign(&mut region, 8, Value::known(3))?;
+                        words.wrapping_add(&mut region, 16, &a, &b)?;
+                        words.wrapping_mul(&mut region, 32, &a, &b)?;
+
+                        // The result of the byte operation is at row 52.
+                        let bytes = config.bytes;
+                        let a = bytes.assign(&mut region, 48, Value::known(200))?;
+                        let b = bytes.assign(&mut region, 49, Value::known(100))?;
+                        bytes.wrapping_add(&mut region, 50, &a, &b)?;
+                        Ok(())
+                    },
+                )
+            }
+        }
+
+        let prover = MockProver::run(K, &TypedCircuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+        let witness = prover.witness();
+        let (bit, byte, word) = (0, 1, 2);
+        assert_eq!(witness.advice[bit][12], Some(Fp::one()));
+        assert_eq!(witness.advice[byte][52], Some(Fp::from(44)));
+        assert_eq!(witness.advice[word][16], Some(Fp::one()));
+        assert_eq!(witness.advice[word][32], Some(Fp::from(u64::MAX - 5)));
+
+        // Replaces the given cells with wrong results, which are still in range, and
+        // returns the names of the constraints that are not satisfied.
+        let failures = |cells: &[(usize, usize, u64)]| {
+            let mut witness = witness.clone();
+            for (column, row, value) in cells {
+                witness.advice[*column][*row] = Some(Fp::from(*value));
+            }
+            let prover = MockProver::replay(&TypedCircuit, &witness).unwrap();
+            prover
+                .verify()
+                .unwrap_err()
+                .into_iter()
+                .map(|failure| match failure {
+                    VerifyFailure::ConstraintNotSatisfied { constraint, .. } => constraint.name,
+                    _ => panic!("unexpected failure: {failure:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(failures(&[(bit, 3, 1)]), vec!["not"]);
+        assert_eq!(failures(&[(bit, 6, 1)]), vec!["and"]);
+        assert_eq!(failures(&[(bit, 9, 0)]), vec!["or"]);
+        assert_eq!(failures(&[(bit, 12, 0)]), vec!["xor"]);
+        assert_eq!(failures(&[(byte, 52, 45)]), vec!["sum"]);
+        // The wrong words are decomposed consistently, so that only the operations fail.
+        assert_eq!(
+            failures(&[(word, 16, 2), (byte, 16, 2)]),
+            vec!["wrapping add"]
+        );
+        assert_eq!(
+            failures(&[(word, 32, u64::MAX - 4), (byte, 32, 0xfb)]),
+            vec!["wrapping mul"]
+        );
+    }
+
+    #[test]
+    fn instance_decomposition() {
+        use halo2_common::circuit::instance::{
+            encode_u64, Endianness, InstanceDecompositionConfig,
+        };
+        use halo2_common::circuit::typed::ByteConfig;
+
+        const K: u32 = 9;
+
+        #[derive(Clone)]
+        struct DecompositionConfig {
+            bytes: ByteConfig,
+            decomposition: InstanceDecompositionConfig,
+            instance: Column<Instance>,
+        }
+
+        struct DecompositionCircuit;
+
+        impl Circuit<Fp> for DecompositionCircuit {
+            type Config = DecompositionConfig;
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                let byte = meta.advice_column();
+                let sum = meta.advice_column();
+                let table = meta.lookup_table_column();
+                let instance = meta.instance_column();
+                meta.enable_equality(instance);
+                let bytes = ByteConfig::configure(meta, byte, table);
+                let decomposition = InstanceDecompositionConfig::configure(meta, sum, bytes);
+                DecompositionConfig {
+                    bytes,
+                    decomposition,
+                    instance,
+                }
+            }
+
+            fn without_witnesses(&self) -> Self {
+                Self
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                config.bytes.load_table(&mut layouter)?;
+                config.decomposition.decompose_instance(
+                    layouter.namespace(|| "u64"),
+                    config.instance,
+                    0,
+                    8,
+                    Endianness::Little,
+                )?;
+                Ok(())
+            }
+        }
+
+        let instance = encode_u64::<Fp>(0x0102_0304_0506_0708);
+        let prover = MockProver::run(K, &DecompositionCircuit, vec![vec![instance]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+        let mut witness = prover.witness();
+
+        let is_permutation_failure =
+            |failure: &VerifyFailure| matches!(failure, VerifyFailure::Permutation { .. });
+
+        // An instance out of the range of a `u64` has no decomposition.
+        let too_large = Fp::from(u64::MAX) + Fp::one();
+        let prover = MockProver::run(K, &DecompositionCircuit, vec![vec![too_large]]).unwrap();
+        let failures = prover.verify().unwrap_err();
+        assert!(failures.iter().all(is_permutation_failure));
+
+        // The decomposition of an instance doesn't prove another one.
+        witness.instance[0][0] = encode_u64(0x0102_0304_0506_0709);
+        let prover = MockProver::replay(&DecompositionCircuit, &witness).unwrap();
+        let failures = prover.verify().unwrap_err();
+        assert!(failures.iter().all(is_permutation_failure));
+    }
+
+    #[test]
+    fn user_challenges() {
+        use halo2_common::plonk::{circuit::Challenge, FirstPhase, SecondPhase};
+        use rand_core::OsRng;
+
+        const K: u32 = 4;
+
+        #[derive(Clone)]
+        struct RlcConfig {
+            a: Column<Advice>,
+            b: Column<Advice>,
+            q: Selector,
+            c: Challenge,
+        }
+
+        struct RlcCircuit;
+
+        impl Circuit<Fp> for RlcCircuit {
+            type Config = RlcConfig;
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                let a = meta.advice_column_in(FirstPhase);
+                let c = meta.challenge_usable_after(FirstPhase);
+                let b = meta.advice_column_in(SecondPhase);
+                let q = meta.selector();
+                meta.create_gate("b = a * c", |meta| {
+                    let q = meta.query_selector(q);
+                    let a = meta.query_advice(a, Rotation::cur());
+                    let b = meta.query_advice(b, Rotation::cur());
+                    let c = meta.query_challenge(c);
+                    vec![q * (b - a * c)]
+                });
+                RlcConfig { a, b, q, c }
+            }
+
+            fn without_witnesses(&self) -> Self {
+                Self
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                let c = layouter.get_challenge(config.c);
+                layouter.assign_region(
+                    || "rlc",
+                    |mut region| {
+                        config.q.enable(&mut region, 0)?;
+                        region.assign_advice(|| "a", config.a, 0, || Value::known(Fp::from(3)))?;
+                        region.assign_advice(|| "b", config.b, 0, || c.map(|c| c * Fp::from(3)))?;
+                        Ok(())
+                    },
+                )
+            }
+        }
+
+        let prover =
+            MockProver::run_with_challenges(K, &RlcCircuit, vec![], vec![Fp::from(5)]).unwrap();
+        assert_eq!(prover.challenges(), [Fp::from(5)]);
+        let b = Column::new(1, Advice::new(1));
+        assert_eq!(
+            prover.advice_values(b)[0],
+            CellValue::Assigned(Fp::from(15))
+        );
+        assert_eq!(prover.verify(), Ok(()));
+
+        let prover = MockProver::run_with_random_challenges(K, &RlcCircuit, vec![], OsRng).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        assert!(matches!(
+            MockProver::run_with_challenges(K, &RlcCircuit, vec![], vec![]).unwrap_err(),
+            Error::Other(_)
+        ));
+    }
+
+    #[test]
+    fn challenge_phases() {
+        use crate::circuit::{compile_circuit, WitnessCalculator};
+        use halo2_common::plonk::{circuit::Challenge, FirstPhase, SecondPhase};
+        use std::collections::HashMap;
+
+        const K: u32 = 4;
+
+        #[derive(Clone)]
+        struct BatchConfig {
+            advice: [Column<Advice>; 4],
+            r: Column<Advice>,
+            q: Selector,
+            c: Challenge,
+        }
+
+        struct BatchCircuit {
+            // Assigns a first phase column with the value of the challenge.
+            misuse: bool,
+        }
+
+        impl Circuit<Fp> for BatchCircuit {
+            type Config = BatchConfig;
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                let advice = [(); 4].map(|_| meta.advice_column_in(FirstPhase));
+                let c = meta.challenge_usable_after(FirstPhase);
+                let r = meta.advice_column_in(SecondPhase);
+                let q = meta.selector();
+                // Two constraints on the first phase columns, batched with the challenge.
+                meta.create_gate("batched", |meta| {
+                    let q = meta.query_selector(q);
+                    let [a, b, d, e] =
+                        advice.map(|column| meta.query_advice(column, Rotation::cur()));
+                    let c = meta.query_challenge(c);
+                    vec![q * ((a - b) + c * (d - e))]
+                });
+                meta.create_gate("r = c * a", |meta| {
+                    let q = meta.query_selector(q);
+                    let a = meta.query_advice(advice[0], Rotation::cur());
+                    let r = meta.query_advice(r, Rotation::cur());
+                    vec![q * (r - meta.query_challenge(c) * a)]
+                });
+                BatchConfig { advice, r, q, c }
+            }
+
+            fn without_witnesses(&self) -> Self {
+                Self {
+                    misuse: self.misuse,
+                }
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                let c = layouter.get_challenge(config.c);
+                layouter.assign_region(
+                    || "batch",
+                    |mut region| {
+                        config.q.enable(&mut region, 0)?;
+                        for (column, value) in config.advice.iter().zip([3, 3, 4, 4]) {
+                            region.assign_advice(
+                                || "advice",
+                                *column,
+                                0,
+                                || Value::known(Fp::from(value)),
+                            )?;
+                        }
+                        region.assign_advice(|| "r", config.r, 0, || c.map(|c| c * Fp::from(3)))?;
+                        if self.misuse {
+                            region.assign_advice(|| "e", config.advice[3], 1, || c)?;
+                        }
+                        Ok(())
+                    },
+                )
+            }
+        }
+
+        // Batching constraints with a challenge doesn't require a column of a later phase.
+        let circuit = BatchCircuit { misuse: false };
+        let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let misuse = BatchCircuit { misuse: true };
+        let expected = "advice column 3 of phase 0 is assigned an unknown value at row 1, after \
+                        the value of challenge 0 was requested";
+        match MockProver::run(K, &misuse, vec![]) {
+            Err(Error::Other(error)) => assert!(error.starts_with(expected), "{error}"),
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        // The witness calculator of the prover reports the same error.
+        for (circuit, valid) in [(&circuit, true), (&misuse, false)] {
+            let (_, config, cs) = compile_circuit(K, circuit, false).unwrap();
+            let mut witness_calc = WitnessCalculator::new(K, circuit, &config, &cs, &[]);
+            match witness_calc.calc(0, &HashMap::new()) {
+                Ok(_) => assert!(valid),
+                Err(Error::Other(error)) => {
+                    assert!(!valid);
+                    assert!(error.starts_with(expected), "{error}");
+                }
+                Err(error) => panic!("unexpected error: {error:?}"),
+            }
+        }
+
+        // A challenge requested for the second phase, used to accumulate a first phase
+        // column, is rejected once `configure` returns.
+        struct AccumulatorCircuit;
+
+        impl Circuit<Fp> for AccumulatorCircuit {
+            type Config = ();
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                let a = meta.advice_column_named("a");
+                let c = meta.challenge_for_phase(SecondPhase);
+                let q = meta.selector();
+                meta.create_gate("accumulate", |meta| {
+                    let q = meta.query_selector(q);
+                    let a_next = meta.query_advice(a, Rotation::next());
+                    let a = meta.query_advice(a, Rotation::cur());
+                    vec![("a' = a * c", q * (a_next - a * meta.query_challenge(c)))]
+                });
+            }
+
+            fn without_witnesses(&self) -> Self {
+                Self
+            }
+
+            fn synthesize(&self, _: Self::Config, _: impl Layouter<Fp>) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        let expected = "constraint 0 \"a' = a * c\" of gate \"accumulate\" uses challenge 0, \
+                        requested for phase 1, but queries only advice 0 \"a\" (phase 0)";
+        match MockProver::run(K, &AccumulatorCircuit, vec![]) {
+            Err(Error::Other(error)) => assert!(error.starts_with(expected), "{error}"),
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+        match compile_circuit(K, &AccumulatorCircuit, false) {
+            Err(Error::Other(error)) => assert!(error.starts_with(expected), "{error}"),
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn copy_between_column_types() {
+        use halo2_common::circuit::Cell;
+
+        const K: u32 = 4;
+
+        #[derive(Clone, Copy)]
+        enum Pair {
+            AdviceAdvice,
+            AdviceFixed,
+            FixedFixed,
+            AdviceInstance,
+            FixedInstance,
+            // A fixed cell that is never assigned, and so is zero.
+            UnassignedFixedInstance,
+        }
+
+        struct CopyCircuit {
+            pair: Pair,
+            left: Fp,
+            right: Fp,
+        }
+
+        impl Circuit<Fp> for CopyCircuit {
+            type Config = (Column<Advice>, Column<Fixed>, Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                Self {
+                    pair: self.pair,
+                    left: self.left,
+                    right: self.right,
+                }
+            }
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                let a = meta.advice_column();
+                let f = meta.fixed_column();
+                let i = meta.instance_column();
+                meta.enable_equality(a);
+                meta.enable_equality(f);
+                meta.enable_equality(i);
+                (a, f, i)
+            }
+
+            fn synthesize(
+                &self,
+                (a, f, i): Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                layouter.assign_region(
+                    || "copy",
+                    |mut region| {
+                        let left = Value::known(self.left);
+                        let right = Value::known(self.right);
+                        match self.pair {
+                            Pair::AdviceAdvice => {
+                                let l = region.assign_advice(|| "l", a, 0, || left)?;
+                                let r = region.assign_advice(|| "r", a, 1, || right)?;
+                                region.constrain_equal(l.cell(), r.cell())
+                            }
+                            Pair::AdviceFixed => {
+                                let l = region.assign_advice(|| "l", a, 0, || left)?;
+                                let r = region.assign_fixed(|| "r", f, 0, || right)?;
+                                region.constrain_equal(l.cell(), r.cell())
+                            }
+                            Pair::FixedFixed => {
+                                let l = region.assign_fixed(|| "l", f, 0, || left)?;
+                                let r = region.assign_fixed(|| "r", f, 1, || right)?;
+                                region.constrain_equal(l.cell(), r.cell())
+                            }
+                            Pair::AdviceInstance => {
+                                let l = region.assign_advice(|| "l", a, 0, || left)?;
+                                region.constrain_instance(l.cell(), i, 0)
+                            }
+                            Pair::FixedInstance => {
+                                let l = region.assign_fixed(|| "l", f, 0, || left)?;
+                                region.constrain_instance(l.cell(), i, 0)
+                            }
+                            Pair::UnassignedFixedInstance => {
+                                let a = region.assign_advice(|| "a", a, 0, || left)?;
+                                let l = Cell {
+                                    column: f.into(),
+                                    ..a.cell()
+                                };
+                                region.constrain_instance(l, i, 0)
+                            }
+                        }
+                    },
+                )
+            }
+        }
+
+        for pair in [
+            Pair::AdviceAdvice,
+            Pair::AdviceFixed,
+            Pair::FixedFixed,
+            Pair::AdviceInstance,
+            Pair::FixedInstance,
+            Pair::UnassignedFixedInstance,
+        ] {
+            let (left, right) = match pair {
+                Pair::UnassignedFixedInstance => (Fp::zero(), Fp::zero()),
+                _ => (Fp::from(5), Fp::from(5)),
+            };
+            let circuit = CopyCircuit { pair, left, right };
+            let prover = MockProver::run(K, &circuit, vec![vec![right]]).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            // The instance cell is the right-hand side of the copies to instance columns.
+            let prover = MockProver::run(K, &circuit, vec![vec![right + Fp::one()]]).unwrap();
+            let instance_copy = matches!(
+                pair,
+                Pair::AdviceInstance | Pair::FixedInstance | Pair::UnassignedFixedInstance
+            );
+            assert_eq!(prover.verify().is_err(), instance_copy);
+
+            if !instance_copy {
+                let circuit = CopyCircuit {
+                    pair,
+                    left,
+                    right: right + Fp::one(),
+                };
+                let prover = MockProver::run(K, &circuit, vec![vec![]]).unwrap();
+                assert!(matches!(
+                    prover.verify().unwrap_err()[..],
+                    [VerifyFailure::Permutation { .. }]
+                ));
+            }
+        }
+    }
+
+    #[cfg(feature = "cell-provenance")]
+    #[test]
+    fn cell_provenance() {
+        const K: u32 = 4;
+
+        struct OneCircuit;
+
+        impl Circuit<Fp> for OneCircuit {
+            type Config = (Column<Advice>, Column<Fixed>, Selector);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                Self
+            }
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                let a = meta.advice_column();
+                let f = meta.fixed_column();
+                let s = meta.selector();
+                meta.create_gate("one", |meta| {
+                    let s = meta.query_selector(s);
+                    let a = meta.query_advice(a, Rotation::cur());
+                    let f = meta.query_fixed(f, Rotation::cur());
+                    vec![s * (a - f)]
+                });
+                (a, f, s)
+            }
+
+            fn synthesize(
+                &self,
+                (a, f, s): Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                layouter.assign_region(
+                    || "not one",
+                    |mut region| {
+                        s.enable(&mut region, 1)?;
+                        region.assign_fixed(|| "f", f, 1, || Value::known(Fp::one()))?;
+                        region.assign_advice(|| "a", a, 1, || Value::known(Fp::zero()))?;
+                        Ok(())
+                    },
+                )
+            }
+        }
+
+        let prover = MockProver::run(K, &OneCircuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+
+        let (a, f, _) = OneCircuit::configure(&mut ConstraintSystem::default());
+        let provenance = prover.provenance(a, 1).unwrap();
+        assert_eq!(provenance.region.as_deref(), Some("not one"));
+        assert_eq!(provenance.annotation, "a");
+        let location = provenance.location.unwrap();
+        assert_eq!(location.file(), file!());
+        assert_eq!(prover.provenance(f, 1).unwrap().annotation, "f");
+        assert!(prover.provenance(a, 0).is_none());
+    }
 }
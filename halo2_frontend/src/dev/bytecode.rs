@@ -0,0 +1,233 @@
+//! Gates compiled to a list of instructions, which the checks of the [`MockProver`]
+//! interpret at each row instead of walking the expressions of the gates.
+//!
+//! [`MockProver`]: super::MockProver
+
+use std::collections::HashMap;
+
+use halo2_common::plonk::{Expression, Gate};
+use halo2_middleware::ff::Field;
+
+use super::{CellValue, InstanceValue, Value};
+
+/// An operand of an [`Instruction`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Source {
+    /// A constant of the bytecode.
+    Constant(usize),
+    /// A cell of a fixed column, relative to the row.
+    Fixed { column: usize, rotation: i32 },
+    /// A cell of an advice column, relative to the row.
+    Advice { column: usize, rotation: i32 },
+    /// A cell of an instance column, relative to the row.
+    Instance { column: usize, rotation: i32 },
+    /// A challenge.
+    Challenge(usize),
+    /// The result of an earlier instruction.
+    Intermediate(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Instruction {
+    Negate(Source),
+    Add(Source, Source),
+    Mul(Source, Source),
+    /// Multiplies a source by a constant.
+    Scale(Source, usize),
+}
+
+/// The cells and challenges read by the instructions.
+pub(super) struct Cells<'a, F: Field> {
+    pub(super) fixed: &'a [Vec<CellValue<F>>],
+    pub(super) advice: &'a [Vec<CellValue<F>>],
+    pub(super) instance: &'a [Vec<InstanceValue<F>>],
+    pub(super) challenges: &'a [F],
+}
+
+/// The constraints of a gate, compiled to instructions whose results are numbered in
+/// order, so that subexpressions shared by its constraints are only computed once.
+#[derive(Clone, Debug)]
+pub(super) struct GateBytecode<F: Field> {
+    constants: Vec<F>,
+    instructions: Vec<Instruction>,
+    /// The value of each constraint of the gate.
+    outputs: Vec<Source>,
+}
+
+impl<F: Field> GateBytecode<F> {
+    /// Compiles the constraints of `gate`.
+    pub(super) fn compile(gate: &Gate<F>) -> Self {
+        let mut compiler = Compiler {
+            bytecode: GateBytecode {
+                constants: vec![],
+                instructions: vec![],
+                outputs: vec![],
+            },
+            intermediates: HashMap::new(),
+        };
+        for poly in gate.polynomials() {
+            let output = compiler.compile(poly);
+            compiler.bytecode.outputs.push(output);
+        }
+        compiler.bytecode
+    }
+
+    /// Evaluates the constraints of the gate at `row` of a circuit with `n` rows, with
+    /// the same results as evaluating their expressions.
+    pub(super) fn evaluate(&self, cells: &Cells<'_, F>, n: usize, row: usize) -> Vec<Value<F>> {
+        let at = |rotation: i32| (row as i32 + n as i32 + rotation) as usize % n;
+        let mut intermediates = Vec::with_capacity(self.instructions.len());
+        let get = |intermediates: &[Value<F>], source: Source| match source {
+            Source::Constant(index) => Value::Real(self.constants[index]),
+            Source::Fixed { column, rotation } => cells.fixed[column][at(rotation)].into(),
+            Source::Advice { column, rotation } => cells.advice[column][at(rotation)].into(),
+            Source::Instance { column, rotation } => {
+                Value::Real(cells.instance[column][at(rotation)].value())
+            }
+            Source::Challenge(index) => Value::Real(cells.challenges[index]),
+            Source::Intermediate(index) => intermediates[index],
+        };
+        for instruction in self.instructions.iter() {
+            let value = match *instruction {
+                Instruction::Negate(a) => -get(&intermediates, a),
+                Instruction::Add(a, b) => get(&intermediates, a) + get(&intermediates, b),
+                Instruction::Mul(a, b) => get(&intermediates, a) * get(&intermediates, b),
+                Instruction::Scale(a, constant) => {
+                    get(&intermediates, a) * self.constants[constant]
+                }
+            };
+            intermediates.push(value);
+        }
+        self.outputs
+            .iter()
+            .map(|output| get(&intermediates, *output))
+            .collect()
+    }
+}
+
+struct Compiler<F: Field> {
+    bytecode: GateBytecode<F>,
+    /// The index of the result of each instruction.
+    intermediates: HashMap<Instruction, usize>,
+}
+
+impl<F: Field> Compiler<F> {
+    fn constant(&mut self, value: F) -> usize {
+        let constants = &mut self.bytecode.constants;
+        constants
+            .iter()
+            .position(|constant| *constant == value)
+            .unwrap_or_else(|| {
+                constants.push(value);
+                constants.len() - 1
+            })
+    }
+
+    fn push(&mut self, instruction: Instruction) -> Source {
+        let instructions = &mut self.bytecode.instructions;
+        let index = *self.intermediates.entry(instruction).or_insert_with(|| {
+            instructions.push(instruction);
+            instructions.len() - 1
+        });
+        Source::Intermediate(index)
+    }
+
+    fn compile(&mut self, expression: &Expression<F>) -> Source {
+        match expression {
+            Expression::Constant(value) => Source::Constant(self.constant(*value)),
+            Expression::Selector(_) => panic!("virtual selectors are removed during optimization"),
+            Expression::Fixed(query) => Source::Fixed {
+                column: query.column_index,
+                rotation: query.rotation.0,
+            },
+            Expression::Advice(query) => Source::Advice {
+                column: query.column_index,
+                rotation: query.rotation.0,
+            },
+            Expression::Instance(query) => Source::Instance {
+                column: query.column_index,
+                rotation: query.rotation.0,
+            },
+            Expression::Challenge(challenge) => Source::Challenge(challenge.index()),
+            Expression::Negated(a) => {
+                let a = self.compile(a);
+                self.push(Instruction::Negate(a))
+            }
+            Expression::Sum(a, b) => {
+                let (a, b) = (self.compile(a), self.compile(b));
+                self.push(Instruction::Add(a, b))
+            }
+            Expression::Product(a, b) => {
+                let (a, b) = (self.compile(a), self.compile(b));
+                self.push(Instruction::Mul(a, b))
+            }
+            Expression::Scaled(a, value) => {
+                let a = self.compile(a);
+                let constant = self.constant(*value);
+                self.push(Instruction::Scale(a, constant))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_common::plonk::ConstraintSystem;
+    use halo2_middleware::poly::Rotation;
+    use halo2curves::pasta::Fp;
+
+    #[test]
+    fn shared_subexpressions() {
+        let mut meta = ConstraintSystem::<Fp>::default();
+        let a = meta.advice_column();
+        let f = meta.fixed_column();
+        meta.create_gate("gate", |meta| {
+            let a_cur = meta.query_advice(a, Rotation::cur());
+            let a_next = meta.query_advice(a, Rotation::next());
+            let f = meta.query_fixed(f, Rotation::cur());
+            let sum = a_cur + a_next;
+            vec![f.clone() * sum.clone(), sum.clone() * sum * Fp::from(3) - f]
+        });
+        let bytecode = GateBytecode::compile(&meta.gates[0]);
+        // `a_cur + a_next` is only computed once.
+        assert_eq!(
+            bytecode
+                .instructions
+                .iter()
+                .filter(|instruction| matches!(instruction, Instruction::Add(..)))
+                .count(),
+            2
+        );
+
+        let n = 4;
+        let fixed = vec![(0..n)
+            .map(|row| CellValue::Assigned(Fp::from(row as u64 + 1)))
+            .collect()];
+        let advice = vec![vec![
+            CellValue::Assigned(Fp::from(2)),
+            CellValue::Unassigned,
+            CellValue::Poison(0),
+            CellValue::Assigned(Fp::from(5)),
+        ]];
+        let cells = Cells {
+            fixed: &fixed,
+            advice: &advice,
+            instance: &[],
+            challenges: &[],
+        };
+        // At the last row, `a_next` wraps around to the first row.
+        let sum = Fp::from(7);
+        assert_eq!(
+            bytecode.evaluate(&cells, n, 3),
+            vec![
+                Value::Real(Fp::from(4) * sum),
+                Value::Real(sum * sum * Fp::from(3) - Fp::from(4)),
+            ]
+        );
+        assert_eq!(
+            bytecode.evaluate(&cells, n, 1),
+            vec![Value::Poison, Value::Poison]
+        );
+    }
+}
@@ -0,0 +1,450 @@
+//! Developer tools for finding advice columns that could be merged.
+//!
+//! Every advice column costs a commitment and one evaluation per distinct rotation in
+//! each proof, and an MSM and an extended-domain FFT in the prover. Circuits often end up
+//! with columns that are only used in a few rows: if two columns of the same phase are
+//! never used at the same rows, one of them can be replaced by the other in `configure`.
+//!
+//! [`ColumnMerges::measure`] synthesizes a circuit, records the rows used by each advice
+//! column and greedily groups the columns whose rows never overlap. The rows used by a
+//! column are the rows it is assigned at, extended by the spread of the rotations it is
+//! queried at, so that a gate reading one column of a group never reads the cells of
+//! another one. Columns queried by the same gate, lookup or shuffle are never grouped:
+//! once merged, the expressions reading both of them would read the same cells twice.
+//! This is a heuristic: the suggestions should be checked with the `MockProver` once
+//! applied.
+//!
+//! ```ignore
+//! let merges = ColumnMerges::measure(k, &circuit)?;
+//! println!("{}", merges);
+//! println!("{}", merges.rewrite_plan());
+//! ```
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+use group::prime::PrimeGroup;
+use halo2_middleware::circuit::{Advice, Any};
+use halo2_middleware::ff::{Field, PrimeField};
+
+use halo2_common::{
+    circuit::layouter::RegionColumn,
+    plonk::{circuit::Column, Circuit, Error, Expression, FloorPlanner},
+};
+
+use super::cost::Layout;
+use crate::circuit::CompiledConstraintSystem;
+
+/// How an advice column is used by a circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdviceColumnUsage {
+    /// The column.
+    pub column: Column<Advice>,
+    /// The name of the column, if any.
+    pub name: Option<String>,
+    /// The number of rows the column is assigned at.
+    pub assigned_rows: usize,
+    /// The rotations the column is queried at.
+    pub rotations: BTreeSet<i32>,
+    /// Whether the column is part of the permutation argument.
+    pub equality: bool,
+    /// Whether the column is blinded.
+    pub blinded: bool,
+}
+
+impl AdviceColumnUsage {
+    fn describe(&self) -> String {
+        match &self.name {
+            Some(name) => format!("advice column {} ({})", self.column.index(), name),
+            None => format!("advice column {}", self.column.index()),
+        }
+    }
+}
+
+/// A group of advice columns that could be merged into a single one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeSuggestion {
+    /// The index of the column the other ones are merged into.
+    pub target: usize,
+    /// The indices of the columns that are replaced by `target`.
+    pub merged: Vec<usize>,
+    /// The number of evaluations removed from each proof.
+    pub saved_evaluations: usize,
+}
+
+/// The advice columns of a circuit that could be merged, see the [module
+/// documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnMerges {
+    /// The circuit has `2^k` rows.
+    pub k: u32,
+    /// The usage of each advice column, indexed by column.
+    pub columns: Vec<AdviceColumnUsage>,
+    /// The suggested merges, each one removing `merged.len()` columns.
+    pub suggestions: Vec<MergeSuggestion>,
+}
+
+struct Group {
+    target: usize,
+    merged: Vec<usize>,
+    /// The columns queried by the same expressions as a column of the group.
+    conflicts: HashSet<usize>,
+    phase: u8,
+    blinded: bool,
+    rows: HashSet<usize>,
+}
+
+/// Returns the indices of the advice columns queried by `expressions`.
+fn advice_columns<'a, F: Field>(
+    expressions: impl IntoIterator<Item = &'a Expression<F>>,
+) -> HashSet<usize> {
+    expressions
+        .into_iter()
+        .flat_map(|expression| {
+            expression.evaluate(
+                &|_| vec![],
+                &|_| vec![],
+                &|_| vec![],
+                &|query| vec![query.column_index],
+                &|_| vec![],
+                &|_| vec![],
+                &|a| a,
+                &|mut a, mut b| {
+                    a.append(&mut b);
+                    a
+                },
+                &|mut a, mut b| {
+                    a.append(&mut b);
+                    a
+                },
+                &|a, _| a,
+            )
+        })
+        .collect()
+}
+
+impl ColumnMerges {
+    /// Synthesizes `circuit` with `2^k` rows and suggests advice columns to merge.
+    pub fn measure<F: Field, ConcreteCircuit: Circuit<F>>(
+        k: u32,
+        circuit: &ConcreteCircuit,
+    ) -> Result<Self, Error> {
+        let (cs, config) = CompiledConstraintSystem::new(circuit).to_parts();
+        let mut layout = Layout::new(k, 1 << k, cs.num_selectors);
+        ConcreteCircuit::FloorPlanner::synthesize(
+            &mut layout,
+            circuit,
+            config,
+            cs.constants.clone(),
+        )?;
+
+        let mut assigned: Vec<HashSet<usize>> = vec![HashSet::new(); cs.num_advice_columns];
+        let cells = layout
+            .regions
+            .iter()
+            .flat_map(|region| region.cells.iter())
+            .chain(layout.loose_cells.iter());
+        for (column, row) in cells {
+            if let RegionColumn::Column(column) = column {
+                if let Any::Advice(_) = column.column_type() {
+                    assigned[column.index()].insert(*row);
+                }
+            }
+        }
+
+        let mut rotations = vec![BTreeSet::new(); cs.num_advice_columns];
+        for (column, rotation) in cs.advice_queries.iter() {
+            rotations[column.index()].insert(rotation.0);
+        }
+        // The advice columns queried by each gate, lookup and shuffle conflict with each
+        // other.
+        let queried_together = cs
+            .gates
+            .iter()
+            .map(|gate| advice_columns(gate.polynomials()))
+            .chain(cs.lookups.iter().map(|lookup| {
+                advice_columns(
+                    lookup
+                        .input_expressions()
+                        .iter()
+                        .chain(lookup.table_expressions()),
+                )
+            }))
+            .chain(cs.shuffles.iter().map(|shuffle| {
+                advice_columns(
+                    shuffle
+                        .input_expressions()
+                        .iter()
+                        .chain(shuffle.shuffle_expressions()),
+                )
+            }));
+        let mut conflicts: Vec<HashSet<usize>> = vec![HashSet::new(); cs.num_advice_columns];
+        for columns in queried_together {
+            for column in columns.iter() {
+                conflicts[*column].extend(columns.iter().filter(|other| *other != column).copied());
+            }
+        }
+
+        let equality: HashSet<_> = cs.permutation.get_columns().into_iter().collect();
+        let phases = cs.advice_column_phase();
+
+        let columns: Vec<_> = (0..cs.num_advice_columns)
+            .map(|index| {
+                let column = Column::new(index, Advice::new(phases[index]));
+                AdviceColumnUsage {
+                    column,
+                    name: cs.column_name(column).map(str::to_string),
+                    assigned_rows: assigned[index].len(),
+                    rotations: rotations[index].clone(),
+                    equality: equality.contains(&Column::<Any>::from(column)),
+                    blinded: !cs.unblinded_advice_columns.contains(&index),
+                }
+            })
+            .collect();
+
+        // A gate that reads a column at some rotation, at a row it is assigned, reads it
+        // at all its other rotations relative to the same row.
+        let footprint = |index: usize| -> HashSet<usize> {
+            let rotations = &columns[index].rotations;
+            let spreads: BTreeSet<i64> = rotations
+                .iter()
+                .flat_map(|a| rotations.iter().map(move |b| (*b - *a) as i64))
+                .chain(Some(0))
+                .collect();
+            assigned[index]
+                .iter()
+                .flat_map(|row| spreads.iter().map(move |spread| *row as i64 + spread))
+                .filter(|row| *row >= 0)
+                .map(|row| row as usize)
+                .collect()
+        };
+
+        let mut groups: Vec<Group> = vec![];
+        for (index, usage) in columns.iter().enumerate() {
+            let rows = footprint(index);
+            let phase = usage.column.column_type().phase();
+            match groups.iter_mut().find(|group| {
+                group.phase == phase
+                    && group.blinded == usage.blinded
+                    && !group.conflicts.contains(&index)
+                    && group.rows.is_disjoint(&rows)
+            }) {
+                Some(group) => {
+                    group.merged.push(index);
+                    group.conflicts.extend(conflicts[index].iter().copied());
+                    group.rows.extend(rows);
+                }
+                None => groups.push(Group {
+                    target: index,
+                    merged: vec![],
+                    conflicts: conflicts[index].clone(),
+                    phase,
+                    blinded: usage.blinded,
+                    rows,
+                }),
+            }
+        }
+
+        let suggestions = groups
+            .into_iter()
+            .filter(|group| !group.merged.is_empty())
+            .map(|group| {
+                let members = Some(group.target).into_iter().chain(group.merged.clone());
+                let mut evaluations = 0;
+                let mut union = BTreeSet::new();
+                let mut permutation_columns = 0;
+                for index in members {
+                    evaluations += columns[index].rotations.len();
+                    union.extend(columns[index].rotations.iter().copied());
+                    permutation_columns += columns[index].equality as usize;
+                }
+                // Each column of the permutation argument is evaluated once.
+                let saved_permutation_evaluations = permutation_columns.saturating_sub(1);
+                MergeSuggestion {
+                    target: group.target,
+                    merged: group.merged,
+                    saved_evaluations: evaluations - union.len() + saved_permutation_evaluations,
+                }
+            })
+            .collect();
+
+        Ok(ColumnMerges {
+            k,
+            columns,
+            suggestions,
+        })
+    }
+
+    /// Returns the number of advice columns removed by the suggested merges.
+    ///
+    /// Each removed column saves the prover a commitment, which is an MSM of size `2^k`,
+    /// and the FFTs of the column to the extended domain.
+    pub fn saved_columns(&self) -> usize {
+        self.suggestions
+            .iter()
+            .map(|suggestion| suggestion.merged.len())
+            .sum()
+    }
+
+    /// Returns the number of evaluations removed from each proof by the suggested merges.
+    pub fn saved_evaluations(&self) -> usize {
+        self.suggestions
+            .iter()
+            .map(|suggestion| suggestion.saved_evaluations)
+            .sum()
+    }
+
+    /// Returns the number of bytes removed from each proof by the suggested merges, for
+    /// commitments in the group `G`.
+    pub fn saved_proof_size<G: PrimeGroup>(&self) -> usize {
+        let point = G::Repr::default().as_ref().len();
+        let scalar = <G::Scalar as PrimeField>::Repr::default().as_ref().len();
+        self.saved_columns() * point + self.saved_evaluations() * scalar
+    }
+
+    /// Returns the steps to apply the suggested merges to the circuit, one per line.
+    pub fn rewrite_plan(&self) -> String {
+        let mut plan = String::new();
+        for suggestion in self.suggestions.iter() {
+            let target = &self.columns[suggestion.target];
+            for index in suggestion.merged.iter() {
+                let merged = &self.columns[*index];
+                plan.push_str(&format!(
+                    "replace {} with {} in `configure`\n",
+                    merged.describe(),
+                    target.describe()
+                ));
+                if merged.equality && !target.equality {
+                    plan.push_str(&format!("enable equality on {}\n", target.describe()));
+                }
+            }
+        }
+        plan
+    }
+}
+
+impl fmt::Display for ColumnMerges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} advice columns, {} could be removed ({} evaluations per proof)",
+            self.columns.len(),
+            self.saved_columns(),
+            self.saved_evaluations()
+        )?;
+        for suggestion in self.suggestions.iter() {
+            let merged: Vec<_> = suggestion
+                .merged
+                .iter()
+                .map(|index| self.columns[*index].describe())
+                .collect();
+            writeln!(
+                f,
+                "- merge {} into {}",
+                merged.join(", "),
+                self.columns[suggestion.target].describe()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColumnMerges;
+    use crate::circuit::{Layouter, SimpleFloorPlanner, Value};
+    use halo2_common::plonk::{circuit::Column, Circuit, ConstraintSystem, Error};
+    use halo2_middleware::circuit::Advice;
+    use halo2_middleware::poly::Rotation;
+    use halo2curves::pasta::{Eq, Fp};
+
+    /// A circuit whose columns `a` and `b` are used at different rows, and `c` at all of
+    /// them. `a` and `b` are queried by the same gate, or with `SEPARATE` by different
+    /// gates.
+    struct MyCircuit<const SEPARATE: bool>;
+
+    impl<const SEPARATE: bool> Circuit<Fp> for MyCircuit<SEPARATE> {
+        type Config = [Column<Advice>; 3];
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let b = meta.advice_column();
+            let c = meta.advice_column();
+            let s = meta.selector();
+            if SEPARATE {
+                meta.create_gate("a", |meta| {
+                    let s = meta.query_selector(s);
+                    let a = meta.query_advice(a, Rotation::cur());
+                    let c = meta.query_advice(c, Rotation::cur());
+                    let next = meta.query_advice(c, Rotation::next());
+                    vec![s * (a - c + next)]
+                });
+                meta.create_gate("b", |meta| {
+                    let s = meta.query_selector(s);
+                    let b = meta.query_advice(b, Rotation::cur());
+                    let c = meta.query_advice(c, Rotation::cur());
+                    vec![s * (b - c)]
+                });
+            } else {
+                meta.create_gate("a + b", |meta| {
+                    let s = meta.query_selector(s);
+                    let a = meta.query_advice(a, Rotation::cur());
+                    let b = meta.query_advice(b, Rotation::cur());
+                    let c = meta.query_advice(c, Rotation::cur());
+                    let next = meta.query_advice(c, Rotation::next());
+                    vec![s * (a + b - c + next)]
+                });
+            }
+            [a, b, c]
+        }
+
+        fn synthesize(
+            &self,
+            [a, b, c]: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "region",
+                |mut region| {
+                    region.assign_advice(|| "a", a, 0, || Value::known(Fp::one()))?;
+                    region.assign_advice(|| "b", b, 1, || Value::known(Fp::one()))?;
+                    for row in 0..2 {
+                        region.assign_advice(|| "c", c, row, || Value::known(Fp::one()))?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn merges_disjoint_columns() {
+        let merges = ColumnMerges::measure(4, &MyCircuit::<true>).unwrap();
+        assert_eq!(merges.suggestions.len(), 1);
+        assert_eq!(merges.suggestions[0].target, 0);
+        assert_eq!(merges.suggestions[0].merged, vec![1]);
+        assert_eq!(merges.saved_columns(), 1);
+        assert_eq!(merges.saved_evaluations(), 1);
+        assert_eq!(merges.saved_proof_size::<Eq>(), 64);
+        assert_eq!(
+            merges.rewrite_plan(),
+            "replace advice column 1 with advice column 0 in `configure`\n"
+        );
+    }
+
+    #[test]
+    fn keeps_columns_queried_together() {
+        // Merging `a` and `b` would turn the gate `a + b` into `2 * a`.
+        let merges = ColumnMerges::measure(4, &MyCircuit::<false>).unwrap();
+        assert!(merges.suggestions.is_empty());
+        assert_eq!(merges.saved_columns(), 0);
+        assert_eq!(merges.rewrite_plan(), "");
+    }
+}
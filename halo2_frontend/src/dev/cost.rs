@@ -16,11 +16,13 @@ use halo2_common::{
     circuit::{layouter::RegionColumn, Value},
     plonk::{
         circuit::{Challenge, Column},
-        Assigned, Assignment, Circuit, ConstraintSystem, Error, FloorPlanner, Selector,
+        Assigned, Assignment, Circuit, Error, FloorPlanner, Selector,
     },
 };
 use halo2_middleware::circuit::{Advice, Any, Fixed, Instance};
 
+use crate::circuit::CompiledConstraintSystem;
+
 /// Measures a circuit to determine its costs, and explain what contributes to them.
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -273,9 +275,20 @@ impl<G: PrimeGroup, ConcreteCircuit: Circuit<G::Scalar>> CircuitCost<G, Concrete
     ///
     /// Panics if `k` is not large enough for the circuit.
     pub fn measure(k: u32, circuit: &ConcreteCircuit) -> Self {
+        Self::measure_cached(k, circuit, &CompiledConstraintSystem::new(circuit))
+    }
+
+    /// Measures a circuit like [`CircuitCost::measure`], using the result of `configure`
+    /// cached in `compiled_cs` instead of running it again.
+    ///
+    /// Panics if `k` is not large enough for the circuit.
+    pub fn measure_cached(
+        k: u32,
+        circuit: &ConcreteCircuit,
+        compiled_cs: &CompiledConstraintSystem<G::Scalar, ConcreteCircuit>,
+    ) -> Self {
         // Collect the layout details.
-        let mut cs = ConstraintSystem::default();
-        let config = ConcreteCircuit::configure(&mut cs);
+        let (cs, config) = compiled_cs.to_parts();
         let mut layout = Layout::new(k, 1 << k, cs.num_selectors);
         ConcreteCircuit::FloorPlanner::synthesize(
             &mut layout,
@@ -529,6 +542,7 @@ mod tests {
     use halo2curves::pasta::{Eq, Fp};
 
     use crate::circuit::SimpleFloorPlanner;
+    use halo2_common::plonk::ConstraintSystem;
 
     use super::*;
 
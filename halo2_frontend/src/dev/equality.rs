@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use halo2_middleware::circuit::Any;
+use halo2_middleware::ff::Field;
+
+use halo2_common::plonk::{circuit::Column, Circuit, Error, FloorPlanner};
+
+use super::cost::Layout;
+use crate::circuit::CompiledConstraintSystem;
+
+/// The cost of the columns in the permutation argument of a circuit, and the copy
+/// constraints made on each of them.
+///
+/// The permutation argument splits its columns into chunks of `chunk_len` columns, each of
+/// which adds a grand product commitment and its evaluations to the proof, on top of the
+/// commitment and evaluations of each column. Columns with equality enabled but without
+/// any copy constraint can be removed from the argument, see
+/// [`CompiledConstraintSystem::prune_unused_equality`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EqualityReport {
+    /// The number of columns in each chunk of the permutation argument.
+    pub chunk_len: usize,
+    /// The columns of the permutation argument, in order.
+    pub columns: Vec<EqualityColumn>,
+}
+
+/// A column of the permutation argument, in an [`EqualityReport`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EqualityColumn {
+    /// The column.
+    pub column: Column<Any>,
+    /// The chunk of the permutation argument the column is in.
+    pub chunk: usize,
+    /// The number of copy constraints involving a cell of the column.
+    pub copies: usize,
+}
+
+impl EqualityReport {
+    /// Synthesizes `circuit` with `2^k` rows and reports the copy constraints made on each
+    /// column of its permutation argument.
+    pub fn measure<F: Field, ConcreteCircuit: Circuit<F>>(
+        k: u32,
+        circuit: &ConcreteCircuit,
+    ) -> Result<Self, Error> {
+        Self::measure_cached(k, circuit, &CompiledConstraintSystem::new(circuit))
+    }
+
+    /// Reports on a circuit like [`EqualityReport::measure`], using the result of
+    /// `configure` cached in `compiled_cs` instead of running it again.
+    pub fn measure_cached<F: Field, ConcreteCircuit: Circuit<F>>(
+        k: u32,
+        circuit: &ConcreteCircuit,
+        compiled_cs: &CompiledConstraintSystem<F, ConcreteCircuit>,
+    ) -> Result<Self, Error> {
+        let (cs, config) = compiled_cs.to_parts();
+        let mut layout = Layout::new(k, 1 << k, cs.num_selectors);
+        ConcreteCircuit::FloorPlanner::synthesize(
+            &mut layout,
+            circuit,
+            config,
+            cs.constants.clone(),
+        )?;
+        let (cs, _) = cs.compress_selectors(layout.selectors);
+
+        let mut copies: HashMap<Column<Any>, usize> = HashMap::new();
+        for (left, _, right, _) in layout.equality {
+            *copies.entry(left).or_default() += 1;
+            // A copy constraint within a column only counts once.
+            if right != left {
+                *copies.entry(right).or_default() += 1;
+            }
+        }
+
+        let chunk_len = cs.degree() - 2;
+        let columns = cs
+            .permutation()
+            .get_columns()
+            .into_iter()
+            .enumerate()
+            .map(|(i, column)| EqualityColumn {
+                column,
+                chunk: i / chunk_len,
+                copies: copies.get(&column).copied().unwrap_or_default(),
+            })
+            .collect();
+        Ok(EqualityReport { chunk_len, columns })
+    }
+
+    /// Returns the number of chunks of the permutation argument.
+    pub fn chunks(&self) -> usize {
+        (self.columns.len() + self.chunk_len - 1) / self.chunk_len
+    }
+
+    /// Returns the columns of the permutation argument without any copy constraint.
+    pub fn unused_columns(&self) -> Vec<Column<Any>> {
+        self.columns
+            .iter()
+            .filter(|column| column.copies == 0)
+            .map(|column| column.column)
+            .collect()
+    }
+
+    /// Returns the number of chunks of the permutation argument once the columns without
+    /// any copy constraint are removed.
+    pub fn chunks_after_pruning(&self) -> usize {
+        let used = self.columns.len() - self.unused_columns().len();
+        (used + self.chunk_len - 1) / self.chunk_len
+    }
+}
+
+impl fmt::Display for EqualityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} columns in {} chunks of {}",
+            self.columns.len(),
+            self.chunks(),
+            self.chunk_len
+        )?;
+        for column in &self.columns {
+            write!(
+                f,
+                "  {:?} {}: chunk {}, {} copies",
+                column.column.column_type(),
+                column.column.index(),
+                column.chunk,
+                column.copies
+            )?;
+            if column.copies == 0 {
+                write!(f, " (unused)")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(
+            f,
+            "{} chunks without the unused columns",
+            self.chunks_after_pruning()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EqualityReport;
+    use crate::circuit::{
+        compile_circuit_cached, CompiledConstraintSystem, Layouter, SimpleFloorPlanner, Value,
+    };
+    use halo2_common::plonk::{circuit::Column, Circuit, ConstraintSystem, Error};
+    use halo2_middleware::circuit::{Advice, Any};
+    use halo2curves::pasta::Fp;
+
+    #[derive(Default)]
+    struct MyCircuit;
+
+    impl Circuit<Fp> for MyCircuit {
+        type Config = [Column<Advice>; 3];
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let columns = [
+                meta.advice_column(),
+                meta.advice_column(),
+                meta.advice_column(),
+            ];
+            for column in columns {
+                meta.enable_equality(column);
+            }
+            columns
+        }
+
+        fn synthesize(
+            &self,
+            [a, b, _]: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "copy",
+                |mut region| {
+                    let x = region.assign_advice(|| "x", a, 0, || Value::known(Fp::one()))?;
+                    x.copy_advice(|| "y", &mut region, b, 0)?;
+                    x.copy_advice(|| "z", &mut region, a, 1)?;
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn unused_columns() {
+        let [a, b, c] = MyCircuit::configure(&mut ConstraintSystem::default());
+        let report = EqualityReport::measure(4, &MyCircuit).unwrap();
+        let copies: Vec<_> = report
+            .columns
+            .iter()
+            .map(|column| (column.column, column.copies))
+            .collect();
+        assert_eq!(copies, [(a.into(), 2), (b.into(), 1), (c.into(), 0)]);
+        assert_eq!(report.unused_columns(), [Column::<Any>::from(c)]);
+
+        let compiled_cs = CompiledConstraintSystem::new(&MyCircuit);
+        let (_, _, cs) = compile_circuit_cached(4, &MyCircuit, &compiled_cs, false).unwrap();
+        assert_eq!(cs.permutation().get_columns().len(), 3);
+        let (_, _, cs) =
+            compile_circuit_cached(4, &MyCircuit, &compiled_cs.prune_unused_equality(), false)
+                .unwrap();
+        assert_eq!(
+            cs.permutation().get_columns(),
+            [Column::<Any>::from(a), b.into()]
+        );
+    }
+}
@@ -89,11 +89,12 @@ impl FailureLocation {
             })
             .collect();
 
-        Self::find(regions, failure_row, failure_columns)
+        Self::find(cs, regions, failure_row, failure_columns)
     }
 
     /// Figures out whether the given row and columns overlap an assigned region.
-    pub(super) fn find(
+    pub(super) fn find<F: Field>(
+        cs: &ConstraintSystem<F>,
         regions: &[Region],
         failure_row: usize,
         failure_columns: HashSet<Column<Any>>,
@@ -115,7 +116,12 @@ impl FailureLocation {
                 }
             })
             .map(|(r_i, r)| FailureLocation::InRegion {
-                region: (r_i, r.name.clone(), r.annotations.clone()).into(),
+                region: (
+                    r_i,
+                    r.name.clone(),
+                    r.annotations_or_names(&cs.general_column_annotations),
+                )
+                    .into(),
                 offset: failure_row - r.rows.unwrap().0,
             })
             .unwrap_or_else(|| FailureLocation::OutsideRegion { row: failure_row })
@@ -483,6 +489,40 @@ fn render_constraint_not_satisfied<F: Field>(
     }
 }
 
+/// Renders where each cell of a `VerifyFailure::ConstraintNotSatisfied` was assigned,
+/// following the output of [`render_constraint_not_satisfied`].
+///
+/// ```text
+///   Cell provenance:
+///     x0 = 'a' in region 'Faulty synthesis' at src/circuit.rs:42:20
+///     x1 = unassigned
+/// ```
+#[cfg(feature = "cell-provenance")]
+fn render_cell_provenance<F: Field>(
+    prover: &MockProver<F>,
+    location: &FailureLocation,
+    cell_values: &[(metadata::VirtualCell, String)],
+) {
+    let row = match location {
+        FailureLocation::InRegion { region, offset } => {
+            prover.regions[region.index].rows.unwrap().0 + offset
+        }
+        FailureLocation::OutsideRegion { row } => *row,
+    } as i32;
+    let n = prover.n as i32;
+
+    eprintln!();
+    eprintln!("  Cell provenance:");
+    for (i, (cell, _)) in cell_values.iter().enumerate() {
+        let column = Column::new(cell.column.index, cell.column.column_type);
+        let cell_row = (row + cell.rotation).rem_euclid(n) as usize;
+        match prover.provenance(column, cell_row) {
+            Some(provenance) => eprintln!("    x{i} = {provenance}"),
+            None => eprintln!("    x{i} = unassigned"),
+        }
+    }
+}
+
 /// Renders `VerifyFailure::Lookup`.
 ///
 /// ```text
@@ -857,7 +897,14 @@ impl VerifyFailure {
                 location,
                 cell_values,
             } => {
-                render_constraint_not_satisfied(&prover.cs.gates, constraint, location, cell_values)
+                render_constraint_not_satisfied(
+                    &prover.cs.gates,
+                    constraint,
+                    location,
+                    cell_values,
+                );
+                #[cfg(feature = "cell-provenance")]
+                render_cell_provenance(prover, location, cell_values);
             }
             Self::Lookup {
                 name,
@@ -0,0 +1,428 @@
+//! A fast check of a `MockProver`, reporting only whether it is satisfied.
+
+use std::fmt;
+
+use halo2_middleware::circuit::{Any, ColumnMid};
+use halo2_middleware::ff::{Field, FromUniformBytes};
+
+use halo2_common::multicore::prelude::*;
+use halo2_common::plonk::Expression;
+
+use super::{CellValue, InstanceValue, MockProver, Value};
+
+/// The first failure found by [`MockProver::check`].
+///
+/// A `FastFailure` only identifies the failing check, without the region, cell values
+/// and names carried by a [`VerifyFailure`]. Once a failing circuit has been found, for
+/// example the shrunken counterexample of a property test, [`MockProver::verify`] or
+/// [`MockProver::assert_satisfied`] report the full diagnostics.
+///
+/// [`VerifyFailure`]: super::VerifyFailure
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FastFailure {
+    /// A cell queried by a gate enabled by a selector was not assigned.
+    CellNotAssigned {
+        /// The index of the gate.
+        gate: usize,
+        /// The row the gate is enabled at.
+        gate_row: usize,
+        /// The unassigned cell.
+        column: ColumnMid,
+        /// The row of the unassigned cell.
+        row: usize,
+    },
+    /// A constraint doesn't evaluate to zero.
+    ConstraintNotSatisfied {
+        /// The index of the gate.
+        gate: usize,
+        /// The index of the constraint within the gate.
+        constraint: usize,
+        /// The row the constraint is evaluated at.
+        row: usize,
+    },
+    /// A constraint depends on a poisoned cell.
+    ConstraintPoisoned {
+        /// The index of the gate.
+        gate: usize,
+        /// The index of the constraint within the gate.
+        constraint: usize,
+    },
+    /// The inputs of a lookup at a row are not in its table.
+    Lookup {
+        /// The index of the lookup.
+        lookup: usize,
+        /// The row of the inputs.
+        row: usize,
+    },
+    /// The inputs of a shuffle are not a permutation of its shuffled values.
+    Shuffle {
+        /// The index of the shuffle.
+        shuffle: usize,
+    },
+    /// Two cells constrained to be equal have different values.
+    Permutation {
+        /// The column of the first cell.
+        column: ColumnMid,
+        /// The row of the first cell.
+        row: usize,
+    },
+}
+
+impl fmt::Display for FastFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FastFailure::CellNotAssigned {
+                gate,
+                gate_row,
+                column,
+                row,
+            } => write!(
+                f,
+                "cell ({column:?}, {row}) queried by gate {gate} at row {gate_row} is not assigned"
+            ),
+            FastFailure::ConstraintNotSatisfied {
+                gate,
+                constraint,
+                row,
+            } => write!(
+                f,
+                "constraint {constraint} of gate {gate} is not satisfied at row {row}"
+            ),
+            FastFailure::ConstraintPoisoned { gate, constraint } => write!(
+                f,
+                "constraint {constraint} of gate {gate} depends on a poisoned cell"
+            ),
+            FastFailure::Lookup { lookup, row } => {
+                write!(
+                    f,
+                    "inputs of lookup {lookup} at row {row} are not in its table"
+                )
+            }
+            FastFailure::Shuffle { shuffle } => {
+                write!(f, "inputs of shuffle {shuffle} are not a permutation")
+            }
+            FastFailure::Permutation { column, row } => {
+                write!(f, "copy of cell ({column:?}, {row}) is not satisfied")
+            }
+        }
+    }
+}
+
+impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
+    /// Returns `true` if this `MockProver` is satisfied.
+    ///
+    /// This is equivalent to `self.check().is_ok()`, see [`MockProver::check`].
+    pub fn is_satisfied(&self) -> bool {
+        self.check().is_ok()
+    }
+
+    /// Returns `Ok(())` if this `MockProver` is satisfied, or the first failure found.
+    ///
+    /// This performs the same checks as [`MockProver::verify`] at the usable rows, but
+    /// stops at the first failure and doesn't build its report, which makes it suitable
+    /// for property tests running many circuits. The rows of each check are searched in
+    /// parallel, and the failure at the lowest row is returned, so that the result is
+    /// deterministic.
+    pub fn check(&self) -> Result<(), FastFailure> {
+        if let Some(failure) = self.check_selectors() {
+            return Err(failure);
+        }
+        if let Some(failure) = self.check_gates() {
+            return Err(failure);
+        }
+        if let Some(failure) = self.check_lookups() {
+            return Err(failure);
+        }
+        if let Some(failure) = self.check_permutation() {
+            return Err(failure);
+        }
+        if let Some(failure) = self.check_shuffles() {
+            return Err(failure);
+        }
+        Ok(())
+    }
+
+    /// Checks that the cells queried by the gates enabled in each region are assigned.
+    fn check_selectors(&self) -> Option<FastFailure> {
+        let n = self.n as i32;
+        self.regions.iter().find_map(|region| {
+            let mut selectors: Vec<_> = region.enabled_selectors.iter().collect();
+            selectors.sort_unstable_by_key(|(selector, _)| selector.0);
+            selectors.into_iter().find_map(|(selector, rows)| {
+                self.cs
+                    .gates
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, gate)| gate.queried_selectors().contains(selector))
+                    .find_map(|(gate_index, gate)| {
+                        rows.iter().find_map(|&gate_row| {
+                            gate.queried_cells().iter().find_map(|cell| {
+                                let row = ((gate_row as i32 + n + cell.rotation.0) % n) as usize;
+                                let assigned = match cell.column.column_type() {
+                                    Any::Instance => matches!(
+                                        self.instance[cell.column.index()][row],
+                                        InstanceValue::Assigned(_)
+                                    ),
+                                    _ => region.cells.contains_key(&(cell.column, row)),
+                                };
+                                (!assigned).then(|| FastFailure::CellNotAssigned {
+                                    gate: gate_index,
+                                    gate_row,
+                                    column: cell.column.into(),
+                                    row,
+                                })
+                            })
+                        })
+                    })
+            })
+        })
+    }
+
+    /// Checks that all gates are satisfied at the usable and blinding rows.
+    fn check_gates(&self) -> Option<FastFailure> {
+        let blinding_rows = (self.n as usize - (self.cs.blinding_factors() + 1))..self.n as usize;
+        self.cs
+            .gates
+            .iter()
+            .enumerate()
+            .find_map(|(gate_index, gate)| {
+                self.usable_rows
+                    .clone()
+                    .into_par_iter()
+                    .chain(blinding_rows.clone().into_par_iter())
+                    // A gate on a subdomain is only enforced on its usable rows.
+                    .filter(|row| {
+                        gate.subdomain().map_or(true, |subdomain| {
+                            subdomain.contains(*row) && self.usable_rows.contains(row)
+                        })
+                    })
+                    .find_map_first(|row| {
+                        self.gates[gate_index]
+                            .evaluate(&self.gate_cells(), self.n as usize, row)
+                            .into_iter()
+                            .enumerate()
+                            .find_map(|(poly_index, value)| match value {
+                                Value::Real(x) if x.is_zero_vartime() => None,
+                                Value::Real(_) => Some(FastFailure::ConstraintNotSatisfied {
+                                    gate: gate_index,
+                                    constraint: poly_index,
+                                    row,
+                                }),
+                                Value::Poison => Some(FastFailure::ConstraintPoisoned {
+                                    gate: gate_index,
+                                    constraint: poly_index,
+                                }),
+                            })
+                    })
+            })
+    }
+
+    /// Evaluates `expression` at `row`.
+    pub(super) fn load(&self, expression: &Expression<F>, row: usize) -> Value<F> {
+        let n = self.n as i32;
+        let at = |rotation: i32| (row as i32 + n + rotation) as usize % n as usize;
+        expression.evaluate_lazy(
+            &|scalar| Value::Real(scalar),
+            &|_| panic!("virtual selectors are removed during optimization"),
+            &|query| self.fixed[query.column_index][at(query.rotation.0)].into(),
+            &|query| self.advice[query.column_index][at(query.rotation.0)].into(),
+            &|query| Value::Real(self.instance[query.column_index][at(query.rotation.0)].value()),
+            &|challenge| Value::Real(self.challenges[challenge.index()]),
+            &|a| -a,
+            &|a, b| a + b,
+            &|a, b| a * b,
+            &|a, scalar| a * scalar,
+            &Value::Real(F::ZERO),
+        )
+    }
+
+    /// Checks that the inputs of every lookup are in its table at the usable rows.
+    fn check_lookups(&self) -> Option<FastFailure> {
+        self.cs
+            .lookups
+            .iter()
+            .enumerate()
+            .find_map(|(lookup_index, lookup)| {
+                let load_row = |expressions: &[Expression<F>], row| {
+                    expressions
+                        .iter()
+                        .map(|expression| self.load(expression, row))
+                        .collect::<Vec<_>>()
+                };
+                let mut table: Vec<_> = self
+                    .usable_rows
+                    .clone()
+                    .into_par_iter()
+                    .map(|row| load_row(&lookup.table_expressions, row))
+                    .collect();
+                table.par_sort_unstable();
+                table.dedup();
+
+                self.usable_rows
+                    .clone()
+                    .into_par_iter()
+                    .find_map_first(|row| {
+                        table
+                            .binary_search(&load_row(&lookup.input_expressions, row))
+                            .is_err()
+                            .then_some(FastFailure::Lookup {
+                                lookup: lookup_index,
+                                row,
+                            })
+                    })
+            })
+    }
+
+    /// Checks that the inputs of every shuffle are a permutation of its shuffled values.
+    fn check_shuffles(&self) -> Option<FastFailure> {
+        self.cs
+            .shuffles
+            .iter()
+            .enumerate()
+            .find_map(|(shuffle_index, shuffle)| {
+                let load_rows = |expressions: &[Expression<F>]| {
+                    let mut rows: Vec<_> = self
+                        .usable_rows
+                        .clone()
+                        .into_par_iter()
+                        .map(|row| {
+                            expressions
+                                .iter()
+                                .map(|expression| self.load(expression, row))
+                                .collect::<Vec<_>>()
+                        })
+                        .collect();
+                    rows.par_sort_unstable();
+                    rows
+                };
+                (load_rows(&shuffle.input_expressions) != load_rows(&shuffle.shuffle_expressions))
+                    .then_some(FastFailure::Shuffle {
+                        shuffle: shuffle_index,
+                    })
+            })
+    }
+
+    /// Checks that the copies preserve the values of the cells.
+    fn check_permutation(&self) -> Option<FastFailure> {
+        let original = |column: ColumnMid, row: usize| match column.column_type {
+            Any::Advice(_) => self.advice[column.index][row],
+            // Unassigned fixed cells are zero in the fixed polynomials.
+            Any::Fixed => match self.fixed[column.index][row] {
+                CellValue::Unassigned => CellValue::Assigned(F::ZERO),
+                cell => cell,
+            },
+            Any::Instance => CellValue::Assigned(self.instance[column.index][row].value()),
+        };
+        self.permutation.copies.iter().find_map(|(a, b)| {
+            (original(a.column, a.row) != original(b.column, b.row)).then_some(
+                FastFailure::Permutation {
+                    column: a.column,
+                    row: a.row,
+                },
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::pasta::Fp;
+
+    use super::FastFailure;
+    use crate::circuit::{Layouter, SimpleFloorPlanner, Value};
+    use crate::dev::MockProver;
+    use halo2_common::plonk::{circuit::Column, Circuit, ConstraintSystem, Error, Selector};
+    use halo2_middleware::circuit::Advice;
+    use halo2_middleware::poly::Rotation;
+
+    #[derive(Clone)]
+    struct SquareConfig {
+        a: Column<Advice>,
+        b: Column<Advice>,
+        q: Selector,
+    }
+
+    /// Assigns `a` and `b` at each row of `rows`, with `b` constrained to `a * a`.
+    struct SquareCircuit {
+        rows: Vec<(u64, u64)>,
+    }
+
+    impl Circuit<Fp> for SquareCircuit {
+        type Config = SquareConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let b = meta.advice_column();
+            let q = meta.selector();
+            meta.create_gate("square", |cells| {
+                let a = cells.query_advice(a, Rotation::cur());
+                let b = cells.query_advice(b, Rotation::cur());
+                let q = cells.query_selector(q);
+                vec![q * (a.clone() * a - b)]
+            });
+            SquareConfig { a, b, q }
+        }
+
+        fn without_witnesses(&self) -> Self {
+            SquareCircuit { rows: vec![] }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "squares",
+                |mut region| {
+                    for (offset, (a, b)) in self.rows.iter().enumerate() {
+                        config.q.enable(&mut region, offset)?;
+                        region.assign_advice(
+                            || "a",
+                            config.a,
+                            offset,
+                            || Value::known(Fp::from(*a)),
+                        )?;
+                        region.assign_advice(
+                            || "b",
+                            config.b,
+                            offset,
+                            || Value::known(Fp::from(*b)),
+                        )?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn check_matches_verify() {
+        let circuit = SquareCircuit {
+            rows: vec![(2, 4), (3, 9), (4, 16)],
+        };
+        let prover = MockProver::run(4, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_ok());
+        assert_eq!(prover.check(), Ok(()));
+        assert!(prover.is_satisfied());
+
+        let circuit = SquareCircuit {
+            rows: vec![(2, 4), (3, 8), (4, 15)],
+        };
+        let prover = MockProver::run(4, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify().unwrap_err().len(), 2);
+        // The failure at the lowest row is returned.
+        assert_eq!(
+            prover.check(),
+            Err(FastFailure::ConstraintNotSatisfied {
+                gate: 0,
+                constraint: 0,
+                row: 1,
+            })
+        );
+        assert!(!prover.is_satisfied());
+    }
+}
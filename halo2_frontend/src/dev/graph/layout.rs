@@ -6,7 +6,10 @@ use plotters::{
 use std::collections::HashSet;
 use std::ops::Range;
 
-use crate::{circuit::layouter::RegionColumn, dev::cost::Layout};
+use crate::{
+    circuit::{layouter::RegionColumn, CompiledConstraintSystem},
+    dev::cost::Layout,
+};
 use halo2_common::plonk::{circuit::Column, Circuit, ConstraintSystem, FloorPlanner};
 use halo2_middleware::circuit::Any;
 
@@ -85,17 +88,30 @@ impl CircuitLayout {
         k: u32,
         circuit: &ConcreteCircuit,
         drawing_area: &DrawingArea<DB, Shift>,
+    ) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+        self.render_cached(
+            k,
+            circuit,
+            &CompiledConstraintSystem::new(circuit),
+            drawing_area,
+        )
+    }
+
+    /// Renders the given circuit like [`CircuitLayout::render`], using the result of
+    /// `configure` cached in `compiled_cs` instead of running it again.
+    pub fn render_cached<F: Field, ConcreteCircuit: Circuit<F>, DB: DrawingBackend>(
+        self,
+        k: u32,
+        circuit: &ConcreteCircuit,
+        compiled_cs: &CompiledConstraintSystem<F, ConcreteCircuit>,
+        drawing_area: &DrawingArea<DB, Shift>,
     ) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
         use plotters::coord::types::RangedCoordusize;
         use plotters::prelude::*;
 
         let n = 1 << k;
         // Collect the layout details.
-        let mut cs = ConstraintSystem::default();
-        #[cfg(feature = "circuit-params")]
-        let config = ConcreteCircuit::configure_with_params(&mut cs, circuit.params());
-        #[cfg(not(feature = "circuit-params"))]
-        let config = ConcreteCircuit::configure(&mut cs);
+        let (cs, config) = compiled_cs.to_parts();
         let mut layout = Layout::new(k, n, cs.num_selectors);
         ConcreteCircuit::FloorPlanner::synthesize(
             &mut layout,
@@ -293,6 +309,14 @@ impl CircuitLayout {
 
         // Render labels last, on top of everything else.
         if let Some(labels) = labels {
+            // Named columns are labelled at the top of the column.
+            for (column, name) in cs.general_column_annotations() {
+                let column = Column::new(column.index, column.column_type);
+                root.draw(
+                    &(EmptyElement::at((column_index(&cs, RegionColumn::Column(column)), 0))
+                        + Text::new(name.clone(), (2, 2), ("sans-serif", 12.0).into_font())),
+                )?;
+            }
             for (label, top_left) in labels {
                 root.draw(
                     &(EmptyElement::at(top_left)
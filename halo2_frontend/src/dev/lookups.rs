@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use halo2_middleware::ff::FromUniformBytes;
+
+use halo2_common::multicore::prelude::*;
+use halo2_common::plonk::Expression;
+
+use super::MockProver;
+
+/// How the tables of the lookup arguments of a circuit are used by their inputs.
+///
+/// Tables with many rows that are never looked up are candidates for splitting, and
+/// tables whose rows are looked up many times are those where an argument with
+/// multiplicities, such as mv-lookup, saves the most.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LookupReport {
+    /// The statistics of each lookup argument, in the order of the constraint system.
+    pub lookups: Vec<LookupStats>,
+}
+
+/// The statistics of a lookup argument, in a [`LookupReport`].
+///
+/// The rows of the table are counted once for each distinct value they hold, since the
+/// argument doesn't distinguish rows with the same value. The inputs are those at every
+/// usable row, including the rows where the lookup is disabled, which usually look up a
+/// default value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupStats {
+    /// The index of the lookup argument.
+    pub lookup_index: usize,
+    /// The name of the lookup argument.
+    pub name: String,
+    /// The number of distinct rows of the table.
+    pub table_rows: usize,
+    /// The number of inputs.
+    pub inputs: usize,
+    /// The number of inputs that are not in the table.
+    pub missing_inputs: usize,
+    /// The number of distinct table rows hit by each number of inputs, including the rows
+    /// hit by none.
+    pub hits: BTreeMap<usize, usize>,
+}
+
+impl LookupStats {
+    /// Returns the number of distinct table rows that no input hits.
+    pub fn unused_rows(&self) -> usize {
+        self.hits.get(&0).copied().unwrap_or_default()
+    }
+
+    /// Returns the fraction of the distinct table rows that no input hits.
+    pub fn unused_fraction(&self) -> f64 {
+        if self.table_rows == 0 {
+            0.0
+        } else {
+            self.unused_rows() as f64 / self.table_rows as f64
+        }
+    }
+
+    /// Returns the largest number of inputs hitting a single table row.
+    pub fn max_hits(&self) -> usize {
+        self.hits.keys().next_back().copied().unwrap_or_default()
+    }
+}
+
+impl<F: FromUniformBytes<64> + Ord> MockProver<F> {
+    /// Reports how often each row of the table of each lookup argument is hit by its
+    /// inputs, at the usable rows of the circuit.
+    ///
+    /// The report doesn't check the circuit: inputs that are not in their table are only
+    /// counted, and are reported by [`MockProver::verify`].
+    pub fn lookup_report(&self) -> LookupReport {
+        let lookups = self
+            .cs
+            .lookups
+            .iter()
+            .enumerate()
+            .map(|(lookup_index, lookup)| {
+                let load_rows = |expressions: &[Expression<F>]| {
+                    let mut rows: Vec<_> = self
+                        .usable_rows
+                        .clone()
+                        .into_par_iter()
+                        .map(|row| {
+                            expressions
+                                .iter()
+                                .map(|expression| self.load(expression, row))
+                                .collect::<Vec<_>>()
+                        })
+                        .collect();
+                    rows.par_sort_unstable();
+                    rows
+                };
+                let mut table = load_rows(&lookup.table_expressions);
+                table.dedup();
+                let inputs = load_rows(&lookup.input_expressions);
+
+                let mut row_hits = vec![0; table.len()];
+                let mut missing_inputs = 0;
+                for input in &inputs {
+                    match table.binary_search(input) {
+                        Ok(row) => row_hits[row] += 1,
+                        Err(_) => missing_inputs += 1,
+                    }
+                }
+                let mut hits = BTreeMap::new();
+                for count in row_hits {
+                    *hits.entry(count).or_default() += 1;
+                }
+
+                LookupStats {
+                    lookup_index,
+                    name: lookup.name.clone(),
+                    table_rows: table.len(),
+                    inputs: inputs.len(),
+                    missing_inputs,
+                    hits,
+                }
+            })
+            .collect();
+        LookupReport { lookups }
+    }
+}
+
+impl fmt::Display for LookupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for stats in &self.lookups {
+            writeln!(
+                f,
+                "lookup {} \"{}\": {} inputs in {} table rows, {} unused ({:.1}%)",
+                stats.lookup_index,
+                stats.name,
+                stats.inputs,
+                stats.table_rows,
+                stats.unused_rows(),
+                100.0 * stats.unused_fraction()
+            )?;
+            for (count, rows) in &stats.hits {
+                writeln!(f, "  {} rows hit {} times", rows, count)?;
+            }
+            if stats.missing_inputs > 0 {
+                writeln!(f, "  {} inputs not in the table", stats.missing_inputs)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::super::MockProver;
+    use crate::circuit::{Layouter, SimpleFloorPlanner, Value};
+    use halo2_common::plonk::{
+        circuit::Column, Circuit, ConstraintSystem, Error, Expression, Selector, TableColumn,
+    };
+    use halo2_middleware::circuit::Advice;
+    use halo2_middleware::poly::Rotation;
+    use halo2curves::pasta::Fp;
+
+    const K: u32 = 5;
+
+    struct MyCircuit;
+
+    impl Circuit<Fp> for MyCircuit {
+        type Config = (Column<Advice>, Selector, TableColumn);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let q = meta.complex_selector();
+            let table = meta.lookup_table_column();
+            meta.lookup("range", |meta| {
+                let a = meta.query_advice(a, Rotation::cur());
+                let q = meta.query_selector(q);
+                vec![(q * a, table)]
+            });
+            // A lookup that is never enabled.
+            meta.lookup("disabled", |meta| {
+                let a = meta.query_advice(a, Rotation::cur());
+                vec![(a * Expression::Constant(Fp::zero()), table)]
+            });
+            (a, q, table)
+        }
+
+        fn synthesize(
+            &self,
+            (a, q, table): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_table(
+                || "range",
+                |mut table_layouter| {
+                    for row in 0..8 {
+                        table_layouter.assign_cell(
+                            || "value",
+                            table,
+                            row,
+                            || Value::known(Fp::from(row as u64)),
+                        )?;
+                    }
+                    Ok(())
+                },
+            )?;
+            layouter.assign_region(
+                || "inputs",
+                |mut region| {
+                    for (row, value) in [1, 1, 1, 2, 2, 5].into_iter().enumerate() {
+                        q.enable(&mut region, row)?;
+                        region.assign_advice(|| "a", a, row, || Value::known(Fp::from(value)))?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn lookup_report() {
+        let prover = MockProver::run(K, &MyCircuit, vec![]).unwrap();
+        let report = prover.lookup_report();
+        let usable_rows = prover.usable_rows().len();
+
+        let range = &report.lookups[0];
+        assert_eq!(range.name, "range");
+        assert_eq!(range.table_rows, 8);
+        assert_eq!(range.inputs, usable_rows);
+        assert_eq!(range.missing_inputs, 0);
+        // The rows where the lookup is disabled all look up zero.
+        let hits: BTreeMap<_, _> = [(0, 4), (1, 1), (2, 1), (3, 1), (usable_rows - 6, 1)]
+            .into_iter()
+            .collect();
+        assert_eq!(range.hits, hits);
+        assert_eq!(range.unused_rows(), 4);
+        assert_eq!(range.unused_fraction(), 0.5);
+        assert_eq!(range.max_hits(), usable_rows - 6);
+
+        let disabled = &report.lookups[1];
+        assert_eq!(disabled.unused_rows(), 7);
+        assert_eq!(disabled.max_hits(), usable_rows);
+
+        assert!(report.to_string().starts_with(&format!(
+            "lookup 0 \"range\": {} inputs in 8 table rows, 4 unused (50.0%)",
+            usable_rows
+        )));
+    }
+}
@@ -0,0 +1,28 @@
+use std::fmt;
+use std::panic::Location;
+
+/// Where a cell was assigned, recorded by [`MockProver`](super::MockProver) when the
+/// `cell-provenance` feature is enabled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CellProvenance {
+    /// The name of the region the cell was assigned in, if any.
+    pub region: Option<String>,
+    /// The annotation given to the assignment.
+    pub annotation: String,
+    /// The location of the `Region` or `Table` method call that assigned the cell, if
+    /// it was assigned through one.
+    pub location: Option<&'static Location<'static>>,
+}
+
+impl fmt::Display for CellProvenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}'", self.annotation)?;
+        if let Some(region) = &self.region {
+            write!(f, " in region '{region}'")?;
+        }
+        if let Some(location) = self.location {
+            write!(f, " at {location}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,419 @@
+use std::fmt;
+
+use halo2_middleware::ff::Field;
+
+use halo2_common::plonk::{Circuit, ConstraintSystem, Error, FloorPlanner, Selector};
+
+use super::cost::Layout;
+use crate::circuit::CompiledConstraintSystem;
+
+/// The rows at which each selector of a circuit is enabled, once its regions are laid out.
+///
+/// This can be used to cross-check hand-written optimizations of the fixed columns that
+/// selectors are turned into, or to inline selector values in an external verifier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectorMap {
+    /// The circuit has `2^k` rows.
+    pub k: u32,
+    /// The sorted rows at which each selector is enabled, indexed by selector.
+    pub rows: Vec<Vec<usize>>,
+}
+
+/// Synthesizes `circuit` with `2^k` rows and returns the rows at which each of its
+/// selectors is enabled.
+///
+/// Returns `Error::NotEnoughRowsAvailable` if a selector is enabled outside of the usable
+/// rows of the circuit.
+pub fn selector_map<F: Field, ConcreteCircuit: Circuit<F>>(
+    k: u32,
+    circuit: &ConcreteCircuit,
+) -> Result<SelectorMap, Error> {
+    let (cs, config) = CompiledConstraintSystem::new(circuit).to_parts();
+    selector_map_inner(k, circuit, &cs, config)
+}
+
+fn selector_map_inner<F: Field, ConcreteCircuit: Circuit<F>>(
+    k: u32,
+    circuit: &ConcreteCircuit,
+    cs: &ConstraintSystem<F>,
+    config: ConcreteCircuit::Config,
+) -> Result<SelectorMap, Error> {
+    let n = 1usize << k;
+    let mut layout = Layout::new(k, n, cs.num_selectors);
+    ConcreteCircuit::FloorPlanner::synthesize(&mut layout, circuit, config, cs.constants.clone())?;
+
+    let usable_rows = n.saturating_sub(cs.blinding_factors() + 1);
+    let rows: Vec<Vec<usize>> = layout
+        .selectors
+        .iter()
+        .map(|selector| {
+            selector
+                .iter()
+                .enumerate()
+                .filter_map(|(row, enabled)| (*enabled).then_some(row))
+                .collect()
+        })
+        .collect();
+    if rows.iter().flatten().any(|row| *row >= usable_rows) {
+        return Err(Error::not_enough_rows_available(k));
+    }
+
+    Ok(SelectorMap { k, rows })
+}
+
+impl SelectorMap {
+    /// Returns the rows at which `selector` is enabled.
+    pub fn active_rows(&self, selector: Selector) -> &[usize] {
+        &self.rows[selector.0]
+    }
+
+    /// Returns whether `selector` is enabled at `row`.
+    pub fn is_active(&self, selector: Selector, row: usize) -> bool {
+        self.active_rows(selector).binary_search(&row).is_ok()
+    }
+
+    /// Returns the assignment of each selector, over all the rows of the circuit.
+    pub fn to_bitmaps(&self) -> Vec<Vec<bool>> {
+        self.rows
+            .iter()
+            .map(|rows| {
+                let mut bitmap = vec![false; 1 << self.k];
+                for row in rows {
+                    bitmap[*row] = true;
+                }
+                bitmap
+            })
+            .collect()
+    }
+
+    /// Returns the values of the fixed columns the selectors are turned into, for the
+    /// constraint system `cs` of the circuit.
+    ///
+    /// These columns follow the fixed columns of the circuit, as in `compile_circuit` with
+    /// the same `compress_selectors` setting.
+    pub fn fixed_columns<F: Field>(
+        &self,
+        cs: &ConstraintSystem<F>,
+        compress_selectors: bool,
+    ) -> Vec<Vec<F>> {
+        let cs = cs.clone();
+        if compress_selectors {
+            cs.compress_selectors(self.to_bitmaps()).1
+        } else {
+            cs.directly_convert_selectors_to_fixed(self.to_bitmaps()).1
+        }
+    }
+}
+
+/// How much of the trace of a circuit each of its gates is enabled on.
+///
+/// Gates that are never enabled constrain nothing, but their constraints are still
+/// evaluated by the prover and the verifier. They can be removed from the keys with
+/// [`CompiledConstraintSystem::prune_dead_gates`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct GateCoverage {
+    /// The number of usable rows of the circuit.
+    pub usable_rows: usize,
+    /// The coverage of each gate, in the order of the constraint system.
+    pub gates: Vec<GateActivity>,
+}
+
+/// The coverage of a gate, in a [`GateCoverage`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct GateActivity {
+    /// The index of the gate.
+    pub gate_index: usize,
+    /// The name of the gate.
+    pub name: String,
+    /// The selectors queried by the gate.
+    pub selectors: Vec<Selector>,
+    /// The number of usable rows where one of the selectors of the gate is enabled, or
+    /// all of them if the gate doesn't query any selector.
+    pub active_rows: usize,
+    /// The fraction of the usable rows where the gate is active.
+    pub active_fraction: f64,
+    /// Whether the gate constrains nothing, see `Gate::is_dead`.
+    pub dead: bool,
+}
+
+/// Synthesizes `circuit` with `2^k` rows and reports the fraction of the usable rows
+/// where each of its gates is enabled, and the gates that are never enabled.
+///
+/// Returns `Error::NotEnoughRowsAvailable` if a selector is enabled outside of the usable
+/// rows of the circuit.
+pub fn gate_coverage<F: Field, ConcreteCircuit: Circuit<F>>(
+    k: u32,
+    circuit: &ConcreteCircuit,
+) -> Result<GateCoverage, Error> {
+    let (cs, config) = CompiledConstraintSystem::new(circuit).to_parts();
+    let map = selector_map_inner(k, circuit, &cs, config)?;
+    let usable_rows = cs.usable_rows(k).len();
+    let bitmaps = map.to_bitmaps();
+
+    let gates = cs
+        .gates()
+        .iter()
+        .enumerate()
+        .map(|(gate_index, gate)| {
+            let selectors = gate.queried_selectors().to_vec();
+            let active_rows = if selectors.is_empty() {
+                usable_rows
+            } else {
+                (0..usable_rows)
+                    .filter(|row| selectors.iter().any(|s| bitmaps[s.0][*row]))
+                    .count()
+            };
+            GateActivity {
+                gate_index,
+                name: gate.name().to_string(),
+                selectors,
+                active_rows,
+                active_fraction: if usable_rows == 0 {
+                    0.0
+                } else {
+                    active_rows as f64 / usable_rows as f64
+                },
+                dead: gate.is_dead(&bitmaps),
+            }
+        })
+        .collect();
+    Ok(GateCoverage { usable_rows, gates })
+}
+
+impl GateCoverage {
+    /// Returns the indices of the gates that constrain nothing.
+    pub fn dead_gates(&self) -> Vec<usize> {
+        self.gates
+            .iter()
+            .filter(|gate| gate.dead)
+            .map(|gate| gate.gate_index)
+            .collect()
+    }
+}
+
+impl fmt::Display for GateCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for gate in &self.gates {
+            write!(
+                f,
+                "gate {} \"{}\": active at {} of {} usable rows ({:.1}%)",
+                gate.gate_index,
+                gate.name,
+                gate.active_rows,
+                self.usable_rows,
+                100.0 * gate.active_fraction
+            )?;
+            if gate.dead {
+                write!(f, " (dead)")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SelectorMap {
+    /// Writes the active rows of each selector on a line, with consecutive rows written as
+    /// ranges: `selector 0: 0..4, 7`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, rows) in self.rows.iter().enumerate() {
+            let mut ranges: Vec<(usize, usize)> = vec![];
+            for row in rows {
+                match ranges.last_mut() {
+                    Some((_, end)) if *end == *row => *end += 1,
+                    _ => ranges.push((*row, *row + 1)),
+                }
+            }
+            let ranges: Vec<_> = ranges
+                .into_iter()
+                .map(|(start, end)| {
+                    if end == start + 1 {
+                        start.to_string()
+                    } else {
+                        format!("{}..{}", start, end)
+                    }
+                })
+                .collect();
+            writeln!(f, "selector {}: {}", index, ranges.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gate_coverage, selector_map};
+    use crate::circuit::{
+        compile_circuit_cached, CompiledConstraintSystem, Layouter, SimpleFloorPlanner, Value,
+    };
+    use halo2_common::plonk::{circuit::Column, Circuit, ConstraintSystem, Error, Selector};
+    use halo2_middleware::circuit::Advice;
+    use halo2_middleware::poly::Rotation;
+    use halo2curves::pasta::Fp;
+
+    #[derive(Default)]
+    struct MyCircuit;
+
+    impl Circuit<Fp> for MyCircuit {
+        type Config = (Column<Advice>, Selector, Selector);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let s = meta.selector();
+            let t = meta.selector();
+            meta.create_gate("a", |meta| {
+                let s = meta.query_selector(s);
+                let t = meta.query_selector(t);
+                let a = meta.query_advice(a, Rotation::cur());
+                vec![s * a.clone(), t * (a - Fp::one())]
+            });
+            (a, s, t)
+        }
+
+        fn synthesize(
+            &self,
+            (a, s, t): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "zeros",
+                |mut region| {
+                    for offset in 0..3 {
+                        s.enable(&mut region, offset)?;
+                        region.assign_advice(|| "a", a, offset, || Value::known(Fp::zero()))?;
+                    }
+                    Ok(())
+                },
+            )?;
+            layouter.assign_region(
+                || "one",
+                |mut region| {
+                    t.enable(&mut region, 0)?;
+                    region.assign_advice(|| "a", a, 0, || Value::known(Fp::one()))?;
+                    s.enable(&mut region, 1)?;
+                    region.assign_advice(|| "a", a, 1, || Value::known(Fp::zero()))?;
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn active_rows() {
+        let (_, s, t) = MyCircuit::configure(&mut ConstraintSystem::default());
+        let map = selector_map(4, &MyCircuit).unwrap();
+        assert_eq!(map.active_rows(s), [0, 1, 2, 4]);
+        assert_eq!(map.active_rows(t), [3]);
+        assert!(map.is_active(t, 3) && !map.is_active(t, 4));
+        assert_eq!(map.to_string(), "selector 0: 0..3, 4\nselector 1: 3\n");
+
+        let compiled = CompiledConstraintSystem::new(&MyCircuit);
+        let fixed = map.fixed_columns(compiled.cs(), false);
+        assert_eq!(fixed.len(), 2);
+        assert_eq!(fixed[1][3], Fp::one());
+        assert_eq!(
+            fixed[1]
+                .iter()
+                .filter(|value| **value != Fp::zero())
+                .count(),
+            1
+        );
+
+        // Selectors can't be enabled in the blinding rows.
+        assert!(matches!(
+            selector_map(2, &MyCircuit).unwrap_err(),
+            Error::NotEnoughRowsAvailable { .. }
+        ));
+    }
+
+    struct DeadGateCircuit;
+
+    impl Circuit<Fp> for DeadGateCircuit {
+        type Config = (Column<Advice>, Selector, Selector);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let s = meta.selector();
+            let t = meta.selector();
+            meta.create_gate("used", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                vec![s * a]
+            });
+            meta.create_gate("dead", |meta| {
+                let t = meta.query_selector(t);
+                let a = meta.query_advice(a, Rotation::cur());
+                vec![t * (a - Fp::one())]
+            });
+            meta.create_gate("always", |meta| {
+                let a = meta.query_advice(a, Rotation::cur());
+                vec![a.clone() * (a - Fp::one())]
+            });
+            (a, s, t)
+        }
+
+        fn synthesize(
+            &self,
+            (a, s, _): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "zeros",
+                |mut region| {
+                    for offset in 0..4 {
+                        s.enable(&mut region, offset)?;
+                        region.assign_advice(|| "a", a, offset, || Value::known(Fp::zero()))?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn dead_gates() {
+        let coverage = gate_coverage(4, &DeadGateCircuit).unwrap();
+        let active: Vec<_> = coverage
+            .gates
+            .iter()
+            .map(|gate| (gate.name.as_str(), gate.active_rows, gate.dead))
+            .collect();
+        let usable_rows = coverage.usable_rows;
+        assert_eq!(
+            active,
+            [
+                ("used", 4, false),
+                ("dead", 0, true),
+                ("always", usable_rows, false)
+            ]
+        );
+        assert_eq!(coverage.gates[2].active_fraction, 1.0);
+        assert_eq!(coverage.dead_gates(), [1]);
+        assert!(coverage
+            .to_string()
+            .contains("gate 1 \"dead\": active at 0 of"));
+
+        let compiled_cs = CompiledConstraintSystem::new(&DeadGateCircuit);
+        let (_, _, cs) = compile_circuit_cached(4, &DeadGateCircuit, &compiled_cs, false).unwrap();
+        assert_eq!(cs.gates().len(), 3);
+        let (_, _, cs) =
+            compile_circuit_cached(4, &DeadGateCircuit, &compiled_cs.prune_dead_gates(), false)
+                .unwrap();
+        let names: Vec<_> = cs.gates().iter().map(|gate| gate.name()).collect();
+        assert_eq!(names, ["used", "always"]);
+    }
+}
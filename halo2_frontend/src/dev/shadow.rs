@@ -0,0 +1,613 @@
+//! Shadow execution of a circuit under two floor planners.
+//!
+//! A chip that accidentally relies on absolute rows, for example by reading with a
+//! rotated query the cells of whichever region happens to be placed next to its own, can
+//! be correct under one floor planner and broken under another. [`shadow_planners`]
+//! synthesizes a circuit under two floor planners and cross-checks the results.
+//!
+//! The layouts can't be compared row by row, since moving regions around is the job of
+//! the floor planners. Instead, the cells are located independently of the floor planner:
+//! the cells of a region by their offset from the first row the region uses, the cells of
+//! instance columns by their row, and the other cells, such as constants, by their value.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use halo2_common::plonk::{circuit::Column, Circuit, Error, FloorPlanner, Selector};
+use halo2_middleware::circuit::{Any, Fixed};
+use halo2_middleware::ff::{Field, FromUniformBytes};
+
+use super::{CellValue, MockProver, Region};
+use crate::circuit::WithFloorPlanner;
+
+/// A cell of a circuit, located independently of the floor planner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlacedCell<F> {
+    /// A cell assigned in a region, at an offset from the first row the region uses.
+    InRegion {
+        /// The index of the region.
+        region: usize,
+        /// The column of the cell.
+        column: Column<Any>,
+        /// The offset of the cell in the region.
+        offset: usize,
+    },
+    /// A cell of an instance column, whose row doesn't depend on the floor planner.
+    Instance {
+        /// The column of the cell.
+        column: Column<Any>,
+        /// The row of the cell.
+        row: usize,
+    },
+    /// Another cell outside of the regions, such as a constant, located by its value.
+    Global {
+        /// The column of the cell.
+        column: Column<Any>,
+        /// The value of the cell, if it is assigned.
+        value: Option<F>,
+    },
+}
+
+impl<F: Ord> PlacedCell<F> {
+    fn key(&self) -> (u8, usize, (u8, usize), usize, Option<&F>) {
+        match self {
+            PlacedCell::InRegion {
+                region,
+                column,
+                offset,
+            } => (0, *region, column_key(column), *offset, None),
+            PlacedCell::Instance { column, row } => (1, 0, column_key(column), *row, None),
+            PlacedCell::Global { column, value } => (2, 0, column_key(column), 0, value.as_ref()),
+        }
+    }
+}
+
+impl<F: Ord> PartialOrd for PlacedCell<F> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<F: Ord> Ord for PlacedCell<F> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+fn column_key(column: &Column<Any>) -> (u8, usize) {
+    let kind = match column.column_type() {
+        Any::Advice(_) => 0,
+        Any::Fixed => 1,
+        Any::Instance => 2,
+    };
+    (kind, column.index())
+}
+
+/// A difference between the syntheses of a circuit under two floor planners, in a
+/// [`ShadowReport`]. `first` and `second` are the values under each floor planner.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlannerMismatch<F> {
+    /// [`MockProver::verify`] reports a different number of failures.
+    Failures {
+        /// The number of failures under the first floor planner.
+        first: usize,
+        /// The number of failures under the second floor planner.
+        second: usize,
+    },
+    /// The circuit has a different number of regions.
+    Regions {
+        /// The number of regions under the first floor planner.
+        first: usize,
+        /// The number of regions under the second floor planner.
+        second: usize,
+    },
+    /// A fixed cell of a region has a different value, or is only assigned once.
+    FixedCell {
+        /// The index of the region.
+        region: usize,
+        /// The name of the region under the first floor planner.
+        name: String,
+        /// The column of the cell.
+        column: Column<Fixed>,
+        /// The offset of the cell in the region.
+        offset: usize,
+        /// The value of the cell under the first floor planner, if it is assigned.
+        first: Option<F>,
+        /// The value of the cell under the second floor planner, if it is assigned.
+        second: Option<F>,
+    },
+    /// A selector is enabled at different offsets of a region.
+    Selector {
+        /// The index of the region.
+        region: usize,
+        /// The name of the region under the first floor planner.
+        name: String,
+        /// The selector.
+        selector: Selector,
+        /// The offsets where the selector is enabled under the first floor planner.
+        first: Vec<usize>,
+        /// The offsets where the selector is enabled under the second floor planner.
+        second: Vec<usize>,
+    },
+    /// The fixed cells of a column assigned outside of the regions, such as constants,
+    /// have different values, given in order.
+    GlobalFixed {
+        /// The column.
+        column: Column<Fixed>,
+        /// The values under the first floor planner.
+        first: Vec<F>,
+        /// The values under the second floor planner.
+        second: Vec<F>,
+    },
+    /// A cycle of cells constrained to be equal only exists under one floor planner.
+    CopyCycle {
+        /// The cells of the cycle, in order.
+        cycle: Vec<PlacedCell<F>>,
+        /// Whether the cycle exists under the first floor planner rather than the second.
+        in_first: bool,
+    },
+}
+
+/// The differences between the syntheses of a circuit under two floor planners, as
+/// returned by [`shadow_planners`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShadowReport<F> {
+    /// The differences, empty if the syntheses are equivalent.
+    pub mismatches: Vec<PlannerMismatch<F>>,
+}
+
+impl<F: FromUniformBytes<64> + Ord> ShadowReport<F> {
+    /// Compares the syntheses of the same circuit recorded by `first` and `second`.
+    ///
+    /// The regions are matched by index, which is the order the circuit assigns them in
+    /// under every floor planner.
+    pub fn compare(first: &MockProver<F>, second: &MockProver<F>) -> Self {
+        let (first, second) = (Normalized::new(first), Normalized::new(second));
+        let mut mismatches = vec![];
+
+        if first.failures != second.failures {
+            mismatches.push(PlannerMismatch::Failures {
+                first: first.failures,
+                second: second.failures,
+            });
+        }
+        if first.regions.len() != second.regions.len() {
+            mismatches.push(PlannerMismatch::Regions {
+                first: first.regions.len(),
+                second: second.regions.len(),
+            });
+        }
+
+        for (region, (a, b)) in first.regions.iter().zip(second.regions.iter()).enumerate() {
+            let cells: BTreeSet<_> = a.fixed.keys().chain(b.fixed.keys()).collect();
+            for &(column, offset) in cells {
+                let (first, second) = (
+                    a.fixed.get(&(column, offset)).copied().flatten(),
+                    b.fixed.get(&(column, offset)).copied().flatten(),
+                );
+                if first != second {
+                    mismatches.push(PlannerMismatch::FixedCell {
+                        region,
+                        name: a.name.clone(),
+                        column: Column::new(column, Fixed),
+                        offset,
+                        first,
+                        second,
+                    });
+                }
+            }
+
+            let selectors: BTreeMap<_, _> = a.selectors.iter().chain(b.selectors.iter()).collect();
+            for (index, (selector, _)) in selectors {
+                let offsets = |region: &NormalizedRegion<F>| {
+                    region
+                        .selectors
+                        .get(index)
+                        .map(|(_, offsets)| offsets.clone())
+                        .unwrap_or_default()
+                };
+                let (first, second) = (offsets(a), offsets(b));
+                if first != second {
+                    mismatches.push(PlannerMismatch::Selector {
+                        region,
+                        name: a.name.clone(),
+                        selector: *selector,
+                        first,
+                        second,
+                    });
+                }
+            }
+        }
+
+        let columns: BTreeSet<_> = first
+            .global_fixed
+            .keys()
+            .chain(second.global_fixed.keys())
+            .collect();
+        for column in columns {
+            let values = |normalized: &Normalized<F>| {
+                normalized
+                    .global_fixed
+                    .get(column)
+                    .cloned()
+                    .unwrap_or_default()
+            };
+            let (a, b) = (values(&first), values(&second));
+            if a != b {
+                mismatches.push(PlannerMismatch::GlobalFixed {
+                    column: Column::new(*column, Fixed),
+                    first: a,
+                    second: b,
+                });
+            }
+        }
+
+        for (a, b, in_first) in [(&first, &second, true), (&second, &first, false)] {
+            for cycle in a.cycles.difference(&b.cycles) {
+                mismatches.push(PlannerMismatch::CopyCycle {
+                    cycle: cycle.clone(),
+                    in_first,
+                });
+            }
+        }
+
+        ShadowReport { mismatches }
+    }
+
+    /// Returns `true` if the syntheses are equivalent.
+    pub fn is_equivalent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    /// Panics if the syntheses are not equivalent, listing their differences.
+    pub fn assert_equivalent(&self) {
+        if !self.is_equivalent() {
+            let mismatches: Vec<_> = self
+                .mismatches
+                .iter()
+                .map(|mismatch| format!("{:?}", mismatch))
+                .collect();
+            panic!("the floor planners disagree:\n{}", mismatches.join("\n"));
+        }
+    }
+}
+
+/// Synthesizes `circuit` with `2^k` rows under the floor planners `P` and `Q`, and
+/// cross-checks that the fixed cells, the selectors, the cycles of cells constrained to be
+/// equal and the failures of [`MockProver::verify`] are equivalent.
+///
+/// ```ignore
+/// let report = shadow_planners::<_, _, SimpleFloorPlanner, V1>(k, &circuit, instances)?;
+/// report.assert_equivalent();
+/// ```
+pub fn shadow_planners<F, ConcreteCircuit, P, Q>(
+    k: u32,
+    circuit: &ConcreteCircuit,
+    instance: Vec<Vec<F>>,
+) -> Result<ShadowReport<F>, Error>
+where
+    F: FromUniformBytes<64> + Ord,
+    ConcreteCircuit: Circuit<F>,
+    P: FloorPlanner,
+    Q: FloorPlanner,
+{
+    let first = MockProver::run(k, &WithFloorPlanner::<_, P>::new(circuit), instance.clone())?;
+    let second = MockProver::run(k, &WithFloorPlanner::<_, Q>::new(circuit), instance)?;
+    Ok(ShadowReport::compare(&first, &second))
+}
+
+/// A synthesis recorded by a [`MockProver`], with the cells located independently of the
+/// floor planner.
+struct Normalized<F> {
+    failures: usize,
+    regions: Vec<NormalizedRegion<F>>,
+    /// The values of the fixed cells outside of the regions, by column index, in order.
+    global_fixed: BTreeMap<usize, Vec<F>>,
+    cycles: BTreeSet<Vec<PlacedCell<F>>>,
+}
+
+struct NormalizedRegion<F> {
+    name: String,
+    /// The fixed cells, by column index and offset.
+    fixed: BTreeMap<(usize, usize), Option<F>>,
+    /// The offsets where each selector is enabled, in order, by selector index.
+    selectors: BTreeMap<usize, (Selector, Vec<usize>)>,
+}
+
+impl<F: FromUniformBytes<64> + Ord> Normalized<F> {
+    fn new(prover: &MockProver<F>) -> Self {
+        let failures = prover.verify().err().map_or(0, |failures| failures.len());
+        let anchors: Vec<_> = prover.regions.iter().map(region_anchor).collect();
+        let cells: HashMap<_, _> = prover
+            .regions
+            .iter()
+            .enumerate()
+            .rev()
+            .flat_map(|(index, region)| region.cells.keys().map(move |cell| (*cell, index)))
+            .collect();
+
+        let regions = prover
+            .regions
+            .iter()
+            .zip(anchors.iter())
+            .map(|(region, anchor)| NormalizedRegion {
+                name: region.name.clone(),
+                fixed: region
+                    .cells
+                    .keys()
+                    .filter(|(column, _)| *column.column_type() == Any::Fixed)
+                    .map(|(column, row)| {
+                        let value = assigned(prover.fixed[column.index()][*row]);
+                        ((column.index(), row - anchor), value)
+                    })
+                    .collect(),
+                selectors: region
+                    .enabled_selectors
+                    .iter()
+                    .map(|(selector, rows)| {
+                        let mut offsets: Vec<_> = rows.iter().map(|row| row - anchor).collect();
+                        offsets.sort_unstable();
+                        (selector.0, (*selector, offsets))
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let mut global_fixed = BTreeMap::new();
+        for (index, values) in prover.fixed.iter().enumerate() {
+            let column = Column::new(index, Fixed).into();
+            let mut values: Vec<_> = values
+                .iter()
+                .enumerate()
+                .filter(|(row, _)| !cells.contains_key(&(column, *row)))
+                .filter_map(|(_, value)| assigned(*value))
+                .collect();
+            if !values.is_empty() {
+                values.sort();
+                global_fixed.insert(index, values);
+            }
+        }
+
+        let place = |column: Column<Any>, row: usize| match cells.get(&(column, row)) {
+            Some(&region) => PlacedCell::InRegion {
+                region,
+                column,
+                offset: row - anchors[region],
+            },
+            None if *column.column_type() == Any::Instance => PlacedCell::Instance { column, row },
+            None => PlacedCell::Global {
+                column,
+                value: prover.cell_value(column, row).and_then(assigned),
+            },
+        };
+
+        // The cycles are the connected components of the copy constraints.
+        let mut indices = HashMap::new();
+        let mut parents: Vec<usize> = vec![];
+        let mut placed = vec![];
+        for (left, right) in prover.permutation.copies.iter() {
+            let [left, right] = [left, right].map(|cell| {
+                let column: Column<Any> = cell.column.into();
+                *indices.entry((column, cell.row)).or_insert_with(|| {
+                    parents.push(parents.len());
+                    placed.push(place(column, cell.row));
+                    parents.len() - 1
+                })
+            });
+            let (left, right) = (find(&mut parents, left), find(&mut parents, right));
+            parents[left] = right;
+        }
+        let mut components: BTreeMap<usize, Vec<PlacedCell<F>>> = BTreeMap::new();
+        for (index, cell) in placed.into_iter().enumerate() {
+            let root = find(&mut parents, index);
+            components.entry(root).or_default().push(cell);
+        }
+        let cycles = components
+            .into_values()
+            .map(|mut cycle| {
+                cycle.sort();
+                cycle.dedup();
+                cycle
+            })
+            .collect();
+
+        Normalized {
+            failures,
+            regions,
+            global_fixed,
+            cycles,
+        }
+    }
+}
+
+/// Returns the first row used by `region`, from which the offsets of its cells are
+/// counted.
+fn region_anchor(region: &Region) -> usize {
+    region
+        .cells
+        .keys()
+        .map(|(_, row)| *row)
+        .chain(region.enabled_selectors.values().flatten().copied())
+        .min()
+        .unwrap_or_default()
+}
+
+fn assigned<F: Field>(value: CellValue<F>) -> Option<F> {
+    match value {
+        CellValue::Assigned(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Returns the root of the set of `index`, compressing the path to it.
+fn find(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_common::plonk::ConstraintSystem;
+    use halo2_middleware::circuit::{Advice, Instance};
+    use halo2_middleware::poly::Rotation;
+    use halo2curves::pasta::Fp;
+
+    use super::*;
+    use crate::circuit::floor_planner::{RotationAware, V1};
+    use crate::circuit::{Layouter, SimpleFloorPlanner, Value};
+
+    #[derive(Clone)]
+    struct AddConfig {
+        a: Column<Advice>,
+        b: Column<Advice>,
+        c: Column<Fixed>,
+        s: Selector,
+        instance: Column<Instance>,
+    }
+
+    /// Adds a constant to a fixed value, then copies the result into a second addition
+    /// whose result is public.
+    struct AddCircuit;
+
+    impl Circuit<Fp> for AddCircuit {
+        type Config = AddConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let b = meta.advice_column();
+            let c = meta.fixed_column();
+            let constants = meta.fixed_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(a);
+            meta.enable_equality(b);
+            meta.enable_equality(instance);
+            meta.enable_constant(constants);
+            let s = meta.selector();
+            meta.create_gate("add", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                let b = meta.query_advice(b, Rotation::cur());
+                let c = meta.query_fixed(c, Rotation::cur());
+                vec![s * (a + c - b)]
+            });
+            AddConfig {
+                a,
+                b,
+                c,
+                s,
+                instance,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let sum = layouter.assign_region(
+                || "first",
+                |mut region| {
+                    config.s.enable(&mut region, 0)?;
+                    region.assign_advice_from_constant(|| "a", config.a, 0, Fp::from(2))?;
+                    region.assign_fixed(|| "c", config.c, 0, || Value::known(Fp::from(3)))?;
+                    region.assign_advice(|| "b", config.b, 0, || Value::known(Fp::from(5)))
+                },
+            )?;
+            let sum = layouter.assign_region(
+                || "second",
+                |mut region| {
+                    config.s.enable(&mut region, 1)?;
+                    sum.copy_advice(|| "a", &mut region, config.a, 1)?;
+                    region.assign_fixed(|| "c", config.c, 1, || Value::known(Fp::from(1)))?;
+                    region.assign_advice(|| "b", config.b, 1, || Value::known(Fp::from(6)))
+                },
+            )?;
+            layouter.constrain_instance(sum.cell(), config.instance, 0)
+        }
+    }
+
+    #[test]
+    fn equivalent_planners() {
+        let report = shadow_planners::<_, _, SimpleFloorPlanner, V1>(
+            4,
+            &AddCircuit,
+            vec![vec![Fp::from(6)]],
+        )
+        .unwrap();
+        report.assert_equivalent();
+    }
+
+    /// Reads the cell after its region, which is only assigned if the next region is
+    /// placed right after it.
+    struct AbuttingCircuit;
+
+    impl Circuit<Fp> for AbuttingCircuit {
+        type Config = (Column<Advice>, Selector);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let s = meta.selector();
+            meta.create_gate("next equal", |meta| {
+                let s = meta.query_selector(s);
+                let cur = meta.query_advice(a, Rotation::cur());
+                let next = meta.query_advice(a, Rotation::next());
+                vec![s * (next - cur)]
+            });
+            (a, s)
+        }
+
+        fn synthesize(
+            &self,
+            (a, s): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            for (name, enable) in [("reader", true), ("neighbour", false)] {
+                layouter.assign_region(
+                    || name,
+                    |mut region| {
+                        if enable {
+                            s.enable(&mut region, 0)?;
+                        }
+                        region.assign_advice(|| "a", a, 0, || Value::known(Fp::from(7)))?;
+                        Ok(())
+                    },
+                )?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn planner_sensitive_circuit() {
+        let report =
+            shadow_planners::<_, _, SimpleFloorPlanner, SimpleFloorPlanner<RotationAware>>(
+                4,
+                &AbuttingCircuit,
+                vec![],
+            )
+            .unwrap();
+        // The gate only reads the neighbouring region when it is placed right after.
+        assert!(!report.is_equivalent());
+        assert!(matches!(
+            report.mismatches[..],
+            [PlannerMismatch::Failures { first: 0, second }] if second > 0
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fmt, marker::PhantomData};
+use std::{fmt, marker::PhantomData, ops::Range};
 
 use halo2_middleware::ff::Field;
 use tracing::{debug, debug_span, span::EnteredSpan};
@@ -9,7 +9,7 @@ use halo2_common::circuit::{
 };
 use halo2_common::plonk::{
     circuit::{Challenge, Column},
-    Assigned, Assignment, Circuit, ConstraintSystem, Error, FloorPlanner, Selector,
+    Assigned, Assignment, Circuit, ConstraintSystem, Error, FloorPlanner, NamePolicy, Selector,
 };
 use halo2_middleware::circuit::{Advice, Any, Fixed, Instance};
 
@@ -181,6 +181,26 @@ impl<F: Field, L: Layouter<F>> Layouter<F> for TracingLayouter<F, L> {
         })
     }
 
+    fn assign_uniform_regions<A, AR, N, NR>(
+        &mut self,
+        name: N,
+        steps: usize,
+        mut assignment: A,
+    ) -> Result<Vec<AR>, Error>
+    where
+        A: FnMut(Region<'_, F>, usize) -> Result<AR, Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        let _span = debug_span!("uniform_regions", name = name().into(), steps).entered();
+        self.layouter
+            .assign_uniform_regions(name, steps, |region, step| {
+                let mut region = TracingRegion(region);
+                let region: &mut dyn RegionLayouter<F> = &mut region;
+                assignment(region.into(), step)
+            })
+    }
+
     fn assign_table<A, N, NR>(&mut self, name: N, assignment: A) -> Result<(), Error>
     where
         A: FnMut(Table<'_, F>) -> Result<(), Error>,
@@ -252,6 +272,25 @@ impl<'r, F: Field> RegionLayouter<F> for TracingRegion<'r, F> {
         self.0.enable_selector(annotation, selector, offset)
     }
 
+    fn enable_selector_range<'v>(
+        &'v mut self,
+        annotation: &'v (dyn Fn() -> String + 'v),
+        selector: &Selector,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), Error> {
+        let _guard = debug_span!(
+            "enable_selector_range",
+            name = annotation(),
+            offset = offset,
+            len = len
+        )
+        .entered();
+        debug!(target: "layouter", "Entered");
+        self.0
+            .enable_selector_range(annotation, selector, offset, len)
+    }
+
     fn name_column<'v>(
         &'v mut self,
         _: &'v (dyn std::ops::Fn() -> std::string::String + 'v),
@@ -275,6 +314,26 @@ impl<'r, F: Field> RegionLayouter<F> for TracingRegion<'r, F> {
             .map(debug_value_and_return_cell)
     }
 
+    fn assign_advice_column_slice<'v>(
+        &'v mut self,
+        annotation: &'v (dyn Fn() -> String + 'v),
+        column: Column<Advice>,
+        offset: usize,
+        len: usize,
+        values: Value<&[Assigned<F>]>,
+    ) -> Result<(), Error> {
+        let _guard = debug_span!("assign_advice_column_slice",
+            name = annotation(),
+            column = ?column,
+            offset = offset,
+            len = len,
+        )
+        .entered();
+        debug!(target: "layouter", "Entered");
+        self.0
+            .assign_advice_column_slice(annotation, column, offset, len, values)
+    }
+
     fn assign_advice_from_constant<'v>(
         &'v mut self,
         annotation: &'v (dyn Fn() -> String + 'v),
@@ -328,6 +387,10 @@ impl<'r, F: Field> RegionLayouter<F> for TracingRegion<'r, F> {
         self.0.instance_value(instance, row)
     }
 
+    fn advice_value(&mut self, column: Column<Advice>, offset: usize) -> Result<Value<F>, Error> {
+        self.0.advice_value(column, offset)
+    }
+
     fn assign_fixed<'v>(
         &'v mut self,
         annotation: &'v (dyn Fn() -> String + 'v),
@@ -353,6 +416,16 @@ impl<'r, F: Field> RegionLayouter<F> for TracingRegion<'r, F> {
         debug!(target: "constrain_equal", left = ?left, right = ?right);
         self.0.constrain_equal(left, right)
     }
+
+    fn constrain_instance(
+        &mut self,
+        cell: Cell,
+        instance: Column<Instance>,
+        row: usize,
+    ) -> Result<(), Error> {
+        debug!(target: "constrain_instance", cell = ?cell, instance = ?instance, row = row);
+        self.0.constrain_instance(cell, instance, row)
+    }
 }
 
 /// A helper type that augments an [`Assignment`] with [`tracing`] spans and events.
@@ -373,6 +446,10 @@ impl<'cs, F: Field, CS: Assignment<F>> TracingAssignment<'cs, F, CS> {
 }
 
 impl<'cs, F: Field, CS: Assignment<F>> Assignment<F> for TracingAssignment<'cs, F, CS> {
+    fn name_policy(&self) -> NamePolicy {
+        self.cs.name_policy()
+    }
+
     fn enter_region<NR, N>(&mut self, name_fn: N)
     where
         NR: Into<String>,
@@ -413,12 +490,37 @@ impl<'cs, F: Field, CS: Assignment<F>> Assignment<F> for TracingAssignment<'cs,
         self.cs.enable_selector(|| annotation, selector, row)
     }
 
+    fn enable_selector_range<A, AR>(
+        &mut self,
+        annotation: A,
+        selector: &Selector,
+        rows: Range<usize>,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let annotation = annotation().into();
+        if self.in_region {
+            debug!(target: "position", rows = ?rows);
+        } else {
+            debug!(target: "enable_selector_range", name = annotation, rows = ?rows);
+        }
+        self.cs.enable_selector_range(|| annotation, selector, rows)
+    }
+
     fn query_instance(&self, column: Column<Instance>, row: usize) -> Result<Value<F>, Error> {
         let _guard = debug_span!("positioned").entered();
         debug!(target: "query_instance", column = ?column, row = row);
         self.cs.query_instance(column, row)
     }
 
+    fn query_advice(&self, column: Column<Advice>, row: usize) -> Result<Value<F>, Error> {
+        let _guard = debug_span!("positioned").entered();
+        debug!(target: "query_advice", column = ?column, row = row);
+        self.cs.query_advice(column, row)
+    }
+
     fn assign_advice<V, VR, A, AR>(
         &mut self,
         annotation: A,
@@ -441,6 +543,27 @@ impl<'cs, F: Field, CS: Assignment<F>> Assignment<F> for TracingAssignment<'cs,
         self.cs.assign_advice(|| annotation, column, row, to)
     }
 
+    fn assign_advice_column_slice<A, AR>(
+        &mut self,
+        annotation: A,
+        column: Column<Advice>,
+        rows: Range<usize>,
+        values: Value<&[Assigned<F>]>,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let annotation = annotation().into();
+        if self.in_region {
+            debug!(target: "position", rows = ?rows);
+        } else {
+            debug!(target: "assign_advice_column_slice", name = annotation, column = ?column, rows = ?rows);
+        }
+        self.cs
+            .assign_advice_column_slice(|| annotation, column, rows, values)
+    }
+
     fn assign_fixed<V, VR, A, AR>(
         &mut self,
         annotation: A,
@@ -0,0 +1,619 @@
+//! Timing of the witness generation of a circuit, split between its regions.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    marker::PhantomData,
+    ops::Range,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use halo2_middleware::ff::Field;
+
+use halo2_common::circuit::{Cell, Layouter, Region, Table, Value};
+use halo2_common::plonk::{
+    circuit::{Challenge, Column},
+    sealed::SealedPhase,
+    Assigned, Assignment, Circuit, ConstraintSystem, Error, FirstPhase, FloorPlanner, NamePolicy,
+    Selector,
+};
+use halo2_middleware::circuit::{Advice, Any, Fixed, Instance};
+
+use crate::circuit::{CompiledConstraintSystem, WitnessCollection};
+
+/// The time spent synthesizing one region or table of a circuit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegionTiming {
+    /// The name of the region.
+    pub name: String,
+    /// The time spent in the region closure while the floor planner measures the shape of
+    /// the region.
+    pub shape: Duration,
+    /// The time spent in the region closure while the region is assigned, excluding
+    /// `backend`.
+    pub assignment: Duration,
+    /// The time spent by the witness backend to write the cells assigned in the region,
+    /// excluding the closures computing their values.
+    pub backend: Duration,
+}
+
+/// The time spent synthesizing a circuit, as returned by [`time_synthesis`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SynthesisTimings {
+    /// The regions and tables of the circuit, in the order they are assigned.
+    pub regions: Vec<RegionTiming>,
+    /// The time spent in the floor planner, including the regions.
+    pub total: Duration,
+}
+
+/// Synthesizes `circuit` with `2^k` rows into the witness backend used by the prover, and
+/// returns the time spent in each of its regions.
+///
+/// This runs the first phase of witness generation: the values of advice cells in later
+/// phases depend on challenges, and are not computed. Timings are wall-clock times of a
+/// single run, so slow regions are best compared over a few runs.
+///
+/// ```ignore
+/// let timings = time_synthesis(k, &circuit, instance)?;
+/// println!("{}", timings);
+/// ```
+pub fn time_synthesis<F: Field, ConcreteCircuit: Circuit<F>>(
+    k: u32,
+    circuit: &ConcreteCircuit,
+    instance: Vec<Vec<F>>,
+) -> Result<SynthesisTimings, Error> {
+    let (cs, config) = CompiledConstraintSystem::new(circuit).to_parts();
+    let n = 1usize << k;
+    let instances: Vec<&[F]> = instance.iter().map(|values| values.as_slice()).collect();
+    let challenges = HashMap::new();
+    let mut witness = WitnessCollection {
+        k,
+        current_phase: FirstPhase.to_sealed(),
+        advice: vec![vec![Assigned::Zero; n]; cs.num_advice_columns],
+        challenges: &challenges,
+        instances: &instances,
+        usable_rows: ..cs.usable_rows(k).end,
+        earlier_phases: &[],
+        name_policy: NamePolicy::KeepAll,
+        assignments: None,
+        unknown_challenge: Default::default(),
+        _marker: PhantomData,
+    };
+
+    let state = Arc::new(Mutex::new(TimingState::default()));
+    let start = Instant::now();
+    ConcreteCircuit::FloorPlanner::synthesize(
+        &mut TimingAssignment {
+            cs: &mut witness,
+            state: state.clone(),
+            _marker: PhantomData,
+        },
+        &TimingCircuit::borrowed(circuit, state.clone()),
+        config,
+        cs.constants.clone(),
+    )?;
+    let total = start.elapsed();
+
+    let regions = state.lock().unwrap().regions.clone();
+    Ok(SynthesisTimings { regions, total })
+}
+
+impl SynthesisTimings {
+    /// Returns the time spent in the region closures while the floor planner measures
+    /// their shapes.
+    pub fn shape(&self) -> Duration {
+        self.regions.iter().map(|region| region.shape).sum()
+    }
+
+    /// Returns the time spent in the region closures while the regions are assigned,
+    /// excluding the backend.
+    pub fn assignment(&self) -> Duration {
+        self.regions.iter().map(|region| region.assignment).sum()
+    }
+
+    /// Returns the time spent by the witness backend in the regions.
+    pub fn backend(&self) -> Duration {
+        self.regions.iter().map(|region| region.backend).sum()
+    }
+
+    /// Returns the time spent in the floor planner outside of the region closures.
+    pub fn layouter(&self) -> Duration {
+        self.total
+            .saturating_sub(self.shape() + self.assignment() + self.backend())
+    }
+}
+
+impl fmt::Display for SynthesisTimings {
+    /// Writes the timings of each region on a line, followed by the totals.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for region in &self.regions {
+            writeln!(
+                f,
+                "{}: shape {:?}, assignment {:?}, backend {:?}",
+                region.name, region.shape, region.assignment, region.backend
+            )?;
+        }
+        writeln!(
+            f,
+            "total {:?}: shape {:?}, assignment {:?}, backend {:?}, layouter {:?}",
+            self.total,
+            self.shape(),
+            self.assignment(),
+            self.backend(),
+            self.layouter()
+        )
+    }
+}
+
+/// The timings collected during the synthesis, shared between the layouter and the
+/// backend.
+#[derive(Debug, Default)]
+struct TimingState {
+    /// Whether the floor planner is assigning a region, rather than measuring its shape.
+    in_region: bool,
+    /// The time spent by the backend since the synthesis started.
+    backend: Duration,
+    regions: Vec<RegionTiming>,
+    /// The index of the next region assigned in the current pass of the floor planner.
+    next_region: usize,
+}
+
+impl TimingState {
+    /// Returns the index of the next `count` regions named `name`. The floor planner may
+    /// synthesize the circuit several times, so the regions are matched by their order.
+    fn next_regions(&mut self, name: String, count: usize) -> usize {
+        let index = self.next_region;
+        self.next_region += count;
+        while self.regions.len() < self.next_region {
+            self.regions.push(RegionTiming {
+                name: name.clone(),
+                ..Default::default()
+            });
+        }
+        index
+    }
+
+    /// Records `elapsed` in a closure of the region at `index`, during which the backend
+    /// time went from `backend`.
+    fn record(&mut self, index: usize, backend: Duration, elapsed: Duration) {
+        let backend = self.backend.saturating_sub(backend);
+        let region = &mut self.regions[index];
+        if self.in_region {
+            region.assignment += elapsed.saturating_sub(backend);
+            region.backend += backend;
+        } else {
+            region.shape += elapsed;
+        }
+    }
+}
+
+/// Runs `closure` for the region at `index`, and records its time.
+fn timed<R>(state: &Mutex<TimingState>, index: usize, closure: impl FnOnce() -> R) -> R {
+    let backend = state.lock().unwrap().backend;
+    let start = Instant::now();
+    let result = closure();
+    let elapsed = start.elapsed();
+    state.lock().unwrap().record(index, backend, elapsed);
+    result
+}
+
+enum CircuitRef<'c, C> {
+    Borrowed(&'c C),
+    Owned(C),
+}
+
+/// A [`Circuit`] that times the closures of the regions of another one.
+struct TimingCircuit<'c, F: Field, C: Circuit<F>> {
+    circuit: CircuitRef<'c, C>,
+    state: Arc<Mutex<TimingState>>,
+    _marker: PhantomData<F>,
+}
+
+impl<'c, F: Field, C: Circuit<F>> TimingCircuit<'c, F, C> {
+    fn borrowed(circuit: &'c C, state: Arc<Mutex<TimingState>>) -> Self {
+        Self {
+            circuit: CircuitRef::Borrowed(circuit),
+            state,
+            _marker: PhantomData,
+        }
+    }
+
+    fn inner_ref(&self) -> &C {
+        match &self.circuit {
+            CircuitRef::Borrowed(circuit) => circuit,
+            CircuitRef::Owned(circuit) => circuit,
+        }
+    }
+}
+
+impl<'c, F: Field, C: Circuit<F>> Circuit<F> for TimingCircuit<'c, F, C> {
+    type Config = C::Config;
+    type FloorPlanner = C::FloorPlanner;
+    #[cfg(feature = "circuit-params")]
+    type Params = C::Params;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            circuit: CircuitRef::Owned(self.inner_ref().without_witnesses()),
+            state: self.state.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    #[cfg(feature = "circuit-params")]
+    fn params(&self) -> Self::Params {
+        self.inner_ref().params()
+    }
+
+    #[cfg(feature = "circuit-params")]
+    fn configure_with_params(meta: &mut ConstraintSystem<F>, params: Self::Params) -> Self::Config {
+        C::configure_with_params(meta, params)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        C::configure(meta)
+    }
+
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<F>) -> Result<(), Error> {
+        // Each pass of the floor planner assigns the regions in the same order.
+        self.state.lock().unwrap().next_region = 0;
+        self.inner_ref().synthesize(
+            config,
+            TimingLayouter {
+                layouter,
+                state: self.state.clone(),
+                _marker: PhantomData,
+            },
+        )
+    }
+}
+
+/// A [`Layouter`] that times the closures of the regions it assigns.
+struct TimingLayouter<F: Field, L: Layouter<F>> {
+    layouter: L,
+    state: Arc<Mutex<TimingState>>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: Field, L: Layouter<F>> Layouter<F> for TimingLayouter<F, L> {
+    type Root = Self;
+
+    fn assign_region<A, AR, N, NR>(&mut self, name: N, mut assignment: A) -> Result<AR, Error>
+    where
+        A: FnMut(Region<'_, F>) -> Result<AR, Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        let index = self.state.lock().unwrap().next_regions(name().into(), 1);
+        let state = &self.state;
+        self.layouter
+            .assign_region(name, |region| timed(state, index, || assignment(region)))
+    }
+
+    fn assign_uniform_regions<A, AR, N, NR>(
+        &mut self,
+        name: N,
+        steps: usize,
+        mut assignment: A,
+    ) -> Result<Vec<AR>, Error>
+    where
+        A: FnMut(Region<'_, F>, usize) -> Result<AR, Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        let first = self
+            .state
+            .lock()
+            .unwrap()
+            .next_regions(name().into(), steps);
+        let state = &self.state;
+        self.layouter
+            .assign_uniform_regions(name, steps, |region, step| {
+                timed(state, first + step, || assignment(region, step))
+            })
+    }
+
+    fn assign_table<A, N, NR>(&mut self, name: N, mut assignment: A) -> Result<(), Error>
+    where
+        A: FnMut(Table<'_, F>) -> Result<(), Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        let index = self.state.lock().unwrap().next_regions(name().into(), 1);
+        let state = &self.state;
+        self.layouter
+            .assign_table(name, |table| timed(state, index, || assignment(table)))
+    }
+
+    fn constrain_instance(
+        &mut self,
+        cell: Cell,
+        column: Column<Instance>,
+        row: usize,
+    ) -> Result<(), Error> {
+        self.layouter.constrain_instance(cell, column, row)
+    }
+
+    fn get_challenge(&self, challenge: Challenge) -> Value<F> {
+        self.layouter.get_challenge(challenge)
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.layouter.push_namespace(name_fn);
+    }
+
+    fn pop_namespace(&mut self, gadget_name: Option<String>) {
+        self.layouter.pop_namespace(gadget_name);
+    }
+}
+
+/// An [`Assignment`] that times the calls to another one.
+struct TimingAssignment<'cs, F: Field, CS: Assignment<F>> {
+    cs: &'cs mut CS,
+    state: Arc<Mutex<TimingState>>,
+    _marker: PhantomData<F>,
+}
+
+impl<'cs, F: Field, CS: Assignment<F>> TimingAssignment<'cs, F, CS> {
+    /// Runs `call` on the backend, and records its time.
+    fn timed<R>(&mut self, call: impl FnOnce(&mut CS) -> R) -> R {
+        let start = Instant::now();
+        let result = call(&mut *self.cs);
+        let elapsed = start.elapsed();
+        self.state.lock().unwrap().backend += elapsed;
+        result
+    }
+
+    /// Removes `elapsed`, spent by the backend in the closures of the circuit, from the
+    /// backend time.
+    fn untimed(&self, elapsed: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.backend = state.backend.saturating_sub(elapsed);
+    }
+}
+
+/// Wraps `to` to add its time to `elapsed`.
+fn value_timed<'a, T, V: FnOnce() -> T + 'a>(
+    to: V,
+    elapsed: &'a mut Duration,
+) -> impl FnOnce() -> T + 'a {
+    move || {
+        let start = Instant::now();
+        let value = to();
+        *elapsed += start.elapsed();
+        value
+    }
+}
+
+impl<'cs, F: Field, CS: Assignment<F>> Assignment<F> for TimingAssignment<'cs, F, CS> {
+    fn name_policy(&self) -> NamePolicy {
+        self.cs.name_policy()
+    }
+
+    fn enter_region<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.state.lock().unwrap().in_region = true;
+        self.cs.enter_region(name_fn);
+    }
+
+    fn annotate_column<A, AR>(&mut self, annotation: A, column: Column<Any>)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.cs.annotate_column(annotation, column);
+    }
+
+    fn exit_region(&mut self) {
+        self.cs.exit_region();
+        self.state.lock().unwrap().in_region = false;
+    }
+
+    fn enable_selector<A, AR>(
+        &mut self,
+        annotation: A,
+        selector: &Selector,
+        row: usize,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.timed(|cs| cs.enable_selector(annotation, selector, row))
+    }
+
+    fn enable_selector_range<A, AR>(
+        &mut self,
+        annotation: A,
+        selector: &Selector,
+        rows: Range<usize>,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.timed(|cs| cs.enable_selector_range(annotation, selector, rows))
+    }
+
+    fn query_instance(&self, column: Column<Instance>, row: usize) -> Result<Value<F>, Error> {
+        self.cs.query_instance(column, row)
+    }
+
+    fn query_advice(&self, column: Column<Advice>, row: usize) -> Result<Value<F>, Error> {
+        self.cs.query_advice(column, row)
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        annotation: A,
+        column: Column<Advice>,
+        row: usize,
+        to: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let mut value = Duration::ZERO;
+        let result =
+            self.timed(|cs| cs.assign_advice(annotation, column, row, value_timed(to, &mut value)));
+        self.untimed(value);
+        result
+    }
+
+    fn assign_advice_column_slice<A, AR>(
+        &mut self,
+        annotation: A,
+        column: Column<Advice>,
+        rows: Range<usize>,
+        values: Value<&[Assigned<F>]>,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.timed(|cs| cs.assign_advice_column_slice(annotation, column, rows, values))
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        annotation: A,
+        column: Column<Fixed>,
+        row: usize,
+        to: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let mut value = Duration::ZERO;
+        let result =
+            self.timed(|cs| cs.assign_fixed(annotation, column, row, value_timed(to, &mut value)));
+        self.untimed(value);
+        result
+    }
+
+    fn copy(
+        &mut self,
+        left_column: Column<Any>,
+        left_row: usize,
+        right_column: Column<Any>,
+        right_row: usize,
+    ) -> Result<(), Error> {
+        self.timed(|cs| cs.copy(left_column, left_row, right_column, right_row))
+    }
+
+    fn fill_from_row(
+        &mut self,
+        column: Column<Fixed>,
+        row: usize,
+        to: Value<Assigned<F>>,
+    ) -> Result<(), Error> {
+        self.timed(|cs| cs.fill_from_row(column, row, to))
+    }
+
+    fn get_challenge(&self, challenge: Challenge) -> Value<F> {
+        self.cs.get_challenge(challenge)
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.cs.push_namespace(name_fn)
+    }
+
+    fn pop_namespace(&mut self, gadget_name: Option<String>) {
+        self.cs.pop_namespace(gadget_name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::time_synthesis;
+    use crate::circuit::{floor_planner::V1, Layouter, SimpleFloorPlanner, Value};
+    use halo2_common::plonk::{circuit::Column, Circuit, ConstraintSystem, Error, FloorPlanner};
+    use halo2_middleware::circuit::Advice;
+    use halo2curves::pasta::Fp;
+    use std::marker::PhantomData;
+
+    struct MyCircuit<P>(PhantomData<P>);
+
+    impl<P: FloorPlanner> Circuit<Fp> for MyCircuit<P> {
+        type Config = Column<Advice>;
+        type FloorPlanner = P;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self(PhantomData)
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            meta.advice_column()
+        }
+
+        fn synthesize(
+            &self,
+            a: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "first",
+                |mut region| {
+                    region.assign_advice(|| "a", a, 0, || Value::known(Fp::one()))?;
+                    Ok(())
+                },
+            )?;
+            layouter.namespace(|| "gadget").assign_uniform_regions(
+                || "steps",
+                3,
+                |mut region, step| {
+                    region.assign_advice(|| "a", a, 0, || Value::known(Fp::from(step as u64)))?;
+                    Ok(())
+                },
+            )?;
+            Ok(())
+        }
+    }
+
+    fn region_names<P: FloorPlanner>() -> Vec<String> {
+        let timings = time_synthesis(4, &MyCircuit::<P>(PhantomData), vec![]).unwrap();
+        assert!(timings.total >= timings.shape() + timings.assignment() + timings.backend());
+        assert_eq!(
+            timings.to_string().lines().count(),
+            timings.regions.len() + 1
+        );
+        timings
+            .regions
+            .into_iter()
+            .map(|region| region.name)
+            .collect()
+    }
+
+    #[test]
+    fn regions() {
+        // Both floor planners time the same regions, although `V1` synthesizes the circuit
+        // twice.
+        let names = ["first", "steps", "steps", "steps"];
+        assert_eq!(region_names::<SimpleFloorPlanner>(), names);
+        assert_eq!(region_names::<V1>(), names);
+    }
+}
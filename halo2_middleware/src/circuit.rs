@@ -93,11 +93,60 @@ impl<F: Field> ExpressionMid<F> {
     }
 }
 
+/// The rows `offset`, `offset + period`, `offset + 2 * period`, ... of a circuit, on which a
+/// gate can be enforced instead of on every row.
+///
+/// As the period is a power of two, the rows are a coset of a subgroup of the evaluation
+/// domain, whose vanishing polynomial divides that of the domain. The constraints of a gate
+/// on a subdomain are multiplied by the quotient of the two polynomials, which is zero on
+/// the other rows, rather than by a selector column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Subdomain {
+    period: usize,
+    offset: usize,
+}
+
+impl Subdomain {
+    /// Returns the rows `offset + i * period`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` isn't a power of two, or if `offset` isn't smaller than `period`.
+    pub fn new(period: usize, offset: usize) -> Self {
+        assert!(
+            period.is_power_of_two(),
+            "the period must be a power of two"
+        );
+        assert!(
+            offset < period,
+            "the offset must be smaller than the period"
+        );
+        Subdomain { period, offset }
+    }
+
+    /// Returns the distance between two rows of the subdomain.
+    pub fn period(&self) -> usize {
+        self.period
+    }
+
+    /// Returns the first row of the subdomain.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns whether `row` belongs to the subdomain.
+    pub fn contains(&self, row: usize) -> bool {
+        row % self.period == self.offset
+    }
+}
+
 /// A Gate contains a single polynomial identity with a name as metadata.
 #[derive(Clone, Debug)]
 pub struct GateV2Backend<F: Field> {
     pub name: String,
     pub poly: ExpressionMid<F>,
+    /// The rows the identity is enforced on, or every row if `None`.
+    pub subdomain: Option<Subdomain>,
 }
 
 impl<F: Field> GateV2Backend<F> {
@@ -110,6 +159,11 @@ impl<F: Field> GateV2Backend<F> {
     pub fn polynomial(&self) -> &ExpressionMid<F> {
         &self.poly
     }
+
+    /// Returns the rows the identity is enforced on, or `None` if it's enforced on every row.
+    pub fn subdomain(&self) -> Option<Subdomain> {
+        self.subdomain
+    }
 }
 
 /// This is a description of the circuit environment, such as the gate, column and
@@ -124,6 +178,14 @@ pub struct ConstraintSystemV2Backend<F: Field> {
     /// Contains the index of each advice column that is left unblinded.
     pub unblinded_advice_columns: Vec<usize>,
 
+    /// Contains the index of each instance column that is committed with a blinding factor
+    /// by commitment schemes that commit to instance columns.
+    pub blinded_instance_columns: Vec<usize>,
+
+    /// The number of unusable rows added to those of the blinding factors, for example to
+    /// pad the circuit for a protocol built on top of the proofs.
+    pub extra_unusable_rows: usize,
+
     /// Contains the phase for each advice column. Should have same length as num_advice_columns.
     pub advice_column_phase: Vec<u8>,
     /// Contains the phase for each challenge. Should have same length as num_challenges.
@@ -144,6 +206,9 @@ pub struct ConstraintSystemV2Backend<F: Field> {
 
     // List of indexes of Fixed columns which are associated to a circuit-general Column tied to their annotation.
     pub general_column_annotations: HashMap<metadata::Column, String>,
+
+    /// The stable identifier of each column that was given one.
+    pub column_ids: HashMap<metadata::Column, metadata::ColumnId>,
 }
 
 /// Data that needs to be preprocessed from a circuit
@@ -1,8 +1,10 @@
 pub mod circuit;
 pub mod lookup;
 pub mod metadata;
+pub mod passes;
 pub mod permutation;
 pub mod poly;
+pub mod serialization;
 pub mod shuffle;
 
 pub use ff;
@@ -42,3 +42,82 @@ impl From<circuit::ColumnMid> for Column {
         }
     }
 }
+
+/// A stable identifier of a column, made of a name and a UUID.
+///
+/// The index of a column depends on the order in which the columns are allocated, which
+/// shifts whenever the configuration of the circuit changes, while the identifier of a
+/// column stays the same across compilations. External tooling should reference columns
+/// by their UUID, which is unique in a constraint system; the name is for display.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ColumnId {
+    /// The name of the column.
+    pub name: String,
+    /// The UUID of the column.
+    pub uuid: u128,
+}
+
+impl ColumnId {
+    /// Creates an identifier whose UUID is derived from `name`, so that it is the same
+    /// wherever the name is. The UUID is a version 8 UUID holding the 128-bit FNV-1a hash
+    /// of the name.
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        let hash = name.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u128).wrapping_mul(FNV_PRIME)
+        });
+        // Sets the version (8) and the variant (0b10) of the UUID.
+        let uuid = (hash & !(0xf << 76) & !(0b11 << 62)) | (0x8 << 76) | (0b10 << 62);
+        Self { name, uuid }
+    }
+
+    /// Creates an identifier with the given UUID.
+    pub fn with_uuid(name: impl Into<String>, uuid: u128) -> Self {
+        Self {
+            name: name.into(),
+            uuid,
+        }
+    }
+}
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// Formats a UUID in its hyphenated form, such as `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+pub fn format_uuid(uuid: u128) -> String {
+    let hex = format!("{:032x}", uuid);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+impl fmt::Display for ColumnId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, format_uuid(self.uuid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_uuid, ColumnId};
+
+    #[test]
+    fn column_id() {
+        let id = ColumnId::new("a");
+        assert_eq!(id, ColumnId::new("a"));
+        assert_ne!(id.uuid, ColumnId::new("b").uuid);
+        let uuid = format_uuid(id.uuid);
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "8");
+        assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"));
+        assert_eq!(id.to_string(), format!("a ({})", uuid));
+
+        let id = ColumnId::with_uuid("b", 0x67e5504410b1426f9247bb680e5fe0c8);
+        assert_eq!(format_uuid(id.uuid), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+    }
+}
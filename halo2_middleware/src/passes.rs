@@ -0,0 +1,554 @@
+//! Optimization passes over compiled circuits.
+//!
+//! Circuits generated by other tools often contain fixed columns that are never queried,
+//! selectors that are never enabled, or the same constraint several times. The passes of
+//! this module clean a [`CompiledCircuitV2`] up before keygen, and a [`PassManager`] runs
+//! them in order and reports what each one changed.
+//!
+//! The passes only change the fixed columns and the constraints of a circuit: its advice
+//! and instance columns are kept, so the witness of the original circuit can still be used
+//! to prove the optimized one. The optimized circuit has different keys, so it must be
+//! used with the keygen and proving functions that take a compiled circuit and a witness.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use ff::Field;
+
+use crate::circuit::{
+    Any, ColumnMid, CompiledCircuitV2, ConstraintSystemV2Backend, ExpressionMid, GateV2Backend,
+};
+use crate::metadata;
+
+/// An optimization pass over a compiled circuit.
+pub trait Pass<F: Field> {
+    /// The name of the pass, used in reports.
+    fn name(&self) -> &'static str;
+
+    /// Optimizes `circuit` in place, and returns a description of each change.
+    fn run(&self, circuit: &mut CompiledCircuitV2<F>) -> Vec<String>;
+}
+
+/// Runs a sequence of passes over compiled circuits.
+pub struct PassManager<F: Field> {
+    passes: Vec<Box<dyn Pass<F>>>,
+}
+
+impl<F: Field> fmt::Debug for PassManager<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.passes.iter().map(|pass| pass.name()))
+            .finish()
+    }
+}
+
+impl<F: Field> Default for PassManager<F> {
+    /// Returns a manager running [`ConstantPropagation`], [`GateMerging`] and
+    /// [`DeadColumnElimination`], in that order.
+    fn default() -> Self {
+        PassManager::empty()
+            .with(ConstantPropagation)
+            .with(GateMerging)
+            .with(DeadColumnElimination)
+    }
+}
+
+impl<F: Field> PassManager<F> {
+    /// Returns a manager that doesn't run any pass.
+    pub fn empty() -> Self {
+        PassManager { passes: vec![] }
+    }
+
+    /// Adds `pass` after the passes of the manager.
+    pub fn with(mut self, pass: impl Pass<F> + 'static) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Runs the passes over `circuit`, in order.
+    pub fn run(&self, circuit: &mut CompiledCircuitV2<F>) -> PassReport {
+        PassReport {
+            passes: self
+                .passes
+                .iter()
+                .map(|pass| (pass.name(), pass.run(circuit)))
+                .collect(),
+        }
+    }
+}
+
+/// The changes made by the passes of a [`PassManager`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PassReport {
+    /// The name of each pass, with the description of each of its changes.
+    pub passes: Vec<(&'static str, Vec<String>)>,
+}
+
+impl PassReport {
+    /// Returns whether the circuit was left unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.passes.iter().all(|(_, changes)| changes.is_empty())
+    }
+}
+
+impl fmt::Display for PassReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, changes) in &self.passes {
+            writeln!(f, "{}: {} changes", name, changes.len())?;
+            for change in changes {
+                writeln!(f, "  {}", change)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns every expression of the constraints of `cs`.
+fn expressions_mut<F: Field>(
+    cs: &mut ConstraintSystemV2Backend<F>,
+) -> impl Iterator<Item = &mut ExpressionMid<F>> {
+    cs.gates
+        .iter_mut()
+        .map(|gate| &mut gate.poly)
+        .chain(cs.lookups.iter_mut().flat_map(|lookup| {
+            lookup
+                .input_expressions
+                .iter_mut()
+                .chain(lookup.table_expressions.iter_mut())
+        }))
+        .chain(cs.shuffles.iter_mut().flat_map(|shuffle| {
+            shuffle
+                .input_expressions
+                .iter_mut()
+                .chain(shuffle.shuffle_expressions.iter_mut())
+        }))
+}
+
+/// Replaces each leaf of `expression` by the result of `f`, if any.
+fn rewrite_leaves<F: Field>(
+    expression: &mut ExpressionMid<F>,
+    f: &mut impl FnMut(&ExpressionMid<F>) -> Option<ExpressionMid<F>>,
+) {
+    match expression {
+        ExpressionMid::Negated(a) | ExpressionMid::Scaled(a, _) => rewrite_leaves(a, f),
+        ExpressionMid::Sum(a, b) | ExpressionMid::Product(a, b) => {
+            rewrite_leaves(a, f);
+            rewrite_leaves(b, f);
+        }
+        leaf => {
+            if let Some(rewritten) = f(leaf) {
+                *leaf = rewritten;
+            }
+        }
+    }
+}
+
+/// Folds the constant subexpressions of `expression`, and removes the terms that are
+/// multiplied by zero.
+fn simplify<F: Field>(expression: ExpressionMid<F>) -> ExpressionMid<F> {
+    use ExpressionMid::*;
+    match expression {
+        Negated(a) => match simplify(*a) {
+            Constant(a) => Constant(-a),
+            a => Negated(Box::new(a)),
+        },
+        Sum(a, b) => match (simplify(*a), simplify(*b)) {
+            (Constant(a), Constant(b)) => Constant(a + b),
+            (Constant(zero), b) if zero == F::ZERO => b,
+            (a, Constant(zero)) if zero == F::ZERO => a,
+            (a, b) => Sum(Box::new(a), Box::new(b)),
+        },
+        Product(a, b) => match (simplify(*a), simplify(*b)) {
+            (Constant(a), Constant(b)) => Constant(a * b),
+            (Constant(zero), _) | (_, Constant(zero)) if zero == F::ZERO => Constant(F::ZERO),
+            (Constant(one), b) if one == F::ONE => b,
+            (a, Constant(one)) if one == F::ONE => a,
+            (a, b) => Product(Box::new(a), Box::new(b)),
+        },
+        Scaled(a, scalar) => match simplify(*a) {
+            _ if scalar == F::ZERO => Constant(F::ZERO),
+            Constant(a) => Constant(a * scalar),
+            a if scalar == F::ONE => a,
+            a => Scaled(Box::new(a), scalar),
+        },
+        leaf => leaf,
+    }
+}
+
+/// Replaces the queries of fixed columns that have the same value in every row by this
+/// value, and folds the constant subexpressions of the constraints.
+///
+/// Gates that become identically zero, for example because their selector is never
+/// enabled, are removed.
+#[derive(Clone, Copy, Debug)]
+pub struct ConstantPropagation;
+
+impl<F: Field> Pass<F> for ConstantPropagation {
+    fn name(&self) -> &'static str {
+        "constant propagation"
+    }
+
+    fn run(&self, circuit: &mut CompiledCircuitV2<F>) -> Vec<String> {
+        let mut changes = vec![];
+        let constants: HashMap<usize, F> = circuit
+            .preprocessing
+            .fixed
+            .iter()
+            .enumerate()
+            .filter_map(|(index, values)| {
+                let first = *values.first()?;
+                values
+                    .iter()
+                    .all(|value| *value == first)
+                    .then_some((index, first))
+            })
+            .collect();
+
+        let mut propagated = BTreeSet::new();
+        for expression in expressions_mut(&mut circuit.cs) {
+            rewrite_leaves(expression, &mut |leaf| match leaf {
+                ExpressionMid::Fixed(query) => {
+                    let constant = constants.get(&query.column_index)?;
+                    propagated.insert(query.column_index);
+                    Some(ExpressionMid::Constant(*constant))
+                }
+                _ => None,
+            });
+            *expression = simplify(std::mem::replace(
+                expression,
+                ExpressionMid::Constant(F::ZERO),
+            ));
+        }
+        for index in propagated {
+            changes.push(format!(
+                "replaced the queries of constant fixed column {}",
+                index
+            ));
+        }
+
+        circuit.cs.gates.retain(|gate| {
+            let zero = gate.poly == ExpressionMid::Constant(F::ZERO);
+            if zero {
+                changes.push(format!(
+                    "removed gate \"{}\", which is always zero",
+                    gate.name
+                ));
+            }
+            !zero
+        });
+        changes
+    }
+}
+
+/// Merges the gates that enforce the same constraint on the same rows, such as gates enabled
+/// by the same selector that constrain the same cells.
+#[derive(Clone, Copy, Debug)]
+pub struct GateMerging;
+
+impl<F: Field> Pass<F> for GateMerging {
+    fn name(&self) -> &'static str {
+        "gate merging"
+    }
+
+    fn run(&self, circuit: &mut CompiledCircuitV2<F>) -> Vec<String> {
+        let mut changes = vec![];
+        let mut merged: Vec<GateV2Backend<F>> = vec![];
+        for gate in circuit.cs.gates.drain(..) {
+            match merged
+                .iter_mut()
+                .find(|other| other.poly == gate.poly && other.subdomain == gate.subdomain)
+            {
+                Some(other) => {
+                    changes.push(format!(
+                        "merged gate \"{}\" into gate \"{}\"",
+                        gate.name, other.name
+                    ));
+                }
+                None => merged.push(gate),
+            }
+        }
+        circuit.cs.gates = merged;
+        changes
+    }
+}
+
+/// Removes the fixed columns that are neither queried by a constraint nor part of the
+/// permutation argument.
+///
+/// Advice and instance columns are kept, even if they are unused, so that the witness and
+/// the instances of the circuit don't change.
+#[derive(Clone, Copy, Debug)]
+pub struct DeadColumnElimination;
+
+impl<F: Field> Pass<F> for DeadColumnElimination {
+    fn name(&self) -> &'static str {
+        "dead column elimination"
+    }
+
+    fn run(&self, circuit: &mut CompiledCircuitV2<F>) -> Vec<String> {
+        let cs = &mut circuit.cs;
+        let mut live = vec![false; cs.num_fixed_columns];
+        for expression in expressions_mut(cs) {
+            rewrite_leaves(expression, &mut |leaf| {
+                if let ExpressionMid::Fixed(query) = leaf {
+                    live[query.column_index] = true;
+                }
+                None
+            });
+        }
+        for column in &cs.permutation.columns {
+            if column.column_type == Any::Fixed {
+                live[column.index] = true;
+            }
+        }
+        if live.iter().all(|live| *live) {
+            return vec![];
+        }
+
+        // The new index of each live column.
+        let mut remap = HashMap::new();
+        let mut changes = vec![];
+        for (index, live) in live.iter().enumerate() {
+            if *live {
+                remap.insert(index, remap.len());
+            } else {
+                changes.push(format!("removed unused fixed column {}", index));
+            }
+        }
+
+        for expression in expressions_mut(cs) {
+            rewrite_leaves(expression, &mut |leaf| match leaf {
+                ExpressionMid::Fixed(query) => {
+                    let mut query = *query;
+                    query.column_index = remap[&query.column_index];
+                    Some(ExpressionMid::Fixed(query))
+                }
+                _ => None,
+            });
+        }
+        let remap_column = |column: &mut ColumnMid| {
+            if column.column_type == Any::Fixed {
+                column.index = remap[&column.index];
+            }
+        };
+        cs.permutation.columns.iter_mut().for_each(remap_column);
+        for (a, b) in circuit.preprocessing.permutation.copies.iter_mut() {
+            remap_column(&mut a.column);
+            remap_column(&mut b.column);
+        }
+        cs.general_column_annotations =
+            remap_fixed_keys(std::mem::take(&mut cs.general_column_annotations), &remap);
+        cs.column_ids = remap_fixed_keys(std::mem::take(&mut cs.column_ids), &remap);
+
+        let mut index = 0;
+        circuit.preprocessing.fixed.retain(|_| {
+            index += 1;
+            live[index - 1]
+        });
+        cs.num_fixed_columns = remap.len();
+        changes
+    }
+}
+
+/// Moves the entries of `map` keyed by a fixed column to the new index of the column in
+/// `remap`, dropping those of the columns that were removed.
+fn remap_fixed_keys<V>(
+    map: HashMap<metadata::Column, V>,
+    remap: &HashMap<usize, usize>,
+) -> HashMap<metadata::Column, V> {
+    map.into_iter()
+        .filter_map(|(column, value)| match column.column_type {
+            Any::Fixed => remap.get(&column.index).map(|index| {
+                (
+                    metadata::Column {
+                        column_type: Any::Fixed,
+                        index: *index,
+                    },
+                    value,
+                )
+            }),
+            _ => Some((column, value)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{AdviceQueryMid, FixedQueryMid, PreprocessingV2};
+    use crate::permutation;
+    use crate::poly::Rotation;
+    use halo2curves::pasta::Fp;
+
+    fn fixed(column_index: usize) -> ExpressionMid<Fp> {
+        ExpressionMid::Fixed(FixedQueryMid {
+            column_index,
+            rotation: Rotation::cur(),
+        })
+    }
+
+    fn advice(column_index: usize) -> ExpressionMid<Fp> {
+        ExpressionMid::Advice(AdviceQueryMid {
+            column_index,
+            rotation: Rotation::cur(),
+            phase: 0,
+        })
+    }
+
+    fn gate(name: &str, selector: usize, poly: ExpressionMid<Fp>) -> GateV2Backend<Fp> {
+        GateV2Backend {
+            name: name.to_string(),
+            poly: ExpressionMid::Product(Box::new(fixed(selector)), Box::new(poly)),
+            subdomain: None,
+        }
+    }
+
+    #[test]
+    fn default_passes() {
+        // Fixed columns: 0 is a selector, 1 is a selector that is never enabled, 2 is the
+        // constant 3 and 3 isn't used.
+        let n = 8;
+        let mut selector = vec![Fp::ZERO; n];
+        selector[1] = Fp::ONE;
+        let a_minus_3 = ExpressionMid::Sum(
+            Box::new(advice(0)),
+            Box::new(ExpressionMid::Negated(Box::new(fixed(2)))),
+        );
+        let mut circuit = CompiledCircuitV2 {
+            preprocessing: PreprocessingV2 {
+                permutation: permutation::AssemblyMid { copies: vec![] },
+                fixed: vec![
+                    selector.clone(),
+                    vec![Fp::ZERO; n],
+                    vec![Fp::from(3); n],
+                    selector,
+                ],
+            },
+            cs: ConstraintSystemV2Backend {
+                num_fixed_columns: 4,
+                num_advice_columns: 2,
+                num_instance_columns: 0,
+                num_challenges: 0,
+                unblinded_advice_columns: vec![],
+                blinded_instance_columns: vec![],
+                extra_unusable_rows: 0,
+                advice_column_phase: vec![0, 0],
+                challenge_phase: vec![],
+                gates: vec![
+                    gate("a is 3", 0, a_minus_3.clone()),
+                    gate("a is 3 again", 0, a_minus_3),
+                    gate("never", 1, advice(1)),
+                ],
+                permutation: permutation::ArgumentV2 { columns: vec![] },
+                lookups: vec![],
+                shuffles: vec![],
+                general_column_annotations: HashMap::new(),
+                column_ids: HashMap::new(),
+            },
+        };
+
+        let report = PassManager::default().run(&mut circuit);
+        assert_eq!(
+            report.to_string(),
+            "constant propagation: 3 changes\n\
+             \x20 replaced the queries of constant fixed column 1\n\
+             \x20 replaced the queries of constant fixed column 2\n\
+             \x20 removed gate \"never\", which is always zero\n\
+             gate merging: 1 changes\n\
+             \x20 merged gate \"a is 3 again\" into gate \"a is 3\"\n\
+             dead column elimination: 3 changes\n\
+             \x20 removed unused fixed column 1\n\
+             \x20 removed unused fixed column 2\n\
+             \x20 removed unused fixed column 3\n"
+        );
+
+        let cs = &circuit.cs;
+        assert_eq!(cs.num_fixed_columns, 1);
+        assert_eq!(circuit.preprocessing.fixed.len(), 1);
+        assert_eq!(cs.num_advice_columns, 2);
+        assert_eq!(cs.gates.len(), 1);
+        assert_eq!(
+            cs.gates[0].poly,
+            ExpressionMid::Product(
+                Box::new(fixed(0)),
+                Box::new(ExpressionMid::Sum(
+                    Box::new(advice(0)),
+                    Box::new(ExpressionMid::Constant(-Fp::from(3)))
+                ))
+            )
+        );
+
+        // The passes don't change an optimized circuit.
+        assert!(PassManager::default().run(&mut circuit).is_empty());
+    }
+
+    #[test]
+    fn permutation_columns_are_kept() {
+        use crate::circuit::Cell;
+
+        let n = 4;
+        let column = |index| ColumnMid {
+            index,
+            column_type: Any::Fixed,
+        };
+        let mut circuit = CompiledCircuitV2 {
+            preprocessing: PreprocessingV2 {
+                permutation: permutation::AssemblyMid {
+                    copies: vec![(
+                        Cell {
+                            column: column(1),
+                            row: 0,
+                        },
+                        Cell {
+                            column: column(1),
+                            row: 1,
+                        },
+                    )],
+                },
+                fixed: vec![vec![Fp::ZERO; n], vec![Fp::ONE; n]],
+            },
+            cs: ConstraintSystemV2Backend {
+                num_fixed_columns: 2,
+                num_advice_columns: 0,
+                num_instance_columns: 0,
+                num_challenges: 0,
+                unblinded_advice_columns: vec![],
+                blinded_instance_columns: vec![],
+                extra_unusable_rows: 0,
+                advice_column_phase: vec![],
+                challenge_phase: vec![],
+                gates: vec![],
+                permutation: permutation::ArgumentV2 {
+                    columns: vec![column(1)],
+                },
+                lookups: vec![],
+                shuffles: vec![],
+                general_column_annotations: HashMap::from([(
+                    metadata::Column::from((Any::Fixed, 1)),
+                    "ones".to_string(),
+                )]),
+                column_ids: HashMap::from([(
+                    metadata::Column::from((Any::Fixed, 1)),
+                    metadata::ColumnId::new("ones"),
+                )]),
+            },
+        };
+
+        DeadColumnElimination.run(&mut circuit);
+        assert_eq!(circuit.cs.num_fixed_columns, 1);
+        assert_eq!(circuit.preprocessing.fixed, vec![vec![Fp::ONE; n]]);
+        assert_eq!(circuit.cs.permutation.columns, vec![column(0)]);
+        assert_eq!(
+            circuit.preprocessing.permutation.copies[0].0.column,
+            column(0)
+        );
+        assert_eq!(
+            circuit.cs.general_column_annotations[&metadata::Column::from((Any::Fixed, 0))],
+            "ones"
+        );
+        assert_eq!(
+            circuit.cs.column_ids[&metadata::Column::from((Any::Fixed, 0))],
+            metadata::ColumnId::new("ones")
+        );
+    }
+}
@@ -0,0 +1,542 @@
+//! A stable binary format for expressions, gates and column identifiers.
+//!
+//! The format only depends on the structure of the expressions, not on a verifying key or
+//! on the layout of a constraint system, so it can be read by tools written in other
+//! languages. Integers are little-endian, and field elements are written in the
+//! representation of [`PrimeField::to_repr`].
+//!
+//! An expression is written in prefix order, as a tag byte followed by its contents:
+//!
+//! | Tag | Expression  | Contents                                                   |
+//! |-----|-------------|------------------------------------------------------------|
+//! | 0   | `Constant`  | the constant                                               |
+//! | 1   | `Fixed`     | column index (`u32`), rotation (`i32`)                     |
+//! | 2   | `Advice`    | column index (`u32`), rotation (`i32`), phase (`u8`)       |
+//! | 3   | `Instance`  | column index (`u32`), rotation (`i32`)                     |
+//! | 4   | `Challenge` | challenge index (`u32`), phase (`u8`)                      |
+//! | 5   | `Negated`   | the negated expression                                     |
+//! | 6   | `Sum`       | the two terms                                              |
+//! | 7   | `Product`   | the two factors                                            |
+//! | 8   | `Scaled`    | the scaled expression, then the factor                     |
+//!
+//! A list of gates starts with the version of the format ([`FORMAT_VERSION`], `u8`) and
+//! the number of gates (`u32`), followed by the name of each gate, as its length in bytes
+//! (`u32`) and its UTF-8 encoding, its expression, and its [subdomain](Subdomain): `0u8`
+//! if it's enforced on every row, otherwise `1u8` followed by the period (`u32`) and the
+//! offset (`u32`) of the subdomain.
+//!
+//! The expressions of the frontend are written once converted to [`ExpressionMid`], after
+//! their selectors are turned into fixed columns.
+//!
+//! A list of [column identifiers](ColumnId) starts with the version of the format and the
+//! number of columns (`u32`), followed by each column, in the order of
+//! [`metadata::Column`]: its type as the tag of its queries above, its index (`u32`), its
+//! phase (`u8`) if it's an advice column, its UUID (`u128`) and its name.
+//!
+//! Version 1 of the format is also read: it only differs in its gates, which have no
+//! subdomain.
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+use ff::PrimeField;
+
+use crate::circuit::{
+    Advice, AdviceQueryMid, Any, ChallengeMid, ExpressionMid, FixedQueryMid, GateV2Backend,
+    InstanceQueryMid, Subdomain,
+};
+use crate::metadata::{self, ColumnId};
+use crate::poly::Rotation;
+
+/// The version of the format, written at the start of a list of gates or of columns.
+pub const FORMAT_VERSION: u8 = 2;
+
+/// Expressions nested deeper than this are rejected when reading them, to bound the
+/// recursion of the reader.
+pub const MAX_EXPRESSION_DEPTH: usize = 1 << 10;
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn write_u32<W: io::Write>(writer: &mut W, value: usize) -> io::Result<()> {
+    let value = u32::try_from(value).map_err(|_| invalid_data("value doesn't fit in a u32"))?;
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u32<R: io::Read>(reader: &mut R) -> io::Result<usize> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes) as usize)
+}
+
+fn read_u8<R: io::Read>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0u8; 1];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn write_string<W: io::Write>(writer: &mut W, value: &str) -> io::Result<()> {
+    write_u32(writer, value.len())?;
+    writer.write_all(value.as_bytes())
+}
+
+fn read_string<R: io::Read>(reader: &mut R) -> io::Result<String> {
+    let len = read_u32(reader)?;
+    // The length is not trusted to preallocate the string.
+    let mut bytes = vec![];
+    reader.by_ref().take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|_| invalid_data("string isn't UTF-8"))
+}
+
+/// Reads the version of the format, and returns it if it's supported.
+fn read_version<R: io::Read>(reader: &mut R) -> io::Result<u8> {
+    let version = read_u8(reader)?;
+    if !(1..=FORMAT_VERSION).contains(&version) {
+        return Err(invalid_data(format!(
+            "unsupported format version {}",
+            version
+        )));
+    }
+    Ok(version)
+}
+
+fn write_subdomain<W: io::Write>(writer: &mut W, subdomain: Option<Subdomain>) -> io::Result<()> {
+    match subdomain {
+        None => writer.write_all(&[0]),
+        Some(subdomain) => {
+            writer.write_all(&[1])?;
+            write_u32(writer, subdomain.period())?;
+            write_u32(writer, subdomain.offset())
+        }
+    }
+}
+
+fn read_subdomain<R: io::Read>(reader: &mut R) -> io::Result<Option<Subdomain>> {
+    match read_u8(reader)? {
+        0 => Ok(None),
+        1 => {
+            let period = read_u32(reader)?;
+            let offset = read_u32(reader)?;
+            if !period.is_power_of_two() || offset >= period {
+                return Err(invalid_data(format!(
+                    "invalid subdomain of period {} and offset {}",
+                    period, offset
+                )));
+            }
+            Ok(Some(Subdomain::new(period, offset)))
+        }
+        tag => Err(invalid_data(format!("unknown subdomain tag {}", tag))),
+    }
+}
+
+fn write_rotation<W: io::Write>(writer: &mut W, rotation: Rotation) -> io::Result<()> {
+    writer.write_all(&rotation.0.to_le_bytes())
+}
+
+fn read_rotation<R: io::Read>(reader: &mut R) -> io::Result<Rotation> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(Rotation(i32::from_le_bytes(bytes)))
+}
+
+fn write_field<F: PrimeField, W: io::Write>(writer: &mut W, value: &F) -> io::Result<()> {
+    writer.write_all(value.to_repr().as_ref())
+}
+
+fn read_field<F: PrimeField, R: io::Read>(reader: &mut R) -> io::Result<F> {
+    let mut repr = F::Repr::default();
+    reader.read_exact(repr.as_mut())?;
+    Option::from(F::from_repr(repr)).ok_or_else(|| invalid_data("non-canonical field element"))
+}
+
+impl<F: PrimeField> ExpressionMid<F> {
+    /// Writes the expression in the format of the [module documentation](self).
+    pub fn write<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            ExpressionMid::Constant(constant) => {
+                writer.write_all(&[0])?;
+                write_field(writer, constant)
+            }
+            ExpressionMid::Fixed(query) => {
+                writer.write_all(&[1])?;
+                write_u32(writer, query.column_index)?;
+                write_rotation(writer, query.rotation)
+            }
+            ExpressionMid::Advice(query) => {
+                writer.write_all(&[2])?;
+                write_u32(writer, query.column_index)?;
+                write_rotation(writer, query.rotation)?;
+                writer.write_all(&[query.phase])
+            }
+            ExpressionMid::Instance(query) => {
+                writer.write_all(&[3])?;
+                write_u32(writer, query.column_index)?;
+                write_rotation(writer, query.rotation)
+            }
+            ExpressionMid::Challenge(challenge) => {
+                writer.write_all(&[4])?;
+                write_u32(writer, challenge.index)?;
+                writer.write_all(&[challenge.phase])
+            }
+            ExpressionMid::Negated(a) => {
+                writer.write_all(&[5])?;
+                a.write(writer)
+            }
+            ExpressionMid::Sum(a, b) => {
+                writer.write_all(&[6])?;
+                a.write(writer)?;
+                b.write(writer)
+            }
+            ExpressionMid::Product(a, b) => {
+                writer.write_all(&[7])?;
+                a.write(writer)?;
+                b.write(writer)
+            }
+            ExpressionMid::Scaled(a, scalar) => {
+                writer.write_all(&[8])?;
+                a.write(writer)?;
+                write_field(writer, scalar)
+            }
+        }
+    }
+
+    /// Reads an expression written by [`ExpressionMid::write`].
+    pub fn read<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        Self::read_nested(reader, 0)
+    }
+
+    fn read_nested<R: io::Read>(reader: &mut R, depth: usize) -> io::Result<Self> {
+        if depth > MAX_EXPRESSION_DEPTH {
+            return Err(invalid_data("expression is nested too deeply"));
+        }
+        let read = |reader: &mut R| Self::read_nested(reader, depth + 1).map(Box::new);
+        Ok(match read_u8(reader)? {
+            0 => ExpressionMid::Constant(read_field(reader)?),
+            1 => ExpressionMid::Fixed(FixedQueryMid {
+                column_index: read_u32(reader)?,
+                rotation: read_rotation(reader)?,
+            }),
+            2 => ExpressionMid::Advice(AdviceQueryMid {
+                column_index: read_u32(reader)?,
+                rotation: read_rotation(reader)?,
+                phase: read_u8(reader)?,
+            }),
+            3 => ExpressionMid::Instance(InstanceQueryMid {
+                column_index: read_u32(reader)?,
+                rotation: read_rotation(reader)?,
+            }),
+            4 => ExpressionMid::Challenge(ChallengeMid {
+                index: read_u32(reader)?,
+                phase: read_u8(reader)?,
+            }),
+            5 => ExpressionMid::Negated(read(reader)?),
+            6 => ExpressionMid::Sum(read(reader)?, read(reader)?),
+            7 => ExpressionMid::Product(read(reader)?, read(reader)?),
+            8 => ExpressionMid::Scaled(read(reader)?, read_field(reader)?),
+            tag => return Err(invalid_data(format!("unknown expression tag {}", tag))),
+        })
+    }
+
+    /// Returns the expression in the format of the [module documentation](self).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write(&mut bytes)
+            .expect("the indices of the expression fit in a u32");
+        bytes
+    }
+
+    /// Reads an expression from `bytes`, which must contain nothing else.
+    pub fn from_bytes(mut bytes: &[u8]) -> io::Result<Self> {
+        let expression = Self::read(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(invalid_data("trailing bytes after the expression"));
+        }
+        Ok(expression)
+    }
+}
+
+/// Writes `gates` in the format of the [module documentation](self).
+pub fn write_gates<F: PrimeField, W: io::Write>(
+    gates: &[GateV2Backend<F>],
+    writer: &mut W,
+) -> io::Result<()> {
+    writer.write_all(&[FORMAT_VERSION])?;
+    write_u32(writer, gates.len())?;
+    for gate in gates {
+        write_string(writer, &gate.name)?;
+        gate.poly.write(writer)?;
+        write_subdomain(writer, gate.subdomain)?;
+    }
+    Ok(())
+}
+
+/// Reads gates written by [`write_gates`].
+pub fn read_gates<F: PrimeField, R: io::Read>(reader: &mut R) -> io::Result<Vec<GateV2Backend<F>>> {
+    let version = read_version(reader)?;
+    let len = read_u32(reader)?;
+    // The length is not trusted to preallocate the gates.
+    let mut gates = vec![];
+    for _ in 0..len {
+        let name = read_string(reader)?;
+        let poly = ExpressionMid::read(reader)?;
+        let subdomain = if version == 1 {
+            None
+        } else {
+            read_subdomain(reader)?
+        };
+        gates.push(GateV2Backend {
+            name,
+            poly,
+            subdomain,
+        });
+    }
+    Ok(gates)
+}
+
+/// Writes the identifiers of the columns of a constraint system in the format of the
+/// [module documentation](self).
+pub fn write_column_ids<W: io::Write>(
+    column_ids: &HashMap<metadata::Column, ColumnId>,
+    writer: &mut W,
+) -> io::Result<()> {
+    let mut column_ids: Vec<_> = column_ids.iter().collect();
+    column_ids.sort();
+    writer.write_all(&[FORMAT_VERSION])?;
+    write_u32(writer, column_ids.len())?;
+    for (column, id) in column_ids {
+        match column.column_type {
+            Any::Fixed => writer.write_all(&[1])?,
+            Any::Advice(_) => writer.write_all(&[2])?,
+            Any::Instance => writer.write_all(&[3])?,
+        }
+        write_u32(writer, column.index)?;
+        if let Any::Advice(advice) = column.column_type {
+            writer.write_all(&[advice.phase])?;
+        }
+        writer.write_all(&id.uuid.to_le_bytes())?;
+        write_string(writer, &id.name)?;
+    }
+    Ok(())
+}
+
+/// Reads the identifiers of columns written by [`write_column_ids`].
+pub fn read_column_ids<R: io::Read>(
+    reader: &mut R,
+) -> io::Result<HashMap<metadata::Column, ColumnId>> {
+    read_version(reader)?;
+    let len = read_u32(reader)?;
+    let mut column_ids = HashMap::new();
+    for _ in 0..len {
+        let tag = read_u8(reader)?;
+        let index = read_u32(reader)?;
+        let column_type = match tag {
+            1 => Any::Fixed,
+            2 => Any::Advice(Advice::new(read_u8(reader)?)),
+            3 => Any::Instance,
+            _ => return Err(invalid_data(format!("unknown column type {}", tag))),
+        };
+        let mut uuid = [0u8; 16];
+        reader.read_exact(&mut uuid)?;
+        let id = ColumnId::with_uuid(read_string(reader)?, u128::from_le_bytes(uuid));
+        if column_ids
+            .insert(metadata::Column { column_type, index }, id)
+            .is_some()
+        {
+            return Err(invalid_data("duplicate column"));
+        }
+    }
+    Ok(column_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ff::Field;
+    use halo2curves::pasta::Fp;
+    use proptest::prelude::*;
+
+    fn arb_expression() -> impl Strategy<Value = ExpressionMid<Fp>> {
+        let leaf = prop_oneof![
+            any::<u64>().prop_map(|value| ExpressionMid::Constant(Fp::from(value))),
+            (0usize..16, -4i32..4).prop_map(|(column_index, rotation)| {
+                ExpressionMid::Fixed(FixedQueryMid {
+                    column_index,
+                    rotation: Rotation(rotation),
+                })
+            }),
+            (0usize..16, -4i32..4, 0u8..3).prop_map(|(column_index, rotation, phase)| {
+                ExpressionMid::Advice(AdviceQueryMid {
+                    column_index,
+                    rotation: Rotation(rotation),
+                    phase,
+                })
+            }),
+            (0usize..16, -4i32..4).prop_map(|(column_index, rotation)| {
+                ExpressionMid::Instance(InstanceQueryMid {
+                    column_index,
+                    rotation: Rotation(rotation),
+                })
+            }),
+            (0usize..4, 0u8..3)
+                .prop_map(|(index, phase)| ExpressionMid::Challenge(ChallengeMid { index, phase })),
+        ];
+        leaf.prop_recursive(6, 64, 2, |inner| {
+            prop_oneof![
+                inner
+                    .clone()
+                    .prop_map(|a| ExpressionMid::Negated(Box::new(a))),
+                (inner.clone(), inner.clone())
+                    .prop_map(|(a, b)| ExpressionMid::Sum(Box::new(a), Box::new(b))),
+                (inner.clone(), inner.clone())
+                    .prop_map(|(a, b)| ExpressionMid::Product(Box::new(a), Box::new(b))),
+                (inner, any::<u64>())
+                    .prop_map(|(a, s)| ExpressionMid::Scaled(Box::new(a), Fp::from(s))),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn expression_round_trip(expression in arb_expression()) {
+            let bytes = expression.to_bytes();
+            prop_assert_eq!(ExpressionMid::from_bytes(&bytes).unwrap(), expression);
+        }
+
+        #[test]
+        fn gates_round_trip(
+            gates in proptest::collection::vec(
+                (arb_expression(), proptest::option::of((0u32..4, 0usize..8))),
+                0..4,
+            ),
+        ) {
+            let gates: Vec<_> = gates
+                .into_iter()
+                .enumerate()
+                .map(|(i, (poly, subdomain))| GateV2Backend {
+                    name: format!("gate {}", i),
+                    poly,
+                    subdomain: subdomain
+                        .map(|(log_period, offset)| Subdomain::new(1 << log_period, offset % (1 << log_period))),
+                })
+                .collect();
+            let mut bytes = vec![];
+            write_gates(&gates, &mut bytes).unwrap();
+            let read = read_gates::<Fp, _>(&mut &bytes[..]).unwrap();
+            prop_assert_eq!(read.len(), gates.len());
+            for (read, gate) in read.iter().zip(gates.iter()) {
+                prop_assert_eq!(&read.name, &gate.name);
+                prop_assert_eq!(&read.poly, &gate.poly);
+                prop_assert_eq!(read.subdomain, gate.subdomain);
+            }
+        }
+    }
+
+    #[test]
+    fn stable_encoding() {
+        // -(a[1] * 2) + f[-1], with a in the second phase
+        let expression = ExpressionMid::Sum(
+            Box::new(ExpressionMid::Negated(Box::new(ExpressionMid::Scaled(
+                Box::new(ExpressionMid::Advice(AdviceQueryMid {
+                    column_index: 3,
+                    rotation: Rotation(1),
+                    phase: 1,
+                })),
+                Fp::from(2),
+            )))),
+            Box::new(ExpressionMid::Fixed(FixedQueryMid {
+                column_index: 0,
+                rotation: Rotation(-1),
+            })),
+        );
+        let mut two = vec![2];
+        two.resize(32, 0);
+        let expected: Vec<u8> = [
+            &[6, 5, 8, 2, 3, 0, 0, 0, 1, 0, 0, 0, 1][..],
+            &two,
+            &[1, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff],
+        ]
+        .concat();
+        assert_eq!(expression.to_bytes(), expected);
+
+        // Non-canonical field elements, unknown tags and trailing bytes are rejected.
+        let mut modulus = (-Fp::ONE).to_repr();
+        modulus[0] += 1;
+        assert!(ExpressionMid::<Fp>::from_bytes(&[&[0][..], &modulus].concat()).is_err());
+        assert!(ExpressionMid::<Fp>::from_bytes(&[9]).is_err());
+        assert!(ExpressionMid::<Fp>::from_bytes(&[expected, vec![0]].concat()).is_err());
+
+        // Deeply nested expressions are rejected.
+        let nested = vec![5; MAX_EXPRESSION_DEPTH + 2];
+        assert!(ExpressionMid::<Fp>::from_bytes(&nested).is_err());
+    }
+
+    #[test]
+    fn gates_versions() {
+        // A gate "g" constraining the constant 1, in version 1 then in version 2 on the
+        // rows 1, 5, 9, ...
+        let mut one = vec![1];
+        one.resize(32, 0);
+        let gates = [&[1, 0, 0, 0, 1, 0, 0, 0, b'g', 0][..], &one].concat();
+        let v1 = [&[1][..], &gates].concat();
+        let v2 = [&[2][..], &gates, &[1, 4, 0, 0, 0, 1, 0, 0, 0]].concat();
+
+        let gates = read_gates::<Fp, _>(&mut &v1[..]).unwrap();
+        assert_eq!(gates[0].poly, ExpressionMid::Constant(Fp::ONE));
+        assert_eq!(gates[0].subdomain, None);
+        let gates = read_gates::<Fp, _>(&mut &v2[..]).unwrap();
+        assert_eq!(gates[0].subdomain, Some(Subdomain::new(4, 1)));
+        let mut bytes = vec![];
+        write_gates(&gates, &mut bytes).unwrap();
+        assert_eq!(bytes, v2);
+
+        // Periods which aren't powers of two, offsets out of the period and unknown
+        // versions are rejected.
+        let mut invalid = v2.clone();
+        invalid[v2.len() - 8] = 3;
+        assert!(read_gates::<Fp, _>(&mut &invalid[..]).is_err());
+        let mut invalid = v2.clone();
+        invalid[v2.len() - 4] = 4;
+        assert!(read_gates::<Fp, _>(&mut &invalid[..]).is_err());
+        let mut invalid = v2;
+        invalid[0] = FORMAT_VERSION + 1;
+        assert!(read_gates::<Fp, _>(&mut &invalid[..]).is_err());
+    }
+
+    #[test]
+    fn column_ids_round_trip() {
+        let column_ids = HashMap::from([
+            (
+                metadata::Column::from((Any::Instance, 0)),
+                ColumnId::new("public"),
+            ),
+            (
+                metadata::Column::from((Any::Advice(Advice::new(1)), 2)),
+                ColumnId::with_uuid("acc", 7),
+            ),
+            (
+                metadata::Column::from((Any::Fixed, 1)),
+                ColumnId::new("table"),
+            ),
+        ]);
+        let mut bytes = vec![];
+        write_column_ids(&column_ids, &mut bytes).unwrap();
+        assert_eq!(read_column_ids(&mut &bytes[..]).unwrap(), column_ids);
+
+        // The columns are sorted, so the encoding doesn't depend on the order of the map:
+        // the instance column comes first, then the advice column.
+        assert_eq!(&bytes[..6], &[FORMAT_VERSION, 3, 0, 0, 0, 3]);
+        let advice: Vec<u8> =
+            [&[2, 2, 0, 0, 0, 1, 7][..], &[0; 15], &[3, 0, 0, 0], b"acc"].concat();
+        let offset = 5 + 1 + 4 + 16 + 4 + "public".len();
+        assert_eq!(&bytes[offset..offset + advice.len()], &advice[..]);
+
+        // Unknown column types are rejected.
+        let mut unknown = bytes.clone();
+        unknown[5] = 4;
+        assert!(read_column_ids(&mut &unknown[..]).is_err());
+    }
+}
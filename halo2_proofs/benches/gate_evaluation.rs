@@ -0,0 +1,122 @@
+#[macro_use]
+extern crate criterion;
+
+use group::ff::Field;
+use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner, Value};
+use halo2_proofs::plonk::*;
+use halo2_proofs::poly::ipa::commitment::{IPACommitmentScheme, ParamsIPA};
+use halo2_proofs::poly::ipa::multiopen::ProverIPA;
+use halo2_proofs::poly::{commitment::ParamsProver, Rotation};
+use halo2_proofs::transcript::{Blake2bWrite, Challenge255, TranscriptWriterBuffer};
+use halo2curves::pasta::{EqAffine, Fp};
+use rand_core::OsRng;
+
+use criterion::measurement::WallTime;
+use criterion::{BenchmarkGroup, BenchmarkId, Criterion};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    /// A circuit of `WIDTH` advice columns holding the row number, with a gate of degree 3
+    /// per column, so that the evaluation of the gates dominates the quotient polynomial.
+    #[derive(Clone)]
+    struct GatesCircuit<const WIDTH: usize> {
+        rows: usize,
+    }
+
+    impl<const WIDTH: usize> Circuit<Fp> for GatesCircuit<WIDTH> {
+        type Config = (Selector, Vec<Column<Advice>>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let selector = meta.selector();
+            let advice: Vec<_> = (0..WIDTH).map(|_| meta.advice_column()).collect();
+            meta.create_gate("products", |meta| {
+                let selector = meta.query_selector(selector);
+                (0..WIDTH)
+                    .map(|i| {
+                        // (r + 1) * r - r * r - r = 0
+                        let next = meta.query_advice(advice[i], Rotation::next());
+                        let mut cur =
+                            |j: usize| meta.query_advice(advice[(i + j) % WIDTH], Rotation::cur());
+                        selector.clone() * (next * cur(1) - cur(2) * cur(2) - cur(3))
+                    })
+                    .collect::<Vec<_>>()
+            });
+            (selector, advice)
+        }
+
+        fn synthesize(
+            &self,
+            (selector, advice): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "rows",
+                |mut region| {
+                    for row in 0..=self.rows {
+                        if row < self.rows {
+                            selector.enable(&mut region, row)?;
+                        }
+                        let value = Fp::from(row as u64);
+                        for column in &advice {
+                            region.assign_advice(|| "a", *column, row, || Value::known(value))?;
+                        }
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    fn bench_width<const WIDTH: usize>(
+        group: &mut BenchmarkGroup<'_, WallTime>,
+        params: &ParamsIPA<EqAffine>,
+        k: u32,
+    ) {
+        let circuit = GatesCircuit::<WIDTH> {
+            rows: (1 << k) - 16,
+        };
+        let vk = keygen_vk(params, &circuit).expect("keygen_vk should not fail");
+        let mut pk = keygen_pk(params, vk, &circuit).expect("keygen_pk should not fail");
+        for (name, evaluation) in [
+            ("interpreter", GateEvaluation::Interpreter),
+            ("jit", GateEvaluation::Jit),
+        ] {
+            pk.set_gate_evaluation(evaluation)
+                .expect("the gates should compile");
+            group.bench_with_input(BenchmarkId::new(name, WIDTH), &circuit, |b, circuit| {
+                b.iter(|| {
+                    let mut transcript = Blake2bWrite::<_, _, Challenge255<EqAffine>>::init(vec![]);
+                    create_proof::<IPACommitmentScheme<EqAffine>, ProverIPA<EqAffine>, _, _, _, _>(
+                        params,
+                        &pk,
+                        &[circuit.clone()],
+                        &[&[]],
+                        OsRng,
+                        &mut transcript,
+                    )
+                    .expect("proof generation should not fail");
+                    transcript.finalize()
+                });
+            });
+        }
+    }
+
+    let k = 12;
+    let params: ParamsIPA<EqAffine> = ParamsIPA::new(k);
+
+    let mut group = c.benchmark_group("gate-evaluation");
+    group.sample_size(10);
+    bench_width::<16>(&mut group, &params, k);
+    bench_width::<64>(&mut group, &params, k);
+    bench_width::<128>(&mut group, &params, k);
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
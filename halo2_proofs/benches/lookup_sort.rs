@@ -0,0 +1,61 @@
+#[macro_use]
+extern crate criterion;
+
+use group::ff::Field;
+use halo2_proofs::arithmetic::sort_by_repr;
+use halo2curves::pasta::Fp;
+use rand_core::OsRng;
+
+use criterion::{BatchSize, BenchmarkId, Criterion};
+
+/// Compares the sort of the inputs of the lookup prover by their representations with
+/// the comparison sort of the field elements it replaces.
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup-sort");
+    group.sample_size(10);
+    for k in [18, 20, 22] {
+        let n = 1 << k;
+        // The compressed inputs of a lookup are random elements, while those of a
+        // range check are small values.
+        let inputs = [
+            (
+                "random",
+                (0..n).map(|_| Fp::random(OsRng)).collect::<Vec<_>>(),
+            ),
+            (
+                "range",
+                (0..n as u64)
+                    .map(|i| Fp::from((i * 7919) % (1 << 16)))
+                    .collect(),
+            ),
+        ];
+        for (name, values) in inputs.iter() {
+            group.bench_with_input(
+                BenchmarkId::new(format!("sort-{name}"), k),
+                values,
+                |b, values| {
+                    b.iter_batched(
+                        || values.clone(),
+                        |mut values| values.sort(),
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+            group.bench_with_input(
+                BenchmarkId::new(format!("radix-{name}"), k),
+                values,
+                |b, values| {
+                    b.iter_batched(
+                        || values.clone(),
+                        |mut values| sort_by_repr(&mut values),
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,424 @@
+//! Key generation, proving and verification of reference circuits across `k`, which
+//! stress the parts of the prover separately: wide custom gates, lookups, the permutation
+//! argument and multiphase circuits with challenges.
+//!
+//! Each circuit fills all the usable rows of its domain, so that the benchmarks of a
+//! circuit at several `k` are comparable.
+
+#[macro_use]
+extern crate criterion;
+
+use group::ff::Field;
+use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner, Value};
+use halo2_proofs::plonk::*;
+use halo2_proofs::poly::ipa::commitment::{IPACommitmentScheme, ParamsIPA};
+use halo2_proofs::poly::ipa::multiopen::ProverIPA;
+use halo2_proofs::poly::ipa::strategy::SingleStrategy;
+use halo2_proofs::poly::{commitment::ParamsProver, Rotation, VerificationStrategy};
+use halo2_proofs::transcript::{
+    Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+};
+use halo2curves::pasta::{EqAffine, Fp};
+use rand_core::OsRng;
+
+use criterion::{BenchmarkId, Criterion};
+
+/// The rows of the domain which are not usable by the circuits, an upper bound of the
+/// blinding rows of all of them.
+const UNUSABLE_ROWS: usize = 16;
+
+/// A reference circuit, which fills the domain of size `2^k`.
+trait ReferenceCircuit: Circuit<Fp> {
+    /// The name of the benchmark group of the circuit.
+    const NAME: &'static str;
+
+    /// The smallest `k` the circuit fits in.
+    const MIN_K: u32;
+
+    /// Returns the circuit filling the domain of size `2^k`.
+    fn new(k: u32) -> Self;
+}
+
+fn usable_rows(k: u32) -> usize {
+    (1 << k) - UNUSABLE_ROWS
+}
+
+/// A circuit of `WIDTH` advice columns, where each column at the next row is the product
+/// of the column and of the next one at the current row, plus one.
+#[derive(Clone)]
+struct WideArithmetic<const WIDTH: usize> {
+    rows: usize,
+}
+
+impl<const WIDTH: usize> Circuit<Fp> for WideArithmetic<WIDTH> {
+    type Config = (Selector, [Column<Advice>; WIDTH]);
+    type FloorPlanner = SimpleFloorPlanner;
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let selector = meta.selector();
+        let advice = [(); WIDTH].map(|_| meta.advice_column());
+        meta.create_gate("products", |meta| {
+            let selector = meta.query_selector(selector);
+            (0..WIDTH)
+                .map(|i| {
+                    let next = meta.query_advice(advice[i], Rotation::next());
+                    let a = meta.query_advice(advice[i], Rotation::cur());
+                    let b = meta.query_advice(advice[(i + 1) % WIDTH], Rotation::cur());
+                    selector.clone() * (next - a * b - Expression::Constant(Fp::ONE))
+                })
+                .collect::<Vec<_>>()
+        });
+        (selector, advice)
+    }
+
+    fn synthesize(
+        &self,
+        (selector, advice): Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "products",
+            |mut region| {
+                let mut values: Vec<_> = (0..WIDTH).map(|i| Fp::from(i as u64)).collect();
+                for row in 0..self.rows {
+                    for (column, value) in advice.iter().zip(&values) {
+                        region.assign_advice(|| "a", *column, row, || Value::known(*value))?;
+                    }
+                    if row + 1 < self.rows {
+                        selector.enable(&mut region, row)?;
+                        values = (0..WIDTH)
+                            .map(|i| values[i] * values[(i + 1) % WIDTH] + Fp::ONE)
+                            .collect();
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+impl<const WIDTH: usize> ReferenceCircuit for WideArithmetic<WIDTH> {
+    const NAME: &'static str = "wide-arithmetic";
+    const MIN_K: u32 = 6;
+
+    fn new(k: u32) -> Self {
+        WideArithmetic {
+            rows: usable_rows(k),
+        }
+    }
+}
+
+/// A circuit of `LOOKUPS` advice columns, each of which is range checked by a lookup into
+/// an 8-bit table at every row.
+#[derive(Clone)]
+struct LookupHeavy<const LOOKUPS: usize> {
+    rows: usize,
+}
+
+impl<const LOOKUPS: usize> Circuit<Fp> for LookupHeavy<LOOKUPS> {
+    type Config = (Selector, TableColumn, [Column<Advice>; LOOKUPS]);
+    type FloorPlanner = SimpleFloorPlanner;
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let selector = meta.complex_selector();
+        let table = meta.lookup_table_column();
+        let advice = [(); LOOKUPS].map(|_| meta.advice_column());
+        for column in advice {
+            meta.lookup("range check", |meta| {
+                let selector = meta.query_selector(selector);
+                let value = meta.query_advice(column, Rotation::cur());
+                vec![(selector * value, table)]
+            });
+        }
+        (selector, table, advice)
+    }
+
+    fn synthesize(
+        &self,
+        (selector, table, advice): Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        layouter.assign_table(
+            || "8-bit table",
+            |mut table_layouter| {
+                for row in 0..(1 << 8) {
+                    table_layouter.assign_cell(
+                        || "value",
+                        table,
+                        row,
+                        || Value::known(Fp::from(row as u64)),
+                    )?;
+                }
+                Ok(())
+            },
+        )?;
+        layouter.assign_region(
+            || "values",
+            |mut region| {
+                for row in 0..self.rows {
+                    selector.enable(&mut region, row)?;
+                    for (i, column) in advice.iter().enumerate() {
+                        let value = Fp::from(((row * (i + 1)) % (1 << 8)) as u64);
+                        region.assign_advice(|| "value", *column, row, || Value::known(value))?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+impl<const LOOKUPS: usize> ReferenceCircuit for LookupHeavy<LOOKUPS> {
+    const NAME: &'static str = "lookup-heavy";
+    const MIN_K: u32 = 9;
+
+    fn new(k: u32) -> Self {
+        LookupHeavy {
+            rows: usable_rows(k),
+        }
+    }
+}
+
+/// A circuit of `COLUMNS` advice columns in the permutation, where the cells of each row
+/// are constrained to be equal to each other, and the first cell of each row to the last
+/// cell of the previous row.
+#[derive(Clone)]
+struct PermutationHeavy<const COLUMNS: usize> {
+    rows: usize,
+}
+
+impl<const COLUMNS: usize> Circuit<Fp> for PermutationHeavy<COLUMNS> {
+    type Config = [Column<Advice>; COLUMNS];
+    type FloorPlanner = SimpleFloorPlanner;
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let advice = [(); COLUMNS].map(|_| meta.advice_column());
+        for column in advice {
+            meta.enable_equality(column);
+        }
+        advice
+    }
+
+    fn synthesize(
+        &self,
+        advice: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "copies",
+            |mut region| {
+                let mut previous = None;
+                for row in 0..self.rows {
+                    for column in advice {
+                        let cell = region
+                            .assign_advice(|| "value", column, row, || Value::known(Fp::ONE))?
+                            .cell();
+                        if let Some(previous) = previous {
+                            region.constrain_equal(previous, cell)?;
+                        }
+                        previous = Some(cell);
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+impl<const COLUMNS: usize> ReferenceCircuit for PermutationHeavy<COLUMNS> {
+    const NAME: &'static str = "permutation-heavy";
+    const MIN_K: u32 = 6;
+
+    fn new(k: u32) -> Self {
+        PermutationHeavy {
+            rows: usable_rows(k),
+        }
+    }
+}
+
+/// A circuit computing the random linear combination of a first phase column with a
+/// challenge, accumulated row by row in a second phase column.
+#[derive(Clone)]
+struct MultiphaseRlc {
+    rows: usize,
+}
+
+#[derive(Clone)]
+struct MultiphaseRlcConfig {
+    first: Selector,
+    next: Selector,
+    values: Column<Advice>,
+    acc: Column<Advice>,
+    challenge: Challenge,
+}
+
+impl Circuit<Fp> for MultiphaseRlc {
+    type Config = MultiphaseRlcConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let first = meta.selector();
+        let next = meta.selector();
+        let values = meta.advice_column_in(FirstPhase);
+        let challenge = meta.challenge_usable_after(FirstPhase);
+        let acc = meta.advice_column_in(SecondPhase);
+        meta.create_gate("rlc", |meta| {
+            let first = meta.query_selector(first);
+            let next = meta.query_selector(next);
+            let value = meta.query_advice(values, Rotation::cur());
+            let next_value = meta.query_advice(values, Rotation::next());
+            let acc_cur = meta.query_advice(acc, Rotation::cur());
+            let acc_next = meta.query_advice(acc, Rotation::next());
+            let challenge = meta.query_challenge(challenge);
+            vec![
+                first * (acc_cur.clone() - value),
+                next * (acc_next - acc_cur * challenge - next_value),
+            ]
+        });
+        MultiphaseRlcConfig {
+            first,
+            next,
+            values,
+            acc,
+            challenge,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let challenge = layouter.get_challenge(config.challenge);
+        layouter.assign_region(
+            || "rlc",
+            |mut region| {
+                config.first.enable(&mut region, 0)?;
+                let mut acc = Value::known(Fp::ZERO);
+                for row in 0..self.rows {
+                    let value = Value::known(Fp::from(row as u64 + 1));
+                    region.assign_advice(|| "value", config.values, row, || value)?;
+                    acc = if row == 0 {
+                        value
+                    } else {
+                        acc * challenge + value
+                    };
+                    region.assign_advice(|| "acc", config.acc, row, || acc)?;
+                    if row + 1 < self.rows {
+                        config.next.enable(&mut region, row)?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+impl ReferenceCircuit for MultiphaseRlc {
+    const NAME: &'static str = "multiphase-rlc";
+    const MIN_K: u32 = 6;
+
+    fn new(k: u32) -> Self {
+        MultiphaseRlc {
+            rows: usable_rows(k),
+        }
+    }
+}
+
+fn keygen<C: ReferenceCircuit>(k: u32) -> (ParamsIPA<EqAffine>, ProvingKey<EqAffine>) {
+    let params: ParamsIPA<EqAffine> = ParamsIPA::new(k);
+    let circuit = C::new(k).without_witnesses();
+    let vk = keygen_vk(&params, &circuit).expect("keygen_vk should not fail");
+    let pk = keygen_pk(&params, vk, &circuit).expect("keygen_pk should not fail");
+    (params, pk)
+}
+
+fn prover<C: ReferenceCircuit>(
+    k: u32,
+    params: &ParamsIPA<EqAffine>,
+    pk: &ProvingKey<EqAffine>,
+) -> Vec<u8> {
+    let mut transcript = Blake2bWrite::<_, _, Challenge255<EqAffine>>::init(vec![]);
+    create_proof::<IPACommitmentScheme<EqAffine>, ProverIPA<EqAffine>, _, _, _, _>(
+        params,
+        pk,
+        &[C::new(k)],
+        &[&[]],
+        OsRng,
+        &mut transcript,
+    )
+    .expect("proof generation should not fail");
+    transcript.finalize()
+}
+
+fn verifier(params: &ParamsIPA<EqAffine>, vk: &VerifyingKey<EqAffine>, proof: &[u8]) {
+    let strategy = SingleStrategy::new(params);
+    let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+    assert!(verify_proof(params, vk, strategy, &[&[]], &mut transcript).is_ok());
+}
+
+/// Benchmarks key generation, proving and verification of `C` for each `k` of `k_range`
+/// it fits in.
+fn bench_circuit<C: ReferenceCircuit>(c: &mut Criterion, k_range: impl Iterator<Item = u32>) {
+    let mut group = c.benchmark_group(C::NAME);
+    group.sample_size(10);
+    for k in k_range.filter(|k| *k >= C::MIN_K) {
+        group.bench_with_input(BenchmarkId::new("keygen", k), &k, |b, &k| {
+            b.iter(|| keygen::<C>(k));
+        });
+
+        let (params, pk) = keygen::<C>(k);
+        group.bench_with_input(
+            BenchmarkId::new("prove", k),
+            &(k, &params, &pk),
+            |b, &(k, params, pk)| {
+                b.iter(|| prover::<C>(k, params, pk));
+            },
+        );
+
+        let proof = prover::<C>(k, &params, &pk);
+        group.bench_with_input(
+            BenchmarkId::new("verify", k),
+            &(&params, pk.get_vk(), &proof[..]),
+            |b, &(params, vk, proof)| {
+                b.iter(|| verifier(params, vk, proof));
+            },
+        );
+    }
+    group.finish();
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let k_range = 10..=14;
+    bench_circuit::<WideArithmetic<16>>(c, k_range.clone());
+    bench_circuit::<LookupHeavy<8>>(c, k_range.clone());
+    bench_circuit::<PermutationHeavy<8>>(c, k_range.clone());
+    bench_circuit::<MultiphaseRlc>(c, k_range);
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
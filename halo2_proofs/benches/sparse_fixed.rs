@@ -0,0 +1,124 @@
+#[macro_use]
+extern crate criterion;
+
+use group::ff::Field;
+use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner, Value};
+use halo2_proofs::plonk::*;
+use halo2_proofs::poly::ipa::commitment::{IPACommitmentScheme, ParamsIPA};
+use halo2_proofs::poly::ipa::multiopen::ProverIPA;
+use halo2_proofs::poly::{commitment::ParamsProver, Rotation};
+use halo2_proofs::transcript::{Blake2bWrite, Challenge255, TranscriptWriterBuffer};
+use halo2curves::pasta::{EqAffine, Fp};
+use rand_core::OsRng;
+
+use criterion::measurement::WallTime;
+use criterion::{BenchmarkGroup, BenchmarkId, Criterion};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    /// A circuit of `COLUMNS` fixed columns, each enabling a gate on the rows where it is
+    /// one. The columns are one on a single row when `dense` is false, so that the proving
+    /// key keeps them sparse and their polynomials are computed for each proof.
+    #[derive(Clone)]
+    struct FixedCircuit<const COLUMNS: usize> {
+        rows: usize,
+        dense: bool,
+    }
+
+    impl<const COLUMNS: usize> Circuit<Fp> for FixedCircuit<COLUMNS> {
+        type Config = (Column<Advice>, Column<Advice>, Vec<Column<Fixed>>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let b = meta.advice_column();
+            let fixed: Vec<_> = (0..COLUMNS).map(|_| meta.fixed_column()).collect();
+            meta.create_gate("equal", |meta| {
+                let a = meta.query_advice(a, Rotation::cur());
+                let b = meta.query_advice(b, Rotation::cur());
+                fixed
+                    .iter()
+                    .map(|column| {
+                        meta.query_fixed(*column, Rotation::cur()) * (a.clone() - b.clone())
+                    })
+                    .collect::<Vec<_>>()
+            });
+            (a, b, fixed)
+        }
+
+        fn synthesize(
+            &self,
+            (a, b, fixed): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "rows",
+                |mut region| {
+                    for row in 0..self.rows {
+                        let value = Value::known(Fp::from(row as u64));
+                        region.assign_advice(|| "a", a, row, || value)?;
+                        region.assign_advice(|| "b", b, row, || value)?;
+                        for (i, column) in fixed.iter().enumerate() {
+                            if self.dense || row == i {
+                                region.assign_fixed(
+                                    || "f",
+                                    *column,
+                                    row,
+                                    || Value::known(Fp::ONE),
+                                )?;
+                            }
+                        }
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    fn bench_columns<const COLUMNS: usize>(
+        group: &mut BenchmarkGroup<'_, WallTime>,
+        params: &ParamsIPA<EqAffine>,
+        k: u32,
+    ) {
+        for (name, dense) in [("sparse", false), ("dense", true)] {
+            let circuit = FixedCircuit::<COLUMNS> {
+                rows: (1 << k) - 16,
+                dense,
+            };
+            let vk = keygen_vk(params, &circuit).expect("keygen_vk should not fail");
+            let pk = keygen_pk(params, vk, &circuit).expect("keygen_pk should not fail");
+            group.bench_with_input(BenchmarkId::new(name, COLUMNS), &circuit, |b, circuit| {
+                b.iter(|| {
+                    let mut transcript = Blake2bWrite::<_, _, Challenge255<EqAffine>>::init(vec![]);
+                    create_proof::<IPACommitmentScheme<EqAffine>, ProverIPA<EqAffine>, _, _, _, _>(
+                        params,
+                        &pk,
+                        &[circuit.clone()],
+                        &[&[]],
+                        OsRng,
+                        &mut transcript,
+                    )
+                    .expect("proof generation should not fail");
+                    transcript.finalize()
+                });
+            });
+        }
+    }
+
+    let k = 12;
+    let params: ParamsIPA<EqAffine> = ParamsIPA::new(k);
+
+    let mut group = c.benchmark_group("sparse-fixed");
+    group.sample_size(10);
+    bench_columns::<16>(&mut group, &params, k);
+    bench_columns::<64>(&mut group, &params, k);
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,131 @@
+#[macro_use]
+extern crate criterion;
+
+use ff::PrimeField;
+use halo2_proofs::circuit::{floor_planner::V1, Layouter, SimpleFloorPlanner, Value};
+use halo2_proofs::dev::time_synthesis;
+use halo2_proofs::plonk::*;
+use halo2_proofs::poly::Rotation;
+use halo2curves::pasta::pallas;
+
+use std::marker::PhantomData;
+
+use criterion::{BenchmarkId, Criterion};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    /// A circuit of `regions` regions of `rows` rows, each computing a running sum.
+    struct MyCircuit<F: PrimeField, P: FloorPlanner> {
+        regions: usize,
+        rows: usize,
+        _marker: PhantomData<(F, P)>,
+    }
+
+    #[derive(Clone)]
+    struct MyConfig {
+        selector: Selector,
+        a: Column<Advice>,
+        b: Column<Advice>,
+    }
+
+    impl<F: PrimeField, P: FloorPlanner> Circuit<F> for MyCircuit<F, P> {
+        type Config = MyConfig;
+        type FloorPlanner = P;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                regions: self.regions,
+                rows: self.rows,
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> MyConfig {
+            let config = MyConfig {
+                selector: meta.selector(),
+                a: meta.advice_column(),
+                b: meta.advice_column(),
+            };
+
+            meta.create_gate("running sum", |meta| {
+                let selector = meta.query_selector(config.selector);
+                let a = meta.query_advice(config.a, Rotation::cur());
+                let b = meta.query_advice(config.b, Rotation::cur());
+                let b_next = meta.query_advice(config.b, Rotation::next());
+                vec![selector * (b + a - b_next)]
+            });
+
+            config
+        }
+
+        fn synthesize(
+            &self,
+            config: MyConfig,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            for region in 0..self.regions {
+                layouter.assign_region(
+                    || format!("region {region}"),
+                    |mut region| {
+                        let mut sum = F::ZERO;
+                        for offset in 0..self.rows {
+                            let a = F::from(offset as u64);
+                            config.selector.enable(&mut region, offset)?;
+                            region.assign_advice(|| "a", config.a, offset, || Value::known(a))?;
+                            region.assign_advice(|| "b", config.b, offset, || Value::known(sum))?;
+                            sum += a;
+                        }
+                        region.assign_advice(|| "b", config.b, self.rows, || Value::known(sum))?;
+                        Ok(())
+                    },
+                )?;
+            }
+            Ok(())
+        }
+    }
+
+    fn circuit<P: FloorPlanner>(k: u32) -> MyCircuit<pallas::Base, P> {
+        MyCircuit {
+            regions: 1 << (k - 6),
+            rows: 31,
+            _marker: PhantomData,
+        }
+    }
+
+    let k_range = 10..=14;
+
+    // Print the breakdown of the largest circuit once, to compare the time spent in the
+    // region closures with the time spent in the floor planners.
+    let k = *k_range.end();
+    for timings in [
+        time_synthesis(k, &circuit::<SimpleFloorPlanner>(k), vec![]).unwrap(),
+        time_synthesis(k, &circuit::<V1>(k), vec![]).unwrap(),
+    ] {
+        println!(
+            "total {:?}: shape {:?}, assignment {:?}, backend {:?}, layouter {:?}",
+            timings.total,
+            timings.shape(),
+            timings.assignment(),
+            timings.backend(),
+            timings.layouter()
+        );
+    }
+
+    let mut group = c.benchmark_group("synthesis");
+    group.sample_size(10);
+    for k in k_range {
+        group.bench_with_input(BenchmarkId::new("single-pass", k), &k, |b, &k| {
+            let circuit = circuit::<SimpleFloorPlanner>(k);
+            b.iter(|| time_synthesis(k, &circuit, vec![]).unwrap());
+        });
+        group.bench_with_input(BenchmarkId::new("v1", k), &k, |b, &k| {
+            let circuit = circuit::<V1>(k);
+            b.iter(|| time_synthesis(k, &circuit, vec![]).unwrap());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,139 @@
+#[macro_use]
+extern crate criterion;
+
+use group::ff::Field;
+use halo2_backend::plonk::{prover::ProverV2Single, WitnessLayout};
+use halo2_frontend::circuit::{compile_circuit, WitnessCalculator};
+use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner, Value};
+use halo2_proofs::plonk::*;
+use halo2_proofs::poly::ipa::commitment::{IPACommitmentScheme, ParamsIPA};
+use halo2_proofs::poly::ipa::multiopen::ProverIPA;
+use halo2_proofs::poly::{commitment::ParamsProver, Rotation};
+use halo2_proofs::transcript::{Blake2bWrite, Challenge255, TranscriptWriterBuffer};
+use halo2curves::pasta::{EqAffine, Fp};
+use rand_core::OsRng;
+use std::collections::HashMap;
+
+use criterion::measurement::WallTime;
+use criterion::{BenchmarkGroup, BenchmarkId, Criterion};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    /// A circuit of `WIDTH` advice columns, where each column at the next row is the next
+    /// column plus one, so that every gate reads several columns at each row.
+    #[derive(Clone)]
+    struct WideCircuit<const WIDTH: usize> {
+        rows: usize,
+    }
+
+    impl<const WIDTH: usize> Circuit<Fp> for WideCircuit<WIDTH> {
+        type Config = (Selector, Vec<Column<Advice>>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let selector = meta.selector();
+            let advice: Vec<_> = (0..WIDTH).map(|_| meta.advice_column()).collect();
+            meta.create_gate("increments", |meta| {
+                let selector = meta.query_selector(selector);
+                (0..WIDTH)
+                    .map(|i| {
+                        let next = meta.query_advice(advice[i], Rotation::next());
+                        let cur = meta.query_advice(advice[(i + 1) % WIDTH], Rotation::cur());
+                        selector.clone() * (next - cur - Expression::Constant(Fp::ONE))
+                    })
+                    .collect::<Vec<_>>()
+            });
+            (selector, advice)
+        }
+
+        fn synthesize(
+            &self,
+            (selector, advice): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "rows",
+                |mut region| {
+                    for row in 0..=self.rows {
+                        if row < self.rows {
+                            selector.enable(&mut region, row)?;
+                        }
+                        let value = Fp::from(row as u64);
+                        for column in &advice {
+                            region.assign_advice(|| "a", *column, row, || Value::known(value))?;
+                        }
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    fn bench_width<const WIDTH: usize>(
+        group: &mut BenchmarkGroup<'_, WallTime>,
+        params: &ParamsIPA<EqAffine>,
+        k: u32,
+    ) {
+        let circuit = WideCircuit::<WIDTH> {
+            rows: (1 << k) - 16,
+        };
+        let vk = keygen_vk(params, &circuit).expect("keygen_vk should not fail");
+        let pk = keygen_pk(params, vk, &circuit).expect("keygen_pk should not fail");
+        for (name, layout) in [
+            ("column-major", WitnessLayout::ColumnMajor),
+            ("row-major", WitnessLayout::RowMajor),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, WIDTH), &circuit, |b, circuit| {
+                b.iter(|| {
+                    let (_, config, cs) =
+                        compile_circuit(k, circuit, pk.get_vk().compress_selectors)
+                            .expect("circuit compilation should not fail");
+                    let mut witness_calc = WitnessCalculator::new(k, circuit, &config, &cs, &[]);
+                    let mut transcript = Blake2bWrite::<_, _, Challenge255<EqAffine>>::init(vec![]);
+                    let mut prover = ProverV2Single::<
+                        IPACommitmentScheme<EqAffine>,
+                        ProverIPA<EqAffine>,
+                        _,
+                        _,
+                        _,
+                    >::new(
+                        params, &pk, &[], OsRng, &mut transcript
+                    )
+                    .expect("prover creation should not fail");
+                    prover.set_witness_layout(layout);
+                    let mut challenges = HashMap::new();
+                    for phase in 0..cs.phases().count() {
+                        let witness = witness_calc
+                            .calc(phase as u8, &challenges)
+                            .expect("witness calculation should not fail");
+                        challenges = prover
+                            .commit_phase(phase as u8, witness)
+                            .expect("phase commitment should not fail");
+                    }
+                    prover
+                        .create_proof()
+                        .expect("proof generation should not fail");
+                    transcript.finalize()
+                });
+            });
+        }
+    }
+
+    let k = 12;
+    let params: ParamsIPA<EqAffine> = ParamsIPA::new(k);
+
+    let mut group = c.benchmark_group("witness-layout");
+    group.sample_size(10);
+    bench_width::<16>(&mut group, &params, k);
+    bench_width::<64>(&mut group, &params, k);
+    bench_width::<128>(&mut group, &params, k);
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+//! Generates the boilerplate of a new circuit into a crate:
+//!
+//! ```text
+//! cargo run --example scaffold -- <CircuitName> <crate_name> <crate_root> [--lookup]
+//! ```
+
+use std::path::Path;
+
+use halo2_proofs::scaffold::{generate, CircuitSpec};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let positional: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    let [name, crate_name, root] = positional[..] else {
+        eprintln!("usage: scaffold <CircuitName> <crate_name> <crate_root> [--lookup]");
+        std::process::exit(1);
+    };
+
+    let spec = CircuitSpec {
+        lookup: args.iter().any(|arg| arg == "--lookup"),
+        ..CircuitSpec::new(name.as_str(), crate_name.as_str())
+    };
+    let scaffold = generate(&spec).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    scaffold
+        .write_to(Path::new(root))
+        .expect("the files should not exist yet");
+
+    for file in &scaffold.files {
+        println!("wrote {}", file.path.display());
+    }
+    println!(
+        "declare `pub mod {};` in the crate, and add to its manifest:\n\n{}",
+        spec.module_name(),
+        scaffold.manifest
+    );
+}
@@ -0,0 +1,369 @@
+//! Measurement of the key generation, proving and verification of a circuit, to track
+//! performance regressions in CI.
+
+use std::fmt::{self, Write as _};
+use std::time::{Duration, Instant};
+
+use halo2_backend::plonk::prover::create_proof_v2_with_queries;
+use halo2_common::plonk::{circuit::Circuit, Error};
+use halo2_frontend::circuit::{compile_circuit, WitnessCalculator};
+use halo2curves::bn256::{Bn256, Fr, G1Affine};
+use rand_core::OsRng;
+
+use crate::plonk::{keygen_pk, keygen_vk, verify_proof};
+use crate::poly::commitment::{Params, ParamsProver};
+use crate::poly::kzg::commitment::{KZGCommitmentScheme, ParamsKZG};
+use crate::poly::kzg::multiopen::{ProverSHPLONK, VerifierSHPLONK};
+use crate::poly::kzg::strategy::SingleStrategy;
+use crate::poly::VerificationStrategy;
+use crate::transcript::{
+    Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+};
+
+/// The version of the JSON output of [`BenchmarkReport::to_json`], which changes when its
+/// fields change.
+pub const BENCHMARK_REPORT_VERSION: u32 = 1;
+
+/// The times measured for a phase, one per iteration.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PhaseTiming {
+    /// The time of each iteration, in the order they ran.
+    pub samples: Vec<Duration>,
+}
+
+impl PhaseTiming {
+    /// Returns the shortest time.
+    pub fn min(&self) -> Duration {
+        self.samples.iter().copied().min().unwrap_or_default()
+    }
+
+    /// Returns the longest time.
+    pub fn max(&self) -> Duration {
+        self.samples.iter().copied().max().unwrap_or_default()
+    }
+
+    /// Returns the median time, the mean of the two middle times for an even number of
+    /// samples. It is less sensitive to outliers than the mean, so regressions are best
+    /// tracked with it.
+    pub fn median(&self) -> Duration {
+        let mut samples = self.samples.clone();
+        samples.sort();
+        match samples.len() {
+            0 => Duration::ZERO,
+            len if len % 2 == 1 => samples[len / 2],
+            len => (samples[len / 2 - 1] + samples[len / 2]) / 2,
+        }
+    }
+
+    /// Returns the mean time.
+    pub fn mean(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    fn write_json(&self, json: &mut String) {
+        write!(
+            json,
+            "{{\"min_ns\":{},\"median_ns\":{},\"mean_ns\":{},\"max_ns\":{}}}",
+            self.min().as_nanos(),
+            self.median().as_nanos(),
+            self.mean().as_nanos(),
+            self.max().as_nanos()
+        )
+        .unwrap();
+    }
+}
+
+/// The performance of a circuit, as measured by [`benchmark_circuit`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BenchmarkReport {
+    /// The circuit has `2^k` rows.
+    pub k: u32,
+    /// The number of proofs created and verified.
+    pub iterations: usize,
+    /// The generation of the verifying key, which runs once.
+    pub keygen_vk: PhaseTiming,
+    /// The generation of the proving key, which runs once.
+    pub keygen_pk: PhaseTiming,
+    /// The compilation of the circuit and the generation of the witness, in all phases.
+    pub witness: PhaseTiming,
+    /// The rest of the creation of the proof: the commitments, the arguments, the
+    /// quotient polynomial and the multiopen argument.
+    pub proving: PhaseTiming,
+    /// The verification of the proof.
+    pub verification: PhaseTiming,
+    /// The size of the proof, in bytes.
+    pub proof_size: usize,
+    /// The peak resident set size of the process after the benchmark, in bytes, if the
+    /// platform reports it. It includes the memory used before the benchmark.
+    pub peak_rss: Option<u64>,
+}
+
+impl BenchmarkReport {
+    /// Returns the report as a single line of JSON, for CI to compare with the reports of
+    /// previous runs.
+    ///
+    /// The output is stable: fields are always in the same order, times are integers of
+    /// nanoseconds and `peak_rss` is `null` when it is unknown. The `version` field is
+    /// [`BENCHMARK_REPORT_VERSION`].
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\"version\":{},\"k\":{},\"iterations\":{},\"proof_size\":{},\"peak_rss\":",
+            BENCHMARK_REPORT_VERSION, self.k, self.iterations, self.proof_size
+        );
+        match self.peak_rss {
+            Some(peak_rss) => write!(json, "{peak_rss}").unwrap(),
+            None => json.push_str("null"),
+        }
+        json.push_str(",\"phases\":{");
+        for (i, (name, phase)) in self.phases().into_iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(json, "\"{name}\":").unwrap();
+            phase.write_json(&mut json);
+        }
+        json.push_str("}}");
+        json
+    }
+
+    /// Returns the phases with their names, in the order of the JSON output.
+    pub fn phases(&self) -> [(&'static str, &PhaseTiming); 5] {
+        [
+            ("keygen_vk", &self.keygen_vk),
+            ("keygen_pk", &self.keygen_pk),
+            ("witness", &self.witness),
+            ("proving", &self.proving),
+            ("verification", &self.verification),
+        ]
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "k: {}", self.k)?;
+        writeln!(f, "iterations: {}", self.iterations)?;
+        for (name, phase) in self.phases() {
+            writeln!(f, "{}: {:?} (median)", name, phase.median())?;
+        }
+        writeln!(f, "proof size: {} bytes", self.proof_size)?;
+        match self.peak_rss {
+            Some(peak_rss) => write!(f, "peak rss: {} bytes", peak_rss),
+            None => write!(f, "peak rss: unknown"),
+        }
+    }
+}
+
+/// Returns the peak resident set size of the process, in bytes, on platforms that report
+/// it.
+fn peak_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?;
+    let kib: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Measures the key generation, proving and verification of `circuit`, which has no
+/// instance columns, with `2^k` rows.
+///
+/// See [`benchmark_circuit_with_instances`].
+pub fn benchmark_circuit<C: Circuit<Fr>>(
+    circuit: &C,
+    k: u32,
+    iterations: usize,
+) -> Result<BenchmarkReport, Error> {
+    benchmark_circuit_with_instances(circuit, k, &[], iterations)
+}
+
+/// Measures the key generation, proving and verification of `circuit` with the values of
+/// its instance columns `instances` and `2^k` rows.
+///
+/// The circuit is proven with KZG over BN254, SHPLONK and a BLAKE2b transcript, the
+/// usual configuration of on-chain verification. The keys are generated once, and the
+/// proof is created and verified `iterations` times. The parameters are generated before
+/// the measurements, from a random setup.
+///
+/// Returns an error if `iterations` is zero, or if the circuit can't be proven or its
+/// proof doesn't verify.
+///
+/// ```ignore
+/// let report = benchmark_circuit(&circuit, k, 10)?;
+/// println!("{}", report.to_json());
+/// ```
+pub fn benchmark_circuit_with_instances<C: Circuit<Fr>>(
+    circuit: &C,
+    k: u32,
+    instances: &[&[Fr]],
+    iterations: usize,
+) -> Result<BenchmarkReport, Error> {
+    if iterations == 0 {
+        return Err(Error::Other("at least one iteration is needed".to_string()));
+    }
+    let params = ParamsKZG::<Bn256>::setup(k, OsRng);
+    let mut report = BenchmarkReport {
+        k,
+        iterations,
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    let vk = keygen_vk(&params, circuit)?;
+    report.keygen_vk.samples.push(start.elapsed());
+    let start = Instant::now();
+    let pk = keygen_pk(&params, vk, circuit)?;
+    report.keygen_pk.samples.push(start.elapsed());
+
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let (_, config, cs) = compile_circuit(params.k(), circuit, pk.get_vk().compress_selectors)?;
+        let mut witness_calc = WitnessCalculator::new(params.k(), circuit, &config, &cs, instances);
+        let mut witness = start.elapsed();
+
+        let start = Instant::now();
+        let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+        create_proof_v2_with_queries::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<_>, _, _, _, _>(
+            &params,
+            &pk,
+            &[instances],
+            |phase, challenges| {
+                let start = Instant::now();
+                let witnesses = witness_calc.calc(phase, challenges).map(|w| vec![w]);
+                witness += start.elapsed();
+                witnesses
+            },
+            &[],
+            OsRng,
+            &mut transcript,
+        )?;
+        let proof = transcript.finalize();
+        report
+            .proving
+            .samples
+            .push(start.elapsed().saturating_sub(witness));
+        report.witness.samples.push(witness);
+        report.proof_size = proof.len();
+
+        let start = Instant::now();
+        let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&proof[..]);
+        verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<_>, _, _, _>(
+            params.verifier_params(),
+            pk.get_vk(),
+            SingleStrategy::new(params.verifier_params()),
+            &[instances],
+            &mut transcript,
+        )?;
+        report.verification.samples.push(start.elapsed());
+    }
+
+    report.peak_rss = peak_rss();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{Layouter, SimpleFloorPlanner, Value};
+    use crate::plonk::{Advice, Column, ConstraintSystem, Instance, Selector};
+    use crate::poly::Rotation;
+    use halo2_middleware::ff::Field;
+
+    #[derive(Clone, Default)]
+    struct DoubleCircuit;
+
+    impl Circuit<Fr> for DoubleCircuit {
+        type Config = (Selector, Column<Advice>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let selector = meta.selector();
+            let advice = meta.advice_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(advice);
+            meta.enable_equality(instance);
+            meta.create_gate("double", |meta| {
+                let selector = meta.query_selector(selector);
+                let cur = meta.query_advice(advice, Rotation::cur());
+                let next = meta.query_advice(advice, Rotation::next());
+                vec![selector * (next - cur.clone() - cur)]
+            });
+            (selector, advice, instance)
+        }
+
+        fn synthesize(
+            &self,
+            (selector, advice, instance): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let last = layouter.assign_region(
+                || "double",
+                |mut region| {
+                    let mut value = Fr::ONE;
+                    selector.enable(&mut region, 0)?;
+                    region.assign_advice(|| "a", advice, 0, || Value::known(value))?;
+                    value = value.double();
+                    region.assign_advice(|| "b", advice, 1, || Value::known(value))
+                },
+            )?;
+            layouter.constrain_instance(last.cell(), instance, 0)
+        }
+    }
+
+    #[test]
+    fn benchmark() {
+        let report =
+            benchmark_circuit_with_instances(&DoubleCircuit, 4, &[&[Fr::from(2)]], 3).unwrap();
+        assert_eq!(report.iterations, 3);
+        for (_, phase) in &report.phases()[2..] {
+            assert_eq!(phase.samples.len(), 3);
+        }
+        assert_eq!(report.keygen_vk.samples.len(), 1);
+        assert!(report.proof_size > 0);
+
+        // The proof of another instance doesn't verify.
+        assert!(benchmark_circuit_with_instances(&DoubleCircuit, 4, &[&[Fr::from(3)]], 1).is_err());
+        assert!(benchmark_circuit_with_instances(&DoubleCircuit, 4, &[&[Fr::ONE]], 0).is_err());
+    }
+
+    #[test]
+    fn json() {
+        let timing = |nanos: &[u64]| PhaseTiming {
+            samples: nanos.iter().copied().map(Duration::from_nanos).collect(),
+        };
+        let report = BenchmarkReport {
+            k: 10,
+            iterations: 2,
+            keygen_vk: timing(&[5]),
+            keygen_pk: timing(&[7]),
+            witness: timing(&[3, 1]),
+            proving: timing(&[10, 20]),
+            verification: timing(&[4, 4]),
+            proof_size: 1024,
+            peak_rss: None,
+        };
+        assert_eq!(
+            report.to_json(),
+            "{\"version\":1,\"k\":10,\"iterations\":2,\"proof_size\":1024,\"peak_rss\":null,\
+             \"phases\":{\
+             \"keygen_vk\":{\"min_ns\":5,\"median_ns\":5,\"mean_ns\":5,\"max_ns\":5},\
+             \"keygen_pk\":{\"min_ns\":7,\"median_ns\":7,\"mean_ns\":7,\"max_ns\":7},\
+             \"witness\":{\"min_ns\":1,\"median_ns\":2,\"mean_ns\":2,\"max_ns\":3},\
+             \"proving\":{\"min_ns\":10,\"median_ns\":15,\"mean_ns\":15,\"max_ns\":20},\
+             \"verification\":{\"min_ns\":4,\"median_ns\":4,\"mean_ns\":4,\"max_ns\":4}}}"
+        );
+        let report = BenchmarkReport {
+            peak_rss: Some(4096),
+            ..report
+        };
+        assert!(report.to_json().contains("\"peak_rss\":4096,"));
+    }
+}
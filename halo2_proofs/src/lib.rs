@@ -11,26 +11,53 @@
 #![deny(missing_docs)]
 #![deny(unsafe_code)]
 
+#[cfg(feature = "kzg")]
+mod benchmark;
 pub mod plonk;
+pub mod scaffold;
 
 /// Traits and structs for implementing circuit components.
 pub mod circuit {
     pub use halo2_common::circuit::floor_planner;
+    #[cfg(feature = "gadgets")]
+    pub use halo2_common::circuit::{instance, packed_table};
     pub use halo2_common::circuit::{
         AssignedCell, Cell, Chip, Layouter, Region, SimpleFloorPlanner, Value,
     };
+    pub use halo2_frontend::circuit::{CompiledConstraintSystem, WithFloorPlanner};
 }
 /// This module provides common utilities, traits and structures for group,
 /// field and polynomial arithmetic.
 pub mod arithmetic {
-    pub use halo2_backend::arithmetic::{parallelize, CurveAffine, CurveExt, Field};
+    pub use halo2_backend::arithmetic::{parallelize, sort_by_repr, CurveAffine, CurveExt, Field};
+}
+/// Encoding of proofs and instances as the calldata of on-chain verifiers.
+pub mod calldata {
+    pub use halo2_backend::calldata::{
+        function_selector, Calldata, VERIFY_PROOF_SIGNATURE, VERIFY_PROOF_WITH_VK_SIGNATURE,
+    };
 }
 /// Tools for developing circuits.
 pub mod dev {
-    pub use halo2_frontend::dev::{metadata, FailureLocation, MockProver, VerifyFailure};
+    pub use halo2_backend::dev::{arithmetic, blinding, diff_transcripts, tamper, transcript_log};
+    pub use halo2_frontend::dev::{
+        column_merge, gate_coverage, metadata, selector_map, shadow_planners, time_synthesis,
+        EqualityColumn, EqualityReport, FailureLocation, FastFailure, GateActivity, GateCoverage,
+        LookupReport, LookupStats, MockProver, PlacedCell, PlannerMismatch, RegionTiming,
+        SelectorMap, ShadowReport, SynthesisTimings, VerifyFailure,
+    };
 
+    #[cfg(feature = "kzg")]
+    pub use crate::benchmark::{
+        benchmark_circuit, benchmark_circuit_with_instances, BenchmarkReport, PhaseTiming,
+        BENCHMARK_REPORT_VERSION,
+    };
+    #[cfg(feature = "lookup-injection")]
+    pub use halo2_backend::dev::lookup_injection;
     #[cfg(feature = "cost-estimator")]
     pub use halo2_frontend::dev::cost_model;
+    #[cfg(feature = "cell-provenance")]
+    pub use halo2_frontend::dev::CellProvenance;
 
     #[cfg(feature = "dev-graph")]
     pub use halo2_frontend::dev::{circuit_dot_graph, CircuitLayout};
@@ -39,18 +66,34 @@ pub mod dev {
 /// various forms, including computing commitments to them and provably opening
 /// the committed polynomials at arbitrary points.
 pub mod poly {
+    pub use halo2_backend::poly::commitment;
+    pub use halo2_backend::poly::equivalence;
+    #[cfg(feature = "ipa")]
+    pub use halo2_backend::poly::ipa;
+    #[cfg(feature = "kzg")]
+    pub use halo2_backend::poly::kzg;
     pub use halo2_backend::poly::VerificationStrategy;
-    pub use halo2_backend::poly::{commitment, ipa, kzg};
     pub use halo2_middleware::poly::Rotation;
 }
 /// This module contains utilities and traits for dealing with Fiat-Shamir
 /// transcripts.
 pub mod transcript {
     pub use halo2_backend::transcript::{
-        Blake2bRead, Blake2bWrite, Challenge255, EncodedChallenge, TranscriptReadBuffer,
-        TranscriptWriterBuffer,
+        absorb_domain, read_proof_envelope, read_proof_envelope_with_encoding,
+        write_proof_envelope, write_proof_envelope_with_encoding, Blake2bRead, Blake2bWrite,
+        Challenge255, EncodedChallenge, ProofEncoding, Transcript, TranscriptPhase, TranscriptRead,
+        TranscriptReadBuffer, TranscriptWrite, TranscriptWriterBuffer,
     };
 }
+/// Pluggable engines computing the multiscalar multiplications and FFTs of the prover and
+/// of key generation.
+pub mod zal {
+    pub use halo2_backend::zal::{traits, H2cEngine, PlonkEngine, PlonkEngineConfig};
+}
+/// A serialization format for circuit witnesses.
+pub mod witness {
+    pub use halo2_common::witness::{read_witness, write_witness, Witness, WitnessRegion};
+}
 mod helpers {
     pub use halo2_common::helpers::SerdeFormat;
 }
@@ -6,20 +6,55 @@
 //! [plonk]: https://eprint.iacr.org/2019/953
 
 mod keygen;
+mod pool;
 mod prover;
 mod verifier {
-    pub use halo2_backend::plonk::verifier::verify_proof;
+    #[cfg(feature = "ipa")]
+    pub use halo2_backend::plonk::verifier::cycle;
+    #[cfg(feature = "kzg")]
+    pub use halo2_backend::plonk::verifier::recursion;
+    #[cfg(feature = "batch")]
+    pub use halo2_backend::plonk::verifier::BatchVerifier;
+    pub use halo2_backend::plonk::verifier::{
+        verify_arbitrary_bytes, verify_proof, verify_split_proof, FuzzInput, InstanceCache,
+        Opening, VerifierBuilder, VerifierMetrics, VerifierTrace,
+    };
 }
 
-pub use keygen::{keygen_pk, keygen_vk};
+pub use keygen::{
+    keygen_pk, keygen_pk_cached, keygen_pk_from_compiled, keygen_pk_multi_k, keygen_pk_with_engine,
+    keygen_pk_with_limits, keygen_pk_with_planner, keygen_vk, keygen_vk_cached,
+    keygen_vk_with_engine, keygen_vk_with_limits, keygen_vk_with_planner,
+};
 
-pub use prover::create_proof;
-pub use verifier::verify_proof;
+pub use pool::{ProofQueue, ProverPool};
+pub use prover::{create_proof, ProofBuilder};
+#[cfg(feature = "ipa")]
+pub use verifier::cycle;
+#[cfg(feature = "kzg")]
+pub use verifier::recursion;
+#[cfg(feature = "batch")]
+pub use verifier::BatchVerifier;
+pub use verifier::{
+    verify_arbitrary_bytes, verify_proof, verify_split_proof, FuzzInput, InstanceCache, Opening,
+    VerifierBuilder, VerifierMetrics, VerifierTrace,
+};
 
-pub use halo2_backend::plonk::{ProvingKey, VerifyingKey};
+#[cfg(feature = "jit")]
+pub use halo2_backend::plonk::GateEvaluation;
+#[cfg(feature = "vanishing-pieces")]
+pub use halo2_backend::plonk::VanishingPieces;
+pub use halo2_backend::plonk::{
+    verify_evaluation_proof, verify_proof_section, EvaluationProof, ExtraQuery, ProofSection,
+    ProofSections, ProverLimits, ProvingKey, QueryGroup, QueryOrder, VerifyingKey,
+    PROTOCOL_VERSION,
+};
+pub use halo2_common::circuit_config;
 pub use halo2_common::plonk::{
     circuit::{Challenge, Column},
-    Assigned, Circuit, ConstraintSystem, Error, Expression, FirstPhase, SecondPhase, Selector,
-    TableColumn, ThirdPhase,
+    Assigned, Circuit, ConstraintSystem, Error, Expression, FirstPhase, GateBuilder, NamePolicy,
+    SecondPhase, Selector, TableColumn, ThirdPhase,
 };
-pub use halo2_middleware::circuit::{Advice, Fixed, Instance};
+pub use halo2_frontend::circuit::minimum_k;
+pub use halo2_middleware::circuit::{Advice, Any, Fixed, Instance, Subdomain};
+pub use halo2_middleware::metadata::ColumnId;
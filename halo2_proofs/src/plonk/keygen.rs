@@ -1,10 +1,14 @@
 use halo2_backend::plonk::{
-    keygen::{keygen_pk_v2, keygen_vk_v2},
-    ProvingKey, VerifyingKey,
+    keygen::{self as backend, keygen_pk_v2, keygen_vk_v2},
+    ProverLimits, ProvingKey, VerifyingKey,
 };
-use halo2_backend::{arithmetic::CurveAffine, poly::commitment::Params};
-use halo2_common::plonk::{circuit::Circuit, Error};
-use halo2_frontend::circuit::compile_circuit;
+use halo2_backend::{arithmetic::CurveAffine, poly::commitment::Params, zal::PlonkEngine};
+use halo2_common::plonk::{circuit::Circuit, Error, FloorPlanner};
+use halo2_frontend::circuit::{
+    compile_circuit, compile_circuit_cached, compile_circuit_multi_k, CompiledConstraintSystem,
+    WithFloorPlanner,
+};
+use halo2_middleware::circuit::CompiledCircuitV2;
 use halo2_middleware::ff::FromUniformBytes;
 
 /// Generate a `VerifyingKey` from an instance of `Circuit`.
@@ -19,7 +23,7 @@ where
     ConcreteCircuit: Circuit<C::Scalar>,
     C::Scalar: FromUniformBytes<64>,
 {
-    keygen_vk_custom(params, circuit, true)
+    keygen_vk_with_engine(&PlonkEngine::default(), params, circuit)
 }
 
 /// Generate a `VerifyingKey` from an instance of `Circuit`.
@@ -53,6 +57,306 @@ where
     P: Params<'params, C>,
     ConcreteCircuit: Circuit<C::Scalar>,
 {
-    let (compiled_circuit, _, _) = compile_circuit(params.k(), circuit, vk.compress_selectors)?;
+    keygen_pk_with_engine(&PlonkEngine::default(), params, vk, circuit)
+}
+
+/// Generate a `ProvingKey` from a circuit compiled with [`compile_circuit`], without
+/// synthesizing the circuit again to collect its fixed columns. Its `VerifyingKey` is
+/// generated from the same compiled circuit.
+///
+/// The compiled circuit doesn't record whether its selectors were compressed, which the
+/// key records so that [`create_proof`] compiles the circuit in the same way:
+/// `compress_selectors` must be the value the circuit was compiled with.
+///
+/// [`compile_circuit`]: halo2_frontend::circuit::compile_circuit
+/// [`create_proof`]: crate::plonk::create_proof
+pub fn keygen_pk_from_compiled<'params, C, P>(
+    params: &P,
+    compiled: &CompiledCircuitV2<C::Scalar>,
+    compress_selectors: bool,
+) -> Result<ProvingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    C::Scalar: FromUniformBytes<64>,
+{
+    let mut vk = keygen_vk_v2(params, compiled)?;
+    vk.compress_selectors = compress_selectors;
+    keygen_pk_v2(params, vk, compiled)
+}
+
+/// Generate a `VerifyingKey` from an instance of `Circuit`, using the result of `configure`
+/// cached in `compiled_cs` instead of running it again.
+///
+/// The selector compression optimization is turned on only if `compress_selectors` is `true`.
+pub fn keygen_vk_cached<'params, C, P, ConcreteCircuit>(
+    params: &P,
+    circuit: &ConcreteCircuit,
+    compiled_cs: &CompiledConstraintSystem<C::Scalar, ConcreteCircuit>,
+    compress_selectors: bool,
+) -> Result<VerifyingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    ConcreteCircuit: Circuit<C::Scalar>,
+    C::Scalar: FromUniformBytes<64>,
+{
+    let (compiled_circuit, _, _) =
+        compile_circuit_cached(params.k(), circuit, compiled_cs, compress_selectors)?;
+    let mut vk = keygen_vk_v2(params, &compiled_circuit)?;
+    vk.compress_selectors = compress_selectors;
+    Ok(vk)
+}
+
+/// Generate a `ProvingKey` from a `VerifyingKey` and an instance of `Circuit`, using the
+/// result of `configure` cached in `compiled_cs` instead of running it again.
+pub fn keygen_pk_cached<'params, C, P, ConcreteCircuit>(
+    params: &P,
+    vk: VerifyingKey<C>,
+    circuit: &ConcreteCircuit,
+    compiled_cs: &CompiledConstraintSystem<C::Scalar, ConcreteCircuit>,
+) -> Result<ProvingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    ConcreteCircuit: Circuit<C::Scalar>,
+{
+    let (compiled_circuit, _, _) =
+        compile_circuit_cached(params.k(), circuit, compiled_cs, vk.compress_selectors)?;
     keygen_pk_v2(params, vk, &compiled_circuit)
 }
+
+/// Generate a `ProvingKey` of a circuit for each of `params`, which usually have
+/// different `k`s, using the result of `configure` cached in `compiled_cs`.
+///
+/// The circuit is synthesized, and its selectors compressed, only once, and only the
+/// polynomials and commitments which depend on the size of the circuit are computed for
+/// each `k`, see [`compile_circuit_multi_k`]. The keys are those generated by
+/// [`keygen_vk_cached`] and [`keygen_pk_cached`] for each of `params`.
+///
+/// [`compile_circuit_multi_k`]: halo2_frontend::circuit::compile_circuit_multi_k
+pub fn keygen_pk_multi_k<'params, C, P, ConcreteCircuit>(
+    params: &[&P],
+    circuit: &ConcreteCircuit,
+    compiled_cs: &CompiledConstraintSystem<C::Scalar, ConcreteCircuit>,
+    compress_selectors: bool,
+) -> Result<Vec<ProvingKey<C>>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    ConcreteCircuit: Circuit<C::Scalar>,
+    C::Scalar: FromUniformBytes<64>,
+{
+    let ks: Vec<_> = params.iter().map(|params| params.k()).collect();
+    let compiled = compile_circuit_multi_k(&ks, circuit, compiled_cs, compress_selectors)?;
+    params
+        .iter()
+        .zip(&compiled)
+        .map(|(params, compiled)| keygen_pk_from_compiled(*params, compiled, compress_selectors))
+        .collect()
+}
+
+/// Generate a `VerifyingKey` from an instance of `Circuit`, like [`keygen_vk`], laying the
+/// circuit out with the floor planner `FP` instead of `ConcreteCircuit::FloorPlanner`.
+///
+/// Proving keys and proofs for this key must use the same floor planner, see
+/// [`keygen_pk_with_planner`] and [`ProofBuilder::with_planner`].
+///
+/// [`ProofBuilder::with_planner`]: crate::plonk::ProofBuilder::with_planner
+pub fn keygen_vk_with_planner<'params, FP, C, P, ConcreteCircuit>(
+    params: &P,
+    circuit: &ConcreteCircuit,
+) -> Result<VerifyingKey<C>, Error>
+where
+    FP: FloorPlanner,
+    C: CurveAffine,
+    P: Params<'params, C>,
+    ConcreteCircuit: Circuit<C::Scalar>,
+    C::Scalar: FromUniformBytes<64>,
+{
+    keygen_vk(params, &WithFloorPlanner::<_, FP>::new(circuit))
+}
+
+/// Generate a `ProvingKey` from a `VerifyingKey` and an instance of `Circuit`, laying the
+/// circuit out with the floor planner `FP` instead of `ConcreteCircuit::FloorPlanner`.
+pub fn keygen_pk_with_planner<'params, FP, C, P, ConcreteCircuit>(
+    params: &P,
+    vk: VerifyingKey<C>,
+    circuit: &ConcreteCircuit,
+) -> Result<ProvingKey<C>, Error>
+where
+    FP: FloorPlanner,
+    C: CurveAffine,
+    P: Params<'params, C>,
+    ConcreteCircuit: Circuit<C::Scalar>,
+{
+    keygen_pk(params, vk, &WithFloorPlanner::<_, FP>::new(circuit))
+}
+
+/// Generate a `VerifyingKey` from an instance of `Circuit`, like [`keygen_vk`], after
+/// checking that the circuit is within `limits`.
+///
+/// [`ProverLimits::max_k`] is checked before the circuit is compiled, and the other limits
+/// before the fixed columns are committed.
+pub fn keygen_vk_with_limits<'params, C, P, ConcreteCircuit>(
+    params: &P,
+    circuit: &ConcreteCircuit,
+    limits: &ProverLimits,
+) -> Result<VerifyingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    ConcreteCircuit: Circuit<C::Scalar>,
+    C::Scalar: FromUniformBytes<64>,
+{
+    limits.check_k(params.k())?;
+    let (compiled_circuit, _, _) = compile_circuit(params.k(), circuit, true)?;
+    let mut vk = backend::keygen_vk_with_limits(params, &compiled_circuit, limits)?;
+    vk.compress_selectors = true;
+    Ok(vk)
+}
+
+/// Generate a `ProvingKey` from a `VerifyingKey` and an instance of `Circuit`, like
+/// [`keygen_pk`], after checking that the circuit is within `limits`.
+pub fn keygen_pk_with_limits<'params, C, P, ConcreteCircuit>(
+    params: &P,
+    vk: VerifyingKey<C>,
+    circuit: &ConcreteCircuit,
+    limits: &ProverLimits,
+) -> Result<ProvingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    ConcreteCircuit: Circuit<C::Scalar>,
+{
+    limits.check(params.k(), vk.cs(), 1)?;
+    let (compiled_circuit, _, _) = compile_circuit(params.k(), circuit, vk.compress_selectors)?;
+    backend::keygen_pk_with_limits(params, vk, &compiled_circuit, limits)
+}
+
+/// Generate a `VerifyingKey` from an instance of `Circuit`, like [`keygen_vk`], computing
+/// the multiscalar multiplications and FFTs with `engine`.
+pub fn keygen_vk_with_engine<'params, C, P, ConcreteCircuit>(
+    engine: &PlonkEngine<C>,
+    params: &P,
+    circuit: &ConcreteCircuit,
+) -> Result<VerifyingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    ConcreteCircuit: Circuit<C::Scalar>,
+    C::Scalar: FromUniformBytes<64>,
+{
+    let (compiled_circuit, _, _) = compile_circuit(params.k(), circuit, true)?;
+    let mut vk = backend::keygen_vk_with_engine(engine, params, &compiled_circuit)?;
+    vk.compress_selectors = true;
+    Ok(vk)
+}
+
+/// Generate a `ProvingKey` from a `VerifyingKey` and an instance of `Circuit`, like
+/// [`keygen_pk`], computing the multiscalar multiplications and FFTs with `engine`.
+pub fn keygen_pk_with_engine<'params, C, P, ConcreteCircuit>(
+    engine: &PlonkEngine<C>,
+    params: &P,
+    vk: VerifyingKey<C>,
+    circuit: &ConcreteCircuit,
+) -> Result<ProvingKey<C>, Error>
+where
+    C: CurveAffine,
+    P: Params<'params, C>,
+    ConcreteCircuit: Circuit<C::Scalar>,
+{
+    let (compiled_circuit, _, _) = compile_circuit(params.k(), circuit, vk.compress_selectors)?;
+    backend::keygen_pk_with_engine(engine, params, vk, &compiled_circuit)
+}
+
+#[cfg(feature = "ipa")]
+#[test]
+fn test_keygen_pk_multi_k() {
+    use crate::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        plonk::{Advice, Column, ConstraintSystem, Fixed, Selector},
+        poly::{commitment::ParamsProver, ipa::commitment::ParamsIPA, Rotation},
+        SerdeFormat,
+    };
+    use halo2curves::pasta::{EqAffine, Fp};
+
+    /// Squares a fixed constant and doubles it twice in an advice column with two
+    /// selectors, so that the keys have fixed columns, compressed selectors and a
+    /// permutation. The copy constraint only needs to hold in the keys.
+    #[derive(Clone)]
+    struct MyCircuit;
+
+    impl Circuit<Fp> for MyCircuit {
+        type Config = (Column<Advice>, Column<Fixed>, Selector, Selector);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let a = meta.advice_column();
+            let f = meta.fixed_column();
+            let double = meta.selector();
+            let square = meta.selector();
+            meta.enable_equality(a);
+            meta.create_gate("double", |meta| {
+                let s = meta.query_selector(double);
+                let cur = meta.query_advice(a, Rotation::cur());
+                let next = meta.query_advice(a, Rotation::next());
+                vec![s * (cur.clone() + cur - next)]
+            });
+            meta.create_gate("square", |meta| {
+                let s = meta.query_selector(square);
+                let cur = meta.query_advice(a, Rotation::cur());
+                let f = meta.query_fixed(f, Rotation::cur());
+                vec![s * (cur - f.clone() * f)]
+            });
+            (a, f, double, square)
+        }
+
+        fn synthesize(
+            &self,
+            (a, f, double, square): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "rows",
+                |mut region| {
+                    region.assign_fixed(|| "f", f, 0, || Value::known(Fp::from(3)))?;
+                    square.enable(&mut region, 0)?;
+                    double.enable(&mut region, 0)?;
+                    double.enable(&mut region, 1)?;
+                    let first = region.assign_advice(|| "a", a, 0, || Value::known(Fp::from(9)))?;
+                    region.assign_advice(|| "a", a, 1, || Value::known(Fp::from(18)))?;
+                    let last = region.assign_advice(|| "a", a, 2, || Value::known(Fp::from(36)))?;
+                    region.constrain_equal(first.cell(), last.cell())
+                },
+            )
+        }
+    }
+
+    let ks = [5, 4, 6];
+    let params: Vec<ParamsIPA<EqAffine>> = ks.into_iter().map(ParamsIPA::new).collect();
+    let params: Vec<_> = params.iter().collect();
+    let compiled_cs = CompiledConstraintSystem::new(&MyCircuit);
+    let pks = keygen_pk_multi_k(&params, &MyCircuit, &compiled_cs, true).unwrap();
+    assert_eq!(pks.len(), 3);
+    for ((params, pk), k) in params.iter().zip(&pks).zip(ks) {
+        let vk = keygen_vk(*params, &MyCircuit).unwrap();
+        assert_eq!(pk.get_vk().get_domain().k(), k);
+        assert_eq!(pk.get_vk().transcript_repr(), vk.transcript_repr());
+        let expected = keygen_pk(*params, vk, &MyCircuit).unwrap();
+        assert_eq!(
+            pk.to_bytes(SerdeFormat::RawBytes),
+            expected.to_bytes(SerdeFormat::RawBytes)
+        );
+    }
+
+    // The circuit must fit in the smallest `k`.
+    let small = ParamsIPA::<EqAffine>::new(2);
+    assert!(keygen_pk_multi_k(&[&small, params[0]], &MyCircuit, &compiled_cs, true).is_err());
+}
@@ -0,0 +1,240 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::plonk::create_proof;
+use crate::poly::commitment::{CommitmentScheme, Params, Prover};
+use halo2_backend::plonk::{ProverLimits, ProvingKey};
+use halo2_backend::transcript::{EncodedChallenge, TranscriptWriterBuffer};
+use halo2_common::plonk::{circuit::Circuit, Error};
+use halo2_middleware::ff::{FromUniformBytes, WithSmallOrderMulGroup};
+use rand_core::OsRng;
+
+/// A proof job: the circuit, its instances, and where to send its proof.
+type Job<ConcreteCircuit, F> = (ConcreteCircuit, Vec<Vec<F>>, Sender<Result<Vec<u8>, Error>>);
+
+/// Proves several witnesses of the same circuit concurrently, with the same parameters
+/// and proving key.
+///
+/// Each worker creates one proof at a time, with [`create_proof`], so the number of
+/// workers bounds the number of proofs whose polynomials are allocated at once, and in
+/// particular of their extended domain polynomials, which dominate the memory of the
+/// prover. [`ProverPool::with_memory_budget`] derives it from a memory budget.
+///
+/// Proofs are submitted to the [`ProofQueue`] of [`ProverPool::run`], which returns a
+/// channel for each of them.
+#[derive(Debug)]
+pub struct ProverPool<'params, Scheme: CommitmentScheme> {
+    params: &'params Scheme::ParamsProver,
+    pk: &'params ProvingKey<Scheme::Curve>,
+    num_workers: usize,
+}
+
+impl<'params, Scheme: CommitmentScheme> ProverPool<'params, Scheme>
+where
+    Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    /// Creates a pool of `num_workers` workers, at least one, proving with `params` and
+    /// `pk`.
+    pub fn new(
+        params: &'params Scheme::ParamsProver,
+        pk: &'params ProvingKey<Scheme::Curve>,
+        num_workers: usize,
+    ) -> Self {
+        ProverPool {
+            params,
+            pk,
+            num_workers: num_workers.max(1),
+        }
+    }
+
+    /// Limits the number of workers so that the proofs being created at once are
+    /// estimated to use at most `bytes` of memory, with at least one worker.
+    ///
+    /// Each proof is estimated with [`ProverLimits::memory_estimate`], which counts the
+    /// polynomials of the proving key for each proof although they are shared, so the
+    /// limit is conservative.
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self {
+        let estimate =
+            ProverLimits::memory_estimate(self.params.k(), self.pk.get_vk().cs(), 1).max(1);
+        self.num_workers = self.num_workers.min(bytes / estimate).max(1);
+        self
+    }
+
+    /// Returns the number of workers of the pool.
+    pub fn num_workers(&self) -> usize {
+        self.num_workers
+    }
+
+    /// Starts the workers, and calls `f` with the queue the proofs are submitted to.
+    ///
+    /// The proofs are created with the prover `P`, in transcripts of type `T` with
+    /// randomness from [`OsRng`]. Once `f` returns, the workers finish the proofs already
+    /// submitted and exit, and its result is returned.
+    pub fn run<P, E, T, ConcreteCircuit, Output>(
+        &self,
+        f: impl FnOnce(&ProofQueue<ConcreteCircuit, Scheme::Scalar>) -> Output,
+    ) -> Output
+    where
+        P: Prover<'params, Scheme>,
+        E: EncodedChallenge<Scheme::Curve>,
+        T: TranscriptWriterBuffer<Vec<u8>, Scheme::Curve, E>,
+        ConcreteCircuit: Circuit<Scheme::Scalar> + Send,
+    {
+        let (jobs, receiver) = channel::<Job<ConcreteCircuit, Scheme::Scalar>>();
+        let receiver = Mutex::new(receiver);
+        thread::scope(|scope| {
+            for _ in 0..self.num_workers {
+                scope.spawn(|| loop {
+                    // The lock is released before proving, so that the other workers can
+                    // take the next jobs.
+                    let job = receiver.lock().unwrap().recv();
+                    let Ok((circuit, instances, proof)) = job else {
+                        break;
+                    };
+                    let instances: Vec<_> = instances.iter().map(|column| &column[..]).collect();
+                    let mut transcript = T::init(vec![]);
+                    let result = create_proof::<Scheme, P, _, _, _, _>(
+                        self.params,
+                        self.pk,
+                        &[circuit],
+                        &[&instances[..]],
+                        OsRng,
+                        &mut transcript,
+                    )
+                    .map(|_| transcript.finalize());
+                    // The submitter may have dropped the receiver of the proof.
+                    let _ = proof.send(result);
+                });
+            }
+            f(&ProofQueue { jobs })
+        })
+    }
+}
+
+/// The queue of the proofs of a [`ProverPool`].
+#[derive(Debug)]
+pub struct ProofQueue<ConcreteCircuit, F> {
+    jobs: Sender<Job<ConcreteCircuit, F>>,
+}
+
+impl<ConcreteCircuit, F> ProofQueue<ConcreteCircuit, F> {
+    /// Submits a proof of `circuit` with the values of its instance columns, and returns
+    /// the channel on which the proof is sent once it is created.
+    pub fn submit(
+        &self,
+        circuit: ConcreteCircuit,
+        instances: Vec<Vec<F>>,
+    ) -> Receiver<Result<Vec<u8>, Error>> {
+        let (proof, receiver) = channel();
+        self.jobs
+            .send((circuit, instances, proof))
+            .expect("the workers run as long as the queue");
+        receiver
+    }
+}
+
+#[cfg(feature = "kzg")]
+#[test]
+fn test_prover_pool() {
+    use crate::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        plonk::{keygen_pk, keygen_vk, verify_proof, Advice, Column, ConstraintSystem, Instance},
+        poly::{
+            commitment::ParamsProver,
+            kzg::{
+                commitment::{KZGCommitmentScheme, ParamsKZG},
+                multiopen::{ProverSHPLONK, VerifierSHPLONK},
+                strategy::SingleStrategy,
+            },
+        },
+        transcript::{Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer},
+    };
+    use halo2curves::bn256::{Bn256, Fr, G1Affine};
+
+    /// Copies its value to an instance cell.
+    #[derive(Clone)]
+    struct MyCircuit {
+        value: Value<Fr>,
+    }
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = (Column<Advice>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            MyCircuit {
+                value: Value::unknown(),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let a = meta.advice_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(a);
+            meta.enable_equality(instance);
+            (a, instance)
+        }
+
+        fn synthesize(
+            &self,
+            (a, instance): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let cell = layouter.assign_region(
+                || "a",
+                |mut region| region.assign_advice(|| "a", a, 0, || self.value),
+            )?;
+            layouter.constrain_instance(cell.cell(), instance, 0)
+        }
+    }
+
+    let params = ParamsKZG::<Bn256>::new(4);
+    let empty_circuit = MyCircuit {
+        value: Value::unknown(),
+    };
+    let vk = keygen_vk(&params, &empty_circuit).unwrap();
+    let pk = keygen_pk(&params, vk, &empty_circuit).unwrap();
+
+    let pool = ProverPool::<KZGCommitmentScheme<_>>::new(&params, &pk, 2);
+    assert_eq!(pool.num_workers(), 2);
+    assert_eq!(
+        ProverPool::<KZGCommitmentScheme<_>>::new(&params, &pk, 2)
+            .with_memory_budget(0)
+            .num_workers(),
+        1
+    );
+
+    let values: Vec<_> = (1..=3).map(Fr::from).collect();
+    let (proofs, invalid) = pool
+        .run::<ProverSHPLONK<_>, _, Blake2bWrite<_, G1Affine, Challenge255<_>>, _, _>(|queue| {
+            let proofs: Vec<_> = values
+                .iter()
+                .map(|value| {
+                    let circuit = MyCircuit {
+                        value: Value::known(*value),
+                    };
+                    queue.submit(circuit, vec![vec![*value]])
+                })
+                .collect();
+            // A proof without its instance column fails alone.
+            let invalid = queue.submit(empty_circuit.clone(), vec![]);
+            (proofs, invalid)
+        });
+
+    assert!(invalid.recv().unwrap().is_err());
+    for (proof, value) in proofs.iter().zip(&values) {
+        let proof = proof.recv().unwrap().unwrap();
+        let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&proof[..]);
+        assert!(verify_proof::<_, VerifierSHPLONK<_>, _, _, _>(
+            params.verifier_params(),
+            pk.get_vk(),
+            SingleStrategy::new(params.verifier_params()),
+            &[&[&[*value]]],
+            &mut transcript,
+        )
+        .is_ok());
+    }
+}
@@ -1,16 +1,29 @@
 use crate::poly::commitment::{CommitmentScheme, Params, Prover};
-use halo2_backend::plonk::{prover::ProverV2, ProvingKey};
-use halo2_backend::transcript::{EncodedChallenge, TranscriptWrite};
-use halo2_common::plonk::{circuit::Circuit, Error};
-use halo2_frontend::circuit::{compile_circuit, WitnessCalculator};
-use halo2_middleware::ff::{FromUniformBytes, WithSmallOrderMulGroup};
+#[cfg(feature = "vanishing-pieces")]
+use halo2_backend::plonk::VanishingPieces;
+use halo2_backend::plonk::{
+    prover::ProverV2, EvaluationProof, ExtraQuery, ProofSections, ProverLimits, ProvingKey,
+    QueryOrder,
+};
+use halo2_backend::transcript::{
+    absorb_domain, EncodedChallenge, TranscriptWrite, TranscriptWriterBuffer,
+};
+use halo2_backend::zal::PlonkEngine;
+use halo2_common::plonk::{circuit::Circuit, Error, FloorPlanner};
+use halo2_common::witness::Witness;
+use halo2_frontend::circuit::{compile_circuit, WithFloorPlanner, WitnessCalculator};
+use halo2_middleware::ff::{Field, FromUniformBytes, WithSmallOrderMulGroup};
 use rand_core::RngCore;
 use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
 
 /// This creates a proof for the provided `circuit` when given the public
 /// parameters `params` and the proving key [`ProvingKey`] that was
 /// generated previously for the same circuit. The provided `instances`
 /// are zero-padded internally.
+///
+/// Proofs with other options are created with a [`ProofBuilder`].
 pub fn create_proof<
     'params,
     Scheme: CommitmentScheme,
@@ -30,29 +43,498 @@ pub fn create_proof<
 where
     Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
 {
-    if circuits.len() != instances.len() {
-        return Err(Error::InvalidInstances);
-    }
-    let (_, config, cs) =
-        compile_circuit(params.k(), &circuits[0], pk.get_vk().compress_selectors)?;
-    let mut witness_calcs: Vec<_> = circuits
-        .iter()
-        .enumerate()
-        .map(|(i, circuit)| WitnessCalculator::new(params.k(), circuit, &config, &cs, instances[i]))
-        .collect();
-    let mut prover = ProverV2::<Scheme, P, _, _, _>::new(params, pk, instances, rng, transcript)?;
-    let mut challenges = HashMap::new();
-    let phases = prover.phases().to_vec();
-    for phase in phases.iter() {
-        let mut witnesses = Vec::with_capacity(circuits.len());
-        for witness_calc in witness_calcs.iter_mut() {
-            witnesses.push(witness_calc.calc(phase.0, &challenges)?);
-        }
-        challenges = prover.commit_phase(phase.0, witnesses).unwrap();
-    }
-    prover.create_proof()
+    ProofBuilder::<Scheme, P, _>::new(params, pk, circuits, instances)
+        .create(rng, transcript)
+        .map(|_| ())
 }
 
+/// The builder of a proof like the one of [`create_proof`], with options.
+///
+/// The options are set with the `with_*` methods, then the proof is written to the
+/// transcript by one of the `create*` methods:
+///
+/// ```ignore
+/// let evals = ProofBuilder::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _>::new(
+///     &params,
+///     &pk,
+///     &[circuit],
+///     &[&[]],
+/// )
+/// .with_queries(&queries)
+/// .create(OsRng, &mut transcript)?;
+/// ```
+///
+/// The proof is verified by a [`VerifierBuilder`] with the matching options.
+///
+/// [`VerifierBuilder`]: crate::plonk::VerifierBuilder
+pub struct ProofBuilder<
+    'a,
+    'params,
+    Scheme: CommitmentScheme,
+    P: Prover<'params, Scheme>,
+    ConcreteCircuit: Circuit<Scheme::Scalar>,
+> {
+    params: &'params Scheme::ParamsProver,
+    pk: &'a ProvingKey<Scheme::Curve>,
+    inputs: Inputs<'a, Scheme::Scalar, ConcreteCircuit>,
+    instances: &'a [&'a [&'a [Scheme::Scalar]]],
+    engine: PlonkEngine<Scheme::Curve>,
+    queries: &'a [ExtraQuery<Scheme::Scalar>],
+    query_order: QueryOrder,
+    domain: Option<&'a [u8]>,
+    limits: Option<ProverLimits>,
+    check_assignments: bool,
+    _marker: PhantomData<P>,
+}
+
+/// The inputs the advice columns of a proof are computed from.
+enum Inputs<'a, F, C> {
+    /// Circuits, synthesized when the proof is created.
+    Circuits(&'a [C]),
+    /// Circuits wrapped by [`ProofBuilder::with_planner`].
+    Owned(Vec<C>),
+    /// Precomputed witnesses, see [`ProofBuilder::from_witnesses`].
+    Witnesses(&'a [Witness<F>]),
+}
+
+impl<
+        'a,
+        'params,
+        Scheme: CommitmentScheme,
+        P: Prover<'params, Scheme>,
+        ConcreteCircuit: Circuit<Scheme::Scalar>,
+    > fmt::Debug for ProofBuilder<'a, 'params, Scheme, P, ConcreteCircuit>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (circuits, witnesses) = match &self.inputs {
+            Inputs::Circuits(circuits) => (circuits.len(), 0),
+            Inputs::Owned(circuits) => (circuits.len(), 0),
+            Inputs::Witnesses(witnesses) => (0, witnesses.len()),
+        };
+        f.debug_struct("ProofBuilder")
+            .field("circuits", &circuits)
+            .field("witnesses", &witnesses)
+            .field("queries", &self.queries.len())
+            .field("query_order", &self.query_order)
+            .field("domain", &self.domain)
+            .field("limits", &self.limits)
+            .field("check_assignments", &self.check_assignments)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<
+        'a,
+        'params,
+        Scheme: CommitmentScheme,
+        P: Prover<'params, Scheme>,
+        ConcreteCircuit: Circuit<Scheme::Scalar>,
+    > ProofBuilder<'a, 'params, Scheme, P, ConcreteCircuit>
+where
+    Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    /// Starts a proof for the provided `circuits` when given the public parameters
+    /// `params` and the proving key [`ProvingKey`] that was generated previously for the
+    /// same circuit. The provided `instances` are zero-padded internally.
+    pub fn new(
+        params: &'params Scheme::ParamsProver,
+        pk: &'a ProvingKey<Scheme::Curve>,
+        circuits: &'a [ConcreteCircuit],
+        instances: &'a [&'a [&'a [Scheme::Scalar]]],
+    ) -> Self {
+        Self::with_inputs(params, pk, Inputs::Circuits(circuits), instances)
+    }
+
+    /// Starts a proof from precomputed witnesses, without synthesizing the circuit, when
+    /// given the public parameters `params` and the proving key [`ProvingKey`] that was
+    /// generated previously for `ConcreteCircuit`, the circuit the witnesses belong to.
+    /// The provided `instances` are zero-padded internally.
+    ///
+    /// The advice values of later phases depend on the challenges of the proof, so they
+    /// can't be precomputed: the proof fails for circuits that use more than one phase.
+    pub fn from_witnesses(
+        params: &'params Scheme::ParamsProver,
+        pk: &'a ProvingKey<Scheme::Curve>,
+        witnesses: &'a [Witness<Scheme::Scalar>],
+        instances: &'a [&'a [&'a [Scheme::Scalar]]],
+    ) -> Self {
+        Self::with_inputs(params, pk, Inputs::Witnesses(witnesses), instances)
+    }
+
+    fn with_inputs(
+        params: &'params Scheme::ParamsProver,
+        pk: &'a ProvingKey<Scheme::Curve>,
+        inputs: Inputs<'a, Scheme::Scalar, ConcreteCircuit>,
+        instances: &'a [&'a [&'a [Scheme::Scalar]]],
+    ) -> Self {
+        Self {
+            params,
+            pk,
+            inputs,
+            instances,
+            engine: PlonkEngine::default(),
+            queries: &[],
+            query_order: QueryOrder::default(),
+            domain: None,
+            limits: None,
+            check_assignments: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Computes the multiscalar multiplications and FFTs of the proof with `engine`, for
+    /// example to run them on an accelerator. The proof is the same as the one of the
+    /// default engine with the same randomness.
+    pub fn with_engine(mut self, engine: PlonkEngine<Scheme::Curve>) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Opens the columns of each circuit at the points of `queries` in addition to the
+    /// queries of the circuit. [`Self::create`] returns their evaluations, which the
+    /// verifier obtains from [`VerifierBuilder::verify_with_evaluations`].
+    ///
+    /// The other `create*` methods don't support extra queries, and return an error if
+    /// some are set.
+    ///
+    /// [`VerifierBuilder::verify_with_evaluations`]: crate::plonk::VerifierBuilder::verify_with_evaluations
+    pub fn with_queries(mut self, queries: &'a [ExtraQuery<Scheme::Scalar>]) -> Self {
+        self.queries = queries;
+        self
+    }
+
+    /// Passes the queries of the proof to the multiopen argument in `order`. The proof is
+    /// verified with [`VerifierBuilder::with_query_order`] and the same order.
+    ///
+    /// [`VerifierBuilder::with_query_order`]: crate::plonk::VerifierBuilder::with_query_order
+    pub fn with_query_order(mut self, order: QueryOrder) -> Self {
+        self.query_order = order;
+        self
+    }
+
+    /// Creates the proof for the application with the domain separation `label`, which is
+    /// absorbed into the transcript before anything else.
+    ///
+    /// Applications using identical circuits and parameters should use distinct labels, so
+    /// that the proofs of one can't be replayed to the other. The proof only verifies with
+    /// [`VerifierBuilder::with_domain`] and the same label.
+    ///
+    /// [`VerifierBuilder::with_domain`]: crate::plonk::VerifierBuilder::with_domain
+    pub fn with_domain(mut self, label: &'a [u8]) -> Self {
+        self.domain = Some(label);
+        self
+    }
+
+    /// Checks that the proof is within `limits` before the circuits are synthesized, so
+    /// that oversized proofs are rejected before their polynomials are allocated.
+    pub fn with_limits(mut self, limits: ProverLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Lays the circuits out with the floor planner `FP` instead of
+    /// `ConcreteCircuit::FloorPlanner`. The proving key must have been generated with the
+    /// same floor planner, see [`keygen_pk_with_planner`].
+    ///
+    /// This has no effect on proofs created from witnesses, whose layout is already fixed.
+    ///
+    /// [`keygen_pk_with_planner`]: crate::plonk::keygen_pk_with_planner
+    pub fn with_planner<FP: FloorPlanner>(
+        self,
+    ) -> ProofBuilder<'a, 'params, Scheme, P, WithFloorPlanner<'a, ConcreteCircuit, FP>> {
+        let inputs = match self.inputs {
+            Inputs::Circuits(circuits) => {
+                Inputs::Owned(circuits.iter().map(WithFloorPlanner::new).collect())
+            }
+            Inputs::Owned(circuits) => Inputs::Owned(
+                circuits
+                    .into_iter()
+                    .map(WithFloorPlanner::from_owned)
+                    .collect(),
+            ),
+            Inputs::Witnesses(witnesses) => Inputs::Witnesses(witnesses),
+        };
+        ProofBuilder {
+            params: self.params,
+            pk: self.pk,
+            inputs,
+            instances: self.instances,
+            engine: self.engine,
+            queries: self.queries,
+            query_order: self.query_order,
+            domain: self.domain,
+            limits: self.limits,
+            check_assignments: self.check_assignments,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Checks that every advice cell queried by a gate where the gate is enabled is
+    /// assigned, like [`MockProver`] does, before the proof is created.
+    ///
+    /// The prover otherwise fills unassigned advice cells with zeros, which can satisfy the
+    /// constraints by accident. The check tracks every assignment, so this is meant for
+    /// debugging. The proof then fails with an `Error::Other` naming the first unassigned
+    /// cell, its gate and its region. This has no effect on proofs created from witnesses.
+    ///
+    /// [`MockProver`]: crate::dev::MockProver
+    pub fn with_assignment_check(mut self) -> Self {
+        self.check_assignments = true;
+        self
+    }
+
+    /// Creates the proof, and returns the evaluations of the extra queries of
+    /// [`Self::with_queries`] for each circuit.
+    pub fn create<E, R, T>(
+        self,
+        rng: R,
+        transcript: &mut T,
+    ) -> Result<Vec<Vec<Scheme::Scalar>>, Error>
+    where
+        E: EncodedChallenge<Scheme::Curve>,
+        R: RngCore,
+        T: TranscriptWrite<Scheme::Curve, E>,
+    {
+        let queries = self.queries;
+        self.commit_phases::<E, R, T>(rng, transcript)?
+            .create_proof_with_queries(queries)
+    }
+
+    /// Creates the proof, and returns the commitments to the pieces of the quotient
+    /// polynomial `h(X)` it contains, with their blinds.
+    #[cfg(feature = "vanishing-pieces")]
+    pub fn create_with_vanishing_pieces<E, R, T>(
+        self,
+        rng: R,
+        transcript: &mut T,
+    ) -> Result<VanishingPieces<Scheme::Curve>, Error>
+    where
+        E: EncodedChallenge<Scheme::Curve>,
+        R: RngCore,
+        T: TranscriptWrite<Scheme::Curve, E>,
+    {
+        self.check_no_queries()?;
+        let mut pieces = VanishingPieces::default();
+        let mut prover = self.commit_phases::<E, R, T>(rng, transcript)?;
+        prover.output_vanishing_pieces(&mut pieces);
+        prover.create_proof()?;
+        Ok(pieces)
+    }
+
+    /// Creates the proof, and returns an [`EvaluationProof`] of its openings, which light
+    /// clients can check with [`verify_evaluation_proof`] without verifying the proof.
+    ///
+    /// The evaluation proof is created with a new transcript of the same type as
+    /// `transcript`.
+    ///
+    /// [`verify_evaluation_proof`]: crate::plonk::verify_evaluation_proof
+    pub fn create_with_evaluation_proof<E, R, T>(
+        self,
+        rng: R,
+        transcript: &mut T,
+    ) -> Result<EvaluationProof<Scheme::Curve>, Error>
+    where
+        E: EncodedChallenge<Scheme::Curve>,
+        R: RngCore,
+        T: TranscriptWriterBuffer<Vec<u8>, Scheme::Curve, E>,
+    {
+        self.check_no_queries()?;
+        self.commit_phases::<E, R, T>(rng, transcript)?
+            .create_proof_with_evaluation_proof()
+    }
+
+    /// Creates the proof split into the sections of `sections`, and returns the arguments
+    /// of the sections.
+    ///
+    /// The proof written to `transcript` is checked with [`verify_split_proof`], which
+    /// returns the sections whose arguments are then verified, independently of each
+    /// other, with [`verify_proof_section`]. The argument of each section is created with
+    /// a new transcript of the same type as `transcript`.
+    ///
+    /// [`verify_split_proof`]: crate::plonk::verify_split_proof
+    /// [`verify_proof_section`]: crate::plonk::verify_proof_section
+    pub fn create_with_sections<E, R, T>(
+        self,
+        sections: &ProofSections,
+        rng: R,
+        transcript: &mut T,
+    ) -> Result<Vec<Vec<u8>>, Error>
+    where
+        E: EncodedChallenge<Scheme::Curve>,
+        R: RngCore,
+        T: TranscriptWriterBuffer<Vec<u8>, Scheme::Curve, E>,
+    {
+        self.check_no_queries()?;
+        self.commit_phases::<E, R, T>(rng, transcript)?
+            .create_proof_with_sections(sections)
+    }
+
+    fn check_no_queries(&self) -> Result<(), Error> {
+        if self.queries.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Other(
+                "extra queries are only supported by `ProofBuilder::create`".to_string(),
+            ))
+        }
+    }
+
+    /// Synthesizes the circuits, or takes the precomputed witnesses, and commits to the
+    /// advice columns of every phase, leaving the rest of the proof to the caller.
+    fn commit_phases<'t, E, R, T>(
+        self,
+        rng: R,
+        transcript: &'t mut T,
+    ) -> Result<ProverV2<'t, 'params, Scheme, P, E, R, T>, Error>
+    where
+        'a: 't,
+        E: EncodedChallenge<Scheme::Curve>,
+        R: RngCore,
+        T: TranscriptWrite<Scheme::Curve, E>,
+    {
+        let Self {
+            params,
+            pk,
+            inputs,
+            instances,
+            engine,
+            query_order,
+            domain,
+            limits,
+            check_assignments,
+            ..
+        } = self;
+        let circuits: &[ConcreteCircuit] = match &inputs {
+            Inputs::Circuits(circuits) => circuits,
+            Inputs::Owned(circuits) => circuits,
+            Inputs::Witnesses(witnesses) => {
+                return Self::commit_witnesses(
+                    params,
+                    pk,
+                    witnesses,
+                    instances,
+                    engine,
+                    query_order,
+                    domain,
+                    limits,
+                    rng,
+                    transcript,
+                )
+            }
+        };
+        if circuits.len() != instances.len() {
+            return Err(Error::InvalidInstances);
+        }
+        if let Some(limits) = limits {
+            limits.check(params.k(), pk.get_vk().cs(), circuits.len())?;
+        }
+        if let Some(label) = domain {
+            absorb_domain(transcript, label)?;
+        }
+        let (_, config, cs) =
+            compile_circuit(params.k(), &circuits[0], pk.get_vk().compress_selectors)?;
+        let mut witness_calcs: Vec<_> = circuits
+            .iter()
+            .enumerate()
+            .map(|(i, circuit)| {
+                let witness_calc =
+                    WitnessCalculator::new(params.k(), circuit, &config, &cs, instances[i]);
+                if check_assignments {
+                    witness_calc.check_assignments()
+                } else {
+                    witness_calc
+                }
+            })
+            .collect();
+        let mut prover = ProverV2::<Scheme, P, _, _, _>::new_with_engine(
+            engine, params, pk, instances, rng, transcript,
+        )?;
+        prover.set_query_order(query_order);
+        let mut challenges = HashMap::new();
+        let phases = prover.phases().to_vec();
+        for phase in phases.iter() {
+            let witnesses = witness_calcs
+                .iter_mut()
+                .enumerate()
+                .map(|(i, witness_calc)| {
+                    let earlier_phases = prover.committed_advice(i);
+                    witness_calc.calc_with_earlier_phases(phase.0, &challenges, &earlier_phases)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            challenges = prover.commit_phase(phase.0, witnesses)?;
+        }
+        Ok(prover)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn commit_witnesses<'t, E, R, T>(
+        params: &'params Scheme::ParamsProver,
+        pk: &'a ProvingKey<Scheme::Curve>,
+        witnesses: &[Witness<Scheme::Scalar>],
+        instances: &'a [&'a [&'a [Scheme::Scalar]]],
+        engine: PlonkEngine<Scheme::Curve>,
+        query_order: QueryOrder,
+        domain: Option<&[u8]>,
+        limits: Option<ProverLimits>,
+        rng: R,
+        transcript: &'t mut T,
+    ) -> Result<ProverV2<'t, 'params, Scheme, P, E, R, T>, Error>
+    where
+        'a: 't,
+        E: EncodedChallenge<Scheme::Curve>,
+        R: RngCore,
+        T: TranscriptWrite<Scheme::Curve, E>,
+    {
+        if witnesses.len() != instances.len() {
+            return Err(Error::InvalidInstances);
+        }
+        if witnesses.iter().any(|witness| witness.k != params.k()) {
+            return Err(Error::Other(
+                "witness size does not match the parameters".to_string(),
+            ));
+        }
+        if let Some(limits) = limits {
+            limits.check(params.k(), pk.get_vk().cs(), witnesses.len())?;
+        }
+        if let Some(label) = domain {
+            absorb_domain(transcript, label)?;
+        }
+        let mut prover = ProverV2::<Scheme, P, _, _, _>::new_with_engine(
+            engine, params, pk, instances, rng, transcript,
+        )?;
+        prover.set_query_order(query_order);
+        let phases = prover.phases().to_vec();
+        if phases.len() > 1 {
+            return Err(Error::Other(
+                "witnesses of circuits with multiple phases can't be precomputed".to_string(),
+            ));
+        }
+        for phase in phases.iter() {
+            let witnesses = witnesses
+                .iter()
+                .map(|witness| {
+                    witness
+                        .advice_phases
+                        .iter()
+                        .zip(witness.advice.iter())
+                        .map(|(column_phase, column)| {
+                            (*column_phase == phase.0).then(|| {
+                                column
+                                    .iter()
+                                    .map(|cell| cell.unwrap_or(Scheme::Scalar::ZERO))
+                                    .collect()
+                            })
+                        })
+                        .collect()
+                })
+                .collect();
+            prover.commit_phase(phase.0, witnesses)?;
+        }
+        Ok(prover)
+    }
+}
+
+#[cfg(feature = "kzg")]
 #[test]
 fn test_create_proof() {
     use crate::{
@@ -119,3 +601,640 @@ fn test_create_proof() {
     )
     .expect("proof generation should not fail");
 }
+
+#[cfg(feature = "kzg")]
+#[test]
+fn test_create_proof_from_witness() {
+    use crate::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        plonk::{keygen_pk, keygen_vk, verify_proof, Advice, Column, ConstraintSystem, Selector},
+        poly::{
+            commitment::ParamsProver,
+            kzg::{
+                commitment::{KZGCommitmentScheme, ParamsKZG},
+                multiopen::{ProverSHPLONK, VerifierSHPLONK},
+                strategy::SingleStrategy,
+            },
+            Rotation,
+        },
+        transcript::{
+            Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+        },
+    };
+    use halo2curves::bn256::{Bn256, Fr};
+    use rand_core::OsRng;
+
+    #[derive(Clone)]
+    struct MyCircuit(Value<Fr>);
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = (Column<Advice>, Column<Advice>, Selector);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            MyCircuit(Value::unknown())
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let a = meta.advice_column();
+            let b = meta.advice_column();
+            let s = meta.selector();
+            meta.create_gate("a == b", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                let b = meta.query_advice(b, Rotation::cur());
+                vec![s * (a - b)]
+            });
+            (a, b, s)
+        }
+
+        fn synthesize(
+            &self,
+            (a, b, s): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "region",
+                |mut region| {
+                    s.enable(&mut region, 0)?;
+                    region.assign_advice(|| "a", a, 0, || self.0)?;
+                    region.assign_advice(|| "b", b, 0, || self.0)?;
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    const K: u32 = 4;
+    let circuit = MyCircuit(Value::known(Fr::from(3)));
+    let witness = MockProver::run(K, &circuit, vec![]).unwrap().witness();
+
+    let params: ParamsKZG<Bn256> = ParamsKZG::setup(K, OsRng);
+    let vk = keygen_vk(&params, &circuit.without_witnesses()).unwrap();
+    let pk = keygen_pk(&params, vk, &circuit.without_witnesses()).unwrap();
+
+    let prove = |witness: Witness<Fr>| {
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        ProofBuilder::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, MyCircuit>::from_witnesses(
+            &params,
+            &pk,
+            &[witness],
+            &[&[]],
+        )
+        .create(OsRng, &mut transcript)
+        .expect("proof generation should not fail");
+        transcript.finalize()
+    };
+    let verify = |proof: &[u8]| {
+        let verifier_params = params.verifier_params();
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+        verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+            verifier_params,
+            pk.get_vk(),
+            SingleStrategy::new(verifier_params),
+            &[&[]],
+            &mut transcript,
+        )
+        .is_ok()
+    };
+    assert!(verify(&prove(witness.clone())));
+
+    // The gate is enabled, so a witness which doesn't satisfy it is rejected.
+    let mut tampered = witness;
+    tampered.advice[1][0] = Some(Fr::from(4));
+    assert!(!verify(&prove(tampered)));
+}
+
+#[cfg(feature = "kzg")]
+#[test]
+fn test_create_proof_with_planner() {
+    use crate::{
+        circuit::{floor_planner::V1, Layouter, SimpleFloorPlanner, Value},
+        plonk::{
+            keygen_pk_with_planner, keygen_vk, keygen_vk_with_planner, verify_proof, Advice,
+            Column, ConstraintSystem, Selector,
+        },
+        poly::{
+            commitment::ParamsProver,
+            kzg::{
+                commitment::{KZGCommitmentScheme, ParamsKZG},
+                multiopen::{ProverSHPLONK, VerifierSHPLONK},
+                strategy::SingleStrategy,
+            },
+            Rotation,
+        },
+        transcript::{
+            Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+        },
+    };
+    use halo2curves::bn256::{Bn256, Fr};
+    use rand_core::OsRng;
+
+    #[derive(Clone)]
+    struct MyCircuit(Value<Fr>);
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = (Column<Advice>, Column<Advice>, Selector);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            MyCircuit(Value::unknown())
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let a = meta.advice_column();
+            let b = meta.advice_column();
+            let s = meta.selector();
+            meta.create_gate("a == b", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                let b = meta.query_advice(b, Rotation::cur());
+                vec![s * (a - b)]
+            });
+            (a, b, s)
+        }
+
+        fn synthesize(
+            &self,
+            (a, b, s): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            // The simple planner lays the regions out in order, while V1 lays the larger
+            // region out first, so that the small one comes after it.
+            layouter.assign_region(
+                || "small",
+                |mut region| region.assign_advice(|| "a", a, 0, || self.0),
+            )?;
+            layouter.assign_region(
+                || "large",
+                |mut region| {
+                    for offset in 0..2 {
+                        s.enable(&mut region, offset)?;
+                        region.assign_advice(|| "a", a, offset, || self.0)?;
+                        region.assign_advice(|| "b", b, offset, || self.0)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    const K: u32 = 4;
+    let circuit = MyCircuit(Value::known(Fr::from(3)));
+    let params: ParamsKZG<Bn256> = ParamsKZG::setup(K, OsRng);
+    let vk = keygen_vk_with_planner::<V1, _, _, _>(&params, &circuit.without_witnesses()).unwrap();
+    // The selector is enabled at other rows than with the simple planner.
+    let simple_vk = keygen_vk(&params, &circuit.without_witnesses()).unwrap();
+    assert_ne!(vk.fixed_commitments(), simple_vk.fixed_commitments());
+    let pk =
+        keygen_pk_with_planner::<V1, _, _, _>(&params, vk, &circuit.without_witnesses()).unwrap();
+
+    let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+    ProofBuilder::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _>::new(
+        &params,
+        &pk,
+        &[circuit],
+        &[&[]],
+    )
+    .with_planner::<V1>()
+    .create(OsRng, &mut transcript)
+    .expect("proof generation should not fail");
+    let proof = transcript.finalize();
+
+    let verifier_params = params.verifier_params();
+    let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof.as_slice());
+    verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+        verifier_params,
+        pk.get_vk(),
+        SingleStrategy::new(verifier_params),
+        &[&[]],
+        &mut transcript,
+    )
+    .expect("proof verification should not fail");
+}
+
+#[cfg(feature = "kzg")]
+#[test]
+fn test_protocol_version() {
+    use crate::{
+        circuit::SimpleFloorPlanner,
+        plonk::{
+            keygen_pk, keygen_vk, verify_proof, ConstraintSystem, ProvingKey, VerifyingKey,
+            PROTOCOL_VERSION,
+        },
+        poly::{
+            commitment::ParamsProver,
+            kzg::{
+                commitment::{KZGCommitmentScheme, ParamsKZG},
+                multiopen::{ProverSHPLONK, VerifierSHPLONK},
+                strategy::SingleStrategy,
+            },
+        },
+        transcript::{
+            Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+        },
+        SerdeFormat,
+    };
+    use halo2curves::bn256::{Bn256, Fr, G1Affine};
+    use rand_core::OsRng;
+
+    #[derive(Clone, Copy)]
+    struct MyCircuit;
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = ();
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            *self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            meta.advice_column();
+        }
+
+        fn synthesize(
+            &self,
+            _config: Self::Config,
+            _layouter: impl crate::circuit::Layouter<Fr>,
+        ) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    let params: ParamsKZG<Bn256> = ParamsKZG::setup(3, OsRng);
+    let verifier_params = params.verifier_params();
+    let vk = keygen_vk(&params, &MyCircuit).unwrap();
+    assert_eq!(vk.protocol_version(), PROTOCOL_VERSION);
+    assert!(vk
+        .clone()
+        .with_protocol_version(PROTOCOL_VERSION + 1)
+        .is_err());
+
+    // The protocol version survives serialization.
+    let legacy_vk = vk.clone().with_protocol_version(0).unwrap();
+    let read_vk = VerifyingKey::<G1Affine>::from_bytes::<MyCircuit>(
+        &legacy_vk.to_bytes(SerdeFormat::RawBytes),
+        SerdeFormat::RawBytes,
+        #[cfg(feature = "circuit-params")]
+        (),
+    )
+    .unwrap();
+    assert_eq!(read_vk.protocol_version(), 0);
+    assert_eq!(read_vk.transcript_repr(), legacy_vk.transcript_repr());
+    assert_ne!(legacy_vk.transcript_repr(), vk.transcript_repr());
+
+    let prove = |pk: &ProvingKey<G1Affine>| {
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        create_proof::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _, _, _, _>(
+            &params,
+            pk,
+            &[MyCircuit],
+            &[&[]],
+            OsRng,
+            &mut transcript,
+        )
+        .unwrap();
+        transcript.finalize()
+    };
+    let verify = |vk: &VerifyingKey<G1Affine>, proof: &[u8]| {
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+        verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+            verifier_params,
+            vk,
+            SingleStrategy::new(verifier_params),
+            &[&[]],
+            &mut transcript,
+        )
+        .is_ok()
+    };
+
+    // Proofs for the legacy protocol only verify with a legacy key.
+    let legacy_pk = keygen_pk(&params, legacy_vk.clone(), &MyCircuit).unwrap();
+    let legacy_proof = prove(&legacy_pk);
+    assert!(verify(&legacy_vk, &legacy_proof));
+    assert!(!verify(&vk, &legacy_proof));
+
+    let pk = keygen_pk(&params, vk.clone(), &MyCircuit).unwrap();
+    let proof = prove(&pk);
+    assert!(verify(&vk, &proof));
+    assert!(!verify(&legacy_vk, &proof));
+}
+
+#[cfg(feature = "kzg")]
+#[test]
+fn test_tampered_proofs() {
+    use crate::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::tamper::{assert_rejects_tampered_proofs, RecordingTranscript},
+        plonk::{keygen_pk, keygen_vk, verify_proof, Advice, Column, ConstraintSystem},
+        poly::{
+            commitment::ParamsProver,
+            kzg::{
+                commitment::{KZGCommitmentScheme, ParamsKZG},
+                multiopen::{ProverSHPLONK, VerifierSHPLONK},
+                strategy::SingleStrategy,
+            },
+            Rotation,
+        },
+        transcript::{
+            Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+        },
+    };
+    use halo2curves::bn256::{Bn256, Fr};
+    use rand_core::OsRng;
+
+    #[derive(Clone)]
+    struct MyCircuit(Value<Fr>);
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = (Column<Advice>, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            MyCircuit(Value::unknown())
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let a = meta.advice_column();
+            let b = meta.advice_column();
+            meta.enable_equality(a);
+            meta.enable_equality(b);
+            let s = meta.selector();
+            meta.create_gate("a == 2b", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                let b = meta.query_advice(b, Rotation::cur());
+                vec![s * (a - b.clone() - b)]
+            });
+            (a, b)
+        }
+
+        fn synthesize(
+            &self,
+            (a, b): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "region",
+                |mut region| {
+                    region.assign_advice(|| "a", a, 0, || self.0.map(|v| v + v))?;
+                    let b0 = region.assign_advice(|| "b", b, 0, || self.0)?;
+                    b0.copy_advice(|| "b copy", &mut region, b, 1)?;
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    const K: u32 = 4;
+    let circuit = MyCircuit(Value::known(Fr::from(3)));
+    let params: ParamsKZG<Bn256> = ParamsKZG::setup(K, OsRng);
+    let verifier_params = params.verifier_params();
+    let vk = keygen_vk(&params, &circuit.without_witnesses()).unwrap();
+    let pk = keygen_pk(&params, vk, &circuit.without_witnesses()).unwrap();
+
+    let mut transcript =
+        RecordingTranscript::new(Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]));
+    create_proof::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _, _, _, _>(
+        &params,
+        &pk,
+        &[circuit],
+        &[&[]],
+        OsRng,
+        &mut transcript,
+    )
+    .expect("proof generation should not fail");
+    let (transcript, layout) = transcript.into_parts();
+    let proof = transcript.finalize();
+    assert!(!layout.elements().is_empty());
+
+    let verify = |proof: &[u8]| {
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+        verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+            verifier_params,
+            pk.get_vk(),
+            SingleStrategy::new(verifier_params),
+            &[&[]],
+            &mut transcript,
+        )
+        .is_ok()
+    };
+    assert!(verify(&proof));
+    assert_rejects_tampered_proofs(&proof, &layout, verify);
+}
+
+#[cfg(feature = "kzg")]
+#[test]
+fn test_malformed_verifier_inputs() {
+    use crate::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        plonk::{
+            keygen_pk, keygen_vk, verify_proof, Advice, Column, ConstraintSystem, Instance,
+            VerifyingKey,
+        },
+        poly::{
+            commitment::ParamsProver,
+            kzg::{
+                commitment::{KZGCommitmentScheme, ParamsKZG},
+                multiopen::{ProverSHPLONK, VerifierSHPLONK},
+                strategy::SingleStrategy,
+            },
+            Rotation,
+        },
+        transcript::{
+            Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+        },
+        SerdeFormat,
+    };
+    use halo2_middleware::ff::PrimeField;
+    use halo2curves::{
+        bn256::{Bn256, Fr, G1Affine},
+        CurveAffine, CurveExt,
+    };
+    use rand_core::OsRng;
+
+    #[derive(Clone)]
+    struct MyCircuit(Value<Fr>);
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = (Column<Advice>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            MyCircuit(Value::unknown())
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let a = meta.advice_column();
+            let i = meta.instance_column();
+            meta.enable_equality(a);
+            meta.enable_equality(i);
+            (a, i)
+        }
+
+        fn synthesize(
+            &self,
+            (a, i): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let cell = layouter.assign_region(
+                || "region",
+                |mut region| region.assign_advice(|| "a", a, 0, || self.0),
+            )?;
+            layouter.constrain_instance(cell.cell(), i, 0)
+        }
+    }
+
+    const K: u32 = 4;
+    let circuit = MyCircuit(Value::known(Fr::from(3)));
+    let params: ParamsKZG<Bn256> = ParamsKZG::setup(K, OsRng);
+    let vk = keygen_vk(&params, &circuit.without_witnesses()).unwrap();
+    let pk = keygen_pk(&params, vk.clone(), &circuit.without_witnesses()).unwrap();
+
+    let instance = [Fr::from(3)];
+    let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+    create_proof::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _, _, _, _>(
+        &params,
+        &pk,
+        &[circuit],
+        &[&[&instance]],
+        OsRng,
+        &mut transcript,
+    )
+    .unwrap();
+    let proof = transcript.finalize();
+
+    let verify = |params: &ParamsKZG<Bn256>, instance: &[Fr], proof: &[u8]| {
+        let params = params.verifier_params();
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+        verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+            params,
+            &vk,
+            SingleStrategy::new(params),
+            &[&[instance]],
+            &mut transcript,
+        )
+    };
+    assert!(verify(&params, &instance, &proof).is_ok());
+
+    // Truncated proofs are rejected.
+    for len in [0, 1, proof.len() / 2, proof.len() - 1] {
+        assert!(verify(&params, &instance, &proof[..len]).is_err());
+    }
+    // Instances that don't fit in the circuit are rejected.
+    let long_instance = vec![Fr::from(3); 1 << K];
+    assert!(matches!(
+        verify(&params, &long_instance, &proof),
+        Err(Error::InstanceTooLarge)
+    ));
+    // Parameters of another size are rejected.
+    let other_params: ParamsKZG<Bn256> = ParamsKZG::setup(K + 1, OsRng);
+    assert!(verify(&other_params, &instance, &proof).is_err());
+
+    // Malformed verifying keys are rejected.
+    let read_vk = |bytes: &[u8]| {
+        VerifyingKey::<G1Affine>::from_bytes::<MyCircuit>(
+            bytes,
+            SerdeFormat::RawBytes,
+            #[cfg(feature = "circuit-params")]
+            (),
+        )
+    };
+    let vk_bytes = vk.to_bytes(SerdeFormat::RawBytes);
+    assert!(read_vk(&vk_bytes).is_ok());
+    let error = |bytes: &[u8]| read_vk(bytes).err().expect("the key should be rejected");
+    // The version byte and the identifier of the curve precede the protocol version, `k`,
+    // the compression of the selectors and the number of fixed columns.
+    let header =
+        1 + 4 + 2 + <G1Affine as CurveAffine>::CurveExt::CURVE_ID.len() + Fr::MODULUS.len();
+    assert_eq!(&vk_bytes[1..5], b"H2CV");
+    assert_eq!(vk_bytes[header + 1], K as u8);
+    assert_eq!(
+        vk_bytes[header + 3..header + 7],
+        (vk.fixed_commitments().len() as u32).to_le_bytes()
+    );
+    for len in [0, 3, header + 2, vk_bytes.len() - 1] {
+        assert_eq!(
+            error(&vk_bytes[..len]).kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+    }
+    // A huge number of fixed columns.
+    let mut huge = vk_bytes.clone();
+    huge[header + 3..header + 7].copy_from_slice(&u32::MAX.to_le_bytes());
+    let err = error(&huge);
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "unexpected number of fixed columns");
+    // A `k` too small for the circuit, or too large for the field.
+    for (k, message) in [
+        (0, "is too small for the given circuit"),
+        (200, "exceeds maxium"),
+    ] {
+        let mut bytes = vk_bytes.clone();
+        bytes[header + 1] = k;
+        let err = error(&bytes);
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains(message), "{}", err);
+    }
+    // A key read for another circuit with the same columns doesn't match the proof, which
+    // is rejected rather than panicking.
+    #[derive(Clone)]
+    struct GatedCircuit(MyCircuit);
+
+    impl Circuit<Fr> for GatedCircuit {
+        type Config = (Column<Advice>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            GatedCircuit(self.0.without_witnesses())
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let (a, i) = MyCircuit::configure(meta);
+            meta.create_gate("next", |meta| vec![meta.query_advice(a, Rotation::next())]);
+            (a, i)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            self.0.synthesize(config, layouter)
+        }
+    }
+
+    let inconsistent_vk = VerifyingKey::<G1Affine>::from_bytes::<GatedCircuit>(
+        &vk_bytes,
+        SerdeFormat::RawBytes,
+        #[cfg(feature = "circuit-params")]
+        (),
+    )
+    .unwrap();
+    let verifier_params = params.verifier_params();
+    let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+    assert!(
+        verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+            verifier_params,
+            &inconsistent_vk,
+            SingleStrategy::new(verifier_params),
+            &[&[&instance]],
+            &mut transcript,
+        )
+        .is_err()
+    );
+}
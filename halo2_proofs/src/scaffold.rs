@@ -0,0 +1,303 @@
+//! Generation of the boilerplate of a new circuit.
+//!
+//! From a small [`CircuitSpec`], [`generate`] writes the source of a circuit module, with
+//! its configuration, its chip, its circuit, the layout of its instance columns and tests
+//! with [`MockProver`], and of a benchmark of the real prover. The generated circuit
+//! constrains its last advice column to be the sum of the other ones, so that it can be
+//! tested and benchmarked right away: the constraints and the assignments are then
+//! replaced by those of the circuit.
+//!
+//! [`MockProver`]: crate::dev::MockProver
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::plonk::Error;
+
+const CIRCUIT_TEMPLATE: &str = include_str!("scaffold/circuit.rs.in");
+const BENCH_TEMPLATE: &str = include_str!("scaffold/bench.rs.in");
+
+/// The shape of a new circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitSpec {
+    /// The name of the circuit, in upper camel case, which prefixes the names of its types.
+    /// Its module is named after it, in snake case.
+    pub name: String,
+    /// The name of the crate the circuit is added to, from which the benchmark imports it.
+    pub crate_name: String,
+    /// The circuit has `2^k` rows.
+    pub k: u32,
+    /// The number of advice columns, at least two.
+    pub advice_columns: usize,
+    /// The number of fixed columns, which hold the constants of the circuit.
+    pub fixed_columns: usize,
+    /// The number of instance columns. The sum is exposed in the first one, if any.
+    pub instance_columns: usize,
+    /// Whether the circuit looks its first advice column up in a range table.
+    pub lookup: bool,
+}
+
+impl CircuitSpec {
+    /// Creates the spec of a circuit named `name` in the crate `crate_name`, with `2^8`
+    /// rows, three advice columns, a fixed column, an instance column and no lookup.
+    pub fn new(name: impl Into<String>, crate_name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            crate_name: crate_name.into(),
+            k: 8,
+            advice_columns: 3,
+            fixed_columns: 1,
+            instance_columns: 1,
+            lookup: false,
+        }
+    }
+
+    /// Returns the name of the module of the circuit.
+    pub fn module_name(&self) -> String {
+        let mut module = String::new();
+        for (i, c) in self.name.chars().enumerate() {
+            if c.is_ascii_uppercase() {
+                if i > 0 {
+                    module.push('_');
+                }
+                module.push(c.to_ascii_lowercase());
+            } else {
+                module.push(c);
+            }
+        }
+        module
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        let is_identifier = |name: &str| {
+            matches!(name.chars().next(), Some(c) if c.is_ascii_alphabetic())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        if !is_identifier(&self.name)
+            || !self.name.starts_with(|c: char| c.is_ascii_uppercase())
+            || self.name.contains('_')
+        {
+            return Err(Error::Other(format!(
+                "circuit name \"{}\" is not in upper camel case",
+                self.name
+            )));
+        }
+        if !is_identifier(&self.crate_name) {
+            return Err(Error::Other(format!(
+                "crate name \"{}\" is not an identifier",
+                self.crate_name
+            )));
+        }
+        if self.advice_columns < 2 {
+            return Err(Error::Other(
+                "the circuit needs at least two advice columns".to_string(),
+            ));
+        }
+        // The lookup table has `2^(k - 1)` rows.
+        if !(4..=30).contains(&self.k) {
+            return Err(Error::Other(format!(
+                "k = {} is not between 4 and 30",
+                self.k
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A file generated by [`generate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScaffoldFile {
+    /// The path of the file, relative to the root of the crate.
+    pub path: PathBuf,
+    /// The contents of the file.
+    pub contents: String,
+}
+
+/// The files generated for a circuit by [`generate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scaffold {
+    /// The module of the circuit, in `src/`, and its benchmark, in `benches/`.
+    pub files: Vec<ScaffoldFile>,
+    /// The lines to add to the manifest of the crate, which register the benchmark and
+    /// its dev-dependencies.
+    pub manifest: String,
+}
+
+impl Scaffold {
+    /// Writes the files into the crate at `root`, creating their directories if needed.
+    /// Existing files are not overwritten: an error is returned instead.
+    ///
+    /// The module of the circuit must still be declared in the crate, and
+    /// [`Scaffold::manifest`] added to its manifest.
+    pub fn write_to(&self, root: &Path) -> io::Result<()> {
+        for file in &self.files {
+            let path = root.join(&file.path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?
+                .write_all(file.contents.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Generates the boilerplate of the circuit described by `spec`.
+///
+/// Returns an `Error::Other` if the names of `spec` are not identifiers, if it has less
+/// than two advice columns, or if `k` is not between 4 and 30.
+pub fn generate(spec: &CircuitSpec) -> Result<Scaffold, Error> {
+    spec.check()?;
+    let module = spec.module_name();
+    let variables = [
+        ("NAME", spec.name.clone()),
+        ("MODULE", module.clone()),
+        ("CRATE", spec.crate_name.clone()),
+        ("K", spec.k.to_string()),
+        ("ADVICE", spec.advice_columns.to_string()),
+        ("FIXED", spec.fixed_columns.to_string()),
+        ("INSTANCE", spec.instance_columns.to_string()),
+    ];
+    let sections = [
+        ("instance", spec.instance_columns > 0),
+        ("lookup", spec.lookup),
+    ];
+    let render_template = |template| render(template, &variables, &sections);
+
+    Ok(Scaffold {
+        files: vec![
+            ScaffoldFile {
+                path: Path::new("src").join(format!("{}.rs", module)),
+                contents: render_template(CIRCUIT_TEMPLATE),
+            },
+            ScaffoldFile {
+                path: Path::new("benches").join(format!("{}.rs", module)),
+                contents: render_template(BENCH_TEMPLATE),
+            },
+        ],
+        manifest: format!(
+            "[[bench]]\n\
+             name = \"{}\"\n\
+             harness = false\n\
+             \n\
+             [dev-dependencies]\n\
+             criterion = \"0.3\"\n\
+             rand_core = \"0.6\"\n",
+            module
+        ),
+    })
+}
+
+/// Renders `template`, replacing each `{{VARIABLE}}` by its value, and keeping the lines
+/// between `{{#section}}` and `{{/section}}` only if the section is enabled, and those
+/// between `{{^section}}` and `{{/section}}` only if it is disabled. Section markers are
+/// on lines of their own.
+fn render(template: &str, variables: &[(&str, String)], sections: &[(&str, bool)]) -> String {
+    let enabled = |name: &str| {
+        sections
+            .iter()
+            .any(|(section, enabled)| *section == name && *enabled)
+    };
+
+    let mut output = String::new();
+    // Whether the lines of each open section are kept.
+    let mut kept = vec![];
+    for line in template.lines() {
+        let marker = line
+            .trim()
+            .strip_prefix("{{")
+            .and_then(|marker| marker.strip_suffix("}}"))
+            .filter(|marker| marker.starts_with(['#', '^', '/']));
+        match marker.map(|marker| marker.split_at(1)) {
+            Some(("#", name)) => kept.push(enabled(name)),
+            Some(("^", name)) => kept.push(!enabled(name)),
+            Some(("/", _)) => {
+                kept.pop();
+            }
+            _ if kept.iter().all(|kept| *kept) => {
+                let mut line = line.to_string();
+                for (name, value) in variables {
+                    line = line.replace(&format!("{{{{{}}}}}", name), value);
+                }
+                output.push_str(&line);
+                output.push('\n');
+            }
+            _ => {}
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{generate, render, CircuitSpec};
+
+    #[test]
+    fn render_sections() {
+        let template = "a {{X}}\n{{#on}}\nb\n{{^on}}\nc\n{{/on}}\n{{/on}}\n{{^off}}\nd\n{{/off}}\n";
+        let rendered = render(
+            template,
+            &[("X", "x".to_string())],
+            &[("on", true), ("off", false)],
+        );
+        assert_eq!(rendered, "a x\nb\nd\n");
+    }
+
+    #[test]
+    fn generate_files() {
+        let mut spec = CircuitSpec::new("RangeCheck", "my_circuits");
+        assert_eq!(spec.module_name(), "range_check");
+
+        let scaffold = generate(&spec).unwrap();
+        let paths: Vec<_> = scaffold
+            .files
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("src/range_check.rs"),
+                Path::new("benches/range_check.rs")
+            ]
+        );
+        for file in &scaffold.files {
+            assert!(!file.contents.contains("{{"));
+        }
+        let circuit = &scaffold.files[0].contents;
+        assert!(circuit.contains("pub struct RangeCheckChip<F: PrimeField>"));
+        assert!(circuit.contains("fn expose_public("));
+        assert!(!circuit.contains("TableColumn"));
+        assert!(scaffold.files[1]
+            .contents
+            .contains("use my_circuits::range_check::{public_inputs, RangeCheckCircuit"));
+        assert!(scaffold.manifest.contains("name = \"range_check\""));
+
+        spec.instance_columns = 0;
+        spec.lookup = true;
+        let circuit = &generate(&spec).unwrap().files[0].contents;
+        assert!(!circuit.contains("Instance"));
+        assert!(circuit.contains("fn load_table("));
+
+        for spec in [
+            CircuitSpec::new("range_check", "my_circuits"),
+            CircuitSpec::new("RangeCheck", "my-circuits"),
+            CircuitSpec {
+                advice_columns: 1,
+                ..CircuitSpec::new("RangeCheck", "my_circuits")
+            },
+            CircuitSpec {
+                k: 3,
+                ..CircuitSpec::new("RangeCheck", "my_circuits")
+            },
+        ] {
+            assert!(generate(&spec).is_err());
+        }
+    }
+}
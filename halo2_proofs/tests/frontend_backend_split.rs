@@ -548,6 +548,58 @@ fn test_mycircuit_full_legacy() {
     println!("Verify: {:?}", start.elapsed());
 }
 
+#[test]
+fn test_mycircuit_keygen_from_compiled() {
+    use halo2_common::SerdeFormat;
+    use halo2_proofs::plonk::{create_proof, keygen_pk, keygen_pk_from_compiled, keygen_vk};
+
+    let k = K;
+    let circuit: MyCircuit<Fr, WIDTH_FACTOR> = MyCircuit::new(k, 42);
+    let (compiled_circuit, _, _) = compile_circuit(k, &circuit, true).unwrap();
+
+    let mut rng = BlockRng::new(OneNg {});
+    let params = ParamsKZG::<Bn256>::setup(k, &mut rng);
+    let pk = keygen_pk_from_compiled(&params, &compiled_circuit, true)
+        .expect("keygen_pk_from_compiled should not fail");
+    drop(compiled_circuit);
+
+    // The keys are those generated from the circuit.
+    let vk = keygen_vk(&params, &circuit).expect("keygen_vk should not fail");
+    let circuit_pk = keygen_pk(&params, vk.clone(), &circuit).expect("keygen_pk should not fail");
+    assert_eq!(
+        pk.to_bytes(SerdeFormat::RawBytes),
+        circuit_pk.to_bytes(SerdeFormat::RawBytes)
+    );
+
+    let instances = circuit.instances();
+    let instances_slice: &[&[Fr]] = &(instances
+        .iter()
+        .map(|instance| instance.as_slice())
+        .collect::<Vec<_>>());
+    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+    create_proof::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _, _>(
+        &params,
+        &pk,
+        &[circuit],
+        &[instances_slice],
+        &mut rng,
+        &mut transcript,
+    )
+    .expect("proof generation should not fail");
+    let proof = transcript.finalize();
+
+    let mut verifier_transcript =
+        Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof.as_slice());
+    verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+        &params,
+        &vk,
+        SingleStrategy::new(params.verifier_params()),
+        &[instances_slice],
+        &mut verifier_transcript,
+    )
+    .expect("verify succeeds");
+}
+
 #[test]
 fn test_mycircuit_full_split() {
     #[cfg(feature = "heap-profiling")]
@@ -615,3 +667,185 @@ fn test_mycircuit_full_split() {
     .expect("verify succeeds");
     println!("Verify: {:?}", start.elapsed());
 }
+
+#[test]
+fn test_mycircuit_middleware_only() {
+    use halo2_backend::plonk::{prover::create_proof_v2, ProvingKey, VerifyingKey};
+    use halo2_common::SerdeFormat;
+
+    let k = K;
+    let circuit: MyCircuit<Fr, WIDTH_FACTOR> = MyCircuit::new(k, 42);
+    let (compiled_circuit, config, cs) = compile_circuit(k, &circuit, false).unwrap();
+
+    // Setup, reading the keys back with only the middleware constraint system
+    let mut rng = BlockRng::new(OneNg {});
+    let params = ParamsKZG::<Bn256>::setup(k, &mut rng);
+    let verifier_params = params.verifier_params();
+    let vk = keygen_vk_v2(&params, &compiled_circuit).expect("keygen_vk should not fail");
+    let pk = keygen_pk_v2(&params, vk, &compiled_circuit).expect("keygen_pk should not fail");
+    let pk = ProvingKey::<G1Affine>::from_bytes_v2(
+        &pk.to_bytes(SerdeFormat::RawBytes),
+        SerdeFormat::RawBytes,
+        &compiled_circuit.cs,
+    )
+    .expect("reading the proving key should not fail");
+    let vk = VerifyingKey::<G1Affine>::from_bytes_v2(
+        &pk.get_vk().to_bytes(SerdeFormat::RawBytes),
+        SerdeFormat::RawBytes,
+        &compiled_circuit.cs,
+    )
+    .expect("reading the verifying key should not fail");
+
+    // Proving
+    let instances = circuit.instances();
+    let instances_slice: &[&[Fr]] = &(instances
+        .iter()
+        .map(|instance| instance.as_slice())
+        .collect::<Vec<_>>());
+
+    let mut witness_calc = WitnessCalculator::new(k, &circuit, &config, &cs, instances_slice);
+    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+    create_proof_v2::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _, _>(
+        &params,
+        &pk,
+        &[instances_slice],
+        |phase, challenges| Ok(vec![witness_calc.calc(phase, challenges)?]),
+        &mut rng,
+        &mut transcript,
+    )
+    .expect("proof generation should not fail");
+    let proof = transcript.finalize();
+
+    // Verify
+    let mut verifier_transcript =
+        Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof.as_slice());
+    let strategy = SingleStrategy::new(verifier_params);
+
+    verify_proof_single::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+        &params,
+        &vk,
+        strategy,
+        instances_slice,
+        &mut verifier_transcript,
+    )
+    .expect("verify succeeds");
+}
+
+#[test]
+fn test_external_commitment() {
+    use halo2_backend::plonk::prover::ExternalCommitment;
+    use halo2_backend::transcript::TranscriptRead;
+    use halo2_proofs::poly::commitment::{Blind, Params};
+
+    // Squares the values of an unblinded advice column, committed outside of the prover.
+    #[derive(Clone, Default)]
+    struct SquareCircuit;
+
+    impl Circuit<Fr> for SquareCircuit {
+        type Config = (Selector, Column<Advice>, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let s = meta.selector();
+            let a = meta.unblinded_advice_column();
+            let b = meta.advice_column();
+            meta.create_gate("square", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                let b = meta.query_advice(b, Rotation::cur());
+                vec![s * (a.clone() * a - b)]
+            });
+            (s, a, b)
+        }
+
+        fn synthesize(
+            &self,
+            (s, a, b): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "squares",
+                |mut region| {
+                    for offset in 0..8 {
+                        let value = Fr::from(offset as u64 + 3);
+                        s.enable(&mut region, offset)?;
+                        region.assign_advice(|| "a", a, offset, || Value::known(value))?;
+                        region.assign_advice(|| "b", b, offset, || Value::known(value.square()))?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    let k = 5;
+    let circuit = SquareCircuit;
+    let (compiled_circuit, config, cs) = compile_circuit(k, &circuit, false).unwrap();
+    let (_, a, _) = config;
+
+    let mut rng = BlockRng::new(OneNg {});
+    let params = ParamsKZG::<Bn256>::setup(k, &mut rng);
+    let verifier_params = params.verifier_params();
+    let vk = keygen_vk_v2(&params, &compiled_circuit).expect("keygen_vk should not fail");
+    let pk =
+        keygen_pk_v2(&params, vk.clone(), &compiled_circuit).expect("keygen_pk should not fail");
+
+    let mut witness_calc = WitnessCalculator::new(k, &circuit, &config, &cs, &[]);
+    let witness = witness_calc.calc(0, &HashMap::new()).unwrap();
+
+    // The column is committed before the proof is made, by another party.
+    let values = witness[a.index()].clone().unwrap();
+    let blind = Blind(Fr::from(7));
+    let commitment: G1Affine = params
+        .commit_lagrange(&vk.get_domain().lagrange_from_vec(values), blind)
+        .into();
+
+    let prove = |commitment: G1Affine, rng: &mut BlockRng<OneNg>| {
+        let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+        let mut prover = ProverV2Single::<
+            KZGCommitmentScheme<Bn256>,
+            ProverSHPLONK<'_, Bn256>,
+            _,
+            _,
+            _,
+        >::new(&params, &pk, &[], rng, &mut transcript)?;
+        prover.set_external_commitments(&[ExternalCommitment {
+            column: a,
+            commitment,
+            blind,
+        }])?;
+        prover.commit_phase(0, witness.clone())?;
+        prover.create_proof()?;
+        Ok::<_, Error>(transcript.finalize())
+    };
+
+    let proof = prove(commitment, &mut rng).expect("proof generation should not fail");
+
+    // The proof sends the external commitment as the commitment to the column.
+    let mut verifier_transcript =
+        Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof.as_slice());
+    assert_eq!(verifier_transcript.read_point().unwrap(), commitment);
+
+    let mut verifier_transcript =
+        Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof.as_slice());
+    verify_proof_single::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+        &params,
+        &vk,
+        SingleStrategy::new(verifier_params),
+        &[],
+        &mut verifier_transcript,
+    )
+    .expect("verify succeeds");
+
+    // A commitment to other values is rejected by the prover.
+    let other: G1Affine = params
+        .commit_lagrange(&vk.get_domain().empty_lagrange(), blind)
+        .into();
+    assert!(matches!(prove(other, &mut rng), Err(Error::Other(_))));
+}
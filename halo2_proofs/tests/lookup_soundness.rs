@@ -0,0 +1,230 @@
+//! Test vectors of lookup witnesses that the honest prover refuses to prove, injected with
+//! `ProverV2Single::inject_lookup_witness` to check that the verifier rejects their proofs.
+
+use std::collections::HashMap;
+
+use halo2_backend::plonk::prover::ProverV2Single;
+use halo2_frontend::circuit::{compile_circuit, WitnessCalculator};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    dev::lookup_injection::LookupInjection,
+    plonk::{
+        keygen_pk, keygen_vk, verify_proof, Advice, Circuit, Column, ConstraintSystem, Error,
+        Fixed, ProvingKey,
+    },
+    poly::{
+        commitment::ParamsProver,
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG},
+            multiopen::{ProverSHPLONK, VerifierSHPLONK},
+            strategy::SingleStrategy,
+        },
+        Rotation,
+    },
+    transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+    },
+};
+use halo2curves::bn256::{Bn256, Fr, G1Affine};
+use rand_core::OsRng;
+
+const K: u32 = 4;
+/// The number of rows before the blinding rows, with the 5 blinding factors of the circuit.
+const USABLE_ROWS: usize = 10;
+const TABLE_SIZE: u64 = 8;
+
+/// Looks the values of an advice column up in a fixed table of `0..TABLE_SIZE`.
+#[derive(Clone, Default)]
+struct RangeCircuit {
+    inputs: Vec<u64>,
+}
+
+impl Circuit<Fr> for RangeCircuit {
+    type Config = (Column<Advice>, Column<Fixed>);
+    type FloorPlanner = SimpleFloorPlanner;
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        let input = meta.advice_column();
+        let table = meta.fixed_column();
+        meta.lookup_any("range", |meta| {
+            vec![(
+                meta.query_advice(input, Rotation::cur()),
+                meta.query_fixed(table, Rotation::cur()),
+            )]
+        });
+        (input, table)
+    }
+
+    fn synthesize(
+        &self,
+        (input, table): Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "range",
+            |mut region| {
+                for value in 0..TABLE_SIZE {
+                    region.assign_fixed(
+                        || "table",
+                        table,
+                        value as usize,
+                        || Value::known(Fr::from(value)),
+                    )?;
+                }
+                for (row, value) in self.inputs.iter().enumerate() {
+                    region.assign_advice(
+                        || "input",
+                        input,
+                        row,
+                        || Value::known(Fr::from(*value)),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+fn values(values: &[u64]) -> Vec<Fr> {
+    values.iter().copied().map(Fr::from).collect()
+}
+
+fn setup() -> (ParamsKZG<Bn256>, ProvingKey<G1Affine>) {
+    let params = ParamsKZG::<Bn256>::setup(K, OsRng);
+    let circuit = RangeCircuit::default();
+    let vk = keygen_vk(&params, &circuit).unwrap();
+    let pk = keygen_pk(&params, vk, &circuit).unwrap();
+    (params, pk)
+}
+
+/// Proves `inputs`, committing to the permuted expressions of `injection` if it is given.
+fn prove(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    inputs: &[u64],
+    injection: Option<LookupInjection<Fr>>,
+) -> Result<Vec<u8>, Error> {
+    let circuit = RangeCircuit {
+        inputs: inputs.to_vec(),
+    };
+    let (_, config, cs) = compile_circuit(K, &circuit, pk.get_vk().compress_selectors)?;
+    let mut witness_calc = WitnessCalculator::new(K, &circuit, &config, &cs, &[]);
+    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+    let mut prover =
+        ProverV2Single::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _>::new(
+            params,
+            pk,
+            &[],
+            OsRng,
+            &mut transcript,
+        )?;
+    if let Some(injection) = injection {
+        prover.inject_lookup_witness(0, injection)?;
+    }
+    let mut challenges = HashMap::new();
+    for phase in 0..cs.phases().count() {
+        let witness = witness_calc.calc(phase as u8, &challenges)?;
+        challenges = prover.commit_phase(phase as u8, witness)?;
+    }
+    prover.create_proof()?;
+    Ok(transcript.finalize())
+}
+
+fn verifies(params: &ParamsKZG<Bn256>, pk: &ProvingKey<G1Affine>, proof: &[u8]) -> bool {
+    let verifier_params = params.verifier_params();
+    let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof);
+    verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+        verifier_params,
+        pk.get_vk(),
+        SingleStrategy::new(verifier_params),
+        &[&[]],
+        &mut transcript,
+    )
+    .is_ok()
+}
+
+#[test]
+fn honest_witness() {
+    let (params, pk) = setup();
+    let inputs = [1, 2, 3, 2];
+    assert!(verifies(
+        &params,
+        &pk,
+        &prove(&params, &pk, &inputs, None).unwrap()
+    ));
+
+    // A valid permutation other than the one of the honest prover is accepted, so that
+    // the rejections below are caused by the injected witnesses and not the injection.
+    // The inputs are followed by zeros and the table by zeros, up to the usable rows.
+    let injection = LookupInjection::Values {
+        input: values(&[1, 2, 2, 3, 0, 0, 0, 0, 0, 0]),
+        table: values(&[1, 2, 0, 3, 0, 0, 4, 5, 6, 7]),
+    };
+    assert!(verifies(
+        &params,
+        &pk,
+        &prove(&params, &pk, &inputs, Some(injection)).unwrap()
+    ));
+
+    // The unsorted inputs don't satisfy the constraints on the rows of A' and S'.
+    let proof = prove(&params, &pk, &inputs, Some(LookupInjection::Unpermuted)).unwrap();
+    assert!(!verifies(&params, &pk, &proof));
+}
+
+#[test]
+fn missing_input() {
+    let (params, pk) = setup();
+    let inputs = [1, 100];
+    assert!(matches!(
+        prove(&params, &pk, &inputs, None),
+        Err(Error::ConstraintSystemFailure)
+    ));
+
+    for injection in [
+        LookupInjection::Unpermuted,
+        LookupInjection::Forged,
+        // S' has the missing input in place of the last row of the table.
+        LookupInjection::Values {
+            input: values(&[1, 100, 0, 0, 0, 0, 0, 0, 0, 0]),
+            table: values(&[1, 100, 0, 0, 0, 2, 3, 4, 5, 6]),
+        },
+    ] {
+        let proof = prove(&params, &pk, &inputs, Some(injection.clone())).unwrap();
+        assert!(!verifies(&params, &pk, &proof), "{:?}", injection);
+    }
+}
+
+#[test]
+fn invalid_injection() {
+    let (params, pk) = setup();
+    assert_eq!(
+        USABLE_ROWS,
+        (1 << K) - pk.get_vk().cs().blinding_factors() - 1
+    );
+
+    let injection = LookupInjection::Values {
+        input: values(&[0]),
+        table: values(&[0]),
+    };
+    assert!(prove(&params, &pk, &[], Some(injection)).is_err());
+
+    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+    let mut prover =
+        ProverV2Single::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _>::new(
+            &params,
+            &pk,
+            &[],
+            OsRng,
+            &mut transcript,
+        )
+        .unwrap();
+    assert!(prover
+        .inject_lookup_witness(1, LookupInjection::Forged)
+        .is_err());
+}
@@ -0,0 +1,243 @@
+//! Tests of the chunks of the permutation argument, whose permutation product polynomials
+//! are linked at the boundaries of the chunks.
+
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    plonk::{
+        create_proof, keygen_pk, keygen_vk, verify_proof, Advice, Any, Circuit, Column,
+        ConstraintSystem, Error, Fixed, Instance, ProvingKey,
+    },
+    poly::{
+        commitment::ParamsProver,
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG},
+            multiopen::{ProverSHPLONK, VerifierSHPLONK},
+            strategy::SingleStrategy,
+        },
+        Rotation,
+    },
+    transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+    },
+};
+use halo2curves::bn256::{Bn256, Fr, G1Affine};
+use rand_core::OsRng;
+
+const K: u32 = 4;
+const ADVICE_COLUMNS: usize = 4;
+const VALUE: u64 = 7;
+
+#[derive(Clone, Debug)]
+struct ChunksConfig {
+    advice: [Column<Advice>; ADVICE_COLUMNS],
+    instance: Column<Instance>,
+    fixed: Column<Fixed>,
+}
+
+/// Copies a value across the columns of the permutation argument, which are, in order,
+/// the advice columns, the instance column and the fixed column.
+///
+/// A gate of degree `DEGREE` sets the degree of the constraint system, and so the length
+/// of the chunks of the permutation argument.
+#[derive(Clone, Default)]
+struct ChunksCircuit<const DEGREE: usize> {
+    /// The advice column assigned another value than the one it is copied from.
+    tampered: Option<usize>,
+}
+
+impl<const DEGREE: usize> Circuit<Fr> for ChunksCircuit<DEGREE> {
+    type Config = ChunksConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        let advice = [(); ADVICE_COLUMNS].map(|_| meta.advice_column());
+        let instance = meta.instance_column();
+        let fixed = meta.fixed_column();
+        for column in advice {
+            meta.enable_equality(column);
+        }
+        meta.enable_equality(instance);
+        meta.enable_equality(fixed);
+
+        // The fixed column is never assigned, so that the gate is always satisfied.
+        let q = meta.fixed_column();
+        meta.create_gate("degree", |meta| {
+            let q = meta.query_fixed(q, Rotation::cur());
+            let product = (0..DEGREE - 1).fold(q, |product, i| {
+                product * meta.query_advice(advice[i % ADVICE_COLUMNS], Rotation::cur())
+            });
+            vec![product]
+        });
+
+        ChunksConfig {
+            advice,
+            instance,
+            fixed,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), Error> {
+        let first = layouter.assign_region(
+            || "copies",
+            |mut region| {
+                let mut cells = vec![];
+                for (index, column) in config.advice.iter().enumerate() {
+                    let value = if self.tampered == Some(index) {
+                        VALUE + 1
+                    } else {
+                        VALUE
+                    };
+                    let cell = region.assign_advice(
+                        || "advice",
+                        *column,
+                        0,
+                        || Value::known(Fr::from(value)),
+                    )?;
+                    cells.push(cell.cell());
+                }
+                for cell in &cells[1..] {
+                    region.constrain_equal(cells[0], *cell)?;
+                }
+                let fixed = region.assign_fixed(
+                    || "fixed",
+                    config.fixed,
+                    0,
+                    || Value::known(Fr::from(VALUE)),
+                )?;
+                region.constrain_equal(fixed.cell(), cells[ADVICE_COLUMNS - 1])?;
+                Ok(cells[0])
+            },
+        )?;
+        layouter.constrain_instance(first, config.instance, 0)
+    }
+}
+
+fn setup<const DEGREE: usize>() -> (ParamsKZG<Bn256>, ProvingKey<G1Affine>) {
+    let params = ParamsKZG::<Bn256>::setup(K, OsRng);
+    let circuit = ChunksCircuit::<DEGREE>::default();
+    let vk = keygen_vk(&params, &circuit).unwrap();
+    let pk = keygen_pk(&params, vk, &circuit).unwrap();
+    (params, pk)
+}
+
+fn verifies<const DEGREE: usize>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: ChunksCircuit<DEGREE>,
+    instance: u64,
+) -> bool {
+    let instance = [Fr::from(instance)];
+    let instances: &[&[Fr]] = &[&instance];
+
+    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+    create_proof::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _, _>(
+        params,
+        pk,
+        &[circuit],
+        &[instances],
+        OsRng,
+        &mut transcript,
+    )
+    .unwrap();
+    let proof = transcript.finalize();
+
+    let verifier_params = params.verifier_params();
+    let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof.as_slice());
+    verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+        verifier_params,
+        pk.get_vk(),
+        SingleStrategy::new(verifier_params),
+        &[instances],
+        &mut transcript,
+    )
+    .is_ok()
+}
+
+/// Returns the number of columns of each chunk of the permutation argument.
+fn chunk_lens(pk: &ProvingKey<G1Affine>) -> Vec<usize> {
+    pk.get_vk()
+        .permutation_chunks()
+        .map(|columns| columns.len())
+        .collect()
+}
+
+#[test]
+fn chunk_structure() {
+    let (_, pk) = setup::<3>();
+    assert_eq!(pk.get_vk().permutation_chunk_len(), 1);
+    assert_eq!(chunk_lens(&pk), vec![1; 6]);
+
+    let (_, pk) = setup::<4>();
+    assert_eq!(pk.get_vk().permutation_chunk_len(), 2);
+    assert_eq!(chunk_lens(&pk), vec![2, 2, 2]);
+
+    let (_, pk) = setup::<6>();
+    assert_eq!(pk.get_vk().permutation_chunk_len(), 4);
+    assert_eq!(chunk_lens(&pk), vec![4, 2]);
+
+    // The chunks cover the columns of the argument in order.
+    let columns: Vec<Column<Any>> = pk
+        .get_vk()
+        .permutation_chunks()
+        .flatten()
+        .copied()
+        .collect();
+    assert_eq!(columns, pk.get_vk().cs().permutation().get_columns());
+}
+
+#[test]
+fn cross_chunk_copies() {
+    fn check<const DEGREE: usize>() {
+        let (params, pk) = setup::<DEGREE>();
+        assert!(verifies(
+            &params,
+            &pk,
+            ChunksCircuit::<DEGREE>::default(),
+            VALUE
+        ));
+
+        // The prover with sanity checks refuses to prove the witnesses below, see
+        // `sanity_checks_reject_broken_copies`.
+        #[cfg(not(feature = "sanity-checks"))]
+        {
+            // The copies cross the boundaries of the chunks, except the one between the
+            // first two advice columns when the chunks have 2 columns or more.
+            for tampered in 0..ADVICE_COLUMNS {
+                let circuit = ChunksCircuit::<DEGREE> {
+                    tampered: Some(tampered),
+                };
+                assert!(
+                    !verifies(&params, &pk, circuit, VALUE),
+                    "degree {}, tampered column {}",
+                    DEGREE,
+                    tampered
+                );
+            }
+            let circuit = ChunksCircuit::<DEGREE>::default();
+            assert!(!verifies(&params, &pk, circuit, VALUE + 1));
+        }
+    }
+
+    check::<3>();
+    check::<4>();
+    check::<6>();
+}
+
+#[cfg(feature = "sanity-checks")]
+#[test]
+#[should_panic(expected = "permutation product is correctly constructed")]
+fn sanity_checks_reject_broken_copies() {
+    let (params, pk) = setup::<4>();
+    let circuit = ChunksCircuit::<4> { tampered: Some(2) };
+    verifies(&params, &pk, circuit, VALUE);
+}
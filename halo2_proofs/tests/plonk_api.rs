@@ -8,17 +8,22 @@ use halo2_proofs::circuit::{Cell, Layouter, SimpleFloorPlanner, Value};
 use halo2_proofs::dev::MockProver;
 use halo2_proofs::plonk::{
     create_proof as create_plonk_proof, keygen_pk, keygen_vk, verify_proof as verify_plonk_proof,
-    Advice, Assigned, Circuit, Column, ConstraintSystem, Error, Fixed, ProvingKey, TableColumn,
-    VerifyingKey,
+    Advice, Assigned, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, Instance,
+    ProofBuilder, ProvingKey, TableColumn, VerifierBuilder, VerifyingKey,
 };
 use halo2_proofs::poly::commitment::{CommitmentScheme, ParamsProver, Prover, Verifier};
+use halo2_proofs::poly::kzg::commitment::{KZGCommitmentScheme, ParamsKZG};
+use halo2_proofs::poly::kzg::multiopen::{ProverSHPLONK, VerifierSHPLONK};
+use halo2_proofs::poly::kzg::strategy::SingleStrategy;
 use halo2_proofs::poly::Rotation;
 use halo2_proofs::poly::VerificationStrategy;
 use halo2_proofs::transcript::{
     Blake2bRead, Blake2bWrite, Challenge255, EncodedChallenge, TranscriptReadBuffer,
     TranscriptWriterBuffer,
 };
-use rand_core::{OsRng, RngCore};
+use halo2curves::bn256::{Bn256, Fr, G1Affine};
+use rand_chacha::ChaCha20Rng;
+use rand_core::{OsRng, RngCore, SeedableRng};
 use std::marker::PhantomData;
 
 #[test]
@@ -1024,3 +1029,2106 @@ fn plonk_api() {
     test_plonk_api_gwc();
     test_plonk_api_shplonk();
 }
+
+/// Constrains the cells of an advice column to be bits, looks them up in a fixed column
+/// and copies the first one to an instance column, so that the proofs of the tests below
+/// have a gate, a lookup and a permutation.
+#[derive(Clone)]
+struct BitsCircuit<F: Field> {
+    bits: Vec<Value<F>>,
+}
+
+impl<F: Field> BitsCircuit<F> {
+    /// Returns the circuit assigning `bits`, whose public input is the first one.
+    fn new(bits: &[bool]) -> Self {
+        let bits = bits
+            .iter()
+            .map(|bit| Value::known(if *bit { F::ONE } else { F::ZERO }));
+        Self {
+            bits: bits.collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BitsConfig {
+    advice: Column<Advice>,
+    table: Column<Fixed>,
+    instance: Column<Instance>,
+}
+
+impl<F: Field> Circuit<F> for BitsCircuit<F> {
+    type Config = BitsConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            bits: vec![Value::unknown(); self.bits.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = meta.advice_column();
+        let table = meta.fixed_column();
+        let instance = meta.instance_column();
+        meta.enable_equality(advice);
+        meta.enable_equality(instance);
+        meta.create_gate("bit", |meta| {
+            let a = meta.query_advice(advice, Rotation::cur());
+            vec![a.clone() * (a - Expression::Constant(F::ONE))]
+        });
+        meta.lookup_any("bit in table", |meta| {
+            let a = meta.query_advice(advice, Rotation::cur());
+            let table = meta.query_fixed(table, Rotation::cur());
+            vec![(a, table)]
+        });
+        BitsConfig {
+            advice,
+            table,
+            instance,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let first = layouter.assign_region(
+            || "bits",
+            |mut region| {
+                for (row, value) in [F::ZERO, F::ONE].into_iter().enumerate() {
+                    region.assign_fixed(|| "table", config.table, row, || Value::known(value))?;
+                }
+                let cells = self
+                    .bits
+                    .iter()
+                    .enumerate()
+                    .map(|(row, bit)| region.assign_advice(|| "bit", config.advice, row, || *bit))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(cells[0].cell())
+            },
+        )?;
+        layouter.constrain_instance(first, config.instance, 0)
+    }
+}
+
+/// The builders of the proofs of the tests, with KZG and SHPLONK over BN256.
+type KzgProofBuilder<'a, C> =
+    ProofBuilder<'a, 'a, KZGCommitmentScheme<Bn256>, ProverSHPLONK<'a, Bn256>, C>;
+type KzgVerifierBuilder<'a> =
+    VerifierBuilder<'a, 'a, KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'a, Bn256>>;
+
+/// Generates the proving key of `circuit`.
+fn keygen<C: Circuit<Fr>>(params: &ParamsKZG<Bn256>, circuit: &C) -> ProvingKey<G1Affine> {
+    let circuit = circuit.without_witnesses();
+    let vk = keygen_vk(params, &circuit).unwrap();
+    keygen_pk(params, vk, &circuit).unwrap()
+}
+
+/// Creates a proof of `circuits`, with the options under test set by `options` on the
+/// builder. The proofs of the same inputs and options are the same.
+fn prove<'a, C: Circuit<Fr>>(
+    params: &'a ParamsKZG<Bn256>,
+    pk: &'a ProvingKey<G1Affine>,
+    circuits: &'a [C],
+    instances: &'a [&'a [&'a [Fr]]],
+    options: impl FnOnce(KzgProofBuilder<'a, C>) -> KzgProofBuilder<'a, C>,
+) -> Result<Vec<u8>, Error> {
+    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+    options(ProofBuilder::new(params, pk, circuits, instances))
+        .create(ChaCha20Rng::seed_from_u64(0xdeadbeef), &mut transcript)?;
+    Ok(transcript.finalize())
+}
+
+/// Verifies `proof`, with the options under test set by `options` on the builder.
+fn verify<'a>(
+    params: &'a ParamsKZG<Bn256>,
+    vk: &'a VerifyingKey<G1Affine>,
+    instances: &'a [&'a [&'a [Fr]]],
+    proof: &[u8],
+    options: impl FnOnce(KzgVerifierBuilder<'a>) -> KzgVerifierBuilder<'a>,
+) -> Result<(), Error> {
+    let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof);
+    options(VerifierBuilder::new(
+        params.verifier_params(),
+        vk,
+        instances,
+    ))
+    .verify(
+        SingleStrategy::new(params.verifier_params()),
+        &mut transcript,
+    )
+}
+
+#[test]
+fn blinded_instance_column() {
+    use halo2_proofs::plonk::Selector;
+    use halo2_proofs::poly::ipa::commitment::{IPACommitmentScheme, ParamsIPA};
+    use halo2_proofs::poly::ipa::multiopen::{ProverIPA, VerifierIPA};
+    use halo2_proofs::poly::ipa::strategy::AccumulatorStrategy;
+    use halo2curves::pasta::{EqAffine, Fp};
+
+    const K: u32 = 4;
+
+    /// Checks that an advice cell is the sum of a public and a hidden instance value.
+    #[derive(Clone)]
+    struct MyCircuit<F: Field> {
+        sum: Value<F>,
+    }
+
+    impl<F: Field> Circuit<F> for MyCircuit<F> {
+        type Config = (Selector, Column<Advice>, Column<Instance>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                sum: Value::unknown(),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let s = meta.selector();
+            let a = meta.advice_column();
+            let public = meta.instance_column();
+            let hidden = meta.blinded_instance_column();
+            meta.create_gate("sum", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                let public = meta.query_instance(public, Rotation::cur());
+                let hidden = meta.query_instance(hidden, Rotation::cur());
+                vec![s * (public + hidden - a)]
+            });
+            (s, a, public, hidden)
+        }
+
+        fn synthesize(
+            &self,
+            (s, a, _, _): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "sum",
+                |mut region| {
+                    s.enable(&mut region, 0)?;
+                    region.assign_advice(|| "a", a, 0, || self.sum)?;
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    let (public, hidden) = (Fp::from(2), Fp::from(3));
+    let circuit = MyCircuit {
+        sum: Value::known(public + hidden),
+    };
+    let prover = MockProver::run(K, &circuit, vec![vec![public], vec![hidden]]).unwrap();
+    assert_eq!(prover.verify(), Ok(()));
+
+    // The prover commits to the hidden column, and the verifier doesn't get its values.
+    let params = ParamsIPA::<EqAffine>::new(K);
+    let vk = keygen_vk(&params, &circuit.without_witnesses()).unwrap();
+    let pk = keygen_pk(&params, vk, &circuit.without_witnesses()).unwrap();
+    let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+    create_plonk_proof::<IPACommitmentScheme<_>, ProverIPA<_>, _, _, _, _>(
+        &params,
+        &pk,
+        &[circuit.clone()],
+        &[&[&[public], &[hidden]]],
+        OsRng,
+        &mut transcript,
+    )
+    .unwrap();
+    let proof = transcript.finalize();
+
+    let verify_ipa = |instances: &[&[Fp]]| {
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        verify_plonk_proof::<_, VerifierIPA<_>, _, _, _>(
+            params.verifier_params(),
+            pk.get_vk(),
+            AccumulatorStrategy::new(params.verifier_params()),
+            &[instances],
+            &mut transcript,
+        )
+    };
+    assert!(verify_ipa(&[&[public], &[]]).unwrap().finalize());
+    assert!(!verify_ipa(&[&[public + Fp::ONE], &[]]).map_or(false, |strategy| strategy.finalize()));
+    assert_matches!(
+        verify_ipa(&[&[public], &[hidden]]),
+        Err(Error::InvalidInstances)
+    );
+
+    // Schemes that hash instance values into the transcript can't hide them.
+    let params = ParamsKZG::<Bn256>::new(K);
+    let circuit = MyCircuit {
+        sum: Value::known(Fr::from(5)),
+    };
+    let pk = keygen(&params, &circuit);
+    assert_matches!(
+        prove(
+            &params,
+            &pk,
+            &[circuit],
+            &[&[&[Fr::from(2)], &[Fr::from(3)]]],
+            |builder| builder
+        ),
+        Err(Error::Other(_))
+    );
+}
+
+#[test]
+fn extra_queries() {
+    use halo2_proofs::plonk::ExtraQuery;
+    use halo2_proofs::poly::kzg::strategy::AccumulatorStrategy;
+
+    const K: u32 = 4;
+
+    let circuit = BitsCircuit::new(&[true, false, true, true]);
+    let config = BitsCircuit::<Fr>::configure(&mut ConstraintSystem::default());
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuit);
+    let instances: &[&[&[Fr]]] = &[&[&[Fr::ONE]]];
+
+    // The evaluations are returned by the prover and the verifier, so the builders are
+    // used directly.
+    let create = |queries: &[ExtraQuery<Fr>]| {
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        ProofBuilder::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _>::new(
+            &params,
+            &pk,
+            &[circuit.clone()],
+            instances,
+        )
+        .with_queries(queries)
+        .create(OsRng, &mut transcript)
+        .map(|evals| (evals, transcript.finalize()))
+    };
+    let queries = [
+        ExtraQuery::new(config.advice, Fr::from(7)),
+        ExtraQuery::new(config.table, Fr::from(7)),
+        ExtraQuery::new(config.advice, Fr::from(11)),
+    ];
+    let (evals, proof) = create(&queries).unwrap();
+
+    let check = |queries: &[ExtraQuery<Fr>]| {
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        VerifierBuilder::<KZGCommitmentScheme<_>, VerifierSHPLONK<_>>::new(
+            params.verifier_params(),
+            pk.get_vk(),
+            instances,
+        )
+        .with_queries(queries)
+        .verify_with_evaluations(
+            AccumulatorStrategy::new(params.verifier_params()),
+            &mut transcript,
+        )
+    };
+    let (strategy, verified_evals) = check(&queries).unwrap();
+    assert!(strategy.finalize());
+    assert_eq!(verified_evals, evals);
+
+    // The points are bound to the transcript.
+    let mut other_queries = queries;
+    other_queries[2] = ExtraQuery::new(config.advice, Fr::from(13));
+    assert!(!check(&other_queries).map_or(false, |(strategy, _)| strategy.finalize()));
+
+    // Points of the evaluation domain would reveal the values of cells.
+    assert_matches!(
+        create(&[ExtraQuery::new(config.advice, Fr::ONE)]),
+        Err(Error::Other(_))
+    );
+}
+
+#[cfg(feature = "vanishing-pieces")]
+#[test]
+fn vanishing_pieces() {
+    use group::GroupEncoding;
+
+    const K: u32 = 4;
+
+    let circuit = BitsCircuit::new(&[true, false]);
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuit);
+    let instances: &[&[&[Fr]]] = &[&[&[Fr::ONE]]];
+
+    let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+    let pieces = ProofBuilder::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _>::new(
+        &params,
+        &pk,
+        &[circuit],
+        instances,
+    )
+    .create_with_vanishing_pieces(OsRng, &mut transcript)
+    .unwrap();
+    let proof = transcript.finalize();
+    assert!(verify(&params, pk.get_vk(), instances, &proof, |builder| builder).is_ok());
+
+    // The pieces are written to the proof one after the other.
+    assert_eq!(pieces.h_commitments.len(), pieces.h_blinds.len());
+    assert!(!pieces.h_commitments.is_empty());
+    let written: Vec<u8> = pieces
+        .h_commitments
+        .iter()
+        .flat_map(|commitment| commitment.to_bytes().as_ref().to_vec())
+        .collect();
+    assert!(proof
+        .windows(written.len())
+        .any(|window| window == &written[..]));
+}
+
+#[test]
+fn verifier_trace() {
+    use halo2_proofs::plonk::Opening;
+    use halo2_proofs::transcript::TranscriptRead;
+
+    const K: u32 = 4;
+
+    /// Counts from 0 in an advice column, in the rows where a fixed column is set.
+    #[derive(Clone)]
+    struct MyCircuit;
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = (Column<Advice>, Column<Fixed>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let a = meta.advice_column();
+            let f = meta.fixed_column();
+            meta.create_gate("count", |meta| {
+                let f = meta.query_fixed(f, Rotation::cur());
+                let a_cur = meta.query_advice(a, Rotation::cur());
+                let a_next = meta.query_advice(a, Rotation::next());
+                vec![f * (a_next - a_cur - Expression::Constant(Fr::ONE))]
+            });
+            (a, f)
+        }
+
+        fn synthesize(
+            &self,
+            (a, f): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "count",
+                |mut region| {
+                    for offset in 0..4 {
+                        if offset < 3 {
+                            region.assign_fixed(|| "f", f, offset, || Value::known(Fr::ONE))?;
+                        }
+                        let value = Value::known(Fr::from(offset as u64));
+                        region.assign_advice(|| "a", a, offset, || value)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &MyCircuit);
+    let proof = prove(&params, &pk, &[MyCircuit], &[&[]], |builder| builder).unwrap();
+
+    let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&proof[..]);
+    let (_, trace) = VerifierBuilder::<KZGCommitmentScheme<_>, VerifierSHPLONK<_>>::new(
+        params.verifier_params(),
+        pk.get_vk(),
+        &[&[]],
+    )
+    .verify_with_trace(
+        SingleStrategy::new(params.verifier_params()),
+        &mut transcript,
+    )
+    .unwrap();
+
+    // The advice commitment is the first point of the proof.
+    let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&proof[..]);
+    assert_eq!(
+        trace.advice_commitments,
+        vec![vec![transcript.read_point().unwrap()]]
+    );
+    assert!(trace.instance_commitments[0].is_empty());
+
+    // The advice column is opened at x and at the next row, with the evaluations read
+    // from the proof.
+    let omega = pk.get_vk().get_domain().get_omega();
+    assert_eq!(trace.advice_evals[0].len(), 2);
+    for (point, eval) in [trace.x, trace.x * omega]
+        .into_iter()
+        .zip(trace.advice_evals[0].iter())
+    {
+        assert!(trace.openings.contains(&Opening {
+            commitment: trace.advice_commitments[0][0],
+            point,
+            eval: *eval,
+        }));
+    }
+    assert_eq!(trace.fixed_evals.len(), 1);
+
+    // The gate evaluates at x to its expression over the evaluations of the trace.
+    let vk = pk.get_vk();
+    let (a_cur, a_next) = (trace.advice_evals[0][0], trace.advice_evals[0][1]);
+    assert_eq!(
+        vk.evaluate_gates_at(
+            trace.x,
+            &trace.advice_evals[0],
+            &trace.fixed_evals,
+            &trace.instance_evals[0],
+            &trace.challenges,
+        )
+        .unwrap(),
+        vec![trace.fixed_evals[0] * (a_next - a_cur - Fr::ONE)]
+    );
+    assert!(vk
+        .evaluate_gates_at(trace.x, &[a_cur], &trace.fixed_evals, &[], &[])
+        .is_err());
+}
+
+#[test]
+fn recursion_witness() {
+    use group::{Curve, Group};
+    use halo2_proofs::plonk::recursion::{recursion_witness, VerifyingKeyConstants};
+    use halo2_proofs::poly::kzg::multiopen::{ProverGWC, VerifierGWC};
+    use halo2curves::bn256::G1;
+
+    const K: u32 = 4;
+
+    let circuit = BitsCircuit::new(&[true, false, true]);
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuit);
+
+    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+    create_plonk_proof::<KZGCommitmentScheme<_>, ProverGWC<_>, _, _, _, _>(
+        &params,
+        &pk,
+        &[circuit],
+        &[&[&[Fr::ONE]]],
+        OsRng,
+        &mut transcript,
+    )
+    .unwrap();
+    let proof = transcript.finalize();
+
+    let witness = |public: Fr| {
+        let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&proof[..]);
+        recursion_witness::<_, VerifierGWC<_>, _, _>(
+            params.verifier_params(),
+            pk.get_vk(),
+            &[&[&[public]]],
+            &mut transcript,
+        )
+    };
+
+    // The witness is deterministic, and its deferred pairing check holds.
+    let valid = witness(Fr::ONE).unwrap();
+    assert!(valid.check(params.verifier_params()));
+    assert_eq!(valid.to_bytes(), witness(Fr::ONE).unwrap().to_bytes());
+    assert_eq!(valid.trace.advice_commitments.len(), 1);
+
+    // The accumulator points are the results of their multiscalar multiplications.
+    for (terms, point) in [
+        (&valid.left_terms, valid.left),
+        (&valid.right_terms, valid.right),
+    ] {
+        let sum = terms
+            .iter()
+            .fold(G1::identity(), |acc, (scalar, base)| acc + *base * scalar);
+        assert_eq!(sum.to_affine(), point);
+    }
+
+    // The check fails for other public inputs.
+    assert!(!witness(Fr::ZERO).map_or(false, |witness| witness.check(params.verifier_params())));
+
+    // The constants of the verifying key are those of the key, and only of it.
+    let vk = pk.get_vk();
+    let constants = VerifyingKeyConstants::new(vk);
+    assert!(constants.check(vk).is_ok());
+    assert_eq!(constants.transcript_repr(), vk.transcript_repr());
+    assert_eq!(constants.scalars[constants.layout.n], Fr::from(1 << K));
+    assert_eq!(
+        constants.permutation_commitments(),
+        &vk.permutation().commitments()[..]
+    );
+    assert_eq!(
+        constants.to_bytes().len(),
+        4 + 32 * constants.scalars.len() + 4 + 32 * constants.points.len()
+    );
+    let other = vk.clone().with_protocol_version(0).unwrap();
+    assert!(constants.check(&other).is_err());
+}
+
+#[test]
+fn verifier_metrics() {
+    use halo2_proofs::plonk::VerifierMetrics;
+
+    const K: u32 = 4;
+
+    let circuit = BitsCircuit::new(&[true, false]);
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuit);
+    let instances: &[&[&[Fr]]] = &[&[&[Fr::ONE]]];
+    let proof = prove(&params, &pk, &[circuit], instances, |builder| builder).unwrap();
+
+    // The metrics add up over the verifications they are passed to.
+    let mut metrics = VerifierMetrics::default();
+    for _ in 0..2 {
+        let metrics = &mut metrics;
+        verify(&params, pk.get_vk(), instances, &proof, move |builder| {
+            builder.with_metrics(metrics)
+        })
+        .unwrap();
+    }
+    assert_eq!(metrics.verifications, 2);
+    assert!(metrics.pairing > std::time::Duration::ZERO);
+    assert_eq!(
+        metrics.total(),
+        metrics.transcript + metrics.evaluation + metrics.msm + metrics.pairing
+    );
+}
+
+#[test]
+fn proofs_independent_of_threads() {
+    const K: u32 = 6;
+
+    let circuit = BitsCircuit::new(&[true, false, true, false, true, true, false, true]);
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuit);
+    let instances: &[&[&[Fr]]] = &[&[&[Fr::ONE]]];
+
+    let prove_on = |num_threads: usize| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .unwrap()
+            .install(|| {
+                prove(&params, &pk, &[circuit.clone()], instances, |builder| {
+                    builder
+                })
+                .unwrap()
+            })
+    };
+
+    // The proof only depends on the randomness, so it is the same whether it is created
+    // on one thread, on several, or without the `multicore` feature.
+    let proof = prove_on(1);
+    assert_eq!(proof, prove_on(3));
+    assert_eq!(proof, prove_on(8));
+}
+
+#[test]
+fn proofs_with_domain() {
+    const K: u32 = 4;
+
+    let circuit = BitsCircuit::new(&[true, false]);
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuit);
+    let instances: &[&[&[Fr]]] = &[&[&[Fr::ONE]]];
+    let proof = prove(&params, &pk, &[circuit], instances, |builder| {
+        builder.with_domain(b"application a")
+    })
+    .unwrap();
+
+    let verifies = |label: Option<&[u8]>| {
+        verify(
+            &params,
+            pk.get_vk(),
+            instances,
+            &proof,
+            |builder| match label {
+                Some(label) => builder.with_domain(label),
+                None => builder,
+            },
+        )
+        .is_ok()
+    };
+
+    // The proof only verifies for the application it was created for.
+    assert!(verifies(Some(b"application a")));
+    assert!(!verifies(Some(b"application b")));
+    assert!(!verifies(None));
+}
+
+#[test]
+fn lookup_sliding_window() {
+    use halo2_proofs::dev::VerifyFailure;
+
+    const K: u32 = 6;
+
+    /// Checks that consecutive values of an advice column are consecutive squares, by
+    /// looking up each pair of them in windows over a fixed and an advice column.
+    #[derive(Clone)]
+    struct MyCircuit {
+        values: Vec<Value<Fr>>,
+    }
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = (Column<Fixed>, Column<Advice>, Column<Fixed>, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                values: vec![Value::unknown(); self.values.len()],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let q = meta.fixed_column();
+            let a = meta.advice_column();
+            let f = meta.fixed_column();
+            let t = meta.advice_column();
+            meta.lookup_any("fixed window", |meta| {
+                let q = meta.query_fixed(q, Rotation::cur());
+                let a_cur = meta.query_advice(a, Rotation::cur());
+                let a_next = meta.query_advice(a, Rotation::next());
+                let f_cur = meta.query_fixed(f, Rotation::cur());
+                let f_next = meta.query_fixed(f, Rotation::next());
+                vec![(q.clone() * a_cur, f_cur), (q * a_next, f_next)]
+            });
+            meta.lookup_any("advice window", |meta| {
+                let q = meta.query_fixed(q, Rotation::cur());
+                let a_cur = meta.query_advice(a, Rotation::cur());
+                let a_next = meta.query_advice(a, Rotation::next());
+                let t_cur = meta.query_advice(t, Rotation::cur());
+                let t_next = meta.query_advice(t, Rotation::next());
+                vec![(q.clone() * a_cur, t_cur), (q * a_next, t_next)]
+            });
+            (q, a, f, t)
+        }
+
+        fn synthesize(
+            &self,
+            (q, a, f, t): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "squares",
+                |mut region| {
+                    for row in 0..8 {
+                        let square = Fr::from(row as u64 * row as u64);
+                        region.assign_fixed(|| "f", f, row, || Value::known(square))?;
+                        region.assign_advice(|| "t", t, row, || Value::known(square))?;
+                    }
+                    Ok(())
+                },
+            )?;
+            layouter.assign_region(
+                || "values",
+                |mut region| {
+                    for (row, value) in self.values.iter().enumerate() {
+                        if row + 1 < self.values.len() {
+                            region.assign_fixed(|| "q", q, row, || Value::known(Fr::ONE))?;
+                        }
+                        region.assign_advice(|| "a", a, row, || *value)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    let circuit = |values: &[u64]| MyCircuit {
+        values: values
+            .iter()
+            .map(|value| Value::known(Fr::from(*value)))
+            .collect(),
+    };
+
+    // Each input pair is a row of the tables, which are windows over the squares.
+    let good = circuit(&[1, 4, 9, 16]);
+    assert_eq!(MockProver::run(K, &good, vec![]).unwrap().verify(), Ok(()));
+
+    // (4, 16) is not a window over the squares, in neither table.
+    let bad = circuit(&[1, 4, 16]);
+    let failures = MockProver::run(K, &bad, vec![])
+        .unwrap()
+        .verify()
+        .unwrap_err();
+    assert_eq!(failures.len(), 2);
+    assert_matches!(
+        failures[0],
+        VerifyFailure::Lookup {
+            lookup_index: 0,
+            ..
+        }
+    );
+    assert_matches!(
+        failures[1],
+        VerifyFailure::Lookup {
+            lookup_index: 1,
+            ..
+        }
+    );
+
+    // The real prover computes the same tables.
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &good);
+    let proof = prove(&params, &pk, &[good], &[&[]], |builder| builder).unwrap();
+    assert!(verify(&params, pk.get_vk(), &[&[]], &proof, |builder| builder).is_ok());
+
+    // It can't prove the inputs that aren't in the tables.
+    assert_matches!(
+        prove(&params, &pk, &[bad], &[&[]], |builder| builder),
+        Err(Error::ConstraintSystemFailure)
+    );
+}
+
+#[test]
+fn evaluation_proof() {
+    use halo2_proofs::plonk::{verify_evaluation_proof, EvaluationProof, Opening};
+    use halo2_proofs::SerdeFormat;
+
+    const K: u32 = 4;
+
+    let circuit = BitsCircuit::new(&[true, false]);
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuit);
+    let instances: &[&[Fr]] = &[&[Fr::ONE]];
+
+    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+    let evaluation_proof = ProofBuilder::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _>::new(
+        &params,
+        &pk,
+        &[circuit],
+        &[instances],
+    )
+    .create_with_evaluation_proof(OsRng, &mut transcript)
+    .unwrap();
+    let proof = transcript.finalize();
+
+    // The evaluation proof has the openings of the proof.
+    let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&proof[..]);
+    let (_, trace) = VerifierBuilder::<KZGCommitmentScheme<_>, VerifierSHPLONK<_>>::new(
+        params.verifier_params(),
+        pk.get_vk(),
+        &[instances],
+    )
+    .verify_with_trace(
+        SingleStrategy::new(params.verifier_params()),
+        &mut transcript,
+    )
+    .unwrap();
+    assert_eq!(evaluation_proof.openings, trace.openings);
+    assert!(evaluation_proof.openings.contains(&Opening {
+        commitment: trace.advice_commitments[0][0],
+        point: trace.x,
+        eval: trace.advice_evals[0][0],
+    }));
+
+    // It verifies on its own, after a round trip through its serialization.
+    let mut bytes = vec![];
+    evaluation_proof
+        .write(&mut bytes, SerdeFormat::RawBytes)
+        .unwrap();
+    let read = EvaluationProof::<G1Affine>::read(&mut &bytes[..], SerdeFormat::RawBytes).unwrap();
+    assert_eq!(read, evaluation_proof);
+    let verify_evaluations = |evaluation_proof: &EvaluationProof<G1Affine>| {
+        verify_evaluation_proof::<
+            KZGCommitmentScheme<_>,
+            VerifierSHPLONK<_>,
+            _,
+            Blake2bRead<_, _, Challenge255<_>>,
+            _,
+        >(
+            params.verifier_params(),
+            evaluation_proof,
+            SingleStrategy::new(params.verifier_params()),
+        )
+    };
+    assert!(verify_evaluations(&read).is_ok());
+
+    // Changing an evaluation invalidates it.
+    let mut tampered = evaluation_proof.clone();
+    tampered.openings[0].eval += Fr::ONE;
+    assert!(verify_evaluations(&tampered).is_err());
+    assert!(EvaluationProof::<G1Affine>::read(
+        &mut &bytes[..bytes.len() - 1],
+        SerdeFormat::RawBytes
+    )
+    .is_err());
+}
+
+#[test]
+fn artifacts_record_curve() {
+    use halo2_proofs::poly::commitment::Params;
+    use halo2_proofs::poly::ipa::commitment::ParamsIPA;
+    use halo2_proofs::SerdeFormat;
+    use halo2curves::pasta::{EqAffine, Fp};
+
+    let assert_mismatch = |error: std::io::Error, artifact: &str| {
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        let message = error.to_string();
+        assert!(
+            message.starts_with(&format!(
+                "the {artifact} was serialized for the curve bn256_g1"
+            )),
+            "{message}"
+        );
+        assert!(message.contains("for the curve vesta"), "{message}");
+    };
+
+    // Parameters.
+    let params = ParamsKZG::<Bn256>::new(4);
+    let mut params_bytes = vec![];
+    params.write(&mut params_bytes).unwrap();
+    assert_eq!(
+        ParamsKZG::<Bn256>::read(&mut &params_bytes[..])
+            .unwrap()
+            .k(),
+        4
+    );
+    assert_mismatch(
+        ParamsIPA::<EqAffine>::read(&mut &params_bytes[..]).unwrap_err(),
+        "parameters",
+    );
+
+    // Verifying and proving keys.
+    let pk = keygen(&params, &BitsCircuit::new(&[true]));
+    let vk_bytes = pk.get_vk().to_bytes(SerdeFormat::RawBytes);
+    assert!(VerifyingKey::<G1Affine>::from_bytes::<BitsCircuit<Fr>>(
+        &vk_bytes,
+        SerdeFormat::RawBytes,
+        #[cfg(feature = "circuit-params")]
+        (),
+    )
+    .is_ok());
+    assert_mismatch(
+        VerifyingKey::<EqAffine>::from_bytes::<BitsCircuit<Fp>>(
+            &vk_bytes,
+            SerdeFormat::RawBytes,
+            #[cfg(feature = "circuit-params")]
+            (),
+        )
+        .unwrap_err(),
+        "verifying key",
+    );
+    assert_mismatch(
+        ProvingKey::<EqAffine>::from_bytes::<BitsCircuit<Fp>>(
+            &pk.to_bytes(SerdeFormat::RawBytes),
+            SerdeFormat::RawBytes,
+            #[cfg(feature = "circuit-params")]
+            (),
+        )
+        .unwrap_err(),
+        "verifying key",
+    );
+}
+
+#[test]
+fn keys_with_checksums() {
+    use halo2_proofs::SerdeFormat;
+
+    let params = ParamsKZG::<Bn256>::new(4);
+    let pk = keygen(&params, &BitsCircuit::new(&[true]));
+    let vk = pk.get_vk();
+    let format = SerdeFormat::RawBytesUnchecked;
+
+    let mut vk_bytes = vec![];
+    vk.write_with_checksums(&mut vk_bytes, format).unwrap();
+    let read_vk = |bytes: &[u8]| {
+        VerifyingKey::<G1Affine>::read::<_, BitsCircuit<Fr>>(
+            &mut &bytes[..],
+            format,
+            #[cfg(feature = "circuit-params")]
+            (),
+        )
+    };
+    assert_eq!(
+        read_vk(&vk_bytes).unwrap().transcript_repr(),
+        vk.transcript_repr()
+    );
+
+    let mut pk_bytes = vec![];
+    pk.write_with_checksums(&mut pk_bytes, format).unwrap();
+    let read_pk = |bytes: &[u8]| {
+        ProvingKey::<G1Affine>::read::<_, BitsCircuit<Fr>>(
+            &mut &bytes[..],
+            format,
+            #[cfg(feature = "circuit-params")]
+            (),
+        )
+    };
+    assert_eq!(
+        read_pk(&pk_bytes).unwrap().to_bytes(format),
+        pk.to_bytes(format)
+    );
+
+    // Corrupted values, which aren't checked when read unchecked, are detected by the
+    // checksums of their sections.
+    let assert_corrupted = |result: std::io::Result<_>| {
+        let message = result.map(|_| ()).unwrap_err().to_string();
+        assert!(message.contains("checksum"), "{message}");
+    };
+    let mut corrupted = vk_bytes.clone();
+    let position = corrupted.len() - 40;
+    corrupted[position] ^= 1;
+    assert_corrupted(read_vk(&corrupted));
+    for position in [vk_bytes.len() + 8, pk_bytes.len() - 40] {
+        let mut corrupted = pk_bytes.clone();
+        corrupted[position] ^= 1;
+        assert_corrupted(read_pk(&corrupted));
+    }
+}
+
+#[test]
+fn update_fixed_table() {
+    use halo2_frontend::circuit::compile_circuit;
+    use halo2_proofs::SerdeFormat;
+
+    const K: u32 = 5;
+
+    /// Looks up an advice column in a table of the multiples of `step`.
+    #[derive(Clone)]
+    struct MyCircuit {
+        step: u64,
+    }
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = (Column<Advice>, TableColumn);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let a = meta.advice_column();
+            let table = meta.lookup_table_column();
+            meta.lookup("multiple", |meta| {
+                vec![(meta.query_advice(a, Rotation::cur()), table)]
+            });
+            (a, table)
+        }
+
+        fn synthesize(
+            &self,
+            (a, table): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            layouter.assign_table(
+                || "multiples",
+                |mut t| {
+                    for i in 0..8 {
+                        t.assign_cell(
+                            || "multiple",
+                            table,
+                            i,
+                            || Value::known(Fr::from(i as u64 * self.step)),
+                        )?;
+                    }
+                    Ok(())
+                },
+            )?;
+            layouter.assign_region(
+                || "a",
+                |mut region| {
+                    region.assign_advice(|| "a", a, 0, || Value::known(Fr::from(3 * self.step)))
+                },
+            )?;
+            Ok(())
+        }
+    }
+
+    let params = ParamsKZG::<Bn256>::new(K);
+    let old = MyCircuit { step: 1 };
+    let new = MyCircuit { step: 7 };
+    let mut pk = keygen(&params, &old);
+    let new_pk = keygen(&params, &new);
+
+    // The table is the only fixed column.
+    let (compiled, _, _) = compile_circuit(K, &new, false).unwrap();
+    let (table, _) = pk.get_vk().fixed_column_commitments().next().unwrap();
+    pk.update_fixed_table(&params, table, compiled.preprocessing.fixed[0].clone())
+        .unwrap();
+    assert_eq!(
+        pk.get_vk().transcript_repr(),
+        new_pk.get_vk().transcript_repr()
+    );
+    assert_eq!(
+        pk.to_bytes(SerdeFormat::RawBytes),
+        new_pk.to_bytes(SerdeFormat::RawBytes)
+    );
+
+    // Proofs of the new circuit verify with the updated key.
+    let proof = prove(&params, &pk, &[new], &[&[]], |builder| builder).unwrap();
+    assert!(verify(&params, pk.get_vk(), &[&[]], &proof, |builder| builder).is_ok());
+
+    // The table must have a value for each row.
+    assert!(pk
+        .update_fixed_table(&params, table, vec![Fr::ONE; 3])
+        .is_err());
+}
+
+#[test]
+fn pasta_cycle_accumulation() {
+    use halo2_proofs::arithmetic::CurveAffine;
+    use halo2_proofs::plonk::cycle::{
+        accumulator_from_instances, accumulator_to_instances, CurveCycle, CycleProof,
+        DeferredStrategy, Pasta,
+    };
+    use halo2_proofs::poly::ipa::commitment::{IPACommitmentScheme, ParamsIPA};
+    use halo2_proofs::poly::ipa::multiopen::{ProverIPA, VerifierIPA};
+    use halo2_proofs::poly::ipa::strategy::Accumulator;
+    use halo2curves::pasta::{pallas, vesta};
+
+    const K: u32 = 5;
+
+    /// Copies its instances into an advice column, standing for a verifier of the proofs
+    /// of the other curve whose accumulator the instances encode.
+    #[derive(Clone)]
+    struct Relay {
+        rows: usize,
+    }
+
+    impl<F: Field> Circuit<F> for Relay {
+        type Config = (Column<Advice>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let a = meta.advice_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(a);
+            meta.enable_equality(instance);
+            (a, instance)
+        }
+
+        fn synthesize(
+            &self,
+            (a, instance): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "relay",
+                |mut region| {
+                    for row in 0..self.rows {
+                        region.assign_advice_from_instance(|| "a", instance, row, a, row)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    /// Proves `Relay` over `C` with `instances`, and returns the proof with the
+    /// accumulator of its verification.
+    fn prove<C: CurveAffine>(
+        params: &ParamsIPA<C>,
+        instances: &[C::Scalar],
+    ) -> (Vec<u8>, Accumulator<C>)
+    where
+        C::Scalar: FromUniformBytes<64> + WithSmallOrderMulGroup<3>,
+    {
+        let circuit = Relay {
+            rows: instances.len(),
+        };
+        let vk = keygen_vk(params, &circuit).unwrap();
+        let pk = keygen_pk(params, vk, &circuit).unwrap();
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        create_plonk_proof::<IPACommitmentScheme<_>, ProverIPA<_>, _, _, _, _>(
+            params,
+            &pk,
+            &[circuit],
+            &[&[instances]],
+            OsRng,
+            &mut transcript,
+        )
+        .unwrap();
+        let proof = transcript.finalize();
+
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let accumulator = verify_plonk_proof::<_, VerifierIPA<_>, _, _, _>(
+            params.verifier_params(),
+            pk.get_vk(),
+            DeferredStrategy::new(params.verifier_params()),
+            &[&[instances]],
+            &mut transcript,
+        )
+        .unwrap();
+        (proof, accumulator)
+    }
+
+    let vesta_params = ParamsIPA::<<Pasta as CurveCycle>::Secondary>::new(K);
+    let pallas_params = ParamsIPA::<<Pasta as CurveCycle>::Primary>::new(K);
+
+    // The first layer is proven over Vesta, and its accumulator is an instance of the
+    // second layer, proven over Pallas.
+    let (_, vesta_accumulator) = prove(
+        &vesta_params,
+        &[vesta::Scalar::from(1), vesta::Scalar::from(2)],
+    );
+    assert!(vesta_accumulator.check(&vesta_params));
+    let instances = accumulator_to_instances(&vesta_accumulator);
+    let (proof, pallas_accumulator) = prove(&pallas_params, &instances);
+    assert!(pallas_accumulator.check(&pallas_params));
+
+    let layer = CycleProof::<pallas::Affine, vesta::Affine>::new(proof, vesta_accumulator);
+    assert_eq!(layer.accumulator_instances(), instances);
+    let decoded =
+        accumulator_from_instances::<vesta::Affine>(&layer.accumulator_instances(), K as usize)
+            .unwrap();
+    assert_eq!(decoded.g, layer.accumulator.g);
+    assert_eq!(decoded.u_packed, layer.accumulator.u_packed);
+    assert!(decoded.check(&vesta_params));
+
+    // The accumulator of the second layer is in turn an instance of a proof over Vesta.
+    let instances = accumulator_to_instances(&pallas_accumulator);
+    let decoded = accumulator_from_instances::<pallas::Affine>(&instances, K as usize).unwrap();
+    assert!(decoded.check(&pallas_params));
+    let (_, accumulator) = prove(&vesta_params, &instances);
+    assert!(accumulator.check(&vesta_params));
+
+    // An accumulator is checked against the params of its curve.
+    let mut tampered = decoded;
+    tampered.u_packed[0] += pallas::Scalar::ONE;
+    assert!(!tampered.check(&pallas_params));
+}
+
+#[test]
+fn proofs_with_engine() {
+    use halo2_proofs::arithmetic::CurveAffine;
+    use halo2_proofs::plonk::{keygen_pk_with_engine, keygen_vk_with_engine};
+    use halo2_proofs::zal::traits::{FftAccel, MsmAccel};
+    use halo2_proofs::zal::{H2cEngine, PlonkEngineConfig};
+    use halo2_proofs::SerdeFormat;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const K: u32 = 6;
+
+    /// Forwards to `H2cEngine`, counting the calls.
+    #[derive(Clone, Default)]
+    struct CountingEngine {
+        msms: Arc<AtomicUsize>,
+        ffts: Arc<AtomicUsize>,
+    }
+
+    impl<C: CurveAffine> MsmAccel<C> for CountingEngine {
+        fn msm(&self, coeffs: &[C::Scalar], bases: &[C]) -> C::CurveExt {
+            self.msms.fetch_add(1, Ordering::Relaxed);
+            H2cEngine.msm(coeffs, bases)
+        }
+    }
+
+    impl<F: Field> FftAccel<F> for CountingEngine {
+        fn fft(&self, values: &mut [F], omega: F, log_n: u32) {
+            self.ffts.fetch_add(1, Ordering::Relaxed);
+            H2cEngine.fft(values, omega, log_n)
+        }
+    }
+
+    let counting = CountingEngine::default();
+    let engine = PlonkEngineConfig::new()
+        .set_msm(counting.clone())
+        .set_fft(counting.clone())
+        .build();
+
+    let circuit = BitsCircuit::new(&[false, true, false, true, true, false, true, false]);
+    let params = ParamsKZG::<Bn256>::new(K);
+    let vk = keygen_vk(&params, &circuit.without_witnesses()).unwrap();
+    let engine_vk = keygen_vk_with_engine(&engine, &params, &circuit.without_witnesses()).unwrap();
+    assert_eq!(
+        vk.to_bytes(SerdeFormat::RawBytes),
+        engine_vk.to_bytes(SerdeFormat::RawBytes)
+    );
+    let pk =
+        keygen_pk_with_engine(&engine, &params, engine_vk, &circuit.without_witnesses()).unwrap();
+    assert!(counting.msms.load(Ordering::Relaxed) > 0);
+    assert!(counting.ffts.load(Ordering::Relaxed) > 0);
+
+    let instances: &[&[&[Fr]]] = &[&[&[Fr::ZERO]]];
+    let proof = prove(&params, &pk, &[circuit.clone()], instances, |builder| {
+        builder
+    })
+    .unwrap();
+    let (msms, ffts) = (
+        counting.msms.load(Ordering::Relaxed),
+        counting.ffts.load(Ordering::Relaxed),
+    );
+    let engine_proof = prove(&params, &pk, &[circuit], instances, |builder| {
+        builder.with_engine(engine)
+    })
+    .unwrap();
+
+    // Every computation of the prover goes through the engine, whose results don't
+    // change the proof.
+    assert!(counting.msms.load(Ordering::Relaxed) > msms);
+    assert!(counting.ffts.load(Ordering::Relaxed) > ffts);
+    assert_eq!(proof, engine_proof);
+    assert!(
+        verify(&params, pk.get_vk(), instances, &engine_proof, |builder| {
+            builder
+        })
+        .is_ok()
+    );
+}
+
+#[test]
+fn proofs_with_witness_layout() {
+    use halo2_backend::plonk::{prover::ProverV2Single, WitnessLayout};
+    use halo2_frontend::circuit::{compile_circuit, WitnessCalculator};
+    use std::collections::HashMap;
+
+    const K: u32 = 6;
+    const WIDTH: usize = 5;
+
+    /// Constrains each advice column to be the next one plus one, copies the first column
+    /// to the last one some rows below and looks up the last column in a fixed column.
+    #[derive(Clone)]
+    struct WideCircuit;
+
+    impl Circuit<Fr> for WideCircuit {
+        type Config = (Vec<Column<Advice>>, Column<Fixed>, Column<Fixed>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let advice: Vec<_> = (0..WIDTH).map(|_| meta.advice_column()).collect();
+            let f = meta.fixed_column();
+            let table = meta.fixed_column();
+            for column in &advice {
+                meta.enable_equality(*column);
+            }
+            meta.create_gate("decrements", |meta| {
+                let f = meta.query_fixed(f, Rotation::cur());
+                advice
+                    .windows(2)
+                    .map(|pair| {
+                        let a = meta.query_advice(pair[0], Rotation::cur());
+                        let b = meta.query_advice(pair[1], Rotation::cur());
+                        (a - b - Expression::Constant(Fr::ONE)) * f.clone()
+                    })
+                    .collect::<Vec<_>>()
+            });
+            let last = advice[WIDTH - 1];
+            meta.lookup_any("last in table", |meta| {
+                let a = meta.query_advice(last, Rotation::cur());
+                let table = meta.query_fixed(table, Rotation::cur());
+                vec![(a, table)]
+            });
+            (advice, f, table)
+        }
+
+        fn synthesize(
+            &self,
+            (advice, f, table): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "rows",
+                |mut region| {
+                    let mut firsts = vec![];
+                    let mut lasts = vec![];
+                    for row in 0..8 {
+                        region.assign_fixed(|| "f", f, row, || Value::known(Fr::ONE))?;
+                        let entry = Fr::from(row as u64 + 1);
+                        region.assign_fixed(|| "table", table, row, || Value::known(entry))?;
+                        // The last column holds `row + 1` and the first `row + WIDTH`.
+                        let cells = advice
+                            .iter()
+                            .enumerate()
+                            .map(|(i, column)| {
+                                let value = Fr::from((row + WIDTH - i) as u64);
+                                region.assign_advice(|| "a", *column, row, || Value::known(value))
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        firsts.push(cells[0].cell());
+                        lasts.push(cells[WIDTH - 1].cell());
+                    }
+                    for (first, last) in firsts.iter().zip(&lasts[WIDTH - 1..]) {
+                        region.constrain_equal(*first, *last)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &WideCircuit);
+
+    // The witness layout is an option of the backend prover only.
+    let prove_with = |layout| {
+        let (_, config, cs) =
+            compile_circuit(K, &WideCircuit, pk.get_vk().compress_selectors).unwrap();
+        let mut witness_calc = WitnessCalculator::new(K, &WideCircuit, &config, &cs, &[]);
+        let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+        let mut prover = ProverV2Single::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _, _, _>::new(
+            &params,
+            &pk,
+            &[],
+            ChaCha20Rng::seed_from_u64(0xdeadbeef),
+            &mut transcript,
+        )
+        .unwrap();
+        prover.set_witness_layout(layout);
+        let mut challenges = HashMap::new();
+        for phase in 0..cs.phases().count() {
+            let witness = witness_calc.calc(phase as u8, &challenges).unwrap();
+            challenges = prover.commit_phase(phase as u8, witness).unwrap();
+        }
+        prover.create_proof().unwrap();
+        transcript.finalize()
+    };
+    let proof = prove_with(WitnessLayout::ColumnMajor);
+    let row_major_proof = prove_with(WitnessLayout::RowMajor);
+    assert_eq!(proof, row_major_proof);
+    assert!(
+        verify(&params, pk.get_vk(), &[&[]], &row_major_proof, |builder| {
+            builder
+        })
+        .is_ok()
+    );
+}
+
+#[test]
+fn proofs_of_several_circuits() {
+    const K: u32 = 6;
+
+    // The circuits share the work of the proving key, and have their own witnesses.
+    let circuits = [
+        BitsCircuit::new(&[true, false, true]),
+        BitsCircuit::new(&[false, false]),
+        BitsCircuit::new(&[true, true, true, false, true]),
+    ];
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuits[0]);
+    let instances: &[&[&[Fr]]] = &[&[&[Fr::ONE]], &[&[Fr::ZERO]], &[&[Fr::ONE]]];
+
+    let prove_on = |num_threads: usize| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .unwrap()
+            .install(|| prove(&params, &pk, &circuits, instances, |builder| builder).unwrap())
+    };
+
+    // The circuits are committed in parallel, but the proof doesn't depend on the order
+    // in which the commitments are computed.
+    let proof = prove_on(1);
+    assert_eq!(proof, prove_on(4));
+    assert!(verify(&params, pk.get_vk(), instances, &proof, |builder| builder).is_ok());
+}
+
+#[test]
+fn packed_proofs() {
+    use halo2_proofs::poly::ipa::commitment::{IPACommitmentScheme, ParamsIPA};
+    use halo2_proofs::poly::ipa::multiopen::{ProverIPA, VerifierIPA};
+    use halo2_proofs::poly::ipa::strategy::AccumulatorStrategy;
+    use halo2_proofs::transcript::{
+        read_proof_envelope_with_encoding, write_proof_envelope_with_encoding, ProofEncoding,
+    };
+    use halo2curves::pasta::{EqAffine, Fp};
+
+    const K: u32 = 4;
+
+    let circuit = BitsCircuit::<Fp>::new(&[true, true, false, true]);
+    let params = ParamsIPA::<EqAffine>::new(K);
+    let vk = keygen_vk(&params, &circuit.without_witnesses()).unwrap();
+    let pk = keygen_pk(&params, vk, &circuit.without_witnesses()).unwrap();
+    let instances: &[&[&[Fp]]] = &[&[&[Fp::ONE]]];
+
+    let prove_with = |encoding| {
+        let mut transcript =
+            Blake2bWrite::<_, _, Challenge255<_>>::init_with_encoding(vec![], encoding);
+        create_plonk_proof::<IPACommitmentScheme<_>, ProverIPA<_>, _, _, _, _>(
+            &params,
+            &pk,
+            &[circuit.clone()],
+            instances,
+            ChaCha20Rng::seed_from_u64(0xdeadbeef),
+            &mut transcript,
+        )
+        .unwrap();
+        transcript.finalize()
+    };
+    let verifies = |proof: &[u8], encoding| {
+        let mut transcript =
+            Blake2bRead::<_, _, Challenge255<_>>::init_with_encoding(proof, encoding);
+        verify_plonk_proof::<_, VerifierIPA<_>, _, _, _>(
+            params.verifier_params(),
+            pk.get_vk(),
+            AccumulatorStrategy::new(params.verifier_params()),
+            instances,
+            &mut transcript,
+        )
+        .map_or(false, |strategy| strategy.finalize())
+    };
+
+    // The packed proof is shorter, and has the same points and scalars.
+    let standard = prove_with(ProofEncoding::Standard);
+    let packed = prove_with(ProofEncoding::Packed);
+    assert!(packed.len() < standard.len());
+    assert!(verifies(&standard, ProofEncoding::Standard));
+    assert!(verifies(&packed, ProofEncoding::Packed));
+    assert!(!verifies(&packed, ProofEncoding::Standard));
+
+    // The envelope records the encoding the proof is verified with.
+    let mut envelope = vec![];
+    write_proof_envelope_with_encoding::<EqAffine, _>(
+        &mut envelope,
+        &packed,
+        ProofEncoding::Packed,
+    )
+    .unwrap();
+    let (proof, encoding) =
+        read_proof_envelope_with_encoding::<EqAffine, _>(&mut &envelope[..]).unwrap();
+    assert_eq!((&proof, encoding), (&packed, ProofEncoding::Packed));
+    assert!(verifies(&proof, encoding));
+}
+
+#[test]
+fn proofs_with_query_order() {
+    use halo2_proofs::plonk::{QueryGroup, QueryOrder};
+
+    const K: u32 = 5;
+
+    let circuits = [
+        BitsCircuit::new(&[false, true]),
+        BitsCircuit::new(&[true, true, false]),
+    ];
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuits[0]);
+    let instances: &[&[&[Fr]]] = &[&[&[Fr::ZERO]], &[&[Fr::ONE]]];
+
+    let prove_in = |order: QueryOrder| {
+        prove(&params, &pk, &circuits, instances, |builder| {
+            builder.with_query_order(order)
+        })
+        .unwrap()
+    };
+    let verify_in = |proof: &[u8], order: QueryOrder| {
+        verify(&params, pk.get_vk(), instances, proof, |builder| {
+            builder.with_query_order(order)
+        })
+    };
+
+    // The canonical order is the default one.
+    let proof = prove_in(QueryOrder::canonical());
+    assert_eq!(
+        proof,
+        prove(&params, &pk, &circuits, instances, |builder| builder).unwrap()
+    );
+    assert!(verify_in(&proof, QueryOrder::canonical()).is_ok());
+
+    let order = QueryOrder::new(vec![
+        QueryGroup::Vanishing,
+        QueryGroup::Fixed,
+        QueryGroup::Advice,
+        QueryGroup::Lookups,
+        QueryGroup::PermutationCommon,
+        QueryGroup::Instance,
+        QueryGroup::Permutation,
+        QueryGroup::Shuffles,
+        QueryGroup::ExtraQueries,
+    ])
+    .unwrap();
+    let reordered_proof = prove_in(order.clone());
+    assert!(verify_in(&reordered_proof, order.clone()).is_ok());
+    assert!(verify_in(&reordered_proof, QueryOrder::canonical()).is_err());
+    assert!(verify_in(&proof, order).is_err());
+}
+
+#[test]
+fn transcript_logs() {
+    use halo2_proofs::dev::diff_transcripts;
+    use halo2_proofs::dev::transcript_log::{LoggingTranscript, TranscriptMessage};
+    use halo2_proofs::transcript::TranscriptPhase;
+
+    const K: u32 = 4;
+
+    let circuit = BitsCircuit::new(&[true, false]);
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuit);
+    let instances: &[&[&[Fr]]] = &[&[&[Fr::ONE]]];
+
+    // The logging transcripts wrap the ones of the helpers, so the builders are used
+    // directly.
+    let mut transcript =
+        LoggingTranscript::new(Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]));
+    ProofBuilder::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _>::new(
+        &params,
+        &pk,
+        &[circuit],
+        instances,
+    )
+    .with_domain(b"application a")
+    .create(OsRng, &mut transcript)
+    .unwrap();
+    let (transcript, prover_log) = transcript.into_parts();
+    let proof = transcript.finalize();
+
+    let verify_logged = |label: &[u8]| {
+        let mut transcript = LoggingTranscript::new(
+            Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&proof[..]),
+        );
+        let result = VerifierBuilder::<KZGCommitmentScheme<_>, VerifierSHPLONK<_>>::new(
+            params.verifier_params(),
+            pk.get_vk(),
+            instances,
+        )
+        .with_domain(label)
+        .verify(
+            SingleStrategy::new(params.verifier_params()),
+            &mut transcript,
+        );
+        (result.is_ok(), transcript.into_parts().1)
+    };
+
+    // The prover and the verifier absorb the same messages and squeeze the same challenges.
+    let (verified, verifier_log) = verify_logged(b"application a");
+    assert!(verified);
+    assert_eq!(prover_log.first_mismatch(&verifier_log), None);
+    let purposes: Vec<_> = prover_log
+        .challenges()
+        .map(|(label, _)| label.unwrap().purpose)
+        .collect();
+    assert_eq!(&purposes[..5], &["theta", "beta", "gamma", "y", "x"]);
+
+    // A proof of another application diverges on the domain label.
+    let (verified, verifier_log) = verify_logged(b"application b");
+    assert!(!verified);
+    let diff = diff_transcripts(&prover_log, &verifier_log).unwrap();
+    assert_eq!(diff.index, 0);
+    let entry = diff.prover.unwrap();
+    assert_eq!(entry.label.unwrap().phase, TranscriptPhase::Domain);
+    assert!(matches!(entry.message, TranscriptMessage::CommonScalar(_)));
+    assert_ne!(diff.prover, diff.verifier);
+}
+
+#[test]
+fn vk_column_commitments() {
+    use halo2_proofs::plonk::Any;
+    use halo2_proofs::poly::commitment::{Blind, Params};
+
+    const K: u32 = 4;
+
+    #[derive(Clone)]
+    struct MyConfig {
+        fixed: [Column<Fixed>; 2],
+        advice: Column<Advice>,
+        instance: Column<Instance>,
+    }
+
+    /// Copies a fixed value to an advice cell.
+    #[derive(Clone)]
+    struct MyCircuit;
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = MyConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let fixed = [meta.fixed_column(), meta.fixed_column()];
+            let advice = meta.advice_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            meta.enable_equality(advice);
+            meta.enable_equality(fixed[1]);
+            MyConfig {
+                fixed,
+                advice,
+                instance,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "copy",
+                |mut region| {
+                    let fixed = region.assign_fixed(
+                        || "f",
+                        config.fixed[1],
+                        0,
+                        || Value::known(Fr::from(7)),
+                    )?;
+                    let advice = region.assign_advice(
+                        || "a",
+                        config.advice,
+                        0,
+                        || Value::known(Fr::from(7)),
+                    )?;
+                    region.constrain_equal(fixed.cell(), advice.cell())
+                },
+            )
+        }
+    }
+
+    let params = ParamsKZG::<Bn256>::new(K);
+    let vk = keygen_vk(&params, &MyCircuit).unwrap();
+    let config = MyCircuit::configure(&mut ConstraintSystem::default());
+
+    // The fixed columns are in the order of their indices.
+    let fixed: Vec<_> = vk.fixed_column_commitments().collect();
+    assert_eq!(fixed.len(), vk.fixed_commitments().len());
+    assert_eq!(fixed[0].0, config.fixed[0]);
+    assert_eq!(fixed[1].0, config.fixed[1]);
+    for ((_, commitment), expected) in fixed.iter().zip(vk.fixed_commitments()) {
+        assert_eq!(commitment, expected);
+    }
+    let values = [Fr::from(7)];
+    let expected = G1Affine::from(params.commit_lagrange_slice(&values, Blind::default()));
+    assert_eq!(fixed[1].1, expected);
+
+    // The permutation columns are in the order equality was enabled on them.
+    let permutation: Vec<_> = vk.permutation_column_commitments().collect();
+    let columns: Vec<Column<Any>> = permutation.iter().map(|(column, _)| *column).collect();
+    assert_eq!(
+        columns,
+        vec![
+            config.instance.into(),
+            config.advice.into(),
+            config.fixed[1].into()
+        ]
+    );
+    let commitments: Vec<_> = permutation
+        .iter()
+        .map(|(_, commitment)| *commitment)
+        .collect();
+    assert_eq!(&commitments, vk.permutation().commitments());
+}
+
+#[test]
+fn split_proofs() {
+    use halo2_proofs::plonk::{
+        verify_proof_section, verify_split_proof, ProofSection, ProofSections,
+    };
+
+    const K: u32 = 4;
+
+    let circuit = BitsCircuit::new(&[true, false]);
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuit);
+    let instances: &[&[&[Fr]]] = &[&[&[Fr::ONE]]];
+
+    // The circuit has neither shuffles nor extra queries, and KZG doesn't query the
+    // instance columns, so that these sections are empty and omitted.
+    for (sections, num_verified) in [
+        (ProofSections::per_phase(), 4),
+        (ProofSections::per_argument(), 6),
+    ] {
+        let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+        let section_proofs = ProofBuilder::<KZGCommitmentScheme<_>, ProverSHPLONK<_>, _>::new(
+            &params,
+            &pk,
+            &[circuit.clone()],
+            instances,
+        )
+        .create_with_sections(&sections, OsRng, &mut transcript)
+        .unwrap();
+        let proof = transcript.finalize();
+        assert_eq!(section_proofs.len(), sections.num_sections());
+
+        // The proof has no multiopen argument of its own.
+        assert!(verify(&params, pk.get_vk(), instances, &proof, |builder| builder).is_err());
+
+        let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&proof[..]);
+        let split = verify_split_proof::<KZGCommitmentScheme<_>, VerifierSHPLONK<_>, _, _>(
+            params.verifier_params(),
+            pk.get_vk(),
+            instances,
+            &sections,
+            &mut transcript,
+        )
+        .unwrap();
+        assert_eq!(split.len(), num_verified);
+
+        let verify_section = |section: &ProofSection<G1Affine>| {
+            verify_proof_section::<
+                KZGCommitmentScheme<_>,
+                VerifierSHPLONK<_>,
+                _,
+                Blake2bRead<_, _, Challenge255<_>>,
+                _,
+            >(
+                params.verifier_params(),
+                section,
+                &section_proofs[section.index],
+                SingleStrategy::new(params.verifier_params()),
+            )
+        };
+
+        // The sections are verified in parallel.
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = split
+                .iter()
+                .map(|section| scope.spawn(|| verify_section(section)))
+                .collect();
+            for handle in handles {
+                assert!(handle.join().unwrap().is_ok());
+            }
+        });
+
+        // Changing an evaluation, or the seed, invalidates a section.
+        let mut tampered = split[0].clone();
+        tampered.openings[0].eval += Fr::ONE;
+        assert!(verify_section(&tampered).is_err());
+        let mut tampered = split[0].clone();
+        tampered.seed += Fr::ONE;
+        assert!(verify_section(&tampered).is_err());
+    }
+}
+
+#[test]
+fn gates_on_subdomain() {
+    use halo2_proofs::plonk::Subdomain;
+
+    const K: u32 = 5;
+
+    /// Constrains the values of every fourth row to be bits, without a selector.
+    #[derive(Clone)]
+    struct MyCircuit(Vec<u64>);
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = Column<Advice>;
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let a = meta.advice_column();
+            meta.create_gate_on_subdomain("bit", Subdomain::new(4, 0), |meta| {
+                let a = meta.query_advice(a, Rotation::cur());
+                vec![a.clone() * (a - Expression::Constant(Fr::ONE))]
+            });
+            a
+        }
+
+        fn synthesize(
+            &self,
+            a: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "values",
+                |mut region| {
+                    for (row, value) in self.0.iter().enumerate() {
+                        region.assign_advice(|| "a", a, row, || Value::known(Fr::from(*value)))?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    // The rows which aren't multiples of 4 are unconstrained.
+    let values: Vec<u64> = (0..24)
+        .map(|row| if row % 4 == 0 { (row / 4) % 2 } else { 7 })
+        .collect();
+    let circuit = MyCircuit(values.clone());
+    let mut invalid = values;
+    invalid[8] = 2;
+    let invalid = MyCircuit(invalid);
+
+    let prover = MockProver::run(K, &circuit, vec![]).unwrap();
+    assert_eq!(prover.verify(), Ok(()));
+    let prover = MockProver::run(K, &invalid, vec![]).unwrap();
+    assert!(prover.verify().is_err());
+
+    let params = ParamsKZG::<Bn256>::new(K);
+    let pk = keygen(&params, &circuit);
+    // The gate is multiplied by the selector of the subdomain.
+    assert_eq!(pk.get_vk().cs().degree(), 3);
+    assert!(format!("{:?}", pk.get_vk().cs().pinned()).contains("OnSubdomain"));
+
+    // The rows of the subdomain after the usable rows, such as row 28, hold blinding
+    // factors, which the gate doesn't constrain.
+    let proves = |circuit: MyCircuit| {
+        let proof = prove(&params, &pk, &[circuit], &[&[]], |builder| builder).unwrap();
+        verify(&params, pk.get_vk(), &[&[]], &proof, |builder| builder)
+    };
+    assert!(proves(circuit).is_ok());
+    assert!(proves(invalid).is_err());
+}
+
+#[test]
+fn sparse_fixed_columns() {
+    use halo2_proofs::plonk::Selector;
+    use halo2_proofs::SerdeFormat;
+
+    const K: u32 = 8;
+
+    /// Checks `a = b + c` on the rows where the selector is enabled, with `c` taken from a
+    /// dense fixed column.
+    #[derive(Clone)]
+    struct MyCircuit(Vec<usize>);
+
+    impl Circuit<Fr> for MyCircuit {
+        type Config = (Selector, Column<Advice>, Column<Advice>, Column<Fixed>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let s = meta.selector();
+            let a = meta.advice_column();
+            let b = meta.advice_column();
+            let c = meta.fixed_column();
+            meta.create_gate("add", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                let b = meta.query_advice(b, Rotation::cur());
+                let c = meta.query_fixed(c, Rotation::cur());
+                vec![s * (a - b - c)]
+            });
+            (s, a, b, c)
+        }
+
+        fn synthesize(
+            &self,
+            (s, a, b, c): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "rows",
+                |mut region| {
+                    for row in 0..200 {
+                        let c_value = Fr::from(row as u64 + 1);
+                        region.assign_fixed(|| "c", c, row, || Value::known(c_value))?;
+                        region.assign_advice(|| "b", b, row, || Value::known(Fr::ONE))?;
+                        region.assign_advice(|| "a", a, row, || Value::known(c_value + Fr::ONE))?;
+                    }
+                    for row in self.0.iter() {
+                        s.enable(&mut region, *row)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    let params = ParamsKZG::<Bn256>::new(K);
+    let circuit = MyCircuit(vec![3, 150]);
+    let pk = keygen(&params, &circuit);
+
+    // The selector column is stored by its two non-zero values, the dense column in full,
+    // and both are read back as the same polynomials.
+    let bytes = pk.to_bytes(SerdeFormat::RawBytes);
+    let read = ProvingKey::<G1Affine>::from_bytes::<MyCircuit>(
+        &bytes,
+        SerdeFormat::RawBytes,
+        #[cfg(feature = "circuit-params")]
+        (),
+    )
+    .unwrap();
+    assert_eq!(read.to_bytes(SerdeFormat::RawBytes), bytes);
+
+    // With the selector enabled on every row, its values and polynomials are all stored.
+    let dense = keygen(&params, &MyCircuit((0..200).collect())).to_bytes(SerdeFormat::RawBytes);
+    assert!(dense.len() - bytes.len() > 32 * 2 * (1 << K));
+
+    let proof = prove(&params, &read, &[circuit], &[&[]], |builder| builder).unwrap();
+    assert!(verify(&params, pk.get_vk(), &[&[]], &proof, |builder| builder).is_ok());
+}
+
+#[test]
+fn extra_unusable_rows() {
+    use halo2_proofs::plonk::Selector;
+
+    const K: u32 = 5;
+
+    /// Assigns the row index to `rows` rows and copies the first one to the instance
+    /// column, leaving `EXTRA` more unusable rows.
+    #[derive(Clone)]
+    struct MyCircuit<const EXTRA: usize> {
+        rows: usize,
+    }
+
+    impl<const EXTRA: usize> Circuit<Fr> for MyCircuit<EXTRA> {
+        type Config = (Selector, Column<Advice>, Column<Fixed>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            meta.set_extra_unusable_rows(EXTRA);
+            let s = meta.selector();
+            let a = meta.advice_column();
+            let f = meta.fixed_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(a);
+            meta.enable_equality(instance);
+            meta.create_gate("equal", |meta| {
+                let s = meta.query_selector(s);
+                let a = meta.query_advice(a, Rotation::cur());
+                let f = meta.query_fixed(f, Rotation::cur());
+                vec![s * (a - f)]
+            });
+            (s, a, f, instance)
+        }
+
+        fn synthesize(
+            &self,
+            (s, a, f, instance): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let first = layouter.assign_region(
+                || "rows",
+                |mut region| {
+                    let mut cells = vec![];
+                    for row in 0..self.rows {
+                        let value = Fr::from(row as u64);
+                        s.enable(&mut region, row)?;
+                        region.assign_fixed(|| "f", f, row, || Value::known(value))?;
+                        cells.push(region.assign_advice(|| "a", a, row, || Value::known(value))?);
+                    }
+                    Ok(cells[0].cell())
+                },
+            )?;
+            layouter.constrain_instance(first, instance, 0)
+        }
+    }
+
+    let usable_rows = {
+        let mut meta = ConstraintSystem::<Fr>::default();
+        MyCircuit::<3>::configure(&mut meta);
+        meta.usable_rows(K)
+    };
+    let circuit = MyCircuit::<3> {
+        rows: usable_rows.end,
+    };
+    let prover = MockProver::run(K, &circuit, vec![vec![Fr::ZERO]]).unwrap();
+    assert_eq!(prover.usable_rows(), &usable_rows);
+    assert_eq!(prover.verify(), Ok(()));
+
+    // The rows left unusable can't be assigned, unlike without extra unusable rows.
+    let params = ParamsKZG::<Bn256>::new(K);
+    assert_matches!(
+        keygen_vk(
+            &params,
+            &MyCircuit::<3> {
+                rows: usable_rows.end + 1
+            }
+        ),
+        Err(Error::NotEnoughRowsAvailable { .. })
+    );
+    assert!(keygen_vk(
+        &params,
+        &MyCircuit::<0> {
+            rows: usable_rows.end + 1
+        }
+    )
+    .is_ok());
+
+    // The extra rows are part of the verifying key.
+    let pk = keygen(&params, &circuit);
+    assert!(format!("{:?}", pk.get_vk().cs().pinned()).contains("extra_unusable_rows: 3"));
+
+    let instances: &[&[&[Fr]]] = &[&[&[Fr::ZERO]]];
+    let proof = prove(&params, &pk, &[circuit], instances, |builder| builder).unwrap();
+    assert!(verify(&params, pk.get_vk(), instances, &proof, |builder| builder).is_ok());
+}
+
+#[test]
+fn instance_cache() {
+    use halo2_proofs::plonk::{BatchVerifier, InstanceCache};
+    use halo2_proofs::poly::ipa::commitment::{IPACommitmentScheme, ParamsIPA};
+    use halo2_proofs::poly::ipa::multiopen::{ProverIPA, VerifierIPA};
+    use halo2_proofs::poly::ipa::strategy::AccumulatorStrategy;
+    use halo2curves::pasta::{EqAffine, Fp};
+
+    const K: u32 = 4;
+
+    let circuit = BitsCircuit::<Fp>::new(&[true, false]);
+    let params = ParamsIPA::<EqAffine>::new(K);
+    let vk = keygen_vk(&params, &circuit.without_witnesses()).unwrap();
+    let pk = keygen_pk(&params, vk, &circuit.without_witnesses()).unwrap();
+    let value = Fp::ONE;
+    let proofs: Vec<_> = (0..3)
+        .map(|_| {
+            let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+            create_plonk_proof::<IPACommitmentScheme<_>, ProverIPA<_>, _, _, _, _>(
+                &params,
+                &pk,
+                &[circuit.clone()],
+                &[&[&[value]]],
+                OsRng,
+                &mut transcript,
+            )
+            .unwrap();
+            transcript.finalize()
+        })
+        .collect();
+
+    // The instance column is committed to once for all the proofs.
+    let cache = InstanceCache::new();
+    let verifies = |proof: &[u8], instance: Fp| {
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+        VerifierBuilder::<IPACommitmentScheme<_>, VerifierIPA<_>>::new(
+            params.verifier_params(),
+            pk.get_vk(),
+            &[&[&[instance]]],
+        )
+        .with_instance_cache(&cache)
+        .verify(
+            AccumulatorStrategy::new(params.verifier_params()),
+            &mut transcript,
+        )
+        .map_or(false, |strategy| strategy.finalize())
+    };
+    for proof in proofs.iter() {
+        assert!(verifies(proof, value));
+    }
+    assert_eq!(cache.len(), 1);
+    assert!(!verifies(&proofs[0], value + Fp::ONE));
+    assert_eq!(cache.len(), 2);
+
+    let batch = |cache: Option<&InstanceCache<EqAffine>>, instance: Fp| {
+        let mut batch = BatchVerifier::new();
+        for proof in proofs.iter() {
+            batch.add_proof(vec![vec![vec![instance]]], proof.clone());
+        }
+        match cache {
+            Some(cache) => batch.finalize_with_instance_cache(&params, pk.get_vk(), cache),
+            None => batch.finalize(&params, pk.get_vk()),
+        }
+    };
+    assert!(batch(None, value));
+    assert!(batch(Some(&cache), value));
+    assert!(!batch(Some(&cache), value + Fp::ONE));
+    assert_eq!(cache.len(), 2);
+}
@@ -0,0 +1,24 @@
+//! The circuit generated by `halo2_proofs::scaffold` for the spec below is checked in, so
+//! that it is built and its tests run with this test crate.
+
+use halo2_proofs::scaffold::{generate, CircuitSpec};
+
+#[path = "scaffold/sum_check.rs"]
+mod sum_check;
+
+#[test]
+fn generated_circuit_is_up_to_date() {
+    let spec = CircuitSpec {
+        k: 6,
+        advice_columns: 4,
+        instance_columns: 2,
+        lookup: true,
+        ..CircuitSpec::new("SumCheck", "my_circuits")
+    };
+    let scaffold = generate(&spec).unwrap();
+    assert_eq!(
+        scaffold.files[0].contents,
+        include_str!("scaffold/sum_check.rs"),
+        "regenerate tests/scaffold/sum_check.rs after changing the templates"
+    );
+}
@@ -0,0 +1,236 @@
+//! The SumCheck circuit, generated by `halo2_proofs::scaffold`.
+//!
+//! As generated, the circuit constrains its last advice column to be the sum of the other
+//! ones. Replace the constraints of `SumCheckChip::configure` and the assignments of
+//! `SumCheckChip::assign_row` by those of the circuit, and update the tests.
+
+use std::marker::PhantomData;
+
+use halo2_proofs::circuit::{AssignedCell, Chip, Layouter, SimpleFloorPlanner, Value};
+use halo2_proofs::halo2curves::ff::PrimeField;
+use halo2_proofs::plonk::Instance;
+use halo2_proofs::plonk::TableColumn;
+use halo2_proofs::plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Selector};
+use halo2_proofs::poly::Rotation;
+
+/// The circuit has `2^K` rows.
+pub const K: u32 = 6;
+
+/// The number of advice columns.
+pub const ADVICE_COLUMNS: usize = 4;
+
+/// The number of fixed columns, which hold the constants of the circuit.
+pub const FIXED_COLUMNS: usize = 1;
+
+/// The number of instance columns. The first one holds the sum at its first row.
+pub const INSTANCE_COLUMNS: usize = 2;
+
+/// The number of rows of the lookup table.
+pub const TABLE_ROWS: usize = 1 << (K - 1);
+
+/// The columns of the SumCheck circuit.
+#[derive(Clone, Debug)]
+pub struct SumCheckConfig {
+    advice: [Column<Advice>; ADVICE_COLUMNS],
+    instance: [Column<Instance>; INSTANCE_COLUMNS],
+    table: TableColumn,
+    selector: Selector,
+}
+
+/// The chip assigning the rows of the SumCheck circuit.
+#[derive(Clone, Debug)]
+pub struct SumCheckChip<F: PrimeField> {
+    config: SumCheckConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> Chip<F> for SumCheckChip<F> {
+    type Config = SumCheckConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: PrimeField> SumCheckChip<F> {
+    /// Creates the chip from its configuration.
+    pub fn construct(config: SumCheckConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates the columns and the constraints of the chip.
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> SumCheckConfig {
+        let advice = [(); ADVICE_COLUMNS].map(|_| meta.advice_column());
+        for column in advice {
+            meta.enable_equality(column);
+        }
+        for _ in 0..FIXED_COLUMNS {
+            let fixed = meta.fixed_column();
+            meta.enable_constant(fixed);
+        }
+        let instance = [(); INSTANCE_COLUMNS].map(|_| meta.instance_column());
+        for column in instance {
+            meta.enable_equality(column);
+        }
+        let selector = meta.complex_selector();
+
+        // TODO: replace by the gates of the circuit.
+        meta.create_gate("sum", |meta| {
+            let selector = meta.query_selector(selector);
+            let inputs = advice[..ADVICE_COLUMNS - 1]
+                .iter()
+                .map(|column| meta.query_advice(*column, Rotation::cur()))
+                .reduce(|sum, input| sum + input)
+                .unwrap();
+            let sum = meta.query_advice(advice[ADVICE_COLUMNS - 1], Rotation::cur());
+            vec![selector * (inputs - sum)]
+        });
+
+        // TODO: replace by the lookups of the circuit.
+        let table = meta.lookup_table_column();
+        meta.lookup("range", |meta| {
+            let selector = meta.query_selector(selector);
+            let input = meta.query_advice(advice[0], Rotation::cur());
+            vec![(selector * input, table)]
+        });
+
+        SumCheckConfig {
+            advice,
+            instance,
+            table,
+            selector,
+        }
+    }
+
+    /// Assigns the lookup table, of the values from `0` to `TABLE_ROWS - 1`.
+    pub fn load_table(&self, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "range",
+            |mut table| {
+                for row in 0..TABLE_ROWS {
+                    let value = Value::known(F::from(row as u64));
+                    table.assign_cell(|| "value", self.config.table, row, || value)?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Assigns a row of `inputs` and of their sum, and returns the cell of the sum.
+    pub fn assign_row(
+        &self,
+        mut layouter: impl Layouter<F>,
+        inputs: [Value<F>; ADVICE_COLUMNS - 1],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let config = &self.config;
+        layouter.assign_region(
+            || "row",
+            |mut region| {
+                config.selector.enable(&mut region, 0)?;
+                let mut sum = Value::known(F::ZERO);
+                for (column, input) in config.advice.iter().zip(inputs) {
+                    region.assign_advice(|| "input", *column, 0, || input)?;
+                    sum = sum + input;
+                }
+                region.assign_advice(|| "sum", config.advice[ADVICE_COLUMNS - 1], 0, || sum)
+            },
+        )
+    }
+
+    /// Constrains `cell` to be the value of the first instance column at `row`.
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.instance[0], row)
+    }
+}
+
+/// The SumCheck circuit.
+#[derive(Clone, Debug)]
+pub struct SumCheckCircuit<F: PrimeField> {
+    /// The inputs, which are summed by the circuit.
+    pub inputs: [Value<F>; ADVICE_COLUMNS - 1],
+}
+
+impl<F: PrimeField> SumCheckCircuit<F> {
+    /// Creates the circuit summing `inputs`.
+    pub fn new(inputs: [F; ADVICE_COLUMNS - 1]) -> Self {
+        Self {
+            inputs: inputs.map(Value::known),
+        }
+    }
+}
+
+/// Returns the values of the instance columns of the circuit summing `inputs`.
+pub fn public_inputs<F: PrimeField>(inputs: &[F; ADVICE_COLUMNS - 1]) -> Vec<Vec<F>> {
+    let sum = inputs.iter().fold(F::ZERO, |sum, input| sum + input);
+    let mut instances = vec![vec![]; INSTANCE_COLUMNS];
+    instances[0].push(sum);
+    instances
+}
+
+impl<F: PrimeField> Circuit<F> for SumCheckCircuit<F> {
+    type Config = SumCheckConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            inputs: [Value::unknown(); ADVICE_COLUMNS - 1],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        SumCheckChip::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = SumCheckChip::construct(config);
+        chip.load_table(layouter.namespace(|| "table"))?;
+        let sum = chip.assign_row(layouter.namespace(|| "row"), self.inputs)?;
+        chip.expose_public(layouter.namespace(|| "sum"), &sum, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+    use halo2_proofs::halo2curves::pasta::Fp;
+
+    fn inputs() -> [Fp; ADVICE_COLUMNS - 1] {
+        std::array::from_fn(|i| Fp::from(i as u64 + 1))
+    }
+
+    #[test]
+    fn satisfied() {
+        let circuit = SumCheckCircuit::new(inputs());
+        let prover = MockProver::run(K, &circuit, public_inputs(&inputs())).unwrap();
+        prover.assert_satisfied();
+    }
+
+    #[test]
+    fn wrong_public_input() {
+        let circuit = SumCheckCircuit::new(inputs());
+        let mut instances = public_inputs(&inputs());
+        instances[0][0] += Fp::from(1u64);
+        let prover = MockProver::run(K, &circuit, instances).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}